@@ -78,6 +78,22 @@ pub fn ax_rename(old: &str, new: &str) -> AxResult {
     axfs::api::rename(old, new)
 }
 
+pub fn ax_symlink_attr(path: &str) -> AxResult<AxFileAttr> {
+    axfs::api::symlink_metadata(path).map(|md| *md.raw_metadata())
+}
+
+pub fn ax_read_link(path: &str) -> AxResult<String> {
+    axfs::api::read_link(path)
+}
+
+pub fn ax_symlink(original: &str, link: &str) -> AxResult {
+    axfs::api::symlink(original, link)
+}
+
+pub fn ax_hard_link(original: &str, link: &str) -> AxResult {
+    axfs::api::hard_link(original, link)
+}
+
 pub fn ax_current_dir() -> AxResult<String> {
     axfs::api::current_dir()
 }
@@ -20,7 +20,7 @@ pub fn ax_exit(_exit_code: i32) -> ! {
     #[cfg(feature = "multitask")]
     axtask::exit(_exit_code);
     #[cfg(not(feature = "multitask"))]
-    axhal::misc::terminate();
+    axhal::misc::shutdown();
 }
 
 cfg_task! {
@@ -39,6 +39,9 @@ cfg_task! {
         }
     }
 
+    /// Information and statistics of a task.
+    pub type AxTaskInfo = axtask::TaskInfo;
+
     /// A handle to a wait queue.
     ///
     /// A wait queue is used to store sleeping tasks waiting for a certain event
@@ -108,4 +111,42 @@ cfg_task! {
             }
         }
     }
+
+    pub fn ax_task_list() -> alloc::vec::Vec<AxTaskInfo> {
+        axtask::tasks().map(|task| task.info()).collect()
+    }
+
+    pub fn ax_kill_task(id: u64, exit_code: i32) -> crate::AxResult {
+        let task = axtask::find_task(id)
+            .ok_or_else(|| axerrno::ax_err_type!(NotFound, "ax_kill_task: no such task"))?;
+        if axtask::request_exit(&task, exit_code) {
+            Ok(())
+        } else {
+            axerrno::ax_err!(PermissionDenied, "ax_kill_task: the task cannot be killed")
+        }
+    }
+
+    /// A handle to a timer, which can be used to cancel it.
+    #[cfg(feature = "irq")]
+    pub type AxTimerHandle = axtask::TimerHandle;
+
+    #[cfg(feature = "irq")]
+    pub fn ax_set_timer<F>(deadline: crate::time::AxTimeValue, callback: F) -> AxTimerHandle
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        axtask::set_timer(deadline, callback)
+    }
+
+    #[cfg(feature = "irq")]
+    pub fn ax_set_periodic_timer<F>(
+        deadline: crate::time::AxTimeValue,
+        interval: Duration,
+        callback: F,
+    ) -> AxTimerHandle
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        axtask::set_periodic_timer(deadline, interval, callback)
+    }
 }
@@ -47,6 +47,32 @@ pub mod time {
         /// Returns the time elapsed since epoch, also known as realtime.
         pub fn ax_wall_time() -> AxTimeValue;
     }
+
+    define_api_type! {
+        @cfg (all(feature = "multitask", feature = "irq"));
+        pub type AxTimerHandle;
+    }
+
+    define_api! {
+        @cfg (all(feature = "multitask", feature = "irq"));
+
+        /// Runs the callback once at the given deadline (in wall time).
+        ///
+        /// The callback runs in a dedicated timer task, not in the interrupt
+        /// context. The timer can be cancelled by the returned handle.
+        pub fn ax_set_timer(
+            deadline: AxTimeValue,
+            callback: impl Fn() + Send + Sync + 'static,
+        ) -> AxTimerHandle;
+        /// Runs the callback at the given deadline (in wall time), and then
+        /// every `interval`, until the timer is cancelled by the returned
+        /// handle.
+        pub fn ax_set_periodic_timer(
+            deadline: AxTimeValue,
+            interval: core::time::Duration,
+            callback: impl Fn() + Send + Sync + 'static,
+        ) -> AxTimerHandle;
+    }
 }
 
 /// Memory management.
@@ -121,6 +147,7 @@ pub mod task {
         @cfg "multitask";
         pub type AxTaskHandle;
         pub type AxWaitQueueHandle;
+        pub type AxTaskInfo;
     }
 
     define_api! {
@@ -169,6 +196,13 @@ pub mod task {
         /// The maximum number of tasks to wake up is specified by `count`. If
         /// `count` is `u32::MAX`, it will wake up all tasks in the wait queue.
         pub fn ax_wait_queue_wake(wq: &AxWaitQueueHandle, count: u32);
+
+        /// Returns the information and statistics of all live tasks, in the
+        /// order of their IDs.
+        pub fn ax_task_list() -> alloc::vec::Vec<AxTaskInfo>;
+        /// Asks the task with the given ID to exit with the exit code, at its
+        /// next safe point, e.g., when it returns to user space.
+        pub fn ax_kill_task(id: u64, exit_code: i32) -> crate::AxResult;
     }
 }
 
@@ -249,6 +283,15 @@ pub mod fs {
         ///
         /// It will delete the original file if `old` already exists.
         pub fn ax_rename(old: &str, new: &str) -> AxResult;
+        /// Returns attributes of the file at the path, without following
+        /// symbolic links.
+        pub fn ax_symlink_attr(path: &str) -> AxResult<AxFileAttr>;
+        /// Returns the target of the symbolic link at the path.
+        pub fn ax_read_link(path: &str) -> AxResult<alloc::string::String>;
+        /// Creates a symbolic link at `link`, which points to `original`.
+        pub fn ax_symlink(original: &str, link: &str) -> AxResult;
+        /// Creates a hard link at `link` to the file at `original`.
+        pub fn ax_hard_link(original: &str, link: &str) -> AxResult;
 
         /// Returns the current working directory.
         pub fn ax_current_dir() -> AxResult<alloc::string::String>;
@@ -388,6 +431,8 @@ pub mod modules {
 
     #[cfg(feature = "alloc")]
     pub use axalloc;
+    #[cfg(feature = "async")]
+    pub use axasync;
     #[cfg(feature = "display")]
     pub use axdisplay;
     #[cfg(feature = "dma")]
@@ -17,6 +17,17 @@ macro_rules! define_api_type {
             $vis struct $name;
         )+
     };
+    ( @cfg ($cond:meta); $( $(#[$attr:meta])* $vis:vis type $name:ident; )+ ) => {
+        $(
+            #[cfg($cond)]
+            $(#[$attr])*
+            $vis use $crate::imp::$name;
+
+            #[cfg(all(feature = "dummy-if-not-enabled", not($cond)))]
+            $(#[$attr])*
+            $vis struct $name;
+        )+
+    };
 }
 
 macro_rules! define_api {
@@ -55,6 +66,25 @@ macro_rules! define_api {
             }
         )+
     };
+    (
+        @cfg ($cond:meta);
+        $( $(#[$attr:meta])* $vis:vis fn $name:ident( $($arg:ident : $type:ty),* $(,)? ) $( -> $ret:ty )? ; )+
+    ) => {
+        $(
+            #[cfg($cond)]
+            $(#[$attr])*
+            $vis fn $name( $($arg : $type),* ) $( -> $ret )? {
+                $crate::imp::$name( $($arg),* )
+            }
+
+            #[allow(unused_variables)]
+            #[cfg(all(feature = "dummy-if-not-enabled", not($cond)))]
+            $(#[$attr])*
+            $vis fn $name( $($arg : $type),* ) $( -> $ret )? {
+                unimplemented!(stringify!($name))
+            }
+        )+
+    };
     (
         @cfg $feature:literal;
         $( $(#[$attr:meta])* $vis:vis unsafe fn $name:ident( $($arg:ident : $type:ty),* $(,)? ) $( -> $ret:ty )? ; )+
@@ -48,11 +48,24 @@ typedef struct {{
             "pthread_attr_t",
             "pthread_mutex_t",
             "pthread_mutexattr_t",
+            "pthread_cond_t",
+            "pthread_condattr_t",
+            "pthread_rwlock_t",
+            "pthread_rwlockattr_t",
+            "pthread_barrier_t",
+            "pthread_barrierattr_t",
+            "timespec",
             "epoll_event",
             "iovec",
             "clockid_t",
             "rlimit",
             "aibuf",
+            "sigaction",
+            "siginfo_t",
+            "sigevent",
+            "itimerval",
+            "itimerspec",
+            "timer_t",
         ];
         let allow_vars = [
             "CLOCK_.*",
@@ -68,6 +81,14 @@ typedef struct {{
             "RLIMIT_.*",
             "EAI_.*",
             "MAXADDRS",
+            "SIG.*",
+            "SA_.*",
+            "SI_.*",
+            "ITIMER_.*",
+            "TIMER_ABSTIME",
+            "MS_.*",
+            "MNT_.*",
+            "PTHREAD_BARRIER_SERIAL_THREAD",
         ];
 
         #[derive(Debug)]
@@ -21,18 +21,56 @@ pub trait FileLike: Send + Sync {
     fn set_nonblocking(&self, nonblocking: bool) -> LinuxResult;
 }
 
+/// A file descriptor table.
+pub type FdTable = RwLock<FlattenObjects<Arc<dyn FileLike>, AX_FILE_LIMIT>>;
+
+/// Creates a new file descriptor table with stdin, stdout and stderr opened.
+pub fn new_fd_table() -> FdTable {
+    let mut fd_table = FlattenObjects::new();
+    fd_table.add_at(0, Arc::new(stdin()) as _).unwrap(); // stdin
+    fd_table.add_at(1, Arc::new(stdout()) as _).unwrap(); // stdout
+    fd_table.add_at(2, Arc::new(stdout()) as _).unwrap(); // stderr
+    RwLock::new(fd_table)
+}
+
+/// Creates a copy of the file descriptor table, as `fork` does. The opened
+/// files are shared by the two tables.
+pub fn clone_fd_table(fd_table: &FdTable) -> FdTable {
+    let old = fd_table.read();
+    let mut new = FlattenObjects::new();
+    for fd in 0..AX_FILE_LIMIT {
+        if let Some(f) = old.get(fd) {
+            new.add_at(fd, f.clone());
+        }
+    }
+    RwLock::new(new)
+}
+
 lazy_static::lazy_static! {
-    static ref FD_TABLE: RwLock<FlattenObjects<Arc<dyn FileLike>, AX_FILE_LIMIT>> = {
-        let mut fd_table = FlattenObjects::new();
-        fd_table.add_at(0, Arc::new(stdin()) as _).unwrap(); // stdin
-        fd_table.add_at(1, Arc::new(stdout()) as _).unwrap(); // stdout
-        fd_table.add_at(2, Arc::new(stdout()) as _).unwrap(); // stderr
-        RwLock::new(fd_table)
-    };
+    static ref FD_TABLE: Arc<FdTable> = Arc::new(new_fd_table());
+}
+
+static CURRENT_FD_TABLE_FN: spin::Once<fn() -> Option<Arc<FdTable>>> = spin::Once::new();
+
+/// Sets the function that returns the file descriptor table of the current
+/// task, e.g., the table of the process it belongs to.
+///
+/// The function may return `None` for tasks without their own table, which
+/// then use the global table. If it is not set, all tasks share the global
+/// table.
+pub fn set_current_fd_table_fn(f: fn() -> Option<Arc<FdTable>>) {
+    CURRENT_FD_TABLE_FN.call_once(|| f);
+}
+
+fn fd_table() -> Arc<FdTable> {
+    CURRENT_FD_TABLE_FN
+        .get()
+        .and_then(|f| f())
+        .unwrap_or_else(|| FD_TABLE.clone())
 }
 
 pub fn get_file_like(fd: c_int) -> LinuxResult<Arc<dyn FileLike>> {
-    FD_TABLE
+    fd_table()
         .read()
         .get(fd as usize)
         .cloned()
@@ -40,11 +78,11 @@ pub fn get_file_like(fd: c_int) -> LinuxResult<Arc<dyn FileLike>> {
 }
 
 pub fn add_file_like(f: Arc<dyn FileLike>) -> LinuxResult<c_int> {
-    Ok(FD_TABLE.write().add(f).ok_or(LinuxError::EMFILE)? as c_int)
+    Ok(fd_table().write().add(f).ok_or(LinuxError::EMFILE)? as c_int)
 }
 
 pub fn close_file_like(fd: c_int) -> LinuxResult {
-    let f = FD_TABLE
+    let f = fd_table()
         .write()
         .remove(fd as usize)
         .ok_or(LinuxError::EBADF)?;
@@ -92,7 +130,7 @@ pub fn sys_dup2(old_fd: c_int, new_fd: c_int) -> c_int {
         }
 
         let f = get_file_like(old_fd)?;
-        FD_TABLE
+        fd_table()
             .write()
             .add_at(new_fd as usize, f)
             .ok_or(LinuxError::EMFILE)?;
@@ -1,8 +1,9 @@
 use alloc::sync::Arc;
-use core::ffi::{c_char, c_int};
+use core::ffi::{c_char, c_int, c_ulong, c_void};
 
 use axerrno::{LinuxError, LinuxResult};
-use axfs::fops::OpenOptions;
+use axfs::api::{MountFlags, UmountFlags};
+use axfs::fops::{DirEntry, FileAttr, OpenOptions};
 use axio::{PollState, SeekFrom};
 use axsync::Mutex;
 
@@ -42,21 +43,7 @@ impl FileLike for File {
     }
 
     fn stat(&self) -> LinuxResult<ctypes::stat> {
-        let metadata = self.inner.lock().get_attr()?;
-        let ty = metadata.file_type() as u8;
-        let perm = metadata.perm().bits() as u32;
-        let st_mode = ((ty as u32) << 12) | perm;
-        Ok(ctypes::stat {
-            st_ino: 1,
-            st_nlink: 1,
-            st_mode,
-            st_uid: 1000,
-            st_gid: 1000,
-            st_size: metadata.size() as _,
-            st_blocks: metadata.blocks() as _,
-            st_blksize: 512,
-            ..Default::default()
-        })
+        Ok(attr_to_stat(&self.inner.lock().get_attr()?))
     }
 
     fn into_any(self: Arc<Self>) -> Arc<dyn core::any::Any + Send + Sync> {
@@ -75,6 +62,131 @@ impl FileLike for File {
     }
 }
 
+/// The size of `struct linux_dirent64` without the name.
+const DIRENT64_HEADER_SIZE: usize = 19;
+
+pub struct Directory {
+    inner: Mutex<DirReader>,
+}
+
+struct DirReader {
+    dir: axfs::fops::Directory,
+    /// The entry read from the directory that did not fit in the last buffer.
+    pending: Option<DirEntry>,
+    /// The number of entries returned so far.
+    offset: u64,
+}
+
+impl Directory {
+    fn new(dir: axfs::fops::Directory) -> Self {
+        Self {
+            inner: Mutex::new(DirReader {
+                dir,
+                pending: None,
+                offset: 0,
+            }),
+        }
+    }
+
+    fn add_to_fd_table(self) -> LinuxResult<c_int> {
+        super::fd_ops::add_file_like(Arc::new(self))
+    }
+
+    fn from_fd(fd: c_int) -> LinuxResult<Arc<Self>> {
+        let f = super::fd_ops::get_file_like(fd)?;
+        f.into_any()
+            .downcast::<Self>()
+            .map_err(|_| LinuxError::ENOTDIR)
+    }
+
+    /// Fills `buf` with the next entries as `struct linux_dirent64`.
+    ///
+    /// Returns the number of bytes written, or 0 at the end of the directory.
+    fn read_dirents(&self, buf: &mut [u8]) -> LinuxResult<usize> {
+        let mut reader = self.inner.lock();
+        let mut len = 0;
+        loop {
+            let entry = match reader.pending.take() {
+                Some(entry) => entry,
+                None => {
+                    let mut entries = [DirEntry::default()];
+                    if reader.dir.read_dir(&mut entries)? == 0 {
+                        break;
+                    }
+                    let [entry] = entries;
+                    entry
+                }
+            };
+            let name = entry.name_as_bytes();
+            let reclen = (DIRENT64_HEADER_SIZE + name.len() + 1).next_multiple_of(8);
+            if len + reclen > buf.len() {
+                reader.pending = Some(entry);
+                if len == 0 {
+                    return Err(LinuxError::EINVAL);
+                }
+                break;
+            }
+            reader.offset += 1;
+            let rec = &mut buf[len..len + reclen];
+            rec[0..8].copy_from_slice(&1u64.to_ne_bytes()); // d_ino
+            rec[8..16].copy_from_slice(&reader.offset.to_ne_bytes()); // d_off
+            rec[16..18].copy_from_slice(&(reclen as u16).to_ne_bytes()); // d_reclen
+            rec[18] = entry.entry_type() as u8; // d_type, same as the `DT_*` values
+            rec[DIRENT64_HEADER_SIZE..DIRENT64_HEADER_SIZE + name.len()].copy_from_slice(name);
+            rec[DIRENT64_HEADER_SIZE + name.len()..].fill(0);
+            len += reclen;
+        }
+        Ok(len)
+    }
+}
+
+impl FileLike for Directory {
+    fn read(&self, _buf: &mut [u8]) -> LinuxResult<usize> {
+        Err(LinuxError::EISDIR)
+    }
+
+    fn write(&self, _buf: &[u8]) -> LinuxResult<usize> {
+        Err(LinuxError::EBADF)
+    }
+
+    fn stat(&self) -> LinuxResult<ctypes::stat> {
+        Ok(attr_to_stat(&self.inner.lock().dir.get_attr()?))
+    }
+
+    fn into_any(self: Arc<Self>) -> Arc<dyn core::any::Any + Send + Sync> {
+        self
+    }
+
+    fn poll(&self) -> LinuxResult<PollState> {
+        Ok(PollState {
+            readable: true,
+            writable: false,
+        })
+    }
+
+    fn set_nonblocking(&self, _nonblocking: bool) -> LinuxResult {
+        Ok(())
+    }
+}
+
+/// Convert file attributes to the `stat` structure.
+fn attr_to_stat(attr: &FileAttr) -> ctypes::stat {
+    let ty = attr.file_type() as u8;
+    let perm = attr.perm().bits() as u32;
+    let st_mode = ((ty as u32) << 12) | perm;
+    ctypes::stat {
+        st_ino: 1,
+        st_nlink: 1,
+        st_mode,
+        st_uid: 1000,
+        st_gid: 1000,
+        st_size: attr.size() as _,
+        st_blocks: attr.blocks() as _,
+        st_blksize: 512,
+        ..Default::default()
+    }
+}
+
 /// Convert open flags to [`OpenOptions`].
 fn flags_to_options(flags: c_int, _mode: ctypes::mode_t) -> OpenOptions {
     let flags = flags as u32;
@@ -104,18 +216,53 @@ fn flags_to_options(flags: c_int, _mode: ctypes::mode_t) -> OpenOptions {
 
 /// Open a file by `filename` and insert it into the file descriptor table.
 ///
+/// Directories opened read-only, or with `O_DIRECTORY`, can be read by
+/// [`sys_getdents64`].
+///
 /// Return its index in the file table (`fd`). Return `EMFILE` if it already
 /// has the maximum number of files open.
 pub fn sys_open(filename: *const c_char, flags: c_int, mode: ctypes::mode_t) -> c_int {
     let filename = char_ptr_to_str(filename);
     debug!("sys_open <= {:?} {:#o} {:#o}", filename, flags, mode);
     syscall_body!(sys_open, {
+        let filename = filename?;
         let options = flags_to_options(flags, mode);
-        let file = axfs::fops::File::open(filename?, &options)?;
+        let open_dir = if flags as u32 & ctypes::O_DIRECTORY != 0 {
+            true
+        } else {
+            flags as u32 & 0b11 == ctypes::O_RDONLY
+                && axfs::api::metadata(filename).is_ok_and(|meta| meta.is_dir())
+        };
+        if open_dir {
+            let dir = axfs::fops::Directory::open_dir(filename, &options)?;
+            return Directory::new(dir).add_to_fd_table();
+        }
+        let file = axfs::fops::File::open(filename, &options)?;
         File::new(file).add_to_fd_table()
     })
 }
 
+/// Read the entries of the directory indicated by `fd` into `buf`, as
+/// `struct linux_dirent64`.
+///
+/// Return the number of bytes read, or 0 at the end of the directory.
+pub fn sys_getdents64(fd: c_int, buf: *mut c_void, len: usize) -> ctypes::ssize_t {
+    debug!("sys_getdents64 <= {} {:#x} {}", fd, buf as usize, len);
+    syscall_body!(sys_getdents64, {
+        if buf.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        let dst = unsafe { core::slice::from_raw_parts_mut(buf as *mut u8, len) };
+        Ok(Directory::from_fd(fd)?.read_dirents(dst)? as ctypes::ssize_t)
+    })
+}
+
+/// Returns a new handle of the file indicated by `fd`, e.g., for mapping the
+/// file into memory.
+pub fn get_fs_file(fd: c_int) -> LinuxResult<axfs::fops::File> {
+    Ok(File::from_fd(fd)?.inner.lock().try_clone()?)
+}
+
 /// Set the position of the file indicated by `fd`.
 ///
 /// Return its position after seek.
@@ -177,7 +324,53 @@ pub unsafe fn sys_lstat(path: *const c_char, buf: *mut ctypes::stat) -> ctypes::
         if buf.is_null() {
             return Err(LinuxError::EFAULT);
         }
-        unsafe { *buf = Default::default() }; // TODO
+        let metadata = axfs::api::symlink_metadata(path?)?;
+        unsafe { *buf = attr_to_stat(metadata.raw_metadata()) };
+        Ok(0)
+    })
+}
+
+/// Read the target of the symbolic link `path` into `buf`.
+///
+/// The target is truncated to `bufsize` bytes and not null-terminated.
+/// Return the number of bytes placed in `buf`.
+pub fn sys_readlink(path: *const c_char, buf: *mut c_char, bufsize: usize) -> ctypes::ssize_t {
+    let path = char_ptr_to_str(path);
+    debug!("sys_readlink <= {:?} {:#x} {}", path, buf as usize, bufsize);
+    syscall_body!(sys_readlink, {
+        if buf.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        let target = axfs::api::read_link(path?)?;
+        let len = target.len().min(bufsize);
+        let dst = unsafe { core::slice::from_raw_parts_mut(buf as *mut u8, len) };
+        dst.copy_from_slice(&target.as_bytes()[..len]);
+        Ok(len as ctypes::ssize_t)
+    })
+}
+
+/// Create a symbolic link `link` which contains the string `target`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+pub fn sys_symlink(target: *const c_char, link: *const c_char) -> c_int {
+    syscall_body!(sys_symlink, {
+        let target = char_ptr_to_str(target)?;
+        let link = char_ptr_to_str(link)?;
+        debug!("sys_symlink <= target: {:?}, link: {:?}", target, link);
+        axfs::api::symlink(target, link)?;
+        Ok(0)
+    })
+}
+
+/// Create a new hard link `new` to the existing file `old`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+pub fn sys_link(old: *const c_char, new: *const c_char) -> c_int {
+    syscall_body!(sys_link, {
+        let old_path = char_ptr_to_str(old)?;
+        let new_path = char_ptr_to_str(new)?;
+        debug!("sys_link <= old: {:?}, new: {:?}", old_path, new_path);
+        axfs::api::hard_link(old_path, new_path)?;
         Ok(0)
     })
 }
@@ -215,3 +408,65 @@ pub fn sys_rename(old: *const c_char, new: *const c_char) -> c_int {
         Ok(0)
     })
 }
+
+/// Mount the filesystem `fstype` on `source` at the directory `target`.
+///
+/// Only `MS_RDONLY` and `MS_BIND` are supported, other flags and `data` are
+/// ignored. `fstype` is ignored for bind mounts.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+pub fn sys_mount(
+    source: *const c_char,
+    target: *const c_char,
+    fstype: *const c_char,
+    flags: c_ulong,
+    _data: *const c_void,
+) -> c_int {
+    syscall_body!(sys_mount, {
+        let source = char_ptr_to_str(source)?;
+        let target = char_ptr_to_str(target)?;
+        let flags = flags as u32;
+        debug!(
+            "sys_mount <= source: {:?}, target: {:?}, flags: {:#x}",
+            source, target, flags
+        );
+        let mut mount_flags = MountFlags::empty();
+        if flags & ctypes::MS_RDONLY != 0 {
+            mount_flags |= MountFlags::RDONLY;
+        }
+        let fstype = if flags & ctypes::MS_BIND != 0 {
+            mount_flags |= MountFlags::BIND;
+            ""
+        } else {
+            char_ptr_to_str(fstype)?
+        };
+        axfs::api::mount(source, target, fstype, mount_flags)?;
+        Ok(0)
+    })
+}
+
+/// Unmount the filesystem mounted at `target`.
+///
+/// `MNT_DETACH` detaches it at once even if it is busy. `MNT_FORCE` has no
+/// effect.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+pub fn sys_umount2(target: *const c_char, flags: c_int) -> c_int {
+    syscall_body!(sys_umount2, {
+        let target = char_ptr_to_str(target)?;
+        debug!("sys_umount2 <= target: {:?}, flags: {:#x}", target, flags);
+        let flags = flags as u32;
+        if flags & !(ctypes::MNT_FORCE | ctypes::MNT_DETACH) != 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        let mut umount_flags = UmountFlags::empty();
+        if flags & ctypes::MNT_FORCE != 0 {
+            umount_flags |= UmountFlags::FORCE;
+        }
+        if flags & ctypes::MNT_DETACH != 0 {
+            umount_flags |= UmountFlags::DETACH;
+        }
+        axfs::api::umount(target, umount_flags)?;
+        Ok(0)
+    })
+}
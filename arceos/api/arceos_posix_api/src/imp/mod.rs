@@ -2,6 +2,7 @@ mod stdio;
 
 pub mod io;
 pub mod resources;
+pub mod signal;
 pub mod sys;
 pub mod task;
 pub mod time;
@@ -18,3 +19,5 @@ pub mod net;
 pub mod pipe;
 #[cfg(feature = "multitask")]
 pub mod pthread;
+#[cfg(all(feature = "multitask", feature = "irq"))]
+pub mod timer;
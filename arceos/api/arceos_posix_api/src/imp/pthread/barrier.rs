@@ -0,0 +1,70 @@
+use crate::{ctypes, utils::check_null_mut_ptr};
+
+use axerrno::LinuxError;
+use axsync::Barrier;
+
+use core::ffi::{c_int, c_uint};
+use core::mem::size_of;
+
+use super::LazyBox;
+
+static_assertions::const_assert!(
+    size_of::<PthreadBarrier>() <= size_of::<ctypes::pthread_barrier_t>()
+);
+
+/// The barrier, allocated by `sys_pthread_barrier_init` as it has no static
+/// initializer.
+#[repr(C)]
+pub struct PthreadBarrier(LazyBox<Barrier>);
+
+/// Initialize a barrier for `count` threads.
+pub fn sys_pthread_barrier_init(
+    barrier: *mut ctypes::pthread_barrier_t,
+    _attr: *const ctypes::pthread_barrierattr_t,
+    count: c_uint,
+) -> c_int {
+    debug!(
+        "sys_pthread_barrier_init <= {:#x}, {}",
+        barrier as usize, count
+    );
+    syscall_body!(sys_pthread_barrier_init, {
+        check_null_mut_ptr(barrier)?;
+        if count == 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        let new = PthreadBarrier(LazyBox::new());
+        new.0.get_or_init(|| Barrier::new(count as usize));
+        unsafe {
+            barrier.cast::<PthreadBarrier>().write(new);
+        }
+        Ok(0)
+    })
+}
+
+/// Destroy a barrier.
+pub fn sys_pthread_barrier_destroy(barrier: *mut ctypes::pthread_barrier_t) -> c_int {
+    debug!("sys_pthread_barrier_destroy <= {:#x}", barrier as usize);
+    syscall_body!(sys_pthread_barrier_destroy, {
+        check_null_mut_ptr(barrier)?;
+        unsafe {
+            (*barrier.cast::<PthreadBarrier>()).0.destroy();
+        }
+        Ok(0)
+    })
+}
+
+/// Block until the number of threads given to `sys_pthread_barrier_init` have
+/// called it.
+///
+/// Returns 1 in one of the threads, and 0 in the others.
+pub fn sys_pthread_barrier_wait(barrier: *mut ctypes::pthread_barrier_t) -> c_int {
+    debug!("sys_pthread_barrier_wait <= {:#x}", barrier as usize);
+    syscall_body!(sys_pthread_barrier_wait, {
+        check_null_mut_ptr(barrier)?;
+        let barrier = unsafe { &*barrier.cast::<PthreadBarrier>() };
+        // Calling it on an uninitialized barrier is undefined. Treat it as a
+        // barrier for one thread instead of crashing.
+        let result = barrier.0.get_or_init(|| Barrier::new(1)).wait();
+        Ok(result.is_leader() as c_int)
+    })
+}
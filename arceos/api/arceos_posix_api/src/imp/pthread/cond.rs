@@ -0,0 +1,165 @@
+use crate::{ctypes, utils::check_null_mut_ptr};
+
+use axerrno::LinuxResult;
+use axsync::Condvar;
+
+use core::ffi::c_int;
+use core::mem::{size_of, ManuallyDrop};
+
+use super::{mutex::PthreadMutex, LazyBox};
+
+static_assertions::const_assert!(size_of::<PthreadCond>() <= size_of::<ctypes::pthread_cond_t>());
+
+/// The condition variable, allocated on its first use so that a zeroed
+/// `PTHREAD_COND_INITIALIZER` works.
+#[repr(C)]
+pub struct PthreadCond {
+    inner: LazyBox<Condvar>,
+    /// The clock of the absolute timeouts, `CLOCK_REALTIME` by default.
+    clock: ctypes::clockid_t,
+}
+
+impl PthreadCond {
+    const fn new(clock: ctypes::clockid_t) -> Self {
+        Self {
+            inner: LazyBox::new(),
+            clock,
+        }
+    }
+
+    fn condvar(&self) -> &Condvar {
+        self.inner.get_or_init(Condvar::new)
+    }
+
+    fn wait(&self, mutex: &PthreadMutex) -> LinuxResult {
+        // The mutex has been locked by `pthread_mutex_lock`, which forgot its
+        // guard. Take it back for the wait, and forget it again afterwards.
+        let guard = unsafe { mutex.0.make_guard_unchecked() };
+        let _guard = ManuallyDrop::new(self.condvar().wait(guard));
+        Ok(())
+    }
+
+    #[cfg(feature = "irq")]
+    fn timed_wait(&self, mutex: &PthreadMutex, abstime: &ctypes::timespec) -> LinuxResult {
+        use axerrno::LinuxError;
+        use core::time::Duration;
+
+        if abstime.tv_sec < 0 || !(0..1_000_000_000).contains(&abstime.tv_nsec) {
+            return Err(LinuxError::EINVAL);
+        }
+        let now = match self.clock as u32 {
+            ctypes::CLOCK_MONOTONIC => axhal::time::monotonic_time(),
+            _ => axhal::time::wall_time(),
+        };
+        let dur = Duration::from(*abstime).saturating_sub(now);
+        let guard = unsafe { mutex.0.make_guard_unchecked() };
+        let (guard, timeout) = self.condvar().wait_timeout(guard, dur);
+        let _guard = ManuallyDrop::new(guard);
+        if timeout {
+            Err(LinuxError::ETIMEDOUT)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Initialize a condition variable.
+pub fn sys_pthread_cond_init(
+    cond: *mut ctypes::pthread_cond_t,
+    attr: *const ctypes::pthread_condattr_t,
+) -> c_int {
+    debug!("sys_pthread_cond_init <= {:#x}", cond as usize);
+    syscall_body!(sys_pthread_cond_init, {
+        check_null_mut_ptr(cond)?;
+        let clock = if attr.is_null() {
+            ctypes::CLOCK_REALTIME as _
+        } else {
+            (unsafe { (*attr).__attr } & 0x7fff_ffff) as _
+        };
+        unsafe {
+            cond.cast::<PthreadCond>().write(PthreadCond::new(clock));
+        }
+        Ok(0)
+    })
+}
+
+/// Destroy a condition variable.
+pub fn sys_pthread_cond_destroy(cond: *mut ctypes::pthread_cond_t) -> c_int {
+    debug!("sys_pthread_cond_destroy <= {:#x}", cond as usize);
+    syscall_body!(sys_pthread_cond_destroy, {
+        check_null_mut_ptr(cond)?;
+        unsafe {
+            (*cond.cast::<PthreadCond>()).inner.destroy();
+        }
+        Ok(0)
+    })
+}
+
+/// Unblock one of the threads blocked on the condition variable.
+pub fn sys_pthread_cond_signal(cond: *mut ctypes::pthread_cond_t) -> c_int {
+    debug!("sys_pthread_cond_signal <= {:#x}", cond as usize);
+    syscall_body!(sys_pthread_cond_signal, {
+        check_null_mut_ptr(cond)?;
+        unsafe {
+            (*cond.cast::<PthreadCond>()).condvar().notify_one();
+        }
+        Ok(0)
+    })
+}
+
+/// Unblock all threads blocked on the condition variable.
+pub fn sys_pthread_cond_broadcast(cond: *mut ctypes::pthread_cond_t) -> c_int {
+    debug!("sys_pthread_cond_broadcast <= {:#x}", cond as usize);
+    syscall_body!(sys_pthread_cond_broadcast, {
+        check_null_mut_ptr(cond)?;
+        unsafe {
+            (*cond.cast::<PthreadCond>()).condvar().notify_all();
+        }
+        Ok(0)
+    })
+}
+
+/// Release the locked mutex and block on the condition variable, then lock
+/// the mutex again.
+pub fn sys_pthread_cond_wait(
+    cond: *mut ctypes::pthread_cond_t,
+    mutex: *mut ctypes::pthread_mutex_t,
+) -> c_int {
+    debug!(
+        "sys_pthread_cond_wait <= {:#x}, {:#x}",
+        cond as usize, mutex as usize
+    );
+    syscall_body!(sys_pthread_cond_wait, {
+        check_null_mut_ptr(cond)?;
+        check_null_mut_ptr(mutex)?;
+        unsafe {
+            (*cond.cast::<PthreadCond>()).wait(&*mutex.cast::<PthreadMutex>())?;
+        }
+        Ok(0)
+    })
+}
+
+/// Wait on the condition variable like `sys_pthread_cond_wait`, until the
+/// absolute time `abstime` of the clock of the condition variable.
+///
+/// Returns `-ETIMEDOUT` if the time has passed, with the mutex locked again.
+#[cfg(feature = "irq")]
+pub fn sys_pthread_cond_timedwait(
+    cond: *mut ctypes::pthread_cond_t,
+    mutex: *mut ctypes::pthread_mutex_t,
+    abstime: *const ctypes::timespec,
+) -> c_int {
+    debug!(
+        "sys_pthread_cond_timedwait <= {:#x}, {:#x}",
+        cond as usize, mutex as usize
+    );
+    syscall_body!(sys_pthread_cond_timedwait, {
+        check_null_mut_ptr(cond)?;
+        check_null_mut_ptr(mutex)?;
+        crate::utils::check_null_ptr(abstime)?;
+        unsafe {
+            (*cond.cast::<PthreadCond>()).timed_wait(&*mutex.cast::<PthreadMutex>(), &*abstime)?;
+        }
+        Ok(0)
+    })
+}
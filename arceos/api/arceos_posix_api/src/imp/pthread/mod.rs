@@ -1,6 +1,7 @@
 use alloc::{boxed::Box, collections::BTreeMap, sync::Arc};
 use core::cell::UnsafeCell;
 use core::ffi::{c_int, c_void};
+use core::sync::atomic::{AtomicPtr, Ordering};
 
 use axerrno::{LinuxError, LinuxResult};
 use axtask::AxTaskRef;
@@ -8,7 +9,10 @@ use spin::RwLock;
 
 use crate::ctypes;
 
+pub mod barrier;
+pub mod cond;
 pub mod mutex;
+pub mod rwlock;
 
 lazy_static::lazy_static! {
     static ref TID_TO_PTHREAD: RwLock<BTreeMap<u64, ForceSendSync<ctypes::pthread_t>>> = {
@@ -152,3 +156,48 @@ struct ForceSendSync<T>(T);
 
 unsafe impl<T> Send for ForceSendSync<T> {}
 unsafe impl<T> Sync for ForceSendSync<T> {}
+
+/// A lazily allocated object inside a C `pthread_*_t`, so that the zeroed
+/// static initializers such as `PTHREAD_COND_INITIALIZER` are valid.
+#[repr(transparent)]
+struct LazyBox<T>(AtomicPtr<T>);
+
+impl<T> LazyBox<T> {
+    const fn new() -> Self {
+        Self(AtomicPtr::new(core::ptr::null_mut()))
+    }
+
+    /// Returns the object, allocating it with `init` on the first use.
+    fn get_or_init(&self, init: impl FnOnce() -> T) -> &T {
+        let mut ptr = self.0.load(Ordering::Acquire);
+        if ptr.is_null() {
+            let new = Box::into_raw(Box::new(init()));
+            match self.0.compare_exchange(
+                core::ptr::null_mut(),
+                new,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => ptr = new,
+                Err(other) => {
+                    // Another thread has installed it first.
+                    drop(unsafe { Box::from_raw(new) });
+                    ptr = other;
+                }
+            }
+        }
+        unsafe { &*ptr }
+    }
+
+    /// Frees the object, if it has been allocated.
+    ///
+    /// # Safety
+    ///
+    /// No reference returned by [`LazyBox::get_or_init`] may be alive.
+    unsafe fn destroy(&self) {
+        let ptr = self.0.swap(core::ptr::null_mut(), Ordering::AcqRel);
+        if !ptr.is_null() {
+            drop(unsafe { Box::from_raw(ptr) });
+        }
+    }
+}
@@ -12,7 +12,7 @@ static_assertions::const_assert_eq!(
 );
 
 #[repr(C)]
-pub struct PthreadMutex(Mutex<()>);
+pub struct PthreadMutex(pub(super) Mutex<()>);
 
 impl PthreadMutex {
     const fn new() -> Self {
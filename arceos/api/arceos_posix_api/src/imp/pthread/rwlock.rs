@@ -0,0 +1,151 @@
+use crate::{ctypes, utils::check_null_mut_ptr};
+
+use axerrno::{LinuxError, LinuxResult};
+use axsync::RwLock;
+
+use core::ffi::c_int;
+use core::mem::{forget, size_of};
+
+use super::LazyBox;
+
+static_assertions::const_assert!(
+    size_of::<PthreadRwlock>() <= size_of::<ctypes::pthread_rwlock_t>()
+);
+
+/// The read-write lock, allocated on its first use so that a zeroed
+/// `PTHREAD_RWLOCK_INITIALIZER` works.
+#[repr(C)]
+pub struct PthreadRwlock(LazyBox<RwLock<()>>);
+
+impl PthreadRwlock {
+    const fn new() -> Self {
+        Self(LazyBox::new())
+    }
+
+    fn rwlock(&self) -> &RwLock<()> {
+        self.0.get_or_init(|| RwLock::new(()))
+    }
+
+    fn rdlock(&self) -> LinuxResult {
+        forget(self.rwlock().read());
+        Ok(())
+    }
+
+    fn tryrdlock(&self) -> LinuxResult {
+        forget(self.rwlock().try_read().ok_or(LinuxError::EBUSY)?);
+        Ok(())
+    }
+
+    fn wrlock(&self) -> LinuxResult {
+        forget(self.rwlock().write());
+        Ok(())
+    }
+
+    fn trywrlock(&self) -> LinuxResult {
+        forget(self.rwlock().try_write().ok_or(LinuxError::EBUSY)?);
+        Ok(())
+    }
+
+    fn unlock(&self) -> LinuxResult {
+        let rwlock = self.rwlock();
+        unsafe {
+            if rwlock.is_locked_exclusive() {
+                rwlock.force_write_unlock();
+            } else if rwlock.reader_count() > 0 {
+                rwlock.force_read_unlock();
+            } else {
+                return Err(LinuxError::EPERM);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Initialize a read-write lock.
+pub fn sys_pthread_rwlock_init(
+    rwlock: *mut ctypes::pthread_rwlock_t,
+    _attr: *const ctypes::pthread_rwlockattr_t,
+) -> c_int {
+    debug!("sys_pthread_rwlock_init <= {:#x}", rwlock as usize);
+    syscall_body!(sys_pthread_rwlock_init, {
+        check_null_mut_ptr(rwlock)?;
+        unsafe {
+            rwlock.cast::<PthreadRwlock>().write(PthreadRwlock::new());
+        }
+        Ok(0)
+    })
+}
+
+/// Destroy a read-write lock.
+pub fn sys_pthread_rwlock_destroy(rwlock: *mut ctypes::pthread_rwlock_t) -> c_int {
+    debug!("sys_pthread_rwlock_destroy <= {:#x}", rwlock as usize);
+    syscall_body!(sys_pthread_rwlock_destroy, {
+        check_null_mut_ptr(rwlock)?;
+        unsafe {
+            (*rwlock.cast::<PthreadRwlock>()).0.destroy();
+        }
+        Ok(0)
+    })
+}
+
+/// Lock the given read-write lock for reading.
+pub fn sys_pthread_rwlock_rdlock(rwlock: *mut ctypes::pthread_rwlock_t) -> c_int {
+    debug!("sys_pthread_rwlock_rdlock <= {:#x}", rwlock as usize);
+    syscall_body!(sys_pthread_rwlock_rdlock, {
+        check_null_mut_ptr(rwlock)?;
+        unsafe {
+            (*rwlock.cast::<PthreadRwlock>()).rdlock()?;
+        }
+        Ok(0)
+    })
+}
+
+/// Try to lock the given read-write lock for reading, returning `-EBUSY` if
+/// it is held by a writer.
+pub fn sys_pthread_rwlock_tryrdlock(rwlock: *mut ctypes::pthread_rwlock_t) -> c_int {
+    debug!("sys_pthread_rwlock_tryrdlock <= {:#x}", rwlock as usize);
+    syscall_body!(sys_pthread_rwlock_tryrdlock, {
+        check_null_mut_ptr(rwlock)?;
+        unsafe {
+            (*rwlock.cast::<PthreadRwlock>()).tryrdlock()?;
+        }
+        Ok(0)
+    })
+}
+
+/// Lock the given read-write lock for writing.
+pub fn sys_pthread_rwlock_wrlock(rwlock: *mut ctypes::pthread_rwlock_t) -> c_int {
+    debug!("sys_pthread_rwlock_wrlock <= {:#x}", rwlock as usize);
+    syscall_body!(sys_pthread_rwlock_wrlock, {
+        check_null_mut_ptr(rwlock)?;
+        unsafe {
+            (*rwlock.cast::<PthreadRwlock>()).wrlock()?;
+        }
+        Ok(0)
+    })
+}
+
+/// Try to lock the given read-write lock for writing, returning `-EBUSY` if
+/// it is held.
+pub fn sys_pthread_rwlock_trywrlock(rwlock: *mut ctypes::pthread_rwlock_t) -> c_int {
+    debug!("sys_pthread_rwlock_trywrlock <= {:#x}", rwlock as usize);
+    syscall_body!(sys_pthread_rwlock_trywrlock, {
+        check_null_mut_ptr(rwlock)?;
+        unsafe {
+            (*rwlock.cast::<PthreadRwlock>()).trywrlock()?;
+        }
+        Ok(0)
+    })
+}
+
+/// Unlock the given read-write lock, held either for reading or for writing.
+pub fn sys_pthread_rwlock_unlock(rwlock: *mut ctypes::pthread_rwlock_t) -> c_int {
+    debug!("sys_pthread_rwlock_unlock <= {:#x}", rwlock as usize);
+    syscall_body!(sys_pthread_rwlock_unlock, {
+        check_null_mut_ptr(rwlock)?;
+        unsafe {
+            (*rwlock.cast::<PthreadRwlock>()).unlock()?;
+        }
+        Ok(0)
+    })
+}
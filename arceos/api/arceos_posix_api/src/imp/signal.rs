@@ -0,0 +1,113 @@
+use core::ffi::c_int;
+
+use axerrno::LinuxError;
+use spin::Mutex;
+
+use crate::ctypes;
+
+const NSIG: usize = 65;
+
+const SIG_DFL: usize = 0;
+#[cfg(all(feature = "multitask", feature = "irq"))]
+const SIG_IGN: usize = 1;
+
+/// The action of a signal, with the handler stored as an address.
+#[derive(Clone, Copy)]
+struct SigAction {
+    handler: usize,
+    flags: c_int,
+}
+
+impl SigAction {
+    const DEFAULT: Self = Self {
+        handler: SIG_DFL,
+        flags: 0,
+    };
+}
+
+static SIG_ACTIONS: Mutex<[SigAction; NSIG]> = Mutex::new([SigAction::DEFAULT; NSIG]);
+
+/// Runs the action of the signal on the current task.
+///
+/// There is no way to interrupt other tasks in ArceOS, so the handlers of the
+/// signals raised by timers run in the timer task.
+#[cfg(all(feature = "multitask", feature = "irq"))]
+pub(crate) fn deliver_signal(signo: c_int, code: c_int, value: usize) {
+    let Some(action) = SIG_ACTIONS.lock().get_mut(signo as usize).map(|action| {
+        let old = *action;
+        if old.flags as u32 & ctypes::SA_RESETHAND != 0 {
+            *action = SigAction::DEFAULT;
+        }
+        old
+    }) else {
+        return;
+    };
+    use core::ffi::c_void;
+
+    match action.handler {
+        SIG_DFL => match signo as u32 {
+            ctypes::SIGCHLD | ctypes::SIGCONT | ctypes::SIGURG | ctypes::SIGWINCH => {}
+            _ => {
+                error!("Terminated by signal {}", signo);
+                axhal::misc::shutdown();
+            }
+        },
+        SIG_IGN => {}
+        handler if action.flags as u32 & ctypes::SA_SIGINFO != 0 => {
+            let mut info = ctypes::siginfo_t {
+                si_signo: signo,
+                si_code: code,
+                ..Default::default()
+            };
+            unsafe {
+                info.__si_fields.__si_common.__second.si_value.sival_ptr = value as *mut c_void;
+                let handler: unsafe extern "C" fn(c_int, *mut ctypes::siginfo_t, *mut c_void) =
+                    core::mem::transmute(handler);
+                handler(signo, &mut info, core::ptr::null_mut());
+            }
+        }
+        handler => unsafe {
+            let handler: unsafe extern "C" fn(c_int) = core::mem::transmute(handler);
+            handler(signo);
+        },
+    }
+}
+
+/// Examine and change the action of a signal
+///
+/// Only the handler and the `SA_SIGINFO` and `SA_RESETHAND` flags are used.
+pub unsafe fn sys_sigaction(
+    signum: c_int,
+    act: *const ctypes::sigaction,
+    oldact: *mut ctypes::sigaction,
+) -> c_int {
+    debug!("sys_sigaction <= {}", signum);
+    syscall_body!(sys_sigaction, {
+        if signum <= 0 || signum as usize >= NSIG {
+            return Err(LinuxError::EINVAL);
+        }
+        if !act.is_null() && matches!(signum as u32, ctypes::SIGKILL | ctypes::SIGSTOP) {
+            return Err(LinuxError::EINVAL);
+        }
+        let mut actions = SIG_ACTIONS.lock();
+        let action = &mut actions[signum as usize];
+        if !oldact.is_null() {
+            let mut old = ctypes::sigaction {
+                sa_flags: action.flags,
+                ..Default::default()
+            };
+            unsafe {
+                old.__sa_handler.sa_handler = core::mem::transmute(action.handler);
+                *oldact = old;
+            }
+        }
+        if !act.is_null() {
+            let act = unsafe { &*act };
+            *action = SigAction {
+                handler: unsafe { act.__sa_handler.sa_handler }.map_or(SIG_DFL, |h| h as usize),
+                flags: act.sa_flags,
+            };
+        }
+        Ok(0)
+    })
+}
@@ -57,7 +57,8 @@ pub unsafe fn sys_clock_gettime(clk: ctypes::clockid_t, ts: *mut ctypes::timespe
 
 /// Sleep some nanoseconds
 ///
-/// TODO: should be woken by signals, and set errno
+/// Returns `EINTR` with the remaining time in `rem` if the sleep is
+/// interrupted, e.g., by a signal.
 pub unsafe fn sys_nanosleep(req: *const ctypes::timespec, rem: *mut ctypes::timespec) -> c_int {
     syscall_body!(sys_nanosleep, {
         unsafe {
@@ -74,7 +75,7 @@ pub unsafe fn sys_nanosleep(req: *const ctypes::timespec, rem: *mut ctypes::time
         let now = axhal::time::monotonic_time();
 
         #[cfg(feature = "multitask")]
-        axtask::sleep(dur);
+        let _ = axtask::sleep_interruptible(dur);
         #[cfg(not(feature = "multitask"))]
         axhal::time::busy_wait(dur);
 
@@ -0,0 +1,335 @@
+use alloc::collections::BTreeMap;
+use core::ffi::{c_int, c_uint, c_void};
+use core::sync::atomic::{AtomicUsize, Ordering};
+use core::time::Duration;
+
+use axerrno::{LinuxError, LinuxResult};
+use axhal::time::{monotonic_time, wall_time};
+use axtask::TimerHandle;
+use spin::Mutex;
+
+use super::signal::deliver_signal;
+use crate::ctypes;
+
+const NSIG: c_int = 65;
+
+/// What to do when a timer expires.
+#[derive(Clone, Copy)]
+enum Notify {
+    None,
+    Signal {
+        signo: c_int,
+        value: usize,
+    },
+    /// The function is called in the timer task, instead of a new thread.
+    Thread {
+        function: unsafe extern "C" fn(ctypes::sigval),
+        value: usize,
+    },
+}
+
+impl Notify {
+    fn fire(self) {
+        match self {
+            Self::None => {}
+            Self::Signal { signo, value } => deliver_signal(signo, ctypes::SI_TIMER, value),
+            Self::Thread { function, value } => unsafe {
+                function(ctypes::sigval {
+                    sival_ptr: value as *mut c_void,
+                })
+            },
+        }
+    }
+}
+
+struct PosixTimer {
+    clock: ctypes::clockid_t,
+    notify: Notify,
+    timer: Option<TimerHandle>,
+}
+
+static TIMERS: Mutex<BTreeMap<usize, PosixTimer>> = Mutex::new(BTreeMap::new());
+static NEXT_TIMER_ID: AtomicUsize = AtomicUsize::new(1);
+
+const REAL_CLOCK: ctypes::clockid_t = ctypes::CLOCK_REALTIME as _;
+
+/// The `ITIMER_REAL` timer of `setitimer` and `alarm`.
+static REAL_TIMER: Mutex<Option<TimerHandle>> = Mutex::new(None);
+
+fn check_timespec(ts: &ctypes::timespec) -> LinuxResult<Duration> {
+    if ts.tv_sec < 0 || !(0..1_000_000_000).contains(&ts.tv_nsec) {
+        return Err(LinuxError::EINVAL);
+    }
+    Ok(Duration::from(*ts))
+}
+
+fn check_timeval(tv: &ctypes::timeval) -> LinuxResult<Duration> {
+    if tv.tv_sec < 0 || !(0..1_000_000).contains(&tv.tv_usec) {
+        return Err(LinuxError::EINVAL);
+    }
+    Ok(Duration::from(*tv))
+}
+
+/// Sets a timer that fires first at `value`, and then every `interval` if it
+/// is not zero. The timer is disarmed if `value` is zero.
+fn arm(
+    notify: Notify,
+    clock: ctypes::clockid_t,
+    absolute: bool,
+    value: Duration,
+    interval: Duration,
+) -> Option<TimerHandle> {
+    if value.is_zero() {
+        return None;
+    }
+    // The timers in axtask run on the wall time.
+    let deadline = match (absolute, clock as u32) {
+        (false, _) => wall_time() + value,
+        (true, ctypes::CLOCK_MONOTONIC) => wall_time() + value.saturating_sub(monotonic_time()),
+        (true, _) => value,
+    };
+    let callback = move || notify.fire();
+    Some(if interval.is_zero() {
+        axtask::set_timer(deadline, callback)
+    } else {
+        axtask::set_periodic_timer(deadline, interval, callback)
+    })
+}
+
+/// Returns the time until the next expiration, and the interval.
+fn remaining(timer: Option<&TimerHandle>) -> (Duration, Duration) {
+    let Some(deadline) = timer.and_then(|t| t.deadline()) else {
+        return (Duration::ZERO, Duration::ZERO);
+    };
+    // An active timer never returns zero, which means disarmed.
+    let value = deadline
+        .saturating_sub(wall_time())
+        .max(Duration::from_nanos(1));
+    (value, timer.unwrap().interval().unwrap_or_default())
+}
+
+/// Create a per-process timer
+///
+/// If `sevp` is NULL, `SIGALRM` is sent with the timer ID on expiration.
+/// `SIGEV_THREAD` functions are called in the timer task.
+pub unsafe fn sys_timer_create(
+    clockid: ctypes::clockid_t,
+    sevp: *const ctypes::sigevent,
+    timerid: *mut ctypes::timer_t,
+) -> c_int {
+    debug!("sys_timer_create <= {}", clockid);
+    syscall_body!(sys_timer_create, {
+        if timerid.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        if !matches!(
+            clockid as u32,
+            ctypes::CLOCK_REALTIME | ctypes::CLOCK_MONOTONIC
+        ) {
+            warn!("sys_timer_create: unsupported clock {}", clockid);
+            return Err(LinuxError::EINVAL);
+        }
+        let id = NEXT_TIMER_ID.fetch_add(1, Ordering::Relaxed);
+        let notify = if sevp.is_null() {
+            Notify::Signal {
+                signo: ctypes::SIGALRM as _,
+                value: id,
+            }
+        } else {
+            let sev = unsafe { &*sevp };
+            let value = unsafe { sev.sigev_value.sival_ptr } as usize;
+            match sev.sigev_notify as u32 {
+                ctypes::SIGEV_NONE => Notify::None,
+                ctypes::SIGEV_SIGNAL if (1..NSIG).contains(&sev.sigev_signo) => Notify::Signal {
+                    signo: sev.sigev_signo,
+                    value,
+                },
+                ctypes::SIGEV_THREAD => Notify::Thread {
+                    function: unsafe { sev.__sev_fields.__sev_thread.sigev_notify_function }
+                        .ok_or(LinuxError::EINVAL)?,
+                    value,
+                },
+                _ => return Err(LinuxError::EINVAL),
+            }
+        };
+        let timer = PosixTimer {
+            clock: clockid,
+            notify,
+            timer: None,
+        };
+        TIMERS.lock().insert(id, timer);
+        unsafe { *timerid = id as ctypes::timer_t };
+        Ok(0)
+    })
+}
+
+/// Delete a per-process timer
+pub fn sys_timer_delete(timerid: ctypes::timer_t) -> c_int {
+    debug!("sys_timer_delete <= {:#x}", timerid as usize);
+    syscall_body!(sys_timer_delete, {
+        let timer = TIMERS
+            .lock()
+            .remove(&(timerid as usize))
+            .ok_or(LinuxError::EINVAL)?;
+        if let Some(timer) = timer.timer {
+            timer.cancel();
+        }
+        Ok(0)
+    })
+}
+
+/// Arm or disarm a per-process timer
+pub unsafe fn sys_timer_settime(
+    timerid: ctypes::timer_t,
+    flags: c_int,
+    new_value: *const ctypes::itimerspec,
+    old_value: *mut ctypes::itimerspec,
+) -> c_int {
+    debug!("sys_timer_settime <= {:#x} {:#x}", timerid as usize, flags);
+    syscall_body!(sys_timer_settime, {
+        if new_value.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        let new_value = unsafe { &*new_value };
+        let value = check_timespec(&new_value.it_value)?;
+        let interval = check_timespec(&new_value.it_interval)?;
+        let absolute = flags as u32 & ctypes::TIMER_ABSTIME != 0;
+
+        let mut timers = TIMERS.lock();
+        let timer = timers
+            .get_mut(&(timerid as usize))
+            .ok_or(LinuxError::EINVAL)?;
+        if !old_value.is_null() {
+            let (value, interval) = remaining(timer.timer.as_ref());
+            unsafe {
+                *old_value = ctypes::itimerspec {
+                    it_interval: interval.into(),
+                    it_value: value.into(),
+                }
+            };
+        }
+        if let Some(old) = timer.timer.take() {
+            old.cancel();
+        }
+        timer.timer = arm(timer.notify, timer.clock, absolute, value, interval);
+        Ok(0)
+    })
+}
+
+/// Fetch the state of a per-process timer
+pub unsafe fn sys_timer_gettime(
+    timerid: ctypes::timer_t,
+    curr_value: *mut ctypes::itimerspec,
+) -> c_int {
+    debug!("sys_timer_gettime <= {:#x}", timerid as usize);
+    syscall_body!(sys_timer_gettime, {
+        if curr_value.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        let timers = TIMERS.lock();
+        let timer = timers.get(&(timerid as usize)).ok_or(LinuxError::EINVAL)?;
+        let (value, interval) = remaining(timer.timer.as_ref());
+        unsafe {
+            *curr_value = ctypes::itimerspec {
+                it_interval: interval.into(),
+                it_value: value.into(),
+            }
+        };
+        Ok(0)
+    })
+}
+
+/// Get the overrun count of a per-process timer, for its last expiration
+pub fn sys_timer_getoverrun(timerid: ctypes::timer_t) -> c_int {
+    syscall_body!(sys_timer_getoverrun, {
+        let timers = TIMERS.lock();
+        let timer = timers.get(&(timerid as usize)).ok_or(LinuxError::EINVAL)?;
+        let overrun = timer.timer.as_ref().map_or(0, |t| t.overrun());
+        Ok(overrun.min(c_int::MAX as usize))
+    })
+}
+
+/// Set the value of an interval timer
+///
+/// Only `ITIMER_REAL` is supported, which sends `SIGALRM` on expiration.
+pub unsafe fn sys_setitimer(
+    which: c_int,
+    new_value: *const ctypes::itimerval,
+    old_value: *mut ctypes::itimerval,
+) -> c_int {
+    debug!("sys_setitimer <= {}", which);
+    syscall_body!(sys_setitimer, {
+        if which as u32 != ctypes::ITIMER_REAL {
+            warn!("sys_setitimer: unsupported timer {}", which);
+            return Err(LinuxError::EINVAL);
+        }
+        if new_value.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        let new_value = unsafe { &*new_value };
+        let value = check_timeval(&new_value.it_value)?;
+        let interval = check_timeval(&new_value.it_interval)?;
+
+        let mut real_timer = REAL_TIMER.lock();
+        if !old_value.is_null() {
+            let (value, interval) = remaining(real_timer.as_ref());
+            unsafe {
+                *old_value = ctypes::itimerval {
+                    it_interval: interval.into(),
+                    it_value: value.into(),
+                }
+            };
+        }
+        if let Some(old) = real_timer.take() {
+            old.cancel();
+        }
+        *real_timer = arm(alarm_notify(), REAL_CLOCK, false, value, interval);
+        Ok(0)
+    })
+}
+
+/// Get the value of an interval timer
+pub unsafe fn sys_getitimer(which: c_int, curr_value: *mut ctypes::itimerval) -> c_int {
+    debug!("sys_getitimer <= {}", which);
+    syscall_body!(sys_getitimer, {
+        if which as u32 != ctypes::ITIMER_REAL {
+            warn!("sys_getitimer: unsupported timer {}", which);
+            return Err(LinuxError::EINVAL);
+        }
+        if curr_value.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        let (value, interval) = remaining(REAL_TIMER.lock().as_ref());
+        unsafe {
+            *curr_value = ctypes::itimerval {
+                it_interval: interval.into(),
+                it_value: value.into(),
+            }
+        };
+        Ok(0)
+    })
+}
+
+/// Set an alarm clock for delivery of `SIGALRM`
+///
+/// Returns the seconds remaining of the previous alarm, or zero if there was
+/// none.
+pub fn sys_alarm(seconds: c_uint) -> c_uint {
+    debug!("sys_alarm <= {}", seconds);
+    let mut real_timer = REAL_TIMER.lock();
+    let (remaining, _) = remaining(real_timer.as_ref());
+    if let Some(old) = real_timer.take() {
+        old.cancel();
+    }
+    let value = Duration::from_secs(seconds as u64);
+    *real_timer = arm(alarm_notify(), REAL_CLOCK, false, value, Duration::ZERO);
+    // Round up, as zero means no alarm.
+    remaining.as_secs() as c_uint + (remaining.subsec_nanos() > 0) as c_uint
+}
+
+fn alarm_notify() -> Notify {
+    Notify::Signal {
+        signo: ctypes::SIGALRM as _,
+        value: 0,
+    }
+}
@@ -32,14 +32,21 @@ pub mod ctypes;
 
 pub use imp::io::{sys_read, sys_write, sys_writev};
 pub use imp::resources::{sys_getrlimit, sys_setrlimit};
+pub use imp::signal::sys_sigaction;
 pub use imp::sys::sys_sysconf;
 pub use imp::task::{sys_exit, sys_getpid, sys_sched_yield};
 pub use imp::time::{sys_clock_gettime, sys_nanosleep};
 
 #[cfg(feature = "fd")]
-pub use imp::fd_ops::{sys_close, sys_dup, sys_dup2, sys_fcntl, get_file_like};
+pub use imp::fd_ops::{
+    clone_fd_table, get_file_like, new_fd_table, set_current_fd_table_fn, sys_close, sys_dup,
+    sys_dup2, sys_fcntl, FdTable, FileLike,
+};
 #[cfg(feature = "fs")]
-pub use imp::fs::{sys_fstat, sys_getcwd, sys_lseek, sys_lstat, sys_open, sys_rename, sys_stat};
+pub use imp::fs::{
+    get_fs_file, sys_fstat, sys_getcwd, sys_getdents64, sys_link, sys_lseek, sys_lstat, sys_mount,
+    sys_open, sys_readlink, sys_rename, sys_stat, sys_symlink, sys_umount2,
+};
 #[cfg(feature = "select")]
 pub use imp::io_mpx::sys_select;
 #[cfg(feature = "epoll")]
@@ -53,8 +60,30 @@ pub use imp::net::{
 #[cfg(feature = "pipe")]
 pub use imp::pipe::sys_pipe;
 #[cfg(feature = "multitask")]
+pub use imp::pthread::barrier::{
+    sys_pthread_barrier_destroy, sys_pthread_barrier_init, sys_pthread_barrier_wait,
+};
+#[cfg(all(feature = "multitask", feature = "irq"))]
+pub use imp::pthread::cond::sys_pthread_cond_timedwait;
+#[cfg(feature = "multitask")]
+pub use imp::pthread::cond::{
+    sys_pthread_cond_broadcast, sys_pthread_cond_destroy, sys_pthread_cond_init,
+    sys_pthread_cond_signal, sys_pthread_cond_wait,
+};
+#[cfg(feature = "multitask")]
 pub use imp::pthread::mutex::{
     sys_pthread_mutex_init, sys_pthread_mutex_lock, sys_pthread_mutex_unlock,
 };
 #[cfg(feature = "multitask")]
+pub use imp::pthread::rwlock::{
+    sys_pthread_rwlock_destroy, sys_pthread_rwlock_init, sys_pthread_rwlock_rdlock,
+    sys_pthread_rwlock_tryrdlock, sys_pthread_rwlock_trywrlock, sys_pthread_rwlock_unlock,
+    sys_pthread_rwlock_wrlock,
+};
+#[cfg(feature = "multitask")]
 pub use imp::pthread::{sys_pthread_create, sys_pthread_exit, sys_pthread_join, sys_pthread_self};
+#[cfg(all(feature = "multitask", feature = "irq"))]
+pub use imp::timer::{
+    sys_alarm, sys_getitimer, sys_setitimer, sys_timer_create, sys_timer_delete,
+    sys_timer_getoverrun, sys_timer_gettime, sys_timer_settime,
+};
@@ -0,0 +1,205 @@
+use core::ffi::c_int;
+
+use arceos_posix_api::{self as api, ctypes};
+use axerrno::{LinuxError, LinuxResult};
+
+use super::posix_ret;
+use crate::SyscallArgs;
+
+const AT_FDCWD: c_int = -100;
+const AT_SYMLINK_NOFOLLOW: usize = 0x100;
+const AT_REMOVEDIR: usize = 0x200;
+const AT_EMPTY_PATH: usize = 0x1000;
+
+/// Resolves `path` relative to the directory `dirfd`.
+///
+/// Paths relative to a directory file descriptor are not supported yet, so
+/// only `AT_FDCWD` and absolute paths are valid.
+fn at_path(dirfd: c_int, path: &str) -> LinuxResult<&str> {
+    if dirfd == AT_FDCWD || path.starts_with('/') {
+        Ok(path)
+    } else {
+        Err(LinuxError::EBADF)
+    }
+}
+
+pub(super) fn sys_openat(args: &SyscallArgs) -> LinuxResult<isize> {
+    at_path(args.int(0), &args.cstr(1)?)?;
+    posix_ret(api::sys_open(args.ptr(1), args.int(2), args.arg(3) as _) as isize)
+}
+
+pub(super) fn sys_newfstatat(args: &SyscallArgs) -> LinuxResult<isize> {
+    let path = args.cstr(1)?;
+    let buf = args.ptr::<ctypes::stat>(2);
+    if path.is_empty() && args.arg(3) & AT_EMPTY_PATH != 0 {
+        return posix_ret(unsafe { api::sys_fstat(args.int(0), buf) } as isize);
+    }
+    at_path(args.int(0), &path)?;
+    if args.arg(3) & AT_SYMLINK_NOFOLLOW != 0 {
+        posix_ret(unsafe { api::sys_lstat(args.ptr(1), buf) } as isize)
+    } else {
+        posix_ret(unsafe { api::sys_stat(args.ptr(1), buf) } as isize)
+    }
+}
+
+pub(super) fn sys_fstat(args: &SyscallArgs) -> LinuxResult<isize> {
+    posix_ret(unsafe { api::sys_fstat(args.int(0), args.ptr(1)) } as isize)
+}
+
+pub(super) fn sys_getdents64(args: &SyscallArgs) -> LinuxResult<isize> {
+    posix_ret(api::sys_getdents64(args.int(0), args.ptr(1), args.arg(2)) as isize)
+}
+
+/// Returns the length of the path written, including the trailing NUL.
+pub(super) fn sys_getcwd(args: &SyscallArgs) -> LinuxResult<isize> {
+    let buf = args.ptr::<u8>(0);
+    let size = args.arg(1);
+    if buf.is_null() {
+        return Err(LinuxError::EFAULT);
+    }
+    let cwd = axfs::api::current_dir()?;
+    if cwd.len() >= size {
+        return Err(LinuxError::ERANGE);
+    }
+    let dst = unsafe { core::slice::from_raw_parts_mut(buf, cwd.len() + 1) };
+    dst[..cwd.len()].copy_from_slice(cwd.as_bytes());
+    dst[cwd.len()] = 0;
+    Ok(dst.len() as isize)
+}
+
+pub(super) fn sys_chdir(args: &SyscallArgs) -> LinuxResult<isize> {
+    axfs::api::set_current_dir(&args.cstr(0)?)?;
+    Ok(0)
+}
+
+pub(super) fn sys_mkdirat(args: &SyscallArgs) -> LinuxResult<isize> {
+    axfs::api::create_dir(at_path(args.int(0), &args.cstr(1)?)?)?;
+    Ok(0)
+}
+
+pub(super) fn sys_unlinkat(args: &SyscallArgs) -> LinuxResult<isize> {
+    let path = args.cstr(1)?;
+    let path = at_path(args.int(0), &path)?;
+    if args.arg(2) & AT_REMOVEDIR != 0 {
+        axfs::api::remove_dir(path)?;
+    } else {
+        axfs::api::remove_file(path)?;
+    }
+    Ok(0)
+}
+
+pub(super) fn sys_renameat(args: &SyscallArgs) -> LinuxResult<isize> {
+    let (old, new) = (args.cstr(1)?, args.cstr(3)?);
+    axfs::api::rename(at_path(args.int(0), &old)?, at_path(args.int(2), &new)?)?;
+    Ok(0)
+}
+
+/// `RENAME_NOREPLACE` and the other flags are not supported.
+pub(super) fn sys_renameat2(args: &SyscallArgs) -> LinuxResult<isize> {
+    if args.arg(4) != 0 {
+        return Err(LinuxError::EINVAL);
+    }
+    sys_renameat(args)
+}
+
+pub(super) fn sys_readlinkat(args: &SyscallArgs) -> LinuxResult<isize> {
+    at_path(args.int(0), &args.cstr(1)?)?;
+    posix_ret(api::sys_readlink(args.ptr(1), args.ptr(2), args.arg(3)) as isize)
+}
+
+pub(super) fn sys_symlinkat(args: &SyscallArgs) -> LinuxResult<isize> {
+    at_path(args.int(1), &args.cstr(2)?)?;
+    posix_ret(api::sys_symlink(args.ptr(0), args.ptr(2)) as isize)
+}
+
+/// A symbolic link as the old path is never followed, so `AT_SYMLINK_FOLLOW`
+/// is not supported.
+pub(super) fn sys_linkat(args: &SyscallArgs) -> LinuxResult<isize> {
+    if args.arg(4) != 0 {
+        return Err(LinuxError::EINVAL);
+    }
+    let (old, new) = (args.cstr(1)?, args.cstr(3)?);
+    axfs::api::hard_link(at_path(args.int(0), &old)?, at_path(args.int(2), &new)?)?;
+    Ok(0)
+}
+
+/// There are no permissions, so it only checks if the file exists.
+pub(super) fn sys_faccessat(args: &SyscallArgs) -> LinuxResult<isize> {
+    axfs::api::metadata(at_path(args.int(0), &args.cstr(1)?)?)?;
+    Ok(0)
+}
+
+pub(super) fn sys_mount(args: &SyscallArgs) -> LinuxResult<isize> {
+    let ret = api::sys_mount(
+        args.ptr(0),
+        args.ptr(1),
+        args.ptr(2),
+        args.arg(3) as _,
+        args.ptr(4),
+    );
+    posix_ret(ret as isize)
+}
+
+pub(super) fn sys_umount2(args: &SyscallArgs) -> LinuxResult<isize> {
+    posix_ret(api::sys_umount2(args.ptr(0), args.int(1)) as isize)
+}
+
+#[cfg(target_arch = "x86_64")]
+pub(super) fn sys_open(args: &SyscallArgs) -> LinuxResult<isize> {
+    posix_ret(api::sys_open(args.ptr(0), args.int(1), args.arg(2) as _) as isize)
+}
+
+#[cfg(target_arch = "x86_64")]
+pub(super) fn sys_stat(args: &SyscallArgs) -> LinuxResult<isize> {
+    posix_ret(unsafe { api::sys_stat(args.ptr(0), args.ptr(1)) } as isize)
+}
+
+#[cfg(target_arch = "x86_64")]
+pub(super) fn sys_lstat(args: &SyscallArgs) -> LinuxResult<isize> {
+    posix_ret(unsafe { api::sys_lstat(args.ptr(0), args.ptr(1)) } as isize)
+}
+
+#[cfg(target_arch = "x86_64")]
+pub(super) fn sys_access(args: &SyscallArgs) -> LinuxResult<isize> {
+    axfs::api::metadata(&args.cstr(0)?)?;
+    Ok(0)
+}
+
+#[cfg(target_arch = "x86_64")]
+pub(super) fn sys_mkdir(args: &SyscallArgs) -> LinuxResult<isize> {
+    axfs::api::create_dir(&args.cstr(0)?)?;
+    Ok(0)
+}
+
+#[cfg(target_arch = "x86_64")]
+pub(super) fn sys_rmdir(args: &SyscallArgs) -> LinuxResult<isize> {
+    axfs::api::remove_dir(&args.cstr(0)?)?;
+    Ok(0)
+}
+
+#[cfg(target_arch = "x86_64")]
+pub(super) fn sys_unlink(args: &SyscallArgs) -> LinuxResult<isize> {
+    axfs::api::remove_file(&args.cstr(0)?)?;
+    Ok(0)
+}
+
+#[cfg(target_arch = "x86_64")]
+pub(super) fn sys_rename(args: &SyscallArgs) -> LinuxResult<isize> {
+    axfs::api::rename(&args.cstr(0)?, &args.cstr(1)?)?;
+    Ok(0)
+}
+
+#[cfg(target_arch = "x86_64")]
+pub(super) fn sys_readlink(args: &SyscallArgs) -> LinuxResult<isize> {
+    posix_ret(api::sys_readlink(args.ptr(0), args.ptr(1), args.arg(2)) as isize)
+}
+
+#[cfg(target_arch = "x86_64")]
+pub(super) fn sys_symlink(args: &SyscallArgs) -> LinuxResult<isize> {
+    posix_ret(api::sys_symlink(args.ptr(0), args.ptr(1)) as isize)
+}
+
+#[cfg(target_arch = "x86_64")]
+pub(super) fn sys_link(args: &SyscallArgs) -> LinuxResult<isize> {
+    posix_ret(api::sys_link(args.ptr(0), args.ptr(1)) as isize)
+}
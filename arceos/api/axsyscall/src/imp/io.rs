@@ -0,0 +1,105 @@
+use core::ffi::c_int;
+
+use arceos_posix_api::{self as api, ctypes};
+use axerrno::{LinuxError, LinuxResult};
+
+use super::posix_ret;
+use crate::SyscallArgs;
+
+/// Maximum number of `iovec`s in `readv` and `writev`.
+const IOV_MAX: i32 = 1024;
+
+const O_CLOEXEC: usize = 0o2000000;
+const O_NONBLOCK: usize = 0o4000;
+
+pub(super) fn sys_read(args: &SyscallArgs) -> LinuxResult<isize> {
+    posix_ret(api::sys_read(args.int(0), args.ptr(1), args.arg(2)))
+}
+
+pub(super) fn sys_write(args: &SyscallArgs) -> LinuxResult<isize> {
+    posix_ret(api::sys_write(args.int(0), args.ptr(1), args.arg(2)))
+}
+
+pub(super) fn sys_readv(args: &SyscallArgs) -> LinuxResult<isize> {
+    let fd = args.int(0);
+    let iov = args.ptr::<ctypes::iovec>(1);
+    let iovcnt = args.int(2);
+    if !(0..=IOV_MAX).contains(&iovcnt) {
+        return Err(LinuxError::EINVAL);
+    }
+    if iov.is_null() {
+        return Err(LinuxError::EFAULT);
+    }
+
+    let iovs = unsafe { core::slice::from_raw_parts(iov, iovcnt as usize) };
+    let mut total = 0;
+    for iov in iovs.iter().filter(|iov| iov.iov_len > 0) {
+        let n = posix_ret(api::sys_read(fd, iov.iov_base, iov.iov_len))?;
+        total += n;
+        // Stop at a short read, as the rest buffers would not be filled.
+        if (n as usize) < iov.iov_len {
+            break;
+        }
+    }
+    Ok(total)
+}
+
+pub(super) fn sys_writev(args: &SyscallArgs) -> LinuxResult<isize> {
+    posix_ret(unsafe { api::sys_writev(args.int(0), args.ptr(1), args.int(2)) })
+}
+
+pub(super) fn sys_lseek(args: &SyscallArgs) -> LinuxResult<isize> {
+    posix_ret(api::sys_lseek(args.int(0), args.arg(1) as _, args.int(2)) as isize)
+}
+
+/// Terminal controls are not supported, but `isatty` in libc should not fail.
+pub(super) fn sys_ioctl(args: &SyscallArgs) -> LinuxResult<isize> {
+    debug!("Ignore ioctl: fd {}, op {:#x}", args.int(0), args.arg(1));
+    Ok(0)
+}
+
+pub(super) fn sys_close(args: &SyscallArgs) -> LinuxResult<isize> {
+    posix_ret(api::sys_close(args.int(0)) as isize)
+}
+
+pub(super) fn sys_dup(args: &SyscallArgs) -> LinuxResult<isize> {
+    posix_ret(api::sys_dup(args.int(0)) as isize)
+}
+
+pub(super) fn sys_dup3(args: &SyscallArgs) -> LinuxResult<isize> {
+    let (old_fd, new_fd) = (args.int(0), args.int(1));
+    // `O_CLOEXEC` is accepted but ignored, as there is no `exec` here.
+    if old_fd == new_fd || args.arg(2) & !O_CLOEXEC != 0 {
+        return Err(LinuxError::EINVAL);
+    }
+    posix_ret(api::sys_dup2(old_fd, new_fd) as isize)
+}
+
+#[cfg(target_arch = "x86_64")]
+pub(super) fn sys_dup2(args: &SyscallArgs) -> LinuxResult<isize> {
+    posix_ret(api::sys_dup2(args.int(0), args.int(1)) as isize)
+}
+
+pub(super) fn sys_fcntl(args: &SyscallArgs) -> LinuxResult<isize> {
+    posix_ret(api::sys_fcntl(args.int(0), args.int(1), args.arg(2)) as isize)
+}
+
+fn pipe(fds: *mut c_int, flags: usize) -> LinuxResult<isize> {
+    if fds.is_null() {
+        return Err(LinuxError::EFAULT);
+    }
+    if flags & O_NONBLOCK != 0 {
+        warn!("pipe: O_NONBLOCK is not supported");
+    }
+    let fds = unsafe { core::slice::from_raw_parts_mut(fds, 2) };
+    posix_ret(api::sys_pipe(fds) as isize)
+}
+
+pub(super) fn sys_pipe2(args: &SyscallArgs) -> LinuxResult<isize> {
+    pipe(args.ptr(0), args.arg(1))
+}
+
+#[cfg(target_arch = "x86_64")]
+pub(super) fn sys_pipe(args: &SyscallArgs) -> LinuxResult<isize> {
+    pipe(args.ptr(0), 0)
+}
@@ -0,0 +1,100 @@
+//! Built-in syscall handlers, which do not depend on the process model of the
+//! kernel.
+
+mod fs;
+mod io;
+mod sys;
+mod task;
+mod time;
+
+use axerrno::{LinuxError, LinuxResult};
+
+use crate::{SyscallHandler, Sysno};
+
+/// Converts the return value of an [`arceos_posix_api`] function, which is a
+/// negated `errno` on failure.
+fn posix_ret(ret: isize) -> LinuxResult<isize> {
+    if ret >= 0 {
+        Ok(ret)
+    } else {
+        Err(LinuxError::try_from(-ret as i32).unwrap_or(LinuxError::EINVAL))
+    }
+}
+
+/// Returns the built-in handler of the syscall, if any.
+pub(crate) fn builtin_handler(sysno: Sysno) -> Option<SyscallHandler> {
+    use Sysno::*;
+    let handler: SyscallHandler = match sysno {
+        read => io::sys_read,
+        write => io::sys_write,
+        readv => io::sys_readv,
+        writev => io::sys_writev,
+        lseek => io::sys_lseek,
+        ioctl => io::sys_ioctl,
+        close => io::sys_close,
+        dup => io::sys_dup,
+        dup3 => io::sys_dup3,
+        fcntl => io::sys_fcntl,
+        pipe2 => io::sys_pipe2,
+        openat => fs::sys_openat,
+        newfstatat => fs::sys_newfstatat,
+        fstat => fs::sys_fstat,
+        getdents64 => fs::sys_getdents64,
+        getcwd => fs::sys_getcwd,
+        chdir => fs::sys_chdir,
+        mkdirat => fs::sys_mkdirat,
+        unlinkat => fs::sys_unlinkat,
+        renameat => fs::sys_renameat,
+        renameat2 => fs::sys_renameat2,
+        readlinkat => fs::sys_readlinkat,
+        symlinkat => fs::sys_symlinkat,
+        linkat => fs::sys_linkat,
+        faccessat => fs::sys_faccessat,
+        mount => fs::sys_mount,
+        umount2 => fs::sys_umount2,
+        clock_gettime => time::sys_clock_gettime,
+        clock_getres => time::sys_clock_getres,
+        clock_nanosleep => time::sys_clock_nanosleep,
+        gettimeofday => time::sys_gettimeofday,
+        nanosleep => time::sys_nanosleep,
+        uname => sys::sys_uname,
+        getrandom => sys::sys_getrandom,
+        getuid | geteuid | getgid | getegid => sys::sys_getuid,
+        umask => sys::sys_umask,
+        getrlimit => sys::sys_getrlimit,
+        setrlimit => sys::sys_setrlimit,
+        prlimit64 => sys::sys_prlimit64,
+        sched_yield => task::sys_sched_yield,
+        getpid => task::sys_getpid,
+        gettid => task::sys_gettid,
+        exit | exit_group => task::sys_exit,
+        #[cfg(target_arch = "x86_64")]
+        open => fs::sys_open,
+        #[cfg(target_arch = "x86_64")]
+        stat => fs::sys_stat,
+        #[cfg(target_arch = "x86_64")]
+        lstat => fs::sys_lstat,
+        #[cfg(target_arch = "x86_64")]
+        access => fs::sys_access,
+        #[cfg(target_arch = "x86_64")]
+        mkdir => fs::sys_mkdir,
+        #[cfg(target_arch = "x86_64")]
+        rmdir => fs::sys_rmdir,
+        #[cfg(target_arch = "x86_64")]
+        unlink => fs::sys_unlink,
+        #[cfg(target_arch = "x86_64")]
+        rename => fs::sys_rename,
+        #[cfg(target_arch = "x86_64")]
+        readlink => fs::sys_readlink,
+        #[cfg(target_arch = "x86_64")]
+        symlink => fs::sys_symlink,
+        #[cfg(target_arch = "x86_64")]
+        link => fs::sys_link,
+        #[cfg(target_arch = "x86_64")]
+        pipe => io::sys_pipe,
+        #[cfg(target_arch = "x86_64")]
+        dup2 => io::sys_dup2,
+        _ => return None,
+    };
+    Some(handler)
+}
@@ -0,0 +1,106 @@
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use arceos_posix_api::{self as api, ctypes};
+use axerrno::{LinuxError, LinuxResult};
+
+use super::posix_ret;
+use crate::SyscallArgs;
+
+/// Length of each field of `struct utsname`.
+const UTSNAME_LEN: usize = 65;
+
+cfg_if::cfg_if! {
+    if #[cfg(target_arch = "x86_64")] {
+        const MACHINE: &str = "x86_64";
+    } else if #[cfg(target_arch = "aarch64")] {
+        const MACHINE: &str = "aarch64";
+    } else {
+        const MACHINE: &str = "riscv64";
+    }
+}
+
+/// The file mode creation mask, shared by all tasks.
+static UMASK: AtomicU32 = AtomicU32::new(0o022);
+
+#[repr(C)]
+struct UtsName {
+    sysname: [u8; UTSNAME_LEN],
+    nodename: [u8; UTSNAME_LEN],
+    release: [u8; UTSNAME_LEN],
+    version: [u8; UTSNAME_LEN],
+    machine: [u8; UTSNAME_LEN],
+    domainname: [u8; UTSNAME_LEN],
+}
+
+fn uts_field(s: &str) -> [u8; UTSNAME_LEN] {
+    let mut field = [0; UTSNAME_LEN];
+    field[..s.len()].copy_from_slice(s.as_bytes());
+    field
+}
+
+/// The system claims to be Linux, as some libc check the kernel version.
+pub(super) fn sys_uname(args: &SyscallArgs) -> LinuxResult<isize> {
+    let buf = args.ptr::<UtsName>(0);
+    if buf.is_null() {
+        return Err(LinuxError::EFAULT);
+    }
+    let uts = UtsName {
+        sysname: uts_field("Linux"),
+        nodename: uts_field("arceos"),
+        release: uts_field("5.15.0"),
+        version: uts_field("#1 SMP ArceOS"),
+        machine: uts_field(MACHINE),
+        domainname: uts_field("localdomain"),
+    };
+    unsafe { buf.write(uts) };
+    Ok(0)
+}
+
+pub(super) fn sys_getrandom(args: &SyscallArgs) -> LinuxResult<isize> {
+    let buf = args.ptr::<u8>(0);
+    let len = args.arg(1);
+    if buf.is_null() {
+        return Err(LinuxError::EFAULT);
+    }
+    let buf = unsafe { core::slice::from_raw_parts_mut(buf, len) };
+    for chunk in buf.chunks_mut(16) {
+        let rand = axhal::misc::random().to_ne_bytes();
+        chunk.copy_from_slice(&rand[..chunk.len()]);
+    }
+    Ok(len as isize)
+}
+
+/// Everything runs as root, for `getuid`, `geteuid`, `getgid` and `getegid`.
+pub(super) fn sys_getuid(_args: &SyscallArgs) -> LinuxResult<isize> {
+    Ok(0)
+}
+
+pub(super) fn sys_umask(args: &SyscallArgs) -> LinuxResult<isize> {
+    let mask = args.arg(0) as u32 & 0o777;
+    Ok(UMASK.swap(mask, Ordering::Relaxed) as isize)
+}
+
+pub(super) fn sys_getrlimit(args: &SyscallArgs) -> LinuxResult<isize> {
+    posix_ret(unsafe { api::sys_getrlimit(args.int(0), args.ptr(1)) } as isize)
+}
+
+pub(super) fn sys_setrlimit(args: &SyscallArgs) -> LinuxResult<isize> {
+    posix_ret(unsafe { api::sys_setrlimit(args.int(0), args.ptr(1)) } as isize)
+}
+
+/// Only the limits of the caller itself (`pid` is 0) are supported.
+pub(super) fn sys_prlimit64(args: &SyscallArgs) -> LinuxResult<isize> {
+    if args.int(0) != 0 {
+        return Err(LinuxError::EPERM);
+    }
+    let resource = args.int(1);
+    let new_limit = args.ptr::<ctypes::rlimit>(2);
+    let old_limit = args.ptr::<ctypes::rlimit>(3);
+    if !old_limit.is_null() {
+        posix_ret(unsafe { api::sys_getrlimit(resource, old_limit) } as isize)?;
+    }
+    if !new_limit.is_null() {
+        posix_ret(unsafe { api::sys_setrlimit(resource, new_limit) } as isize)?;
+    }
+    Ok(0)
+}
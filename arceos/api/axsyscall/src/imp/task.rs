@@ -0,0 +1,23 @@
+use arceos_posix_api as api;
+use axerrno::LinuxResult;
+
+use super::posix_ret;
+use crate::SyscallArgs;
+
+pub(super) fn sys_sched_yield(_args: &SyscallArgs) -> LinuxResult<isize> {
+    posix_ret(api::sys_sched_yield() as isize)
+}
+
+pub(super) fn sys_getpid(_args: &SyscallArgs) -> LinuxResult<isize> {
+    posix_ret(api::sys_getpid() as isize)
+}
+
+pub(super) fn sys_gettid(_args: &SyscallArgs) -> LinuxResult<isize> {
+    Ok(axtask::current().id().as_u64() as isize)
+}
+
+/// Without a process model, both `exit` and `exit_group` only terminate the
+/// current task.
+pub(super) fn sys_exit(args: &SyscallArgs) -> LinuxResult<isize> {
+    axtask::exit(args.int(0))
+}
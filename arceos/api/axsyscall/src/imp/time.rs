@@ -0,0 +1,55 @@
+use core::time::Duration;
+
+use arceos_posix_api::{self as api, ctypes};
+use axerrno::{LinuxError, LinuxResult};
+
+use super::posix_ret;
+use crate::SyscallArgs;
+
+const TIMER_ABSTIME: usize = 1;
+
+pub(super) fn sys_clock_gettime(args: &SyscallArgs) -> LinuxResult<isize> {
+    posix_ret(unsafe { api::sys_clock_gettime(args.int(0), args.ptr(1)) } as isize)
+}
+
+/// The clocks have a resolution of 1 nanosecond.
+pub(super) fn sys_clock_getres(args: &SyscallArgs) -> LinuxResult<isize> {
+    let res = args.ptr::<ctypes::timespec>(1);
+    if !res.is_null() {
+        unsafe { *res = Duration::from_nanos(1).into() };
+    }
+    Ok(0)
+}
+
+pub(super) fn sys_gettimeofday(args: &SyscallArgs) -> LinuxResult<isize> {
+    let tv = args.ptr::<ctypes::timeval>(0);
+    if !tv.is_null() {
+        unsafe { *tv = axhal::time::wall_time().into() };
+    }
+    Ok(0)
+}
+
+pub(super) fn sys_nanosleep(args: &SyscallArgs) -> LinuxResult<isize> {
+    posix_ret(unsafe { api::sys_nanosleep(args.ptr(0), args.ptr(1)) } as isize)
+}
+
+pub(super) fn sys_clock_nanosleep(args: &SyscallArgs) -> LinuxResult<isize> {
+    if args.arg(1) & TIMER_ABSTIME == 0 {
+        return posix_ret(unsafe { api::sys_nanosleep(args.ptr(2), args.ptr(3)) } as isize);
+    }
+
+    let req = args.ptr::<ctypes::timespec>(2);
+    if req.is_null() {
+        return Err(LinuxError::EFAULT);
+    }
+    let deadline = Duration::from(unsafe { *req });
+    let now = match args.arg(0) as u32 {
+        ctypes::CLOCK_REALTIME => axhal::time::wall_time(),
+        ctypes::CLOCK_MONOTONIC => axhal::time::monotonic_time(),
+        _ => return Err(LinuxError::EINVAL),
+    };
+    if deadline > now {
+        axtask::sleep_interruptible(deadline - now).map_err(|_| LinuxError::EINTR)?;
+    }
+    Ok(0)
+}
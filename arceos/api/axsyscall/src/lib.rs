@@ -0,0 +1,202 @@
+//! Table-driven Linux syscall layer for [ArceOS] monolithic kernels.
+//!
+//! The syscall numbers of each architecture are listed in one table, from
+//! which the [`Sysno`] enum is generated. [`handle_syscall`] looks up the
+//! handler of a syscall by its number, decodes the arguments, and returns
+//! `ENOSYS` for the unknown and unimplemented ones.
+//!
+//! The handlers that do not depend on the process model of the kernel, such as
+//! `read`, `openat`, `clock_gettime` and `uname`, are built in. The kernel
+//! registers the others, or overrides the built-in ones, with
+//! [`register_syscalls!`]:
+//!
+//! ```ignore
+//! axsyscall::register_syscalls! {
+//!     set_tid_address => |args| Ok(my_set_tid_address(args.ptr(0))),
+//!     exit_group => sys_exit_group,
+//! }
+//!
+//! #[register_trap_handler(SYSCALL)]
+//! fn handle_syscall(tf: &TrapFrame, syscall_num: usize) -> isize {
+//!     axsyscall::handle_syscall(tf, syscall_num)
+//! }
+//! ```
+//!
+//! The strings passed by pointers are read through [`set_read_user_fn`], so
+//! that bad pointers fail with `EFAULT` instead of faulting in the kernel.
+//!
+//! Every syscall can be traced in the style of `strace` with [`set_trace`].
+//!
+//! [ArceOS]: https://github.com/arceos-org/arceos
+
+#![no_std]
+
+#[macro_use]
+extern crate axlog;
+extern crate alloc;
+
+mod imp;
+mod sysno;
+mod trace;
+mod user;
+
+use alloc::string::String;
+use core::ffi::c_int;
+
+use axerrno::{LinuxError, LinuxResult};
+use axhal::arch::TrapFrame;
+use linkme::distributed_slice as def_syscall_handler;
+use user::read_user_cstr;
+
+pub use linkme::distributed_slice as register_syscall_handler;
+pub use sysno::Sysno;
+pub use trace::set_trace;
+pub use user::{set_read_user_fn, ReadUserFn};
+
+#[doc(hidden)]
+pub use linkme;
+
+/// A syscall handler.
+///
+/// The error is returned to the user as a negated `errno`.
+pub type SyscallHandler = fn(&SyscallArgs) -> LinuxResult<isize>;
+
+/// Longest path accepted, including the trailing NUL.
+const PATH_MAX: usize = 4096;
+
+/// Syscall handlers registered by the kernel, which take precedence over the
+/// built-in ones.
+///
+/// Use [`register_syscalls!`] to add handlers. Each syscall should have only
+/// one handler registered.
+#[def_syscall_handler]
+pub static SYSCALL_HANDLERS: [(Sysno, SyscallHandler)];
+
+/// Registers syscall handlers into [`SYSCALL_HANDLERS`].
+///
+/// Each entry maps the name of a syscall in [`Sysno`] to a
+/// [`SyscallHandler`], which can also be a closure not capturing anything.
+/// Entries can have `#[cfg(...)]` attributes for architecture-specific
+/// syscalls.
+#[macro_export]
+macro_rules! register_syscalls {
+    ($($(#[$attr:meta])* $sysno:ident => $handler:expr),* $(,)?) => {
+        $(
+            $(#[$attr])*
+            const _: () = {
+                #[$crate::register_syscall_handler($crate::SYSCALL_HANDLERS)]
+                #[linkme(crate = $crate::linkme)]
+                static HANDLER: ($crate::Sysno, $crate::SyscallHandler) =
+                    ($crate::Sysno::$sysno, $handler);
+            };
+        )*
+    };
+}
+
+/// The arguments of a syscall, decoded from the trap frame.
+pub struct SyscallArgs<'a> {
+    tf: &'a TrapFrame,
+    sysno: Sysno,
+    args: [usize; 6],
+}
+
+impl<'a> SyscallArgs<'a> {
+    fn new(tf: &'a TrapFrame, sysno: Sysno) -> Self {
+        Self {
+            tf,
+            sysno,
+            args: [
+                tf.arg0(),
+                tf.arg1(),
+                tf.arg2(),
+                tf.arg3(),
+                tf.arg4(),
+                tf.arg5(),
+            ],
+        }
+    }
+
+    /// The trap frame of the syscall, e.g., for `clone` to copy.
+    pub fn trap_frame(&self) -> &'a TrapFrame {
+        self.tf
+    }
+
+    /// The syscall being handled.
+    pub fn sysno(&self) -> Sysno {
+        self.sysno
+    }
+
+    /// Returns the raw value of the `idx`-th argument.
+    pub fn arg(&self, idx: usize) -> usize {
+        self.args[idx]
+    }
+
+    /// Returns the `idx`-th argument as a C `int`, such as a file descriptor.
+    pub fn int(&self, idx: usize) -> c_int {
+        self.args[idx] as c_int
+    }
+
+    /// Returns the `idx`-th argument as a user pointer.
+    pub fn ptr<T>(&self, idx: usize) -> *mut T {
+        self.args[idx] as *mut T
+    }
+
+    /// Copies the NUL-terminated path pointed to by the `idx`-th argument.
+    ///
+    /// Returns [`LinuxError::EFAULT`] for a bad pointer,
+    /// [`LinuxError::ENAMETOOLONG`] if it is longer than `PATH_MAX`, and
+    /// [`LinuxError::EINVAL`] if it is not valid UTF-8.
+    pub fn cstr(&self, idx: usize) -> LinuxResult<String> {
+        match read_user_cstr(self.args[idx], PATH_MAX - 1)? {
+            (bytes, true) => String::from_utf8(bytes).map_err(|_| LinuxError::EINVAL),
+            (_, false) => Err(LinuxError::ENAMETOOLONG),
+        }
+    }
+}
+
+/// The handlers indexed by the syscall numbers.
+type HandlerTable = [Option<SyscallHandler>; Sysno::LIMIT];
+
+static HANDLER_TABLE: spin::Once<HandlerTable> = spin::Once::new();
+
+/// Builds the handler table from the registered and the built-in handlers.
+fn build_handler_table() -> HandlerTable {
+    let mut table: HandlerTable = [None; Sysno::LIMIT];
+    for &(sysno, handler) in SYSCALL_HANDLERS.iter() {
+        let slot = &mut table[sysno.nr()];
+        if slot.is_some() {
+            warn!("Syscall {} is registered more than once", sysno);
+        } else {
+            *slot = Some(handler);
+        }
+    }
+    for (nr, slot) in table.iter_mut().enumerate() {
+        if slot.is_none() {
+            *slot = Sysno::new(nr).and_then(imp::builtin_handler);
+        }
+    }
+    table
+}
+
+fn find_handler(sysno: Sysno) -> Option<SyscallHandler> {
+    HANDLER_TABLE.call_once(build_handler_table)[sysno.nr()]
+}
+
+/// Handles the syscall with the number `syscall_num`, and returns the value
+/// for the user, which is a negated `errno` on failure.
+///
+/// It should be called from the `SYSCALL` trap handler of the kernel.
+pub fn handle_syscall(tf: &TrapFrame, syscall_num: usize) -> isize {
+    let Some(sysno) = Sysno::new(syscall_num) else {
+        warn!("Unimplemented syscall: {}", syscall_num);
+        return -LinuxError::ENOSYS.code() as _;
+    };
+    let args = SyscallArgs::new(tf, sysno);
+    match find_handler(sysno) {
+        Some(handler) => trace::call(&args, handler),
+        None => {
+            warn!("Unimplemented syscall: {}", sysno);
+            trace::call(&args, |_| Err(LinuxError::ENOSYS))
+        }
+    }
+}
@@ -0,0 +1,68 @@
+//! Syscall numbers of the supported architectures.
+
+/// Generates the [`Sysno`] enum from a table of `name = number` pairs.
+macro_rules! syscall_table {
+    ($($name:ident = $num:literal,)*) => {
+        /// Linux syscall numbers of the current architecture.
+        ///
+        /// The variants are named after the syscalls, as `strace` shows them.
+        #[allow(non_camel_case_types, missing_docs)]
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        #[repr(usize)]
+        pub enum Sysno {
+            $($name = $num,)*
+        }
+
+        impl Sysno {
+            /// One more than the largest syscall number.
+            pub const LIMIT: usize = {
+                let mut limit = 0;
+                $(
+                    if $num + 1 > limit {
+                        limit = $num + 1;
+                    }
+                )*
+                limit
+            };
+
+            /// Returns the syscall with the given number, or `None` if it is
+            /// unknown.
+            pub const fn new(nr: usize) -> Option<Self> {
+                match nr {
+                    $($num => Some(Self::$name),)*
+                    _ => None,
+                }
+            }
+
+            /// Returns the syscall number.
+            pub const fn nr(self) -> usize {
+                self as usize
+            }
+
+            /// Returns the name of the syscall.
+            pub const fn name(self) -> &'static str {
+                match self {
+                    $(Self::$name => stringify!($name),)*
+                }
+            }
+        }
+
+        impl core::fmt::Display for Sysno {
+            fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+                f.write_str(self.name())
+            }
+        }
+    };
+}
+
+cfg_if::cfg_if! {
+    if #[cfg(target_arch = "x86_64")] {
+        mod x86_64;
+        pub use self::x86_64::Sysno;
+    } else if #[cfg(any(target_arch = "riscv64", target_arch = "aarch64"))] {
+        mod generic;
+        pub use self::generic::Sysno;
+    } else {
+        compile_error!("axsyscall: unsupported architecture");
+    }
+}
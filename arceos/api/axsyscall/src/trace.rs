@@ -0,0 +1,206 @@
+//! `strace`-style tracing of syscalls.
+
+use alloc::string::String;
+use core::fmt::Write;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use axerrno::LinuxResult;
+
+use crate::user::read_user_cstr;
+use crate::{SyscallArgs, SyscallHandler, Sysno};
+
+/// Strings longer than this are truncated in the trace.
+const MAX_STR_LEN: usize = 32;
+
+const AT_FDCWD: i32 = -100;
+
+static TRACE_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Enables or disables tracing of all syscalls.
+///
+/// When enabled, each syscall is printed with its arguments and result, e.g.,
+/// `[5] openat(AT_FDCWD, "/sbin/hello", 0x0, 0o0) = 3`.
+pub fn set_trace(enabled: bool) {
+    TRACE_ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// How an argument is shown in the trace.
+#[derive(Clone, Copy)]
+enum Arg {
+    /// Signed decimal.
+    Int,
+    /// Unsigned decimal, such as a size.
+    Uint,
+    /// Hexadecimal, such as flags.
+    Hex,
+    /// Octal, such as a file mode.
+    Oct,
+    /// A file descriptor, or `AT_FDCWD`.
+    Fd,
+    /// A user pointer, or `NULL`.
+    Ptr,
+    /// A user C string.
+    Str,
+}
+
+/// Returns how to show the arguments of the syscall.
+fn signature(sysno: Sysno) -> &'static [Arg] {
+    use Arg::*;
+    use Sysno::*;
+    match sysno {
+        getcwd => &[Ptr, Uint],
+        dup | close | fsync | fdatasync | fchdir => &[Fd],
+        dup3 => &[Fd, Fd, Hex],
+        fcntl => &[Fd, Int, Hex],
+        ioctl => &[Fd, Hex, Ptr],
+        mkdirat => &[Fd, Str, Oct],
+        unlinkat => &[Fd, Str, Hex],
+        symlinkat => &[Str, Fd, Str],
+        linkat => &[Fd, Str, Fd, Str, Hex],
+        renameat => &[Fd, Str, Fd, Str],
+        renameat2 => &[Fd, Str, Fd, Str, Hex],
+        faccessat => &[Fd, Str, Oct, Hex],
+        fchmodat => &[Fd, Str, Oct],
+        fchownat => &[Fd, Str, Int, Int, Hex],
+        chdir => &[Str],
+        mount => &[Str, Str, Str, Hex, Ptr],
+        umount2 => &[Str, Hex],
+        openat => &[Fd, Str, Hex, Oct],
+        pipe2 => &[Ptr, Hex],
+        getdents64 => &[Fd, Ptr, Uint],
+        lseek => &[Fd, Int, Int],
+        read | write => &[Fd, Ptr, Uint],
+        pread64 | pwrite64 => &[Fd, Ptr, Uint, Int],
+        readv | writev => &[Fd, Ptr, Int],
+        readlinkat => &[Fd, Str, Ptr, Uint],
+        newfstatat => &[Fd, Str, Ptr, Hex],
+        fstat => &[Fd, Ptr],
+        statx => &[Fd, Str, Hex, Hex, Ptr],
+        truncate => &[Str, Int],
+        ftruncate => &[Fd, Int],
+        utimensat => &[Fd, Str, Ptr, Hex],
+        exit | exit_group => &[Int],
+        set_tid_address => &[Ptr],
+        futex => &[Ptr, Int, Hex, Ptr, Ptr, Hex],
+        set_robust_list => &[Ptr, Uint],
+        nanosleep => &[Ptr, Ptr],
+        clock_gettime | clock_getres => &[Int, Ptr],
+        clock_nanosleep => &[Int, Hex, Ptr, Ptr],
+        sched_yield | getpid | getppid | gettid | getuid | geteuid | getgid | getegid
+        | setsid | sync => &[],
+        kill => &[Int, Int],
+        tkill => &[Int, Int],
+        tgkill => &[Int, Int, Int],
+        rt_sigaction => &[Int, Ptr, Ptr, Uint],
+        rt_sigprocmask => &[Int, Ptr, Ptr, Uint],
+        rt_sigreturn => &[],
+        uname | sysinfo | times => &[Ptr],
+        getrlimit | setrlimit => &[Int, Ptr],
+        prlimit64 => &[Int, Int, Ptr, Ptr],
+        umask => &[Oct],
+        gettimeofday => &[Ptr, Ptr],
+        brk => &[Ptr],
+        munmap => &[Ptr, Uint],
+        mremap => &[Ptr, Uint, Uint, Hex, Ptr],
+        mmap => &[Ptr, Uint, Hex, Hex, Fd, Hex],
+        mprotect => &[Ptr, Uint, Hex],
+        msync | madvise => &[Ptr, Uint, Hex],
+        clone => &[Hex, Ptr, Ptr, Ptr, Ptr],
+        execve => &[Str, Ptr, Ptr],
+        wait4 => &[Int, Ptr, Hex, Ptr],
+        getrandom => &[Ptr, Uint, Hex],
+        #[cfg(target_arch = "x86_64")]
+        open => &[Str, Hex, Oct],
+        #[cfg(target_arch = "x86_64")]
+        stat | lstat => &[Str, Ptr],
+        #[cfg(target_arch = "x86_64")]
+        access | mkdir | chmod => &[Str, Oct],
+        #[cfg(target_arch = "x86_64")]
+        rmdir | unlink => &[Str],
+        #[cfg(target_arch = "x86_64")]
+        rename | link | symlink => &[Str, Str],
+        #[cfg(target_arch = "x86_64")]
+        readlink => &[Str, Ptr, Uint],
+        #[cfg(target_arch = "x86_64")]
+        pipe => &[Ptr],
+        #[cfg(target_arch = "x86_64")]
+        dup2 => &[Fd, Fd],
+        #[cfg(target_arch = "x86_64")]
+        fork | vfork => &[],
+        #[cfg(target_arch = "x86_64")]
+        arch_prctl => &[Hex, Ptr],
+        _ => &[Hex, Hex, Hex, Hex, Hex, Hex],
+    }
+}
+
+fn format_arg(out: &mut String, kind: Arg, val: usize) -> core::fmt::Result {
+    match kind {
+        Arg::Int => write!(out, "{}", val as isize),
+        Arg::Uint => write!(out, "{}", val),
+        Arg::Hex => write!(out, "{:#x}", val),
+        Arg::Oct => write!(out, "{:#o}", val),
+        Arg::Fd if val as i32 == AT_FDCWD => out.write_str("AT_FDCWD"),
+        Arg::Fd => write!(out, "{}", val as i32),
+        Arg::Ptr | Arg::Str if val == 0 => out.write_str("NULL"),
+        Arg::Ptr => write!(out, "{:#x}", val),
+        Arg::Str => match read_user_cstr(val, MAX_STR_LEN) {
+            Ok((bytes, terminated)) => {
+                let ellipsis = if terminated { "" } else { "..." };
+                write!(out, "{:?}{}", String::from_utf8_lossy(&bytes), ellipsis)
+            }
+            Err(_) => write!(out, "{:#x}", val),
+        },
+    }
+}
+
+/// Formats `name(arg0, arg1, ...)`.
+///
+/// It must be done before the syscall, as it may change the user memory, e.g.,
+/// `execve`.
+fn format_call(args: &SyscallArgs) -> String {
+    let mut out = String::new();
+    let _ = write!(out, "{}(", args.sysno());
+    for (idx, &kind) in signature(args.sysno()).iter().enumerate() {
+        if idx > 0 {
+            out.push_str(", ");
+        }
+        let _ = format_arg(&mut out, kind, args.arg(idx));
+    }
+    out.push(')');
+    out
+}
+
+fn format_ret(res: &LinuxResult<isize>) -> String {
+    let mut out = String::new();
+    let _ = match res {
+        Ok(ret) => write!(out, "{}", ret),
+        Err(e) => write!(out, "-1 {:?}", e),
+    };
+    out
+}
+
+/// Calls the handler, and traces the syscall if enabled.
+pub(crate) fn call(args: &SyscallArgs, handler: SyscallHandler) -> isize {
+    let res = if TRACE_ENABLED.load(Ordering::Relaxed) {
+        let tid = axtask::current().id().as_u64();
+        let call = format_call(args);
+        // These do not return on success, so show them before they start.
+        let noreturn = matches!(args.sysno(), Sysno::exit | Sysno::exit_group | Sysno::execve);
+        if noreturn {
+            ax_println!("[{}] {} = ?", tid, call);
+        }
+        let res = handler(args);
+        if noreturn {
+            ax_println!("[{}] <... {} resumed> = {}", tid, args.sysno(), format_ret(&res));
+        } else {
+            ax_println!("[{}] {} = {}", tid, call, format_ret(&res));
+        }
+        res
+    } else {
+        handler(args)
+    };
+    match res {
+        Ok(ret) => ret,
+        Err(e) => -e.code() as isize,
+    }
+}
@@ -0,0 +1,57 @@
+//! Reading the user memory of the syscall arguments passed by pointers.
+
+use alloc::vec::Vec;
+
+use axerrno::{LinuxError, LinuxResult};
+use axhal::mem::PAGE_SIZE_4K;
+
+/// A function that copies the user memory at an address into the buffer, or
+/// fails with `EFAULT` if it is not mapped readable.
+pub type ReadUserFn = fn(usize, &mut [u8]) -> LinuxResult;
+
+static READ_USER_FN: spin::Once<ReadUserFn> = spin::Once::new();
+
+/// Sets the function that reads the user memory of the current task, e.g.,
+/// through the address space of the process it belongs to.
+///
+/// If it is not set, the memory is read directly, which faults in the kernel
+/// on bad pointers.
+pub fn set_read_user_fn(f: ReadUserFn) {
+    READ_USER_FN.call_once(|| f);
+}
+
+fn read_user(uaddr: usize, buf: &mut [u8]) -> LinuxResult {
+    match READ_USER_FN.get() {
+        Some(f) => f(uaddr, buf),
+        None if uaddr == 0 => Err(LinuxError::EFAULT),
+        None => {
+            unsafe {
+                core::ptr::copy_nonoverlapping(uaddr as *const u8, buf.as_mut_ptr(), buf.len())
+            };
+            Ok(())
+        }
+    }
+}
+
+/// Reads the NUL-terminated string at `uaddr`, of at most `max_len` bytes.
+///
+/// It is read page by page, so that the string may end right before an
+/// unmapped page. Returns the bytes without the NUL, and whether the NUL is
+/// found, or the first `max_len` bytes otherwise.
+pub(crate) fn read_user_cstr(uaddr: usize, max_len: usize) -> LinuxResult<(Vec<u8>, bool)> {
+    let mut buf = Vec::new();
+    let mut addr = uaddr;
+    while buf.len() <= max_len {
+        let start = buf.len();
+        let len = (PAGE_SIZE_4K - addr % PAGE_SIZE_4K).min(max_len + 1 - start);
+        buf.resize(start + len, 0);
+        read_user(addr, &mut buf[start..])?;
+        if let Some(pos) = buf[start..].iter().position(|&b| b == 0) {
+            buf.truncate(start + pos);
+            return Ok((buf, true));
+        }
+        addr += len;
+    }
+    buf.truncate(max_len);
+    Ok((buf, false))
+}
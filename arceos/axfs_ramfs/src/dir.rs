@@ -7,6 +7,7 @@ use axfs_vfs::{VfsError, VfsResult};
 use spin::RwLock;
 
 use crate::file::FileNode;
+use crate::symlink::SymlinkNode;
 
 /// The directory node in the RAM filesystem.
 ///
@@ -55,6 +56,48 @@ impl DirNode {
         Ok(())
     }
 
+    /// Creates a symbolic link to `target` with the given name in this
+    /// directory.
+    pub fn create_symlink(&self, name: &str, target: &str) -> VfsResult {
+        self.insert_node(name, Arc::new(SymlinkNode::new(target)))
+    }
+
+    /// Adds a hard link to the file or symbolic link `node` with the given
+    /// name in this directory.
+    pub fn create_link(&self, name: &str, node: &VfsNodeRef) -> VfsResult {
+        let any = node.as_any();
+        if !any.is::<FileNode>() && !any.is::<SymlinkNode>() {
+            return Err(VfsError::OperationNotPermitted);
+        }
+        self.insert_node(name, node.clone())
+    }
+
+    fn insert_node(&self, name: &str, node: VfsNodeRef) -> VfsResult {
+        if name.is_empty() || name == "." || name == ".." {
+            return Err(VfsError::AlreadyExists);
+        }
+        let mut children = self.children.write();
+        if children.contains_key(name) {
+            return Err(VfsError::AlreadyExists);
+        }
+        children.insert(name.into(), node);
+        Ok(())
+    }
+
+    /// Returns the node of a path component, in which a node is created.
+    fn subdir(&self, name: &str) -> VfsResult<VfsNodeRef> {
+        match name {
+            "" | "." => Ok(self.this.upgrade().unwrap()),
+            ".." => self.parent().ok_or(VfsError::NotFound),
+            _ => self
+                .children
+                .read()
+                .get(name)
+                .cloned()
+                .ok_or(VfsError::NotFound),
+        }
+    }
+
     /// Removes a node by the given name in this directory.
     pub fn remove_node(&self, name: &str) -> VfsResult {
         let mut children = self.children.write();
@@ -165,6 +208,26 @@ impl VfsNodeOps for DirNode {
         }
     }
 
+    fn symlink(&self, path: &str, target: &str) -> VfsResult {
+        log::debug!("symlink at ramfs: {} -> {}", path, target);
+        let (name, rest) = split_path(path);
+        if let Some(rest) = rest {
+            self.subdir(name)?.symlink(rest, target)
+        } else {
+            self.create_symlink(name, target)
+        }
+    }
+
+    fn link(&self, path: &str, node: &VfsNodeRef) -> VfsResult {
+        log::debug!("link at ramfs: {}", path);
+        let (name, rest) = split_path(path);
+        if let Some(rest) = rest {
+            self.subdir(name)?.link(rest, node)
+        } else {
+            self.create_link(name, node)
+        }
+    }
+
     fn rename(&self, src_path: &str, dst_path: &str) -> VfsResult {
         log::debug!("rename at ramfs: {} -> {}", src_path, dst_path);
 
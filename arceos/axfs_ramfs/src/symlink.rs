@@ -0,0 +1,37 @@
+use alloc::string::String;
+use axfs_vfs::{impl_vfs_non_dir_default, VfsNodeAttr, VfsNodeOps, VfsResult};
+use axfs_vfs::{VfsNodePerm, VfsNodeType};
+
+/// The symbolic link node in the RAM filesystem.
+///
+/// It implements [`axfs_vfs::VfsNodeOps`].
+pub struct SymlinkNode {
+    target: String,
+}
+
+impl SymlinkNode {
+    pub(super) fn new(target: &str) -> Self {
+        Self {
+            target: target.into(),
+        }
+    }
+}
+
+impl VfsNodeOps for SymlinkNode {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        Ok(VfsNodeAttr::new(
+            VfsNodePerm::from_bits_truncate(0o777),
+            VfsNodeType::SymLink,
+            self.target.len() as _,
+            0,
+        ))
+    }
+
+    fn readlink(&self, buf: &mut [u8]) -> VfsResult<usize> {
+        let len = self.target.len().min(buf.len());
+        buf[..len].copy_from_slice(&self.target.as_bytes()[..len]);
+        Ok(len)
+    }
+
+    impl_vfs_non_dir_default! {}
+}
@@ -134,3 +134,67 @@ fn test_ramfs() {
     assert_eq!(root.remove("./foo"), Ok(()));
     assert!(ramfs.root_dir_node().get_entries().is_empty());
 }
+
+#[test]
+fn test_links() {
+    let ramfs = RamFileSystem::new();
+    let root = ramfs.root_dir();
+    root.create("foo", VfsNodeType::Dir).unwrap();
+    root.create("foo/f1", VfsNodeType::File).unwrap();
+
+    // symbolic links are not followed by lookup
+    root.symlink("foo/link", "../foo/f1").unwrap();
+    let link = root.clone().lookup("foo/link").unwrap();
+    assert_eq!(link.get_attr().unwrap().file_type(), VfsNodeType::SymLink);
+    assert_eq!(link.get_attr().unwrap().size(), 9);
+    let mut buf = [0; 16];
+    assert_eq!(link.readlink(&mut buf), Ok(9));
+    assert_eq!(&buf[..9], b"../foo/f1");
+    assert_eq!(link.readlink(&mut buf[..2]), Ok(2));
+    assert_eq!(&buf[..2], b"..");
+    assert_eq!(
+        root.clone().lookup("foo/link/x").err(),
+        Some(VfsError::NotADirectory)
+    );
+    assert_eq!(
+        root.symlink("foo/link", "f2").err(),
+        Some(VfsError::AlreadyExists)
+    );
+    assert_eq!(
+        root.symlink("bar/link", "f2").err(),
+        Some(VfsError::NotFound)
+    );
+    let f1 = root.clone().lookup("foo/f1").unwrap();
+    assert_eq!(f1.readlink(&mut buf).err(), Some(VfsError::InvalidInput));
+
+    // hard links share the node
+    root.link("f1", &f1).unwrap();
+    assert!(Arc::ptr_eq(&root.clone().lookup("f1").unwrap(), &f1));
+    assert_eq!(f1.write_at(0, b"test").unwrap(), 4);
+    assert_eq!(
+        root.clone()
+            .lookup("f1")
+            .unwrap()
+            .get_attr()
+            .unwrap()
+            .size(),
+        4
+    );
+    assert_eq!(root.link("f1", &f1).err(), Some(VfsError::AlreadyExists));
+    let foo = root.clone().lookup("foo").unwrap();
+    assert_eq!(
+        root.link("foo2", &foo).err(),
+        Some(VfsError::OperationNotPermitted)
+    );
+    assert_eq!(f1.link("x", &f1).err(), Some(VfsError::NotADirectory));
+
+    assert_eq!(root.remove("foo/f1"), Ok(()));
+    assert_eq!(
+        root.clone().lookup("f1").unwrap().read_at(0, &mut buf),
+        Ok(4)
+    );
+    assert_eq!(root.remove("foo/link"), Ok(()));
+    assert_eq!(root.remove("f1"), Ok(()));
+    assert_eq!(root.remove("foo"), Ok(()));
+    assert!(ramfs.root_dir_node().get_entries().is_empty());
+}
@@ -5,6 +5,9 @@
 //! ```
 //! ab -n 5000 -c 20 http://X.X.X.X:5555/
 //! ```
+//!
+//! With the `async` feature, the connections are served by async tasks on the
+//! main thread, instead of one thread per connection.
 
 #![cfg_attr(feature = "axstd", no_std)]
 #![cfg_attr(feature = "axstd", no_main)]
@@ -13,8 +16,11 @@
 #[cfg(feature = "axstd")]
 extern crate axstd as std;
 
+#[cfg(not(feature = "async"))]
 use std::io::{self, prelude::*};
+#[cfg(not(feature = "async"))]
 use std::net::{TcpListener, TcpStream};
+#[cfg(not(feature = "async"))]
 use std::thread;
 
 const LOCAL_IP: &str = "0.0.0.0";
@@ -59,6 +65,7 @@ macro_rules! info {
     };
 }
 
+#[cfg(not(feature = "async"))]
 fn http_server(mut stream: TcpStream) -> io::Result<()> {
     let mut buf = [0u8; 4096];
     let _len = stream.read(&mut buf)?;
@@ -69,6 +76,7 @@ fn http_server(mut stream: TcpStream) -> io::Result<()> {
     Ok(())
 }
 
+#[cfg(not(feature = "async"))]
 fn accept_loop() -> io::Result<()> {
     let listener = TcpListener::bind((LOCAL_IP, LOCAL_PORT))?;
     println!("listen on: http://{}/", listener.local_addr().unwrap());
@@ -89,8 +97,50 @@ fn accept_loop() -> io::Result<()> {
     }
 }
 
+#[cfg(feature = "async")]
+mod async_server {
+    use std::io;
+    use std::net::{IpAddr, SocketAddr};
+    use std::rt::{self, net::TcpSocket};
+
+    use super::{CONTENT, LOCAL_IP, LOCAL_PORT};
+
+    async fn http_server(stream: TcpSocket) -> io::Result<()> {
+        let mut buf = [0u8; 4096];
+        let _len = stream.recv(&mut buf).await?;
+
+        let response = format!(header!(), CONTENT.len(), CONTENT);
+        stream.send_all(response.as_bytes()).await?;
+
+        Ok(())
+    }
+
+    pub async fn accept_loop() -> io::Result<()> {
+        let listener = TcpSocket::new();
+        listener.bind(SocketAddr::new(LOCAL_IP.parse::<IpAddr>().unwrap(), LOCAL_PORT))?;
+        listener.listen()?;
+        println!("listen on: http://{}/", listener.local_addr().unwrap());
+
+        let mut i = 0;
+        loop {
+            let stream = listener.accept().await?;
+            info!("new client {}: {}", i, stream.peer_addr().unwrap());
+            rt::spawn(async move {
+                match http_server(stream).await {
+                    Err(e) => info!("client connection error: {:?}", e),
+                    Ok(()) => info!("client {} closed successfully", i),
+                }
+            });
+            i += 1;
+        }
+    }
+}
+
 #[cfg_attr(feature = "axstd", no_mangle)]
 fn main() {
     println!("Hello, ArceOS HTTP server!");
+    #[cfg(not(feature = "async"))]
     accept_loop().expect("test HTTP server failed");
+    #[cfg(feature = "async")]
+    std::rt::block_on(async_server::accept_loop()).expect("test HTTP server failed");
 }
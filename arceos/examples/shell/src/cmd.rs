@@ -5,6 +5,11 @@ use std::{string::String, vec::Vec};
 #[cfg(all(not(feature = "axstd"), unix))]
 use std::os::unix::fs::{FileTypeExt, PermissionsExt};
 
+#[cfg(feature = "axstd")]
+use std::os::arceos::{api::task as api, modules::axtask::SchedPolicy};
+#[cfg(feature = "axstd")]
+use std::{format, string::ToString};
+
 macro_rules! print_err {
     ($cmd: literal, $msg: expr) => {
         println!("{}: {}", $cmd, $msg);
@@ -22,10 +27,16 @@ const CMD_TABLE: &[(&str, CmdHandler)] = &[
     ("echo", do_echo),
     ("exit", do_exit),
     ("help", do_help),
+    #[cfg(feature = "axstd")]
+    ("kill", do_kill),
     ("ls", do_ls),
     ("mkdir", do_mkdir),
+    #[cfg(feature = "axstd")]
+    ("ps", do_ps),
     ("pwd", do_pwd),
     ("rm", do_rm),
+    #[cfg(feature = "axstd")]
+    ("top", do_top),
     ("uname", do_uname),
 ];
 
@@ -260,6 +271,114 @@ fn do_uname(_args: &str) {
     );
 }
 
+#[cfg(feature = "axstd")]
+fn policy_to_string(policy: SchedPolicy) -> String {
+    match policy {
+        SchedPolicy::Normal => "normal".into(),
+        SchedPolicy::Fifo(prio) => format!("fifo:{}", prio),
+        SchedPolicy::RoundRobin(prio) => format!("rr:{}", prio),
+        SchedPolicy::Deadline { runtime, period } => {
+            format!("dl:{}/{}", runtime.as_micros(), period.as_micros())
+        }
+    }
+}
+
+#[cfg(feature = "axstd")]
+fn do_ps(_args: &str) {
+    println!(
+        "{:>5} {:>3} {:<8} {:<10} {:>9} {:>9} {:>7} {:>7} {:>15} NAME",
+        "TID", "CPU", "STATE", "POLICY", "USER(ms)", "SYS(ms)", "VCSW", "IVCSW", "STACK"
+    );
+    for info in api::ax_task_list() {
+        let state = format!("{:?}", info.state);
+        let stack = format!("{}/{}", info.stack_high_water, info.stack_size);
+        println!(
+            "{:>5} {:>3} {:<8} {:<10} {:>9} {:>9} {:>7} {:>7} {:>15} {}{}",
+            info.id,
+            info.cpu_id,
+            state,
+            policy_to_string(info.policy),
+            info.user_time.as_millis(),
+            info.kernel_time.as_millis(),
+            info.voluntary_switches,
+            info.involuntary_switches,
+            stack,
+            info.name,
+            if info.in_wait_queue { " (waiting)" } else { "" },
+        );
+    }
+}
+
+#[cfg(feature = "axstd")]
+fn do_top(args: &str) {
+    let rounds = match args {
+        "" => 1,
+        _ => match args.parse::<usize>() {
+            Ok(n) => n,
+            Err(e) => {
+                print_err!("top", args, e);
+                return;
+            }
+        },
+    };
+    for i in 0..rounds {
+        if i > 0 {
+            println!();
+        }
+        let start = std::time::Instant::now();
+        let before = api::ax_task_list();
+        std::thread::sleep(core::time::Duration::from_secs(1));
+        let after = api::ax_task_list();
+        let elapsed = start.elapsed().as_nanos().max(1);
+
+        // CPU usage in the last second, of the tasks alive all the time.
+        let mut usage = after
+            .iter()
+            .filter_map(|info| {
+                let prev = before.iter().find(|prev| prev.id == info.id)?;
+                let time = (info.user_time + info.kernel_time)
+                    .saturating_sub(prev.user_time + prev.kernel_time);
+                Some((time.as_nanos() * 1000 / elapsed, info))
+            })
+            .collect::<Vec<_>>();
+        usage.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.id.cmp(&b.1.id)));
+
+        println!("{:>5} {:>3} {:<8} {:>6} NAME", "TID", "CPU", "STATE", "%CPU");
+        for (permille, info) in usage {
+            let state = format!("{:?}", info.state);
+            println!(
+                "{:>5} {:>3} {:<8} {:>4}.{} {}",
+                info.id,
+                info.cpu_id,
+                state,
+                permille / 10,
+                permille % 10,
+                info.name
+            );
+        }
+    }
+}
+
+#[cfg(feature = "axstd")]
+fn do_kill(args: &str) {
+    // As a shell reports a task killed by `SIGKILL`.
+    const KILL_EXIT_CODE: i32 = 128 + 9;
+
+    if args.is_empty() {
+        print_err!("kill", "missing task ID");
+        return;
+    }
+    for arg in args.split_whitespace() {
+        let res = match arg.parse::<u64>() {
+            Ok(id) => api::ax_kill_task(id, KILL_EXIT_CODE).map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+        if let Err(e) = res {
+            print_err!("kill", arg, e);
+        }
+    }
+}
+
 fn do_help(_args: &str) {
     println!("Available commands:");
     for (name, _) in CMD_TABLE {
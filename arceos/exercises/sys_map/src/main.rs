@@ -21,13 +21,19 @@ use alloc::sync::Arc;
 use alloc::string::String;
 use alloc::collections::BTreeMap;
 use axmm::AddrSpace;
+use axhal::trap::{register_trap_handler, PAGE_FAULT};
+use axtask::TaskExtRef;
 use loader::load_user_app;
 
 const USER_STACK_SIZE: usize = 0x10000;
 const KERNEL_STACK_SIZE: usize = 0x40000; // 256 KiB
+const USER_ASPACE_END: usize = 0x40_0000_0000;
 
 #[cfg_attr(feature = "axstd", no_mangle)]
 fn main() {
+    // Show the syscalls of the app as they are handled.
+    axsyscall::set_trace(true);
+
     // A new address space for user app.
     let mut uspace = axmm::new_user_aspace().unwrap();
 
@@ -80,3 +86,20 @@ fn init_user_stack(uspace: &mut AddrSpace, populating: bool) -> io::Result<VirtA
 
     Ok(ustack_pointer.into())
 }
+
+#[register_trap_handler(PAGE_FAULT)]
+fn handle_page_fault(vaddr: VirtAddr, access_flags: MappingFlags, is_user: bool) -> bool {
+    // The kernel may also fault on the lazily mapped user memory in syscalls.
+    if !is_user && vaddr.as_usize() >= USER_ASPACE_END {
+        return false;
+    }
+    let curr = axtask::current();
+    if curr.task_ext().aspace.lock().handle_page_fault(vaddr, access_flags) {
+        return true;
+    }
+    if is_user {
+        ax_println!("{}: segmentation fault at {:#x}, exit!", curr.id_name(), vaddr);
+        axtask::exit(-1);
+    }
+    false
+}
@@ -1,49 +1,12 @@
-#![allow(dead_code)]
-
+use alloc::sync::Arc;
 use arceos_posix_api as api;
-use axerrno::LinuxError;
+use axerrno::{LinuxError, LinuxResult};
 use axhal::arch::TrapFrame;
 use axhal::paging::MappingFlags;
 use axhal::trap::{register_trap_handler, SYSCALL};
 use axtask::{current, TaskExtRef};
-use core::ffi::{c_char, c_int, c_void};
 use memory_addr::{AddrRange, MemoryAddr, VirtAddr};
 
-const SYS_IOCTL: usize = 29;
-const SYS_OPENAT: usize = 56;
-const SYS_CLOSE: usize = 57;
-const SYS_READ: usize = 63;
-const SYS_WRITE: usize = 64;
-const SYS_WRITEV: usize = 66;
-const SYS_EXIT: usize = 93;
-const SYS_EXIT_GROUP: usize = 94;
-const SYS_SET_TID_ADDRESS: usize = 96;
-const SYS_MMAP: usize = 222;
-
-const AT_FDCWD: i32 = -100;
-
-/// Macro to generate syscall body
-///
-/// It will receive a function which return Result<_, LinuxError> and convert it to
-/// the type which is specified by the caller.
-#[macro_export]
-macro_rules! syscall_body {
-    ($fn: ident, $($stmt: tt)*) => {{
-        #[allow(clippy::redundant_closure_call)]
-        let res = (|| -> axerrno::LinuxResult<_> { $($stmt)* })();
-        match res {
-            Ok(_) | Err(axerrno::LinuxError::EAGAIN) => debug!(concat!(stringify!($fn), " => {:?}"),  res),
-            Err(_) => info!(concat!(stringify!($fn), " => {:?}"), res),
-        }
-        match res {
-            Ok(v) => v as _,
-            Err(e) => {
-                -e.code() as _
-            }
-        }
-    }};
-}
-
 bitflags::bitflags! {
     #[derive(Clone, Copy, Debug)]
     /// permissions for sys_mmap
@@ -96,46 +59,28 @@ bitflags::bitflags! {
     }
 }
 
+// The other syscalls of the app are handled by the built-in handlers.
+axsyscall::register_syscalls! {
+    set_tid_address => |args| Ok(sys_set_tid_address(args.ptr(0))),
+    mmap => |args| {
+        sys_mmap(
+            args.ptr(0),
+            args.arg(1),
+            args.int(2),
+            args.int(3),
+            args.int(4),
+            args.arg(5) as isize,
+        )
+    },
+    munmap => |args| sys_munmap(args.ptr(0), args.arg(1)),
+    msync => |args| sys_msync(args.ptr(0), args.arg(1), args.int(2)),
+}
+
 #[register_trap_handler(SYSCALL)]
 fn handle_syscall(tf: &TrapFrame, syscall_num: usize) -> isize {
-    ax_println!("handle_syscall [{}] ...", syscall_num);
-    match syscall_num {
-        SYS_IOCTL => sys_ioctl(tf.arg0() as _, tf.arg1() as _, tf.arg2() as _) as _,
-        SYS_SET_TID_ADDRESS => sys_set_tid_address(tf.arg0() as _),
-        SYS_OPENAT => sys_openat(
-            tf.arg0() as _,
-            tf.arg1() as _,
-            tf.arg2() as _,
-            tf.arg3() as _,
-        ),
-        SYS_CLOSE => sys_close(tf.arg0() as _),
-        SYS_READ => sys_read(tf.arg0() as _, tf.arg1() as _, tf.arg2() as _),
-        SYS_WRITE => sys_write(tf.arg0() as _, tf.arg1() as _, tf.arg2() as _),
-        SYS_WRITEV => sys_writev(tf.arg0() as _, tf.arg1() as _, tf.arg2() as _),
-        SYS_EXIT_GROUP => {
-            ax_println!("[SYS_EXIT_GROUP]: system is exiting ..");
-            axtask::exit(tf.arg0() as _)
-        },
-        SYS_EXIT => {
-            ax_println!("[SYS_EXIT]: system is exiting ..");
-            axtask::exit(tf.arg0() as _)
-        },
-        SYS_MMAP => sys_mmap(
-            tf.arg0() as _,
-            tf.arg1() as _,
-            tf.arg2() as _,
-            tf.arg3() as _,
-            tf.arg4() as _,
-            tf.arg5() as _,
-        ),
-        _ => {
-            ax_println!("Unimplemented syscall: {}", syscall_num);
-            -LinuxError::ENOSYS.code() as _
-        },
-    }
+    axsyscall::handle_syscall(tf, syscall_num)
 }
 
-#[allow(unused_variables)]
 fn sys_mmap(
     addr: *mut usize,
     length: usize,
@@ -143,87 +88,85 @@ fn sys_mmap(
     flags: i32,
     fd: i32,
     offset: isize,
-) -> isize {
-    syscall_body!(sys_mmap, {
-        use LinuxError::*;
-
-        let addr = VirtAddr::from_mut_ptr_of(addr);
-        if !addr.is_aligned_4k() || length == 0 {
-            return Err(EINVAL);
-        }
-        let Some(prot) = MmapProt::from_bits(prot) else {
-            return Err::<isize, _>(EINVAL);
-        };
-        let Some(flags) = MmapFlags::from_bits(flags) else {
-            return Err(EINVAL);
-        };
-        if prot.contains(MmapProt::PROT_WRITE) {
-            panic!("PROT_WRITE is not supported yet");
-        }
-        if flags != MmapFlags::MAP_PRIVATE {
-            panic!("only MAP_PRIVATE is supported for now");
-        }
-        if offset != 0 {
-            panic!("`offset` is not supported yet");
-        }
+) -> LinuxResult<isize> {
+    use LinuxError::*;
 
-        let task = axtask::current();
-        let task_ext = task.task_ext();
-        let mut uspace = task_ext.aspace.lock();
-        let addr = uspace
+    let addr = VirtAddr::from_mut_ptr_of(addr);
+    if !addr.is_aligned_4k() || length == 0 {
+        return Err(EINVAL);
+    }
+    if offset < 0 || !(offset as usize).is_aligned_4k() {
+        return Err(EINVAL);
+    }
+    let Some(prot) = MmapProt::from_bits(prot) else {
+        return Err(EINVAL);
+    };
+    let Some(flags) = MmapFlags::from_bits(flags) else {
+        return Err(EINVAL);
+    };
+    let shared = flags.contains(MmapFlags::MAP_SHARED);
+    if shared == flags.contains(MmapFlags::MAP_PRIVATE) {
+        return Err(EINVAL);
+    }
+    let length = length.align_up_4k();
+
+    let task = axtask::current();
+    let task_ext = task.task_ext();
+    let mut uspace = task_ext.aspace.lock();
+    let addr = if flags.contains(MmapFlags::MAP_FIXED) {
+        uspace.unmap(addr, length)?;
+        addr
+    } else {
+        uspace
             .find_free_area(
                 addr,
                 length,
                 AddrRange::new(addr, VirtAddr::from(0x40_0000_0000)),
             )
-            .ok_or(ENOMEM)?;
-        uspace.map_alloc(
+            .ok_or(ENOMEM)?
+    };
+
+    if flags.contains(MmapFlags::MAP_ANONYMOUS) {
+        uspace.map_alloc(addr, length, MappingFlags::from(prot), false)?;
+    } else {
+        // The file content is loaded on page faults, rather than up front.
+        let file = Arc::new(api::get_fs_file(fd)?);
+        uspace.map_file(
             addr,
-            length.align_up_4k(),
-            MappingFlags::from(prot) | MappingFlags::WRITE,
-            true,
+            length,
+            MappingFlags::from(prot),
+            file,
+            offset as usize,
+            shared,
         )?;
+    }
 
-        let file = api::get_file_like(fd)?;
-        let mut buf = [0u8; 64];
-        let mut ptr = addr.as_mut_ptr();
-        loop {
-            let n = file.read(&mut buf)?;
-            unsafe {
-                core::ptr::copy_nonoverlapping(buf.as_ptr(), ptr, n);
-            }
-            if n == 0 {
-                break;
-            }
-            ptr = ptr.wrapping_add(n);
-        }
-
-        uspace.protect(addr, length.align_up_4k(), MappingFlags::from(prot))?;
-        drop(uspace);
-
-        Ok(addr.as_usize() as isize)
-    })
-}
-
-fn sys_openat(dfd: c_int, fname: *const c_char, flags: c_int, mode: api::ctypes::mode_t) -> isize {
-    assert_eq!(dfd, AT_FDCWD);
-    api::sys_open(fname, flags, mode) as isize
+    Ok(addr.as_usize() as isize)
 }
 
-fn sys_close(fd: i32) -> isize {
-    api::sys_close(fd) as isize
-}
+fn sys_munmap(addr: *mut usize, length: usize) -> LinuxResult<isize> {
+    let addr = VirtAddr::from_mut_ptr_of(addr);
+    if !addr.is_aligned_4k() || length == 0 {
+        return Err(LinuxError::EINVAL);
+    }
 
-fn sys_read(fd: i32, buf: *mut c_void, count: usize) -> isize {
-    api::sys_read(fd, buf, count)
+    // Shared file mappings are written back to the files on unmapping.
+    let task = axtask::current();
+    let mut uspace = task.task_ext().aspace.lock();
+    uspace.unmap(addr, length.align_up_4k())?;
+    Ok(0)
 }
 
-fn sys_write(fd: i32, buf: *const c_void, count: usize) -> isize {
-    api::sys_write(fd, buf, count)
-}
+fn sys_msync(addr: *mut usize, length: usize, _flags: i32) -> LinuxResult<isize> {
+    let addr = VirtAddr::from_mut_ptr_of(addr);
+    if !addr.is_aligned_4k() {
+        return Err(LinuxError::EINVAL);
+    }
 
-fn sys_writev(fd: i32, iov: *const api::ctypes::iovec, iocnt: i32) -> isize {
-    unsafe { api::sys_writev(fd, iov, iocnt) }
+    let task = axtask::current();
+    let mut uspace = task.task_ext().aspace.lock();
+    uspace.msync(addr, length.align_up_4k())?;
+    Ok(0)
 }
 
 fn sys_set_tid_address(tid_ptd: *const i32) -> isize {
@@ -231,8 +174,3 @@ fn sys_set_tid_address(tid_ptd: *const i32) -> isize {
     curr.task_ext().set_clear_child_tid(tid_ptd as _);
     curr.id().as_u64() as isize
 }
-
-fn sys_ioctl(_fd: i32, _op: usize, _argp: *mut c_void) -> i32 {
-    ax_println!("Ignore SYS_IOCTL");
-    0
-}
@@ -0,0 +1,278 @@
+//! The executor, which runs the async tasks on the kernel tasks calling
+//! [`block_on`].
+
+use alloc::{boxed::Box, collections::VecDeque, sync::Arc, task::Wake};
+use core::cell::UnsafeCell;
+use core::future::{poll_fn, Future};
+use core::pin::{pin, Pin};
+use core::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use core::task::{Context, Poll, Waker};
+use core::time::Duration;
+
+use axtask::{AxTaskRef, WaitQueue};
+use kspin::SpinNoIrq;
+
+/// The most async tasks to run in a row, before the main future, timers and
+/// network interfaces are checked again.
+const RUN_BUDGET: usize = 64;
+
+/// How often the network interfaces are polled while some tasks wait for
+/// network I/O, as the devices do not raise interrupts to the network stack.
+const NET_POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+const STATE_IDLE: u8 = 0;
+const STATE_SCHEDULED: u8 = 1;
+const STATE_RUNNING: u8 = 2;
+const STATE_NOTIFIED: u8 = 3; // woken up while running
+const STATE_COMPLETE: u8 = 4;
+
+type BoxFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// Async tasks woken up and waiting to be polled.
+static READY_QUEUE: SpinNoIrq<VecDeque<Arc<Task>>> = SpinNoIrq::new(VecDeque::new());
+
+/// Kernel tasks in [`block_on`] with nothing to run.
+static EXEC_WQ: WaitQueue = WaitQueue::new();
+
+struct Task {
+    state: AtomicU8,
+    future: UnsafeCell<Option<BoxFuture>>,
+}
+
+// SAFETY: `future` is only accessed by the kernel task that changed the state
+// from `STATE_SCHEDULED` to `STATE_RUNNING`.
+unsafe impl Sync for Task {}
+
+impl Task {
+    fn schedule(self: Arc<Self>) {
+        READY_QUEUE.lock().push_back(self);
+        EXEC_WQ.notify_one(false);
+    }
+
+    fn run(self: Arc<Self>) {
+        // Tasks in the ready queue are always in `STATE_SCHEDULED`.
+        self.state.store(STATE_RUNNING, Ordering::Release);
+        // SAFETY: see above.
+        let future = unsafe { &mut *self.future.get() };
+        let Some(fut) = future.as_mut() else {
+            return;
+        };
+        let waker = Waker::from(self.clone());
+        let mut cx = Context::from_waker(&waker);
+        if fut.as_mut().poll(&mut cx).is_ready() {
+            *future = None;
+            self.state.store(STATE_COMPLETE, Ordering::Release);
+        } else if self
+            .state
+            .compare_exchange(
+                STATE_RUNNING,
+                STATE_IDLE,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            )
+            .is_err()
+        {
+            // Woken up while polling, run it again later.
+            self.state.store(STATE_SCHEDULED, Ordering::Release);
+            self.schedule();
+        }
+    }
+}
+
+impl Wake for Task {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        let mut state = self.state.load(Ordering::Acquire);
+        loop {
+            let new = match state {
+                STATE_IDLE => STATE_SCHEDULED,
+                STATE_RUNNING => STATE_NOTIFIED,
+                _ => return,
+            };
+            match self
+                .state
+                .compare_exchange_weak(state, new, Ordering::AcqRel, Ordering::Acquire)
+            {
+                Ok(_) => {
+                    if new == STATE_SCHEDULED {
+                        self.clone().schedule();
+                    }
+                    return;
+                }
+                Err(s) => state = s,
+            }
+        }
+    }
+}
+
+/// Wakes up the kernel task running [`block_on`].
+struct MainWaker {
+    task: AxTaskRef,
+    woken: AtomicBool,
+}
+
+impl Wake for MainWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.woken.store(true, Ordering::Release);
+        EXEC_WQ.notify_task(false, &self.task);
+    }
+}
+
+struct JoinState<T> {
+    output: Option<T>,
+    waker: Option<Waker>,
+    finished: bool,
+}
+
+/// An owned permission to wait for an async task to finish, returned by
+/// [`spawn`].
+///
+/// Awaiting it returns the output of the task. The task is detached when it
+/// is dropped.
+pub struct JoinHandle<T> {
+    state: Arc<SpinNoIrq<JoinState<T>>>,
+}
+
+impl<T> JoinHandle<T> {
+    /// Whether the task has finished.
+    pub fn is_finished(&self) -> bool {
+        self.state.lock().finished
+    }
+}
+
+impl<T> Future for JoinHandle<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        let mut state = self.state.lock();
+        if let Some(output) = state.output.take() {
+            Poll::Ready(output)
+        } else {
+            assert!(!state.finished, "`JoinHandle` polled after completion");
+            state.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+/// Spawns an async task, which is run by any kernel task in [`block_on`].
+pub fn spawn<F>(future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    let state = Arc::new(SpinNoIrq::new(JoinState {
+        output: None,
+        waker: None,
+        finished: false,
+    }));
+    let join_state = state.clone();
+    let future = async move {
+        let output = future.await;
+        let waker = {
+            let mut state = join_state.lock();
+            state.output = Some(output);
+            state.finished = true;
+            state.waker.take()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    };
+    let task = Arc::new(Task {
+        state: AtomicU8::new(STATE_SCHEDULED),
+        future: UnsafeCell::new(Some(Box::pin(future))),
+    });
+    task.schedule();
+    JoinHandle { state }
+}
+
+/// Runs the ready async tasks, returns whether any task was run.
+fn run_ready_tasks() -> bool {
+    for i in 0..RUN_BUDGET {
+        let Some(task) = READY_QUEUE.lock().pop_front() else {
+            return i > 0;
+        };
+        task.run();
+    }
+    true
+}
+
+/// Blocks the current kernel task until there is something to run, or the
+/// network interfaces should be polled again if `polling`.
+fn park(main: &MainWaker, polling: bool) {
+    let ready = || main.woken.load(Ordering::Acquire) || !READY_QUEUE.lock().is_empty();
+    let mut deadline = crate::timer::next_deadline();
+    if polling {
+        let poll_at = axhal::time::wall_time() + NET_POLL_INTERVAL;
+        deadline = Some(deadline.map_or(poll_at, |d| d.min(poll_at)));
+    }
+    match deadline {
+        None => EXEC_WQ.wait_until(ready),
+        #[cfg(feature = "irq")]
+        Some(deadline) => {
+            let now = axhal::time::wall_time();
+            if deadline > now {
+                EXEC_WQ.wait_timeout_until(deadline - now, ready);
+            }
+        }
+        // No timer interrupts to wake us up.
+        #[cfg(not(feature = "irq"))]
+        Some(_) => axtask::yield_now(),
+    }
+}
+
+/// Runs a future to completion on the current kernel task.
+///
+/// The async tasks created by [`spawn`] are run here too, while the future is
+/// pending. If several kernel tasks call it, they share the async tasks.
+///
+/// It must not be called in async code, or the caller is blocked.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let main = Arc::new(MainWaker {
+        task: axtask::current().as_task_ref().clone(),
+        woken: AtomicBool::new(true),
+    });
+    let waker = Waker::from(main.clone());
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        if main.woken.swap(false, Ordering::AcqRel) {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+        }
+        crate::timer::check_events();
+        let ran = run_ready_tasks();
+        #[cfg(feature = "net")]
+        let polling = crate::net::poll_interfaces();
+        #[cfg(not(feature = "net"))]
+        let polling = false;
+
+        if !ran && !main.woken.load(Ordering::Acquire) {
+            park(&main, polling);
+        }
+    }
+}
+
+/// Yields to other async tasks.
+pub async fn yield_now() {
+    let mut yielded = false;
+    poll_fn(|cx| {
+        if yielded {
+            Poll::Ready(())
+        } else {
+            yielded = true;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    })
+    .await
+}
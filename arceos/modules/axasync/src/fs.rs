@@ -0,0 +1,77 @@
+//! Async file, wrapping that in [`axfs`].
+//!
+//! The filesystems in ArceOS are synchronous, so each operation is done at
+//! once, and then other async tasks are given a chance to run.
+
+use axerrno::AxResult;
+use axfs::api::Metadata;
+use axio::{Read, Seek, SeekFrom, Write};
+
+use crate::yield_now;
+
+/// An async file.
+pub struct File(axfs::api::File);
+
+impl File {
+    /// Attempts to open a file in read-only mode.
+    pub async fn open(path: &str) -> AxResult<Self> {
+        let file = axfs::api::File::open(path);
+        yield_now().await;
+        file.map(Self)
+    }
+
+    /// Opens a file in write-only mode, which is created or truncated.
+    pub async fn create(path: &str) -> AxResult<Self> {
+        let file = axfs::api::File::create(path);
+        yield_now().await;
+        file.map(Self)
+    }
+
+    /// Reads some bytes into the buffer, returns the number of bytes read.
+    pub async fn read(&mut self, buf: &mut [u8]) -> AxResult<usize> {
+        let res = self.0.read(buf);
+        yield_now().await;
+        res
+    }
+
+    /// Writes some bytes from the buffer, returns the number of bytes
+    /// written.
+    pub async fn write(&mut self, buf: &[u8]) -> AxResult<usize> {
+        let res = self.0.write(buf);
+        yield_now().await;
+        res
+    }
+
+    /// Writes the entire buffer.
+    pub async fn write_all(&mut self, buf: &[u8]) -> AxResult {
+        let res = self.0.write_all(buf);
+        yield_now().await;
+        res
+    }
+
+    /// Flushes the written data.
+    pub async fn flush(&mut self) -> AxResult {
+        let res = self.0.flush();
+        yield_now().await;
+        res
+    }
+
+    /// Seeks to an offset, returns the new position from the start.
+    pub async fn seek(&mut self, pos: SeekFrom) -> AxResult<u64> {
+        let res = self.0.seek(pos);
+        yield_now().await;
+        res
+    }
+
+    /// Queries metadata about the file.
+    pub fn metadata(&self) -> AxResult<Metadata> {
+        self.0.metadata()
+    }
+}
+
+impl From<axfs::api::File> for File {
+    /// Wraps a file opened by other means, such as with other options.
+    fn from(file: axfs::api::File) -> Self {
+        Self(file)
+    }
+}
@@ -0,0 +1,50 @@
+//! [ArceOS](https://github.com/arceos-org/arceos) async runtime.
+//!
+//! Futures are run by [`block_on`] on the calling task, along with the tasks
+//! [`spawn`]ed by them. Many async tasks can share a few kernel tasks, instead
+//! of one kernel task per job.
+//!
+//! When there is nothing to run, the kernel task blocks in a
+//! [`WaitQueue`](axtask::WaitQueue) until the wakers are called, or the next
+//! timer expires. While some tasks wait for network I/O, it also wakes up
+//! periodically to poll the network interfaces, as the devices do not notify
+//! the stack by interrupts.
+//!
+//! # Organization
+//!
+//! - [`block_on`], [`spawn`], [`yield_now`]: Run the futures.
+//! - [`sleep`], [`sleep_until`], [`timeout`]: Timers.
+//! - [`TcpSocket`], [`UdpSocket`]: Async sockets wrapping those in [axnet].
+//! - [`File`]: An async file wrapping that in [axfs].
+//!
+//! # Cargo Features
+//!
+//! - `irq`: Interrupts are enabled. The kernel task sleeps until the next
+//!   timer expires, or the network interfaces should be polled. Otherwise, it
+//!   keeps yielding while there are timers or pending network I/O.
+//! - `net`: Enable the async sockets.
+//! - `fs`: Enable the async file.
+//!
+//! [axnet]: https://arceos-org.github.io/arceos/axnet/index.html
+//! [axfs]: https://arceos-org.github.io/arceos/axfs/index.html
+
+#![no_std]
+#![feature(doc_auto_cfg)]
+
+extern crate alloc;
+
+mod executor;
+mod timer;
+
+#[cfg(feature = "fs")]
+mod fs;
+#[cfg(feature = "net")]
+mod net;
+
+pub use self::executor::{block_on, spawn, yield_now, JoinHandle};
+pub use self::timer::{sleep, sleep_until, timeout, Sleep, Timeout};
+
+#[cfg(feature = "fs")]
+pub use self::fs::File;
+#[cfg(feature = "net")]
+pub use self::net::{TcpSocket, UdpSocket};
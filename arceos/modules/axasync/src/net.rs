@@ -0,0 +1,256 @@
+//! Async sockets, which register the wakers in the sockets of [`axnet`] and
+//! try the operations without blocking.
+
+use core::future::{poll_fn, Future};
+use core::net::SocketAddr;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use core::task::{Poll, Waker};
+
+use axerrno::{AxError, AxResult};
+
+/// Number of pending socket operations.
+static NET_WAITERS: AtomicUsize = AtomicUsize::new(0);
+
+/// Counts a pending socket operation while it is alive.
+struct NetWaiter;
+
+impl NetWaiter {
+    fn new() -> Self {
+        NET_WAITERS.fetch_add(1, Ordering::AcqRel);
+        Self
+    }
+}
+
+impl Drop for NetWaiter {
+    fn drop(&mut self) {
+        NET_WAITERS.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// Polls the network interfaces if any socket operation is pending, which
+/// wakes up the tasks whose sockets are ready. Returns whether it polled.
+pub(crate) fn poll_interfaces() -> bool {
+    if NET_WAITERS.load(Ordering::Acquire) > 0 {
+        axnet::poll_interfaces();
+        true
+    } else {
+        false
+    }
+}
+
+/// Tries the nonblocking `op` until it does not return
+/// [`WouldBlock`](AxError::WouldBlock), with the waker registered by
+/// `register` before each try.
+fn poll_io<T>(
+    register: impl Fn(&Waker),
+    mut op: impl FnMut() -> AxResult<T>,
+) -> impl Future<Output = AxResult<T>> {
+    let mut waiter = None;
+    poll_fn(move |cx| {
+        register(cx.waker());
+        match op() {
+            Err(AxError::WouldBlock) => {
+                waiter.get_or_insert_with(NetWaiter::new);
+                Poll::Pending
+            }
+            res => Poll::Ready(res),
+        }
+    })
+}
+
+/// An async TCP socket.
+///
+/// Only one task should wait for receiving, and one for sending at a time, as
+/// only the last registered waker is woken up.
+pub struct TcpSocket(axnet::TcpSocket);
+
+impl TcpSocket {
+    /// Creates a new TCP socket.
+    pub fn new() -> Self {
+        Self::from_inner(axnet::TcpSocket::new())
+    }
+
+    fn from_inner(socket: axnet::TcpSocket) -> Self {
+        socket.set_nonblocking(true);
+        Self(socket)
+    }
+
+    /// Returns the local address and port, or
+    /// [`Err(NotConnected)`](AxError::NotConnected) if not connected.
+    pub fn local_addr(&self) -> AxResult<SocketAddr> {
+        self.0.local_addr()
+    }
+
+    /// Returns the remote address and port, or
+    /// [`Err(NotConnected)`](AxError::NotConnected) if not connected.
+    pub fn peer_addr(&self) -> AxResult<SocketAddr> {
+        self.0.peer_addr()
+    }
+
+    /// Binds an unbound socket to the given address and port.
+    pub fn bind(&self, local_addr: SocketAddr) -> AxResult {
+        self.0.bind(local_addr)
+    }
+
+    /// Starts listening on the bound address and port.
+    pub fn listen(&self) -> AxResult {
+        self.0.listen()
+    }
+
+    /// Connects to the given address and port.
+    pub async fn connect(&self, remote_addr: SocketAddr) -> AxResult {
+        match self.0.connect(remote_addr) {
+            Err(AxError::WouldBlock) => {}
+            res => return res,
+        }
+        poll_io(
+            |waker| self.0.register_send_waker(waker),
+            || {
+                if !self.0.poll()?.writable {
+                    Err(AxError::WouldBlock)
+                } else if self.0.peer_addr().is_ok() {
+                    Ok(())
+                } else {
+                    Err(AxError::ConnectionRefused)
+                }
+            },
+        )
+        .await
+    }
+
+    /// Accepts a new connection.
+    pub async fn accept(&self) -> AxResult<TcpSocket> {
+        poll_io(
+            |waker| self.0.register_recv_waker(waker),
+            || self.0.accept(),
+        )
+        .await
+        .map(Self::from_inner)
+    }
+
+    /// Receives data from the socket, returns the number of bytes received.
+    pub async fn recv(&self, buf: &mut [u8]) -> AxResult<usize> {
+        poll_io(
+            |waker| self.0.register_recv_waker(waker),
+            || self.0.recv(buf),
+        )
+        .await
+    }
+
+    /// Transmits data in the given buffer, returns the number of bytes sent.
+    pub async fn send(&self, buf: &[u8]) -> AxResult<usize> {
+        poll_io(
+            |waker| self.0.register_send_waker(waker),
+            || self.0.send(buf),
+        )
+        .await
+    }
+
+    /// Transmits all data in the given buffer.
+    pub async fn send_all(&self, mut buf: &[u8]) -> AxResult {
+        while !buf.is_empty() {
+            match self.send(buf).await? {
+                0 => return Err(AxError::WriteZero),
+                n => buf = &buf[n..],
+            }
+        }
+        Ok(())
+    }
+
+    /// Closes the connection.
+    pub fn shutdown(&self) -> AxResult {
+        self.0.shutdown()
+    }
+}
+
+impl Default for TcpSocket {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// An async UDP socket.
+///
+/// Only one task should wait for receiving, and one for sending at a time, as
+/// only the last registered waker is woken up.
+pub struct UdpSocket(axnet::UdpSocket);
+
+impl UdpSocket {
+    /// Creates a new UDP socket.
+    pub fn new() -> Self {
+        let socket = axnet::UdpSocket::new();
+        socket.set_nonblocking(true);
+        Self(socket)
+    }
+
+    /// Returns the local address and port, or
+    /// [`Err(NotConnected)`](AxError::NotConnected) if not bound.
+    pub fn local_addr(&self) -> AxResult<SocketAddr> {
+        self.0.local_addr()
+    }
+
+    /// Returns the remote address and port, or
+    /// [`Err(NotConnected)`](AxError::NotConnected) if not connected.
+    pub fn peer_addr(&self) -> AxResult<SocketAddr> {
+        self.0.peer_addr()
+    }
+
+    /// Binds an unbound socket to the given address and port.
+    pub fn bind(&self, local_addr: SocketAddr) -> AxResult {
+        self.0.bind(local_addr)
+    }
+
+    /// Sets the default remote address, used by [`send`](Self::send) and
+    /// [`recv`](Self::recv).
+    pub fn connect(&self, addr: SocketAddr) -> AxResult {
+        self.0.connect(addr)
+    }
+
+    /// Sends data to the given address, returns the number of bytes sent.
+    pub async fn send_to(&self, buf: &[u8], remote_addr: SocketAddr) -> AxResult<usize> {
+        poll_io(
+            |waker| self.0.register_send_waker(waker),
+            || self.0.send_to(buf, remote_addr),
+        )
+        .await
+    }
+
+    /// Receives a datagram, returns the number of bytes received and the
+    /// source address.
+    pub async fn recv_from(&self, buf: &mut [u8]) -> AxResult<(usize, SocketAddr)> {
+        poll_io(
+            |waker| self.0.register_recv_waker(waker),
+            || self.0.recv_from(buf),
+        )
+        .await
+    }
+
+    /// Sends data to the connected address.
+    pub async fn send(&self, buf: &[u8]) -> AxResult<usize> {
+        poll_io(
+            |waker| self.0.register_send_waker(waker),
+            || self.0.send(buf),
+        )
+        .await
+    }
+
+    /// Receives a datagram from the connected address.
+    pub async fn recv(&self, buf: &mut [u8]) -> AxResult<usize> {
+        poll_io(
+            |waker| self.0.register_recv_waker(waker),
+            || self.0.recv(buf),
+        )
+        .await
+    }
+
+    /// Closes the socket.
+    pub fn shutdown(&self) -> AxResult {
+        self.0.shutdown()
+    }
+}
+
+impl Default for UdpSocket {
+    fn default() -> Self {
+        Self::new()
+    }
+}
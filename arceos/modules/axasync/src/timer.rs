@@ -0,0 +1,145 @@
+//! Timers of async tasks, checked by the executor.
+
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicU64, Ordering};
+use core::task::{Context, Poll, Waker};
+use core::time::Duration;
+
+use axerrno::{AxError, AxResult};
+use axhal::time::{wall_time, TimeValue};
+use kspin::SpinNoIrq;
+use timer_list::{TimerEvent, TimerList};
+
+static TIMERS: SpinNoIrq<Option<TimerList<WakerEvent>>> = SpinNoIrq::new(None);
+
+static NEXT_TIMER_ID: AtomicU64 = AtomicU64::new(0);
+
+struct WakerEvent {
+    id: u64,
+    waker: Waker,
+}
+
+impl TimerEvent for WakerEvent {
+    fn callback(self, _now: TimeValue) {
+        self.waker.wake();
+    }
+}
+
+/// Wakes up the tasks whose timers have expired.
+pub(crate) fn check_events() {
+    let now = wall_time();
+    loop {
+        // Wake up with the lock released, as the wakers may set timers.
+        let event = TIMERS.lock().as_mut().and_then(|t| t.expire_one(now));
+        match event {
+            Some((_deadline, event)) => event.callback(now),
+            None => break,
+        }
+    }
+}
+
+/// Returns the earliest deadline of the timers.
+pub(crate) fn next_deadline() -> Option<TimeValue> {
+    TIMERS.lock().as_ref()?.next_deadline()
+}
+
+/// A future that completes at a deadline, returned by [`sleep`] and
+/// [`sleep_until`].
+pub struct Sleep {
+    deadline: TimeValue,
+    /// The ID of the timer set, with the waker it wakes up.
+    timer: Option<(u64, Waker)>,
+}
+
+impl Sleep {
+    /// Returns the deadline.
+    pub fn deadline(&self) -> TimeValue {
+        self.deadline
+    }
+
+    fn cancel(&mut self) {
+        if let Some((id, _)) = self.timer.take() {
+            if let Some(timers) = TIMERS.lock().as_mut() {
+                timers.cancel(|e| e.id == id);
+            }
+        }
+    }
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if wall_time() >= self.deadline {
+            self.cancel();
+            return Poll::Ready(());
+        }
+        if !matches!(&self.timer, Some((_, waker)) if waker.will_wake(cx.waker())) {
+            self.cancel();
+            let id = NEXT_TIMER_ID.fetch_add(1, Ordering::Relaxed);
+            let waker = cx.waker().clone();
+            TIMERS.lock().get_or_insert_with(TimerList::new).set(
+                self.deadline,
+                WakerEvent {
+                    id,
+                    waker: waker.clone(),
+                },
+            );
+            self.timer = Some((id, waker));
+        }
+        Poll::Pending
+    }
+}
+
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+/// Waits until `dur` has elapsed.
+pub fn sleep(dur: Duration) -> Sleep {
+    sleep_until(wall_time() + dur)
+}
+
+/// Waits until the `deadline` has reached.
+pub fn sleep_until(deadline: TimeValue) -> Sleep {
+    Sleep {
+        deadline,
+        timer: None,
+    }
+}
+
+/// A future with a time limit, returned by [`timeout`].
+pub struct Timeout<F> {
+    future: F,
+    sleep: Sleep,
+}
+
+impl<F: Future> Future for Timeout<F> {
+    type Output = AxResult<F::Output>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // SAFETY: `future` is never moved out of the pinned `Timeout`.
+        let this = unsafe { self.get_unchecked_mut() };
+        let future = unsafe { Pin::new_unchecked(&mut this.future) };
+        if let Poll::Ready(output) = future.poll(cx) {
+            return Poll::Ready(Ok(output));
+        }
+        Pin::new(&mut this.sleep)
+            .poll(cx)
+            .map(|_| Err(AxError::TimedOut))
+    }
+}
+
+/// Runs the `future` for at most `dur`.
+///
+/// Returns [`Err(TimedOut)`](AxError::TimedOut) if it does not complete in
+/// time.
+pub fn timeout<F: Future>(dur: Duration, future: F) -> Timeout<F> {
+    Timeout {
+        future,
+        sleep: sleep(dur),
+    }
+}
@@ -0,0 +1,126 @@
+use core::sync::atomic::{AtomicUsize, Ordering};
+use core::time::Duration;
+use std::sync::{Mutex, Once};
+
+use axasync::{block_on, sleep, spawn, timeout, yield_now};
+use axerrno::AxError;
+use axhal::time::wall_time;
+
+static INIT: Once = Once::new();
+static SERIAL: Mutex<()> = Mutex::new(());
+
+/// Runs `f` with the timer ticks of the host simulated by a kernel task, so
+/// that the timed waits expire.
+fn with_ticks<T>(f: impl FnOnce() -> T) -> T {
+    #[cfg(feature = "irq")]
+    {
+        use core::sync::atomic::AtomicBool;
+        static STOP: AtomicBool = AtomicBool::new(false);
+
+        STOP.store(false, Ordering::Release);
+        let ticker = axtask::spawn(|| {
+            while !STOP.load(Ordering::Acquire) {
+                axtask::on_timer_tick();
+                axtask::yield_now();
+            }
+        });
+        let res = f();
+        STOP.store(true, Ordering::Release);
+        ticker.join();
+        res
+    }
+    #[cfg(not(feature = "irq"))]
+    f()
+}
+
+#[test]
+fn spawn_and_join() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(axtask::init_scheduler);
+
+    const NUM_TASKS: usize = 100;
+    let sum = block_on(async {
+        let handles: Vec<_> = (0..NUM_TASKS)
+            .map(|i| spawn(async move { i * 2 }))
+            .collect();
+        let mut sum = 0;
+        for handle in handles {
+            sum += handle.await;
+        }
+        sum
+    });
+    assert_eq!(sum, NUM_TASKS * (NUM_TASKS - 1));
+}
+
+#[test]
+fn yield_interleaves() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(axtask::init_scheduler);
+
+    static STEPS: AtomicUsize = AtomicUsize::new(0);
+    block_on(async {
+        let task = |parity| async move {
+            for _ in 0..10 {
+                // The two tasks take turns.
+                assert_eq!(STEPS.fetch_add(1, Ordering::AcqRel) % 2, parity);
+                yield_now().await;
+            }
+        };
+        let a = spawn(task(0));
+        let b = spawn(task(1));
+        a.await;
+        b.await;
+    });
+    assert_eq!(STEPS.load(Ordering::Acquire), 20);
+}
+
+#[test]
+fn sleep_and_timeout() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(axtask::init_scheduler);
+
+    with_ticks(|| {
+        let start = wall_time();
+        block_on(sleep(Duration::from_millis(20)));
+        assert!(wall_time() - start >= Duration::from_millis(20));
+
+        let res = block_on(timeout(
+            Duration::from_millis(10),
+            sleep(Duration::from_secs(10)),
+        ));
+        assert_eq!(res, Err(AxError::TimedOut));
+        assert!(wall_time() - start < Duration::from_secs(10));
+
+        let res = block_on(timeout(Duration::from_secs(10), async { 42 }));
+        assert_eq!(res, Ok(42));
+    });
+}
+
+#[test]
+fn shared_by_kernel_tasks() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(axtask::init_scheduler);
+
+    const NUM_TASKS: usize = 20;
+    static FINISHED: AtomicUsize = AtomicUsize::new(0);
+
+    with_ticks(|| {
+        // The async tasks spawned here may be run by the other kernel task.
+        let helper = axtask::spawn(|| block_on(sleep(Duration::from_millis(50))));
+        block_on(async {
+            let handles: Vec<_> = (0..NUM_TASKS)
+                .map(|_| {
+                    spawn(async {
+                        sleep(Duration::from_millis(1)).await;
+                        FINISHED.fetch_add(1, Ordering::AcqRel);
+                    })
+                })
+                .collect();
+            for handle in handles {
+                handle.await;
+            }
+        });
+        helper.join();
+    });
+    assert_eq!(FINISHED.load(Ordering::Acquire), NUM_TASKS);
+}
@@ -65,8 +65,7 @@ impl<D> AxDeviceContainer<D> {
     }
 
     /// Adds one device into the container.
-    #[allow(dead_code)]
-    pub(crate) fn push(&mut self, dev: D) {
+    pub fn push(&mut self, dev: D) {
         self.0.push(dev);
     }
 }
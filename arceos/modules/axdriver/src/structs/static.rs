@@ -56,9 +56,8 @@ impl<D> AxDeviceContainer<D> {
         Self(Some(dev))
     }
 
-    /// Adds one device into the container.
-    #[allow(dead_code)]
-    pub(crate) fn push(&mut self, dev: D) {
+    /// Adds one device into the container, if it is empty.
+    pub fn push(&mut self, dev: D) {
         if self.0.is_none() {
             self.0 = Some(dev);
         }
@@ -26,5 +26,59 @@ create_test_img() {
 	sudo umount mnt
 }
 
+create_ext4_img() {
+	local name=$1
+	local blkcount=$2
+	local src=$(mktemp -d)
+	for i in $(seq 1 1000); do
+	  echo "Rust is cool!" >>"$src/long.txt"
+	done
+	echo "Rust is cool!" >>"$src/short.txt"
+	mkdir -p "$src/very/long/path"
+	echo "Rust is cool!" >>"$src/very/long/path/test.txt"
+	mkdir -p "$src/very-long-dir-name"
+	echo "Rust is cool!" >>"$src/very-long-dir-name/very-long-file-name.txt"
+	rm -f "$name"
+	mkfs.ext4 -q -b 1024 -L "Test!" -d "$src" "$name" ${blkcount}k
+	rm -rf "$src"
+}
+
+# Features the small image above does not reach: a replayed journal, htree
+# directories, multi-level extent trees and files over 4 GiB.
+create_ext4_fixtures_img() {
+	local name=$1
+	local blkcount=$2
+	local src=$(mktemp -d)
+	mkdir -p "$src/htree"
+	for i in $(seq 1 1000); do
+	  echo "file $i" >"$src/htree/file-$i.txt"
+	done
+	# one extent per written block, 400 extents need two levels of index
+	for i in $(seq 0 399); do
+	  printf "block %03d" $i | dd of="$src/fragmented.bin" bs=1024 seek=$((i * 2)) conv=notrunc status=none
+	done
+	echo "Rust is cool!" >"$src/large.bin"
+	echo "Rust is cool!" | dd of="$src/large.bin" bs=4096 seek=$((1024 * 1024 + 1)) conv=notrunc status=none
+	echo "journal: old" >"$src/journal.txt"
+	rm -f "$name"
+	mkfs.ext4 -q -b 1024 -L "Fixtures" -d "$src" "$name" ${blkcount}k
+	rm -rf "$src"
+	# index the large directory
+	e2fsck -fyD "$name" >/dev/null 2>&1
+	# leave a transaction in the journal that overwrites journal.txt
+	local blk=$(debugfs -R "bmap /journal.txt 0" "$name" 2>/dev/null)
+	local data=$(mktemp)
+	echo "journal: new" >"$data"
+	truncate -s 1024 "$data"
+	debugfs -w -f - "$name" >/dev/null 2>&1 <<EOF
+jo
+jw -b $blk $data
+jc
+EOF
+	rm -f "$data"
+}
+
 create_test_img "$CUR_DIR/fat16.img" 2500 16
 create_test_img "$CUR_DIR/fat32.img" 34000 32
+create_ext4_img "$CUR_DIR/ext4.img" 4096
+create_ext4_fixtures_img "$CUR_DIR/ext4_fixtures.img" 16384
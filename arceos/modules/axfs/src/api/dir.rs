@@ -1,5 +1,5 @@
 use alloc::string::String;
-use axio::Result;
+use axio::{Error, Result};
 use core::fmt;
 
 use super::FileType;
@@ -137,14 +137,21 @@ impl DirBuilder {
         if self.recursive {
             self.create_dir_all(path)
         } else {
-            crate::root::create_dir(None, path)
+            crate::root::create_dir(path)
         }
     }
 
-    fn create_dir_all(&self, _path: &str) -> Result<()> {
-        axerrno::ax_err!(
-            Unsupported,
-            "Recursive directory creation is not supported yet"
-        )
+    fn create_dir_all(&self, path: &str) -> Result<()> {
+        let path = crate::root::absolute_path(path)?;
+        let mut prefix = String::new();
+        for part in path.split('/').filter(|part| !part.is_empty()) {
+            prefix += "/";
+            prefix += part;
+            match crate::root::create_dir(&prefix) {
+                Err(Error::AlreadyExists) if super::metadata(&prefix)?.is_dir() => {}
+                result => result?,
+            }
+        }
+        Ok(())
     }
 }
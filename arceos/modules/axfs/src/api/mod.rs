@@ -5,6 +5,8 @@ mod file;
 
 pub use self::dir::{DirBuilder, DirEntry, ReadDir};
 pub use self::file::{File, FileType, Metadata, OpenOptions, Permissions};
+pub use crate::cache::CacheStats;
+pub use crate::root::{MountFlags, MountInfo, UmountFlags};
 
 use alloc::{string::String, vec::Vec};
 use axio::{self as io, prelude::*};
@@ -59,6 +61,32 @@ pub fn metadata(path: &str) -> io::Result<Metadata> {
     File::open(path)?.metadata()
 }
 
+/// Queries the metadata about a file without following symbolic links.
+pub fn symlink_metadata(path: &str) -> io::Result<Metadata> {
+    crate::root::lookup_nofollow(path)?.get_attr().map(Metadata)
+}
+
+/// Reads a symbolic link, returning the path that it points to.
+pub fn read_link(path: &str) -> io::Result<String> {
+    crate::root::read_link(path)
+}
+
+/// Creates a new symbolic link at `link`, which points to `original`.
+///
+/// `original` is not checked, and a relative one is resolved from the
+/// directory of `link` when the link is followed.
+pub fn symlink(original: &str, link: &str) -> io::Result<()> {
+    crate::root::create_symlink(original, link)
+}
+
+/// Creates a new hard link at `link` to the file at `original`.
+///
+/// Both paths must be in the same mount point, and `original` cannot be a
+/// directory. A symbolic link at `original` is not followed.
+pub fn hard_link(original: &str, link: &str) -> io::Result<()> {
+    crate::root::create_link(original, link)
+}
+
 /// Creates a new, empty directory at the provided path.
 pub fn create_dir(path: &str) -> io::Result<()> {
     DirBuilder::new().create(path)
@@ -72,12 +100,12 @@ pub fn create_dir_all(path: &str) -> io::Result<()> {
 
 /// Removes an empty directory.
 pub fn remove_dir(path: &str) -> io::Result<()> {
-    crate::root::remove_dir(None, path)
+    crate::root::remove_dir(path)
 }
 
 /// Removes a file from the filesystem.
 pub fn remove_file(path: &str) -> io::Result<()> {
-    crate::root::remove_file(None, path)
+    crate::root::remove_file(path)
 }
 
 /// Rename a file or directory to a new name.
@@ -87,3 +115,48 @@ pub fn remove_file(path: &str) -> io::Result<()> {
 pub fn rename(old: &str, new: &str) -> io::Result<()> {
     crate::root::rename(old, new)
 }
+
+/// Mounts a filesystem at the directory `target`.
+///
+/// `fstype` is one of:
+///
+/// - `ext4` or `vfat`: the filesystem on the block device `source`, which is
+///   a name like `vdb` or `/dev/vdb`.
+/// - `auto`: the filesystem on `source`, detected by its content.
+/// - `ramfs` or `tmpfs`: a new memory filesystem, and `source` is only a name.
+///
+/// With [`MountFlags::BIND`], the directory `source` is made visible at
+/// `target` instead, and `fstype` is ignored.
+pub fn mount(source: &str, target: &str, fstype: &str, flags: MountFlags) -> io::Result<()> {
+    crate::root::mount(source, target, fstype, flags)
+}
+
+/// Unmounts the filesystem at `target`.
+///
+/// It fails with [`ResourceBusy`](io::Error::ResourceBusy) if there are
+/// opened files, or other mount points under it, unless
+/// [`UmountFlags::DETACH`] is given.
+pub fn umount(target: &str, flags: UmountFlags) -> io::Result<()> {
+    crate::root::umount(target, flags)
+}
+
+/// Returns the information of all mount points, in the order of mounting.
+pub fn mounts() -> Vec<MountInfo> {
+    crate::root::mounts()
+}
+
+/// Writes all modified file contents and metadata back to the filesystems,
+/// and the modified disk blocks in the buffer cache back to the devices.
+pub fn sync() -> io::Result<()> {
+    crate::root::sync()
+}
+
+/// Returns the statistics of the buffer cache of disk blocks.
+pub fn buffer_cache_stats() -> CacheStats {
+    crate::cache::buffer::stats()
+}
+
+/// Returns the statistics of the page cache of file contents.
+pub fn page_cache_stats() -> CacheStats {
+    crate::cache::page::stats()
+}
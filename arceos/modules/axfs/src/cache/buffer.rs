@@ -0,0 +1,302 @@
+//! The buffer cache of disk blocks, shared by all block devices.
+//!
+//! Blocks are cached in buffers of [`BLOCKS_PER_BUFFER`] contiguous blocks.
+//! The lock of the cache only protects the lookup of buffers: the device I/O
+//! is done under the locks of the buffer and the device, so that accesses to
+//! other buffers are not blocked by it.
+
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+use axdriver::prelude::*;
+use axerrno::{AxError, AxResult};
+use axsync::Mutex;
+
+use super::lru::LruCache;
+use super::CacheStats;
+
+/// The size of blocks in the cache, which is the block size of devices.
+pub const BLOCK_SIZE: usize = 512;
+
+/// The number of contiguous blocks in a buffer.
+const BLOCKS_PER_BUFFER: usize = 8;
+
+const BUFFER_SIZE: usize = BLOCK_SIZE * BLOCKS_PER_BUFFER;
+
+struct Device {
+    dev: Mutex<AxBlockDevice>,
+    num_blocks: u64,
+}
+
+struct BufferData {
+    data: Box<[u8; BUFFER_SIZE]>,
+    /// Bitmap of the blocks read from the device or overwritten.
+    valid: u8,
+    /// Bitmap of the blocks not written back.
+    dirty: u8,
+}
+
+/// The blocks from `index * BLOCKS_PER_BUFFER` of a device.
+struct Buffer {
+    index: u64,
+    data: Mutex<BufferData>,
+}
+
+struct BufferCache {
+    /// Registered devices, indexed by the device ID.
+    devs: Vec<Option<Arc<Device>>>,
+    /// Cached buffers, keyed by the device ID and the buffer index.
+    buffers: LruCache<(usize, u64), Arc<Buffer>>,
+    hits: u64,
+    misses: u64,
+    evictions: u64,
+}
+
+static BUFFER_CACHE: Mutex<BufferCache> = Mutex::new(BufferCache::new(
+    axconfig::FS_BUFFER_CACHE_BLOCKS.div_ceil(BLOCKS_PER_BUFFER),
+));
+
+/// The number of dirty blocks, which are counted under the locks of buffers.
+static DIRTY_BLOCKS: AtomicUsize = AtomicUsize::new(0);
+static WRITEBACKS: AtomicU64 = AtomicU64::new(0);
+
+impl Device {
+    /// The number of blocks of the buffer, which is less than
+    /// [`BLOCKS_PER_BUFFER`] at the end of the device.
+    fn blocks_of(&self, index: u64) -> usize {
+        let start = index * BLOCKS_PER_BUFFER as u64;
+        (self.num_blocks - start).min(BLOCKS_PER_BUFFER as u64) as usize
+    }
+
+    /// Reads the blocks of the buffer that are not valid yet.
+    fn fill(&self, index: u64, buf: &mut BufferData) -> DevResult {
+        let count = self.blocks_of(index);
+        let all = ((1u16 << count) - 1) as u8;
+        if buf.valid & all == all {
+            return Ok(());
+        }
+        let mut data = Box::new([0; BUFFER_SIZE]);
+        let block_id = index * BLOCKS_PER_BUFFER as u64;
+        self.dev
+            .lock()
+            .read_block(block_id, &mut data[..count * BLOCK_SIZE])?;
+        for i in (0..count).filter(|i| buf.valid & (1 << i) == 0) {
+            let range = i * BLOCK_SIZE..(i + 1) * BLOCK_SIZE;
+            buf.data[range.clone()].copy_from_slice(&data[range]);
+        }
+        buf.valid = all;
+        Ok(())
+    }
+
+    /// Writes the dirty blocks of the buffer back, in runs of contiguous
+    /// blocks.
+    fn write_back(&self, index: u64, buf: &mut BufferData) -> DevResult {
+        let mut dev = self.dev.lock();
+        let mut i = 0;
+        while i < BLOCKS_PER_BUFFER {
+            if buf.dirty & (1 << i) == 0 {
+                i += 1;
+                continue;
+            }
+            let start = i;
+            while i < BLOCKS_PER_BUFFER && buf.dirty & (1 << i) != 0 {
+                i += 1;
+            }
+            let block_id = index * BLOCKS_PER_BUFFER as u64 + start as u64;
+            dev.write_block(block_id, &buf.data[start * BLOCK_SIZE..i * BLOCK_SIZE])?;
+            let written = ((1u16 << i) - (1u16 << start)) as u8;
+            buf.dirty &= !written;
+            DIRTY_BLOCKS.fetch_sub(i - start, Ordering::Relaxed);
+            WRITEBACKS.fetch_add((i - start) as u64, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+}
+
+impl BufferCache {
+    const fn new(capacity: usize) -> Self {
+        Self {
+            devs: Vec::new(),
+            buffers: LruCache::new(capacity),
+            hits: 0,
+            misses: 0,
+            evictions: 0,
+        }
+    }
+
+    fn dev(&self, dev_id: usize) -> Arc<Device> {
+        self.devs[dev_id].clone().expect("block device removed")
+    }
+}
+
+/// Returns the device and the buffer of the block, inserting an empty buffer
+/// if not cached.
+///
+/// The least recently used buffers not in use are evicted to make room. Dirty
+/// ones are written back first, without holding the lock of the cache.
+fn get_buffer(dev_id: usize, block_id: u64) -> DevResult<(Arc<Device>, Arc<Buffer>)> {
+    let index = block_id / BLOCKS_PER_BUFFER as u64;
+    loop {
+        let mut cache = BUFFER_CACHE.lock();
+        let dev = cache.dev(dev_id);
+        if let Some(buf) = cache.buffers.get_mut(&(dev_id, index)) {
+            let buf = buf.clone();
+            cache.hits += 1;
+            return Ok((dev, buf));
+        }
+        // Buffers referenced only by the cache are not locked by others.
+        let victim = cache
+            .buffers
+            .find_lru(|_, buf| Arc::strong_count(buf) == 1)
+            .map(|(&key, buf)| (key, buf.clone()));
+        match victim {
+            Some(((victim_dev, _), victim)) if cache.buffers.is_full() => {
+                if victim.data.lock().dirty != 0 {
+                    let victim_dev = cache.dev(victim_dev);
+                    drop(cache);
+                    victim_dev.write_back(victim.index, &mut victim.data.lock())?;
+                    continue;
+                }
+                cache.buffers.remove(&(victim_dev, victim.index));
+                cache.evictions += 1;
+            }
+            // All buffers are in use if the cache is still full.
+            _ => {}
+        }
+        cache.misses += 1;
+        let buf = Arc::new(Buffer {
+            index,
+            data: Mutex::new(BufferData {
+                data: Box::new([0; BUFFER_SIZE]),
+                valid: 0,
+                dirty: 0,
+            }),
+        });
+        cache.buffers.insert((dev_id, index), buf.clone());
+        return Ok((dev, buf));
+    }
+}
+
+/// Writes the dirty blocks of the device, or all devices if `dev_id` is
+/// `None`, back in the order of block IDs.
+fn sync_buffers(dev_id: Option<usize>) -> DevResult {
+    let (devs, buffers) = {
+        let cache = BUFFER_CACHE.lock();
+        let buffers: Vec<_> = cache
+            .buffers
+            .iter()
+            .filter(|(&(id, _), _)| dev_id.map_or(true, |dev_id| dev_id == id))
+            .map(|(&(id, _), buf)| (id, buf.clone()))
+            .collect();
+        (cache.devs.clone(), buffers)
+    };
+    let mut synced = Vec::new();
+    for (id, buf) in buffers {
+        let mut data = buf.data.lock();
+        if data.dirty != 0 {
+            let dev = devs[id].as_ref().expect("block device removed");
+            dev.write_back(buf.index, &mut data)?;
+            if !synced.contains(&id) {
+                synced.push(id);
+            }
+        }
+    }
+    for id in synced {
+        devs[id].as_ref().unwrap().dev.lock().flush()?;
+    }
+    Ok(())
+}
+
+/// Registers a block device to the cache, and returns its ID.
+pub fn add_device(dev: AxBlockDevice) -> usize {
+    let mut cache = BUFFER_CACHE.lock();
+    let num_blocks = dev.num_blocks();
+    cache.devs.push(Some(Arc::new(Device {
+        dev: Mutex::new(dev),
+        num_blocks,
+    })));
+    cache.devs.len() - 1
+}
+
+/// Writes back and drops the cached blocks of the device, and unregisters it.
+/// The device must not be accessed concurrently.
+///
+/// The device is returned even if the writeback fails.
+pub fn remove_device(dev_id: usize) -> (AxBlockDevice, DevResult) {
+    let result = sync_buffers(Some(dev_id));
+    let mut cache = BUFFER_CACHE.lock();
+    cache.buffers.retain(|&(id, _), buf| {
+        if id == dev_id {
+            let dirty = buf.data.lock().dirty.count_ones() as usize;
+            DIRTY_BLOCKS.fetch_sub(dirty, Ordering::Relaxed);
+        }
+        id != dev_id
+    });
+    let dev = cache.devs[dev_id].take().expect("block device removed");
+    let dev = Arc::into_inner(dev).expect("block device in use");
+    (dev.dev.into_inner(), result)
+}
+
+/// Reads the block from `offset` into `buf`, which must not cross the end of
+/// the block.
+pub fn read(dev_id: usize, block_id: u64, offset: usize, buf: &mut [u8]) -> DevResult {
+    let (dev, buffer) = get_buffer(dev_id, block_id)?;
+    let mut data = buffer.data.lock();
+    let i = (block_id % BLOCKS_PER_BUFFER as u64) as usize;
+    if data.valid & (1 << i) == 0 {
+        dev.fill(buffer.index, &mut data)?;
+    }
+    let offset = i * BLOCK_SIZE + offset;
+    buf.copy_from_slice(&data.data[offset..offset + buf.len()]);
+    Ok(())
+}
+
+/// Writes `buf` into the block from `offset`, which must not cross the end of
+/// the block. The block is written back to the device later.
+pub fn write(dev_id: usize, block_id: u64, offset: usize, buf: &[u8]) -> DevResult {
+    let (dev, buffer) = get_buffer(dev_id, block_id)?;
+    let mut data = buffer.data.lock();
+    let i = (block_id % BLOCKS_PER_BUFFER as u64) as usize;
+    // A whole block is overwritten without reading it.
+    if buf.len() == BLOCK_SIZE {
+        data.valid |= 1 << i;
+    } else if data.valid & (1 << i) == 0 {
+        dev.fill(buffer.index, &mut data)?;
+    }
+    let start = i * BLOCK_SIZE + offset;
+    data.data[start..start + buf.len()].copy_from_slice(buf);
+    if data.dirty & (1 << i) == 0 {
+        data.dirty |= 1 << i;
+        DIRTY_BLOCKS.fetch_add(1, Ordering::Relaxed);
+    }
+    Ok(())
+}
+
+/// Writes all dirty blocks back to the devices.
+pub fn sync() -> AxResult {
+    sync_buffers(None).map_err(|e| {
+        warn!("failed to write back the buffer cache: {:?}", e);
+        AxError::Io
+    })
+}
+
+/// Writes the dirty blocks of the device back.
+pub fn sync_device(dev_id: usize) -> DevResult {
+    sync_buffers(Some(dev_id))
+}
+
+/// Returns the statistics, where the entries are blocks.
+pub fn stats() -> CacheStats {
+    let cache = BUFFER_CACHE.lock();
+    CacheStats {
+        hits: cache.hits,
+        misses: cache.misses,
+        evictions: cache.evictions,
+        writebacks: WRITEBACKS.load(Ordering::Relaxed),
+        cached: cache.buffers.len() * BLOCKS_PER_BUFFER,
+        dirty: DIRTY_BLOCKS.load(Ordering::Relaxed),
+        capacity: cache.buffers.capacity() * BLOCKS_PER_BUFFER,
+    }
+}
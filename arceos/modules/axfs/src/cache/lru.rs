@@ -0,0 +1,107 @@
+//! A map with least-recently-used eviction.
+
+use alloc::collections::BTreeMap;
+
+/// A map ordered by the time of the last access, with a fixed capacity.
+///
+/// It does not evict entries by itself: users check [`is_full`] and take
+/// an entry found by [`find_lru`] out before inserting, so that they can
+/// skip the entries in use or write back the evicted entry first.
+///
+/// [`is_full`]: LruCache::is_full
+/// [`find_lru`]: LruCache::find_lru
+pub struct LruCache<K, V> {
+    capacity: usize,
+    /// Entries with the time of the last access.
+    entries: BTreeMap<K, (u64, V)>,
+    /// Keys ordered by the time of the last access.
+    order: BTreeMap<u64, K>,
+    clock: u64,
+}
+
+impl<K: Ord + Clone, V> LruCache<K, V> {
+    pub const fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: BTreeMap::new(),
+            order: BTreeMap::new(),
+            clock: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.entries.len() >= self.capacity
+    }
+
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+
+    /// Gets the entry and marks it as the most recently used.
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        let now = self.tick();
+        let (time, value) = self.entries.get_mut(key)?;
+        let key = self.order.remove(time).unwrap();
+        self.order.insert(now, key);
+        *time = now;
+        Some(value)
+    }
+
+    /// Gets the entry without changing the order.
+    pub fn peek_mut(&mut self, key: &K) -> Option<&mut V> {
+        self.entries.get_mut(key).map(|(_, value)| value)
+    }
+
+    /// Inserts an entry as the most recently used, replacing the old value of
+    /// the same key.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let now = self.tick();
+        self.order.insert(now, key.clone());
+        let (time, old) = self.entries.insert(key, (now, value))?;
+        self.order.remove(&time);
+        Some(old)
+    }
+
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let (time, value) = self.entries.remove(key)?;
+        self.order.remove(&time);
+        Some(value)
+    }
+
+    /// Returns the least recently used entry for which `f` returns `true`.
+    pub fn find_lru(&self, mut f: impl FnMut(&K, &V) -> bool) -> Option<(&K, &V)> {
+        self.order.values().find_map(|key| {
+            let (k, (_, v)) = self.entries.get_key_value(key)?;
+            f(k, v).then_some((k, v))
+        })
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.entries.iter().map(|(k, (_, v))| (k, v))
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&K, &mut V)> {
+        self.entries.iter_mut().map(|(k, (_, v))| (k, v))
+    }
+
+    /// Retains only the entries for which `f` returns `true`.
+    pub fn retain(&mut self, mut f: impl FnMut(&K, &mut V) -> bool) {
+        let order = &mut self.order;
+        self.entries.retain(|k, (time, v)| {
+            let keep = f(k, v);
+            if !keep {
+                order.remove(time);
+            }
+            keep
+        });
+    }
+}
@@ -0,0 +1,46 @@
+//! Caches between filesystems and block devices.
+//!
+//! - [`buffer`]: disk blocks shared by all block devices, written back to the
+//!   devices on [`sync`](buffer::sync).
+//! - [`page`]: pages of file contents, which are also mapped to user space.
+//!
+//! The sizes of the caches are set by `fs-buffer-cache-blocks` and
+//! `fs-page-cache-pages` in `axconfig`.
+
+mod lru;
+
+pub mod buffer;
+pub mod page;
+
+/// Statistics of a cache.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CacheStats {
+    /// Number of lookups found in the cache.
+    pub hits: u64,
+    /// Number of lookups not found in the cache.
+    pub misses: u64,
+    /// Number of entries evicted to make room for new ones.
+    pub evictions: u64,
+    /// Number of dirty entries written back.
+    pub writebacks: u64,
+    /// Number of entries in the cache.
+    pub cached: usize,
+    /// Number of dirty entries in the cache.
+    pub dirty: usize,
+    /// Maximum number of entries in the cache.
+    pub capacity: usize,
+}
+
+impl CacheStats {
+    const fn new() -> Self {
+        Self {
+            hits: 0,
+            misses: 0,
+            evictions: 0,
+            writebacks: 0,
+            cached: 0,
+            dirty: 0,
+            capacity: 0,
+        }
+    }
+}
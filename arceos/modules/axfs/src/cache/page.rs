@@ -0,0 +1,530 @@
+//! The page cache of file contents.
+//!
+//! Files are identified by their canonical paths when opened, since the
+//! nodes of most filesystems are created on each lookup. All handles of a
+//! file share its node and pages until the path is invalidated by removing,
+//! renaming or recreating the file, after which the old handles keep a
+//! detached copy.
+//!
+//! Writes go through to the filesystem and update the cached pages. Pages
+//! can also be pinned by memory mappings, which access the page frames
+//! directly: such pages are dirty once written through the mappings, and
+//! are written back when synced or unpinned.
+//!
+//! Reads are cached only for files on block devices. Files of other
+//! filesystems are in memory already, and only their mapped pages are kept.
+
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::ptr::NonNull;
+
+use axfs_vfs::{VfsNodeRef, VfsResult};
+use axsync::Mutex;
+
+use super::lru::LruCache;
+use super::CacheStats;
+
+pub const PAGE_SIZE: usize = 4096;
+
+/// A page frame, aligned to be mapped to user space.
+#[repr(C, align(4096))]
+struct Frame([u8; PAGE_SIZE]);
+
+struct Page {
+    frame: Box<Frame>,
+    /// The number of valid bytes, which are before the end of the file.
+    len: usize,
+    /// The number of pins, which keep the page in the cache.
+    pins: usize,
+    /// Whether the page is modified through mappings and not written back.
+    dirty: bool,
+}
+
+struct FileEntry {
+    path: Arc<str>,
+    /// The node shared by all handles.
+    node: VfsNodeRef,
+    /// Whether reads of the file are cached.
+    cache_reads: bool,
+    /// Whether the file is still at `path`.
+    linked: bool,
+    /// The number of open handles.
+    handles: usize,
+    /// The number of cached pages.
+    pages: usize,
+    /// Increased on each modification, to drop pages read concurrently.
+    version: u64,
+}
+
+struct PageCache {
+    /// The IDs of files at each path.
+    ids: BTreeMap<Arc<str>, u64>,
+    files: BTreeMap<u64, FileEntry>,
+    /// Cached pages, keyed by the file ID and the page index.
+    pages: LruCache<(u64, u64), Page>,
+    next_id: u64,
+    stats: CacheStats,
+}
+
+static PAGE_CACHE: Mutex<PageCache> = Mutex::new(PageCache::new(axconfig::FS_PAGE_CACHE_PAGES));
+
+impl Page {
+    fn new() -> Self {
+        Self {
+            frame: Box::new(Frame([0; PAGE_SIZE])),
+            len: 0,
+            pins: 0,
+            dirty: false,
+        }
+    }
+
+    fn data(&self) -> &[u8; PAGE_SIZE] {
+        &self.frame.0
+    }
+
+    fn data_mut(&mut self) -> &mut [u8; PAGE_SIZE] {
+        &mut self.frame.0
+    }
+}
+
+impl PageCache {
+    const fn new(capacity: usize) -> Self {
+        Self {
+            ids: BTreeMap::new(),
+            files: BTreeMap::new(),
+            pages: LruCache::new(capacity),
+            next_id: 0,
+            stats: CacheStats::new(),
+        }
+    }
+
+    /// Drops the entry of the file if it is no longer used.
+    ///
+    /// The pages are pinned and written back by handles, so they are all
+    /// unpinned and clean without handles.
+    fn try_release(&mut self, id: u64) {
+        let file = &self.files[&id];
+        if file.handles == 0 && (file.pages == 0 || !file.linked) {
+            if file.linked {
+                self.ids.remove(&file.path);
+            }
+            self.files.remove(&id);
+            self.pages.retain(|&(file_id, _), _| file_id != id);
+        }
+    }
+
+    fn remove_page(&mut self, key: (u64, u64)) {
+        if self.pages.remove(&key).is_some() {
+            self.files.get_mut(&key.0).unwrap().pages -= 1;
+        }
+    }
+
+    /// Drops the pages of the file that are not pinned.
+    fn remove_unpinned(&mut self, id: u64) {
+        let mut removed = 0;
+        self.pages.retain(|&(file_id, _), page| {
+            let remove = file_id == id && page.pins == 0;
+            removed += remove as usize;
+            !remove
+        });
+        self.files.get_mut(&id).unwrap().pages -= removed;
+    }
+
+    /// Inserts the page, unless it is cached concurrently, which may be
+    /// pinned already.
+    ///
+    /// The least recently used pages are evicted to make room, except pinned
+    /// or dirty ones, which may exceed the capacity.
+    fn insert_page(&mut self, key: (u64, u64), page: Page) {
+        if self.pages.peek_mut(&key).is_some() {
+            return;
+        }
+        while self.pages.is_full() {
+            let Some((&lru, _)) = self.pages.find_lru(|_, page| page.pins == 0 && !page.dirty)
+            else {
+                break;
+            };
+            self.remove_page(lru);
+            self.stats.evictions += 1;
+            self.try_release(lru.0);
+        }
+        self.files.get_mut(&key.0).unwrap().pages += 1;
+        self.pages.insert(key, page);
+    }
+}
+
+/// A handle of a file in the page cache.
+pub struct CachedFile {
+    id: u64,
+}
+
+impl CachedFile {
+    /// Opens the file at the canonical path `path`, of which `node` is looked
+    /// up. Reads are cached if `cache_reads` is set.
+    pub fn open(path: &str, node: VfsNodeRef, cache_reads: bool) -> Self {
+        let mut cache = PAGE_CACHE.lock();
+        let id = match cache.ids.get(path) {
+            Some(&id) => id,
+            None => {
+                let id = cache.next_id;
+                cache.next_id += 1;
+                let path: Arc<str> = path.into();
+                cache.ids.insert(path.clone(), id);
+                let file = FileEntry {
+                    path,
+                    node,
+                    cache_reads,
+                    linked: true,
+                    handles: 0,
+                    pages: 0,
+                    version: 0,
+                };
+                cache.files.insert(id, file);
+                id
+            }
+        };
+        cache.files.get_mut(&id).unwrap().handles += 1;
+        Self { id }
+    }
+
+    /// Returns the node of the file shared by all handles.
+    pub fn node(&self) -> VfsNodeRef {
+        PAGE_CACHE.lock().files[&self.id].node.clone()
+    }
+
+    /// Reads the file through the cache.
+    pub fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let (node, cache_reads, pages) = {
+            let cache = PAGE_CACHE.lock();
+            let file = &cache.files[&self.id];
+            (file.node.clone(), file.cache_reads, file.pages)
+        };
+        if !cache_reads && pages == 0 {
+            return node.read_at(offset, buf);
+        }
+        let size = node.get_attr()?.size();
+        if offset >= size {
+            return Ok(0);
+        }
+        let end = size.min(offset + buf.len() as u64);
+        let mut pos = offset;
+        while pos < end {
+            let index = pos / PAGE_SIZE as u64;
+            let start = (pos % PAGE_SIZE as u64) as usize;
+            let count = (PAGE_SIZE - start).min((end - pos) as usize);
+            let dst = &mut buf[(pos - offset) as usize..][..count];
+            if !self.read_cached(index, start, dst, cache_reads)? {
+                let valid = if cache_reads {
+                    let page = self.fill(&node, index)?;
+                    let valid = page.len.saturating_sub(start).min(count);
+                    dst[..valid].copy_from_slice(&page.data()[start..start + valid]);
+                    valid
+                } else {
+                    node.read_at(pos, dst)?
+                };
+                if valid < count {
+                    // The file is truncated concurrently.
+                    return Ok((pos - offset) as usize + valid);
+                }
+            }
+            pos += count as u64;
+        }
+        Ok((end - offset) as usize)
+    }
+
+    /// Copies the cached page if it contains the range. The page is pinned
+    /// instead of locking the cache during the copy, since `dst` may be
+    /// mapped to a page of the cache not faulted in yet.
+    fn read_cached(
+        &self,
+        index: u64,
+        start: usize,
+        dst: &mut [u8],
+        cache_reads: bool,
+    ) -> VfsResult<bool> {
+        let data = {
+            let mut cache = PAGE_CACHE.lock();
+            match cache.pages.get_mut(&(self.id, index)) {
+                Some(page) if page.len >= start + dst.len() => {
+                    page.pins += 1;
+                    let data = NonNull::from(page.data());
+                    cache.stats.hits += cache_reads as u64;
+                    data
+                }
+                _ => {
+                    cache.stats.misses += cache_reads as u64;
+                    return Ok(false);
+                }
+            }
+        };
+        // SAFETY: the page is pinned.
+        dst.copy_from_slice(unsafe { &data.as_ref()[start..start + dst.len()] });
+        self.put_page(index)?;
+        Ok(true)
+    }
+
+    /// Reads a page from the file, and caches a copy if the file is not
+    /// modified meanwhile, unless a page is cached concurrently.
+    fn fill(&self, node: &VfsNodeRef, index: u64) -> VfsResult<Page> {
+        let version = PAGE_CACHE.lock().files[&self.id].version;
+        let mut page = Page::new();
+        let offset = index * PAGE_SIZE as u64;
+        while page.len < PAGE_SIZE {
+            let len = page.len;
+            match node.read_at(offset + len as u64, &mut page.data_mut()[len..])? {
+                0 => break,
+                n => page.len += n,
+            }
+        }
+        let mut cache = PAGE_CACHE.lock();
+        if cache.files[&self.id].version == version {
+            let mut copy = Page::new();
+            copy.data_mut().copy_from_slice(page.data());
+            copy.len = page.len;
+            cache.insert_page((self.id, index), copy);
+        }
+        Ok(page)
+    }
+
+    /// Writes the file, and updates the cached pages.
+    pub fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        let result = self.node().write_at(offset, buf);
+        let written = *result.as_ref().unwrap_or(&0);
+        let mut cache = PAGE_CACHE.lock();
+        cache.files.get_mut(&self.id).unwrap().version += 1;
+        let end = offset + written as u64;
+        let mut pos = offset;
+        while pos < end {
+            let index = pos / PAGE_SIZE as u64;
+            let start = (pos % PAGE_SIZE as u64) as usize;
+            let count = (PAGE_SIZE - start).min((end - pos) as usize);
+            let key = (self.id, index);
+            if let Some(page) = cache.pages.peek_mut(&key) {
+                if page.len >= start || page.pins > 0 {
+                    // The hole left before the written part reads as zeros.
+                    let len = page.len.min(start);
+                    page.data_mut()[len..start].fill(0);
+                    let src = &buf[(pos - offset) as usize..][..count];
+                    page.data_mut()[start..start + count].copy_from_slice(src);
+                    page.len = page.len.max(start + count);
+                } else {
+                    cache.remove_page(key);
+                }
+            }
+            pos += count as u64;
+        }
+        if result.is_err() {
+            // The file may be partially written.
+            cache.remove_unpinned(self.id);
+        }
+        result
+    }
+
+    /// Truncates the file, and drops the cached content beyond the size.
+    ///
+    /// Pinned pages are kept, with the part beyond the size zeroed.
+    pub fn truncate(&self, size: u64) -> VfsResult {
+        let result = self.node().truncate(size);
+        let mut cache = PAGE_CACHE.lock();
+        cache.files.get_mut(&self.id).unwrap().version += 1;
+        let id = self.id;
+        let mut removed = 0;
+        cache.pages.retain(|&(file_id, index), page| {
+            if file_id != id {
+                return true;
+            }
+            let start = index * PAGE_SIZE as u64;
+            if start >= size && page.pins == 0 {
+                removed += 1;
+                return false;
+            }
+            let len = size.saturating_sub(start).min(PAGE_SIZE as u64) as usize;
+            let (from, to) = (len.min(page.len), len.max(page.len));
+            page.data_mut()[from..to].fill(0);
+            page.len = len;
+            true
+        });
+        cache.files.get_mut(&id).unwrap().pages -= removed;
+        result
+    }
+
+    /// Pins the page at `index`, reading it from the file if not cached, and
+    /// returns its frame, which is valid until unpinned by [`put_page`].
+    ///
+    /// [`put_page`]: CachedFile::put_page
+    pub fn get_page(&self, index: u64) -> VfsResult<NonNull<[u8; PAGE_SIZE]>> {
+        let node = self.node();
+        loop {
+            let mut cache = PAGE_CACHE.lock();
+            if let Some(page) = cache.pages.get_mut(&(self.id, index)) {
+                page.pins += 1;
+                return Ok(NonNull::from(&mut page.frame.0));
+            }
+            drop(cache);
+            // Not cached if the file is modified meanwhile, then read again.
+            self.fill(&node, index)?;
+        }
+    }
+
+    /// Marks the pinned page at `index` as modified through mappings.
+    pub fn set_dirty(&self, index: u64) {
+        let mut cache = PAGE_CACHE.lock();
+        let page = cache.pages.peek_mut(&(self.id, index)).unwrap();
+        debug_assert!(page.pins > 0);
+        if !core::mem::replace(&mut page.dirty, true) {
+            cache.stats.dirty += 1;
+        }
+    }
+
+    /// Unpins the page at `index`, and writes it back if it is dirty and no
+    /// longer pinned.
+    pub fn put_page(&self, index: u64) -> VfsResult {
+        let dirty = {
+            let mut cache = PAGE_CACHE.lock();
+            let page = cache.pages.peek_mut(&(self.id, index)).unwrap();
+            page.pins -= 1;
+            page.pins == 0 && page.dirty
+        };
+        if dirty {
+            self.write_back(|i| i == index)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Writes the dirty pages of the file back, and then the file itself.
+    pub fn sync(&self) -> VfsResult {
+        self.write_back(|_| true)?;
+        self.node().fsync()
+    }
+
+    /// Writes the dirty pages back whose indexes match `filter`.
+    ///
+    /// The pages are pinned during the writes, which are done without holding
+    /// the lock of the cache. They are not extended beyond the end of the
+    /// file.
+    fn write_back(&self, mut filter: impl FnMut(u64) -> bool) -> VfsResult {
+        let (node, pages) = {
+            let mut cache = PAGE_CACHE.lock();
+            let mut pages = Vec::new();
+            for (&(id, index), page) in cache.pages.iter_mut() {
+                if id == self.id && page.dirty && filter(index) {
+                    page.dirty = false;
+                    page.pins += 1;
+                    pages.push((index, NonNull::from(page.data()), page.len));
+                }
+            }
+            cache.stats.dirty -= pages.len();
+            cache.stats.writebacks += pages.len() as u64;
+            (cache.files[&self.id].node.clone(), pages)
+        };
+        let mut result = Ok(());
+        let mut failed = Vec::new();
+        for &(index, data, len) in &pages {
+            // SAFETY: the page is pinned.
+            let data = unsafe { &data.as_ref()[..len] };
+            let offset = index * PAGE_SIZE as u64;
+            let mut written = 0;
+            while written < len && result.is_ok() {
+                match node.write_at(offset + written as u64, &data[written..]) {
+                    Ok(0) => result = Err(axfs_vfs::VfsError::Io),
+                    Ok(n) => written += n,
+                    Err(e) => result = Err(e),
+                }
+            }
+            if written < len {
+                failed.push(index);
+            }
+        }
+        let mut cache = PAGE_CACHE.lock();
+        for (index, ..) in pages {
+            let page = cache.pages.peek_mut(&(self.id, index)).unwrap();
+            page.pins -= 1;
+            if failed.contains(&index) && !core::mem::replace(&mut page.dirty, true) {
+                cache.stats.dirty += 1;
+            }
+        }
+        result
+    }
+}
+
+impl Clone for CachedFile {
+    fn clone(&self) -> Self {
+        PAGE_CACHE.lock().files.get_mut(&self.id).unwrap().handles += 1;
+        Self { id: self.id }
+    }
+}
+
+impl Drop for CachedFile {
+    fn drop(&mut self) {
+        let mut cache = PAGE_CACHE.lock();
+        cache.files.get_mut(&self.id).unwrap().handles -= 1;
+        cache.try_release(self.id);
+    }
+}
+
+/// Detaches the files at the canonical path `path` or under it from the
+/// path, so that files opened later do not see their pages.
+pub fn invalidate(path: &str) {
+    let mut cache = PAGE_CACHE.lock();
+    let under = |p: &str| {
+        p.strip_prefix(path)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/') || path.ends_with('/'))
+    };
+    let ids: Vec<u64> = cache
+        .ids
+        .iter()
+        .filter(|(p, _)| under(p))
+        .map(|(_, &id)| id)
+        .collect();
+    for id in ids {
+        let file = cache.files.get_mut(&id).unwrap();
+        file.linked = false;
+        let path = file.path.clone();
+        cache.ids.remove(&path);
+        cache.try_release(id);
+    }
+}
+
+/// Writes the dirty pages of all open files back, and the metadata of those
+/// on block devices, e.g., the sizes in the directory entries of FAT.
+pub fn sync() -> VfsResult {
+    let files: Vec<CachedFile> = {
+        let mut cache = PAGE_CACHE.lock();
+        let ids: Vec<u64> = cache
+            .files
+            .iter()
+            .filter(|(_, file)| file.handles > 0)
+            .map(|(&id, _)| id)
+            .collect();
+        for id in &ids {
+            cache.files.get_mut(id).unwrap().handles += 1;
+        }
+        ids.into_iter().map(|id| CachedFile { id }).collect()
+    };
+    let mut result = Ok(());
+    for file in files {
+        let (node, on_disk) = {
+            let cache = PAGE_CACHE.lock();
+            let entry = &cache.files[&file.id];
+            (entry.node.clone(), entry.cache_reads)
+        };
+        if let Err(e) = file.write_back(|_| true) {
+            result = Err(e);
+        } else if on_disk {
+            result = result.and(node.fsync());
+        }
+    }
+    result
+}
+
+pub fn stats() -> CacheStats {
+    let cache = PAGE_CACHE.lock();
+    CacheStats {
+        cached: cache.pages.len(),
+        capacity: cache.pages.capacity(),
+        ..cache.stats
+    }
+}
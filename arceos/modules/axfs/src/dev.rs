@@ -1,28 +1,81 @@
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
 use axdriver::prelude::*;
+use axerrno::{ax_err, AxResult};
+use axsync::Mutex;
 
-const BLOCK_SIZE: usize = 512;
+use crate::cache::buffer::{self, BLOCK_SIZE};
+
+/// Registered block devices by their names, or `None` if in use.
+static DEVICES: Mutex<BTreeMap<String, Option<AxBlockDevice>>> = Mutex::new(BTreeMap::new());
+
+/// Registers a block device, and returns its name, which is `vda`, `vdb`,
+/// ... in the order of registration.
+pub fn add_device(dev: AxBlockDevice) -> String {
+    let mut devices = DEVICES.lock();
+    let name = device_name(devices.len());
+    devices.insert(name.clone(), Some(dev));
+    name
+}
+
+/// Returns the name of the `idx`-th device like Linux: `vda` to `vdz`, then
+/// `vdaa` and so on.
+fn device_name(mut idx: usize) -> String {
+    let mut suffix = Vec::new();
+    loop {
+        suffix.push(b'a' + (idx % 26) as u8);
+        if idx < 26 {
+            break;
+        }
+        idx = idx / 26 - 1;
+    }
+    suffix.reverse();
+    String::from("vd") + core::str::from_utf8(&suffix).unwrap()
+}
 
 /// A disk device with a cursor.
+///
+/// All accesses go through the buffer cache, and writes reach the device
+/// when the cache is synced.
 pub struct Disk {
+    name: String,
+    dev_id: usize,
     block_id: u64,
     offset: usize,
-    dev: AxBlockDevice,
+    num_blocks: u64,
 }
 
 impl Disk {
-    /// Create a new disk.
-    pub fn new(dev: AxBlockDevice) -> Self {
+    /// Opens the registered block device by name. It is in use until the disk
+    /// is dropped.
+    pub fn open(name: &str) -> AxResult<Self> {
+        let dev = match DEVICES.lock().get_mut(name) {
+            Some(dev) => match dev.take() {
+                Some(dev) => dev,
+                None => return ax_err!(ResourceBusy, "block device in use"),
+            },
+            None => return ax_err!(NotFound, "block device not found"),
+        };
         assert_eq!(BLOCK_SIZE, dev.block_size());
-        Self {
+        let num_blocks = dev.num_blocks();
+        Ok(Self {
+            name: name.into(),
+            dev_id: buffer::add_device(dev),
             block_id: 0,
             offset: 0,
-            dev,
-        }
+            num_blocks,
+        })
+    }
+
+    /// Get the name of the device.
+    pub fn name(&self) -> &str {
+        &self.name
     }
 
     /// Get the size of the disk.
     pub fn size(&self) -> u64 {
-        self.dev.num_blocks() * BLOCK_SIZE as u64
+        self.num_blocks * BLOCK_SIZE as u64
     }
 
     /// Get the position of the cursor.
@@ -38,55 +91,40 @@ impl Disk {
 
     /// Read within one block, returns the number of bytes read.
     pub fn read_one(&mut self, buf: &mut [u8]) -> DevResult<usize> {
-        let read_size = if self.offset == 0 && buf.len() >= BLOCK_SIZE {
-            // whole block
-            self.dev
-                .read_block(self.block_id, &mut buf[0..BLOCK_SIZE])?;
-            self.block_id += 1;
-            BLOCK_SIZE
-        } else {
-            // partial block
-            let mut data = [0u8; BLOCK_SIZE];
-            let start = self.offset;
-            let count = buf.len().min(BLOCK_SIZE - self.offset);
-
-            self.dev.read_block(self.block_id, &mut data)?;
-            buf[..count].copy_from_slice(&data[start..start + count]);
-
-            self.offset += count;
-            if self.offset >= BLOCK_SIZE {
-                self.block_id += 1;
-                self.offset -= BLOCK_SIZE;
-            }
-            count
-        };
-        Ok(read_size)
+        let count = buf.len().min(BLOCK_SIZE - self.offset);
+        buffer::read(self.dev_id, self.block_id, self.offset, &mut buf[..count])?;
+        self.advance(count);
+        Ok(count)
     }
 
     /// Write within one block, returns the number of bytes written.
     pub fn write_one(&mut self, buf: &[u8]) -> DevResult<usize> {
-        let write_size = if self.offset == 0 && buf.len() >= BLOCK_SIZE {
-            // whole block
-            self.dev.write_block(self.block_id, &buf[0..BLOCK_SIZE])?;
-            self.block_id += 1;
-            BLOCK_SIZE
-        } else {
-            // partial block
-            let mut data = [0u8; BLOCK_SIZE];
-            let start = self.offset;
-            let count = buf.len().min(BLOCK_SIZE - self.offset);
+        let count = buf.len().min(BLOCK_SIZE - self.offset);
+        buffer::write(self.dev_id, self.block_id, self.offset, &buf[..count])?;
+        self.advance(count);
+        Ok(count)
+    }
 
-            self.dev.read_block(self.block_id, &mut data)?;
-            data[start..start + count].copy_from_slice(&buf[..count]);
-            self.dev.write_block(self.block_id, &data)?;
+    /// Write the cached blocks of the disk back to the device.
+    pub fn flush(&mut self) -> DevResult {
+        buffer::sync_device(self.dev_id)
+    }
 
-            self.offset += count;
-            if self.offset >= BLOCK_SIZE {
-                self.block_id += 1;
-                self.offset -= BLOCK_SIZE;
-            }
-            count
-        };
-        Ok(write_size)
+    fn advance(&mut self, count: usize) {
+        self.offset += count;
+        if self.offset >= BLOCK_SIZE {
+            self.block_id += 1;
+            self.offset -= BLOCK_SIZE;
+        }
+    }
+}
+
+impl Drop for Disk {
+    fn drop(&mut self) {
+        let (dev, result) = buffer::remove_device(self.dev_id);
+        if let Err(e) = result {
+            warn!("failed to write back {}: {:?}", self.name, e);
+        }
+        DEVICES.lock().insert(self.name.clone(), Some(dev));
     }
 }
@@ -1,10 +1,15 @@
 //! Low-level filesystem operations.
 
+use alloc::{string::String, sync::Arc};
 use axerrno::{ax_err, ax_err_type, AxError, AxResult};
 use axfs_vfs::{VfsError, VfsNodeRef};
 use axio::SeekFrom;
 use cap_access::{Cap, WithCap};
 use core::fmt;
+use core::ptr::NonNull;
+
+use crate::cache::{buffer, page::CachedFile};
+use crate::root::MountPoint;
 
 #[cfg(feature = "myfs")]
 pub use crate::dev::Disk;
@@ -23,6 +28,10 @@ pub type FilePerm = axfs_vfs::VfsNodePerm;
 /// An opened file object, with open permissions and a cursor.
 pub struct File {
     node: WithCap<VfsNodeRef>,
+    /// The mount point of the file, which is busy until the file is closed.
+    mount: Arc<MountPoint>,
+    /// The handle in the page cache, for regular files.
+    cache: Option<CachedFile>,
     is_append: bool,
     offset: u64,
 }
@@ -31,6 +40,10 @@ pub struct File {
 /// [`read_dir`](Directory::read_dir).
 pub struct Directory {
     node: WithCap<VfsNodeRef>,
+    /// The mount point of the directory, which is busy until it is closed.
+    _mount: Arc<MountPoint>,
+    /// The absolute path of the directory.
+    path: String,
     entry_idx: usize,
 }
 
@@ -116,13 +129,13 @@ impl File {
         self.node.access_or_err(cap, AxError::PermissionDenied)
     }
 
-    fn _open_at(dir: Option<&VfsNodeRef>, path: &str, opts: &OpenOptions) -> AxResult<Self> {
+    fn _open_at(path: &str, opts: &OpenOptions) -> AxResult<Self> {
         debug!("open file: {} {:?}", path, opts);
         if !opts.is_valid() {
             return ax_err!(InvalidInput);
         }
 
-        let node_option = crate::root::lookup(dir, path);
+        let node_option = crate::root::lookup(path);
         let node = if opts.create || opts.create_new {
             match node_option {
                 Ok(node) => {
@@ -133,7 +146,7 @@ impl File {
                     node
                 }
                 // not exists, create new
-                Err(VfsError::NotFound) => crate::root::create_file(dir, path)?,
+                Err(VfsError::NotFound) => crate::root::create_file(path)?,
                 Err(e) => return Err(e),
             }
         } else {
@@ -151,13 +164,28 @@ impl File {
         if !perm_to_cap(attr.perm()).contains(access_cap) {
             return ax_err!(PermissionDenied);
         }
+        let mount = crate::root::mount_point_of(path)?;
+        if access_cap.contains(Cap::WRITE) && mount.is_read_only() {
+            return ax_err!(ReadOnlyFilesystem);
+        }
 
+        let (node, cache) = if attr.is_file() && mount.is_cacheable() {
+            let cache = crate::root::open_cached(path, node)?;
+            (cache.node(), Some(cache))
+        } else {
+            (node, None)
+        };
         node.open()?;
         if opts.truncate {
-            node.truncate(0)?;
+            match &cache {
+                Some(cache) => cache.truncate(0)?,
+                None => node.truncate(0)?,
+            }
         }
         Ok(Self {
             node: WithCap::new(node, access_cap),
+            mount,
+            cache,
             is_append: opts.append,
             offset: 0,
         })
@@ -166,22 +194,54 @@ impl File {
     /// Opens a file at the path relative to the current directory. Returns a
     /// [`File`] object.
     pub fn open(path: &str, opts: &OpenOptions) -> AxResult<Self> {
-        Self::_open_at(None, path, opts)
+        Self::_open_at(path, opts)
+    }
+
+    /// Creates a new handle of the same opened file, with the same open
+    /// permissions and cursor position.
+    pub fn try_clone(&self) -> AxResult<Self> {
+        let node = unsafe { self.node.access_unchecked() }.clone();
+        node.open()?;
+        Ok(Self {
+            node: WithCap::new(node, self.node.cap()),
+            mount: self.mount.clone(),
+            cache: self.cache.clone(),
+            is_append: self.is_append,
+            offset: self.offset,
+        })
     }
 
     /// Truncates the file to the specified size.
     pub fn truncate(&self, size: u64) -> AxResult {
-        self.access_node(Cap::WRITE)?.truncate(size)?;
+        let node = self.access_node(Cap::WRITE)?;
+        match &self.cache {
+            Some(cache) => cache.truncate(size)?,
+            None => node.truncate(size)?,
+        }
         Ok(())
     }
 
+    fn read_node(&self, node: &VfsNodeRef, offset: u64, buf: &mut [u8]) -> AxResult<usize> {
+        match &self.cache {
+            Some(cache) => cache.read_at(offset, buf),
+            None => node.read_at(offset, buf),
+        }
+    }
+
+    fn write_node(&self, node: &VfsNodeRef, offset: u64, buf: &[u8]) -> AxResult<usize> {
+        match &self.cache {
+            Some(cache) => cache.write_at(offset, buf),
+            None => node.write_at(offset, buf),
+        }
+    }
+
     /// Reads the file at the current position. Returns the number of bytes
     /// read.
     ///
     /// After the read, the cursor will be advanced by the number of bytes read.
     pub fn read(&mut self, buf: &mut [u8]) -> AxResult<usize> {
         let node = self.access_node(Cap::READ)?;
-        let read_len = node.read_at(self.offset, buf)?;
+        let read_len = self.read_node(node, self.offset, buf)?;
         self.offset += read_len as u64;
         Ok(read_len)
     }
@@ -191,7 +251,7 @@ impl File {
     /// It does not update the file cursor.
     pub fn read_at(&self, offset: u64, buf: &mut [u8]) -> AxResult<usize> {
         let node = self.access_node(Cap::READ)?;
-        let read_len = node.read_at(offset, buf)?;
+        let read_len = self.read_node(node, offset, buf)?;
         Ok(read_len)
     }
 
@@ -207,7 +267,7 @@ impl File {
             self.offset
         };
         let node = self.access_node(Cap::WRITE)?;
-        let write_len = node.write_at(offset, buf)?;
+        let write_len = self.write_node(node, offset, buf)?;
         self.offset = offset + write_len as u64;
         Ok(write_len)
     }
@@ -218,16 +278,51 @@ impl File {
     /// It does not update the file cursor.
     pub fn write_at(&self, offset: u64, buf: &[u8]) -> AxResult<usize> {
         let node = self.access_node(Cap::WRITE)?;
-        let write_len = node.write_at(offset, buf)?;
+        let write_len = self.write_node(node, offset, buf)?;
         Ok(write_len)
     }
 
     /// Flushes the file, writes all buffered data to the underlying device.
+    ///
+    /// Dirty blocks of other files in the buffer cache are also written back.
     pub fn flush(&self) -> AxResult {
-        self.access_node(Cap::WRITE)?.fsync()?;
+        let node = self.access_node(Cap::WRITE)?;
+        match &self.cache {
+            Some(cache) => cache.sync()?,
+            None => node.fsync()?,
+        }
+        buffer::sync()?;
+        Ok(())
+    }
+
+    fn cache(&self) -> AxResult<&CachedFile> {
+        self.cache.as_ref().ok_or(AxError::Unsupported)
+    }
+
+    /// Pins the page of the file at `index`, in pages of 4K bytes, and
+    /// returns its frame in the page cache, e.g., to map it to user space.
+    ///
+    /// The frame is valid until [`unmap_page`](Self::unmap_page). It is shared
+    /// by all mappings of the file, and its modifications are written back
+    /// once marked by [`set_page_dirty`](Self::set_page_dirty).
+    pub fn map_page(&self, index: u64) -> AxResult<NonNull<[u8; 4096]>> {
+        self.access_node(Cap::READ)?;
+        self.cache()?.get_page(index)
+    }
+
+    /// Marks the page pinned by [`map_page`](Self::map_page) as modified.
+    pub fn set_page_dirty(&self, index: u64) -> AxResult {
+        self.access_node(Cap::WRITE)?;
+        self.cache()?.set_dirty(index);
         Ok(())
     }
 
+    /// Unpins the page pinned by [`map_page`](Self::map_page), and writes it
+    /// back if it is modified and no longer mapped.
+    pub fn unmap_page(&self, index: u64) -> AxResult {
+        self.cache()?.put_page(index)
+    }
+
     /// Sets the cursor of the file to the specified offset. Returns the new
     /// position after the seek.
     pub fn seek(&mut self, pos: SeekFrom) -> AxResult<u64> {
@@ -253,7 +348,7 @@ impl Directory {
         self.node.access_or_err(cap, AxError::PermissionDenied)
     }
 
-    fn _open_dir_at(dir: Option<&VfsNodeRef>, path: &str, opts: &OpenOptions) -> AxResult<Self> {
+    fn _open_dir_at(path: &str, opts: &OpenOptions) -> AxResult<Self> {
         debug!("open dir: {}", path);
         if !opts.read {
             return ax_err!(InvalidInput);
@@ -262,7 +357,7 @@ impl Directory {
             return ax_err!(InvalidInput);
         }
 
-        let node = crate::root::lookup(dir, path)?;
+        let node = crate::root::lookup(path)?;
         let attr = node.get_attr()?;
         if !attr.is_dir() {
             return ax_err!(NotADirectory);
@@ -275,54 +370,59 @@ impl Directory {
         node.open()?;
         Ok(Self {
             node: WithCap::new(node, access_cap),
+            _mount: crate::root::mount_point_of(path)?,
+            path: crate::root::absolute_path(path)?,
             entry_idx: 0,
         })
     }
 
-    fn access_at(&self, path: &str) -> AxResult<Option<&VfsNodeRef>> {
+    /// Returns the path relative to this directory as an absolute one, which
+    /// requires the execute permission of the directory.
+    fn path_at(&self, path: &str) -> AxResult<String> {
         if path.starts_with('/') {
-            Ok(None)
+            Ok(path.into())
         } else {
-            Ok(Some(self.access_node(Cap::EXECUTE)?))
+            self.access_node(Cap::EXECUTE)?;
+            Ok(alloc::format!("{}/{}", self.path, path))
         }
     }
 
     /// Opens a directory at the path relative to the current directory.
     /// Returns a [`Directory`] object.
     pub fn open_dir(path: &str, opts: &OpenOptions) -> AxResult<Self> {
-        Self::_open_dir_at(None, path, opts)
+        Self::_open_dir_at(path, opts)
     }
 
     /// Opens a directory at the path relative to this directory. Returns a
     /// [`Directory`] object.
     pub fn open_dir_at(&self, path: &str, opts: &OpenOptions) -> AxResult<Self> {
-        Self::_open_dir_at(self.access_at(path)?, path, opts)
+        Self::_open_dir_at(&self.path_at(path)?, opts)
     }
 
     /// Opens a file at the path relative to this directory. Returns a [`File`]
     /// object.
     pub fn open_file_at(&self, path: &str, opts: &OpenOptions) -> AxResult<File> {
-        File::_open_at(self.access_at(path)?, path, opts)
+        File::_open_at(&self.path_at(path)?, opts)
     }
 
     /// Creates an empty file at the path relative to this directory.
     pub fn create_file(&self, path: &str) -> AxResult<VfsNodeRef> {
-        crate::root::create_file(self.access_at(path)?, path)
+        crate::root::create_file(&self.path_at(path)?)
     }
 
     /// Creates an empty directory at the path relative to this directory.
     pub fn create_dir(&self, path: &str) -> AxResult {
-        crate::root::create_dir(self.access_at(path)?, path)
+        crate::root::create_dir(&self.path_at(path)?)
     }
 
     /// Removes a file at the path relative to this directory.
     pub fn remove_file(&self, path: &str) -> AxResult {
-        crate::root::remove_file(self.access_at(path)?, path)
+        crate::root::remove_file(&self.path_at(path)?)
     }
 
     /// Removes a directory at the path relative to this directory.
     pub fn remove_dir(&self, path: &str) -> AxResult {
-        crate::root::remove_dir(self.access_at(path)?, path)
+        crate::root::remove_dir(&self.path_at(path)?)
     }
 
     /// Reads directory entries starts from the current position into the
@@ -338,6 +438,11 @@ impl Directory {
         Ok(n)
     }
 
+    /// Gets the directory attributes.
+    pub fn get_attr(&self) -> AxResult<FileAttr> {
+        self.access_node(Cap::empty())?.get_attr()
+    }
+
     /// Rename a file or directory to a new name.
     /// Delete the original file if `old` already exists.
     ///
@@ -0,0 +1,44 @@
+//! Checksum algorithms used by ext4 metadata.
+//!
+//! Both of them are the raw reflected CRCs as in Linux, i.e., without the
+//! final inversion.
+
+const fn crc_table<const N: usize>(poly: u32) -> [u32; N] {
+    let mut table = [0; N];
+    let mut i = 0;
+    while i < N {
+        let mut crc = i as u32;
+        let mut j = 0;
+        while j < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ poly
+            } else {
+                crc >> 1
+            };
+            j += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+static CRC32C_TABLE: [u32; 256] = crc_table(0x82f6_3b78);
+static CRC16_TABLE: [u32; 256] = crc_table(0xa001);
+
+/// CRC32c (Castagnoli), used with the `metadata_csum` feature.
+pub fn crc32c(mut crc: u32, data: &[u8]) -> u32 {
+    for &b in data {
+        crc = CRC32C_TABLE[((crc ^ b as u32) & 0xff) as usize] ^ (crc >> 8);
+    }
+    crc
+}
+
+/// CRC16 (ANSI), used for group descriptors with the `gdt_csum` feature.
+pub fn crc16(crc: u16, data: &[u8]) -> u16 {
+    let mut crc = crc as u32;
+    for &b in data {
+        crc = CRC16_TABLE[((crc ^ b as u32) & 0xff) as usize] ^ (crc >> 8);
+    }
+    crc as u16
+}
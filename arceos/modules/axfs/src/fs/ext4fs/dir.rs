@@ -0,0 +1,425 @@
+//! Directory entries.
+
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use axfs_vfs::{VfsDirEntry, VfsError, VfsNodeType, VfsResult};
+
+use super::crc::crc32c;
+use super::layout::*;
+use super::volume::{now, Volume};
+
+const DIRENT_HEADER_SIZE: usize = 8;
+/// The size of the fake entry at the end of directory blocks, which holds
+/// the checksum with `metadata_csum`.
+pub const DIRENT_TAIL_SIZE: usize = 12;
+const DIRENT_TAIL_TYPE: u8 = 0xde;
+pub const NAME_MAX: usize = 255;
+
+/// The location of a directory entry.
+pub struct EntryLoc {
+    pub lblk: u32,
+    pub offset: usize,
+    pub ino: u32,
+}
+
+/// A directory entry in a block.
+struct RawEntry {
+    offset: usize,
+    ino: u32,
+    rec_len: usize,
+    name_len: usize,
+    file_type: u8,
+}
+
+impl RawEntry {
+    fn name<'a>(&self, block: &'a [u8]) -> &'a [u8] {
+        &block[self.offset + DIRENT_HEADER_SIZE..][..self.name_len]
+    }
+
+    /// The space taken by the entry, which is 0 if it is unused.
+    fn used_len(&self) -> usize {
+        if self.ino == 0 {
+            0
+        } else {
+            rec_len_of(self.name_len)
+        }
+    }
+}
+
+fn rec_len_of(name_len: usize) -> usize {
+    (DIRENT_HEADER_SIZE + name_len).next_multiple_of(4)
+}
+
+fn write_entry(block: &mut [u8], offset: usize, ino: u32, rec_len: usize, name: &[u8], ty: u8) {
+    put_u32(block, offset, ino);
+    put_u16(block, offset + 4, rec_len as u16);
+    block[offset + 6] = name.len() as u8;
+    block[offset + 7] = ty;
+    block[offset + DIRENT_HEADER_SIZE..][..name.len()].copy_from_slice(name);
+}
+
+/// The file type in directory entries of an inode mode.
+pub fn dirent_type(mode: u16) -> u8 {
+    match mode & S_IFMT {
+        S_IFREG => 1,
+        S_IFDIR => 2,
+        S_IFCHR => 3,
+        S_IFBLK => 4,
+        S_IFIFO => 5,
+        S_IFSOCK => 6,
+        S_IFLNK => 7,
+        _ => 0,
+    }
+}
+
+pub fn vfs_node_type(mode: u16) -> VfsNodeType {
+    match mode & S_IFMT {
+        S_IFDIR => VfsNodeType::Dir,
+        S_IFCHR => VfsNodeType::CharDevice,
+        S_IFBLK => VfsNodeType::BlockDevice,
+        S_IFIFO => VfsNodeType::Fifo,
+        S_IFSOCK => VfsNodeType::Socket,
+        S_IFLNK => VfsNodeType::SymLink,
+        _ => VfsNodeType::File,
+    }
+}
+
+impl Volume {
+    fn has_filetype(&self) -> bool {
+        self.sb.has_incompat(INCOMPAT_FILETYPE)
+    }
+
+    fn is_indexed(&self, dir: &Inode) -> bool {
+        dir.has_flags(INODE_INDEX_FL) && self.sb.feature_compat() & COMPAT_DIR_INDEX != 0
+    }
+
+    /// Parses the entries of a directory block, without the checksum tail.
+    fn parse_dir_block(&self, block: &[u8]) -> VfsResult<Vec<RawEntry>> {
+        let mut entries = Vec::new();
+        let mut offset = 0;
+        while offset < block.len() {
+            if offset + DIRENT_HEADER_SIZE > block.len() {
+                return Err(VfsError::InvalidData);
+            }
+            let ino = get_u32(block, offset);
+            let rec_len = get_u16(block, offset + 4) as usize;
+            let (name_len, file_type) = if self.has_filetype() {
+                (block[offset + 6] as usize, block[offset + 7])
+            } else {
+                (get_u16(block, offset + 6) as usize, 0)
+            };
+            if ino == 0
+                && rec_len == DIRENT_TAIL_SIZE
+                && name_len == 0
+                && file_type == DIRENT_TAIL_TYPE
+                && offset + DIRENT_TAIL_SIZE == block.len()
+            {
+                break;
+            }
+            if rec_len < DIRENT_HEADER_SIZE
+                || rec_len % 4 != 0
+                || offset + rec_len > block.len()
+                || DIRENT_HEADER_SIZE + name_len > rec_len
+            {
+                warn!("ext4: bad directory entry at {}", offset);
+                return Err(VfsError::InvalidData);
+            }
+            entries.push(RawEntry {
+                offset,
+                ino,
+                rec_len,
+                name_len,
+                file_type,
+            });
+            offset += rec_len;
+        }
+        Ok(entries)
+    }
+
+    /// The end of the entries in a directory block, before the checksum tail.
+    fn dir_block_end(&self) -> usize {
+        if self.has_metadata_csum() {
+            self.block_size - DIRENT_TAIL_SIZE
+        } else {
+            self.block_size
+        }
+    }
+
+    fn read_dir_block(&mut self, dir: &Inode, lblk: u32) -> VfsResult<Option<Vec<u8>>> {
+        let mut block = vec![0; self.block_size];
+        match self.map_block(dir, lblk)? {
+            Some((pblk, false)) => self.read_block(pblk, &mut block)?,
+            _ => return Ok(None),
+        }
+        Ok(Some(block))
+    }
+
+    /// Writes a directory block, with the checksum tail set up.
+    pub fn write_dir_block(
+        &mut self,
+        ino: u32,
+        dir: &Inode,
+        lblk: u32,
+        block: &mut [u8],
+    ) -> VfsResult {
+        self.set_dir_block_csum(ino, dir, block);
+        let Some((pblk, _)) = self.map_block(dir, lblk)? else {
+            return Err(VfsError::InvalidData);
+        };
+        self.write_block(pblk, block)
+    }
+
+    fn set_dir_block_csum(&self, ino: u32, dir: &Inode, block: &mut [u8]) {
+        if self.has_metadata_csum() {
+            let tail = self.block_size - DIRENT_TAIL_SIZE;
+            write_entry(block, tail, 0, DIRENT_TAIL_SIZE, &[], DIRENT_TAIL_TYPE);
+            let csum = crc32c(self.inode_seed(ino, dir), &block[..tail]);
+            put_u32(block, tail + 8, csum);
+        }
+    }
+
+    fn dir_block_count(&self, dir: &Inode) -> u32 {
+        (dir.size() / self.block_size as u64) as u32
+    }
+
+    /// Finds the entry of `name` in a block of the directory.
+    pub fn find_in_block(
+        &mut self,
+        dir: &Inode,
+        lblk: u32,
+        name: &[u8],
+    ) -> VfsResult<Option<EntryLoc>> {
+        let Some(block) = self.read_dir_block(dir, lblk)? else {
+            return Ok(None);
+        };
+        let entries = self.parse_dir_block(&block)?;
+        let entry = entries
+            .iter()
+            .find(|e| e.ino != 0 && e.name(&block) == name);
+        Ok(entry.map(|e| EntryLoc {
+            lblk,
+            offset: e.offset,
+            ino: e.ino,
+        }))
+    }
+
+    fn dir_find(&mut self, dir: &Inode, name: &[u8]) -> VfsResult<Option<EntryLoc>> {
+        if name == b"." || name == b".." {
+            // Always the first entries, which are not indexed.
+            return self.find_in_block(dir, 0, name);
+        }
+        if self.is_indexed(dir) {
+            if let Some(res) = self.dx_find(dir, name)? {
+                return Ok(res);
+            }
+        }
+        for lblk in 0..self.dir_block_count(dir) {
+            if let Some(loc) = self.find_in_block(dir, lblk, name)? {
+                return Ok(Some(loc));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Looks up `name` in the directory, returning its inode number.
+    pub fn dir_lookup(&mut self, dir: &Inode, name: &[u8]) -> VfsResult<Option<u32>> {
+        Ok(self.dir_find(dir, name)?.map(|loc| loc.ino))
+    }
+
+    /// Reads the entries of the directory from the `start_idx`-th.
+    pub fn dir_entries(
+        &mut self,
+        dir: &Inode,
+        start_idx: usize,
+        dirents: &mut [VfsDirEntry],
+    ) -> VfsResult<usize> {
+        let mut idx = 0;
+        let mut count = 0;
+        for lblk in 0..self.dir_block_count(dir) {
+            let Some(block) = self.read_dir_block(dir, lblk)? else {
+                continue;
+            };
+            for e in self.parse_dir_block(&block)? {
+                if e.ino == 0 {
+                    continue;
+                }
+                idx += 1;
+                if idx <= start_idx {
+                    continue;
+                }
+                if count == dirents.len() {
+                    return Ok(count);
+                }
+                let ty = if self.has_filetype() && e.file_type != 0 {
+                    vfs_node_type(file_type_mode(e.file_type))
+                } else {
+                    vfs_node_type(self.read_inode(e.ino)?.mode())
+                };
+                let name = String::from_utf8_lossy(e.name(&block));
+                dirents[count] = VfsDirEntry::new(&name, ty);
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    /// Whether the directory has no entries other than "." and "..".
+    pub fn dir_is_empty(&mut self, dir: &Inode) -> VfsResult<bool> {
+        for lblk in 0..self.dir_block_count(dir) {
+            let Some(block) = self.read_dir_block(dir, lblk)? else {
+                continue;
+            };
+            for e in self.parse_dir_block(&block)? {
+                let name = e.name(&block);
+                if e.ino != 0 && name != b"." && name != b".." {
+                    return Ok(false);
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    /// Tries to insert an entry into a block of the directory.
+    fn insert_in_block(
+        &mut self,
+        ino: u32,
+        dir: &Inode,
+        lblk: u32,
+        name: &[u8],
+        target: u32,
+        ty: u8,
+    ) -> VfsResult<bool> {
+        let Some(mut block) = self.read_dir_block(dir, lblk)? else {
+            return Ok(false);
+        };
+        let needed = rec_len_of(name.len());
+        let entries = self.parse_dir_block(&block)?;
+        let Some(e) = entries.iter().find(|e| e.rec_len - e.used_len() >= needed) else {
+            return Ok(false);
+        };
+        let used = e.used_len();
+        if used > 0 {
+            put_u16(&mut block, e.offset + 4, used as u16);
+        }
+        write_entry(
+            &mut block,
+            e.offset + used,
+            target,
+            e.rec_len - used,
+            name,
+            ty,
+        );
+        self.write_dir_block(ino, dir, lblk, &mut block)?;
+        Ok(true)
+    }
+
+    /// Adds an entry to the directory `ino`, whose inode is updated and
+    /// written.
+    pub fn dir_add(
+        &mut self,
+        ino: u32,
+        dir: &mut Inode,
+        name: &[u8],
+        target: u32,
+        mode: u16,
+    ) -> VfsResult {
+        if name.len() > NAME_MAX {
+            return Err(VfsError::InvalidInput);
+        }
+        let ty = if self.has_filetype() {
+            dirent_type(mode)
+        } else {
+            0
+        };
+        let inserted = 'insert: {
+            if self.is_indexed(dir) {
+                if let Some(leaf) = self.dx_leaf(dir, name)? {
+                    if self.insert_in_block(ino, dir, leaf, name, target, ty)? {
+                        break 'insert true;
+                    }
+                }
+                self.dx_drop_index(ino, dir)?;
+            }
+            for lblk in 0..self.dir_block_count(dir) {
+                if self.insert_in_block(ino, dir, lblk, name, target, ty)? {
+                    break 'insert true;
+                }
+            }
+            false
+        };
+        if !inserted {
+            let mut block = vec![0; self.block_size];
+            let end = self.dir_block_end();
+            write_entry(&mut block, 0, target, end, name, ty);
+            self.set_dir_block_csum(ino, dir, &mut block);
+            let size = dir.size();
+            self.write_data(ino, dir, size, &block)?;
+        }
+        let time = now();
+        dir.set_mtime(time);
+        dir.set_ctime(time);
+        self.write_inode(ino, dir)
+    }
+
+    /// Removes the entry of `name` from the directory `ino`, whose inode is
+    /// updated and written. Returns the inode number of the entry.
+    pub fn dir_remove(&mut self, ino: u32, dir: &mut Inode, name: &[u8]) -> VfsResult<u32> {
+        let loc = self.dir_find(dir, name)?.ok_or(VfsError::NotFound)?;
+        let mut block = self.read_dir_block(dir, loc.lblk)?.unwrap();
+        let entries = self.parse_dir_block(&block)?;
+        let pos = entries.iter().position(|e| e.offset == loc.offset).unwrap();
+        if pos > 0 {
+            // Merge into the previous entry.
+            let prev = &entries[pos - 1];
+            let rec_len = prev.rec_len + entries[pos].rec_len;
+            put_u16(&mut block, prev.offset + 4, rec_len as u16);
+        } else {
+            put_u32(&mut block, loc.offset, 0);
+        }
+        self.write_dir_block(ino, dir, loc.lblk, &mut block)?;
+        let time = now();
+        dir.set_mtime(time);
+        dir.set_ctime(time);
+        self.write_inode(ino, dir)?;
+        Ok(loc.ino)
+    }
+
+    /// Writes the first block of a new directory, with "." and "..".
+    pub fn dir_init(&mut self, ino: u32, dir: &mut Inode, parent: u32) -> VfsResult {
+        let ty = if self.has_filetype() {
+            dirent_type(S_IFDIR)
+        } else {
+            0
+        };
+        let mut block = vec![0; self.block_size];
+        let end = self.dir_block_end();
+        write_entry(&mut block, 0, ino, 12, b".", ty);
+        write_entry(&mut block, 12, parent, end - 12, b"..", ty);
+        self.set_dir_block_csum(ino, dir, &mut block);
+        self.write_data(ino, dir, 0, &block)?;
+        Ok(())
+    }
+
+    /// Points ".." of the directory to a new parent.
+    pub fn dir_set_parent(&mut self, ino: u32, dir: &Inode, parent: u32) -> VfsResult {
+        let loc = self.dir_find(dir, b"..")?.ok_or(VfsError::InvalidData)?;
+        let mut block = self.read_dir_block(dir, loc.lblk)?.unwrap();
+        put_u32(&mut block, loc.offset, parent);
+        self.write_dir_block(ino, dir, loc.lblk, &mut block)
+    }
+}
+
+/// The inode mode of a file type in directory entries.
+fn file_type_mode(ty: u8) -> u16 {
+    match ty {
+        2 => S_IFDIR,
+        3 => S_IFCHR,
+        4 => S_IFBLK,
+        5 => S_IFIFO,
+        6 => S_IFSOCK,
+        7 => S_IFLNK,
+        _ => S_IFREG,
+    }
+}
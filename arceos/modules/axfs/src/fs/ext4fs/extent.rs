@@ -0,0 +1,369 @@
+//! Mapping of file blocks, by extent trees or by the legacy block maps.
+//!
+//! Extent trees are changed by collecting all the extents, editing them in
+//! memory, and then writing a new tree. It reuses the blocks of the old tree,
+//! which is a single block or even none for most files.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use axfs_vfs::{VfsError, VfsResult};
+
+use super::crc::crc32c;
+use super::layout::*;
+use super::volume::Volume;
+
+const EXT_MAGIC: u16 = 0xf30a;
+const EXT_HEADER_SIZE: usize = 12;
+const EXT_ENTRY_SIZE: usize = 12;
+/// The maximum length of an initialized extent.
+pub const EXT_INIT_MAX_LEN: u32 = 1 << 15;
+const EXT_MAX_DEPTH: u16 = 5;
+/// The number of direct blocks in a legacy block map.
+const NDIR_BLOCKS: u32 = 12;
+
+/// A contiguous range of file blocks mapped to disk.
+#[derive(Clone, Copy, Debug)]
+pub struct Extent {
+    /// The first logical block.
+    pub lblk: u32,
+    pub len: u32,
+    /// The first physical block.
+    pub pblk: u64,
+    /// Allocated but not initialized, reads as zeros.
+    pub unwritten: bool,
+}
+
+impl Extent {
+    pub fn end(&self) -> u32 {
+        self.lblk + self.len
+    }
+
+    fn max_len(&self) -> u32 {
+        if self.unwritten {
+            EXT_INIT_MAX_LEN - 1
+        } else {
+            EXT_INIT_MAX_LEN
+        }
+    }
+
+    fn decode(raw: &[u8]) -> Self {
+        let len = get_u16(raw, 4) as u32;
+        let unwritten = len > EXT_INIT_MAX_LEN;
+        Self {
+            lblk: get_u32(raw, 0),
+            len: if unwritten {
+                len - EXT_INIT_MAX_LEN
+            } else {
+                len
+            },
+            pblk: (get_u16(raw, 6) as u64) << 32 | get_u32(raw, 8) as u64,
+            unwritten,
+        }
+    }
+
+    fn encode(&self, raw: &mut [u8]) {
+        let len = if self.unwritten {
+            self.len + EXT_INIT_MAX_LEN
+        } else {
+            self.len
+        };
+        put_u32(raw, 0, self.lblk);
+        put_u16(raw, 4, len as u16);
+        put_u16(raw, 6, (self.pblk >> 32) as u16);
+        put_u32(raw, 8, self.pblk as u32);
+    }
+}
+
+/// Finds the extent containing `lblk` in sorted extents, or the position to
+/// insert one.
+pub fn find_extent(extents: &[Extent], lblk: u32) -> Result<usize, usize> {
+    let pos = extents.partition_point(|ext| ext.lblk <= lblk);
+    if pos > 0 && lblk < extents[pos - 1].end() {
+        Ok(pos - 1)
+    } else {
+        Err(pos)
+    }
+}
+
+/// Merges the adjacent extents that are contiguous on disk.
+pub fn merge_extents(extents: &mut Vec<Extent>) {
+    extents.dedup_by(|next, prev| {
+        let mergeable = prev.end() == next.lblk
+            && prev.pblk + prev.len as u64 == next.pblk
+            && prev.unwritten == next.unwritten
+            && prev.len + next.len <= prev.max_len();
+        if mergeable {
+            prev.len += next.len;
+        }
+        mergeable
+    });
+}
+
+struct NodeHeader {
+    entries: usize,
+    depth: u16,
+}
+
+fn parse_header(node: &[u8]) -> VfsResult<NodeHeader> {
+    let entries = get_u16(node, 2) as usize;
+    let max = get_u16(node, 4) as usize;
+    let depth = get_u16(node, 6);
+    if get_u16(node, 0) != EXT_MAGIC
+        || entries > max
+        || EXT_HEADER_SIZE + max * EXT_ENTRY_SIZE > node.len()
+        || depth > EXT_MAX_DEPTH
+    {
+        warn!("ext4: bad extent header");
+        return Err(VfsError::InvalidData);
+    }
+    Ok(NodeHeader { entries, depth })
+}
+
+fn write_header(node: &mut [u8], entries: usize, depth: u16) {
+    let max = (node.len() - EXT_HEADER_SIZE) / EXT_ENTRY_SIZE;
+    put_u16(node, 0, EXT_MAGIC);
+    put_u16(node, 2, entries as u16);
+    put_u16(node, 4, max as u16);
+    put_u16(node, 6, depth);
+    put_u32(node, 8, 0);
+}
+
+fn entry(node: &[u8], i: usize) -> &[u8] {
+    &node[EXT_HEADER_SIZE + i * EXT_ENTRY_SIZE..][..EXT_ENTRY_SIZE]
+}
+
+fn index_key(raw: &[u8]) -> u32 {
+    get_u32(raw, 0)
+}
+
+fn index_child(raw: &[u8]) -> u64 {
+    (get_u16(raw, 8) as u64) << 32 | get_u32(raw, 4) as u64
+}
+
+impl Volume {
+    /// Sets an empty extent tree to a new inode.
+    pub fn init_extents(&self, inode: &mut Inode) {
+        if self.sb.has_incompat(INCOMPAT_EXTENTS) {
+            inode.set_flags(inode.flags() | INODE_EXTENTS_FL);
+            write_header(inode.i_block_mut(), 0, 0);
+        }
+    }
+
+    /// The size of the extent tree nodes out of the inode.
+    fn ext_node_size(&self) -> usize {
+        let max = (self.block_size - EXT_HEADER_SIZE) / EXT_ENTRY_SIZE;
+        EXT_HEADER_SIZE + max * EXT_ENTRY_SIZE
+    }
+
+    fn read_ext_node(&mut self, block: u64) -> VfsResult<Vec<u8>> {
+        let mut buf = vec![0; self.block_size];
+        self.read_block(block, &mut buf)?;
+        buf.truncate(self.ext_node_size());
+        Ok(buf)
+    }
+
+    /// Maps a logical block of the inode to the physical block, which is
+    /// `None` for holes, along with whether it is unwritten.
+    pub fn map_block(&mut self, inode: &Inode, lblk: u32) -> VfsResult<Option<(u64, bool)>> {
+        if !inode.has_flags(INODE_EXTENTS_FL) {
+            return Ok(self.map_indirect(inode, lblk)?.map(|pblk| (pblk, false)));
+        }
+        let mut node = inode.i_block().to_vec();
+        loop {
+            let hdr = parse_header(&node)?;
+            let pos = (0..hdr.entries).take_while(|&i| index_key(entry(&node, i)) <= lblk);
+            let Some(i) = pos.last() else {
+                return Ok(None);
+            };
+            if hdr.depth == 0 {
+                let ext = Extent::decode(entry(&node, i));
+                if lblk >= ext.end() {
+                    return Ok(None);
+                }
+                return Ok(Some((ext.pblk + (lblk - ext.lblk) as u64, ext.unwritten)));
+            }
+            node = self.read_ext_node(index_child(entry(&node, i)))?;
+        }
+    }
+
+    /// Collects all the extents of the inode, and the blocks of the tree.
+    pub fn read_extents(&mut self, inode: &Inode) -> VfsResult<(Vec<Extent>, Vec<u64>)> {
+        let mut extents = Vec::new();
+        let mut nodes = Vec::new();
+        self.collect_extents(inode.i_block().to_vec(), &mut extents, &mut nodes)?;
+        Ok((extents, nodes))
+    }
+
+    fn collect_extents(
+        &mut self,
+        node: Vec<u8>,
+        extents: &mut Vec<Extent>,
+        nodes: &mut Vec<u64>,
+    ) -> VfsResult {
+        let hdr = parse_header(&node)?;
+        for i in 0..hdr.entries {
+            if hdr.depth == 0 {
+                extents.push(Extent::decode(entry(&node, i)));
+            } else {
+                let child = index_child(entry(&node, i));
+                nodes.push(child);
+                let child_node = self.read_ext_node(child)?;
+                if parse_header(&child_node)?.depth + 1 != hdr.depth {
+                    warn!("ext4: bad extent tree depth");
+                    return Err(VfsError::InvalidData);
+                }
+                self.collect_extents(child_node, extents, nodes)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes a new extent tree of the sorted `extents` to the inode, reusing
+    /// the blocks `old_nodes` of the old tree.
+    ///
+    /// The number of sectors of the inode is updated, but it is not written.
+    pub fn write_extents(
+        &mut self,
+        ino: u32,
+        inode: &mut Inode,
+        extents: &[Extent],
+        mut old_nodes: Vec<u64>,
+    ) -> VfsResult {
+        let per_node = (self.block_size - EXT_HEADER_SIZE) / EXT_ENTRY_SIZE;
+        let root_max = (I_BLOCK_SIZE - EXT_HEADER_SIZE) / EXT_ENTRY_SIZE;
+
+        // Allocate the blocks first, to fail before changing anything.
+        let mut node_count = 0;
+        let mut count = extents.len();
+        while count > root_max {
+            count = count.div_ceil(per_node);
+            node_count += count;
+        }
+        let mut nodes = Vec::with_capacity(node_count);
+        let goal = extents.first().map_or(self.inode_goal(ino), |ext| ext.pblk);
+        while old_nodes.len() + nodes.len() < node_count {
+            match self.alloc_blocks(goal, 1) {
+                Ok((block, _)) => nodes.push(block),
+                Err(err) => {
+                    for block in nodes {
+                        self.free_blocks(block, 1)?;
+                    }
+                    return Err(err);
+                }
+            }
+        }
+        let sectors_per_block = self.block_size as u64 / 512;
+        let sectors = inode.blocks(self.block_size) + nodes.len() as u64 * sectors_per_block;
+        let reused = old_nodes.len().min(node_count);
+        nodes.extend(old_nodes.drain(..reused));
+        for &block in old_nodes.iter() {
+            self.free_blocks(block, 1)?;
+        }
+        inode.set_blocks(sectors - old_nodes.len() as u64 * sectors_per_block);
+
+        // Build the tree from the leaves.
+        let seed = self.inode_seed(ino, inode);
+        let mut items: Vec<[u8; EXT_ENTRY_SIZE]> = extents
+            .iter()
+            .map(|ext| {
+                let mut raw = [0; EXT_ENTRY_SIZE];
+                ext.encode(&mut raw);
+                raw
+            })
+            .collect();
+        let mut depth = 0;
+        let mut nodes = nodes.into_iter();
+        while items.len() > root_max {
+            let mut parents = Vec::new();
+            for chunk in items.chunks(per_node) {
+                let block = nodes.next().unwrap();
+                let mut buf = vec![0; self.block_size];
+                let size = self.ext_node_size();
+                write_header(&mut buf[..size], chunk.len(), depth);
+                for (i, raw) in chunk.iter().enumerate() {
+                    buf[EXT_HEADER_SIZE + i * EXT_ENTRY_SIZE..][..EXT_ENTRY_SIZE]
+                        .copy_from_slice(raw);
+                }
+                if self.has_metadata_csum() {
+                    let csum = crc32c(seed, &buf[..size]);
+                    put_u32(&mut buf, size, csum);
+                }
+                self.write_block(block, &buf)?;
+
+                let mut index = [0; EXT_ENTRY_SIZE];
+                put_u32(&mut index, 0, index_key(&chunk[0]));
+                put_u32(&mut index, 4, block as u32);
+                put_u16(&mut index, 8, (block >> 32) as u16);
+                parents.push(index);
+            }
+            items = parents;
+            depth += 1;
+        }
+        let root = inode.i_block_mut();
+        root.fill(0);
+        write_header(root, items.len(), depth);
+        for (i, raw) in items.iter().enumerate() {
+            root[EXT_HEADER_SIZE + i * EXT_ENTRY_SIZE..][..EXT_ENTRY_SIZE].copy_from_slice(raw);
+        }
+        Ok(())
+    }
+
+    /// Maps a logical block by the legacy block map.
+    fn map_indirect(&mut self, inode: &Inode, lblk: u32) -> VfsResult<Option<u64>> {
+        let per_block = (self.block_size / 4) as u32;
+        let mut path = [0; 3];
+        let (slot, levels) = if lblk < NDIR_BLOCKS {
+            (lblk, 0)
+        } else if lblk - NDIR_BLOCKS < per_block {
+            path[0] = lblk - NDIR_BLOCKS;
+            (NDIR_BLOCKS, 1)
+        } else if ((lblk - NDIR_BLOCKS - per_block) as u64) < (per_block as u64).pow(2) {
+            let n = lblk - NDIR_BLOCKS - per_block;
+            path[..2].copy_from_slice(&[n / per_block, n % per_block]);
+            (NDIR_BLOCKS + 1, 2)
+        } else {
+            let n = lblk - NDIR_BLOCKS - per_block - per_block * per_block;
+            let per_block2 = per_block * per_block;
+            path = [n / per_block2, n / per_block % per_block, n % per_block];
+            (NDIR_BLOCKS + 2, 3)
+        };
+        let mut block = get_u32(inode.i_block(), slot as usize * 4) as u64;
+        let mut buf = vec![0; self.block_size];
+        for &index in &path[..levels] {
+            if block == 0 {
+                break;
+            }
+            self.read_block(block, &mut buf)?;
+            block = get_u32(&buf, index as usize * 4) as u64;
+        }
+        Ok((block != 0).then_some(block))
+    }
+
+    /// Collects all the blocks of a legacy block map, including the indirect
+    /// blocks.
+    pub fn indirect_blocks(&mut self, inode: &Inode) -> VfsResult<Vec<u64>> {
+        let mut blocks = Vec::new();
+        for slot in 0..N_BLOCKS {
+            let block = get_u32(inode.i_block(), slot * 4) as u64;
+            let levels = (slot as u32).saturating_sub(NDIR_BLOCKS - 1);
+            self.collect_indirect(block, levels, &mut blocks)?;
+        }
+        Ok(blocks)
+    }
+
+    fn collect_indirect(&mut self, block: u64, levels: u32, blocks: &mut Vec<u64>) -> VfsResult {
+        if block == 0 {
+            return Ok(());
+        }
+        blocks.push(block);
+        if levels > 0 {
+            let mut buf = vec![0; self.block_size];
+            self.read_block(block, &mut buf)?;
+            for i in 0..self.block_size / 4 {
+                self.collect_indirect(get_u32(&buf, i * 4) as u64, levels - 1, blocks)?;
+            }
+        }
+        Ok(())
+    }
+}
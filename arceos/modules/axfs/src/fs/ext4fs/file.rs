@@ -0,0 +1,349 @@
+//! Contents of inodes: reading, writing and truncating the data, and
+//! releasing unlinked inodes.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use axfs_vfs::{VfsError, VfsResult};
+
+use super::crc::crc32c;
+use super::extent::{find_extent, merge_extents, Extent, EXT_INIT_MAX_LEN};
+use super::layout::*;
+use super::volume::{now, Volume};
+
+impl Volume {
+    pub fn read_data(&mut self, inode: &Inode, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let size = inode.size();
+        if offset >= size {
+            return Ok(0);
+        }
+        let len = buf.len().min((size - offset) as usize);
+        let bs = self.block_size as u64;
+        let mut block = vec![0; self.block_size];
+        let mut pos = offset;
+        while pos < offset + len as u64 {
+            let start = (pos % bs) as usize;
+            let count = (self.block_size - start).min((offset + len as u64 - pos) as usize);
+            let dst = &mut buf[(pos - offset) as usize..][..count];
+            match self.map_block(inode, (pos / bs) as u32)? {
+                Some((pblk, false)) if count == self.block_size => self.read_block(pblk, dst)?,
+                Some((pblk, false)) => {
+                    self.read_block(pblk, &mut block)?;
+                    dst.copy_from_slice(&block[start..start + count]);
+                }
+                _ => dst.fill(0), // holes or unwritten
+            }
+            pos += count as u64;
+        }
+        Ok(len)
+    }
+
+    /// Writes data to the inode, allocating blocks for the holes. The inode
+    /// is updated and written.
+    pub fn write_data(
+        &mut self,
+        ino: u32,
+        inode: &mut Inode,
+        offset: u64,
+        buf: &[u8],
+    ) -> VfsResult<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let bs = self.block_size as u64;
+        let end = offset + buf.len() as u64;
+        let first = (offset / bs) as u32;
+        let last = u32::try_from((end - 1) / bs).map_err(|_| VfsError::InvalidInput)?;
+
+        // Blocks that are new or were unwritten, to fill with zeros, and
+        // whether they are new.
+        let mut fresh = Vec::new();
+        let mut extents = Vec::new();
+        if inode.has_flags(INODE_EXTENTS_FL) {
+            let (mut exts, nodes) = self.read_extents(inode)?;
+            if self.prepare_write(ino, inode, &mut exts, first, last, &mut fresh)? {
+                merge_extents(&mut exts);
+                if let Err(err) = self.write_extents(ino, inode, &exts, nodes) {
+                    self.free_fresh(&fresh)?;
+                    return Err(err);
+                }
+            }
+            extents = exts;
+        } else {
+            // Only blocks already mapped can be overwritten.
+            for lblk in first..=last {
+                let Some((pblk, _)) = self.map_block(inode, lblk)? else {
+                    return Err(VfsError::Unsupported);
+                };
+                extents.push(Extent {
+                    lblk,
+                    len: 1,
+                    pblk,
+                    unwritten: false,
+                });
+            }
+        }
+
+        let mut block = vec![0; self.block_size];
+        let mut pos = offset;
+        while pos < end {
+            let lblk = (pos / bs) as u32;
+            let start = (pos % bs) as usize;
+            let count = (self.block_size - start).min((end - pos) as usize);
+            let src = &buf[(pos - offset) as usize..][..count];
+            let ext = extents[find_extent(&extents, lblk).unwrap()];
+            let pblk = ext.pblk + (lblk - ext.lblk) as u64;
+            if count == self.block_size {
+                self.write_block(pblk, src)?;
+            } else {
+                if fresh
+                    .iter()
+                    .any(|(ext, _): &(Extent, bool)| (ext.lblk..ext.end()).contains(&lblk))
+                {
+                    block.fill(0);
+                } else {
+                    self.read_block(pblk, &mut block)?;
+                }
+                block[start..start + count].copy_from_slice(src);
+                self.write_block(pblk, &block)?;
+            }
+            pos += count as u64;
+        }
+
+        if end > inode.size() {
+            inode.set_size(end);
+        }
+        let time = now();
+        inode.set_mtime(time);
+        inode.set_ctime(time);
+        self.write_inode(ino, inode)?;
+        Ok(buf.len())
+    }
+
+    /// Changes the extents so that the blocks from `first` to `last` are
+    /// allocated and initialized, recording the changed ones in `fresh`, and
+    /// whether they are newly allocated.
+    ///
+    /// Returns whether the extents are changed.
+    fn prepare_write(
+        &mut self,
+        ino: u32,
+        inode: &mut Inode,
+        extents: &mut Vec<Extent>,
+        first: u32,
+        last: u32,
+        fresh: &mut Vec<(Extent, bool)>,
+    ) -> VfsResult<bool> {
+        let mut lblk = first;
+        while lblk <= last {
+            match find_extent(extents, lblk) {
+                Ok(i) => {
+                    let ext = extents[i];
+                    let end = ext.end().min(last + 1);
+                    if ext.unwritten {
+                        // Split out the written part.
+                        let mut parts = Vec::new();
+                        if ext.lblk < lblk {
+                            parts.push(Extent {
+                                len: lblk - ext.lblk,
+                                ..ext
+                            });
+                        }
+                        let written = Extent {
+                            lblk,
+                            len: end - lblk,
+                            pblk: ext.pblk + (lblk - ext.lblk) as u64,
+                            unwritten: false,
+                        };
+                        parts.push(written);
+                        if end < ext.end() {
+                            parts.push(Extent {
+                                lblk: end,
+                                len: ext.end() - end,
+                                pblk: ext.pblk + (end - ext.lblk) as u64,
+                                unwritten: true,
+                            });
+                        }
+                        extents.splice(i..i + 1, parts);
+                        fresh.push((written, false));
+                    }
+                    lblk = end;
+                }
+                Err(mut pos) => {
+                    let hole_end = extents
+                        .get(pos)
+                        .map_or(last + 1, |ext| ext.lblk.min(last + 1));
+                    let mut goal = match pos.checked_sub(1).map(|i| extents[i]) {
+                        Some(prev) => prev.pblk + (lblk - prev.lblk) as u64,
+                        None => self.inode_goal(ino),
+                    };
+                    while lblk < hole_end {
+                        let count = (hole_end - lblk).min(EXT_INIT_MAX_LEN);
+                        let (pblk, n) = match self.alloc_blocks(goal, count) {
+                            Ok(res) => res,
+                            Err(err) => {
+                                self.free_fresh(fresh)?;
+                                return Err(err);
+                            }
+                        };
+                        let ext = Extent {
+                            lblk,
+                            len: n,
+                            pblk,
+                            unwritten: false,
+                        };
+                        extents.insert(pos, ext);
+                        fresh.push((ext, true));
+                        let sectors = inode.blocks(self.block_size);
+                        inode.set_blocks(sectors + n as u64 * (self.block_size as u64 / 512));
+                        pos += 1;
+                        lblk += n;
+                        goal = pblk + n as u64;
+                    }
+                }
+            }
+        }
+        Ok(!fresh.is_empty())
+    }
+
+    /// Frees the blocks newly allocated by a failed write.
+    fn free_fresh(&mut self, fresh: &[(Extent, bool)]) -> VfsResult {
+        for (ext, _) in fresh.iter().filter(|(_, new)| *new) {
+            self.free_blocks(ext.pblk, ext.len as u64)?;
+        }
+        Ok(())
+    }
+
+    /// Sets the size of the inode, releasing the blocks beyond it. The inode
+    /// is updated and written.
+    pub fn truncate_data(&mut self, ino: u32, inode: &mut Inode, size: u64) -> VfsResult {
+        let bs = self.block_size as u64;
+        if size < inode.size() {
+            let nblocks = u32::try_from(size.div_ceil(bs)).map_err(|_| VfsError::InvalidInput)?;
+            if inode.has_flags(INODE_EXTENTS_FL) {
+                let (mut extents, nodes) = self.read_extents(inode)?;
+                let mut freed = 0;
+                for ext in extents.iter_mut().rev() {
+                    if ext.end() <= nblocks {
+                        break;
+                    }
+                    let keep = nblocks.saturating_sub(ext.lblk);
+                    self.free_blocks(ext.pblk + keep as u64, (ext.len - keep) as u64)?;
+                    freed += (ext.len - keep) as u64;
+                    ext.len = keep;
+                }
+                extents.retain(|ext| ext.len > 0);
+                let sectors = inode.blocks(self.block_size);
+                inode.set_blocks(sectors - freed * (bs / 512));
+                self.write_extents(ino, inode, &extents, nodes)?;
+            } else if size == 0 {
+                for block in self.indirect_blocks(inode)? {
+                    self.free_blocks(block, 1)?;
+                }
+                inode.i_block_mut().fill(0);
+                inode.set_blocks(0);
+                self.init_extents(inode);
+            } else {
+                return Err(VfsError::Unsupported);
+            }
+            // Clear the tail of the last block, which may be read after the
+            // file is extended.
+            if size % bs != 0 {
+                if let Some((pblk, false)) = self.map_block(inode, (size / bs) as u32)? {
+                    let mut block = vec![0; self.block_size];
+                    self.read_block(pblk, &mut block)?;
+                    block[(size % bs) as usize..].fill(0);
+                    self.write_block(pblk, &block)?;
+                }
+            }
+        }
+        inode.set_size(size);
+        let time = now();
+        inode.set_mtime(time);
+        inode.set_ctime(time);
+        self.write_inode(ino, inode)
+    }
+
+    /// Whether the inode is a symbolic link with the target stored in it,
+    /// which has no data blocks.
+    fn is_fast_symlink(&self, inode: &Inode) -> bool {
+        let xattr_sectors = if inode.file_acl() != 0 {
+            self.block_size as u64 / 512
+        } else {
+            0
+        };
+        inode.is_symlink() && inode.blocks(self.block_size) == xattr_sectors
+    }
+
+    /// Reads the target of a symbolic link.
+    pub fn read_link(&mut self, inode: &Inode) -> VfsResult<Vec<u8>> {
+        let size = inode.size() as usize;
+        if self.is_fast_symlink(inode) {
+            if size > I_BLOCK_SIZE {
+                return Err(VfsError::InvalidData);
+            }
+            return Ok(inode.i_block()[..size].to_vec());
+        }
+        let mut target = vec![0; size];
+        self.read_data(inode, 0, &mut target)?;
+        Ok(target)
+    }
+
+    /// Writes the target of a new symbolic link, into the inode itself if it
+    /// fits, like Linux.
+    pub fn write_link(&mut self, ino: u32, inode: &mut Inode, target: &[u8]) -> VfsResult {
+        if target.len() >= self.block_size {
+            return Err(VfsError::NameTooLong);
+        }
+        if target.len() < I_BLOCK_SIZE {
+            inode.i_block_mut()[..target.len()].copy_from_slice(target);
+            inode.set_size(target.len() as u64);
+            return self.write_inode(ino, inode);
+        }
+        self.init_extents(inode);
+        self.write_data(ino, inode, 0, target)?;
+        Ok(())
+    }
+
+    /// Drops a link to the inode, and releases it with all its blocks if it
+    /// is the last one. Directories are released at once.
+    pub fn unlink_inode(&mut self, ino: u32, inode: &mut Inode) -> VfsResult {
+        let links = inode.links_count();
+        if !inode.is_dir() && links > 1 {
+            inode.set_links_count(links - 1);
+            inode.set_ctime(now());
+            return self.write_inode(ino, inode);
+        }
+        if !self.is_fast_symlink(inode) {
+            self.truncate_data(ino, inode, 0)?;
+        }
+        let xattr_block = inode.file_acl();
+        if xattr_block != 0 {
+            self.release_xattr_block(xattr_block)?;
+        }
+        inode.set_links_count(0);
+        inode.set_dtime(now());
+        self.write_inode(ino, inode)?;
+        self.free_inode(ino, inode.is_dir())
+    }
+
+    /// Drops a reference to a shared block of extended attributes.
+    fn release_xattr_block(&mut self, block: u64) -> VfsResult {
+        const REFCOUNT: usize = 0x4;
+        const CSUM: usize = 0x10;
+        let mut buf = vec![0; self.block_size];
+        self.read_block(block, &mut buf)?;
+        let refcount = get_u32(&buf, REFCOUNT);
+        if refcount <= 1 {
+            return self.free_blocks(block, 1);
+        }
+        put_u32(&mut buf, REFCOUNT, refcount - 1);
+        if self.has_metadata_csum() {
+            put_u32(&mut buf, CSUM, 0);
+            let csum = crc32c(self.csum_seed(), &block.to_le_bytes());
+            let csum = crc32c(csum, &buf);
+            put_u32(&mut buf, CSUM, csum);
+        }
+        self.write_block(block, &buf)
+    }
+}
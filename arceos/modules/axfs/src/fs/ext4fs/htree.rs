@@ -0,0 +1,350 @@
+//! Hashed directory indexes (htree).
+//!
+//! The index is used for lookups and to insert entries into the right leaf
+//! blocks. When a leaf block is full, the index is dropped rather than split,
+//! and the directory is used as a linear one, which is still valid.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use axfs_vfs::VfsResult;
+
+use super::dir::{EntryLoc, DIRENT_TAIL_SIZE};
+use super::layout::*;
+use super::volume::Volume;
+
+const DX_HASH_LEGACY: u8 = 0;
+const DX_HASH_HALF_MD4: u8 = 1;
+const DX_HASH_TEA: u8 = 2;
+const DX_HASH_LEGACY_UNSIGNED: u8 = 3;
+const DX_HASH_HALF_MD4_UNSIGNED: u8 = 4;
+const DX_HASH_TEA_UNSIGNED: u8 = 5;
+
+/// The offset of `dx_root_info`, after the entries of "." and "..".
+const DX_ROOT_INFO: usize = 0x18;
+/// The offset of the entries in the nodes other than the root, after a fake
+/// empty directory entry.
+const DX_NODE_ENTRIES: usize = 0x8;
+const DX_ENTRY_SIZE: usize = 8;
+/// The largest hash, reserved for the end of directories.
+const DX_HASH_EOF: u32 = 0x7fff_ffff;
+
+fn str2hashbuf(msg: &[u8], buf: &mut [u32], unsigned: bool) {
+    let len = msg.len();
+    let mut pad = len as u32 | (len as u32) << 8;
+    pad |= pad << 16;
+    let mut val = pad;
+    let max_len = buf.len() * 4;
+    let mut words = buf.iter_mut();
+    for (i, &c) in msg.iter().take(max_len).enumerate() {
+        let c = if unsigned {
+            c as u32
+        } else {
+            c as i8 as i32 as u32
+        };
+        val = c.wrapping_add(val << 8);
+        if i % 4 == 3 {
+            *words.next().unwrap() = val;
+            val = pad;
+        }
+    }
+    // The partial word, which is `pad` if there is none.
+    if let Some(word) = words.next() {
+        *word = val;
+    }
+    words.for_each(|word| *word = pad);
+}
+
+fn tea_transform(buf: &mut [u32; 4], data: &[u32]) {
+    const DELTA: u32 = 0x9e37_79b9;
+    let (mut b0, mut b1) = (buf[0], buf[1]);
+    let (a, b, c, d) = (data[0], data[1], data[2], data[3]);
+    let mut sum = 0u32;
+    for _ in 0..16 {
+        sum = sum.wrapping_add(DELTA);
+        b0 = b0.wrapping_add(
+            (b1 << 4).wrapping_add(a) ^ b1.wrapping_add(sum) ^ (b1 >> 5).wrapping_add(b),
+        );
+        b1 = b1.wrapping_add(
+            (b0 << 4).wrapping_add(c) ^ b0.wrapping_add(sum) ^ (b0 >> 5).wrapping_add(d),
+        );
+    }
+    buf[0] = buf[0].wrapping_add(b0);
+    buf[1] = buf[1].wrapping_add(b1);
+}
+
+fn half_md4_transform(buf: &mut [u32; 4], data: &[u32]) {
+    const K2: u32 = 0o13240474631;
+    const K3: u32 = 0o15666365641;
+    let f = |x: u32, y: u32, z: u32| z ^ (x & (y ^ z));
+    let g = |x: u32, y: u32, z: u32| (x & y).wrapping_add((x ^ y) & z);
+    let h = |x: u32, y: u32, z: u32| x ^ y ^ z;
+    let [mut a, mut b, mut c, mut d] = *buf;
+    macro_rules! round {
+        ($f:ident, $a:ident, $b:ident, $c:ident, $d:ident, $x:expr, $s:expr) => {
+            $a = $a
+                .wrapping_add($f($b, $c, $d))
+                .wrapping_add($x)
+                .rotate_left($s)
+        };
+    }
+    for i in [0, 4] {
+        round!(f, a, b, c, d, data[i], 3);
+        round!(f, d, a, b, c, data[i + 1], 7);
+        round!(f, c, d, a, b, data[i + 2], 11);
+        round!(f, b, c, d, a, data[i + 3], 19);
+    }
+    for i in [1, 0] {
+        round!(g, a, b, c, d, data[i].wrapping_add(K2), 3);
+        round!(g, d, a, b, c, data[i + 2].wrapping_add(K2), 5);
+        round!(g, c, d, a, b, data[i + 4].wrapping_add(K2), 9);
+        round!(g, b, c, d, a, data[i + 6].wrapping_add(K2), 13);
+    }
+    for i in [3, 1] {
+        round!(h, a, b, c, d, data[i].wrapping_add(K3), 3);
+        round!(h, d, a, b, c, data[i + 4].wrapping_add(K3), 9);
+        round!(h, c, d, a, b, data[i - 1].wrapping_add(K3), 11);
+        round!(h, b, c, d, a, data[i + 3].wrapping_add(K3), 15);
+    }
+    for (word, x) in buf.iter_mut().zip([a, b, c, d]) {
+        *word = word.wrapping_add(x);
+    }
+}
+
+fn legacy_hash(name: &[u8], unsigned: bool) -> u32 {
+    let (mut hash0, mut hash1) = (0x12a3_fe2du32, 0x37ab_e8f9u32);
+    for &c in name {
+        let c = if unsigned {
+            c as u32
+        } else {
+            c as i8 as i32 as u32
+        };
+        let mut hash = hash1.wrapping_add(hash0 ^ c.wrapping_mul(7_152_373));
+        if hash & 0x8000_0000 != 0 {
+            hash = hash.wrapping_sub(0x7fff_ffff);
+        }
+        hash1 = hash0;
+        hash0 = hash;
+    }
+    hash0 << 1
+}
+
+/// Computes the major hash of a name, or `None` for unknown versions.
+fn dx_hash(name: &[u8], version: u8, seed: [u32; 4]) -> Option<u32> {
+    let mut buf = [0x6745_2301, 0xefcd_ab89, 0x98ba_dcfe, 0x1032_5476];
+    if seed.iter().any(|&x| x != 0) {
+        buf = seed;
+    }
+    let hash = match version {
+        DX_HASH_LEGACY | DX_HASH_LEGACY_UNSIGNED => {
+            legacy_hash(name, version == DX_HASH_LEGACY_UNSIGNED)
+        }
+        DX_HASH_HALF_MD4 | DX_HASH_HALF_MD4_UNSIGNED => {
+            let mut data = [0; 8];
+            for off in (0..name.len()).step_by(32) {
+                str2hashbuf(
+                    &name[off..],
+                    &mut data,
+                    version == DX_HASH_HALF_MD4_UNSIGNED,
+                );
+                half_md4_transform(&mut buf, &data);
+            }
+            buf[1]
+        }
+        DX_HASH_TEA | DX_HASH_TEA_UNSIGNED => {
+            let mut data = [0; 4];
+            for off in (0..name.len()).step_by(16) {
+                str2hashbuf(&name[off..], &mut data, version == DX_HASH_TEA_UNSIGNED);
+                tea_transform(&mut buf, &data);
+            }
+            buf[0]
+        }
+        _ => return None,
+    };
+    let hash = hash & !1;
+    Some(if hash == DX_HASH_EOF << 1 {
+        (DX_HASH_EOF - 1) << 1
+    } else {
+        hash
+    })
+}
+
+/// The entries of an index node: the lowest hashes and the logical blocks of
+/// the children.
+type DxEntries = Vec<(u32, u32)>;
+
+/// A node of the index on the path to a leaf block.
+struct DxFrame {
+    entries: DxEntries,
+    pos: usize,
+}
+
+/// Parses the entries of an index node from `offset`, which starts with the
+/// limit and the count in place of the first hash.
+fn parse_dx_entries(block: &[u8], offset: usize) -> Option<DxEntries> {
+    let limit = get_u16(block, offset) as usize;
+    let count = get_u16(block, offset + 2) as usize;
+    if count == 0 || count > limit || offset + limit * DX_ENTRY_SIZE > block.len() {
+        return None;
+    }
+    let entries = (0..count).map(|i| {
+        let raw = &block[offset + i * DX_ENTRY_SIZE..];
+        let hash = if i == 0 { 0 } else { get_u32(raw, 0) };
+        (hash, get_u32(raw, 4) & 0x0fff_ffff)
+    });
+    Some(entries.collect())
+}
+
+impl Volume {
+    fn dx_read(&mut self, dir: &Inode, lblk: u32) -> VfsResult<Option<Vec<u8>>> {
+        let mut block = vec![0; self.block_size];
+        match self.map_block(dir, lblk)? {
+            Some((pblk, false)) => self.read_block(pblk, &mut block)?,
+            _ => return Ok(None),
+        }
+        Ok(Some(block))
+    }
+
+    /// Reads the root of the index, returning the hash version, the number of
+    /// levels of the index nodes, and the entries of the root.
+    fn dx_root(&mut self, dir: &Inode) -> VfsResult<Option<(u8, u8, DxEntries)>> {
+        let Some(block) = self.dx_read(dir, 0)? else {
+            return Ok(None);
+        };
+        let info = &block[DX_ROOT_INFO..];
+        let (mut version, info_len, levels) = (info[4], info[5] as usize, info[6]);
+        let max_levels = if self.sb.has_incompat(INCOMPAT_LARGEDIR) {
+            3
+        } else {
+            2
+        };
+        if get_u32(info, 0) != 0 || info_len != 8 || levels >= max_levels {
+            return Ok(None);
+        }
+        if version <= DX_HASH_TEA && self.sb.unsigned_hash() {
+            version += DX_HASH_LEGACY_UNSIGNED;
+        }
+        let entries = parse_dx_entries(&block, DX_ROOT_INFO + info_len);
+        Ok(entries.map(|entries| (version, levels, entries)))
+    }
+
+    /// Walks down the index to the leaf block that may contain `name`.
+    ///
+    /// Returns `None` if the index is broken or not understood.
+    fn dx_probe(&mut self, dir: &Inode, name: &[u8]) -> VfsResult<Option<(u32, Vec<DxFrame>)>> {
+        let Some((version, levels, entries)) = self.dx_root(dir)? else {
+            return Ok(None);
+        };
+        let Some(hash) = dx_hash(name, version, self.sb.hash_seed()) else {
+            return Ok(None);
+        };
+        let mut frames = Vec::new();
+        let mut entries = entries;
+        loop {
+            let pos = entries.partition_point(|&(h, _)| h <= hash) - 1;
+            let child = entries[pos].1;
+            frames.push(DxFrame { entries, pos });
+            if frames.len() > levels as usize {
+                return Ok(Some((hash, frames)));
+            }
+            let Some(block) = self.dx_read(dir, child)? else {
+                return Ok(None);
+            };
+            match parse_dx_entries(&block, DX_NODE_ENTRIES) {
+                Some(next) => entries = next,
+                None => return Ok(None),
+            }
+        }
+    }
+
+    /// Moves to the next leaf block if it may also contain entries with the
+    /// hash, i.e., the hash collides across the blocks.
+    fn dx_next_leaf(&mut self, dir: &Inode, hash: u32, frames: &mut [DxFrame]) -> VfsResult<bool> {
+        let Some(level) = frames.iter().rposition(|f| f.pos + 1 < f.entries.len()) else {
+            return Ok(false);
+        };
+        let frame = &mut frames[level];
+        frame.pos += 1;
+        if frame.entries[frame.pos].0 & !1 != hash {
+            return Ok(false);
+        }
+        for level in level + 1..frames.len() {
+            let child = frames[level - 1].entries[frames[level - 1].pos].1;
+            let Some(block) = self.dx_read(dir, child)? else {
+                return Ok(false);
+            };
+            let Some(entries) = parse_dx_entries(&block, DX_NODE_ENTRIES) else {
+                return Ok(false);
+            };
+            frames[level] = DxFrame { entries, pos: 0 };
+        }
+        Ok(true)
+    }
+
+    /// Looks up `name` by the index. Returns `None` if the index can't be
+    /// used.
+    pub fn dx_find(&mut self, dir: &Inode, name: &[u8]) -> VfsResult<Option<Option<EntryLoc>>> {
+        let Some((hash, mut frames)) = self.dx_probe(dir, name)? else {
+            return Ok(None);
+        };
+        loop {
+            let frame = frames.last().unwrap();
+            let leaf = frame.entries[frame.pos].1;
+            if let Some(loc) = self.find_in_block(dir, leaf, name)? {
+                return Ok(Some(Some(loc)));
+            }
+            if !self.dx_next_leaf(dir, hash, &mut frames)? {
+                return Ok(Some(None));
+            }
+        }
+    }
+
+    /// Returns the leaf block where a new entry of `name` should be inserted.
+    pub fn dx_leaf(&mut self, dir: &Inode, name: &[u8]) -> VfsResult<Option<u32>> {
+        let frames = self.dx_probe(dir, name)?;
+        Ok(frames.map(|(_, frames)| {
+            let frame = frames.last().unwrap();
+            frame.entries[frame.pos].1
+        }))
+    }
+
+    /// Drops the index of the directory, turning it into a linear one. The
+    /// inode is updated but not written.
+    ///
+    /// The index nodes are already valid empty directory blocks, but they
+    /// need the checksum tails of directory blocks with `metadata_csum`.
+    pub fn dx_drop_index(&mut self, ino: u32, dir: &mut Inode) -> VfsResult {
+        if self.has_metadata_csum() {
+            let mut nodes = Vec::new();
+            if let Some((_, levels, entries)) = self.dx_root(dir)? {
+                let mut children: Vec<u32> = entries.iter().map(|e| e.1).collect();
+                for _ in 0..levels {
+                    let mut next = Vec::new();
+                    for &child in &children {
+                        nodes.push(child);
+                        let Some(block) = self.dx_read(dir, child)? else {
+                            continue;
+                        };
+                        let entries = parse_dx_entries(&block, DX_NODE_ENTRIES).unwrap_or_default();
+                        next.extend(entries.iter().map(|e| e.1));
+                    }
+                    children = next;
+                }
+            }
+            let usable = self.block_size - DIRENT_TAIL_SIZE;
+            if let Some(mut block) = self.dx_read(dir, 0)? {
+                // Keep the entries of "." and "..".
+                put_u16(&mut block, 12 + 4, (usable - 12) as u16);
+                block[24..].fill(0);
+                self.write_dir_block(ino, dir, 0, &mut block)?;
+            }
+            for lblk in nodes {
+                let mut block = vec![0; self.block_size];
+                put_u16(&mut block, 4, usable as u16);
+                self.write_dir_block(ino, dir, lblk, &mut block)?;
+            }
+        }
+        dir.set_flags(dir.flags() & !INODE_INDEX_FL);
+        Ok(())
+    }
+}
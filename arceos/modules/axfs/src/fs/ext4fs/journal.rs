@@ -0,0 +1,250 @@
+//! Replay of the jbd2 journal.
+//!
+//! Only the committed transactions are replayed, in the three passes of
+//! Linux: finding the end of the log, collecting the revoked blocks, and
+//! writing the logged blocks to their home locations. Fast commits and the
+//! checksums of the log are ignored. The fields of the journal are
+//! big-endian.
+
+use alloc::collections::BTreeMap;
+use alloc::vec;
+
+use axfs_vfs::{VfsError, VfsResult};
+
+use super::crc::crc32c;
+use super::layout::Inode;
+use super::volume::Volume;
+
+const JBD2_MAGIC: u32 = 0xc03b_3998;
+
+const BLOCK_DESCRIPTOR: u32 = 1;
+const BLOCK_COMMIT: u32 = 2;
+const BLOCK_SUPERBLOCK_V1: u32 = 3;
+const BLOCK_SUPERBLOCK_V2: u32 = 4;
+const BLOCK_REVOKE: u32 = 5;
+
+const FEATURE_INCOMPAT_64BIT: u32 = 0x2;
+const FEATURE_INCOMPAT_CSUM_V2: u32 = 0x8;
+const FEATURE_INCOMPAT_CSUM_V3: u32 = 0x10;
+const FEATURE_INCOMPAT_FAST_COMMIT: u32 = 0x20;
+const DEFAULT_FAST_COMMIT_BLOCKS: u32 = 256;
+
+const TAG_FLAG_ESCAPE: u32 = 0x1;
+const TAG_FLAG_SAME_UUID: u32 = 0x2;
+const TAG_FLAG_LAST_TAG: u32 = 0x8;
+
+const HEADER_SIZE: usize = 12;
+const UUID_SIZE: usize = 16;
+
+fn get_be32(buf: &[u8], off: usize) -> u32 {
+    u32::from_be_bytes(buf[off..off + 4].try_into().unwrap())
+}
+
+fn get_be16(buf: &[u8], off: usize) -> u16 {
+    u16::from_be_bytes([buf[off], buf[off + 1]])
+}
+
+fn put_be32(buf: &mut [u8], off: usize, val: u32) {
+    buf[off..off + 4].copy_from_slice(&val.to_be_bytes());
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Pass {
+    Scan,
+    Revoke,
+    Replay,
+}
+
+struct Journal {
+    inode: Inode,
+    /// The first and the end of log blocks in the journal.
+    first: u32,
+    last: u32,
+    /// Where the log starts, and its first sequence number.
+    start: u32,
+    sequence: u32,
+    incompat: u32,
+}
+
+impl Journal {
+    fn has_incompat(&self, mask: u32) -> bool {
+        self.incompat & mask != 0
+    }
+
+    fn next(&self, block: u32) -> u32 {
+        if block + 1 >= self.last {
+            self.first
+        } else {
+            block + 1
+        }
+    }
+
+    fn tag_size(&self) -> usize {
+        if self.has_incompat(FEATURE_INCOMPAT_CSUM_V3) {
+            return 16;
+        }
+        let mut size = 12;
+        if self.has_incompat(FEATURE_INCOMPAT_CSUM_V2) {
+            size += 2;
+        }
+        if !self.has_incompat(FEATURE_INCOMPAT_64BIT) {
+            size -= 4;
+        }
+        size
+    }
+
+    fn has_csum(&self) -> bool {
+        self.has_incompat(FEATURE_INCOMPAT_CSUM_V2 | FEATURE_INCOMPAT_CSUM_V3)
+    }
+}
+
+impl Volume {
+    fn read_journal_block(&mut self, journal: &Journal, lblk: u32, buf: &mut [u8]) -> VfsResult {
+        match self.map_block(&journal.inode, lblk)? {
+            Some((pblk, false)) => self.read_block(pblk, buf),
+            _ => Err(VfsError::InvalidData),
+        }
+    }
+
+    /// Replays the committed transactions in the journal and marks it empty.
+    pub fn replay_journal(&mut self) -> VfsResult {
+        let ino = self.sb.journal_inum();
+        if ino == 0 {
+            warn!("ext4: external journals are not supported");
+            return Err(VfsError::Unsupported);
+        }
+        let mut journal = Journal {
+            inode: self.read_inode(ino)?,
+            first: 0,
+            last: 0,
+            start: 0,
+            sequence: 0,
+            incompat: 0,
+        };
+        let mut jsb = vec![0; self.block_size];
+        self.read_journal_block(&journal, 0, &mut jsb)?;
+        let blocktype = get_be32(&jsb, 4);
+        if get_be32(&jsb, 0) != JBD2_MAGIC
+            || (blocktype != BLOCK_SUPERBLOCK_V1 && blocktype != BLOCK_SUPERBLOCK_V2)
+            || get_be32(&jsb, 0xc) as usize != self.block_size
+        {
+            warn!("ext4: bad journal superblock");
+            return Err(VfsError::InvalidData);
+        }
+        journal.first = get_be32(&jsb, 0x14);
+        journal.last = get_be32(&jsb, 0x10);
+        journal.sequence = get_be32(&jsb, 0x18);
+        journal.start = get_be32(&jsb, 0x1c);
+        if blocktype == BLOCK_SUPERBLOCK_V2 {
+            journal.incompat = get_be32(&jsb, 0x28);
+        }
+        if journal.has_incompat(FEATURE_INCOMPAT_FAST_COMMIT) {
+            let fc_blocks = match get_be32(&jsb, 0x54) {
+                0 => DEFAULT_FAST_COMMIT_BLOCKS,
+                n => n,
+            };
+            journal.last -= fc_blocks;
+        }
+        if journal.start == 0 {
+            return Ok(()); // empty
+        }
+
+        let mut revoked = BTreeMap::new();
+        let end = self.journal_pass(&journal, Pass::Scan, u32::MAX, &mut revoked)?;
+        self.journal_pass(&journal, Pass::Revoke, end, &mut revoked)?;
+        self.journal_pass(&journal, Pass::Replay, end, &mut revoked)?;
+        info!(
+            "ext4: replayed journal transactions {}..{}",
+            journal.sequence, end
+        );
+
+        put_be32(&mut jsb, 0x18, end);
+        put_be32(&mut jsb, 0x1c, 0);
+        if journal.has_csum() {
+            put_be32(&mut jsb, 0xfc, 0);
+            let csum = crc32c(!0, &jsb[..1024]);
+            put_be32(&mut jsb, 0xfc, csum);
+        }
+        let Some((pblk, _)) = self.map_block(&journal.inode, 0)? else {
+            return Err(VfsError::InvalidData);
+        };
+        self.write_block(pblk, &jsb)
+    }
+
+    /// Walks through the log until the transaction `end` or a block which is
+    /// not the next one. Returns the sequence number after the last commit.
+    fn journal_pass(
+        &mut self,
+        journal: &Journal,
+        pass: Pass,
+        end: u32,
+        revoked: &mut BTreeMap<u64, u32>,
+    ) -> VfsResult<u32> {
+        let mut buf = vec![0; self.block_size];
+        let mut data = vec![0; self.block_size];
+        let mut block = journal.start;
+        let mut sequence = journal.sequence;
+        let is_64bit = journal.has_incompat(FEATURE_INCOMPAT_64BIT);
+        while sequence != end {
+            self.read_journal_block(journal, block, &mut buf)?;
+            if get_be32(&buf, 0) != JBD2_MAGIC || get_be32(&buf, 8) != sequence {
+                break;
+            }
+            block = journal.next(block);
+            match get_be32(&buf, 4) {
+                BLOCK_DESCRIPTOR => {
+                    let tail = if journal.has_csum() { 4 } else { 0 };
+                    let mut off = HEADER_SIZE;
+                    while off + journal.tag_size() <= self.block_size - tail {
+                        let tag = &buf[off..];
+                        let flags = if journal.has_incompat(FEATURE_INCOMPAT_CSUM_V3) {
+                            get_be32(tag, 4)
+                        } else {
+                            get_be16(tag, 6) as u32
+                        };
+                        let mut target = get_be32(tag, 0) as u64;
+                        if is_64bit {
+                            target |= (get_be32(tag, 8) as u64) << 32;
+                        }
+                        let is_revoked = revoked.get(&target).is_some_and(|&seq| seq >= sequence);
+                        if pass == Pass::Replay && !is_revoked {
+                            self.read_journal_block(journal, block, &mut data)?;
+                            if flags & TAG_FLAG_ESCAPE != 0 {
+                                put_be32(&mut data, 0, JBD2_MAGIC);
+                            }
+                            self.write_block(target, &data)?;
+                        }
+                        block = journal.next(block);
+                        off += journal.tag_size();
+                        if flags & TAG_FLAG_SAME_UUID == 0 {
+                            off += UUID_SIZE;
+                        }
+                        if flags & TAG_FLAG_LAST_TAG != 0 {
+                            break;
+                        }
+                    }
+                }
+                BLOCK_COMMIT => sequence = sequence.wrapping_add(1),
+                BLOCK_REVOKE => {
+                    if pass == Pass::Revoke {
+                        let record_size = if is_64bit { 8 } else { 4 };
+                        let count = (get_be32(&buf, HEADER_SIZE) as usize).min(self.block_size);
+                        let mut off = HEADER_SIZE + 4;
+                        while off + record_size <= count {
+                            let target = if is_64bit {
+                                (get_be32(&buf, off) as u64) << 32 | get_be32(&buf, off + 4) as u64
+                            } else {
+                                get_be32(&buf, off) as u64
+                            };
+                            let seq = revoked.entry(target).or_insert(sequence);
+                            *seq = (*seq).max(sequence);
+                            off += record_size;
+                        }
+                    }
+                }
+                _ => break,
+            }
+        }
+        Ok(sequence)
+    }
+}
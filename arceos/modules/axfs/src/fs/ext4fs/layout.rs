@@ -0,0 +1,568 @@
+//! On-disk structures of ext4.
+//!
+//! The structures are kept as raw little-endian bytes, so that the fields
+//! unknown to us are preserved when written back.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use super::crc::{crc16, crc32c};
+
+pub const SUPERBLOCK_OFFSET: u64 = 1024;
+pub const SUPERBLOCK_SIZE: usize = 1024;
+pub const EXT4_MAGIC: u16 = 0xef53;
+pub const MAGIC_OFFSET: usize = 0x38;
+
+pub const ROOT_INO: u32 = 2;
+/// The number of 32-bit words in `i_block`.
+pub const N_BLOCKS: usize = 15;
+/// The size of `i_block` in bytes.
+pub const I_BLOCK_SIZE: usize = N_BLOCKS * 4;
+const GOOD_OLD_INODE_SIZE: usize = 128;
+
+pub const COMPAT_HAS_JOURNAL: u32 = 0x4;
+pub const COMPAT_DIR_INDEX: u32 = 0x20;
+
+pub const INCOMPAT_FILETYPE: u32 = 0x2;
+pub const INCOMPAT_RECOVER: u32 = 0x4;
+pub const INCOMPAT_META_BG: u32 = 0x10;
+pub const INCOMPAT_EXTENTS: u32 = 0x40;
+pub const INCOMPAT_64BIT: u32 = 0x80;
+pub const INCOMPAT_FLEX_BG: u32 = 0x200;
+pub const INCOMPAT_CSUM_SEED: u32 = 0x2000;
+pub const INCOMPAT_LARGEDIR: u32 = 0x4000;
+/// The incompatible features we understand. Mounting fails with others.
+pub const INCOMPAT_SUPPORTED: u32 = INCOMPAT_FILETYPE
+    | INCOMPAT_RECOVER
+    | INCOMPAT_META_BG
+    | INCOMPAT_EXTENTS
+    | INCOMPAT_64BIT
+    | INCOMPAT_FLEX_BG
+    | INCOMPAT_CSUM_SEED
+    | INCOMPAT_LARGEDIR;
+
+pub const RO_COMPAT_SPARSE_SUPER: u32 = 0x1;
+pub const RO_COMPAT_LARGE_FILE: u32 = 0x2;
+pub const RO_COMPAT_HUGE_FILE: u32 = 0x8;
+pub const RO_COMPAT_GDT_CSUM: u32 = 0x10;
+pub const RO_COMPAT_DIR_NLINK: u32 = 0x20;
+pub const RO_COMPAT_EXTRA_ISIZE: u32 = 0x40;
+pub const RO_COMPAT_METADATA_CSUM: u32 = 0x400;
+/// The read-only compatible features we keep consistent when writing. The
+/// filesystem is mounted read-only with others.
+pub const RO_COMPAT_SUPPORTED: u32 = RO_COMPAT_SPARSE_SUPER
+    | RO_COMPAT_LARGE_FILE
+    | RO_COMPAT_HUGE_FILE
+    | RO_COMPAT_GDT_CSUM
+    | RO_COMPAT_DIR_NLINK
+    | RO_COMPAT_EXTRA_ISIZE
+    | RO_COMPAT_METADATA_CSUM;
+
+/// `s_flags`: the directory hashes treat characters as unsigned.
+const FLAGS_UNSIGNED_HASH: u32 = 0x2;
+
+pub const BG_INODE_UNINIT: u16 = 0x1;
+pub const BG_BLOCK_UNINIT: u16 = 0x2;
+
+pub const INODE_INDEX_FL: u32 = 0x1000;
+pub const INODE_HUGE_FILE_FL: u32 = 0x40000;
+pub const INODE_EXTENTS_FL: u32 = 0x80000;
+
+pub const S_IFMT: u16 = 0o170000;
+pub const S_IFIFO: u16 = 0o010000;
+pub const S_IFCHR: u16 = 0o020000;
+pub const S_IFDIR: u16 = 0o040000;
+pub const S_IFBLK: u16 = 0o060000;
+pub const S_IFREG: u16 = 0o100000;
+pub const S_IFLNK: u16 = 0o120000;
+pub const S_IFSOCK: u16 = 0o140000;
+
+/// The maximum number of hard links to an inode. Directories with more
+/// subdirectories have the link count 1 (`dir_nlink`).
+pub const LINK_MAX: u16 = 65000;
+
+pub fn get_u16(buf: &[u8], off: usize) -> u16 {
+    u16::from_le_bytes([buf[off], buf[off + 1]])
+}
+
+pub fn get_u32(buf: &[u8], off: usize) -> u32 {
+    u32::from_le_bytes(buf[off..off + 4].try_into().unwrap())
+}
+
+pub fn put_u16(buf: &mut [u8], off: usize, val: u16) {
+    buf[off..off + 2].copy_from_slice(&val.to_le_bytes());
+}
+
+pub fn put_u32(buf: &mut [u8], off: usize, val: u32) {
+    buf[off..off + 4].copy_from_slice(&val.to_le_bytes());
+}
+
+fn get_lo_hi(buf: &[u8], lo: usize, hi: Option<usize>) -> u64 {
+    get_u32(buf, lo) as u64 | hi.map_or(0, |hi| (get_u32(buf, hi) as u64) << 32)
+}
+
+fn put_lo_hi(buf: &mut [u8], lo: usize, hi: Option<usize>, val: u64) {
+    put_u32(buf, lo, val as u32);
+    if let Some(hi) = hi {
+        put_u32(buf, hi, (val >> 32) as u32);
+    }
+}
+
+/// The superblock, located at byte 1024 of the volume.
+pub struct SuperBlock {
+    raw: Vec<u8>,
+}
+
+impl SuperBlock {
+    pub fn from_bytes(raw: Vec<u8>) -> Self {
+        debug_assert_eq!(raw.len(), SUPERBLOCK_SIZE);
+        Self { raw }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.raw
+    }
+
+    pub fn magic(&self) -> u16 {
+        get_u16(&self.raw, MAGIC_OFFSET)
+    }
+
+    pub fn inodes_count(&self) -> u32 {
+        get_u32(&self.raw, 0x0)
+    }
+
+    fn hi(&self, off: usize) -> Option<usize> {
+        self.is_64bit().then_some(off)
+    }
+
+    pub fn blocks_count(&self) -> u64 {
+        get_lo_hi(&self.raw, 0x4, self.hi(0x150))
+    }
+
+    pub fn free_blocks_count(&self) -> u64 {
+        get_lo_hi(&self.raw, 0xc, self.hi(0x158))
+    }
+
+    pub fn set_free_blocks_count(&mut self, val: u64) {
+        let hi = self.hi(0x158);
+        put_lo_hi(&mut self.raw, 0xc, hi, val);
+    }
+
+    pub fn free_inodes_count(&self) -> u32 {
+        get_u32(&self.raw, 0x10)
+    }
+
+    pub fn set_free_inodes_count(&mut self, val: u32) {
+        put_u32(&mut self.raw, 0x10, val);
+    }
+
+    pub fn first_data_block(&self) -> u32 {
+        get_u32(&self.raw, 0x14)
+    }
+
+    pub fn log_block_size(&self) -> u32 {
+        get_u32(&self.raw, 0x18)
+    }
+
+    pub fn blocks_per_group(&self) -> u32 {
+        get_u32(&self.raw, 0x20)
+    }
+
+    pub fn inodes_per_group(&self) -> u32 {
+        get_u32(&self.raw, 0x28)
+    }
+
+    pub fn set_wtime(&mut self, time: u32) {
+        put_u32(&mut self.raw, 0x30, time);
+    }
+
+    fn rev_level(&self) -> u32 {
+        get_u32(&self.raw, 0x4c)
+    }
+
+    pub fn first_ino(&self) -> u32 {
+        if self.rev_level() == 0 {
+            11
+        } else {
+            get_u32(&self.raw, 0x54)
+        }
+    }
+
+    pub fn inode_size(&self) -> usize {
+        if self.rev_level() == 0 {
+            GOOD_OLD_INODE_SIZE
+        } else {
+            get_u16(&self.raw, 0x58) as usize
+        }
+    }
+
+    pub fn feature_compat(&self) -> u32 {
+        get_u32(&self.raw, 0x5c)
+    }
+
+    pub fn feature_incompat(&self) -> u32 {
+        get_u32(&self.raw, 0x60)
+    }
+
+    pub fn set_feature_incompat(&mut self, val: u32) {
+        put_u32(&mut self.raw, 0x60, val);
+    }
+
+    pub fn feature_ro_compat(&self) -> u32 {
+        get_u32(&self.raw, 0x64)
+    }
+
+    pub fn has_incompat(&self, mask: u32) -> bool {
+        self.feature_incompat() & mask != 0
+    }
+
+    pub fn has_ro_compat(&self, mask: u32) -> bool {
+        self.feature_ro_compat() & mask != 0
+    }
+
+    pub fn is_64bit(&self) -> bool {
+        self.has_incompat(INCOMPAT_64BIT)
+    }
+
+    pub fn has_metadata_csum(&self) -> bool {
+        self.has_ro_compat(RO_COMPAT_METADATA_CSUM)
+    }
+
+    pub fn uuid(&self) -> &[u8] {
+        &self.raw[0x68..0x78]
+    }
+
+    pub fn journal_inum(&self) -> u32 {
+        get_u32(&self.raw, 0xe0)
+    }
+
+    pub fn last_orphan(&self) -> u32 {
+        get_u32(&self.raw, 0xe8)
+    }
+
+    pub fn hash_seed(&self) -> [u32; 4] {
+        core::array::from_fn(|i| get_u32(&self.raw, 0xec + i * 4))
+    }
+
+    /// The size of a group descriptor.
+    pub fn desc_size(&self) -> usize {
+        let size = get_u16(&self.raw, 0xfe) as usize;
+        if self.is_64bit() && size >= 64 {
+            size
+        } else {
+            32
+        }
+    }
+
+    pub fn reserved_gdt_blocks(&self) -> u16 {
+        get_u16(&self.raw, 0xce)
+    }
+
+    /// The number of blocks of the inode table of a group.
+    pub fn inode_table_blocks(&self, block_size: usize) -> usize {
+        (self.inodes_per_group() as usize * self.inode_size()).div_ceil(block_size)
+    }
+
+    pub fn first_meta_bg(&self) -> u32 {
+        get_u32(&self.raw, 0x104)
+    }
+
+    pub fn want_extra_isize(&self) -> u16 {
+        get_u16(&self.raw, 0x15e)
+    }
+
+    pub fn unsigned_hash(&self) -> bool {
+        get_u32(&self.raw, 0x160) & FLAGS_UNSIGNED_HASH != 0
+    }
+
+    /// The seed of all the `metadata_csum` checksums.
+    pub fn csum_seed(&self) -> u32 {
+        if self.has_incompat(INCOMPAT_CSUM_SEED) {
+            get_u32(&self.raw, 0x270)
+        } else {
+            crc32c(!0, self.uuid())
+        }
+    }
+
+    /// Updates the checksum of the superblock itself before writing it.
+    pub fn update_csum(&mut self) {
+        if self.has_metadata_csum() {
+            let csum = crc32c(!0, &self.raw[..0x3fc]);
+            put_u32(&mut self.raw, 0x3fc, csum);
+        }
+    }
+}
+
+/// A block group descriptor.
+pub struct GroupDesc {
+    raw: Vec<u8>,
+}
+
+impl GroupDesc {
+    pub fn from_bytes(raw: &[u8]) -> Self {
+        Self { raw: raw.to_vec() }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.raw
+    }
+
+    fn hi(&self, off: usize) -> Option<usize> {
+        (self.raw.len() >= 64).then_some(off)
+    }
+
+    fn get_u16_lo_hi(&self, lo: usize, hi: usize) -> u32 {
+        get_u16(&self.raw, lo) as u32
+            | self
+                .hi(hi)
+                .map_or(0, |hi| (get_u16(&self.raw, hi) as u32) << 16)
+    }
+
+    fn put_u16_lo_hi(&mut self, lo: usize, hi: usize, val: u32) {
+        put_u16(&mut self.raw, lo, val as u16);
+        if let Some(hi) = self.hi(hi) {
+            put_u16(&mut self.raw, hi, (val >> 16) as u16);
+        }
+    }
+
+    pub fn block_bitmap(&self) -> u64 {
+        get_lo_hi(&self.raw, 0x0, self.hi(0x20))
+    }
+
+    pub fn inode_bitmap(&self) -> u64 {
+        get_lo_hi(&self.raw, 0x4, self.hi(0x24))
+    }
+
+    pub fn inode_table(&self) -> u64 {
+        get_lo_hi(&self.raw, 0x8, self.hi(0x28))
+    }
+
+    pub fn free_blocks_count(&self) -> u32 {
+        self.get_u16_lo_hi(0xc, 0x2c)
+    }
+
+    pub fn set_free_blocks_count(&mut self, val: u32) {
+        self.put_u16_lo_hi(0xc, 0x2c, val)
+    }
+
+    pub fn free_inodes_count(&self) -> u32 {
+        self.get_u16_lo_hi(0xe, 0x2e)
+    }
+
+    pub fn set_free_inodes_count(&mut self, val: u32) {
+        self.put_u16_lo_hi(0xe, 0x2e, val)
+    }
+
+    pub fn used_dirs_count(&self) -> u32 {
+        self.get_u16_lo_hi(0x10, 0x30)
+    }
+
+    pub fn set_used_dirs_count(&mut self, val: u32) {
+        self.put_u16_lo_hi(0x10, 0x30, val)
+    }
+
+    pub fn flags(&self) -> u16 {
+        get_u16(&self.raw, 0x12)
+    }
+
+    pub fn set_flags(&mut self, flags: u16) {
+        put_u16(&mut self.raw, 0x12, flags);
+    }
+
+    pub fn itable_unused(&self) -> u32 {
+        self.get_u16_lo_hi(0x1c, 0x32)
+    }
+
+    pub fn set_itable_unused(&mut self, val: u32) {
+        self.put_u16_lo_hi(0x1c, 0x32, val)
+    }
+
+    pub fn set_block_bitmap_csum(&mut self, csum: u32) {
+        self.put_u16_lo_hi(0x18, 0x38, csum)
+    }
+
+    pub fn set_inode_bitmap_csum(&mut self, csum: u32) {
+        self.put_u16_lo_hi(0x1a, 0x3a, csum)
+    }
+
+    /// Updates the checksum of the descriptor of group `group`.
+    pub fn update_csum(&mut self, sb: &SuperBlock, group: u32) {
+        const CSUM_OFFSET: usize = 0x1e;
+        let size = self.raw.len();
+        let csum = if sb.has_metadata_csum() {
+            let mut crc = crc32c(sb.csum_seed(), &group.to_le_bytes());
+            crc = crc32c(crc, &self.raw[..CSUM_OFFSET]);
+            crc = crc32c(crc, &[0; 2]);
+            crc32c(crc, &self.raw[CSUM_OFFSET + 2..size]) as u16
+        } else if sb.has_ro_compat(RO_COMPAT_GDT_CSUM) {
+            let mut crc = crc16(!0, sb.uuid());
+            crc = crc16(crc, &group.to_le_bytes());
+            crc = crc16(crc, &self.raw[..CSUM_OFFSET]);
+            crc16(crc, &self.raw[CSUM_OFFSET + 2..size])
+        } else {
+            return;
+        };
+        put_u16(&mut self.raw, CSUM_OFFSET, csum);
+    }
+}
+
+/// An inode.
+#[derive(Clone)]
+pub struct Inode {
+    raw: Vec<u8>,
+}
+
+impl Inode {
+    pub fn from_bytes(raw: &[u8]) -> Self {
+        Self { raw: raw.to_vec() }
+    }
+
+    /// Creates a zeroed inode of `size` bytes with the given mode, to be
+    /// written to a free slot in the inode table.
+    pub fn new(size: usize, extra_isize: u16, mode: u16, generation: u32) -> Self {
+        let mut inode = Self { raw: vec![0; size] };
+        put_u16(&mut inode.raw, 0x0, mode);
+        put_u32(&mut inode.raw, 0x64, generation);
+        if size > GOOD_OLD_INODE_SIZE {
+            put_u16(&mut inode.raw, 0x80, extra_isize);
+        }
+        inode
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.raw
+    }
+
+    pub fn mode(&self) -> u16 {
+        get_u16(&self.raw, 0x0)
+    }
+
+    pub fn file_type(&self) -> u16 {
+        self.mode() & S_IFMT
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.file_type() == S_IFDIR
+    }
+
+    pub fn is_symlink(&self) -> bool {
+        self.file_type() == S_IFLNK
+    }
+
+    pub fn size(&self) -> u64 {
+        get_lo_hi(&self.raw, 0x4, Some(0x6c))
+    }
+
+    pub fn set_size(&mut self, size: u64) {
+        put_lo_hi(&mut self.raw, 0x4, Some(0x6c), size);
+    }
+
+    pub fn set_atime(&mut self, time: u32) {
+        put_u32(&mut self.raw, 0x8, time);
+    }
+
+    pub fn set_ctime(&mut self, time: u32) {
+        put_u32(&mut self.raw, 0xc, time);
+    }
+
+    pub fn set_mtime(&mut self, time: u32) {
+        put_u32(&mut self.raw, 0x10, time);
+    }
+
+    pub fn set_dtime(&mut self, time: u32) {
+        put_u32(&mut self.raw, 0x14, time);
+    }
+
+    /// Sets the creation time, if there is room for it.
+    pub fn set_crtime(&mut self, time: u32) {
+        if self.extra_isize() >= 0x94 - GOOD_OLD_INODE_SIZE {
+            put_u32(&mut self.raw, 0x90, time);
+        }
+    }
+
+    pub fn links_count(&self) -> u16 {
+        get_u16(&self.raw, 0x1a)
+    }
+
+    pub fn set_links_count(&mut self, count: u16) {
+        put_u16(&mut self.raw, 0x1a, count);
+    }
+
+    /// The number of 512-byte sectors used, with `block_size` of the
+    /// filesystem for `huge_file`s.
+    pub fn blocks(&self, block_size: usize) -> u64 {
+        let count = get_u32(&self.raw, 0x1c) as u64 | (get_u16(&self.raw, 0x74) as u64) << 32;
+        if self.flags() & INODE_HUGE_FILE_FL != 0 {
+            count * (block_size as u64 / 512)
+        } else {
+            count
+        }
+    }
+
+    pub fn set_blocks(&mut self, sectors: u64) {
+        put_u32(&mut self.raw, 0x1c, sectors as u32);
+        put_u16(&mut self.raw, 0x74, (sectors >> 32) as u16);
+        self.set_flags(self.flags() & !INODE_HUGE_FILE_FL);
+    }
+
+    pub fn flags(&self) -> u32 {
+        get_u32(&self.raw, 0x20)
+    }
+
+    pub fn set_flags(&mut self, flags: u32) {
+        put_u32(&mut self.raw, 0x20, flags);
+    }
+
+    pub fn has_flags(&self, mask: u32) -> bool {
+        self.flags() & mask != 0
+    }
+
+    /// The `i_block` area, holding the block map, the extent tree root, or
+    /// the target of a fast symlink.
+    pub fn i_block(&self) -> &[u8] {
+        &self.raw[0x28..0x28 + I_BLOCK_SIZE]
+    }
+
+    pub fn i_block_mut(&mut self) -> &mut [u8] {
+        &mut self.raw[0x28..0x28 + I_BLOCK_SIZE]
+    }
+
+    pub fn generation(&self) -> u32 {
+        get_u32(&self.raw, 0x64)
+    }
+
+    /// The extended attribute block.
+    pub fn file_acl(&self) -> u64 {
+        get_u32(&self.raw, 0x68) as u64 | (get_u16(&self.raw, 0x76) as u64) << 32
+    }
+
+    fn extra_isize(&self) -> usize {
+        if self.raw.len() > GOOD_OLD_INODE_SIZE {
+            get_u16(&self.raw, 0x80) as usize
+        } else {
+            0
+        }
+    }
+
+    /// The seed of the `metadata_csum` checksums of the inode and its blocks.
+    pub fn csum_seed(&self, fs_seed: u32, ino: u32) -> u32 {
+        let seed = crc32c(fs_seed, &ino.to_le_bytes());
+        crc32c(seed, &self.generation().to_le_bytes())
+    }
+
+    /// Updates the checksum of the inode before writing it.
+    pub fn update_csum(&mut self, inode_seed: u32) {
+        const CSUM_LO: usize = 0x7c;
+        const CSUM_HI: usize = 0x82;
+        let has_hi = self.extra_isize() >= CSUM_HI + 2 - GOOD_OLD_INODE_SIZE;
+        put_u16(&mut self.raw, CSUM_LO, 0);
+        if has_hi {
+            put_u16(&mut self.raw, CSUM_HI, 0);
+        }
+        let csum = crc32c(inode_seed, &self.raw);
+        put_u16(&mut self.raw, CSUM_LO, csum as u16);
+        if has_hi {
+            put_u16(&mut self.raw, CSUM_HI, (csum >> 16) as u16);
+        }
+    }
+}
@@ -0,0 +1,453 @@
+//! A native ext4 filesystem.
+//!
+//! It supports extents, hashed (htree) directories, permissions and symbolic
+//! links, with checksums of metadata (`metadata_csum`) or group descriptors
+//! (`uninit_bg`). The journal is replayed on mount if the volume was not
+//! cleanly unmounted, but later updates are written directly, without
+//! journaling. Files with legacy block maps can be read but not extended.
+//! Volumes with unknown incompatible features are refused, and those with
+//! unknown read-only compatible features are mounted read-only.
+
+mod crc;
+mod dir;
+mod extent;
+mod file;
+mod htree;
+mod journal;
+mod layout;
+mod volume;
+
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use axfs_vfs::{VfsDirEntry, VfsError, VfsNodePerm, VfsResult};
+use axfs_vfs::{VfsNodeAttr, VfsNodeOps, VfsNodeRef, VfsNodeType, VfsOps};
+use axsync::Mutex;
+
+use self::dir::vfs_node_type;
+use self::layout::*;
+use self::volume::{now, Volume};
+use crate::dev::Disk;
+
+/// The maximum number of symbolic links followed in a lookup, as in Linux.
+const MAX_SYMLINK_FOLLOWS: usize = 40;
+
+pub struct Ext4FileSystem {
+    vol: Arc<Mutex<Volume>>,
+}
+
+/// An inode of the filesystem, either a directory or not.
+pub struct Ext4Node {
+    vol: Arc<Mutex<Volume>>,
+    ino: u32,
+}
+
+impl Ext4FileSystem {
+    /// Checks whether the disk contains an ext2/3/4 filesystem.
+    pub fn probe(disk: &mut Disk) -> bool {
+        Volume::probe(disk)
+    }
+
+    pub fn new(disk: Disk) -> VfsResult<Self> {
+        let vol = Volume::load(disk)?;
+        info!(
+            "ext4: {} blocks of {} bytes, {} inodes{}",
+            vol.sb.blocks_count(),
+            vol.block_size,
+            vol.sb.inodes_count(),
+            if vol.is_read_only() {
+                ", read-only"
+            } else {
+                ""
+            }
+        );
+        Ok(Self {
+            vol: Arc::new(Mutex::new(vol)),
+        })
+    }
+}
+
+impl VfsOps for Ext4FileSystem {
+    fn root_dir(&self) -> VfsNodeRef {
+        Ext4Node::new_ref(self.vol.clone(), ROOT_INO)
+    }
+}
+
+/// Splits a path into its components, dropping empty ones and ".".
+fn components(path: &str) -> impl DoubleEndedIterator<Item = &str> {
+    path.split('/').filter(|s| !s.is_empty() && *s != ".")
+}
+
+/// Resolves `path` relative to the directory `dir`, following symbolic links
+/// in it. The last component is followed only if `follow_last` is set.
+fn resolve(vol: &mut Volume, mut dir: u32, path: &str, follow_last: bool) -> VfsResult<u32> {
+    // The components left, in reverse order.
+    let mut rest: Vec<String> = components(path).rev().map(String::from).collect();
+    let mut follows = 0;
+    while let Some(name) = rest.pop() {
+        if name == ".." && dir == ROOT_INO {
+            // Beyond the filesystem, which is handled by the mount points.
+            return Err(VfsError::NotFound);
+        }
+        let parent = vol.read_inode(dir)?;
+        if !parent.is_dir() {
+            return Err(VfsError::NotADirectory);
+        }
+        let ino = vol
+            .dir_lookup(&parent, name.as_bytes())?
+            .ok_or(VfsError::NotFound)?;
+        let inode = vol.read_inode(ino)?;
+        if inode.is_symlink() && (follow_last || !rest.is_empty()) {
+            follows += 1;
+            if follows > MAX_SYMLINK_FOLLOWS {
+                return Err(VfsError::FilesystemLoop);
+            }
+            let target = vol.read_link(&inode)?;
+            let target = String::from_utf8(target).map_err(|_| VfsError::InvalidData)?;
+            if target.starts_with('/') {
+                dir = ROOT_INO;
+            }
+            rest.extend(components(&target).rev().map(String::from));
+        } else {
+            dir = ino;
+        }
+    }
+    Ok(dir)
+}
+
+/// Resolves the parent directory of `path`, and returns it with the last
+/// component, which is empty if there is none.
+fn resolve_parent<'a>(vol: &mut Volume, dir: u32, path: &'a str) -> VfsResult<(u32, &'a str)> {
+    let path = path.trim_end_matches('/');
+    let (parent_path, name) = path.rsplit_once('/').unwrap_or(("", path));
+    let parent = resolve(vol, dir, parent_path, true)?;
+    if !vol.read_inode(parent)?.is_dir() {
+        return Err(VfsError::NotADirectory);
+    }
+    Ok((parent, name))
+}
+
+/// Whether the directory `ino` is `ancestor` or inside it.
+fn is_descendant(vol: &mut Volume, mut ino: u32, ancestor: u32) -> VfsResult<bool> {
+    loop {
+        if ino == ancestor {
+            return Ok(true);
+        }
+        if ino == ROOT_INO {
+            return Ok(false);
+        }
+        let dir = vol.read_inode(ino)?;
+        ino = vol.dir_lookup(&dir, b"..")?.ok_or(VfsError::InvalidData)?;
+    }
+}
+
+/// Adds a link to the parent for the ".." of a new subdirectory.
+fn inc_dir_links(vol: &Volume, dir: &mut Inode) -> VfsResult {
+    let links = dir.links_count();
+    if links >= LINK_MAX || links == 1 {
+        // Too many subdirectories, or already counted as unknown.
+        if !vol.sb.has_ro_compat(RO_COMPAT_DIR_NLINK) {
+            return Err(VfsError::StorageFull);
+        }
+        dir.set_links_count(1);
+    } else {
+        dir.set_links_count(links + 1);
+    }
+    Ok(())
+}
+
+/// Drops the link of a removed subdirectory from its parent.
+fn dec_dir_links(dir: &mut Inode) {
+    let links = dir.links_count();
+    if links > 2 {
+        dir.set_links_count(links - 1);
+    }
+}
+
+/// Removes the entry `name` from the directory `parent`, and unlinks its
+/// inode. Directories must be empty.
+fn remove_entry(vol: &mut Volume, parent: u32, name: &str) -> VfsResult {
+    if name.is_empty() || name == "." || name == ".." {
+        return Err(VfsError::InvalidInput);
+    }
+    let mut dir = vol.read_inode(parent)?;
+    let ino = vol
+        .dir_lookup(&dir, name.as_bytes())?
+        .ok_or(VfsError::NotFound)?;
+    let mut inode = vol.read_inode(ino)?;
+    if inode.is_dir() && !vol.dir_is_empty(&inode)? {
+        return Err(VfsError::DirectoryNotEmpty);
+    }
+    vol.dir_remove(parent, &mut dir, name.as_bytes())?;
+    if inode.is_dir() {
+        dec_dir_links(&mut dir);
+        vol.write_inode(parent, &mut dir)?;
+    }
+    vol.unlink_inode(ino, &mut inode)
+}
+
+impl Ext4Node {
+    fn new_ref(vol: Arc<Mutex<Volume>>, ino: u32) -> VfsNodeRef {
+        Arc::new(Self { vol, ino })
+    }
+
+    fn new_node(&self, ino: u32) -> VfsNodeRef {
+        Self::new_ref(self.vol.clone(), ino)
+    }
+}
+
+impl VfsNodeOps for Ext4Node {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        let mut vol = self.vol.lock();
+        let inode = vol.read_inode(self.ino)?;
+        let perm = VfsNodePerm::from_bits_truncate(inode.mode() & 0o777);
+        let ty = vfs_node_type(inode.mode());
+        let blocks = inode.blocks(vol.block_size);
+        Ok(VfsNodeAttr::new(perm, ty, inode.size(), blocks))
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let mut vol = self.vol.lock();
+        let inode = vol.read_inode(self.ino)?;
+        if inode.is_dir() {
+            return Err(VfsError::IsADirectory);
+        }
+        vol.read_data(&inode, offset, buf)
+    }
+
+    fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        let mut vol = self.vol.lock();
+        vol.check_writable()?;
+        let mut inode = vol.read_inode(self.ino)?;
+        if inode.is_dir() {
+            return Err(VfsError::IsADirectory);
+        }
+        vol.write_data(self.ino, &mut inode, offset, buf)
+    }
+
+    fn fsync(&self) -> VfsResult {
+        self.vol.lock().flush()
+    }
+
+    fn truncate(&self, size: u64) -> VfsResult {
+        let mut vol = self.vol.lock();
+        vol.check_writable()?;
+        let mut inode = vol.read_inode(self.ino)?;
+        if inode.is_dir() {
+            return Err(VfsError::IsADirectory);
+        }
+        if size > inode.size() {
+            // Extended with a hole.
+            inode.set_size(size);
+            let time = now();
+            inode.set_mtime(time);
+            inode.set_ctime(time);
+            return vol.write_inode(self.ino, &mut inode);
+        }
+        vol.truncate_data(self.ino, &mut inode, size)
+    }
+
+    fn parent(&self) -> Option<VfsNodeRef> {
+        if self.ino == ROOT_INO {
+            return None;
+        }
+        let mut vol = self.vol.lock();
+        let inode = vol.read_inode(self.ino).ok()?;
+        if !inode.is_dir() {
+            return None;
+        }
+        let parent = vol.dir_lookup(&inode, b"..").ok()??;
+        Some(self.new_node(parent))
+    }
+
+    fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
+        debug!("lookup at ext4fs: {}", path);
+        let ino = resolve(&mut self.vol.lock(), self.ino, path, false)?;
+        Ok(self.new_node(ino))
+    }
+
+    fn create(&self, path: &str, ty: VfsNodeType) -> VfsResult {
+        debug!("create {:?} at ext4fs: {}", ty, path);
+        let mut vol = self.vol.lock();
+        let (parent, name) = resolve_parent(&mut vol, self.ino, path)?;
+        let mut dir = vol.read_inode(parent)?;
+        if name.is_empty() {
+            return Ok(()); // the directory itself
+        }
+        if let Some(ino) = vol.dir_lookup(&dir, name.as_bytes())? {
+            // Like `fatfs`, creating an existing node of the same type opens it.
+            return if vfs_node_type(vol.read_inode(ino)?.mode()) == ty {
+                Ok(())
+            } else {
+                Err(VfsError::AlreadyExists)
+            };
+        }
+        vol.check_writable()?;
+        match ty {
+            VfsNodeType::File => {
+                let (ino, mut inode) = vol.new_inode(parent, S_IFREG | 0o644)?;
+                if let Err(err) = vol.dir_add(parent, &mut dir, name.as_bytes(), ino, inode.mode())
+                {
+                    vol.unlink_inode(ino, &mut inode)?;
+                    return Err(err);
+                }
+                Ok(())
+            }
+            VfsNodeType::Dir => {
+                inc_dir_links(&vol, &mut dir)?;
+                let (ino, mut inode) = vol.new_inode(parent, S_IFDIR | 0o755)?;
+                inode.set_links_count(2);
+                if let Err(err) = vol.dir_init(ino, &mut inode, parent) {
+                    vol.unlink_inode(ino, &mut inode)?;
+                    return Err(err);
+                }
+                if let Err(err) = vol.dir_add(parent, &mut dir, name.as_bytes(), ino, inode.mode())
+                {
+                    vol.unlink_inode(ino, &mut inode)?;
+                    return Err(err);
+                }
+                Ok(())
+            }
+            _ => Err(VfsError::Unsupported),
+        }
+    }
+
+    fn remove(&self, path: &str) -> VfsResult {
+        debug!("remove at ext4fs: {}", path);
+        let mut vol = self.vol.lock();
+        vol.check_writable()?;
+        let (parent, name) = resolve_parent(&mut vol, self.ino, path)?;
+        remove_entry(&mut vol, parent, name)
+    }
+
+    fn read_dir(&self, start_idx: usize, dirents: &mut [VfsDirEntry]) -> VfsResult<usize> {
+        let mut vol = self.vol.lock();
+        let inode = vol.read_inode(self.ino)?;
+        if !inode.is_dir() {
+            return Err(VfsError::NotADirectory);
+        }
+        vol.dir_entries(&inode, start_idx, dirents)
+    }
+
+    fn rename(&self, src_path: &str, dst_path: &str) -> VfsResult {
+        debug!(
+            "rename at ext4fs, src_path: {}, dst_path: {}",
+            src_path, dst_path
+        );
+        let mut vol = self.vol.lock();
+        vol.check_writable()?;
+        let (src_parent, src_name) = resolve_parent(&mut vol, self.ino, src_path)?;
+        // `dst_path` is relative to the root of the filesystem.
+        let (dst_parent, dst_name) = resolve_parent(&mut vol, ROOT_INO, dst_path)?;
+        for name in [src_name, dst_name] {
+            if name.is_empty() || name == "." || name == ".." {
+                return Err(VfsError::InvalidInput);
+            }
+        }
+
+        let src_dir = vol.read_inode(src_parent)?;
+        let ino = vol
+            .dir_lookup(&src_dir, src_name.as_bytes())?
+            .ok_or(VfsError::NotFound)?;
+        let mut inode = vol.read_inode(ino)?;
+        if inode.is_dir() && is_descendant(&mut vol, dst_parent, ino)? {
+            return Err(VfsError::InvalidInput); // into its own subtree
+        }
+        let dst_dir = vol.read_inode(dst_parent)?;
+        if let Some(old) = vol.dir_lookup(&dst_dir, dst_name.as_bytes())? {
+            if old == ino {
+                return Ok(());
+            }
+            let old_is_dir = vol.read_inode(old)?.is_dir();
+            if inode.is_dir() != old_is_dir {
+                return Err(if old_is_dir {
+                    VfsError::IsADirectory
+                } else {
+                    VfsError::NotADirectory
+                });
+            }
+            remove_entry(&mut vol, dst_parent, dst_name)?;
+        }
+
+        let mut dst_dir = vol.read_inode(dst_parent)?;
+        if inode.is_dir() && src_parent != dst_parent {
+            inc_dir_links(&vol, &mut dst_dir)?;
+        }
+        let mode = inode.mode();
+        vol.dir_add(dst_parent, &mut dst_dir, dst_name.as_bytes(), ino, mode)?;
+        let mut src_dir = vol.read_inode(src_parent)?;
+        vol.dir_remove(src_parent, &mut src_dir, src_name.as_bytes())?;
+        if inode.is_dir() && src_parent != dst_parent {
+            vol.dir_set_parent(ino, &inode, dst_parent)?;
+            dec_dir_links(&mut src_dir);
+            vol.write_inode(src_parent, &mut src_dir)?;
+        }
+        inode.set_ctime(now());
+        vol.write_inode(ino, &mut inode)
+    }
+
+    fn symlink(&self, path: &str, target: &str) -> VfsResult {
+        debug!("symlink at ext4fs: {} -> {}", path, target);
+        let mut vol = self.vol.lock();
+        vol.check_writable()?;
+        let (parent, name) = resolve_parent(&mut vol, self.ino, path)?;
+        let mut dir = vol.read_inode(parent)?;
+        if name.is_empty() || vol.dir_lookup(&dir, name.as_bytes())?.is_some() {
+            return Err(VfsError::AlreadyExists);
+        }
+        let (ino, mut inode) = vol.new_inode(parent, S_IFLNK | 0o777)?;
+        let result = vol
+            .write_link(ino, &mut inode, target.as_bytes())
+            .and_then(|_| vol.dir_add(parent, &mut dir, name.as_bytes(), ino, inode.mode()));
+        if let Err(err) = result {
+            vol.unlink_inode(ino, &mut inode)?;
+            return Err(err);
+        }
+        Ok(())
+    }
+
+    fn link(&self, path: &str, node: &VfsNodeRef) -> VfsResult {
+        debug!("link at ext4fs: {}", path);
+        let node = node
+            .as_any()
+            .downcast_ref::<Ext4Node>()
+            .filter(|node| Arc::ptr_eq(&node.vol, &self.vol))
+            .ok_or(VfsError::CrossesDevices)?;
+        let mut vol = self.vol.lock();
+        vol.check_writable()?;
+        let mut inode = vol.read_inode(node.ino)?;
+        if inode.is_dir() {
+            return Err(VfsError::OperationNotPermitted);
+        }
+        let links = inode.links_count();
+        if links >= LINK_MAX {
+            return Err(VfsError::StorageFull); // too many links
+        }
+        let (parent, name) = resolve_parent(&mut vol, self.ino, path)?;
+        let mut dir = vol.read_inode(parent)?;
+        if name.is_empty() || vol.dir_lookup(&dir, name.as_bytes())?.is_some() {
+            return Err(VfsError::AlreadyExists);
+        }
+        vol.dir_add(parent, &mut dir, name.as_bytes(), node.ino, inode.mode())?;
+        inode.set_links_count(links + 1);
+        inode.set_ctime(now());
+        vol.write_inode(node.ino, &mut inode)
+    }
+
+    fn readlink(&self, buf: &mut [u8]) -> VfsResult<usize> {
+        let mut vol = self.vol.lock();
+        let inode = vol.read_inode(self.ino)?;
+        if !inode.is_symlink() {
+            return Err(VfsError::InvalidInput);
+        }
+        let target = vol.read_link(&inode)?;
+        let len = target.len().min(buf.len());
+        buf[..len].copy_from_slice(&target[..len]);
+        Ok(len)
+    }
+
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
+}
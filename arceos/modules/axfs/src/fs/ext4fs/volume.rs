@@ -0,0 +1,545 @@
+//! Block I/O, the metadata of block groups, and the allocation of blocks and
+//! inodes.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use axfs_vfs::{VfsError, VfsResult};
+
+use super::crc::crc32c;
+use super::layout::*;
+use crate::dev::Disk;
+
+/// A mounted ext4 volume, with all the metadata that is kept in memory.
+///
+/// Every update is written through to the disk immediately, so the volume is
+/// consistent whenever no operation is in progress.
+pub struct Volume {
+    disk: Disk,
+    pub sb: SuperBlock,
+    groups: Vec<GroupDesc>,
+    pub block_size: usize,
+    csum_seed: u32,
+    read_only: bool,
+    next_generation: u32,
+}
+
+fn io_err<E>(_: E) -> VfsError {
+    VfsError::Io
+}
+
+/// Returns the current wall time in seconds, for the timestamps of inodes.
+pub fn now() -> u32 {
+    axhal::time::wall_time().as_secs() as u32
+}
+
+fn find_zero_bit(bitmap: &[u8], start: usize, end: usize) -> Option<usize> {
+    (start..end).find(|&i| bitmap[i / 8] & (1 << (i % 8)) == 0)
+}
+
+fn test_bit(bitmap: &[u8], i: usize) -> bool {
+    bitmap[i / 8] & (1 << (i % 8)) != 0
+}
+
+fn set_bit(bitmap: &mut [u8], i: usize) {
+    bitmap[i / 8] |= 1 << (i % 8);
+}
+
+fn clear_bit(bitmap: &mut [u8], i: usize) {
+    bitmap[i / 8] &= !(1 << (i % 8));
+}
+
+/// Sets the padding bits of a bitmap from `start` to the end of the block.
+fn mark_bitmap_end(bitmap: &mut [u8], start: usize) {
+    (start..bitmap.len() * 8).for_each(|i| set_bit(bitmap, i));
+}
+
+/// Whether `n` is a power of `base`.
+fn is_power_of(mut n: u32, base: u32) -> bool {
+    while n > 1 && n % base == 0 {
+        n /= base;
+    }
+    n == 1
+}
+
+impl Volume {
+    /// Checks whether the disk contains a filesystem of the ext family.
+    pub fn probe(disk: &mut Disk) -> bool {
+        let mut magic = [0; 2];
+        disk.set_position(SUPERBLOCK_OFFSET + MAGIC_OFFSET as u64);
+        let ok = read_exact(disk, &mut magic).is_ok();
+        disk.set_position(0);
+        ok && u16::from_le_bytes(magic) == EXT4_MAGIC
+    }
+
+    /// Loads the volume, replaying the journal if it needs recovery.
+    pub fn load(disk: Disk) -> VfsResult<Self> {
+        let mut vol = Self {
+            disk,
+            sb: SuperBlock::from_bytes(vec![0; SUPERBLOCK_SIZE]),
+            groups: Vec::new(),
+            block_size: 0,
+            csum_seed: 0,
+            read_only: false,
+            next_generation: now(),
+        };
+        vol.load_metadata()?;
+
+        let unknown = vol.sb.feature_incompat() & !INCOMPAT_SUPPORTED;
+        if unknown != 0 {
+            warn!("ext4: unsupported incompatible features {:#x}", unknown);
+            return Err(VfsError::Unsupported);
+        }
+        let unknown = vol.sb.feature_ro_compat() & !RO_COMPAT_SUPPORTED;
+        if unknown != 0 {
+            warn!("ext4: unsupported features {:#x}, mount read-only", unknown);
+            vol.read_only = true;
+        }
+        if !vol.sb.has_incompat(INCOMPAT_EXTENTS) {
+            warn!("ext4: no extents, mount read-only");
+            vol.read_only = true;
+        }
+
+        if vol.sb.has_incompat(INCOMPAT_RECOVER) {
+            if vol.sb.feature_compat() & COMPAT_HAS_JOURNAL == 0 {
+                warn!("ext4: needs recovery without a journal");
+                return Err(VfsError::InvalidData);
+            }
+            vol.replay_journal()?;
+            // The superblock and the group descriptors may be replayed.
+            vol.load_metadata()?;
+            let features = vol.sb.feature_incompat() & !INCOMPAT_RECOVER;
+            vol.sb.set_feature_incompat(features);
+            vol.write_super()?;
+        }
+        if vol.sb.last_orphan() != 0 {
+            warn!("ext4: orphan inodes are left, run e2fsck to release them");
+        }
+        if !vol.read_only {
+            // Like Linux, trust the group descriptors rather than the summary.
+            let free_blocks = vol.groups.iter().map(|gd| gd.free_blocks_count() as u64);
+            let free_inodes = vol.groups.iter().map(|gd| gd.free_inodes_count());
+            vol.sb.set_free_blocks_count(free_blocks.sum());
+            vol.sb.set_free_inodes_count(free_inodes.sum());
+            vol.sb.set_wtime(now());
+            vol.write_super()?;
+        }
+        Ok(vol)
+    }
+
+    fn load_metadata(&mut self) -> VfsResult {
+        let mut raw = vec![0; SUPERBLOCK_SIZE];
+        self.read_bytes(SUPERBLOCK_OFFSET, &mut raw)?;
+        self.sb = SuperBlock::from_bytes(raw);
+        if self.sb.magic() != EXT4_MAGIC {
+            return Err(VfsError::InvalidData);
+        }
+        if self.sb.log_block_size() > 6
+            || self.sb.blocks_per_group() == 0
+            || self.sb.inodes_per_group() == 0
+            || self.sb.inode_size() < 128
+        {
+            warn!("ext4: bad superblock");
+            return Err(VfsError::InvalidData);
+        }
+        self.block_size = 1024 << self.sb.log_block_size();
+        self.csum_seed = self.sb.csum_seed();
+
+        let desc_size = self.sb.desc_size();
+        let per_block = (self.block_size / desc_size) as u32;
+        let mut block = vec![0; self.block_size];
+        self.groups.clear();
+        for group in 0..self.group_count() {
+            if group % per_block == 0 {
+                self.read_block(self.desc_block(group / per_block), &mut block)?;
+            }
+            let off = (group % per_block) as usize * desc_size;
+            let gd = GroupDesc::from_bytes(&block[off..off + desc_size]);
+            self.groups.push(gd);
+        }
+        Ok(())
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Fails if the volume is mounted read-only.
+    pub fn check_writable(&self) -> VfsResult {
+        if self.read_only {
+            Err(VfsError::PermissionDenied)
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn has_metadata_csum(&self) -> bool {
+        self.sb.has_metadata_csum()
+    }
+
+    pub fn csum_seed(&self) -> u32 {
+        self.csum_seed
+    }
+
+    pub fn read_bytes(&mut self, pos: u64, buf: &mut [u8]) -> VfsResult {
+        self.disk.set_position(pos);
+        read_exact(&mut self.disk, buf)
+    }
+
+    pub fn write_bytes(&mut self, pos: u64, mut buf: &[u8]) -> VfsResult {
+        self.disk.set_position(pos);
+        while !buf.is_empty() {
+            match self.disk.write_one(buf).map_err(io_err)? {
+                0 => return Err(VfsError::WriteZero),
+                n => buf = &buf[n..],
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the cached blocks back to the disk.
+    pub fn flush(&mut self) -> VfsResult {
+        self.disk.flush().map_err(io_err)
+    }
+
+    pub fn read_block(&mut self, block: u64, buf: &mut [u8]) -> VfsResult {
+        self.read_bytes(block * self.block_size as u64, buf)
+    }
+
+    pub fn write_block(&mut self, block: u64, buf: &[u8]) -> VfsResult {
+        self.write_bytes(block * self.block_size as u64, buf)
+    }
+
+    pub fn write_super(&mut self) -> VfsResult {
+        self.sb.update_csum();
+        let raw = self.sb.as_bytes().to_vec();
+        self.write_bytes(SUPERBLOCK_OFFSET, &raw)
+    }
+
+    pub fn group_count(&self) -> u32 {
+        let blocks = self.sb.blocks_count() - self.sb.first_data_block() as u64;
+        blocks.div_ceil(self.sb.blocks_per_group() as u64) as u32
+    }
+
+    fn group_first_block(&self, group: u32) -> u64 {
+        self.sb.first_data_block() as u64 + group as u64 * self.sb.blocks_per_group() as u64
+    }
+
+    fn group_block_count(&self, group: u32) -> u32 {
+        let end = self.group_first_block(group) + self.sb.blocks_per_group() as u64;
+        (end.min(self.sb.blocks_count()) - self.group_first_block(group)) as u32
+    }
+
+    /// Whether the group has a backup of the superblock.
+    fn group_has_super(&self, group: u32) -> bool {
+        group <= 1
+            || !self.sb.has_ro_compat(RO_COMPAT_SPARSE_SUPER)
+            || is_power_of(group, 3)
+            || is_power_of(group, 5)
+            || is_power_of(group, 7)
+    }
+
+    fn descs_per_block(&self) -> u32 {
+        (self.block_size / self.sb.desc_size()) as u32
+    }
+
+    /// The location of the `nr`-th block of group descriptors.
+    fn desc_block(&self, nr: u32) -> u64 {
+        let first = self.sb.first_data_block() as u64;
+        if !self.sb.has_incompat(INCOMPAT_META_BG) || nr < self.sb.first_meta_bg() {
+            first + 1 + nr as u64
+        } else {
+            let group = nr * self.descs_per_block();
+            self.group_first_block(group) + self.group_has_super(group) as u64
+        }
+    }
+
+    fn write_group(&mut self, group: u32) -> VfsResult {
+        let gd = &mut self.groups[group as usize];
+        gd.update_csum(&self.sb, group);
+        let raw = gd.as_bytes().to_vec();
+        let per_block = self.descs_per_block();
+        let pos = self.desc_block(group / per_block) * self.block_size as u64
+            + (group % per_block) as u64 * raw.len() as u64;
+        self.write_bytes(pos, &raw)
+    }
+
+    fn inode_pos(&self, ino: u32) -> VfsResult<u64> {
+        if ino == 0 || ino > self.sb.inodes_count() {
+            warn!("ext4: bad inode number {}", ino);
+            return Err(VfsError::InvalidData);
+        }
+        let ipg = self.sb.inodes_per_group();
+        let gd = &self.groups[((ino - 1) / ipg) as usize];
+        let index = ((ino - 1) % ipg) as u64;
+        Ok(gd.inode_table() * self.block_size as u64 + index * self.sb.inode_size() as u64)
+    }
+
+    pub fn read_inode(&mut self, ino: u32) -> VfsResult<Inode> {
+        let pos = self.inode_pos(ino)?;
+        let mut raw = vec![0; self.sb.inode_size()];
+        self.read_bytes(pos, &mut raw)?;
+        Ok(Inode::from_bytes(&raw))
+    }
+
+    pub fn write_inode(&mut self, ino: u32, inode: &mut Inode) -> VfsResult {
+        if self.has_metadata_csum() {
+            inode.update_csum(self.inode_seed(ino, inode));
+        }
+        let pos = self.inode_pos(ino)?;
+        self.write_bytes(pos, inode.as_bytes())
+    }
+
+    /// The seed of the checksums of the inode and its metadata blocks.
+    pub fn inode_seed(&self, ino: u32, inode: &Inode) -> u32 {
+        inode.csum_seed(self.csum_seed, ino)
+    }
+
+    /// The first block of the group of the inode, as a goal of allocation.
+    pub fn inode_goal(&self, ino: u32) -> u64 {
+        self.group_first_block((ino - 1) / self.sb.inodes_per_group())
+    }
+
+    fn read_block_bitmap(&mut self, group: u32) -> VfsResult<Vec<u8>> {
+        let mut bitmap = vec![0; self.block_size];
+        let gd = &self.groups[group as usize];
+        if gd.flags() & BG_BLOCK_UNINIT == 0 {
+            self.read_block(gd.block_bitmap(), &mut bitmap)?;
+            return Ok(bitmap);
+        }
+        // Never written, the used blocks are those of the metadata.
+        let start = self.group_first_block(group);
+        let count = self.group_block_count(group) as u64;
+        let has_super = self.group_has_super(group) as u32;
+        let per_block = self.descs_per_block();
+        let meta_bg = self.sb.has_incompat(INCOMPAT_META_BG);
+        let desc_blocks = if !meta_bg || group < self.sb.first_meta_bg() * per_block {
+            let count = if meta_bg {
+                self.sb.first_meta_bg()
+            } else {
+                self.group_count().div_ceil(per_block)
+            };
+            has_super * (count + self.sb.reserved_gdt_blocks() as u32)
+        } else {
+            let first = group / per_block * per_block;
+            (group == first || group == first + 1 || group == first + per_block - 1) as u32
+        };
+        (0..(has_super + desc_blocks) as usize).for_each(|i| set_bit(&mut bitmap, i));
+        for gd in self.groups.iter() {
+            let table = (0..self.sb.inode_table_blocks(self.block_size) as u64)
+                .map(|i| gd.inode_table() + i);
+            for block in [gd.block_bitmap(), gd.inode_bitmap()]
+                .into_iter()
+                .chain(table)
+            {
+                if (start..start + count).contains(&block) {
+                    set_bit(&mut bitmap, (block - start) as usize);
+                }
+            }
+        }
+        mark_bitmap_end(&mut bitmap, count as usize);
+        Ok(bitmap)
+    }
+
+    fn write_block_bitmap(&mut self, group: u32, bitmap: &[u8]) -> VfsResult {
+        let gd = &mut self.groups[group as usize];
+        if self.sb.has_metadata_csum() {
+            let len = self.sb.blocks_per_group() as usize / 8;
+            gd.set_block_bitmap_csum(crc32c(self.csum_seed, &bitmap[..len]));
+        }
+        gd.set_flags(gd.flags() & !BG_BLOCK_UNINIT);
+        let block = gd.block_bitmap();
+        self.write_block(block, bitmap)
+    }
+
+    fn read_inode_bitmap(&mut self, group: u32) -> VfsResult<Vec<u8>> {
+        let mut bitmap = vec![0; self.block_size];
+        let gd = &self.groups[group as usize];
+        if gd.flags() & BG_INODE_UNINIT == 0 {
+            self.read_block(gd.inode_bitmap(), &mut bitmap)?;
+        } else {
+            mark_bitmap_end(&mut bitmap, self.sb.inodes_per_group() as usize);
+        }
+        Ok(bitmap)
+    }
+
+    fn write_inode_bitmap(&mut self, group: u32, bitmap: &[u8]) -> VfsResult {
+        let gd = &mut self.groups[group as usize];
+        if self.sb.has_metadata_csum() {
+            let len = self.sb.inodes_per_group() as usize / 8;
+            gd.set_inode_bitmap_csum(crc32c(self.csum_seed, &bitmap[..len]));
+        }
+        gd.set_flags(gd.flags() & !BG_INODE_UNINIT);
+        let block = gd.inode_bitmap();
+        self.write_block(block, bitmap)
+    }
+
+    /// Allocates at most `count` contiguous blocks, preferably from `goal`.
+    ///
+    /// Returns the first block and the number of blocks allocated.
+    pub fn alloc_blocks(&mut self, goal: u64, count: u32) -> VfsResult<(u64, u32)> {
+        let first_data = self.sb.first_data_block() as u64;
+        let goal = goal.clamp(first_data, self.sb.blocks_count() - 1);
+        let bpg = self.sb.blocks_per_group() as u64;
+        let goal_group = ((goal - first_data) / bpg) as u32;
+        let ngroups = self.group_count();
+        for i in 0..ngroups {
+            let group = (goal_group + i) % ngroups;
+            if self.groups[group as usize].free_blocks_count() == 0 {
+                continue;
+            }
+            let mut bitmap = self.read_block_bitmap(group)?;
+            let len = self.group_block_count(group) as usize;
+            let start = if i == 0 {
+                (goal - self.group_first_block(group)) as usize
+            } else {
+                0
+            };
+            let Some(first) =
+                find_zero_bit(&bitmap, start, len).or_else(|| find_zero_bit(&bitmap, 0, start))
+            else {
+                continue;
+            };
+            let mut n = 0;
+            while n < count && first + (n as usize) < len && !test_bit(&bitmap, first + n as usize)
+            {
+                set_bit(&mut bitmap, first + n as usize);
+                n += 1;
+            }
+            self.write_block_bitmap(group, &bitmap)?;
+            let gd = &mut self.groups[group as usize];
+            gd.set_free_blocks_count(gd.free_blocks_count().saturating_sub(n));
+            self.write_group(group)?;
+            let free = self.sb.free_blocks_count().saturating_sub(n as u64);
+            self.sb.set_free_blocks_count(free);
+            self.write_super()?;
+            return Ok((self.group_first_block(group) + first as u64, n));
+        }
+        Err(VfsError::StorageFull)
+    }
+
+    /// Frees `count` contiguous blocks from `start`.
+    pub fn free_blocks(&mut self, mut start: u64, mut count: u64) -> VfsResult {
+        let first_data = self.sb.first_data_block() as u64;
+        let bpg = self.sb.blocks_per_group() as u64;
+        while count > 0 {
+            if start < first_data || start + count > self.sb.blocks_count() {
+                warn!("ext4: freeing bad blocks {}+{}", start, count);
+                return Err(VfsError::InvalidData);
+            }
+            let group = ((start - first_data) / bpg) as u32;
+            let offset = (start - self.group_first_block(group)) as usize;
+            let n = count.min(bpg - offset as u64);
+            let mut bitmap = self.read_block_bitmap(group)?;
+            for i in offset..offset + n as usize {
+                if !test_bit(&bitmap, i) {
+                    warn!("ext4: freeing free block {}", start + (i - offset) as u64);
+                }
+                clear_bit(&mut bitmap, i);
+            }
+            self.write_block_bitmap(group, &bitmap)?;
+            let gd = &mut self.groups[group as usize];
+            gd.set_free_blocks_count(gd.free_blocks_count() + n as u32);
+            self.write_group(group)?;
+            let free = self.sb.free_blocks_count() + n;
+            self.sb.set_free_blocks_count(free);
+            self.write_super()?;
+            start += n;
+            count -= n;
+        }
+        Ok(())
+    }
+
+    /// Allocates an inode, preferably in the group of its parent directory.
+    pub fn alloc_inode(&mut self, parent: u32, is_dir: bool) -> VfsResult<u32> {
+        let ipg = self.sb.inodes_per_group();
+        let ngroups = self.group_count();
+        let has_unused = self.sb.has_metadata_csum() || self.sb.has_ro_compat(RO_COMPAT_GDT_CSUM);
+        for i in 0..ngroups {
+            let group = ((parent - 1) / ipg + i) % ngroups;
+            if self.groups[group as usize].free_inodes_count() == 0 {
+                continue;
+            }
+            let mut bitmap = self.read_inode_bitmap(group)?;
+            // Skip the reserved inodes.
+            let start = self.sb.first_ino().saturating_sub(group * ipg + 1) as usize;
+            let Some(index) = find_zero_bit(&bitmap, start, ipg as usize) else {
+                continue;
+            };
+            set_bit(&mut bitmap, index);
+            self.write_inode_bitmap(group, &bitmap)?;
+            let gd = &mut self.groups[group as usize];
+            gd.set_free_inodes_count(gd.free_inodes_count() - 1);
+            if is_dir {
+                gd.set_used_dirs_count(gd.used_dirs_count() + 1);
+            }
+            if has_unused {
+                let used = ipg - gd.itable_unused();
+                if index as u32 >= used {
+                    gd.set_itable_unused(ipg - index as u32 - 1);
+                }
+            }
+            self.write_group(group)?;
+            let free = self.sb.free_inodes_count() - 1;
+            self.sb.set_free_inodes_count(free);
+            self.write_super()?;
+            return Ok(group * ipg + index as u32 + 1);
+        }
+        Err(VfsError::StorageFull)
+    }
+
+    pub fn free_inode(&mut self, ino: u32, is_dir: bool) -> VfsResult {
+        let ipg = self.sb.inodes_per_group();
+        let group = (ino - 1) / ipg;
+        let index = ((ino - 1) % ipg) as usize;
+        let mut bitmap = self.read_inode_bitmap(group)?;
+        if !test_bit(&bitmap, index) {
+            warn!("ext4: freeing free inode {}", ino);
+        }
+        clear_bit(&mut bitmap, index);
+        self.write_inode_bitmap(group, &bitmap)?;
+        let gd = &mut self.groups[group as usize];
+        gd.set_free_inodes_count(gd.free_inodes_count() + 1);
+        if is_dir {
+            gd.set_used_dirs_count(gd.used_dirs_count().saturating_sub(1));
+        }
+        self.write_group(group)?;
+        let free = self.sb.free_inodes_count() + 1;
+        self.sb.set_free_inodes_count(free);
+        self.write_super()
+    }
+
+    /// Creates an inode with the given mode in a free slot.
+    pub fn new_inode(&mut self, parent: u32, mode: u16) -> VfsResult<(u32, Inode)> {
+        let ino = self.alloc_inode(parent, mode & S_IFMT == S_IFDIR)?;
+        let extra_isize = self.sb.want_extra_isize().max(32);
+        self.next_generation = self.next_generation.wrapping_add(1);
+        let mut inode = Inode::new(
+            self.sb.inode_size(),
+            extra_isize,
+            mode,
+            self.next_generation,
+        );
+        inode.set_links_count(1);
+        let time = now();
+        inode.set_atime(time);
+        inode.set_ctime(time);
+        inode.set_mtime(time);
+        inode.set_crtime(time);
+        if mode & S_IFMT != S_IFLNK {
+            self.init_extents(&mut inode);
+        }
+        self.write_inode(ino, &mut inode)?;
+        Ok((ino, inode))
+    }
+}
+
+fn read_exact(disk: &mut Disk, mut buf: &mut [u8]) -> VfsResult {
+    while !buf.is_empty() {
+        match disk.read_one(buf).map_err(io_err)? {
+            0 => return Err(VfsError::UnexpectedEof),
+            n => buf = &mut buf[n..],
+        }
+    }
+    Ok(())
+}
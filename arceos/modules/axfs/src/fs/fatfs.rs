@@ -48,6 +48,15 @@ impl FatFileSystem {
         }
     }
 
+    /// Opens the FAT filesystem on the disk, which is never formatted.
+    pub fn open(disk: Disk) -> VfsResult<Self> {
+        let inner = fatfs::FileSystem::new(disk, fatfs::FsOptions::new()).map_err(as_vfs_err)?;
+        Ok(Self {
+            inner,
+            root_dir: UnsafeCell::new(None),
+        })
+    }
+
     pub fn init(&'static self) {
         // must be called before later operations
         unsafe { *self.root_dir.get() = Some(Self::new_dir(self.inner.root_dir())) }
@@ -90,6 +99,11 @@ impl VfsNodeOps for FileWrapper<'static> {
         file.seek(SeekFrom::Start(size)).map_err(as_vfs_err)?; // TODO: more efficient
         file.truncate().map_err(as_vfs_err)
     }
+
+    fn fsync(&self) -> VfsResult {
+        // writes the directory entry with the size back, then the disk
+        self.0.lock().flush().map_err(as_vfs_err)
+    }
 }
 
 impl VfsNodeOps for DirWrapper<'static> {
@@ -243,7 +257,7 @@ impl Write for Disk {
         Ok(write_len)
     }
     fn flush(&mut self) -> Result<(), Self::Error> {
-        Ok(())
+        Disk::flush(self).map_err(|_| ())
     }
 }
 
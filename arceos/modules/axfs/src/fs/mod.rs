@@ -1,8 +1,11 @@
 cfg_if::cfg_if! {
     if #[cfg(feature = "myfs")] {
         pub mod myfs;
-    } else if #[cfg(feature = "fatfs")] {
+    } else {
+        #[cfg(feature = "fatfs")]
         pub mod fatfs;
+        #[cfg(feature = "ext4fs")]
+        pub mod ext4fs;
     }
 }
 
@@ -11,3 +14,5 @@ pub use axfs_devfs as devfs;
 
 #[cfg(feature = "ramfs")]
 pub use axfs_ramfs as ramfs;
+
+pub mod procfs;
@@ -0,0 +1,360 @@
+//! A filesystem whose files are generated from the kernel state when
+//! opened, as `/proc` in Linux.
+//!
+//! Other modules add their files by [`add`] and [`add_dynamic`], before or
+//! after it is mounted. All mounts of it share the same files. Files are
+//! read-only unless created by [`ProcFile::new_rw`], whose writes take effect
+//! at once.
+
+use alloc::{boxed::Box, collections::BTreeMap, string::String, sync::Arc, vec::Vec};
+use axfs_vfs::{VfsDirEntry, VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeType, VfsOps};
+use axsync::Mutex;
+use lazyinit::LazyInit;
+
+pub use axfs_vfs::{VfsError, VfsNodeRef, VfsResult};
+
+type ContentFn = Box<dyn Fn() -> VfsResult<String> + Send + Sync>;
+type WriteFn = Box<dyn Fn(&str) -> VfsResult + Send + Sync>;
+type ListFn = Box<dyn Fn() -> Vec<String> + Send + Sync>;
+type LookupFn = Box<dyn Fn(&str) -> Option<VfsNodeRef> + Send + Sync>;
+
+static ROOT: LazyInit<Arc<ProcDir>> = LazyInit::new();
+
+fn root() -> &'static Arc<ProcDir> {
+    ROOT.call_once(ProcDir::new);
+    &ROOT
+}
+
+/// The procfs. It implements [`axfs_vfs::VfsOps`].
+pub struct ProcFileSystem;
+
+impl VfsOps for ProcFileSystem {
+    fn root_dir(&self) -> VfsNodeRef {
+        root().clone()
+    }
+}
+
+/// The generators of a [`ProcFile`], shared by all its opened instances.
+struct ProcFileOps {
+    read: ContentFn,
+    write: Option<WriteFn>,
+}
+
+/// A file whose content is generated when opened.
+///
+/// Each lookup of the file returns a new instance of it, which keeps the
+/// content generated on open, so that reads in chunks see a consistent
+/// snapshot.
+pub struct ProcFile {
+    ops: Arc<ProcFileOps>,
+    content: Mutex<Option<String>>,
+}
+
+impl ProcFile {
+    /// Creates a read-only file whose content is generated by `read`.
+    pub fn new<R>(read: R) -> Arc<Self>
+    where
+        R: Fn() -> VfsResult<String> + Send + Sync + 'static,
+    {
+        Self::with_ops(ProcFileOps {
+            read: Box::new(read),
+            write: None,
+        })
+    }
+
+    /// Creates a writable file, like a tunable in `/proc/sys`.
+    ///
+    /// A write replaces the whole content, which is passed to `write` with
+    /// the surrounding whitespace trimmed.
+    pub fn new_rw<R, W>(read: R, write: W) -> Arc<Self>
+    where
+        R: Fn() -> VfsResult<String> + Send + Sync + 'static,
+        W: Fn(&str) -> VfsResult + Send + Sync + 'static,
+    {
+        Self::with_ops(ProcFileOps {
+            read: Box::new(read),
+            write: Some(Box::new(write)),
+        })
+    }
+
+    fn with_ops(ops: ProcFileOps) -> Arc<Self> {
+        Arc::new(Self {
+            ops: Arc::new(ops),
+            content: Mutex::new(None),
+        })
+    }
+
+    /// Returns a new instance of the file, without any content generated.
+    fn instance(&self) -> Arc<Self> {
+        Arc::new(Self {
+            ops: self.ops.clone(),
+            content: Mutex::new(None),
+        })
+    }
+
+    /// Generates the content unless it is generated already.
+    fn snapshot(&self, content: &mut Option<String>) -> VfsResult {
+        if content.is_none() {
+            *content = Some((self.ops.read)()?);
+        }
+        Ok(())
+    }
+}
+
+impl VfsNodeOps for ProcFile {
+    fn open(&self) -> VfsResult {
+        self.snapshot(&mut self.content.lock())
+    }
+
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        let perm = if self.ops.write.is_some() {
+            0o644
+        } else {
+            0o444
+        };
+        let perm = VfsNodePerm::from_bits_truncate(perm);
+        // the size is unknown until generated, so it is 0 as in Linux
+        Ok(VfsNodeAttr::new(perm, VfsNodeType::File, 0, 0))
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let mut content = self.content.lock();
+        // read without being opened, or after a write
+        self.snapshot(&mut content)?;
+        let content = content.as_deref().unwrap_or_default().as_bytes();
+        let start = content.len().min(offset as usize);
+        let len = buf.len().min(content.len() - start);
+        buf[..len].copy_from_slice(&content[start..start + len]);
+        Ok(len)
+    }
+
+    fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        let write = self.ops.write.as_ref().ok_or(VfsError::PermissionDenied)?;
+        if offset != 0 {
+            return Err(VfsError::InvalidInput);
+        }
+        let value = core::str::from_utf8(buf).map_err(|_| VfsError::InvalidInput)?;
+        write(value.trim())?;
+        // the next read sees the new value
+        *self.content.lock() = None;
+        Ok(buf.len())
+    }
+
+    fn truncate(&self, _size: u64) -> VfsResult {
+        Ok(()) // a write replaces the whole content anyway
+    }
+
+    axfs_vfs::impl_vfs_non_dir_default! {}
+}
+
+/// A symbolic link whose target is generated on each read, like
+/// `/proc/self`.
+pub struct ProcSymlink {
+    target: ContentFn,
+}
+
+impl ProcSymlink {
+    /// Creates a symbolic link whose target is generated by `target`.
+    pub fn new<T>(target: T) -> Arc<Self>
+    where
+        T: Fn() -> VfsResult<String> + Send + Sync + 'static,
+    {
+        Arc::new(Self {
+            target: Box::new(target),
+        })
+    }
+}
+
+impl VfsNodeOps for ProcSymlink {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        let len = (self.target)()?.len() as u64;
+        let perm = VfsNodePerm::from_bits_truncate(0o777);
+        Ok(VfsNodeAttr::new(perm, VfsNodeType::SymLink, len, 0))
+    }
+
+    fn readlink(&self, buf: &mut [u8]) -> VfsResult<usize> {
+        let target = (self.target)()?;
+        let len = buf.len().min(target.len());
+        buf[..len].copy_from_slice(&target.as_bytes()[..len]);
+        Ok(len)
+    }
+
+    axfs_vfs::impl_vfs_non_dir_default! {}
+}
+
+/// Entries of a directory generated on demand, like `/proc/<tid>`.
+struct DynamicEntries {
+    list: ListFn,
+    lookup: LookupFn,
+}
+
+/// A directory of fixed entries and ones generated on demand.
+pub struct ProcDir {
+    entries: Mutex<BTreeMap<String, VfsNodeRef>>,
+    dynamic: Mutex<Vec<Arc<DynamicEntries>>>,
+}
+
+impl ProcDir {
+    /// Creates an empty directory.
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self {
+            entries: Mutex::new(BTreeMap::new()),
+            dynamic: Mutex::new(Vec::new()),
+        })
+    }
+
+    /// Adds a node to the directory, replacing the one with the same name.
+    pub fn add(&self, name: &str, node: VfsNodeRef) {
+        self.entries.lock().insert(name.into(), node);
+    }
+
+    /// Adds entries generated on demand. Their names are listed by `list`,
+    /// and `lookup` returns the node of a name, or `None` if it does not
+    /// exist (anymore).
+    ///
+    /// The fixed entries take precedence over them.
+    pub fn add_dynamic<L, F>(&self, list: L, lookup: F)
+    where
+        L: Fn() -> Vec<String> + Send + Sync + 'static,
+        F: Fn(&str) -> Option<VfsNodeRef> + Send + Sync + 'static,
+    {
+        self.dynamic.lock().push(Arc::new(DynamicEntries {
+            list: Box::new(list),
+            lookup: Box::new(lookup),
+        }));
+    }
+
+    fn get(&self, name: &str) -> Option<VfsNodeRef> {
+        let node = self.entries.lock().get(name).cloned().or_else(|| {
+            // the generators may take other locks, so they run without ours
+            let dynamic = self.dynamic.lock().clone();
+            dynamic.iter().find_map(|entries| (entries.lookup)(name))
+        })?;
+        // each open of a file has its own content
+        match node.as_any().downcast_ref::<ProcFile>() {
+            Some(file) => Some(file.instance()),
+            None => Some(node),
+        }
+    }
+
+    /// Returns the subdirectory of the name, which is created if it does not
+    /// exist.
+    fn get_or_create_dir(&self, name: &str) -> VfsResult<VfsNodeRef> {
+        let mut entries = self.entries.lock();
+        let node = entries
+            .entry(name.into())
+            .or_insert_with(|| Self::new() as _);
+        as_proc_dir(node)?;
+        Ok(node.clone())
+    }
+}
+
+impl VfsNodeOps for ProcDir {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        let perm = VfsNodePerm::from_bits_truncate(0o555);
+        Ok(VfsNodeAttr::new(perm, VfsNodeType::Dir, 0, 0))
+    }
+
+    fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
+        let (name, rest) = split_path(path);
+        let node = match name {
+            "" | "." => self.clone() as VfsNodeRef,
+            _ => self.get(name).ok_or(VfsError::NotFound)?,
+        };
+        match rest {
+            Some(rest) => node.lookup(rest),
+            None => Ok(node),
+        }
+    }
+
+    fn read_dir(&self, start_idx: usize, dirents: &mut [VfsDirEntry]) -> VfsResult<usize> {
+        let mut children: Vec<(String, VfsNodeType)> = self
+            .entries
+            .lock()
+            .iter()
+            .map(|(name, node)| Ok((name.clone(), node.get_attr()?.file_type())))
+            .collect::<VfsResult<_>>()?;
+        let dynamic = self.dynamic.lock().clone();
+        for entries in dynamic.iter() {
+            for name in (entries.list)() {
+                // the entry may be gone since listed
+                if let Some(node) = (entries.lookup)(&name) {
+                    children.push((name, node.get_attr()?.file_type()));
+                }
+            }
+        }
+
+        let mut children = children.iter().skip(start_idx.max(2) - 2);
+        for (i, ent) in dirents.iter_mut().enumerate() {
+            match i + start_idx {
+                0 => *ent = VfsDirEntry::new(".", VfsNodeType::Dir),
+                1 => *ent = VfsDirEntry::new("..", VfsNodeType::Dir),
+                _ => {
+                    if let Some((name, ty)) = children.next() {
+                        *ent = VfsDirEntry::new(name, *ty);
+                    } else {
+                        return Ok(i);
+                    }
+                }
+            }
+        }
+        Ok(dirents.len())
+    }
+
+    fn create(&self, _path: &str, _ty: VfsNodeType) -> VfsResult {
+        Err(VfsError::PermissionDenied) // nodes are only added by the kernel
+    }
+
+    fn remove(&self, _path: &str) -> VfsResult {
+        Err(VfsError::PermissionDenied)
+    }
+
+    axfs_vfs::impl_vfs_dir_default! {}
+}
+
+fn as_proc_dir(node: &VfsNodeRef) -> VfsResult<&ProcDir> {
+    node.as_any()
+        .downcast_ref::<ProcDir>()
+        .ok_or(VfsError::NotADirectory)
+}
+
+fn split_path(path: &str) -> (&str, Option<&str>) {
+    let trimmed_path = path.trim_start_matches('/');
+    trimmed_path.find('/').map_or((trimmed_path, None), |n| {
+        (&trimmed_path[..n], Some(&trimmed_path[n + 1..]))
+    })
+}
+
+/// Returns the directory at the path relative to `/proc`, creating the
+/// missing ones.
+fn dir_at(path: &str) -> VfsResult<VfsNodeRef> {
+    let mut dir: VfsNodeRef = root().clone();
+    for name in path.split('/').filter(|s| !s.is_empty()) {
+        dir = as_proc_dir(&dir)?.get_or_create_dir(name)?;
+    }
+    Ok(dir)
+}
+
+/// Adds the node at the path relative to `/proc`, creating the missing
+/// parent directories. The node with the same path is replaced.
+pub fn add(path: &str, node: VfsNodeRef) -> VfsResult {
+    let path = path.trim_matches('/');
+    let (parent, name) = path.rsplit_once('/').unwrap_or(("", path));
+    if name.is_empty() {
+        return Err(VfsError::InvalidInput);
+    }
+    as_proc_dir(&dir_at(parent)?)?.add(name, node);
+    Ok(())
+}
+
+/// Adds entries generated on demand to the directory at the path relative to
+/// `/proc`, which is created if it does not exist. See
+/// [`ProcDir::add_dynamic`].
+pub fn add_dynamic<L, F>(path: &str, list: L, lookup: F) -> VfsResult
+where
+    L: Fn() -> Vec<String> + Send + Sync + 'static,
+    F: Fn(&str) -> Option<VfsNodeRef> + Send + Sync + 'static,
+{
+    as_proc_dir(&dir_at(path)?)?.add_dynamic(list, lookup);
+    Ok(())
+}
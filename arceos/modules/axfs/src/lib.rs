@@ -6,17 +6,26 @@
 //!
 //! - `fatfs`: Use [FAT] as the main filesystem and mount it on `/`. This feature
 //!    is **enabled** by default.
+//! - `ext4fs`: Use [ext4] as the main filesystem if the disk contains one,
+//!    otherwise fall back to FAT if `fatfs` is also enabled.
 //! - `devfs`: Mount [`axfs_devfs::DeviceFileSystem`] on `/dev`. This feature is
 //!    **enabled** by default.
 //! - `ramfs`: Mount [`axfs_ramfs::RamFileSystem`] on `/tmp`. This feature is
 //!    **enabled** by default.
+//! - `procfs`: Mount the [`procfs`] on `/proc`. Its files can be added by
+//!    other modules even if it is not mounted. This feature is **enabled** by
+//!    default.
 //! - `myfs`: Allow users to define their custom filesystems to override the
 //!    default. In this case, [`MyFileSystemIf`] is required to be implemented
 //!    to create and initialize other filesystems. This feature is **disabled** by
 //!    by default, but it will override other filesystem selection features if
 //!    both are enabled.
 //!
+//! File contents and disk blocks are cached in memory, see [`api::sync`] and
+//! [`api::CacheStats`].
+//!
 //! [FAT]: https://en.wikipedia.org/wiki/File_Allocation_Table
+//! [ext4]: https://en.wikipedia.org/wiki/Ext4
 //! [`MyFileSystemIf`]: fops::MyFileSystemIf
 
 #![cfg_attr(all(not(test), not(doc)), no_std)]
@@ -26,6 +35,7 @@
 extern crate log;
 extern crate alloc;
 
+mod cache;
 mod dev;
 mod fs;
 mod mounts;
@@ -34,13 +44,30 @@ mod root;
 pub mod api;
 pub mod fops;
 
+pub use self::fs::procfs;
+
 use axdriver::{prelude::*, AxDeviceContainer};
 
 /// Initializes filesystems by block devices.
+///
+/// The first device is mounted on `/`, and the others are registered for
+/// [`api::mount`].
 pub fn init_filesystems(mut blk_devs: AxDeviceContainer<AxBlockDevice>) {
     info!("Initialize filesystems...");
 
     let dev = blk_devs.take_one().expect("No block device found!");
     info!("  use block device 0: {:?}", dev.device_name());
-    self::root::init_rootfs(self::dev::Disk::new(dev));
+    let name = self::dev::add_device(dev);
+    while let Some(dev) = blk_devs.take_one() {
+        add_block_device(dev);
+    }
+    let disk = self::dev::Disk::open(&name).expect("failed to open the block device");
+    self::root::init_rootfs(disk);
+}
+
+/// Registers a block device plugged in at runtime, and returns its name for
+/// [`api::mount`], like `vdb`.
+pub fn add_block_device(dev: AxBlockDevice) -> alloc::string::String {
+    info!("  register block device: {:?}", dev.device_name());
+    self::dev::add_device(dev)
 }
@@ -1,8 +1,93 @@
 use alloc::sync::Arc;
+use axerrno::{ax_err, AxResult};
 use axfs_vfs::{VfsNodeType, VfsOps, VfsResult};
 
+use crate::dev::Disk;
 use crate::fs;
 
+/// Creates the filesystem of `fstype` for mounting, which is on the block
+/// device `source` unless it is a memory filesystem.
+///
+/// Returns the filesystem, the actual type of it, and whether it is on a
+/// block device. The type `auto` selects the filesystem on the device by its
+/// content.
+pub(crate) fn new_fs(
+    source: &str,
+    fstype: &str,
+) -> AxResult<(Arc<dyn VfsOps>, &'static str, bool)> {
+    match fstype {
+        #[cfg(feature = "ramfs")]
+        "ramfs" | "tmpfs" => Ok((ramfs(), "ramfs", false)),
+        #[cfg(feature = "procfs")]
+        "proc" => Ok((procfs()?, "proc", false)),
+        _ => {
+            let name = source.strip_prefix("/dev/").unwrap_or(source);
+            let (fs, fstype) = disk_fs(Disk::open(name)?, fstype)?;
+            Ok((fs, fstype, true))
+        }
+    }
+}
+
+/// Creates the filesystem of `fstype` on the disk.
+#[allow(unused_mut)]
+fn disk_fs(mut disk: Disk, fstype: &str) -> AxResult<(Arc<dyn VfsOps>, &'static str)> {
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "myfs")] {
+            let _ = fstype;
+            Ok((fs::myfs::new_myfs(disk), "myfs"))
+        } else {
+            #[cfg(feature = "ext4fs")]
+            if matches!(fstype, "ext4" | "auto") && fs::ext4fs::Ext4FileSystem::probe(&mut disk) {
+                return Ok((Arc::new(fs::ext4fs::Ext4FileSystem::new(disk)?), "ext4"));
+            }
+            #[cfg(feature = "fatfs")]
+            if matches!(fstype, "vfat" | "auto") {
+                return Ok((fat_fs(fs::fatfs::FatFileSystem::open(disk)?), "vfat"));
+            }
+            let _ = disk;
+            ax_err!(InvalidInput, "unsupported filesystem")
+        }
+    }
+}
+
+/// Selects the main filesystem by the content of the disk.
+pub(crate) fn main_fs(disk: Disk) -> (Arc<dyn VfsOps>, &'static str) {
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "myfs")] { // override the default filesystem
+            (fs::myfs::new_myfs(disk), "myfs")
+        } else {
+            #[cfg(feature = "ext4fs")]
+            let disk = {
+                let mut disk = disk;
+                if fs::ext4fs::Ext4FileSystem::probe(&mut disk) {
+                    info!("  use ext4 as the main filesystem");
+                    let ext4_fs = fs::ext4fs::Ext4FileSystem::new(disk)
+                        .expect("failed to initialize ext4 filesystem");
+                    return (Arc::new(ext4_fs), "ext4");
+                }
+                disk
+            };
+            cfg_if::cfg_if! {
+                if #[cfg(feature = "fatfs")] {
+                    (fat_fs(fs::fatfs::FatFileSystem::new(disk)), "vfat")
+                } else {
+                    let _ = disk;
+                    panic!("no supported filesystem found on the disk");
+                }
+            }
+        }
+    }
+}
+
+/// Initializes the FAT filesystem, which is never freed since its root
+/// directory borrows it.
+#[cfg(all(feature = "fatfs", not(feature = "myfs")))]
+fn fat_fs(fs: fs::fatfs::FatFileSystem) -> Arc<dyn VfsOps> {
+    let fs: &'static Arc<fs::fatfs::FatFileSystem> = alloc::boxed::Box::leak(Arc::new(fs).into());
+    fs.init();
+    fs.clone()
+}
+
 #[cfg(feature = "devfs")]
 pub(crate) fn devfs() -> Arc<fs::devfs::DeviceFileSystem> {
     let null = fs::devfs::NullDev;
@@ -22,29 +107,32 @@ pub(crate) fn ramfs() -> Arc<fs::ramfs::RamFileSystem> {
 }
 
 #[cfg(feature = "procfs")]
-pub(crate) fn procfs() -> VfsResult<Arc<fs::ramfs::RamFileSystem>> {
-    let procfs = fs::ramfs::RamFileSystem::new();
-    let proc_root = procfs.root_dir();
-
-    // Create /proc/sys/net/core/somaxconn
-    proc_root.create("sys", VfsNodeType::Dir)?;
-    proc_root.create("sys/net", VfsNodeType::Dir)?;
-    proc_root.create("sys/net/core", VfsNodeType::Dir)?;
-    proc_root.create("sys/net/core/somaxconn", VfsNodeType::File)?;
-    let file_somaxconn = proc_root.clone().lookup("./sys/net/core/somaxconn")?;
-    file_somaxconn.write_at(0, b"4096\n")?;
-
-    // Create /proc/sys/vm/overcommit_memory
-    proc_root.create("sys/vm", VfsNodeType::Dir)?;
-    proc_root.create("sys/vm/overcommit_memory", VfsNodeType::File)?;
-    let file_over = proc_root.clone().lookup("./sys/vm/overcommit_memory")?;
-    file_over.write_at(0, b"0\n")?;
-
-    // Create /proc/self/stat
-    proc_root.create("self", VfsNodeType::Dir)?;
-    proc_root.create("self/stat", VfsNodeType::File)?;
-
-    Ok(Arc::new(procfs))
+pub(crate) fn procfs() -> VfsResult<Arc<fs::procfs::ProcFileSystem>> {
+    use fs::procfs::{self, ProcFile};
+
+    // Create /proc/mounts, which is replaced if mounted again
+    procfs::add("mounts", ProcFile::new(proc_mounts))?;
+
+    Ok(Arc::new(fs::procfs::ProcFileSystem))
+}
+
+/// Generates `/proc/mounts` from the mount points.
+#[cfg(feature = "procfs")]
+fn proc_mounts() -> VfsResult<alloc::string::String> {
+    use crate::api::MountFlags;
+    use core::fmt::Write;
+
+    let mut buf = alloc::string::String::new();
+    for info in crate::root::mounts() {
+        let mode = if info.flags.contains(MountFlags::RDONLY) {
+            "ro"
+        } else {
+            "rw"
+        };
+        let (source, target, fstype) = (info.source, info.target, info.fstype);
+        writeln!(buf, "{source} {target} {fstype} {mode} 0 0").unwrap();
+    }
+    Ok(buf)
 }
 
 #[cfg(feature = "sysfs")]
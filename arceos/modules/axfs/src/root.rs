@@ -1,195 +1,368 @@
-//! Root directory of the filesystem
+//! Root directory of the filesystem, with a tree of mount points.
 //!
-//! TODO: it doesn't work very well if the mount points have containment relationships.
+//! Paths are made absolute and resolved first, following symbolic links
+//! across mount points, and then looked up in the innermost mount point
+//! containing them, so mount points can be nested in each other. The mount
+//! points in use by opened files and directories, or the current directory,
+//! cannot be unmounted.
 
-use alloc::{string::String, sync::Arc, vec::Vec};
+use alloc::{format, string::String, sync::Arc, vec, vec::Vec};
 use axerrno::{ax_err, AxError, AxResult};
-use axfs_vfs::{VfsNodeAttr, VfsNodeOps, VfsNodeRef, VfsNodeType, VfsOps, VfsResult};
+use axfs_vfs::{VfsNodeRef, VfsNodeType, VfsOps};
 use axsync::Mutex;
 use lazyinit::LazyInit;
 
-use crate::{api::FileType, fs, mounts};
+use crate::cache::page::CachedFile;
+use crate::{api::FileType, mounts};
+
+/// The maximum number of symbolic links followed in a lookup, as in Linux.
+const MAX_SYMLINK_FOLLOWS: usize = 40;
+
+bitflags::bitflags! {
+    /// Flags of mounting.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct MountFlags: u32 {
+        /// Files cannot be created, modified or removed in the mount point.
+        const RDONLY = 1 << 0;
+        /// Makes a directory visible at another path, instead of mounting a
+        /// filesystem.
+        const BIND = 1 << 12;
+    }
+}
 
-static CURRENT_DIR_PATH: Mutex<String> = Mutex::new(String::new());
-static CURRENT_DIR: LazyInit<Mutex<VfsNodeRef>> = LazyInit::new();
+bitflags::bitflags! {
+    /// Flags of unmounting.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct UmountFlags: u32 {
+        /// Accepted for compatibility. It makes no difference, since there
+        /// are no remote filesystems.
+        const FORCE = 1 << 0;
+        /// Detaches the mount point and the ones under it even if they are
+        /// busy. Their filesystems are released when no longer used.
+        const DETACH = 1 << 1;
+    }
+}
 
-struct MountPoint {
-    path: &'static str,
+/// A filesystem, or a directory of it for bind mounts, mounted at a path.
+pub(crate) struct MountPoint {
+    /// The absolute path of the mount point.
+    path: String,
+    /// The block device, the filesystem name, or the bound path.
+    source: String,
+    fstype: &'static str,
     fs: Arc<dyn VfsOps>,
+    /// The mounted directory, and its path in the filesystem.
+    root: VfsNodeRef,
+    root_path: String,
+    flags: MountFlags,
+    /// Whether the filesystem is on a block device, so that reads of its
+    /// files are cached.
+    on_disk: bool,
 }
 
-struct RootDirectory {
-    main_fs: Arc<dyn VfsOps>,
-    mounts: Vec<MountPoint>,
+/// Information of a mount point, as in `/proc/mounts`.
+#[derive(Debug, Clone)]
+pub struct MountInfo {
+    /// The block device, the filesystem name, or the bound path.
+    pub source: String,
+    /// The absolute path of the mount point.
+    pub target: String,
+    /// The filesystem type.
+    pub fstype: &'static str,
+    /// The mount flags.
+    pub flags: MountFlags,
 }
 
-static ROOT_DIR: LazyInit<Arc<RootDirectory>> = LazyInit::new();
-
-impl MountPoint {
-    pub fn new(path: &'static str, fs: Arc<dyn VfsOps>) -> Self {
-        Self { path, fs }
-    }
+struct RootDirectory {
+    /// Mount points in the order of mounting, the first of which is `/`.
+    mounts: Mutex<Vec<Arc<MountPoint>>>,
 }
 
-impl Drop for MountPoint {
-    fn drop(&mut self) {
-        self.fs.umount().ok();
-    }
-}
+static ROOT_DIR: LazyInit<RootDirectory> = LazyInit::new();
 
-impl RootDirectory {
-    pub const fn new(main_fs: Arc<dyn VfsOps>) -> Self {
+/// The absolute path of the current directory, ending with `/`.
+static CURRENT_DIR_PATH: Mutex<String> = Mutex::new(String::new());
+/// The mount point of the current directory, which keeps it busy.
+static CURRENT_DIR: LazyInit<Mutex<Arc<MountPoint>>> = LazyInit::new();
+
+impl MountPoint {
+    fn new(path: String, source: String, fstype: &'static str, fs: Arc<dyn VfsOps>) -> Self {
         Self {
-            main_fs,
-            mounts: Vec::new(),
+            path,
+            source,
+            fstype,
+            root: fs.root_dir(),
+            root_path: "/".into(),
+            fs,
+            flags: MountFlags::empty(),
+            on_disk: false,
         }
     }
 
-    pub fn mount(&mut self, path: &'static str, fs: Arc<dyn VfsOps>) -> AxResult {
-        if path == "/" {
-            return ax_err!(InvalidInput, "cannot mount root filesystem");
-        }
-        if !path.starts_with('/') {
-            return ax_err!(InvalidInput, "mount path must start with '/'");
-        }
-        if self.mounts.iter().any(|mp| mp.path == path) {
-            return ax_err!(InvalidInput, "mount point already exists");
-        }
-        // create the mount point in the main filesystem if it does not exist
-        self.main_fs.root_dir().create(path, FileType::Dir)?;
-        fs.mount(path, self.main_fs.root_dir().lookup(path)?)?;
-        self.mounts.push(MountPoint::new(path, fs));
-        Ok(())
+    /// Whether the files are kept in the page cache. The files of procfs are
+    /// generated when opened, and each open has its own content.
+    pub(crate) fn is_cacheable(&self) -> bool {
+        self.fstype != "proc"
     }
 
-    pub fn _umount(&mut self, path: &str) {
-        self.mounts.retain(|mp| mp.path != path);
+    pub(crate) fn is_read_only(&self) -> bool {
+        self.flags.contains(MountFlags::RDONLY)
     }
 
-    pub fn contains(&self, path: &str) -> bool {
-        self.mounts.iter().any(|mp| mp.path == path)
+    fn check_writable(&self) -> AxResult {
+        if self.is_read_only() {
+            ax_err!(ReadOnlyFilesystem, "read-only mount point")
+        } else {
+            Ok(())
+        }
     }
 
-    fn lookup_mounted_fs<F, T>(&self, path: &str, f: F) -> AxResult<T>
-    where
-        F: FnOnce(Arc<dyn VfsOps>, &str) -> AxResult<T>,
-    {
-        debug!("lookup at root: {}", path);
-        let path = path.trim_matches('/');
-        if let Some(rest) = path.strip_prefix("./") {
-            return self.lookup_mounted_fs(rest, f);
+    /// Looks up the path relative to the mount point.
+    fn lookup(&self, rest: &str) -> AxResult<VfsNodeRef> {
+        if rest.is_empty() {
+            Ok(self.root.clone())
+        } else {
+            self.root.clone().lookup(rest)
         }
+    }
 
-        let mut idx = 0;
-        let mut max_len = 0;
-
-        // Find the filesystem that has the longest mounted path match
-        // TODO: more efficient, e.g. trie
-        for (i, mp) in self.mounts.iter().enumerate() {
-            // skip the first '/'
-            if path.starts_with(&mp.path[1..]) && mp.path.len() - 1 > max_len {
-                max_len = mp.path.len() - 1;
-                idx = i;
-            }
+    fn info(&self) -> MountInfo {
+        MountInfo {
+            source: self.source.clone(),
+            target: self.path.clone(),
+            fstype: self.fstype,
+            flags: self.flags,
         }
+    }
+}
 
-        if max_len == 0 {
-            f(self.main_fs.clone(), path) // not matched any mount point
-        } else {
-            f(self.mounts[idx].fs.clone(), &path[max_len..]) // matched at `idx`
+impl Drop for MountPoint {
+    fn drop(&mut self) {
+        if !self.flags.contains(MountFlags::BIND) {
+            // The cached nodes of the files keep the filesystem alive.
+            invalidate_cache(self, "");
+            self.fs.umount().ok();
         }
     }
 }
 
-impl VfsNodeOps for RootDirectory {
-    axfs_vfs::impl_vfs_dir_default! {}
+/// Whether the absolute path is `dir` or under it.
+fn is_under(path: &str, dir: &str) -> bool {
+    dir == "/"
+        || path
+            .strip_prefix(dir)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
 
-    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
-        self.main_fs.root_dir().get_attr()
+impl RootDirectory {
+    fn new(main_fs: Arc<dyn VfsOps>, source: String, fstype: &'static str) -> Self {
+        let mut root = MountPoint::new("/".into(), source, fstype, main_fs);
+        root.on_disk = true;
+        Self {
+            mounts: Mutex::new(vec![Arc::new(root)]),
+        }
     }
 
-    fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
-        self.lookup_mounted_fs(path, |fs, rest_path| fs.root_dir().lookup(rest_path))
+    /// Returns the innermost mount point containing the absolute path, and
+    /// the path relative to it.
+    fn resolve<'a>(&self, path: &'a str) -> (Arc<MountPoint>, &'a str) {
+        let mounts = self.mounts.lock();
+        let mp = mounts
+            .iter()
+            .rev() // the last one if mounted at the same path
+            .filter(|mp| is_under(path, &mp.path))
+            .max_by_key(|mp| mp.path.len())
+            .unwrap();
+        let rest = path[mp.path.len()..].trim_start_matches('/');
+        (mp.clone(), rest)
     }
 
-    fn create(&self, path: &str, ty: VfsNodeType) -> VfsResult {
-        self.lookup_mounted_fs(path, |fs, rest_path| {
-            if rest_path.is_empty() {
-                Ok(()) // already exists
-            } else {
-                fs.root_dir().create(rest_path, ty)
-            }
-        })
+    fn mount(&self, mp: MountPoint) -> AxResult {
+        let mut mounts = self.mounts.lock();
+        if mounts.iter().any(|m| m.path == mp.path) {
+            return ax_err!(ResourceBusy, "mount point already exists");
+        }
+        mounts.push(Arc::new(mp));
+        Ok(())
     }
 
-    fn remove(&self, path: &str) -> VfsResult {
-        self.lookup_mounted_fs(path, |fs, rest_path| {
-            if rest_path.is_empty() {
-                ax_err!(PermissionDenied) // cannot remove mount points
-            } else {
-                fs.root_dir().remove(rest_path)
+    fn umount(&self, path: &str, flags: UmountFlags) -> AxResult {
+        let mut mounts = self.mounts.lock();
+        let Some(idx) = mounts.iter().position(|mp| mp.path == path) else {
+            return ax_err!(InvalidInput, "not a mount point");
+        };
+        if idx == 0 {
+            return ax_err!(ResourceBusy, "cannot unmount the root filesystem");
+        }
+        let removed: Vec<_> = if flags.contains(UmountFlags::DETACH) {
+            let (removed, kept) = mounts.drain(..).partition(|mp| is_under(&mp.path, path));
+            *mounts = kept;
+            removed
+        } else {
+            if mounts
+                .iter()
+                .any(|mp| mp.path != path && is_under(&mp.path, path))
+            {
+                return ax_err!(ResourceBusy, "mount points under it");
             }
-        })
-    }
-
-    fn rename(&self, src_path: &str, dst_path: &str) -> VfsResult {
-        self.lookup_mounted_fs(src_path, |fs, rest_path| {
-            if rest_path.is_empty() {
-                ax_err!(PermissionDenied) // cannot rename mount points
-            } else {
-                fs.root_dir().rename(rest_path, dst_path)
+            if Arc::strong_count(&mounts[idx]) > 1 {
+                return ax_err!(ResourceBusy, "mount point in use");
             }
-        })
+            vec![mounts.remove(idx)]
+        };
+        drop(mounts);
+        // the filesystems may be released here, after the lock
+        drop(removed);
+        Ok(())
     }
 }
 
 pub(crate) fn init_rootfs(disk: crate::dev::Disk) {
-    cfg_if::cfg_if! {
-        if #[cfg(feature = "myfs")] { // override the default filesystem
-            let main_fs = fs::myfs::new_myfs(disk);
-        } else if #[cfg(feature = "fatfs")] {
-            static FAT_FS: LazyInit<Arc<fs::fatfs::FatFileSystem>> = LazyInit::new();
-            FAT_FS.init_once(Arc::new(fs::fatfs::FatFileSystem::new(disk)));
-            FAT_FS.init();
-            let main_fs = FAT_FS.clone();
-        }
-    }
-
-    let mut root_dir = RootDirectory::new(main_fs);
+    let source = format!("/dev/{}", disk.name());
+    let (main_fs, fstype) = mounts::main_fs(disk);
+    ROOT_DIR.init_once(RootDirectory::new(main_fs, source, fstype));
+    CURRENT_DIR.init_once(Mutex::new(ROOT_DIR.mounts.lock()[0].clone()));
+    *CURRENT_DIR_PATH.lock() = "/".into();
 
     #[cfg(feature = "devfs")]
-    root_dir
-        .mount("/dev", mounts::devfs())
-        .expect("failed to mount devfs at /dev");
+    mount_at_init("/dev", "devfs", mounts::devfs()).expect("failed to mount devfs at /dev");
 
     #[cfg(feature = "ramfs")]
-    root_dir
-        .mount("/tmp", mounts::ramfs())
-        .expect("failed to mount ramfs at /tmp");
+    mount_at_init("/tmp", "ramfs", mounts::ramfs()).expect("failed to mount ramfs at /tmp");
 
-    // Mount another ramfs as procfs
+    // Mount procfs, whose files are generated from the kernel state
     #[cfg(feature = "procfs")]
-    root_dir // should not fail
-        .mount("/proc", mounts::procfs().unwrap())
+    mount_at_init("/proc", "proc", mounts::procfs().unwrap()) // should not fail
         .expect("fail to mount procfs at /proc");
 
     // Mount another ramfs as sysfs
     #[cfg(feature = "sysfs")]
-    root_dir // should not fail
-        .mount("/sys", mounts::sysfs().unwrap())
+    mount_at_init("/sys", "sysfs", mounts::sysfs().unwrap()) // should not fail
         .expect("fail to mount sysfs at /sys");
+}
 
-    ROOT_DIR.init_once(Arc::new(root_dir));
-    CURRENT_DIR.init_once(Mutex::new(ROOT_DIR.clone()));
-    *CURRENT_DIR_PATH.lock() = "/".into();
+/// Mounts a memory filesystem at the path, which is created in the main
+/// filesystem if it does not exist.
+#[allow(dead_code)]
+fn mount_at_init(path: &str, fstype: &'static str, fs: Arc<dyn VfsOps>) -> AxResult {
+    let main_root = ROOT_DIR.mounts.lock()[0].root.clone();
+    main_root.create(&path[1..], FileType::Dir)?;
+    fs.mount(path, main_root.lookup(&path[1..])?)?;
+    ROOT_DIR.mount(MountPoint::new(path.into(), fstype.into(), fstype, fs))
 }
 
-fn parent_node_of(dir: Option<&VfsNodeRef>, path: &str) -> VfsNodeRef {
-    if path.starts_with('/') {
-        ROOT_DIR.clone()
+/// Splits a path into its components, dropping empty ones and `.`.
+fn components(path: &str) -> impl DoubleEndedIterator<Item = &str> {
+    path.split('/').filter(|s| !s.is_empty() && *s != ".")
+}
+
+/// Reads the target of the symbolic link.
+fn link_target(node: &VfsNodeRef) -> AxResult<String> {
+    let mut buf = vec![0; node.get_attr()?.size() as usize];
+    let len = node.readlink(&mut buf)?;
+    buf.truncate(len);
+    String::from_utf8(buf).map_err(|_| AxError::InvalidData)
+}
+
+/// Returns the absolute form of the path with `.`, `..` and symbolic links
+/// resolved, where `..` of `/` is not found, like in each filesystem.
+///
+/// The last component is followed only if `follow` is set or the path ends
+/// with `/`, and it may not exist.
+fn real_path(path: &str, follow: bool) -> AxResult<String> {
+    let follow = follow || path.ends_with('/');
+    let mut buf = if path.starts_with('/') {
+        String::new()
     } else {
-        dir.cloned().unwrap_or_else(|| CURRENT_DIR.lock().clone())
+        let cwd = CURRENT_DIR_PATH.lock();
+        String::from(cwd.trim_end_matches('/'))
+    };
+    // The components left, in reverse order, and the node at `buf` if known.
+    let mut rest: Vec<String> = components(path).rev().map(String::from).collect();
+    let mut node: Option<VfsNodeRef> = None;
+    let mut follows = 0;
+    while let Some(name) = rest.pop() {
+        if name == ".." {
+            let idx = buf.rfind('/').ok_or(AxError::NotFound)?;
+            buf.truncate(idx);
+            node = None;
+            continue;
+        }
+        let parent_len = buf.len();
+        buf.push('/');
+        buf.push_str(&name);
+        if rest.is_empty() && !follow {
+            break;
+        }
+        let (mp, sub) = ROOT_DIR.resolve(&buf);
+        let child = match (sub.is_empty(), node.take()) {
+            // The parent is in the same mount point, unless this is one.
+            (false, Some(parent)) => parent.lookup(&name),
+            _ => mp.lookup(sub),
+        };
+        let child = match child {
+            Ok(child) => child,
+            Err(AxError::NotFound) if rest.is_empty() => break,
+            Err(e) => return Err(e),
+        };
+        let ty = child.get_attr()?.file_type();
+        if ty.is_symlink() {
+            follows += 1;
+            if follows > MAX_SYMLINK_FOLLOWS {
+                return ax_err!(FilesystemLoop);
+            }
+            let target = link_target(&child)?;
+            if target.starts_with('/') {
+                buf.clear();
+            } else {
+                buf.truncate(parent_len);
+            }
+            rest.extend(components(&target).rev().map(String::from));
+        } else if !rest.is_empty() && !ty.is_dir() {
+            return ax_err!(NotADirectory);
+        } else {
+            node = Some(child);
+        }
     }
+    if buf.is_empty() {
+        buf.push('/');
+    }
+    Ok(buf)
+}
+
+/// Returns the mount point of the path, and the path relative to it.
+fn resolve(path: &str, follow: bool) -> AxResult<(Arc<MountPoint>, String)> {
+    let path = real_path(path, follow)?;
+    let (mp, rest) = ROOT_DIR.resolve(&path);
+    let rest = rest.into();
+    Ok((mp, rest))
+}
+
+/// Returns the mount point of the path.
+pub(crate) fn mount_point_of(path: &str) -> AxResult<Arc<MountPoint>> {
+    Ok(resolve(path, true)?.0)
+}
+
+/// Returns the key of a file in the page cache.
+///
+/// The key is the path in the filesystem, so that the same file in bind
+/// mounts shares the pages.
+fn cache_key(mp: &MountPoint, rest: &str) -> String {
+    let fs_id = Arc::as_ptr(&mp.fs) as *const () as usize;
+    let path = axfs_vfs::path::canonicalize(&format!("{}/{}", mp.root_path, rest));
+    format!("{:#x}:{}", fs_id, path)
+}
+
+/// Opens the regular file at the path in the page cache, of which `node` is
+/// looked up. Reads are cached if it is on a block device.
+pub(crate) fn open_cached(path: &str, node: VfsNodeRef) -> AxResult<CachedFile> {
+    let (mp, rest) = resolve(path, true)?;
+    Ok(CachedFile::open(&cache_key(&mp, &rest), node, mp.on_disk))
+}
+
+/// Drops the file or directory from the page cache.
+fn invalidate_cache(mp: &MountPoint, rest: &str) {
+    crate::cache::page::invalidate(&cache_key(mp, rest));
 }
 
 pub(crate) fn absolute_path(path: &str) -> AxResult<String> {
@@ -201,11 +374,12 @@ pub(crate) fn absolute_path(path: &str) -> AxResult<String> {
     }
 }
 
-pub(crate) fn lookup(dir: Option<&VfsNodeRef>, path: &str) -> AxResult<VfsNodeRef> {
+fn lookup_at(path: &str, follow: bool) -> AxResult<VfsNodeRef> {
     if path.is_empty() {
         return ax_err!(NotFound);
     }
-    let node = parent_node_of(dir, path).lookup(path)?;
+    let (mp, rest) = resolve(path, follow)?;
+    let node = mp.lookup(&rest)?;
     if path.ends_with('/') && !node.get_attr()?.is_dir() {
         ax_err!(NotADirectory)
     } else {
@@ -213,38 +387,105 @@ pub(crate) fn lookup(dir: Option<&VfsNodeRef>, path: &str) -> AxResult<VfsNodeRe
     }
 }
 
-pub(crate) fn create_file(dir: Option<&VfsNodeRef>, path: &str) -> AxResult<VfsNodeRef> {
+/// Looks up the node at the path, following symbolic links.
+pub(crate) fn lookup(path: &str) -> AxResult<VfsNodeRef> {
+    lookup_at(path, true)
+}
+
+/// Looks up the node at the path, which is the symbolic link itself if the
+/// last component is one.
+pub(crate) fn lookup_nofollow(path: &str) -> AxResult<VfsNodeRef> {
+    lookup_at(path, false)
+}
+
+pub(crate) fn create_file(path: &str) -> AxResult<VfsNodeRef> {
     if path.is_empty() {
         return ax_err!(NotFound);
     } else if path.ends_with('/') {
         return ax_err!(NotADirectory);
     }
-    let parent = parent_node_of(dir, path);
-    parent.create(path, VfsNodeType::File)?;
-    parent.lookup(path)
+    let (mp, rest) = resolve(path, true)?;
+    if !rest.is_empty() {
+        mp.check_writable()?;
+        invalidate_cache(&mp, &rest);
+        mp.root.create(&rest, VfsNodeType::File)?;
+    }
+    mp.lookup(&rest)
+}
+
+pub(crate) fn create_dir(path: &str) -> AxResult {
+    match lookup_nofollow(path) {
+        Ok(_) => ax_err!(AlreadyExists),
+        Err(AxError::NotFound) => {
+            let (mp, rest) = resolve(path, false)?;
+            mp.check_writable()?;
+            mp.root.create(&rest, VfsNodeType::Dir)
+        }
+        Err(e) => Err(e),
+    }
 }
 
-pub(crate) fn create_dir(dir: Option<&VfsNodeRef>, path: &str) -> AxResult {
-    match lookup(dir, path) {
+/// Creates a symbolic link at `path`, which points to `target`.
+pub(crate) fn create_symlink(target: &str, path: &str) -> AxResult {
+    if target.is_empty() {
+        return ax_err!(NotFound);
+    }
+    match lookup_nofollow(path) {
         Ok(_) => ax_err!(AlreadyExists),
-        Err(AxError::NotFound) => parent_node_of(dir, path).create(path, VfsNodeType::Dir),
+        Err(AxError::NotFound) => {
+            let (mp, rest) = resolve(path, false)?;
+            mp.check_writable()?;
+            mp.root.symlink(&rest, target)
+        }
         Err(e) => Err(e),
     }
 }
 
-pub(crate) fn remove_file(dir: Option<&VfsNodeRef>, path: &str) -> AxResult {
-    let node = lookup(dir, path)?;
+/// Creates a hard link at `new` to the file at `old`, in the same mount
+/// point.
+pub(crate) fn create_link(old: &str, new: &str) -> AxResult {
+    let (old_mp, old_rest) = resolve(old, false)?;
+    let node = old_mp.lookup(&old_rest)?;
+    if node.get_attr()?.is_dir() {
+        return ax_err!(OperationNotPermitted, "hard link to a directory");
+    }
+    if lookup_nofollow(new).is_ok() {
+        return ax_err!(AlreadyExists);
+    }
+    let (mp, rest) = resolve(new, false)?;
+    if !Arc::ptr_eq(&old_mp, &mp) {
+        return ax_err!(CrossesDevices, "cannot link across mount points");
+    }
+    mp.check_writable()?;
+    mp.root.link(&rest, &node)
+}
+
+/// Returns the target of the symbolic link at the path.
+pub(crate) fn read_link(path: &str) -> AxResult<String> {
+    let node = lookup_nofollow(path)?;
+    if !node.get_attr()?.file_type().is_symlink() {
+        return ax_err!(InvalidInput, "not a symbolic link");
+    }
+    link_target(&node)
+}
+
+pub(crate) fn remove_file(path: &str) -> AxResult {
+    let node = lookup_nofollow(path)?;
     let attr = node.get_attr()?;
     if attr.is_dir() {
         ax_err!(IsADirectory)
     } else if !attr.perm().owner_writable() {
         ax_err!(PermissionDenied)
     } else {
-        parent_node_of(dir, path).remove(path)
+        let (mp, rest) = resolve(path, false)?;
+        mp.check_writable()?;
+        mp.root.remove(&rest)?;
+        invalidate_cache(&mp, &rest);
+        Ok(())
     }
 }
 
-pub(crate) fn remove_dir(dir: Option<&VfsNodeRef>, path: &str) -> AxResult {
+pub(crate) fn remove_dir(path: &str) -> AxResult {
     if path.is_empty() {
         return ax_err!(NotFound);
     }
@@ -258,19 +499,20 @@ pub(crate) fn remove_dir(dir: Option<&VfsNodeRef>, path: &str) -> AxResult {
     {
         return ax_err!(InvalidInput);
     }
-    if ROOT_DIR.contains(&absolute_path(path)?) {
-        return ax_err!(PermissionDenied);
-    }
 
-    let node = lookup(dir, path)?;
+    let node = lookup_nofollow(path)?;
     let attr = node.get_attr()?;
     if !attr.is_dir() {
-        ax_err!(NotADirectory)
+        return ax_err!(NotADirectory);
     } else if !attr.perm().owner_writable() {
-        ax_err!(PermissionDenied)
-    } else {
-        parent_node_of(dir, path).remove(path)
+        return ax_err!(PermissionDenied);
+    }
+    let (mp, rest) = resolve(path, false)?;
+    if rest.is_empty() {
+        return ax_err!(ResourceBusy); // cannot remove mount points
     }
+    mp.check_writable()?;
+    mp.root.remove(&rest)
 }
 
 pub(crate) fn current_dir() -> AxResult<String> {
@@ -278,33 +520,101 @@ pub(crate) fn current_dir() -> AxResult<String> {
 }
 
 pub(crate) fn set_current_dir(path: &str) -> AxResult {
-    let mut abs_path = absolute_path(path)?;
-    if !abs_path.ends_with('/') {
-        abs_path += "/";
-    }
-    if abs_path == "/" {
-        *CURRENT_DIR.lock() = ROOT_DIR.clone();
-        *CURRENT_DIR_PATH.lock() = "/".into();
-        return Ok(());
-    }
-
-    let node = lookup(None, &abs_path)?;
+    let mut abs_path = real_path(&absolute_path(path)?, true)?;
+    let node = lookup(&abs_path)?;
     let attr = node.get_attr()?;
     if !attr.is_dir() {
         ax_err!(NotADirectory)
     } else if !attr.perm().owner_executable() {
         ax_err!(PermissionDenied)
     } else {
-        *CURRENT_DIR.lock() = node;
+        if !abs_path.ends_with('/') {
+            abs_path += "/";
+        }
+        *CURRENT_DIR.lock() = mount_point_of(&abs_path)?;
         *CURRENT_DIR_PATH.lock() = abs_path;
         Ok(())
     }
 }
 
 pub(crate) fn rename(old: &str, new: &str) -> AxResult {
-    if parent_node_of(None, new).lookup(new).is_ok() {
+    if lookup_nofollow(new).is_ok() {
         warn!("dst file already exist, now remove it");
-        remove_file(None, new)?;
+        remove_file(new)?;
     }
-    parent_node_of(None, old).rename(old, new)
+    let (mp, old_rest) = resolve(old, false)?;
+    let (new_mp, new_rest) = resolve(new, false)?;
+    if old_rest.is_empty() {
+        return ax_err!(ResourceBusy); // cannot rename mount points
+    }
+    if !Arc::ptr_eq(&mp, &new_mp) {
+        return ax_err!(InvalidInput, "cannot rename across mount points");
+    }
+    mp.check_writable()?;
+    let result = mp.root.rename(&old_rest, &new_rest);
+    invalidate_cache(&mp, &old_rest);
+    invalidate_cache(&mp, &new_rest);
+    result
+}
+
+/// Mounts the filesystem of `fstype` on `source` at `target`, or binds the
+/// directory `source` to `target` with [`MountFlags::BIND`].
+pub(crate) fn mount(source: &str, target: &str, fstype: &str, flags: MountFlags) -> AxResult {
+    let path = real_path(target, true)?;
+    let node = lookup(&path)?;
+    if !node.get_attr()?.is_dir() {
+        return ax_err!(NotADirectory);
+    }
+    let mut mp = if flags.contains(MountFlags::BIND) {
+        let (src, rest) = resolve(source, true)?;
+        let root = src.lookup(&rest)?;
+        if !root.get_attr()?.is_dir() {
+            return ax_err!(NotADirectory);
+        }
+        MountPoint {
+            path,
+            source: real_path(source, true)?,
+            fstype: src.fstype,
+            fs: src.fs.clone(),
+            root,
+            root_path: axfs_vfs::path::canonicalize(&format!("{}/{}", src.root_path, rest)),
+            flags: MountFlags::empty(),
+            on_disk: src.on_disk,
+        }
+    } else {
+        let (fs, fstype, on_disk) = mounts::new_fs(source, fstype)?;
+        fs.mount(&path, node)?;
+        let mut mp = MountPoint::new(path, source.into(), fstype, fs);
+        mp.on_disk = on_disk;
+        mp
+    };
+    mp.flags = flags;
+    info!(
+        "mount {} at {} ({}, {:?})",
+        mp.source, mp.path, mp.fstype, flags
+    );
+    ROOT_DIR.mount(mp)
+}
+
+/// Unmounts the filesystem at `target`, and writes its cached blocks back.
+pub(crate) fn umount(target: &str, flags: UmountFlags) -> AxResult {
+    let path = real_path(target, true)?;
+    ROOT_DIR.umount(&path, flags)?;
+    info!("umount {}", path);
+    sync()
+}
+
+/// Writes the modified pages and metadata of the open files back to the
+/// filesystems, and then the modified blocks to the devices.
+pub(crate) fn sync() -> AxResult {
+    let result = crate::cache::page::sync();
+    if let Err(e) = result {
+        warn!("failed to write back the page cache: {:?}", e);
+    }
+    crate::cache::buffer::sync().and(result)
+}
+
+/// Returns the information of all mount points.
+pub(crate) fn mounts() -> Vec<MountInfo> {
+    ROOT_DIR.mounts.lock().iter().map(|mp| mp.info()).collect()
 }
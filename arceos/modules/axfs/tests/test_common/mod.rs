@@ -1,7 +1,7 @@
 use axfs::api as fs;
 use axio as io;
 
-use fs::{File, FileType, OpenOptions};
+use fs::{File, FileType, MountFlags, OpenOptions, UmountFlags};
 use io::{prelude::*, Error, Result};
 
 macro_rules! assert_err {
@@ -224,7 +224,7 @@ fn test_devfs_ramfs() -> Result<()> {
     assert_err!(fs::write("/dev/stdout", "test"), PermissionDenied);
     assert_err!(fs::create_dir("/dev/test"), PermissionDenied);
     assert_err!(fs::remove_file("/dev/null"), PermissionDenied);
-    assert_err!(fs::remove_dir("./dev"), PermissionDenied);
+    assert_err!(fs::remove_dir("./dev"), ResourceBusy);
     assert_err!(fs::remove_dir("./dev/."), InvalidInput);
     assert_err!(fs::remove_dir("///dev//..//"), InvalidInput);
 
@@ -234,7 +234,7 @@ fn test_devfs_ramfs() -> Result<()> {
     assert_err!(fs::remove_file("./dev//../..//233//.///test.txt"), NotFound);
     assert_eq!(fs::remove_file("./dev//..//233//../233/./test.txt"), Ok(()));
     assert_eq!(fs::remove_dir("dev//foo/../foo/../.././/233"), Ok(()));
-    assert_err!(fs::remove_dir("very/../dev//"), PermissionDenied);
+    assert_err!(fs::remove_dir("very/../dev//"), ResourceBusy);
 
     // tests in /tmp
     assert_eq!(fs::metadata("tmp")?.file_type(), FileType::Dir);
@@ -252,6 +252,266 @@ fn test_devfs_ramfs() -> Result<()> {
     Ok(())
 }
 
+fn test_links() -> Result<()> {
+    // symbolic links of versioned libraries
+    println!("test symbolic links in /tmp:");
+    fs::create_dir("/tmp/lib")?;
+    fs::write("/tmp/lib/libfoo.so.1.2", "foo")?;
+    fs::symlink("libfoo.so.1.2", "/tmp/lib/libfoo.so.1")?;
+    fs::symlink("/tmp/lib//libfoo.so.1", "tmp/lib/libfoo.so")?;
+    assert_eq!(fs::read_to_string("/tmp/lib/libfoo.so")?, "foo");
+    assert_eq!(
+        fs::read_link("/tmp/lib/libfoo.so")?,
+        "/tmp/lib//libfoo.so.1"
+    );
+    assert!(fs::symlink_metadata("/tmp/lib/libfoo.so")?.is_symlink());
+    assert!(fs::metadata("/tmp/lib/libfoo.so")?.is_file());
+    assert_err!(fs::read_link("/tmp/lib/libfoo.so.1.2"), InvalidInput);
+    assert_err!(fs::symlink("bar", "/tmp/lib/libfoo.so"), AlreadyExists);
+    assert_err!(fs::symlink("", "/tmp/lib/libbar.so"), NotFound);
+
+    // links across mount points, and `..` of the target
+    fs::symlink("/very/long", "/tmp/long")?;
+    fs::symlink("../tmp/lib", "/tmp/lib2")?;
+    assert!(fs::metadata("/tmp/long/path/test.txt")?.is_file());
+    assert!(fs::metadata("/tmp/long/../long")?.is_dir());
+    assert_err!(fs::metadata("/tmp/long/../lib"), NotFound);
+    assert_eq!(fs::read_to_string("/tmp/lib2/libfoo.so")?, "foo");
+    fs::set_current_dir("/tmp/lib2")?;
+    assert_eq!(fs::current_dir()?, "/tmp/lib/");
+    fs::set_current_dir("/")?;
+
+    // dangling links and loops
+    fs::symlink("new.txt", "/tmp/dangling")?;
+    assert_err!(fs::metadata("/tmp/dangling"), NotFound);
+    assert!(fs::symlink_metadata("/tmp/dangling")?.is_symlink());
+    fs::write("/tmp/dangling", "test")?;
+    assert_eq!(fs::read_to_string("/tmp/new.txt")?, "test");
+    fs::symlink("loop2", "/tmp/loop1")?;
+    fs::symlink("loop1", "/tmp/loop2")?;
+    assert_err!(fs::metadata("/tmp/loop1"), FilesystemLoop);
+    assert_err!(fs::write("/tmp/loop2/test.txt", "test"), FilesystemLoop);
+    assert!(fs::symlink_metadata("/tmp/loop1")?.is_symlink());
+
+    // hard links
+    fs::hard_link("/tmp/lib/libfoo.so.1.2", "/tmp/hard")?;
+    fs::write("/tmp/hard", "bar")?;
+    assert_eq!(fs::read_to_string("/tmp/lib/libfoo.so")?, "bar");
+    fs::remove_file("/tmp/lib/libfoo.so.1.2")?;
+    assert_eq!(fs::read_to_string("/tmp/hard")?, "bar");
+    assert_err!(fs::metadata("/tmp/lib/libfoo.so"), NotFound);
+    assert_err!(
+        fs::hard_link("/tmp/lib", "/tmp/lib3"),
+        OperationNotPermitted
+    );
+    assert_err!(fs::hard_link("/tmp/hard", "/tmp/new.txt"), AlreadyExists);
+    assert_err!(fs::hard_link("/tmp/hard", "/very/hard"), CrossesDevices);
+
+    // links are removed, not their targets
+    assert_err!(fs::remove_dir("/tmp/lib2"), NotADirectory);
+    for link in [
+        "lib/libfoo.so",
+        "lib/libfoo.so.1",
+        "long",
+        "lib2",
+        "dangling",
+    ] {
+        fs::remove_file(&format!("/tmp/{}", link))?;
+    }
+    assert!(fs::metadata("/very/long")?.is_dir());
+    for file in ["loop1", "loop2", "hard", "new.txt"] {
+        fs::remove_file(&format!("/tmp/{}", file))?;
+    }
+    fs::remove_dir("/tmp/lib")?;
+    assert_eq!(fs::read_dir("/tmp")?.count(), 0);
+
+    println!("test_links() OK!");
+    Ok(())
+}
+
+fn test_mount() -> Result<()> {
+    // mount a tmpfs on a directory of the main filesystem
+    let dirname = "/mnt//./tmpfs";
+    println!("test mount {:?}:", dirname);
+    fs::create_dir_all(dirname)?;
+    fs::mount("none", dirname, "tmpfs", MountFlags::empty())?;
+    assert_eq!(fs::read_dir(dirname)?.count(), 0);
+    fs::create_dir("/mnt/tmpfs/dir")?;
+    fs::write("/mnt/tmpfs/dir/test.txt", "test")?;
+    assert_eq!(
+        fs::read_to_string("/mnt//tmpfs/../tmpfs/dir/test.txt")?,
+        "test"
+    );
+    assert!(fs::mounts().iter().any(|m| m.target == "/mnt/tmpfs"));
+
+    // bind it read-only to another directory
+    fs::create_dir("/mnt/bind")?;
+    let flags = MountFlags::BIND | MountFlags::RDONLY;
+    fs::mount("/mnt/tmpfs/dir", "/mnt/bind", "", flags)?;
+    assert_eq!(fs::read_to_string("/mnt/bind/test.txt")?, "test");
+    assert_err!(fs::write("/mnt/bind/test.txt", "test"), ReadOnlyFilesystem);
+    assert_err!(fs::write("/mnt/bind/new.txt", "test"), ReadOnlyFilesystem);
+    assert_err!(fs::remove_file("/mnt/bind/test.txt"), ReadOnlyFilesystem);
+    fs::write("/mnt/tmpfs/dir/test.txt", "changed")?;
+    assert_eq!(fs::read_to_string("/mnt/bind/test.txt")?, "changed");
+
+    // mount points in use
+    assert_err!(
+        fs::mount("none", dirname, "tmpfs", MountFlags::empty()),
+        ResourceBusy
+    );
+    assert_err!(fs::remove_dir(dirname), ResourceBusy);
+    assert_err!(fs::umount("/", UmountFlags::empty()), ResourceBusy);
+    assert_err!(fs::umount("/mnt", UmountFlags::empty()), InvalidInput);
+    let file = File::open("/mnt/bind/test.txt")?;
+    assert_err!(fs::umount("/mnt/bind", UmountFlags::empty()), ResourceBusy);
+    drop(file);
+    fs::umount("/mnt/bind", UmountFlags::empty())?;
+    fs::set_current_dir("/mnt/tmpfs/dir")?;
+    assert_err!(fs::umount(dirname, UmountFlags::empty()), ResourceBusy);
+    fs::set_current_dir("/")?;
+
+    // lazy unmount, the open file is still usable
+    let mut file = File::open("/mnt/tmpfs/dir/test.txt")?;
+    fs::umount(dirname, UmountFlags::DETACH)?;
+    assert_err!(fs::metadata("/mnt/tmpfs/dir"), NotFound);
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)?;
+    assert_eq!(contents, "changed");
+    drop(file);
+    assert!(!fs::mounts().iter().any(|m| m.target.starts_with("/mnt")));
+
+    fs::remove_dir(dirname)?;
+    fs::remove_dir("/mnt/bind")?;
+    fs::remove_dir("/mnt")?;
+
+    println!("test_mount() OK!");
+    Ok(())
+}
+
+fn test_mapped_pages() -> Result<()> {
+    use axfs::fops;
+
+    let fname = "/mapped.txt";
+    println!("test mapped pages of {:?}:", fname);
+    fs::write(fname, "hello")?;
+    let mut opts = fops::OpenOptions::new();
+    opts.read(true);
+    opts.write(true);
+    let file1 = fops::File::open(fname, &opts)?;
+    let file2 = fops::File::open(fname, &opts)?;
+
+    // all handles share the frame
+    let page = file1.map_page(0)?;
+    assert_eq!(file2.map_page(0)?, page);
+    let data = unsafe { &mut *page.as_ptr() };
+    assert_eq!(&data[..6], b"hello\0");
+
+    // modified through the mapping, and written back when no longer mapped
+    data[..5].copy_from_slice(b"HELLO");
+    file2.set_page_dirty(0)?;
+    assert_eq!(fs::read_to_string(fname)?, "HELLO");
+    assert_eq!(fs::page_cache_stats().dirty, 1);
+    file2.unmap_page(0)?;
+    assert_eq!(fs::page_cache_stats().dirty, 1);
+
+    // writes and truncation update the mapped frame
+    fs::write(fname, "abc")?;
+    assert_eq!(&data[..6], b"abc\0\0\0");
+    file1.set_page_dirty(0)?;
+    file1.unmap_page(0)?;
+    assert_eq!(fs::page_cache_stats().dirty, 0);
+    assert_eq!(fs::read_to_string(fname)?, "abc");
+
+    let dir = fops::File::open("/very", &fops::OpenOptions::new());
+    assert!(dir.is_err() || dir?.map_page(0).is_err());
+    drop((file1, file2));
+    fs::remove_file(fname)?;
+    println!("test_mapped_pages() OK!");
+    Ok(())
+}
+
+#[cfg(feature = "procfs")]
+fn test_procfs() -> Result<()> {
+    use axfs::procfs::{self, ProcFile, ProcSymlink};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static VALUE: AtomicUsize = AtomicUsize::new(128);
+
+    // /proc/mounts follows the mount points
+    println!("test procfs:");
+    let mounts = fs::read_to_string("/proc/mounts")?;
+    assert!(mounts.lines().any(|l| l == "ramfs /tmp ramfs rw 0 0"));
+    fs::create_dir("/tmp/ro")?;
+    fs::mount("none", "/tmp/ro", "tmpfs", MountFlags::RDONLY)?;
+    let mounts = fs::read_to_string("/proc/mounts")?;
+    assert!(mounts.lines().any(|l| l == "none /tmp/ro ramfs ro 0 0"));
+    fs::umount("/tmp/ro", UmountFlags::empty())?;
+    fs::remove_dir("/tmp/ro")?;
+    assert!(!fs::read_to_string("/proc/mounts")?.contains("/tmp/ro"));
+
+    // a tunable takes effect at once
+    let tunable = ProcFile::new_rw(
+        || Ok(format!("{}\n", VALUE.load(Ordering::Relaxed))),
+        |value| {
+            let value = value.parse().map_err(|_| Error::InvalidInput)?;
+            VALUE.store(value, Ordering::Relaxed);
+            Ok(())
+        },
+    );
+    procfs::add("sys/test/value", tunable)?;
+    assert_eq!(fs::read_to_string("/proc/sys/test/value")?, "128\n");
+    fs::write("/proc/sys/test/value", "4096\n")?;
+    assert_eq!(VALUE.load(Ordering::Relaxed), 4096);
+    assert_eq!(fs::read_to_string("/proc/sys/test/value")?, "4096\n");
+    assert_err!(fs::write("/proc/sys/test/value", "-1"), InvalidInput);
+    assert_err!(fs::write("/proc/mounts", "test"), PermissionDenied);
+    assert_err!(fs::create_dir("/proc/test"), PermissionDenied);
+    assert_err!(fs::remove_file("/proc/mounts"), PermissionDenied);
+
+    // the content is generated on open, and read in chunks consistently
+    static OPENS: AtomicUsize = AtomicUsize::new(0);
+    let counter = ProcFile::new(|| {
+        let n = OPENS.fetch_add(1, Ordering::Relaxed);
+        Ok(format!("{}{}\n", n, "-".repeat(8192)))
+    });
+    procfs::add("test_opens", counter)?;
+    let mut file = File::open("/proc/test_opens")?;
+    let mut head = [0; 1];
+    file.read_exact(&mut head)?;
+    let mut rest = String::new();
+    file.read_to_string(&mut rest)?;
+    assert_eq!(head, *b"0");
+    assert_eq!(rest.len(), 8193);
+    assert!(fs::read_to_string("/proc/test_opens")?.starts_with('1'));
+    assert_eq!(OPENS.load(Ordering::Relaxed), 2);
+
+    // entries generated on demand, and a link to one of them
+    procfs::add_dynamic(
+        "test",
+        || vec!["1".into(), "2".into()],
+        |name| match name {
+            "1" | "2" => {
+                let content = format!("{}\n", name);
+                Some(ProcFile::new(move || Ok(content.clone())))
+            }
+            _ => None,
+        },
+    )?;
+    procfs::add("test/last", ProcSymlink::new(|| Ok("2".into())))?;
+    let dirents = fs::read_dir("/proc/test")?
+        .map(|e| e.unwrap().file_name())
+        .collect::<Vec<_>>();
+    assert_eq!(dirents, ["last", "1", "2"]);
+    assert_eq!(fs::read_to_string("/proc/test/1")?, "1\n");
+    assert_eq!(fs::read_to_string("/proc/test/last")?, "2\n");
+    assert_eq!(fs::read_link("/proc/test/last")?, "2");
+    assert_err!(fs::metadata("/proc/test/3"), NotFound);
+
+    println!("test_procfs() OK!");
+    Ok(())
+}
+
 pub fn test_all() {
     test_read_write_file().expect("test_read_write_file() failed");
     test_read_dir().expect("test_read_dir() failed");
@@ -259,4 +519,9 @@ pub fn test_all() {
     test_create_file_dir().expect("test_create_file_dir() failed");
     test_remove_file_dir().expect("test_remove_file_dir() failed");
     test_devfs_ramfs().expect("test_devfs_ramfs() failed");
+    test_links().expect("test_links() failed");
+    test_mount().expect("test_mount() failed");
+    test_mapped_pages().expect("test_mapped_pages() failed");
+    #[cfg(feature = "procfs")]
+    test_procfs().expect("test_procfs() failed");
 }
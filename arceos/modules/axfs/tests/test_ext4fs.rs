@@ -0,0 +1,68 @@
+#![cfg(all(feature = "ext4fs", not(feature = "myfs")))]
+
+mod test_common;
+
+use axdriver::AxDeviceContainer;
+use axdriver_block::ramdisk::RamDisk;
+use axfs::api as fs;
+use axio::Result;
+
+const IMG_PATH: &str = "resources/ext4.img";
+
+fn make_disk() -> std::io::Result<RamDisk> {
+    let path = std::env::current_dir()?.join(IMG_PATH);
+    println!("Loading disk image from {:?} ...", path);
+    let data = std::fs::read(path)?;
+    println!("size = {} bytes", data.len());
+    Ok(RamDisk::from(&data))
+}
+
+fn test_links_on_disk() -> Result<()> {
+    // a fast symbolic link in the inode, and a slow one in a block
+    let long_target = "./very-long-dir-name/../very-long-dir-name/../very/long/path/test.txt";
+    assert!(long_target.len() > 60);
+    fs::symlink("very/long/path", "/path-link")?;
+    fs::symlink(long_target, "/very/test-link")?;
+    assert_eq!(fs::read_link("/path-link")?, "very/long/path");
+    assert_eq!(fs::read_link("/very/test-link")?, long_target);
+    assert!(fs::metadata("/path-link/test.txt")?.is_file());
+    assert_eq!(
+        fs::read_to_string("/path-link/test.txt")?,
+        fs::read_to_string("/very/long/path/test.txt")?
+    );
+    assert!(fs::symlink_metadata("/very/test-link")?.is_symlink());
+
+    // hard links share the inode
+    fs::hard_link("/very/long/path/test.txt", "/very/hard-link.txt")?;
+    assert_eq!(
+        fs::read("/very/hard-link.txt")?,
+        fs::read("/very/long/path/test.txt")?
+    );
+
+    fs::remove_file("/path-link")?;
+    fs::remove_file("/very/test-link")?;
+    fs::remove_file("/very/hard-link.txt")?;
+    assert!(fs::metadata("/very/long/path/test.txt")?.is_file());
+    Ok(())
+}
+
+#[test]
+fn test_ext4fs() {
+    println!("Testing ext4fs with ramdisk ...");
+
+    let disk = make_disk().expect("failed to load disk image");
+    axtask::init_scheduler(); // call this to use `axsync::Mutex`.
+    axfs::init_filesystems(AxDeviceContainer::from_one(disk));
+
+    test_common::test_all();
+    test_links_on_disk().expect("test_links_on_disk() failed");
+
+    let buffer_stats = axfs::api::buffer_cache_stats();
+    let page_stats = axfs::api::page_cache_stats();
+    println!("buffer cache: {:?}", buffer_stats);
+    println!("page cache: {:?}", page_stats);
+    assert!(buffer_stats.hits > 0);
+    assert!(page_stats.hits > 0);
+    axfs::api::sync().expect("failed to sync");
+    assert_eq!(axfs::api::buffer_cache_stats().dirty, 0);
+}
@@ -0,0 +1,139 @@
+#![cfg(all(feature = "ext4fs", not(feature = "myfs")))]
+
+use axdriver::AxDeviceContainer;
+use axdriver_block::ramdisk::RamDisk;
+use axfs::api as fs;
+use axio::{prelude::*, Error, Result, SeekFrom};
+use fs::File;
+
+const IMG_PATH: &str = "resources/ext4_fixtures.img";
+
+const GIB: u64 = 1 << 30;
+
+fn make_disk() -> std::io::Result<RamDisk> {
+    let path = std::env::current_dir()?.join(IMG_PATH);
+    println!("Loading disk image from {:?} ...", path);
+    let data = std::fs::read(path)?;
+    println!("size = {} bytes", data.len());
+    Ok(RamDisk::from(&data))
+}
+
+fn test_journal_replay() -> Result<()> {
+    // the committed transaction is replayed on mount
+    assert_eq!(fs::read_to_string("/journal.txt")?, "journal: new\n");
+    println!("test_journal_replay() OK!");
+    Ok(())
+}
+
+fn test_htree_dir() -> Result<()> {
+    let count = fs::read_dir("/htree")?
+        .filter(|entry| entry.as_ref().unwrap().file_name().starts_with("file-"))
+        .count();
+    assert_eq!(count, 1000);
+    for i in [1, 2, 500, 999, 1000] {
+        let fname = format!("/htree/file-{}.txt", i);
+        assert_eq!(fs::read_to_string(&fname)?, format!("file {}\n", i));
+    }
+    assert_eq!(
+        fs::metadata("/htree/file-1001.txt").err(),
+        Some(Error::NotFound)
+    );
+
+    // insert into and remove from the indexed directory
+    for i in 1001..=1100 {
+        fs::write(&format!("/htree/file-{}.txt", i), format!("file {}\n", i))?;
+    }
+    for i in (1..=1100).step_by(3) {
+        fs::remove_file(&format!("/htree/file-{}.txt", i))?;
+    }
+    for i in 1..=1100 {
+        let fname = format!("/htree/file-{}.txt", i);
+        if i % 3 == 1 {
+            assert_eq!(fs::metadata(&fname).err(), Some(Error::NotFound));
+        } else {
+            assert_eq!(fs::read_to_string(&fname)?, format!("file {}\n", i));
+        }
+    }
+    println!("test_htree_dir() OK!");
+    Ok(())
+}
+
+fn test_extent_tree() -> Result<()> {
+    // every other block is a hole, and the last one has only the text
+    let data = fs::read("/fragmented.bin")?;
+    assert_eq!(data.len(), 798 * 1024 + 9);
+    for (i, block) in data.chunks(1024).enumerate() {
+        if i % 2 == 0 {
+            let text = format!("block {:03}", i / 2);
+            assert_eq!(&block[..text.len()], text.as_bytes());
+            assert!(block[text.len()..].iter().all(|&b| b == 0));
+        } else {
+            assert!(block.iter().all(|&b| b == 0));
+        }
+    }
+
+    // fill the holes, which merges and splits the leaves
+    let mut file = File::options().write(true).open("/fragmented.bin")?;
+    for i in (1..799).step_by(2) {
+        file.seek(SeekFrom::Start(i * 1024))?;
+        file.write_all(format!("hole {:03}", i).as_bytes())?;
+    }
+    drop(file);
+    let data = fs::read("/fragmented.bin")?;
+    assert_eq!(data.len(), 798 * 1024 + 9);
+    for (i, block) in data.chunks(1024).enumerate() {
+        let text = if i % 2 == 0 {
+            format!("block {:03}", i / 2)
+        } else {
+            format!("hole {:03}", i)
+        };
+        assert_eq!(&block[..text.len()], text.as_bytes());
+    }
+    println!("test_extent_tree() OK!");
+    Ok(())
+}
+
+fn test_large_file() -> Result<()> {
+    let mut file = File::options().read(true).write(true).open("/large.bin")?;
+    assert_eq!(file.metadata()?.len(), 4 * GIB + 4096 + 14);
+
+    let mut buf = [0; 14];
+    file.read_exact(&mut buf)?;
+    assert_eq!(&buf, b"Rust is cool!\n");
+    file.seek(SeekFrom::Start(4 * GIB + 4096))?;
+    file.read_exact(&mut buf)?;
+    assert_eq!(&buf, b"Rust is cool!\n");
+
+    // a hole below 4 GiB, and an extension beyond it
+    file.seek(SeekFrom::Start(3 * GIB))?;
+    file.read_exact(&mut buf)?;
+    assert_eq!(buf, [0; 14]);
+    file.seek(SeekFrom::Start(5 * GIB))?;
+    file.write_all(b"Hello, world!\n")?;
+    drop(file);
+
+    let mut file = File::open("/large.bin")?;
+    assert_eq!(file.metadata()?.len(), 5 * GIB + 14);
+    file.seek(SeekFrom::Start(5 * GIB))?;
+    file.read_exact(&mut buf)?;
+    assert_eq!(&buf, b"Hello, world!\n");
+    println!("test_large_file() OK!");
+    Ok(())
+}
+
+#[test]
+fn test_ext4fs_fixtures() {
+    println!("Testing ext4fs fixtures with ramdisk ...");
+
+    let disk = make_disk().expect("failed to load disk image");
+    axtask::init_scheduler(); // call this to use `axsync::Mutex`.
+    axfs::init_filesystems(AxDeviceContainer::from_one(disk));
+
+    test_journal_replay().expect("test_journal_replay() failed");
+    test_htree_dir().expect("test_htree_dir() failed");
+    test_extent_tree().expect("test_extent_tree() failed");
+    test_large_file().expect("test_large_file() failed");
+
+    axfs::api::sync().expect("failed to sync");
+    assert_eq!(axfs::api::buffer_cache_stats().dirty, 0);
+}
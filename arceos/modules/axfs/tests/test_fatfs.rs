@@ -24,4 +24,13 @@ fn test_fatfs() {
     axfs::init_filesystems(AxDeviceContainer::from_one(disk));
 
     test_common::test_all();
+
+    let buffer_stats = axfs::api::buffer_cache_stats();
+    let page_stats = axfs::api::page_cache_stats();
+    println!("buffer cache: {:?}", buffer_stats);
+    println!("page cache: {:?}", page_stats);
+    assert!(buffer_stats.hits > 0);
+    assert!(page_stats.hits > 0);
+    axfs::api::sync().expect("failed to sync");
+    assert_eq!(axfs::api::buffer_cache_stats().dirty, 0);
 }
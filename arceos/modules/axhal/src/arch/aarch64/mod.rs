@@ -35,6 +35,19 @@ pub fn wait_for_irqs() {
     aarch64_cpu::asm::wfi();
 }
 
+/// Enables interrupts and waits for them, without taking any interrupt in
+/// between.
+///
+/// It must be called with interrupts disabled, which are enabled on return.
+/// The wake-up events checked before it are not missed then.
+#[inline]
+pub fn enable_irqs_and_wait() {
+    // A pending interrupt wakes up the CPU even if it is masked, and it is
+    // taken once enabled.
+    aarch64_cpu::asm::wfi();
+    enable_irqs();
+}
+
 /// Halt the current CPU.
 #[inline]
 pub fn halt() {
@@ -37,8 +37,16 @@ fn invalid_exception(tf: &TrapFrame, kind: TrapKind, source: TrapSource) {
 }
 
 #[no_mangle]
-fn handle_irq_exception(_tf: &TrapFrame) {
+fn handle_irq_exception(tf: &mut TrapFrame) {
+    #[cfg(feature = "uspace")]
+    if tf.is_user() {
+        crate::trap::handle_user_entry(tf);
+    }
     handle_trap!(IRQ, 0);
+    #[cfg(feature = "uspace")]
+    if tf.is_user() {
+        crate::trap::handle_user_return(tf);
+    }
 }
 
 fn handle_instruction_abort(tf: &TrapFrame, iss: u64, is_user: bool) {
@@ -76,6 +84,9 @@ fn handle_data_abort(tf: &TrapFrame, iss: u64, is_user: bool) {
         access_flags |= MappingFlags::USER;
     }
     let vaddr = va!(FAR_EL1.get() as usize);
+    if !is_user && crate::trap::is_kernel_stack_overflow(vaddr) {
+        crate::trap::handle_kernel_stack_overflow(vaddr, tf.elr as usize);
+    }
 
     // Only handle Translation fault and Permission fault
     if !matches!(iss & 0b111100, 0b0100 | 0b1100) // IFSC or DFSC bits
@@ -95,9 +106,19 @@ fn handle_data_abort(tf: &TrapFrame, iss: u64, is_user: bool) {
 
 #[no_mangle]
 fn handle_sync_exception(tf: &mut TrapFrame) {
+    #[cfg(feature = "uspace")]
+    if tf.is_user() {
+        crate::trap::handle_user_entry(tf);
+    }
     let esr = ESR_EL1.extract();
     let iss = esr.read(ESR_EL1::ISS);
     match esr.read_as_enum(ESR_EL1::EC) {
+        // `ELR_EL1` already points to the instruction after `SVC`.
+        #[cfg(feature = "uspace")]
+        Some(ESR_EL1::EC::Value::SVC64) => {
+            tf.r[0] = crate::trap::handle_syscall(tf, tf.r[8] as usize) as u64;
+        }
+        #[cfg(not(feature = "uspace"))]
         Some(ESR_EL1::EC::Value::SVC64) => {
             warn!("No syscall is supported currently!");
         }
@@ -119,4 +140,8 @@ fn handle_sync_exception(tf: &mut TrapFrame) {
             );
         }
     }
+    #[cfg(feature = "uspace")]
+    if tf.is_user() {
+        crate::trap::handle_user_return(tf);
+    }
 }
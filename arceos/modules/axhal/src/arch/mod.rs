@@ -12,3 +12,9 @@ cfg_if::cfg_if! {
         pub use self::aarch64::*;
     }
 }
+
+/// The lowest address of the kernel stack of the current task, see
+/// [`crate::trap::set_kernel_stack_limit`]. It is also read by the trap entry
+/// of riscv, so it is defined here for all architectures.
+#[percpu::def_percpu]
+pub(crate) static KSTACK_LIMIT: usize = 0;
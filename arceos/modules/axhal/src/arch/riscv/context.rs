@@ -85,6 +85,30 @@ impl TrapFrame {
     pub const fn arg5(&self) -> usize {
         self.regs.a5
     }
+    /// Gets the instruction pointer.
+    pub const fn get_ip(&self) -> usize {
+        self.sepc
+    }
+
+    /// Sets the instruction pointer.
+    pub const fn set_ip(&mut self, pc: usize) {
+        self.sepc = pc;
+    }
+
+    /// Gets the stack pointer.
+    pub const fn get_sp(&self) -> usize {
+        self.regs.sp
+    }
+
+    /// Sets the stack pointer.
+    pub const fn set_sp(&mut self, sp: usize) {
+        self.regs.sp = sp;
+    }
+
+    /// Sets the return value register.
+    pub const fn set_retval(&mut self, val: usize) {
+        self.regs.a0 = val;
+    }
 }
 
 /// Saved hardware states of a task.
@@ -240,6 +264,12 @@ impl UspaceContext {
         self.0.regs.a0 = a0;
     }
 
+    /// Sets the thread pointer register, which points to the thread-local
+    /// storage of user space.
+    pub const fn set_tls(&mut self, tp: usize) {
+        self.0.regs.tp = tp;
+    }
+
     /// Enters user space.
     ///
     /// It restores the user registers and jumps to the user entry point
@@ -1,14 +1,27 @@
 use page_table_entry::MappingFlags;
 use riscv::register::scause::{self, Exception as E, Trap};
-use riscv::register::stval;
+use riscv::register::{sepc, stval};
 
 use super::TrapFrame;
 
 include_asm_marcos!();
 
+const OVERFLOW_STACK_SIZE: usize = 0x4000;
+
+#[repr(align(16))]
+struct OverflowStack([u8; OVERFLOW_STACK_SIZE]);
+
+/// The stack to handle kernel stack overflows on, as the overflowed stack has
+/// no room for the trap frame.
+#[percpu::def_percpu]
+static OVERFLOW_STACK: OverflowStack = OverflowStack([0; OVERFLOW_STACK_SIZE]);
+
 core::arch::global_asm!(
     include_str!("trap.S"),
     trapframe_size = const core::mem::size_of::<TrapFrame>(),
+    kstack_limit = sym crate::arch::__PERCPU_KSTACK_LIMIT,
+    overflow_stack = sym __PERCPU_OVERFLOW_STACK,
+    overflow_stack_size = const OVERFLOW_STACK_SIZE,
 );
 
 fn handle_breakpoint(sepc: &mut usize) {
@@ -21,6 +34,9 @@ fn handle_page_fault(tf: &TrapFrame, mut access_flags: MappingFlags, is_user: bo
         access_flags |= MappingFlags::USER;
     }
     let vaddr = va!(stval::read());
+    if !is_user && crate::trap::is_kernel_stack_overflow(vaddr) {
+        crate::trap::handle_kernel_stack_overflow(vaddr, tf.sepc);
+    }
     if !handle_trap!(PAGE_FAULT, vaddr, access_flags, is_user) {
         panic!(
             "Unhandled {} Page Fault @ {:#x}, fault_vaddr={:#x} ({:?}):\n{:#x?}",
@@ -33,14 +49,25 @@ fn handle_page_fault(tf: &TrapFrame, mut access_flags: MappingFlags, is_user: bo
     }
 }
 
+/// Called on the overflow stack, when a trap from S mode is taken with the
+/// stack pointer `sp` too close to the kernel stack limit.
+#[no_mangle]
+fn riscv_kernel_stack_overflow(sp: usize) -> ! {
+    crate::trap::handle_kernel_stack_overflow(va!(sp), sepc::read())
+}
+
 #[no_mangle]
 fn riscv_trap_handler(tf: &mut TrapFrame, from_user: bool) {
+    #[cfg(feature = "uspace")]
+    if from_user {
+        crate::trap::handle_user_entry(tf);
+    }
     let scause = scause::read();
     match scause.cause() {
         #[cfg(feature = "uspace")]
         Trap::Exception(E::UserEnvCall) => {
-            tf.regs.a0 = crate::trap::handle_syscall(tf, tf.regs.a7) as usize;
             tf.sepc += 4;
+            tf.regs.a0 = crate::trap::handle_syscall(tf, tf.regs.a7) as usize;
         }
         Trap::Exception(E::LoadPageFault) => handle_page_fault(tf, MappingFlags::READ, from_user),
         Trap::Exception(E::StorePageFault) => handle_page_fault(tf, MappingFlags::WRITE, from_user),
@@ -60,4 +87,8 @@ fn riscv_trap_handler(tf: &mut TrapFrame, from_user: bool) {
             );
         }
     }
+    #[cfg(feature = "uspace")]
+    if from_user {
+        crate::trap::handle_user_return(tf);
+    }
 }
@@ -1,11 +1,15 @@
 use core::fmt;
 
+use x86::irq::DOUBLE_FAULT_VECTOR;
 use x86_64::addr::VirtAddr;
 use x86_64::structures::idt::{Entry, HandlerFunc, InterruptDescriptorTable};
 use x86_64::structures::DescriptorTablePointer;
 
 const NUM_INT: usize = 256;
 
+/// The index of the double fault stack in the interrupt stack table of TSS.
+pub(crate) const DOUBLE_FAULT_IST_INDEX: u16 = 0;
+
 /// A wrapper of the Interrupt Descriptor Table (IDT).
 #[repr(transparent)]
 pub struct IdtStruct {
@@ -33,7 +37,12 @@ impl IdtStruct {
         };
         for i in 0..NUM_INT {
             #[allow(clippy::missing_transmute_annotations)]
-            entries[i].set_handler_fn(unsafe { core::mem::transmute(ENTRIES[i]) });
+            let opts = entries[i].set_handler_fn(unsafe { core::mem::transmute(ENTRIES[i]) });
+            if i == DOUBLE_FAULT_VECTOR as usize {
+                // Double faults may be caused by kernel stack overflows, so
+                // they are handled on another stack.
+                unsafe { opts.set_stack_index(DOUBLE_FAULT_IST_INDEX) };
+            }
         }
         idt
     }
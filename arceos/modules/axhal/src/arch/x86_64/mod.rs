@@ -14,6 +14,9 @@ use x86_64::instructions::interrupts;
 pub use self::context::{ExtendedState, FxsaveArea, TaskContext, TrapFrame};
 pub use self::gdt::GdtStruct;
 pub use self::idt::IdtStruct;
+pub(crate) use self::idt::DOUBLE_FAULT_IST_INDEX;
+#[cfg(target_os = "none")]
+pub(crate) use self::trap::double_fault_stack_top;
 pub use x86_64::structures::tss::TaskStateSegment;
 
 /// Allows the current CPU to respond to interrupts.
@@ -48,6 +51,22 @@ pub fn wait_for_irqs() {
     }
 }
 
+/// Enables interrupts and waits for them, without taking any interrupt in
+/// between.
+///
+/// It must be called with interrupts disabled, which are enabled on return.
+/// The wake-up events checked before it are not missed then.
+#[inline]
+pub fn enable_irqs_and_wait() {
+    if cfg!(target_os = "none") {
+        // Interrupts are not taken until the instruction after `sti`.
+        unsafe { asm!("sti; hlt") }
+    } else {
+        enable_irqs();
+        core::hint::spin_loop()
+    }
+}
+
 /// Halt the current CPU.
 #[inline]
 pub fn halt() {
@@ -9,10 +9,28 @@ core::arch::global_asm!(include_str!("trap.S"));
 const IRQ_VECTOR_START: u8 = 0x20;
 const IRQ_VECTOR_END: u8 = 0xff;
 
+const DOUBLE_FAULT_STACK_SIZE: usize = 0x4000;
+
+#[repr(align(16))]
+struct DoubleFaultStack([u8; DOUBLE_FAULT_STACK_SIZE]);
+
+/// The stack to handle double faults on, e.g., the page faults that cannot be
+/// delivered as the kernel stack overflows into its guard area.
+#[percpu::def_percpu]
+static DOUBLE_FAULT_STACK: DoubleFaultStack = DoubleFaultStack([0; DOUBLE_FAULT_STACK_SIZE]);
+
+/// Returns the top of the double fault stack of the current CPU.
+pub(crate) fn double_fault_stack_top() -> usize {
+    unsafe { DOUBLE_FAULT_STACK.current_ptr() as usize + DOUBLE_FAULT_STACK_SIZE }
+}
+
 fn handle_page_fault(tf: &TrapFrame) {
     let access_flags = err_code_to_flags(tf.error_code)
         .unwrap_or_else(|e| panic!("Invalid #PF error code: {:#x}", e));
     let vaddr = va!(unsafe { cr2() });
+    if !tf.is_user() && crate::trap::is_kernel_stack_overflow(vaddr) {
+        crate::trap::handle_kernel_stack_overflow(vaddr, tf.rip as usize);
+    }
     if !handle_trap!(PAGE_FAULT, vaddr, access_flags, tf.is_user()) {
         panic!(
             "Unhandled {} #PF @ {:#x}, fault_vaddr={:#x}, error_code={:#x} ({:?}):\n{:#x?}",
@@ -27,9 +45,20 @@ fn handle_page_fault(tf: &TrapFrame) {
 }
 
 #[no_mangle]
-fn x86_trap_handler(tf: &TrapFrame) {
+fn x86_trap_handler(tf: &mut TrapFrame) {
+    #[cfg(feature = "uspace")]
+    if tf.is_user() {
+        crate::trap::handle_user_entry(tf);
+    }
     match tf.vector as u8 {
         PAGE_FAULT_VECTOR => handle_page_fault(tf),
+        DOUBLE_FAULT_VECTOR => {
+            let vaddr = va!(unsafe { cr2() });
+            if crate::trap::is_kernel_stack_overflow(vaddr) {
+                crate::trap::handle_kernel_stack_overflow(vaddr, tf.rip as usize);
+            }
+            panic!("#DF @ {:#x}:\n{:#x?}", tf.rip, tf);
+        }
         BREAKPOINT_VECTOR => debug!("#BP @ {:#x} ", tf.rip),
         GENERAL_PROTECTION_FAULT_VECTOR => {
             panic!(
@@ -51,6 +80,10 @@ fn x86_trap_handler(tf: &TrapFrame) {
             );
         }
     }
+    #[cfg(feature = "uspace")]
+    if tf.is_user() {
+        crate::trap::handle_user_return(tf);
+    }
 }
 
 fn vec_to_str(vec: u64) -> &'static str {
@@ -1,21 +1,55 @@
 //! Interrupt management.
 
+use core::sync::atomic::{AtomicU64, Ordering};
+
 use handler_table::HandlerTable;
 
-use crate::platform::irq::{dispatch_irq, MAX_IRQ_COUNT};
+use crate::platform::irq::dispatch_irq;
 use crate::trap::{register_trap_handler, IRQ};
 
-pub use crate::platform::irq::{register_handler, set_enable};
+pub use crate::platform::irq::{
+    register_handler, send_ipi, set_enable, IPI_IRQ_NUM, MAX_IRQ_COUNT,
+};
 
 /// The type if an IRQ handler.
 pub type IrqHandler = handler_table::Handler;
 
 static IRQ_HANDLER_TABLE: HandlerTable<MAX_IRQ_COUNT> = HandlerTable::new();
 
+/// Nesting depth of the IRQ handlers running on this CPU.
+#[percpu::def_percpu]
+static IRQ_DEPTH: usize = 0;
+
+/// Number of times that each IRQ was taken on each CPU.
+static IRQ_COUNTS: [[AtomicU64; MAX_IRQ_COUNT]; axconfig::SMP] =
+    [const { [const { AtomicU64::new(0) }; MAX_IRQ_COUNT] }; axconfig::SMP];
+
+/// Returns the number of times that the IRQ was taken on the CPU, as in
+/// `/proc/interrupts`.
+///
+/// # Panics
+///
+/// Panics if `cpu_id` is not less than [`axconfig::SMP`].
+pub fn irq_count(irq_num: usize, cpu_id: usize) -> u64 {
+    IRQ_COUNTS[cpu_id]
+        .get(irq_num)
+        .map_or(0, |count| count.load(Ordering::Relaxed))
+}
+
+/// Returns whether the current CPU is handling an IRQ.
+pub fn in_irq() -> bool {
+    // Safety: out of the IRQ handlers, it is 0 on every CPU, so a migration
+    // does not matter.
+    unsafe { IRQ_DEPTH.read_current_raw() > 0 }
+}
+
 /// Platform-independent IRQ dispatching.
 #[allow(dead_code)]
 pub(crate) fn dispatch_irq_common(irq_num: usize) {
     trace!("IRQ {}", irq_num);
+    if let Some(count) = IRQ_COUNTS[crate::cpu::this_cpu_id()].get(irq_num) {
+        count.fetch_add(1, Ordering::Relaxed);
+    }
     if !IRQ_HANDLER_TABLE.handle(irq_num) {
         warn!("Unhandled IRQ {}", irq_num);
     }
@@ -38,7 +72,9 @@ pub(crate) fn register_handler_common(irq_num: usize, handler: IrqHandler) -> bo
 #[register_trap_handler(IRQ)]
 fn handler_irq(irq_num: usize) -> bool {
     let guard = kernel_guard::NoPreempt::new();
+    unsafe { IRQ_DEPTH.write_current_raw(IRQ_DEPTH.read_current_raw() + 1) };
     dispatch_irq(irq_num);
+    unsafe { IRQ_DEPTH.write_current_raw(IRQ_DEPTH.read_current_raw() - 1) };
     drop(guard); // rescheduling may occur when preemption is re-enabled.
     true
 }
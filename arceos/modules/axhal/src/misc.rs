@@ -19,3 +19,19 @@ pub fn random() -> u128 {
     }
     ret
 }
+
+/// A slice of functions called by [`shutdown`] before the machine is shut
+/// down, e.g., to write the cached file contents back to the disks.
+#[linkme::distributed_slice]
+pub static SHUTDOWN: [fn()];
+
+pub use linkme::distributed_slice as register_shutdown_hook;
+
+/// Calls the functions in [`SHUTDOWN`], and then shuts down the machine by
+/// [`terminate`], which is called directly on panics.
+pub fn shutdown() -> ! {
+    for hook in SHUTDOWN.iter() {
+        hook();
+    }
+    terminate()
+}
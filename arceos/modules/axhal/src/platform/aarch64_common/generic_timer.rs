@@ -40,9 +40,10 @@ pub fn set_oneshot_timer(deadline_ns: u64) {
     let cnptct = CNTPCT_EL0.get();
     let cnptct_deadline = nanos_to_ticks(deadline_ns);
     if cnptct < cnptct_deadline {
+        // A later deadline is cut to the longest interval, after which the
+        // timer is set again.
         let interval = cnptct_deadline - cnptct;
-        debug_assert!(interval <= u32::MAX as u64);
-        CNTP_TVAL_EL0.set(interval);
+        CNTP_TVAL_EL0.set(interval.min(i32::MAX as u64));
     } else {
         CNTP_TVAL_EL0.set(0);
     }
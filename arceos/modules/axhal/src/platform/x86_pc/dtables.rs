@@ -1,7 +1,9 @@
 //! Description tables (per-CPU GDT, per-CPU ISS, IDT)
 
+use crate::arch::{double_fault_stack_top, DOUBLE_FAULT_IST_INDEX};
 use crate::arch::{GdtStruct, IdtStruct, TaskStateSegment};
 use lazyinit::LazyInit;
+use x86_64::VirtAddr;
 
 static IDT: LazyInit<IdtStruct> = LazyInit::new();
 
@@ -16,7 +18,10 @@ fn init_percpu() {
         IDT.load();
         let tss = TSS.current_ref_mut_raw();
         let gdt = GDT.current_ref_mut_raw();
-        tss.init_once(TaskStateSegment::new());
+        let mut new_tss = TaskStateSegment::new();
+        new_tss.interrupt_stack_table[DOUBLE_FAULT_IST_INDEX as usize] =
+            VirtAddr::new(double_fault_stack_top() as u64);
+        tss.init_once(new_tss);
         gdt.init_once(GdtStruct::new(tss));
         gdt.load();
         gdt.load_tss();
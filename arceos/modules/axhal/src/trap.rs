@@ -1,7 +1,7 @@
 //! Trap handling.
 
 use linkme::distributed_slice as def_trap_handler;
-use memory_addr::VirtAddr;
+use memory_addr::{VirtAddr, PAGE_SIZE_4K};
 use page_table_entry::MappingFlags;
 
 #[cfg(feature = "uspace")]
@@ -9,6 +9,10 @@ use crate::arch::TrapFrame;
 
 pub use linkme::distributed_slice as register_trap_handler;
 
+/// Size of the unmapped guard area below a kernel stack, see
+/// [`set_kernel_stack_limit`].
+pub const KERNEL_STACK_GUARD_SIZE: usize = PAGE_SIZE_4K;
+
 /// A slice of IRQ handler functions.
 #[def_trap_handler]
 pub static IRQ: [fn(usize) -> bool];
@@ -17,11 +21,32 @@ pub static IRQ: [fn(usize) -> bool];
 #[def_trap_handler]
 pub static PAGE_FAULT: [fn(VirtAddr, MappingFlags, bool) -> bool];
 
+/// A slice of functions called when the current task overflows its kernel
+/// stack, with the faulting address in the guard area or the stack pointer,
+/// e.g., to report the task. The kernel panics after calling them.
+#[def_trap_handler]
+pub static STACK_OVERFLOW: [fn(VirtAddr)];
+
 /// A slice of syscall handler functions.
+///
+/// The instruction pointer in the trap frame has been advanced past the
+/// syscall instruction when they are called.
 #[cfg(feature = "uspace")]
 #[def_trap_handler]
 pub static SYSCALL: [fn(&TrapFrame, usize) -> isize];
 
+/// A slice of functions called on traps from user space before they are
+/// handled, e.g., to account the CPU time.
+#[cfg(feature = "uspace")]
+#[def_trap_handler]
+pub static USER_ENTRY: [fn(&TrapFrame)];
+
+/// A slice of functions called before returning to user space, e.g., to
+/// deliver signals.
+#[cfg(feature = "uspace")]
+#[def_trap_handler]
+pub static USER_RETURN: [fn(&mut TrapFrame)];
+
 #[allow(unused_macros)]
 macro_rules! handle_trap {
     ($trap:ident, $($args:tt)*) => {{
@@ -38,8 +63,58 @@ macro_rules! handle_trap {
     }}
 }
 
+/// Sets the lowest address of the kernel stack of the current task, below
+/// which there is an unmapped guard area of [`KERNEL_STACK_GUARD_SIZE`], or 0
+/// if the stack has no guard.
+///
+/// Faults in the guard area are reported as stack overflows, see
+/// [`STACK_OVERFLOW`]. On riscv, traps are also checked on entry, and those
+/// without the room for a trap frame on the stack are handled on a per-CPU
+/// overflow stack. On x86_64, the double faults caused by pushing to the guard
+/// area are handled on a per-CPU interrupt stack. On aarch64, overflows are
+/// recognized only when the exception can still be taken on the stack.
+pub fn set_kernel_stack_limit(limit: VirtAddr) {
+    crate::arch::KSTACK_LIMIT.write_current(limit.as_usize());
+}
+
+/// Returns whether the faulting address is in the guard area below the kernel
+/// stack of the current task.
+pub(crate) fn is_kernel_stack_overflow(vaddr: VirtAddr) -> bool {
+    let limit = crate::arch::KSTACK_LIMIT.read_current();
+    limit != 0 && (limit - KERNEL_STACK_GUARD_SIZE..limit).contains(&vaddr.as_usize())
+}
+
+/// Call all registered handlers on a kernel stack overflow, and panic.
+pub(crate) fn handle_kernel_stack_overflow(vaddr: VirtAddr, pc: usize) -> ! {
+    // Traps taken from now on, e.g., in the handlers, are handled as usual.
+    set_kernel_stack_limit(VirtAddr::from(0));
+    for handler in STACK_OVERFLOW.iter() {
+        handler(vaddr);
+    }
+    panic!(
+        "Kernel stack overflow @ {:#x}, fault_vaddr={:#x}",
+        pc, vaddr
+    );
+}
+
 /// Call the external syscall handler.
 #[cfg(feature = "uspace")]
 pub(crate) fn handle_syscall(tf: &TrapFrame, syscall_num: usize) -> isize {
     SYSCALL[0](tf, syscall_num)
 }
+
+/// Call all registered handlers on a trap from user space.
+#[cfg(feature = "uspace")]
+pub(crate) fn handle_user_entry(tf: &TrapFrame) {
+    for handler in USER_ENTRY.iter() {
+        handler(tf);
+    }
+}
+
+/// Call all registered handlers before returning to user space.
+#[cfg(feature = "uspace")]
+pub(crate) fn handle_user_return(tf: &mut TrapFrame) {
+    for handler in USER_RETURN.iter() {
+        handler(tf);
+    }
+}
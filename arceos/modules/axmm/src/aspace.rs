@@ -1,24 +1,46 @@
 use core::fmt;
+use core::mem::size_of;
+use core::sync::atomic::AtomicU32;
 
 use axerrno::{ax_err, AxError, AxResult};
 use axhal::{
     mem::phys_to_virt,
-    paging::{MappingFlags, PageTable},
+    paging::{MappingFlags, PageSize, PageTable},
 };
 use memory_addr::{
-    is_aligned_4k, pa, MemoryAddr, PageIter4K, PhysAddr, VirtAddr, VirtAddrRange, PAGE_SIZE_4K,
+    is_aligned_4k, pa, va, MemoryAddr, PageIter4K, PhysAddr, VirtAddr, VirtAddrRange,
+    PAGE_SIZE_4K,
 };
-use memory_set::{MemoryArea, MemorySet};
-use crate::backend::Backend;
+use lazyinit::LazyInit;
+use memory_set::{MappingBackend, MemoryArea, MemorySet};
+use crate::backend::{frame_is_shared, present_frame, release_frame, Backend};
+#[cfg(feature = "swap")]
+use crate::swap::{is_swappable, SwapState};
 use crate::paging_err_to_ax_err;
 use crate::mapping_err_to_ax_err;
+use crate::vma::{Heap, VmaInfo, VmaKind, VmaKinds};
+use alloc::string::String;
+#[cfg(feature = "fs")]
+use alloc::sync::Arc;
 use alloc::vec::Vec;
 
+/// Permissions of the program break heap.
+const HEAP_FLAGS: MappingFlags = MappingFlags::READ
+    .union(MappingFlags::WRITE)
+    .union(MappingFlags::USER);
+
 /// The virtual memory address space.
 pub struct AddrSpace {
     va_range: VirtAddrRange,
     areas: MemorySet<Backend>,
     pt: PageTable,
+    kinds: VmaKinds,
+    heap: Option<Heap>,
+    /// Ranges whose page tables are copied from other address spaces, see
+    /// [`AddrSpace::copy_mappings_from`].
+    copied: Vec<VirtAddrRange>,
+    #[cfg(feature = "swap")]
+    swap: SwapState,
 }
 
 impl AddrSpace {
@@ -59,6 +81,11 @@ impl AddrSpace {
             va_range: VirtAddrRange::from_start_size(base, size),
             areas: MemorySet::new(),
             pt: PageTable::try_new().map_err(|_| AxError::NoMemory)?,
+            kinds: VmaKinds::new(),
+            heap: None,
+            copied: Vec::new(),
+            #[cfg(feature = "swap")]
+            swap: SwapState::new(),
         })
     }
 
@@ -74,6 +101,7 @@ impl AddrSpace {
             return ax_err!(InvalidInput, "address space overlap");
         }
         self.pt.copy_from(&other.pt, other.base(), other.size());
+        self.copied.push(other.va_range);
         Ok(())
     }
 
@@ -94,7 +122,8 @@ impl AddrSpace {
     /// Add a new linear mapping.
     ///
     /// The mapping is linear, i.e., `start_vaddr` is mapped to `start_paddr`,
-    /// and `start_vaddr + size` is mapped to `start_paddr + size`.
+    /// and `start_vaddr + size` is mapped to `start_paddr + size`. 2M and 1G
+    /// huge pages are used where the alignment and size allow.
     ///
     /// The `flags` parameter indicates the mapping permissions and attributes.
     ///
@@ -121,7 +150,7 @@ impl AddrSpace {
                 |va| pa!(va.as_usize() - offset),
                 size,
                 flags,
-                false, // allow_huge
+                true,  // allow_huge
                 false, // flush_tlb_by_page
             )
             .map_err(paging_err_to_ax_err)?
@@ -131,7 +160,9 @@ impl AddrSpace {
 
     /// Add a new allocation mapping.
     ///
-    /// See [`Backend`] for more details about the mapping backends.
+    /// See [`Backend`] for more details about the mapping backends. Populated
+    /// mappings use 2M and 1G huge pages where the alignment and size allow,
+    /// while lazy mappings are always faulted in as 4K pages.
     ///
     /// The `flags` parameter indicates the mapping permissions and attributes.
     ///
@@ -158,6 +189,69 @@ impl AddrSpace {
         Ok(())
     }
 
+    /// Add a new file mapping.
+    ///
+    /// The content of `file` at `offset` is mapped at `start`, and is loaded on
+    /// demand. If `shared` is `true`, the modifications are written back to the
+    /// file by [`AddrSpace::msync`] or when the pages are unmapped, otherwise
+    /// they are private to this mapping.
+    ///
+    /// The `flags` parameter indicates the mapping permissions and attributes.
+    ///
+    /// Returns an error if the address range is out of the address space, or
+    /// the address range or `offset` is not aligned.
+    #[cfg(feature = "fs")]
+    pub fn map_file(
+        &mut self,
+        start: VirtAddr,
+        size: usize,
+        flags: MappingFlags,
+        file: Arc<axfs::fops::File>,
+        offset: usize,
+        shared: bool,
+    ) -> AxResult {
+        if !self.contains_range(start, size) {
+            return ax_err!(InvalidInput, "address out of range");
+        }
+        if !start.is_aligned_4k() || !is_aligned_4k(size) || !is_aligned_4k(offset) {
+            return ax_err!(InvalidInput, "address not aligned");
+        }
+
+        let backend = Backend::new_file(file, start, offset, shared);
+        let area = MemoryArea::new(start, size, flags, backend);
+        self.areas
+            .map(area, &mut self.pt, false)
+            .map_err(mapping_err_to_ax_err)?;
+        Ok(())
+    }
+
+    /// Writes the modified pages of the shared file mappings within the
+    /// specified virtual address range back to the files.
+    ///
+    /// Returns an error if the address range is out of the address space or not
+    /// aligned.
+    pub fn msync(&mut self, start: VirtAddr, size: usize) -> AxResult {
+        if !self.contains_range(start, size) {
+            return ax_err!(InvalidInput, "address out of range");
+        }
+        if !start.is_aligned_4k() || !is_aligned_4k(size) {
+            return ax_err!(InvalidInput, "address not aligned");
+        }
+
+        let end = start + size;
+        for area in self.areas.iter() {
+            let (sync_start, sync_end) = (area.start().max(start), area.end().min(end));
+            if sync_start < sync_end
+                && !area
+                    .backend()
+                    .sync(sync_start, sync_end - sync_start, &mut self.pt)
+            {
+                return ax_err!(Io, "failed to write back the mapping");
+            }
+        }
+        Ok(())
+    }
+
     /// Removes mappings within the specified virtual address range.
     ///
     /// Returns an error if the address range is out of the address space or not
@@ -170,10 +264,36 @@ impl AddrSpace {
             return ax_err!(InvalidInput, "address not aligned");
         }
 
-        self.pt
-            .unmap_region(start, size, true)
-            .map_err(paging_err_to_ax_err)?
-            .ignore();
+        self.split_huge_pages(start, size)?;
+        self.kinds.remove(VirtAddrRange::from_start_size(start, size));
+        if self.areas.overlaps(VirtAddrRange::from_start_size(start, size)) {
+            #[cfg(feature = "swap")]
+            self.swap.release(start, size);
+            // Let the backends release the frames and write back the files.
+            self.areas
+                .unmap(start, size, &mut self.pt)
+                .map_err(mapping_err_to_ax_err)?;
+        } else {
+            self.pt
+                .unmap_region(start, size, true)
+                .map_err(paging_err_to_ax_err)?
+                .ignore();
+        }
+        Ok(())
+    }
+
+    /// Splits the huge pages crossing the boundaries of the given range into
+    /// smaller pages, so that the range can be unmapped or protected without
+    /// affecting the memory outside.
+    fn split_huge_pages(&mut self, start: VirtAddr, size: usize) -> AxResult {
+        for vaddr in [start, start + size] {
+            while let Ok((_, _, page_size)) = self.pt.query(vaddr) {
+                if vaddr.is_aligned(page_size) {
+                    break;
+                }
+                split_huge_page(&mut self.pt, vaddr)?;
+            }
+        }
         Ok(())
     }
 
@@ -211,6 +331,29 @@ impl AddrSpace {
         Ok(())
     }
 
+    /// Makes sure that the pages in the given range are present with the
+    /// `access_flags`, as if the user program accessed them. The pages are
+    /// allocated, swapped in or copied on write if necessary.
+    ///
+    /// Returns an error if any page is not mapped with the `access_flags`.
+    fn fault_in(&mut self, start: VirtAddr, size: usize, access_flags: MappingFlags) -> AxResult {
+        if !self.contains_range(start, size) {
+            return ax_err!(InvalidInput, "address out of range");
+        }
+        let end = (start + size).align_up_4k();
+        for vaddr in PageIter4K::new(start.align_down_4k(), end).unwrap() {
+            if let Ok((_, flags, _)) = self.pt.query(vaddr) {
+                if flags.contains(access_flags) {
+                    continue;
+                }
+            }
+            if !self.handle_page_fault(vaddr, access_flags) {
+                return ax_err!(BadAddress);
+            }
+        }
+        Ok(())
+    }
+
     /// To read data from the address space.
     ///
     /// # Arguments
@@ -225,16 +368,67 @@ impl AddrSpace {
 
     /// To write data to the address space.
     ///
+    /// The data is written to the mapped frames directly, regardless of the
+    /// permissions. Frames shared copy-on-write by [`AddrSpace::clone_cow`]
+    /// are not written, use [`AddrSpace::write_user`] for them instead.
+    ///
     /// # Arguments
     ///
     /// * `start_vaddr` - The start virtual address to write.
     /// * `buf` - The buffer to write to the address space.
     pub fn write(&self, start: VirtAddr, buf: &[u8]) -> AxResult {
+        if !buf.is_empty() {
+            let end = (start + buf.len()).align_up_4k();
+            for vaddr in PageIter4K::new(start.align_down_4k(), end).unwrap() {
+                if present_frame(&self.pt, vaddr).is_some_and(frame_is_shared) {
+                    return ax_err!(BadState, "shared copy-on-write page");
+                }
+            }
+        }
         self.process_area_data(start, buf.len(), |dst, offset, write_size| unsafe {
             core::ptr::copy_nonoverlapping(buf.as_ptr().add(offset), dst.as_mut_ptr(), write_size);
         })
     }
 
+    /// Reads data from the address space as the user program does, e.g., the
+    /// futex words and the syscall arguments passed by pointers.
+    ///
+    /// Unlike [`AddrSpace::read`], the pages are faulted in if necessary, and
+    /// it fails with [`AxError::BadAddress`] if any page in the range is not
+    /// mapped readable.
+    pub fn read_user(&mut self, start: VirtAddr, buf: &mut [u8]) -> AxResult {
+        self.fault_in(start, buf.len(), MappingFlags::READ)?;
+        self.read(start, buf)
+    }
+
+    /// Writes data to the address space as the user program does, e.g., the
+    /// signal frames and the TIDs of `clone`.
+    ///
+    /// Unlike [`AddrSpace::write`], the pages are faulted in and copied on
+    /// write if necessary, and it fails with [`AxError::BadAddress`] if any
+    /// page in the range is not mapped writable.
+    pub fn write_user(&mut self, start: VirtAddr, buf: &[u8]) -> AxResult {
+        self.fault_in(start, buf.len(), MappingFlags::WRITE)?;
+        self.write(start, buf)
+    }
+
+    /// Returns the user word at `vaddr` for atomic operations, e.g., the
+    /// futex words updated on behalf of an exiting thread.
+    ///
+    /// The page is faulted in and copied on write as by
+    /// [`AddrSpace::write_user`], and it stays mapped while the address space
+    /// is borrowed.
+    pub fn atomic_user_u32(&mut self, vaddr: VirtAddr) -> AxResult<&AtomicU32> {
+        if !vaddr.is_aligned(size_of::<u32>()) {
+            return ax_err!(InvalidInput, "unaligned address");
+        }
+        self.fault_in(vaddr, size_of::<u32>(), MappingFlags::WRITE)?;
+        let (paddr, _, _) = self.pt.query(vaddr).map_err(|_| AxError::BadAddress)?;
+        // SAFETY: the word is aligned, and in a present frame of this address
+        // space.
+        Ok(unsafe { AtomicU32::from_ptr(phys_to_virt(paddr).as_mut_ptr() as *mut u32) })
+    }
+
     /// Updates mapping within the specified virtual address range.
     ///
     /// Returns an error if the address range is out of the address space or not
@@ -247,11 +441,335 @@ impl AddrSpace {
             return ax_err!(InvalidInput, "address not aligned");
         }
 
-        self.pt
-            .protect_region(start, size, flags, true)
+        self.split_huge_pages(start, size)?;
+        if self.areas.overlaps(VirtAddrRange::from_start_size(start, size)) {
+            // Let the backends decide, e.g., shared copy-on-write frames must
+            // stay read-only.
+            self.areas
+                .protect(start, size, |_| Some(flags), &mut self.pt)
+                .map_err(mapping_err_to_ax_err)?;
+        } else {
+            self.pt
+                .protect_region(start, size, flags, true)
+                .map_err(paging_err_to_ax_err)?
+                .ignore();
+        }
+        Ok(())
+    }
+
+    /// Sets what the address range is used for, which is shown in the
+    /// memory map dump. See [`AddrSpace::vmas`].
+    ///
+    /// Ranges are [`VmaKind::Anon`] by default, and are forgotten when
+    /// unmapped.
+    pub fn set_kind(&mut self, start: VirtAddr, size: usize, kind: VmaKind) {
+        self.kinds
+            .set(VirtAddrRange::from_start_size(start, size), kind);
+    }
+
+    /// Maps a stack of `size` below `top`, with inaccessible guard pages of
+    /// `guard_size` below it, so that a stack overflow faults instead of
+    /// corrupting the memory below.
+    ///
+    /// The stack memory is allocated on demand unless `populate` is `true`.
+    ///
+    /// Returns an error if the address range is out of the address space or not
+    /// aligned.
+    pub fn map_stack(
+        &mut self,
+        top: VirtAddr,
+        size: usize,
+        guard_size: usize,
+        populate: bool,
+    ) -> AxResult {
+        let start = top - size;
+        if guard_size > 0 {
+            let guard_start = start - guard_size;
+            self.map_alloc(guard_start, guard_size, MappingFlags::empty(), false)?;
+            self.set_kind(guard_start, guard_size, VmaKind::Guard);
+        }
+        self.map_alloc(start, size, HEAP_FLAGS, populate)?;
+        self.set_kind(start, size, VmaKind::Stack);
+        Ok(())
+    }
+
+    /// Sets the initial program break, usually the end of the data segment of
+    /// the program. The heap is empty then.
+    pub fn init_heap(&mut self, start: VirtAddr) {
+        self.heap = Some(Heap { start, brk: start });
+    }
+
+    /// Changes the program break to `new_brk`, as `brk` does.
+    ///
+    /// The heap is mapped with lazily allocated memory, and is grown or shrunk
+    /// in pages. It cannot shrink below the initial program break, or grow
+    /// into other mappings.
+    ///
+    /// Returns the new program break, or the current one if it cannot be
+    /// changed, e.g., when `new_brk` is 0. Returns 0 if the heap is not set up
+    /// by [`AddrSpace::init_heap`].
+    pub fn brk(&mut self, new_brk: VirtAddr) -> VirtAddr {
+        let Some(heap) = self.heap else {
+            return VirtAddr::from(0);
+        };
+        if new_brk < heap.start {
+            return heap.brk;
+        }
+        let (old_end, new_end) = (heap.brk.align_up_4k(), new_brk.align_up_4k());
+        let heap_start = heap.start.align_up_4k();
+        if new_end > old_end {
+            // Fails if it would grow out of the address space or into another
+            // mapping.
+            if self.grow_heap(heap_start, old_end, new_end).is_err() {
+                return heap.brk;
+            }
+            self.set_kind(heap_start, new_end - heap_start, VmaKind::Heap);
+        } else if new_end < old_end && self.unmap(new_end, old_end - new_end).is_err() {
+            return heap.brk;
+        }
+        self.heap = Some(Heap {
+            start: heap.start,
+            brk: new_brk,
+        });
+        new_brk
+    }
+
+    /// Grows the heap from `old_end` to `new_end`. The last area of the heap is
+    /// extended in place, so that the heap does not take a new area for each
+    /// growth.
+    fn grow_heap(
+        &mut self,
+        heap_start: VirtAddr,
+        old_end: VirtAddr,
+        new_end: VirtAddr,
+    ) -> AxResult {
+        let heap_area = (old_end > heap_start)
+            .then(|| self.areas.find(old_end - 1))
+            .flatten()
+            .filter(|area| {
+                area.end() == old_end
+                    && area.flags() == HEAP_FLAGS
+                    && matches!(area.backend(), Backend::Alloc { populate: false })
+            })
+            .map(|area| (area.start(), area.size()));
+        let Some((area_start, area_size)) = heap_area else {
+            // The heap is empty, or its last page is remapped or protected.
+            return self.map_alloc(old_end, new_end - old_end, HEAP_FLAGS, false);
+        };
+        if !self.contains_range(old_end, new_end - old_end) {
+            return ax_err!(NoMemory, "heap out of range");
+        }
+        self.areas
+            .extend(area_start, area_size + (new_end - old_end), &mut self.pt)
+            .map_err(mapping_err_to_ax_err)
+    }
+
+    /// Resizes the mapping at `old_start`, and moves it if necessary, as
+    /// `mremap` does. Returns the new start address of the mapping.
+    ///
+    /// The range `[old_start, old_start + old_size)` must be within one area.
+    /// The mapping is shrunk or grown in place if possible. Otherwise, if
+    /// `may_move` is `true`, it is moved to a free range, or to `new_start` if
+    /// it is given, whose old mappings are removed first. The physical frames
+    /// are moved along rather than copied, and the grown part is allocated by
+    /// the backend of the area as usual.
+    ///
+    /// Returns an error if the ranges are out of the address space or not
+    /// aligned, the old range is not in one area or is a linear mapping, or it
+    /// cannot grow in place when it is not allowed to move.
+    pub fn remap(
+        &mut self,
+        old_start: VirtAddr,
+        old_size: usize,
+        new_size: usize,
+        may_move: bool,
+        new_start: Option<VirtAddr>,
+    ) -> AxResult<VirtAddr> {
+        if !self.contains_range(old_start, old_size) {
+            return ax_err!(InvalidInput, "address out of range");
+        }
+        if !old_start.is_aligned_4k() || !is_aligned_4k(old_size) || !is_aligned_4k(new_size) {
+            return ax_err!(InvalidInput, "address not aligned");
+        }
+        if old_size == 0 || new_size == 0 {
+            return ax_err!(InvalidInput, "empty mapping");
+        }
+        let old_end = old_start + old_size;
+        let area = match self.areas.find(old_start) {
+            Some(area) if old_end <= area.end() => area,
+            _ => return ax_err!(BadAddress, "not in one area"),
+        };
+        let (area_start, area_size) = (area.start(), area.size());
+        let (flags, backend) = (area.flags(), area.backend().clone());
+        if matches!(backend, Backend::Linear { .. }) {
+            return ax_err!(InvalidInput, "cannot remap linear mappings");
+        }
+        let kind = self.kinds.get(old_start).clone();
+
+        if new_start.is_none() {
+            if new_size <= old_size {
+                if new_size < old_size {
+                    self.unmap(old_start + new_size, old_size - new_size)?;
+                }
+                return Ok(old_start);
+            }
+            let grow_size = new_size - old_size;
+            let grow_range = VirtAddrRange::from_start_size(old_end, grow_size);
+            if old_end == area_start + area_size
+                && self.contains_range(old_end, grow_size)
+                && !self.areas.overlaps(grow_range)
+            {
+                self.areas
+                    .extend(area_start, area_size + grow_size, &mut self.pt)
+                    .map_err(mapping_err_to_ax_err)?;
+                self.kinds.set(grow_range, kind);
+                return Ok(old_start);
+            }
+        }
+        if !may_move {
+            return ax_err!(NoMemory, "cannot grow in place");
+        }
+
+        let new_start = match new_start {
+            Some(new_start) => {
+                if !self.contains_range(new_start, new_size) {
+                    return ax_err!(InvalidInput, "address out of range");
+                }
+                if !new_start.is_aligned_4k() {
+                    return ax_err!(InvalidInput, "address not aligned");
+                }
+                let new_range = VirtAddrRange::from_start_size(new_start, new_size);
+                if new_range.overlaps(VirtAddrRange::new(old_start, old_end)) {
+                    return ax_err!(InvalidInput, "ranges overlap");
+                }
+                if self.areas.overlaps(new_range) {
+                    self.unmap(new_start, new_size)?;
+                }
+                new_start
+            }
+            None => self
+                .find_free_area(old_start, new_size, self.va_range)
+                .ok_or(AxError::NoMemory)?,
+        };
+        let move_size = old_size.min(new_size);
+
+        // Swap slots are indexed by the virtual address, read the pages back
+        // so that they can be moved.
+        #[cfg(feature = "swap")]
+        if !self.swap.swap_in_all(&self.areas, &mut self.pt) {
+            return ax_err!(NoMemory, "failed to swap in pages");
+        }
+        for vaddr in PageIter4K::new(old_start, old_start + move_size).unwrap() {
+            while split_huge_page(&mut self.pt, vaddr)? {}
+        }
+
+        // Map the new area first, then move the present frames into it, so
+        // that every frame is owned by one of the areas if anything fails.
+        let backend = match backend {
+            #[cfg(feature = "fs")]
+            Backend::File {
+                file,
+                start,
+                offset,
+                shared,
+            } => Backend::new_file(file, new_start, old_start - start + offset, shared),
+            backend => backend,
+        };
+        self.areas
+            .map(
+                MemoryArea::new(new_start, new_size, flags, backend),
+                &mut self.pt,
+                false,
+            )
+            .map_err(mapping_err_to_ax_err)?;
+        for offset in (0..move_size).step_by(PAGE_SIZE_4K) {
+            let (old_vaddr, new_vaddr) = (old_start + offset, new_start + offset);
+            let Some(frame) = present_frame(&self.pt, old_vaddr) else {
+                continue;
+            };
+            // Shared copy-on-write pages stay read-only after moving.
+            let (_, pte_flags, _) = self.pt.query(old_vaddr).map_err(paging_err_to_ax_err)?;
+            // Populated areas have allocated the frame already, or left the
+            // page unmapped if out of memory.
+            while split_huge_page(&mut self.pt, new_vaddr)? {}
+            match self.pt.query(new_vaddr) {
+                Ok((new_frame, new_flags, _)) => {
+                    if !new_flags.is_empty() {
+                        release_frame(new_frame);
+                    }
+                    self.pt
+                        .remap(new_vaddr, frame, pte_flags)
+                        .map(|(_, tlb)| tlb)
+                }
+                Err(_) => self.pt.map(new_vaddr, frame, PageSize::Size4K, pte_flags),
+            }
             .map_err(paging_err_to_ax_err)?
             .ignore();
-        Ok(())
+            self.pt
+                .remap(old_vaddr, pa!(0), MappingFlags::empty())
+                .map_err(paging_err_to_ax_err)?
+                .1
+                .flush();
+        }
+        self.unmap(old_start, old_size)?;
+        self.set_kind(new_start, new_size, kind);
+        Ok(new_start)
+    }
+
+    /// Returns the information and statistics of all areas, in the order of
+    /// their addresses.
+    pub fn vmas(&self) -> Vec<VmaInfo> {
+        self.areas
+            .iter()
+            .map(|area| {
+                let (shared, offset) = match area.backend() {
+                    #[cfg(feature = "fs")]
+                    Backend::File {
+                        start,
+                        offset,
+                        shared,
+                        ..
+                    } => (*shared, area.start() - *start + *offset),
+                    _ => (false, 0),
+                };
+                VmaInfo {
+                    range: VirtAddrRange::new(area.start(), area.end()),
+                    flags: area.flags(),
+                    shared,
+                    offset,
+                    kind: self.kinds.get(area.start()).clone(),
+                    rss: self.resident_size(area.start(), area.end()),
+                }
+            })
+            .collect()
+    }
+
+    /// Dumps all areas in the format of `/proc/self/maps`, one line for each.
+    pub fn maps(&self) -> String {
+        use core::fmt::Write;
+        let mut out = String::new();
+        for vma in self.vmas() {
+            let _ = writeln!(out, "{}", vma);
+        }
+        out
+    }
+
+    /// Returns the size of the memory mapped to physical frames in the range.
+    fn resident_size(&self, start: VirtAddr, end: VirtAddr) -> usize {
+        let mut rss = 0;
+        let mut vaddr = start;
+        while vaddr < end {
+            let (present, page_size) = match self.pt.query(vaddr) {
+                Ok((_, flags, page_size)) => (!flags.is_empty(), page_size),
+                Err(_) => (false, PageSize::Size4K),
+            };
+            let next = (vaddr.align_down(page_size) + usize::from(page_size)).min(end);
+            if present {
+                rss += next - vaddr;
+            }
+            vaddr = next;
+        }
+        rss
     }
 
     /// Handles a page fault at the given address.
@@ -267,14 +785,160 @@ impl AddrSpace {
         if let Some(area) = self.areas.find(vaddr) {
             let orig_flags = area.flags();
             if orig_flags.contains(access_flags) {
-                return area
+                #[cfg(feature = "swap")]
+                {
+                    self.swap.balance(&self.areas, &mut self.pt);
+                    if let Some(ok) = self.swap.swap_in(vaddr, orig_flags, &mut self.pt) {
+                        return ok;
+                    }
+                }
+                let ok = area
                     .backend()
-                    .handle_page_fault(vaddr, orig_flags, &mut self.pt);
+                    .handle_page_fault(vaddr, access_flags, orig_flags, &mut self.pt);
+                #[cfg(feature = "swap")]
+                if ok && is_swappable(area.backend()) {
+                    self.swap.touch(vaddr);
+                }
+                return ok;
             }
         }
         false
     }
 
+    /// Swaps out up to `nr_pages` anonymous pages of the address space, to
+    /// free some physical memory. Returns the number of pages reclaimed.
+    ///
+    /// It is done automatically on page faults when the free physical memory
+    /// is low. See [`crate::swap`] for more details.
+    #[cfg(feature = "swap")]
+    pub fn reclaim(&mut self, nr_pages: usize) -> usize {
+        self.swap.reclaim(&self.areas, &mut self.pt, nr_pages)
+    }
+
+    /// Clones the address space with copy-on-write semantics, as `fork` does.
+    ///
+    /// The allocation and copy-on-write areas become copy-on-write areas in the
+    /// new address space. Their present physical frames are shared read-only
+    /// instead of being copied, and a later write to them from either address
+    /// space is resolved in [`AddrSpace::handle_page_fault`] by copying the
+    /// page. Private file mappings are handled in the same way, while shared
+    /// file mappings and linear areas are shared as they are.
+    ///
+    /// If this is a user address space, the kernel mappings are copied to the
+    /// new one as well.
+    ///
+    /// Only the areas tracked by the address space are cloned, the mappings
+    /// created by [`AddrSpace::map_linear`] are not.
+    ///
+    /// The areas of this address space are left as they are. On failure, the
+    /// frames shared so far are released along with the new address space,
+    /// and the pages made read-only here are made writable again on the next
+    /// write.
+    pub fn clone_cow(&mut self) -> AxResult<Self> {
+        // Swap slots are not shared, read all pages back first.
+        #[cfg(feature = "swap")]
+        if !self.swap.swap_in_all(&self.areas, &mut self.pt) {
+            return ax_err!(NoMemory, "failed to swap in pages");
+        }
+
+        let mut aspace = Self::new_empty(self.base(), self.size())?;
+        let kernel_range = VirtAddrRange::from_start_size(
+            va!(axconfig::KERNEL_ASPACE_BASE),
+            axconfig::KERNEL_ASPACE_SIZE,
+        );
+        if !self.va_range.overlaps(kernel_range) {
+            aspace.copy_mappings_from(&crate::kernel_aspace().lock())?;
+        }
+        aspace.kinds = self.kinds.clone();
+        aspace.heap = self.heap;
+
+        for area in self.areas.iter() {
+            let (start, size, flags) = (area.start(), area.size(), area.flags());
+            let backend = match area.backend() {
+                Backend::Alloc { .. } => Backend::new_cow(),
+                backend => backend.clone(),
+            };
+            // Once mapped, the area releases the frames shared to it when the
+            // new address space is dropped.
+            aspace
+                .areas
+                .map(
+                    MemoryArea::new(start, size, flags, backend),
+                    &mut aspace.pt,
+                    false,
+                )
+                .map_err(mapping_err_to_ax_err)?;
+            if matches!(area.backend(), Backend::Linear { .. }) {
+                continue;
+            }
+            // Share the present frames. The private ones are made read-only
+            // in both address spaces, so that they are copied on write, while
+            // the others keep their flags in the page table.
+            let cow = area.backend().is_cow();
+            for vaddr in PageIter4K::new(start, area.end()).unwrap() {
+                // Frames are shared and copied in 4K pages.
+                while split_huge_page(&mut self.pt, vaddr)? {}
+                let (frame, pte_flags) = match self.pt.query(vaddr) {
+                    Ok((frame, pte_flags, _)) if !pte_flags.is_empty() => (frame, pte_flags),
+                    _ => continue,
+                };
+                let share_flags = if cow {
+                    flags - MappingFlags::WRITE
+                } else {
+                    pte_flags
+                };
+                if cow {
+                    let (_, tlb) = self
+                        .pt
+                        .protect(vaddr, share_flags)
+                        .map_err(paging_err_to_ax_err)?;
+                    tlb.flush();
+                }
+                aspace
+                    .pt
+                    .remap(vaddr, frame, share_flags)
+                    .map_err(paging_err_to_ax_err)?
+                    .1
+                    .ignore();
+                if !area.backend().share_page(vaddr, frame) {
+                    // Not referenced by the new address space, so that it is
+                    // not released with it.
+                    aspace
+                        .pt
+                        .remap(vaddr, pa!(0), MappingFlags::empty())
+                        .map_err(paging_err_to_ax_err)?
+                        .1
+                        .ignore();
+                    return ax_err!(NoMemory, "failed to share the page");
+                }
+            }
+        }
+        Ok(aspace)
+    }
+
+    /// Removes all mappings in the address space, and releases the physical
+    /// frames owned by them.
+    ///
+    /// It never fails. The areas that cannot be unmapped cleanly, e.g., the
+    /// file mappings failed to be written back, are removed anyway with a
+    /// warning.
+    pub fn clear(&mut self) {
+        #[cfg(feature = "swap")]
+        self.swap.release(self.base(), self.size());
+        let areas = core::mem::replace(&mut self.areas, MemorySet::new());
+        for area in areas.iter() {
+            if !area.backend().unmap(area.start(), area.size(), &mut self.pt) {
+                warn!(
+                    "failed to unmap [{:#x}, {:#x}) cleanly",
+                    area.start(),
+                    area.end()
+                );
+            }
+        }
+        self.kinds.clear();
+        self.heap = None;
+    }
+
     pub fn translated_byte_buffer(
         &self,
         vaddr: VirtAddr,
@@ -324,6 +988,50 @@ impl AddrSpace {
     }
 }
 
+/// Splits the huge page mapping `vaddr` into pages of the next smaller size,
+/// with the same flags.
+///
+/// Returns `false` if `vaddr` is not mapped by a huge page.
+///
+/// The huge page is unmapped temporarily, so it must not be in use by the
+/// current CPU, e.g., the kernel code or stack.
+fn split_huge_page(pt: &mut PageTable, vaddr: VirtAddr) -> AxResult<bool> {
+    let (paddr, flags, page_size) = match pt.query(vaddr) {
+        Ok((paddr, flags, page_size)) if page_size.is_huge() => (paddr, flags, page_size),
+        _ => return Ok(false),
+    };
+    let sub_size = match page_size {
+        PageSize::Size1G => PageSize::Size2M,
+        _ => PageSize::Size4K,
+    };
+    let block_vaddr = vaddr.align_down(page_size);
+    let block_paddr = paddr - (vaddr - block_vaddr);
+    pt.unmap(block_vaddr).map_err(paging_err_to_ax_err)?.2.flush();
+    for offset in (0..usize::from(page_size)).step_by(sub_size.into()) {
+        pt.map(block_vaddr + offset, block_paddr + offset, sub_size, flags)
+            .map_err(paging_err_to_ax_err)?
+            .ignore();
+    }
+    Ok(true)
+}
+
+impl Drop for AddrSpace {
+    fn drop(&mut self) {
+        self.clear();
+        // The page tables of the copied mappings are owned by the other address
+        // space, unlink them so that they are not freed along with ours.
+        static EMPTY_PAGE_TABLE: LazyInit<PageTable> = LazyInit::new();
+        if !self.copied.is_empty() {
+            EMPTY_PAGE_TABLE
+                .call_once(|| PageTable::try_new().expect("failed to allocate a page table"));
+            for range in self.copied.drain(..) {
+                self.pt
+                    .copy_from(&EMPTY_PAGE_TABLE, range.start, range.size());
+            }
+        }
+    }
+}
+
 impl fmt::Debug for AddrSpace {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_struct("AddrSpace")
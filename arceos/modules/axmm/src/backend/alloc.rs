@@ -1,22 +1,50 @@
 use axalloc::global_allocator;
 use axhal::mem::{phys_to_virt, virt_to_phys};
 use axhal::paging::{MappingFlags, PageSize, PageTable};
-use memory_addr::{PageIter4K, PhysAddr, VirtAddr, PAGE_SIZE_4K};
+use memory_addr::{MemoryAddr, PhysAddr, VirtAddr, PAGE_SIZE_4K};
 
+use super::cow::{present_frame, protect_present, release_frame, resolve_cow};
 use super::Backend;
 
-fn alloc_frame(zeroed: bool) -> Option<PhysAddr> {
-    let vaddr = VirtAddr::from(global_allocator().alloc_pages(1, PAGE_SIZE_4K).ok()?);
+pub(crate) fn alloc_frame(zeroed: bool) -> Option<PhysAddr> {
+    alloc_frames(PageSize::Size4K, zeroed)
+}
+
+pub(crate) fn dealloc_frame(frame: PhysAddr) {
+    dealloc_frames(frame, PageSize::Size4K);
+}
+
+/// Allocates a physically contiguous block for a page of `page_size`, which is
+/// aligned to `page_size` as well.
+fn alloc_frames(page_size: PageSize, zeroed: bool) -> Option<PhysAddr> {
+    let size: usize = page_size.into();
+    let vaddr = VirtAddr::from(global_allocator().alloc_pages(size / PAGE_SIZE_4K, size).ok()?);
+    let paddr = virt_to_phys(vaddr);
+    if !paddr.is_aligned(size) {
+        // The allocator only guarantees the alignment of virtual addresses.
+        global_allocator().dealloc_pages(vaddr.as_usize(), size / PAGE_SIZE_4K);
+        return None;
+    }
     if zeroed {
-        unsafe { core::ptr::write_bytes(vaddr.as_mut_ptr(), 0, PAGE_SIZE_4K) };
+        unsafe { core::ptr::write_bytes(vaddr.as_mut_ptr(), 0, size) };
     }
-    let paddr = virt_to_phys(vaddr);
     Some(paddr)
 }
 
-fn dealloc_frame(frame: PhysAddr) {
+fn dealloc_frames(frame: PhysAddr, page_size: PageSize) {
+    let size: usize = page_size.into();
     let vaddr = phys_to_virt(frame);
-    global_allocator().dealloc_pages(vaddr.as_usize(), 1);
+    global_allocator().dealloc_pages(vaddr.as_usize(), size / PAGE_SIZE_4K);
+}
+
+/// Allocates a page for `vaddr` in the populated mapping, preferring the
+/// largest page size allowed by the alignment of `vaddr` and the remaining
+/// size `remain`. Falls back to smaller pages if the allocation fails.
+fn alloc_populated_page(vaddr: VirtAddr, remain: usize) -> Option<(PhysAddr, PageSize)> {
+    [PageSize::Size1G, PageSize::Size2M, PageSize::Size4K]
+        .into_iter()
+        .filter(|&page_size| vaddr.is_aligned(page_size) && remain >= usize::from(page_size))
+        .find_map(|page_size| Some((alloc_frames(page_size, true)?, page_size)))
 }
 
 impl Backend {
@@ -41,14 +69,20 @@ impl Backend {
             populate
         );
         if populate {
-            // allocate all possible physical frames for populated mapping.
-            for addr in PageIter4K::new(start, start + size).unwrap() {
-                if let Some(frame) = alloc_frame(true) {
-                    if let Ok(tlb) = pt.map(addr, frame, PageSize::Size4K, flags) {
+            // allocate all possible physical frames for populated mapping, using
+            // huge pages if the alignment and size allow.
+            let end = start + size;
+            let mut addr = start;
+            while addr < end {
+                if let Some((frame, page_size)) = alloc_populated_page(addr, end - addr) {
+                    if let Ok(tlb) = pt.map(addr, frame, page_size, flags) {
                         tlb.ignore(); // TLB flush on map is unnecessary, as there are no outdated mappings.
                     } else {
                         return false;
                     }
+                    addr += page_size.into();
+                } else {
+                    addr += PAGE_SIZE_4K;
                 }
             }
             true
@@ -69,30 +103,59 @@ impl Backend {
         _populate: bool,
     ) -> bool {
         debug!("unmap_alloc: [{:#x}, {:#x})", start, start + size);
-        for addr in PageIter4K::new(start, start + size).unwrap() {
+        let end = start + size;
+        let mut addr = start;
+        while addr < end {
+            if let Ok((_, _, page_size)) = pt.query(addr) {
+                // Huge pages crossing the range should have been split by the
+                // caller.
+                if !addr.is_aligned(page_size) || end - addr < usize::from(page_size) {
+                    return false;
+                }
+            }
             if let Ok((frame, page_size, tlb)) = pt.unmap(addr) {
                 // Deallocate the physical frame if there is a mapping in the
-                // page table.
+                // page table. 4K frames may be shared with forked address
+                // spaces, drop our reference only.
+                tlb.flush();
                 if page_size.is_huge() {
-                    return false;
+                    dealloc_frames(frame, page_size);
+                } else {
+                    release_frame(frame);
                 }
-                tlb.flush();
-                dealloc_frame(frame);
+                addr += page_size.into();
             } else {
                 // Deallocation is needn't if the page is not mapped.
+                addr += PAGE_SIZE_4K;
             }
         }
         true
     }
 
+    pub(crate) fn protect_alloc(
+        &self,
+        start: VirtAddr,
+        size: usize,
+        new_flags: MappingFlags,
+        pt: &mut PageTable,
+    ) -> bool {
+        debug!("protect_alloc: [{:#x}, {:#x}) {:?}", start, start + size, new_flags);
+        // Frames shared by `clone_cow` are still copied on write.
+        protect_present(start, size, new_flags, pt, true)
+    }
+
     pub(crate) fn handle_page_fault_alloc(
         &self,
         vaddr: VirtAddr,
+        access_flags: MappingFlags,
         orig_flags: MappingFlags,
         pt: &mut PageTable,
         populate: bool,
     ) -> bool {
-        if populate {
+        if let Some(frame) = present_frame(pt, vaddr) {
+            // The frame has been shared by `clone_cow` and made read-only.
+            access_flags.contains(MappingFlags::WRITE) && resolve_cow(vaddr, frame, orig_flags, pt)
+        } else if populate {
             false // Populated mappings should not trigger page faults.
         } else if let Some(frame) = alloc_frame(true) {
             // Allocate a physical frame lazily and map it to the fault address.
@@ -0,0 +1,202 @@
+use alloc::collections::BTreeMap;
+
+use axhal::mem::phys_to_virt;
+use axhal::paging::{MappingFlags, PageSize, PageTable, PagingError};
+use kspin::SpinNoIrq;
+use memory_addr::{MemoryAddr, PageIter4K, PhysAddr, VirtAddr, PAGE_SIZE_4K};
+
+use super::alloc::{alloc_frame, dealloc_frame};
+use super::Backend;
+
+/// Reference counts of the physical frames shared by copy-on-write mappings.
+///
+/// Frames that are not in the table are exclusively owned by one mapping, i.e.,
+/// their reference count is 1.
+static FRAME_REFS: SpinNoIrq<BTreeMap<PhysAddr, usize>> = SpinNoIrq::new(BTreeMap::new());
+
+/// Increases the reference count of a frame which is going to be shared.
+pub(crate) fn share_frame(frame: PhysAddr) {
+    *FRAME_REFS.lock().entry(frame).or_insert(1) += 1;
+}
+
+/// Returns whether the frame is shared by more than one mapping.
+pub(crate) fn frame_is_shared(frame: PhysAddr) -> bool {
+    FRAME_REFS.lock().contains_key(&frame)
+}
+
+/// Drops one reference of the frame, and deallocates it if it was the last
+/// one.
+pub(crate) fn release_frame(frame: PhysAddr) {
+    let mut refs = FRAME_REFS.lock();
+    match refs.get_mut(&frame) {
+        Some(cnt) if *cnt > 2 => *cnt -= 1,
+        Some(_) => {
+            refs.remove(&frame);
+        }
+        None => {
+            drop(refs);
+            dealloc_frame(frame);
+        }
+    }
+}
+
+/// Returns the physical frame mapped at `vaddr`, or `None` if there is only an
+/// empty entry or no entry at all.
+pub(crate) fn present_frame(pt: &PageTable, vaddr: VirtAddr) -> Option<PhysAddr> {
+    match pt.query(vaddr.align_down_4k()) {
+        Ok((frame, flags, _)) if !flags.is_empty() => Some(frame),
+        _ => None,
+    }
+}
+
+impl Backend {
+    /// Creates a new copy-on-write mapping backend.
+    pub const fn new_cow() -> Self {
+        Self::Cow
+    }
+
+    pub(crate) fn map_cow(
+        &self,
+        start: VirtAddr,
+        size: usize,
+        _flags: MappingFlags,
+        pt: &mut PageTable,
+    ) -> bool {
+        debug!("map_cow: [{:#x}, {:#x})", start, start + size);
+        map_empty(start, size, pt)
+    }
+
+    pub(crate) fn unmap_cow(&self, start: VirtAddr, size: usize, pt: &mut PageTable) -> bool {
+        debug!("unmap_cow: [{:#x}, {:#x})", start, start + size);
+        for addr in PageIter4K::new(start, start + size).unwrap() {
+            let frame = present_frame(pt, addr);
+            if let Ok((_, page_size, tlb)) = pt.unmap(addr) {
+                if page_size.is_huge() {
+                    return false;
+                }
+                tlb.flush();
+            }
+            if let Some(frame) = frame {
+                release_frame(frame);
+            }
+        }
+        true
+    }
+
+    pub(crate) fn protect_cow(
+        &self,
+        start: VirtAddr,
+        size: usize,
+        new_flags: MappingFlags,
+        pt: &mut PageTable,
+    ) -> bool {
+        debug!("protect_cow: [{:#x}, {:#x}) {:?}", start, start + size, new_flags);
+        protect_present(start, size, new_flags, pt, true)
+    }
+
+    pub(crate) fn handle_page_fault_cow(
+        &self,
+        vaddr: VirtAddr,
+        access_flags: MappingFlags,
+        orig_flags: MappingFlags,
+        pt: &mut PageTable,
+    ) -> bool {
+        let Some(frame) = present_frame(pt, vaddr) else {
+            // Never touched since the mapping was created, allocate it lazily.
+            return match alloc_frame(true) {
+                Some(frame) => pt
+                    .remap(vaddr, frame, orig_flags)
+                    .map(|(_, tlb)| tlb.flush())
+                    .is_ok(),
+                None => false,
+            };
+        };
+        if !access_flags.contains(MappingFlags::WRITE) {
+            return false; // Only writes to a shared frame can fault.
+        }
+        resolve_cow(vaddr, frame, orig_flags, pt)
+    }
+}
+
+/// Maps the pages in the range that are not present yet to empty entries, for
+/// on-demand allocation. Pages that are already present are kept as they are.
+pub(super) fn map_empty(start: VirtAddr, size: usize, pt: &mut PageTable) -> bool {
+    for addr in PageIter4K::new(start, start + size).unwrap() {
+        match pt.map(addr, 0.into(), PageSize::Size4K, MappingFlags::empty()) {
+            Ok(tlb) => tlb.ignore(),
+            Err(PagingError::AlreadyMapped) => {}
+            Err(_) => return false,
+        }
+    }
+    true
+}
+
+/// Changes the flags of the present pages in the range, which may be huge
+/// pages not crossing the boundaries of the range.
+///
+/// If `keep_shared_ro` is `true`, the shared frames stay read-only so that
+/// writes to them still fault and can be resolved by [`resolve_cow`].
+pub(super) fn protect_present(
+    start: VirtAddr,
+    size: usize,
+    new_flags: MappingFlags,
+    pt: &mut PageTable,
+    keep_shared_ro: bool,
+) -> bool {
+    let end = start + size;
+    let mut addr = start;
+    while addr < end {
+        let (frame, page_size) = match pt.query(addr) {
+            Ok((frame, flags, page_size)) if !flags.is_empty() => (frame, page_size),
+            _ => {
+                addr += PAGE_SIZE_4K;
+                continue;
+            }
+        };
+        // Only 4K frames are shared.
+        let flags = if keep_shared_ro && !page_size.is_huge() && frame_is_shared(frame) {
+            new_flags - MappingFlags::WRITE
+        } else {
+            new_flags
+        };
+        match pt.protect(addr, flags) {
+            Ok((_, tlb)) => tlb.flush(),
+            Err(_) => return false,
+        }
+        addr = addr.align_down(page_size) + usize::from(page_size);
+    }
+    true
+}
+
+/// Resolves a write fault on the present `frame` mapped at `vaddr`.
+///
+/// If the frame is still shared, the page is copied to a new frame. Otherwise,
+/// the frame is remapped with its original flags directly.
+pub(super) fn resolve_cow(
+    vaddr: VirtAddr,
+    frame: PhysAddr,
+    orig_flags: MappingFlags,
+    pt: &mut PageTable,
+) -> bool {
+    let new_frame = if frame_is_shared(frame) {
+        // Copy the page out of the shared frame, and drop our reference.
+        let Some(new_frame) = alloc_frame(false) else {
+            return false;
+        };
+        unsafe {
+            core::ptr::copy_nonoverlapping(
+                phys_to_virt(frame).as_ptr(),
+                phys_to_virt(new_frame).as_mut_ptr(),
+                PAGE_SIZE_4K,
+            )
+        };
+        release_frame(frame);
+        new_frame
+    } else {
+        // All other sharers are gone, reuse the frame directly.
+        frame
+    };
+    pt.remap(vaddr.align_down_4k(), new_frame, orig_flags)
+        .map(|(_, tlb)| tlb.flush())
+        .is_ok()
+}
@@ -0,0 +1,254 @@
+use alloc::sync::Arc;
+
+use axfs::fops::File;
+use axhal::mem::{phys_to_virt, virt_to_phys};
+use axhal::paging::{MappingFlags, PageTable};
+use memory_addr::{MemoryAddr, PageIter4K, PhysAddr, VirtAddr, PAGE_SIZE_4K};
+
+use super::alloc::{alloc_frame, dealloc_frame};
+use super::cow::{map_empty, present_frame, protect_present, release_frame, resolve_cow};
+use super::Backend;
+
+/// Fills the frame with the file content at `offset`. The part beyond the end
+/// of the file is left zeroed.
+fn fill_frame(file: &File, offset: u64, frame: PhysAddr) -> bool {
+    let buf = unsafe {
+        core::slice::from_raw_parts_mut(phys_to_virt(frame).as_mut_ptr(), PAGE_SIZE_4K)
+    };
+    let mut filled = 0;
+    while filled < PAGE_SIZE_4K {
+        match file.read_at(offset + filled as u64, &mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(_) => return false,
+        }
+    }
+    true
+}
+
+impl Backend {
+    /// Creates a new file mapping backend.
+    ///
+    /// The file content at `offset` is mapped at the virtual address `start`.
+    /// If `shared` is `true`, the modifications are written back to the file.
+    pub fn new_file(file: Arc<File>, start: VirtAddr, offset: usize, shared: bool) -> Self {
+        Self::File {
+            file,
+            start,
+            offset,
+            shared,
+        }
+    }
+
+    /// Returns the mapped file, the file offset of the page at `vaddr`, and
+    /// whether the mapping is shared.
+    fn file_page(&self, vaddr: VirtAddr) -> (&File, u64, bool) {
+        match self {
+            Self::File {
+                file,
+                start,
+                offset,
+                shared,
+            } => (
+                file.as_ref(),
+                (vaddr.align_down_4k() - *start + *offset) as u64,
+                *shared,
+            ),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Returns the mapped file and the index of the page at `vaddr` in the
+    /// page cache of the file.
+    fn file_page_index(&self, vaddr: VirtAddr) -> (&File, u64) {
+        let (file, offset, _) = self.file_page(vaddr);
+        (file, offset / PAGE_SIZE_4K as u64)
+    }
+
+    pub(crate) fn map_file(
+        &self,
+        start: VirtAddr,
+        size: usize,
+        flags: MappingFlags,
+        pt: &mut PageTable,
+    ) -> bool {
+        debug!(
+            "map_file: [{:#x}, {:#x}) {:?} (offset={:#x})",
+            start,
+            start + size,
+            flags,
+            self.file_page(start).1
+        );
+        // The file content is loaded on demand.
+        map_empty(start, size, pt)
+    }
+
+    pub(crate) fn unmap_file(&self, start: VirtAddr, size: usize, pt: &mut PageTable) -> bool {
+        debug!("unmap_file: [{:#x}, {:#x})", start, start + size);
+        let shared = self.file_page(start).2;
+        let mut ok = true;
+        for addr in PageIter4K::new(start, start + size).unwrap() {
+            let page = match pt.query(addr) {
+                Ok((frame, flags, _)) if !flags.is_empty() => Some((frame, flags)),
+                _ => None,
+            };
+            if let Ok((_, page_size, tlb)) = pt.unmap(addr) {
+                if page_size.is_huge() {
+                    return false;
+                }
+                tlb.flush();
+            }
+            match page {
+                // The pages of the page cache are written back once they are
+                // unmapped from all address spaces.
+                Some((_, flags)) if shared => {
+                    let (file, index) = self.file_page_index(addr);
+                    if flags.contains(MappingFlags::WRITE) && file.set_page_dirty(index).is_err() {
+                        ok = false;
+                    }
+                    if let Err(e) = file.unmap_page(index) {
+                        warn!("unmap_file: failed to write back the page: {:?}", e);
+                        ok = false;
+                    }
+                }
+                Some((frame, _)) => release_frame(frame),
+                None => {}
+            }
+        }
+        ok
+    }
+
+    pub(crate) fn protect_file(
+        &self,
+        start: VirtAddr,
+        size: usize,
+        new_flags: MappingFlags,
+        pt: &mut PageTable,
+    ) -> bool {
+        if !self.file_page(start).2 {
+            // Private pages shared with forked address spaces are copied on
+            // write.
+            return protect_present(start, size, new_flags, pt, true);
+        }
+        // Shared pages stay read-only until written, to track the modified
+        // ones.
+        self.clean_pages(start, size, pt).is_some()
+            && protect_present(start, size, new_flags - MappingFlags::WRITE, pt, false)
+    }
+
+    /// Makes the writable pages of a shared file mapping read-only, and marks
+    /// them as modified in the page cache. Returns whether any page is marked,
+    /// or `None` on failure.
+    ///
+    /// The next write to the pages faults, and makes them writable again.
+    fn clean_pages(&self, start: VirtAddr, size: usize, pt: &mut PageTable) -> Option<bool> {
+        let mut dirty = false;
+        for addr in PageIter4K::new(start, start + size).unwrap() {
+            let flags = match pt.query(addr) {
+                Ok((_, flags, _)) if flags.contains(MappingFlags::WRITE) => flags,
+                _ => continue,
+            };
+            // Write-protect the page first, so that no write is missed.
+            let (_, tlb) = pt.protect(addr, flags - MappingFlags::WRITE).ok()?;
+            tlb.flush();
+            let (file, index) = self.file_page_index(addr);
+            file.set_page_dirty(index).ok()?;
+            dirty = true;
+        }
+        Some(dirty)
+    }
+
+    /// Writes the modified pages of a shared file mapping back to the file.
+    pub(crate) fn sync_file(&self, start: VirtAddr, size: usize, pt: &mut PageTable) -> bool {
+        let (file, _, shared) = self.file_page(start);
+        if !shared {
+            return true; // Private mappings are never written back.
+        }
+        match self.clean_pages(start, size, pt) {
+            Some(true) => file
+                .flush()
+                .inspect_err(|e| warn!("sync_file: failed to flush the file: {:?}", e))
+                .is_ok(),
+            Some(false) => true,
+            None => false,
+        }
+    }
+
+    /// Takes a reference to the page of a shared file mapping at `vaddr`, for
+    /// the same page mapped in another address space.
+    pub(crate) fn share_file_page(&self, vaddr: VirtAddr) -> bool {
+        let (file, index) = self.file_page_index(vaddr);
+        file.map_page(index).is_ok()
+    }
+
+    pub(crate) fn handle_page_fault_file(
+        &self,
+        vaddr: VirtAddr,
+        access_flags: MappingFlags,
+        orig_flags: MappingFlags,
+        pt: &mut PageTable,
+    ) -> bool {
+        let vaddr = vaddr.align_down_4k();
+        let (file, offset, shared) = self.file_page(vaddr);
+        let write = access_flags.contains(MappingFlags::WRITE);
+        if shared {
+            return self.handle_shared_fault(vaddr, write, orig_flags, pt);
+        }
+        if let Some(frame) = present_frame(pt, vaddr) {
+            // Private pages shared by `clone_cow` fault on writes.
+            return write && resolve_cow(vaddr, frame, orig_flags, pt);
+        }
+
+        let Some(frame) = alloc_frame(true) else {
+            return false;
+        };
+        if !fill_frame(file, offset, frame) {
+            dealloc_frame(frame);
+            return false;
+        }
+        pt.remap(vaddr, frame, orig_flags)
+            .map(|(_, tlb)| tlb.flush())
+            .is_ok()
+    }
+
+    /// Maps the page of the page cache for a fault in a shared file mapping.
+    ///
+    /// The page is mapped read-only unless it is being written, and the first
+    /// write to it marks it as modified.
+    fn handle_shared_fault(
+        &self,
+        vaddr: VirtAddr,
+        write: bool,
+        orig_flags: MappingFlags,
+        pt: &mut PageTable,
+    ) -> bool {
+        let (file, index) = self.file_page_index(vaddr);
+        if present_frame(pt, vaddr).is_some() {
+            return write
+                && file.set_page_dirty(index).is_ok()
+                && pt
+                    .protect(vaddr, orig_flags)
+                    .map(|(_, tlb)| tlb.flush())
+                    .is_ok();
+        }
+
+        let Ok(page) = file.map_page(index) else {
+            return false;
+        };
+        let frame = virt_to_phys(VirtAddr::from_mut_ptr_of(page.as_ptr()));
+        let flags = if write {
+            orig_flags
+        } else {
+            orig_flags - MappingFlags::WRITE
+        };
+        let mapped = (!write || file.set_page_dirty(index).is_ok())
+            && pt
+                .remap(vaddr, frame, flags)
+                .map(|(_, tlb)| tlb.flush())
+                .is_ok();
+        if !mapped {
+            let _ = file.unmap_page(index);
+        }
+        mapped
+    }
+}
@@ -1,21 +1,38 @@
 //! Memory mapping backends.
 #![allow(dead_code)]
 
+#[cfg(feature = "fs")]
+use ::alloc::sync::Arc;
+
 use axhal::paging::{MappingFlags, PageTable};
-use memory_addr::VirtAddr;
+use memory_addr::{PhysAddr, VirtAddr};
 use memory_set::MappingBackend;
 
 mod alloc;
+mod cow;
+#[cfg(feature = "fs")]
+mod file;
 mod linear;
 
+#[cfg(feature = "swap")]
+pub(crate) use self::alloc::{alloc_frame, dealloc_frame};
+pub(crate) use cow::{frame_is_shared, present_frame, release_frame};
+use cow::share_frame;
+
 /// A unified enum type for different memory mapping backends.
 ///
-/// Currently, two backends are implemented:
+/// Currently, four backends are implemented:
 ///
 /// - **Linear**: used for linear mappings. The target physical frames are
 ///   contiguous and their addresses should be known when creating the mapping.
 /// - **Allocation**: used in general, or for lazy mappings. The target physical
 ///   frames are obtained from the global allocator.
+/// - **Copy-on-write**: used for the private memory of forked address spaces.
+///   The physical frames may be shared read-only with other address spaces,
+///   and are copied on the first write.
+/// - **File** (requires the `fs` feature): used for file mappings. The
+///   private physical frames are filled with the file content on demand, while
+///   shared mappings map the frames of the page cache of the file.
 #[derive(Clone)]
 pub enum Backend {
     /// Linear mapping backend.
@@ -33,10 +50,48 @@ pub enum Backend {
     /// mapping is created, and no page faults are triggered during the memory
     /// access. Otherwise, the physical frames are allocated on demand (by
     /// handling page faults).
+    ///
+    /// The 4K frames may be shared read-only with the address spaces cloned by
+    /// [`AddrSpace::clone_cow`], then they are copied on write as well.
+    ///
+    /// [`AddrSpace::clone_cow`]: crate::AddrSpace::clone_cow
     Alloc {
         /// Whether to populate the physical frames when creating the mapping.
         populate: bool,
     },
+    /// Copy-on-write mapping backend.
+    ///
+    /// The present physical frames are shared with other address spaces (see
+    /// [`AddrSpace::clone_cow`]) and reference counted. A write to a shared
+    /// frame triggers a page fault, which copies the page to a new frame. Pages
+    /// that are not present are allocated on demand, like the lazy allocation
+    /// backend.
+    ///
+    /// [`AddrSpace::clone_cow`]: crate::AddrSpace::clone_cow
+    Cow,
+    /// File mapping backend.
+    ///
+    /// The pages are mapped on demand (by handling page faults). If `shared` is
+    /// `true`, the frames of the page cache of the file are mapped, so that all
+    /// shared mappings of the file see the same pages. They are mapped
+    /// read-only until written, and the modified pages are written back to the
+    /// file on [`AddrSpace::msync`] and when they are no longer mapped.
+    ///
+    /// Otherwise, the physical frames are allocated and filled with the file
+    /// content, and the modifications are private to the mapping.
+    ///
+    /// [`AddrSpace::msync`]: crate::AddrSpace::msync
+    #[cfg(feature = "fs")]
+    File {
+        /// The mapped file.
+        file: Arc<axfs::fops::File>,
+        /// The virtual address where the file content at `offset` is mapped.
+        start: VirtAddr,
+        /// The file offset mapped at `start`.
+        offset: usize,
+        /// Whether the modifications are written back to the file.
+        shared: bool,
+    },
 }
 
 impl MappingBackend for Backend {
@@ -47,6 +102,9 @@ impl MappingBackend for Backend {
         match *self {
             Self::Linear { pa_va_offset } => self.map_linear(start, size, flags, pt, pa_va_offset),
             Self::Alloc { populate } => self.map_alloc(start, size, flags, pt, populate),
+            Self::Cow => self.map_cow(start, size, flags, pt),
+            #[cfg(feature = "fs")]
+            Self::File { .. } => self.map_file(start, size, flags, pt),
         }
     }
 
@@ -54,6 +112,9 @@ impl MappingBackend for Backend {
         match *self {
             Self::Linear { pa_va_offset } => self.unmap_linear(start, size, pt, pa_va_offset),
             Self::Alloc { populate } => self.unmap_alloc(start, size, pt, populate),
+            Self::Cow => self.unmap_cow(start, size, pt),
+            #[cfg(feature = "fs")]
+            Self::File { .. } => self.unmap_file(start, size, pt),
         }
     }
 
@@ -64,24 +125,77 @@ impl MappingBackend for Backend {
         new_flags: Self::Flags,
         page_table: &mut Self::PageTable,
     ) -> bool {
-        page_table
-            .protect_region(start, size, new_flags, true)
-            .map(|tlb| tlb.ignore())
-            .is_ok()
+        match *self {
+            Self::Alloc { .. } => self.protect_alloc(start, size, new_flags, page_table),
+            Self::Cow => self.protect_cow(start, size, new_flags, page_table),
+            #[cfg(feature = "fs")]
+            Self::File { .. } => self.protect_file(start, size, new_flags, page_table),
+            _ => page_table
+                .protect_region(start, size, new_flags, true)
+                .map(|tlb| tlb.ignore())
+                .is_ok(),
+        }
     }
 }
 
 impl Backend {
+    /// Returns whether the present frames of the mapping are private, i.e.,
+    /// they must be copied on write when shared by [`AddrSpace::clone_cow`].
+    ///
+    /// [`AddrSpace::clone_cow`]: crate::AddrSpace::clone_cow
+    pub(crate) fn is_cow(&self) -> bool {
+        match *self {
+            Self::Linear { .. } => false,
+            Self::Alloc { .. } | Self::Cow => true,
+            #[cfg(feature = "fs")]
+            Self::File { shared, .. } => !shared,
+        }
+    }
+
+    /// Writes the modified pages in the range back to the backing storage, if
+    /// there is one.
+    #[cfg_attr(not(feature = "fs"), allow(unused_variables))]
+    pub(crate) fn sync(&self, start: VirtAddr, size: usize, page_table: &mut PageTable) -> bool {
+        match *self {
+            #[cfg(feature = "fs")]
+            Self::File { .. } => self.sync_file(start, size, page_table),
+            _ => true,
+        }
+    }
+
+    /// Takes a reference to the present frame mapped at `vaddr`, which is
+    /// going to be mapped in another address space by
+    /// [`AddrSpace::clone_cow`].
+    ///
+    /// [`AddrSpace::clone_cow`]: crate::AddrSpace::clone_cow
+    #[cfg_attr(not(feature = "fs"), allow(unused_variables))]
+    pub(crate) fn share_page(&self, vaddr: VirtAddr, frame: PhysAddr) -> bool {
+        match *self {
+            #[cfg(feature = "fs")]
+            Self::File { shared: true, .. } => self.share_file_page(vaddr),
+            _ => {
+                share_frame(frame);
+                true
+            }
+        }
+    }
+
     pub(crate) fn handle_page_fault(
         &self,
         vaddr: VirtAddr,
+        access_flags: MappingFlags,
         orig_flags: MappingFlags,
         page_table: &mut PageTable,
     ) -> bool {
         match *self {
             Self::Linear { .. } => false, // Linear mappings should not trigger page faults.
             Self::Alloc { populate } => {
-                self.handle_page_fault_alloc(vaddr, orig_flags, page_table, populate)
+                self.handle_page_fault_alloc(vaddr, access_flags, orig_flags, page_table, populate)
+            }
+            Self::Cow => self.handle_page_fault_cow(vaddr, access_flags, orig_flags, page_table),
+            #[cfg(feature = "fs")]
+            Self::File { .. } => {
+                self.handle_page_fault_file(vaddr, access_flags, orig_flags, page_table)
             }
         }
     }
@@ -0,0 +1,161 @@
+//! Kernel stacks of tasks, mapped in a dedicated region of the kernel address
+//! space with an unmapped guard area below each one.
+
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use axerrno::{ax_err, AxResult};
+use axhal::paging::MappingFlags;
+use axhal::trap::KERNEL_STACK_GUARD_SIZE;
+use kspin::SpinNoIrq;
+use memory_addr::{align_up_4k, va, MemoryAddr, VirtAddr, VirtAddrRange};
+
+use crate::{AddrSpace, VmaKind};
+
+/// Size of the region, which is covered by one top-level page table entry on
+/// all supported architectures.
+const KSTACK_REGION_SIZE: usize = 0x4000_0000; // 1G
+
+const KSTACK_FLAGS: MappingFlags = MappingFlags::READ.union(MappingFlags::WRITE);
+
+/// Where to look for free space for the next stack.
+///
+/// The addresses are handed out in a round-robin manner, so that those of a
+/// freed stack are not reused soon.
+static NEXT_HINT: AtomicUsize = AtomicUsize::new(0);
+
+/// Generation of the kernel stack mappings, bumped when a stack is unmapped.
+static UNMAP_GEN: AtomicUsize = AtomicUsize::new(0);
+
+/// The generation that each CPU has flushed its TLB for, or `usize::MAX` if
+/// the CPU is not online.
+static FLUSHED_GEN: [AtomicUsize; axconfig::SMP] =
+    [const { AtomicUsize::new(usize::MAX) }; axconfig::SMP];
+
+/// Unmapped stacks with their guard areas, and the generations they were
+/// unmapped in. Their addresses are kept reserved until all CPUs have flushed
+/// them from their TLBs.
+///
+/// It is locked after the kernel address space.
+static QUARANTINE: SpinNoIrq<Vec<(VirtAddrRange, usize)>> = SpinNoIrq::new(Vec::new());
+
+/// The region for kernel stacks, at the end of the kernel address space.
+fn kstack_region() -> VirtAddrRange {
+    let end = va!(axconfig::KERNEL_ASPACE_BASE + axconfig::KERNEL_ASPACE_SIZE);
+    VirtAddrRange::new(end.align_down(KSTACK_REGION_SIZE), end)
+}
+
+/// Reserves the region in the kernel address space.
+///
+/// The first page is mapped as a guard area at once, so that the page table
+/// entries of the region are created before any user address space copies the
+/// kernel mappings, and the stacks mapped later are shared with them.
+pub(crate) fn init(aspace: &mut AddrSpace) -> AxResult {
+    let start = kstack_region().start;
+    aspace.map_alloc(start, KERNEL_STACK_GUARD_SIZE, MappingFlags::empty(), false)?;
+    aspace.set_kind(start, KERNEL_STACK_GUARD_SIZE, VmaKind::Guard);
+    Ok(())
+}
+
+/// Flushes the whole TLB of the current CPU, and records the generation of the
+/// stack mappings that it no longer caches.
+pub(crate) fn flush_local_tlb() {
+    let gen = UNMAP_GEN.load(Ordering::Acquire);
+    axhal::arch::flush_tlb(None);
+    FLUSHED_GEN[axhal::cpu::this_cpu_id()].store(gen, Ordering::Release);
+}
+
+/// Handles the TLB shootdown IPI, which is sent to all CPUs after a kernel
+/// stack is unmapped.
+pub fn handle_tlb_shootdown() {
+    flush_local_tlb();
+}
+
+/// Flushes the unmapped stacks from the TLBs of all CPUs. The local TLB is
+/// flushed at once, while other CPUs are sent IPIs to flush theirs.
+fn shootdown() {
+    flush_local_tlb();
+    #[cfg(feature = "irq")]
+    for (cpu_id, gen) in FLUSHED_GEN.iter().enumerate() {
+        if cpu_id != axhal::cpu::this_cpu_id() && gen.load(Ordering::Acquire) != usize::MAX {
+            axhal::irq::send_ipi(cpu_id);
+        }
+    }
+}
+
+/// Releases the addresses of the quarantined stacks that all CPUs have
+/// flushed from their TLBs.
+fn reclaim(aspace: &mut AddrSpace) -> AxResult {
+    let flushed = FLUSHED_GEN
+        .iter()
+        .map(|gen| gen.load(Ordering::Acquire))
+        .min()
+        .unwrap();
+    let mut quarantine = QUARANTINE.lock();
+    while let Some(i) = quarantine.iter().position(|&(_, gen)| gen <= flushed) {
+        let (range, _) = quarantine.swap_remove(i);
+        aspace.unmap(range.start, range.size())?;
+    }
+    Ok(())
+}
+
+/// Allocates a kernel stack of `size` bytes (rounded up to 4K), with an
+/// unmapped guard area of [`KERNEL_STACK_GUARD_SIZE`] below it.
+///
+/// The stack memory is allocated at once. Returns the lowest address of the
+/// stack.
+pub fn alloc_kernel_stack(size: usize) -> AxResult<VirtAddr> {
+    if size == 0 {
+        return ax_err!(InvalidInput, "empty stack");
+    }
+    let size = align_up_4k(size);
+    let region = kstack_region();
+    let total_size = KERNEL_STACK_GUARD_SIZE + size;
+    let mut aspace = crate::kernel_aspace().lock();
+    reclaim(&mut aspace)?;
+    let hint = va!(NEXT_HINT.load(Ordering::Relaxed)).max(region.start);
+    let Some(guard_start) = aspace
+        .find_free_area(hint, total_size, region)
+        .or_else(|| aspace.find_free_area(region.start, total_size, region))
+    else {
+        return ax_err!(NoMemory, "kernel stack region exhausted");
+    };
+    NEXT_HINT.store((guard_start + total_size).as_usize(), Ordering::Relaxed);
+    let start = guard_start + KERNEL_STACK_GUARD_SIZE;
+    aspace.map_alloc(
+        guard_start,
+        KERNEL_STACK_GUARD_SIZE,
+        MappingFlags::empty(),
+        false,
+    )?;
+    aspace.set_kind(guard_start, KERNEL_STACK_GUARD_SIZE, VmaKind::Guard);
+    if let Err(e) = aspace.map_alloc(start, size, KSTACK_FLAGS, true) {
+        aspace.unmap(guard_start, KERNEL_STACK_GUARD_SIZE)?;
+        return Err(e);
+    }
+    aspace.set_kind(start, size, VmaKind::Stack);
+    Ok(start)
+}
+
+/// Deallocates a kernel stack allocated by [`alloc_kernel_stack`], with its
+/// guard area.
+///
+/// Other CPUs may still cache the stack in their TLBs, so its addresses are
+/// not reused until they have been sent IPIs to flush their TLBs.
+pub fn dealloc_kernel_stack(start: VirtAddr, size: usize) -> AxResult {
+    let guard_start = start - KERNEL_STACK_GUARD_SIZE;
+    let total_size = KERNEL_STACK_GUARD_SIZE + align_up_4k(size);
+    let range = VirtAddrRange::from_start_size(guard_start, total_size);
+    if !kstack_region().contains_range(range) {
+        return ax_err!(InvalidInput, "not a kernel stack");
+    }
+    let mut aspace = crate::kernel_aspace().lock();
+    aspace.unmap(guard_start, total_size)?;
+    aspace.map_alloc(guard_start, total_size, MappingFlags::empty(), false)?;
+    aspace.set_kind(guard_start, total_size, VmaKind::Guard);
+    let gen = UNMAP_GEN.fetch_add(1, Ordering::AcqRel) + 1;
+    QUARANTINE.lock().push((range, gen));
+    drop(aspace);
+    shootdown();
+    Ok(())
+}
@@ -1,6 +1,6 @@
 //! [ArceOS](https://github.com/arceos-org/arceos) memory management module.
 
-#![no_std]
+#![cfg_attr(not(test), no_std)]
 
 #[macro_use]
 extern crate log;
@@ -8,8 +8,16 @@ extern crate alloc;
 
 mod aspace;
 mod backend;
+mod kstack;
+mod vma;
+#[cfg(feature = "swap")]
+pub mod swap;
 
 pub use self::aspace::AddrSpace;
+pub use self::kstack::{alloc_kernel_stack, dealloc_kernel_stack, handle_tlb_shootdown};
+pub use self::vma::{VmaInfo, VmaKind};
+#[cfg(feature = "swap")]
+pub use self::swap::{init_swap, swap_stats, SwapStats};
 
 use axerrno::{AxError, AxResult};
 use axhal::mem::phys_to_virt;
@@ -60,6 +68,7 @@ pub fn new_kernel_aspace() -> AxResult<AddrSpace> {
     for r in axhal::mem::memory_regions() {
         aspace.map_linear(phys_to_virt(r.paddr), r.paddr, r.size, r.flags.into())?;
     }
+    kstack::init(&mut aspace)?;
     Ok(aspace)
 }
 
@@ -84,9 +93,11 @@ pub fn init_memory_management() {
     debug!("kernel address space init OK: {:#x?}", kernel_aspace);
     KERNEL_ASPACE.init_once(SpinNoIrq::new(kernel_aspace));
     axhal::paging::set_kernel_page_table_root(kernel_page_table_root());
+    kstack::flush_local_tlb();
 }
 
 /// Initializes kernel paging for secondary CPUs.
 pub fn init_memory_management_secondary() {
     unsafe { axhal::arch::write_page_table_root(kernel_page_table_root()) };
+    kstack::flush_local_tlb();
 }
@@ -0,0 +1,520 @@
+//! Page reclaim and swapping anonymous pages to a block device.
+//!
+//! When the free physical pages drop below [`axconfig::SWAP_LOW_WATERMARK`],
+//! the address space that is handling a page fault reclaims some of its
+//! anonymous pages (of the allocation and copy-on-write mapping areas), by
+//! writing them to the swap area and freeing their frames. Frames still shared
+//! with other address spaces are skipped. A later access to a swapped-out page
+//! faults, and the page is read back from the swap area transparently.
+//!
+//! Victim pages are chosen by a clock (second-chance) algorithm: the clock
+//! hand sweeps over the anonymous pages in address order, and the pages that
+//! were faulted in since the last sweep get a second chance. A sweep scans a
+//! bounded number of pages, so that a page fault never walks the whole
+//! address space.
+//!
+//! Only a block device with a swap header made by `mkswap` (the `SWAPSPACE2`
+//! format of Linux) is used as the swap area, so that a data disk is never
+//! overwritten. The header page and the bad pages it lists are never used.
+//!
+//! The slot bitmap is protected by a spinlock, while the block device is
+//! accessed under a sleeping mutex, so that the I/O does not run with IRQs
+//! disabled.
+
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use axalloc::global_allocator;
+use axdriver::prelude::*;
+use axhal::mem::phys_to_virt;
+use axhal::paging::{MappingFlags, PageTable};
+use axsync::Mutex;
+use kspin::SpinNoIrq;
+use lazyinit::LazyInit;
+use memory_addr::{MemoryAddr, PageIter4K, PhysAddr, VirtAddr, PAGE_SIZE_4K};
+use memory_set::MemorySet;
+
+use crate::backend::{alloc_frame, dealloc_frame, frame_is_shared, Backend};
+
+/// The most pages scanned by one reclaim for each page to reclaim.
+const SCAN_RATIO: usize = 16;
+
+/// Magic at the end of the header page of a swap area made by `mkswap`.
+const SWAP_MAGIC: &[u8] = b"SWAPSPACE2";
+/// Offset of the header fields in the header page, after the boot sector.
+const SWAP_INFO_OFFSET: usize = 1024;
+/// Offset of the bad page list in the header fields, in 32-bit words.
+const SWAP_BADPAGES_WORD: usize = 128;
+/// Most bad pages that fit in the header page.
+const MAX_SWAP_BADPAGES: usize =
+    (PAGE_SIZE_4K - SWAP_MAGIC.len() - SWAP_INFO_OFFSET) / 4 - SWAP_BADPAGES_WORD;
+
+/// The used slots of the swap area.
+struct SwapSlots {
+    /// Bitmap of used slots.
+    used: Vec<u64>,
+    nr_slots: usize,
+    nr_used: usize,
+    /// Number of slots never used, i.e., the header and the bad pages.
+    nr_reserved: usize,
+}
+
+/// The header of a swap area made by `mkswap`, in its first page.
+struct SwapHeader {
+    /// The last usable page.
+    last_page: usize,
+    /// Pages that must not be used.
+    bad_pages: Vec<usize>,
+}
+
+/// The swap area on a block device, divided into page-sized slots.
+struct SwapArea {
+    dev: Mutex<AxBlockDevice>,
+    blocks_per_slot: u64,
+    slots: SpinNoIrq<SwapSlots>,
+}
+
+static SWAP_AREA: LazyInit<SwapArea> = LazyInit::new();
+static SWAP_INS: AtomicUsize = AtomicUsize::new(0);
+static SWAP_OUTS: AtomicUsize = AtomicUsize::new(0);
+
+/// Statistics of the swap area.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SwapStats {
+    /// Total number of pages that have been read back from the swap area.
+    pub swap_ins: usize,
+    /// Total number of pages that have been written to the swap area.
+    pub swap_outs: usize,
+    /// Number of pages currently in the swap area.
+    pub used_slots: usize,
+    /// Capacity of the swap area in pages.
+    pub total_slots: usize,
+}
+
+impl SwapSlots {
+    fn new(nr_slots: usize) -> Self {
+        Self {
+            used: alloc::vec![0; nr_slots.div_ceil(64)],
+            nr_slots,
+            nr_used: 0,
+            nr_reserved: 0,
+        }
+    }
+
+    /// Marks the slot as never to be used.
+    fn reserve(&mut self, slot: usize) {
+        let word = &mut self.used[slot / 64];
+        if *word & (1 << (slot % 64)) == 0 {
+            *word |= 1 << (slot % 64);
+            self.nr_reserved += 1;
+        }
+    }
+
+    /// Returns the number of slots that can be used.
+    fn capacity(&self) -> usize {
+        self.nr_slots - self.nr_reserved
+    }
+
+    fn alloc_slot(&mut self) -> Option<usize> {
+        let (i, word) = self
+            .used
+            .iter_mut()
+            .enumerate()
+            .find(|(_, word)| **word != u64::MAX)?;
+        let bit = word.trailing_ones() as usize;
+        let slot = i * 64 + bit;
+        if slot >= self.nr_slots {
+            return None;
+        }
+        *word |= 1 << bit;
+        self.nr_used += 1;
+        Some(slot)
+    }
+
+    fn free_slot(&mut self, slot: usize) {
+        let word = &mut self.used[slot / 64];
+        debug_assert!(*word & (1 << (slot % 64)) != 0);
+        *word &= !(1 << (slot % 64));
+        self.nr_used -= 1;
+    }
+}
+
+impl SwapHeader {
+    /// Parses the header page, or returns `None` if it is not a valid swap
+    /// header.
+    fn parse(page: &[u8]) -> Option<Self> {
+        if page.len() != PAGE_SIZE_4K || !page.ends_with(SWAP_MAGIC) {
+            return None;
+        }
+        let word = |idx: usize| {
+            let offset = SWAP_INFO_OFFSET + idx * 4;
+            u32::from_ne_bytes(page[offset..offset + 4].try_into().unwrap()) as usize
+        };
+        // `version`, `last_page` and `nr_badpages`.
+        let (version, last_page, nr_badpages) = (word(0), word(1), word(2));
+        if version != 1 || last_page == 0 || nr_badpages > MAX_SWAP_BADPAGES {
+            return None;
+        }
+        let bad_pages = (0..nr_badpages)
+            .map(|i| word(SWAP_BADPAGES_WORD + i))
+            .collect();
+        Some(Self {
+            last_page,
+            bad_pages,
+        })
+    }
+}
+
+impl SwapArea {
+    /// Opens the swap area on the block device. Returns the device back if it
+    /// has no swap header.
+    fn new(mut dev: AxBlockDevice) -> Result<Self, AxBlockDevice> {
+        let block_size = dev.block_size();
+        if block_size > PAGE_SIZE_4K || PAGE_SIZE_4K % block_size != 0 {
+            return Err(dev);
+        }
+        let blocks_per_slot = (PAGE_SIZE_4K / block_size) as u64;
+        let mut page = alloc::vec![0; PAGE_SIZE_4K];
+        if dev.read_block(0, &mut page).is_err() {
+            return Err(dev);
+        }
+        let Some(header) = SwapHeader::parse(&page) else {
+            return Err(dev);
+        };
+
+        let dev_slots = (dev.num_blocks() / blocks_per_slot) as usize;
+        let mut slots = SwapSlots::new(dev_slots.min(header.last_page + 1));
+        slots.reserve(0);
+        for &page in &header.bad_pages {
+            if page < slots.nr_slots {
+                slots.reserve(page);
+            }
+        }
+        Ok(Self {
+            dev: Mutex::new(dev),
+            blocks_per_slot,
+            slots: SpinNoIrq::new(slots),
+        })
+    }
+
+    fn write_slot(&self, slot: usize, frame: PhysAddr) -> DevResult {
+        let buf = unsafe {
+            core::slice::from_raw_parts(phys_to_virt(frame).as_ptr(), PAGE_SIZE_4K)
+        };
+        self.dev
+            .lock()
+            .write_block(slot as u64 * self.blocks_per_slot, buf)
+    }
+
+    fn read_slot(&self, slot: usize, frame: PhysAddr) -> DevResult {
+        let buf = unsafe {
+            core::slice::from_raw_parts_mut(phys_to_virt(frame).as_mut_ptr(), PAGE_SIZE_4K)
+        };
+        self.dev
+            .lock()
+            .read_block(slot as u64 * self.blocks_per_slot, buf)
+    }
+}
+
+/// Initializes the swap area with the block device, if it has a swap header
+/// made by `mkswap`.
+///
+/// Returns the device back if it has no swap header, or the swap area is
+/// already initialized.
+pub fn init_swap(dev: AxBlockDevice) -> Result<(), AxBlockDevice> {
+    if SWAP_AREA.is_inited() {
+        return Err(dev);
+    }
+    let area = SwapArea::new(dev)?;
+    info!(
+        "Initialize swap area on block device {:?}: {} pages",
+        area.dev.lock().device_name(),
+        area.slots.lock().capacity()
+    );
+    SWAP_AREA.init_once(area);
+    Ok(())
+}
+
+/// Returns the statistics of the swap area.
+pub fn swap_stats() -> SwapStats {
+    let (used_slots, total_slots) = SWAP_AREA
+        .get()
+        .map(|area| {
+            let slots = area.slots.lock();
+            (slots.nr_used, slots.capacity())
+        })
+        .unwrap_or_default();
+    SwapStats {
+        swap_ins: SWAP_INS.load(Ordering::Relaxed),
+        swap_outs: SWAP_OUTS.load(Ordering::Relaxed),
+        used_slots,
+        total_slots,
+    }
+}
+
+/// Returns whether the free physical pages are low, so that some pages should
+/// be reclaimed.
+fn low_on_memory() -> bool {
+    SWAP_AREA.is_inited() && global_allocator().available_pages() < axconfig::SWAP_LOW_WATERMARK
+}
+
+/// Returns whether the pages of the backend are anonymous, which can be
+/// swapped out.
+pub(crate) fn is_swappable(backend: &Backend) -> bool {
+    matches!(backend, Backend::Alloc { .. } | Backend::Cow)
+}
+
+/// Writes the frame to a free slot of the swap area. Returns the slot.
+fn swap_out_frame(frame: PhysAddr) -> Option<usize> {
+    let area = SWAP_AREA.get()?;
+    let slot = area.slots.lock().alloc_slot()?;
+    if let Err(e) = area.write_slot(slot, frame) {
+        warn!("failed to write swap slot {}: {:?}", slot, e);
+        area.slots.lock().free_slot(slot);
+        return None;
+    }
+    SWAP_OUTS.fetch_add(1, Ordering::Relaxed);
+    Some(slot)
+}
+
+/// Reads the slot back to the frame, and frees the slot.
+fn swap_in_frame(slot: usize, frame: PhysAddr) -> bool {
+    let res = SWAP_AREA.read_slot(slot, frame);
+    SWAP_AREA.slots.lock().free_slot(slot);
+    if let Err(e) = res {
+        warn!("failed to read swap slot {}: {:?}", slot, e);
+        return false;
+    }
+    SWAP_INS.fetch_add(1, Ordering::Relaxed);
+    true
+}
+
+/// Per address space swapping state.
+pub(crate) struct SwapState {
+    /// Swapped-out pages and their slots in the swap area.
+    swapped: BTreeMap<VirtAddr, usize>,
+    /// Pages faulted in since the last sweep of the clock hand.
+    referenced: BTreeSet<VirtAddr>,
+    /// The clock hand, where the next sweep starts.
+    hand: VirtAddr,
+}
+
+impl SwapState {
+    pub const fn new() -> Self {
+        Self {
+            swapped: BTreeMap::new(),
+            referenced: BTreeSet::new(),
+            hand: VirtAddr::from_usize(0),
+        }
+    }
+
+    /// Marks the page as recently used.
+    pub fn touch(&mut self, vaddr: VirtAddr) {
+        self.referenced.insert(vaddr.align_down_4k());
+    }
+
+    /// Reclaims some pages if the free physical pages are low.
+    pub fn balance(&mut self, areas: &MemorySet<Backend>, pt: &mut PageTable) {
+        if low_on_memory() {
+            let n = self.reclaim(areas, pt, axconfig::SWAP_RECLAIM_BATCH);
+            debug!("reclaimed {} pages, {:?}", n, swap_stats());
+        }
+    }
+
+    /// Swaps out up to `nr_pages` anonymous pages, scanning at most
+    /// [`SCAN_RATIO`] times as many pages. Returns the number of pages
+    /// reclaimed.
+    pub fn reclaim(
+        &mut self,
+        areas: &MemorySet<Backend>,
+        pt: &mut PageTable,
+        nr_pages: usize,
+    ) -> usize {
+        let ranges = || {
+            areas
+                .iter()
+                .filter(|area| is_swappable(area.backend()))
+                .map(|area| (area.start(), area.end()))
+        };
+        let mut reclaimed = 0;
+        // Sweep from the hand to the end, then wrap around. The pages skipped
+        // in the first round lose their second chance in the second round.
+        let hand = self.hand;
+        let pages = sweep(ranges, hand).chain(sweep(ranges, hand));
+        for vaddr in pages.take(nr_pages * SCAN_RATIO) {
+            if reclaimed == nr_pages {
+                break;
+            }
+            self.hand = vaddr + PAGE_SIZE_4K;
+            // Huge pages of populated mappings are never swapped out, nor are
+            // the frames shared with other address spaces.
+            let frame = match pt.query(vaddr) {
+                Ok((frame, flags, page_size)) if !flags.is_empty() && !page_size.is_huge() => frame,
+                _ => continue,
+            };
+            if self.referenced.remove(&vaddr) || frame_is_shared(frame) {
+                continue;
+            }
+            let Some(slot) = swap_out_frame(frame) else {
+                break; // The swap area is full or broken.
+            };
+            match pt.remap(vaddr, 0.into(), MappingFlags::empty()) {
+                Ok((_, tlb)) => tlb.flush(),
+                Err(_) => {
+                    SWAP_AREA.slots.lock().free_slot(slot);
+                    continue;
+                }
+            }
+            dealloc_frame(frame);
+            self.swapped.insert(vaddr, slot);
+            reclaimed += 1;
+        }
+        reclaimed
+    }
+
+    /// Reads the page at `vaddr` back if it was swapped out.
+    ///
+    /// Returns `None` if the page is not swapped out, otherwise returns whether
+    /// the page is restored successfully.
+    pub fn swap_in(
+        &mut self,
+        vaddr: VirtAddr,
+        flags: MappingFlags,
+        pt: &mut PageTable,
+    ) -> Option<bool> {
+        let vaddr = vaddr.align_down_4k();
+        let slot = *self.swapped.get(&vaddr)?;
+        let Some(frame) = alloc_frame(false) else {
+            return Some(false);
+        };
+        if !swap_in_frame(slot, frame) {
+            dealloc_frame(frame);
+            return Some(false);
+        }
+        self.swapped.remove(&vaddr);
+        self.touch(vaddr);
+        Some(
+            pt.remap(vaddr, frame, flags)
+                .map(|(_, tlb)| tlb.flush())
+                .is_ok(),
+        )
+    }
+
+    /// Reads back all swapped-out pages.
+    pub fn swap_in_all(&mut self, areas: &MemorySet<Backend>, pt: &mut PageTable) -> bool {
+        let pages: Vec<_> = self.swapped.keys().copied().collect();
+        pages.into_iter().all(|vaddr| {
+            let flags = areas.find(vaddr).map_or(MappingFlags::empty(), |a| a.flags());
+            self.swap_in(vaddr, flags, pt) == Some(true)
+        })
+    }
+
+    /// Frees the swap slots of the pages in the range, when they are unmapped.
+    pub fn release(&mut self, start: VirtAddr, size: usize) {
+        let end = start + size;
+        let pages: Vec<_> = self.swapped.range(start..end).map(|(&v, _)| v).collect();
+        for vaddr in pages {
+            let slot = self.swapped.remove(&vaddr).unwrap();
+            SWAP_AREA.slots.lock().free_slot(slot);
+        }
+        self.referenced.retain(|vaddr| !(start..end).contains(vaddr));
+    }
+}
+
+/// Returns the pages in the address ranges in address order, starting from
+/// `hand` and wrapping around to the pages below it.
+fn sweep<F, I>(ranges: F, hand: VirtAddr) -> impl Iterator<Item = VirtAddr>
+where
+    F: Fn() -> I,
+    I: Iterator<Item = (VirtAddr, VirtAddr)>,
+{
+    let above = ranges()
+        .filter(move |&(_, end)| end > hand)
+        .flat_map(move |(start, end)| PageIter4K::new(start.max(hand), end).unwrap());
+    let below = ranges()
+        .filter(move |&(start, _)| start < hand)
+        .flat_map(move |(start, end)| PageIter4K::new(start, end.min(hand)).unwrap());
+    above.chain(below)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use memory_addr::{va, VirtAddr, PAGE_SIZE_4K};
+
+    use super::{sweep, SwapHeader, SwapSlots, SWAP_INFO_OFFSET, SWAP_MAGIC};
+
+    #[test]
+    fn test_slots() {
+        let mut slots = SwapSlots::new(130);
+        for i in 0..130 {
+            assert_eq!(slots.alloc_slot(), Some(i));
+        }
+        assert_eq!(slots.alloc_slot(), None);
+        assert_eq!(slots.nr_used, 130);
+
+        slots.free_slot(65);
+        slots.free_slot(3);
+        assert_eq!(slots.nr_used, 128);
+        assert_eq!(slots.alloc_slot(), Some(3));
+        assert_eq!(slots.alloc_slot(), Some(65));
+        assert_eq!(slots.alloc_slot(), None);
+    }
+
+    #[test]
+    fn test_reserved_slots() {
+        let mut slots = SwapSlots::new(4);
+        slots.reserve(0);
+        slots.reserve(2);
+        slots.reserve(2);
+        assert_eq!(slots.capacity(), 2);
+        assert_eq!(slots.alloc_slot(), Some(1));
+        assert_eq!(slots.alloc_slot(), Some(3));
+        assert_eq!(slots.alloc_slot(), None);
+        assert_eq!(slots.nr_used, 2);
+    }
+
+    #[test]
+    fn test_header() {
+        let mut page = alloc::vec![0u8; PAGE_SIZE_4K];
+        assert!(SwapHeader::parse(&page).is_none());
+
+        // As `mkswap` writes: version 1, 99 pages after the header, 2 bad pages.
+        let words: [u32; 3] = [1, 99, 2];
+        for (i, word) in words.iter().enumerate() {
+            let offset = SWAP_INFO_OFFSET + i * 4;
+            page[offset..offset + 4].copy_from_slice(&word.to_ne_bytes());
+        }
+        for (i, bad) in [7u32, 42].iter().enumerate() {
+            let offset = SWAP_INFO_OFFSET + (128 + i) * 4;
+            page[offset..offset + 4].copy_from_slice(&bad.to_ne_bytes());
+        }
+        assert!(SwapHeader::parse(&page).is_none());
+        page[PAGE_SIZE_4K - SWAP_MAGIC.len()..].copy_from_slice(SWAP_MAGIC);
+        let header = SwapHeader::parse(&page).unwrap();
+        assert_eq!(header.last_page, 99);
+        assert_eq!(header.bad_pages, [7, 42]);
+
+        // The old `SWAP-SPACE` format is not supported.
+        page[PAGE_SIZE_4K - SWAP_MAGIC.len()..].copy_from_slice(b"SWAP-SPACE");
+        assert!(SwapHeader::parse(&page).is_none());
+    }
+
+    #[test]
+    fn test_sweep() {
+        let page = |i: usize| va!(i * PAGE_SIZE_4K);
+        let ranges = [(page(1), page(3)), (page(5), page(8))];
+        let pages = |hand: VirtAddr| -> Vec<_> {
+            sweep(|| ranges.iter().copied(), hand)
+                .map(|vaddr| vaddr.as_usize() / PAGE_SIZE_4K)
+                .collect()
+        };
+        assert_eq!(pages(page(0)), [1, 2, 5, 6, 7]);
+        assert_eq!(pages(page(2)), [2, 5, 6, 7, 1]);
+        assert_eq!(pages(page(3)), [5, 6, 7, 1, 2]);
+        assert_eq!(pages(page(6)), [6, 7, 1, 2, 5]);
+        assert_eq!(pages(page(9)), [1, 2, 5, 6, 7]);
+    }
+}
@@ -0,0 +1,155 @@
+//! Bookkeeping of the virtual memory areas (VMAs) of an address space, on top
+//! of the areas in its [`MemorySet`]: what each area is used for, the program
+//! break, and the dump in the format of `/proc/self/maps`.
+//!
+//! [`MemorySet`]: memory_set::MemorySet
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+use axhal::paging::MappingFlags;
+use memory_addr::{VirtAddr, VirtAddrRange};
+
+/// What a virtual memory area is used for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VmaKind {
+    /// Anonymous memory, which is the kind of the unnamed areas.
+    Anon,
+    /// The heap of the program break, see [`AddrSpace::brk`].
+    ///
+    /// [`AddrSpace::brk`]: crate::AddrSpace::brk
+    Heap,
+    /// A stack, see [`AddrSpace::map_stack`].
+    ///
+    /// [`AddrSpace::map_stack`]: crate::AddrSpace::map_stack
+    Stack,
+    /// The inaccessible guard pages below a stack.
+    Guard,
+    /// Memory loaded or mapped from the file at the path, e.g., the segments
+    /// of a program.
+    File(String),
+}
+
+impl VmaKind {
+    /// The pathname column of `/proc/self/maps`.
+    fn pathname(&self) -> &str {
+        match self {
+            Self::Anon | Self::Guard => "",
+            Self::Heap => "[heap]",
+            Self::Stack => "[stack]",
+            Self::File(path) => path,
+        }
+    }
+}
+
+/// Kinds of the address ranges in an address space.
+///
+/// The ranges are kept apart from the areas of the `MemorySet`, which are
+/// split by `unmap` and `protect`. Addresses not in any range are
+/// [`VmaKind::Anon`].
+#[derive(Clone, Default)]
+pub(crate) struct VmaKinds(BTreeMap<VirtAddr, (VirtAddr, VmaKind)>);
+
+impl VmaKinds {
+    pub const fn new() -> Self {
+        Self(BTreeMap::new())
+    }
+
+    /// Returns the kind of the address.
+    pub fn get(&self, vaddr: VirtAddr) -> &VmaKind {
+        self.0
+            .range(..=vaddr)
+            .next_back()
+            .filter(|(_, (end, _))| vaddr < *end)
+            .map_or(&VmaKind::Anon, |(_, (_, kind))| kind)
+    }
+
+    /// Sets the kind of the range, replacing the old ones.
+    pub fn set(&mut self, range: VirtAddrRange, kind: VmaKind) {
+        self.remove(range);
+        if kind != VmaKind::Anon && !range.is_empty() {
+            self.0.insert(range.start, (range.end, kind));
+        }
+    }
+
+    /// Forgets the kinds within the range. The ranges crossing the boundaries
+    /// are trimmed.
+    pub fn remove(&mut self, range: VirtAddrRange) {
+        let before = self
+            .0
+            .range(..range.start)
+            .next_back()
+            .map(|(&start, (end, kind))| (start, *end, kind.clone()));
+        if let Some((start, end, kind)) = before.filter(|(_, end, _)| *end > range.start) {
+            self.0.insert(start, (range.start, kind.clone()));
+            if end > range.end {
+                self.0.insert(range.end, (end, kind));
+            }
+        }
+        let inner: Vec<_> = self.0.range(range.start..range.end).map(|(&s, _)| s).collect();
+        for start in inner {
+            let (end, kind) = self.0.remove(&start).unwrap();
+            if end > range.end {
+                self.0.insert(range.end, (end, kind));
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+}
+
+/// The heap of the program break.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Heap {
+    /// The initial program break, where the heap starts.
+    pub start: VirtAddr,
+    /// The current program break, i.e., the end of the heap.
+    pub brk: VirtAddr,
+}
+
+/// Information and statistics of a virtual memory area.
+///
+/// It is formatted as a line of `/proc/self/maps`.
+#[derive(Debug, Clone)]
+pub struct VmaInfo {
+    /// The address range of the area.
+    pub range: VirtAddrRange,
+    /// The mapping permissions of the area.
+    pub flags: MappingFlags,
+    /// Whether the modifications are shared with other mappings, i.e., it is
+    /// a shared file mapping.
+    pub shared: bool,
+    /// The file offset mapped at the start of a file mapping, or 0.
+    pub offset: usize,
+    /// What the area is used for.
+    pub kind: VmaKind,
+    /// Size of the memory resident in physical frames, in bytes.
+    pub rss: usize,
+}
+
+/// Width of the columns before the pathname, as Linux aligns them.
+const MAPS_PATHNAME_COLUMN: usize = 73;
+
+impl fmt::Display for VmaInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let perm = |flag, c| if self.flags.contains(flag) { c } else { '-' };
+        let line = alloc::format!(
+            "{:08x}-{:08x} {}{}{}{} {:08x} 00:00 0",
+            self.range.start,
+            self.range.end,
+            perm(MappingFlags::READ, 'r'),
+            perm(MappingFlags::WRITE, 'w'),
+            perm(MappingFlags::EXECUTE, 'x'),
+            if self.shared { 's' } else { 'p' },
+            self.offset,
+        );
+        match self.kind.pathname() {
+            "" => f.write_str(&line),
+            path => write!(f, "{:<width$} {}", line, path, width = MAPS_PATHNAME_COLUMN - 1),
+        }
+    }
+}
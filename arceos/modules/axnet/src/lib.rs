@@ -9,6 +9,7 @@
 //! - [`TcpSocket`]: A TCP socket that provides POSIX-like APIs.
 //! - [`UdpSocket`]: A UDP socket that provides POSIX-like APIs.
 //! - [`dns_query`]: Function for DNS query.
+//! - [`tcp_sockets`]: Information of all TCP sockets.
 //!
 //! # Cargo Features
 //!
@@ -35,6 +36,8 @@ pub use self::net_impl::TcpSocket;
 pub use self::net_impl::UdpSocket;
 pub use self::net_impl::{bench_receive, bench_transmit};
 pub use self::net_impl::{dns_query, poll_interfaces};
+pub use self::net_impl::{listen_queue_size, set_listen_queue_size};
+pub use self::net_impl::{tcp_sockets, TcpSocketInfo, TcpState};
 
 use axdriver::{prelude::*, AxDeviceContainer};
 
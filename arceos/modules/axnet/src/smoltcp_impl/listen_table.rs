@@ -1,5 +1,6 @@
-use alloc::{boxed::Box, collections::VecDeque};
+use alloc::{boxed::Box, collections::VecDeque, vec::Vec};
 use core::ops::{Deref, DerefMut};
+use core::task::Waker;
 
 use axerrno::{ax_err, AxError, AxResult};
 use axsync::Mutex;
@@ -7,20 +8,23 @@ use smoltcp::iface::{SocketHandle, SocketSet};
 use smoltcp::socket::tcp::{self, State};
 use smoltcp::wire::{IpAddress, IpEndpoint, IpListenEndpoint};
 
-use super::{SocketSetWrapper, LISTEN_QUEUE_SIZE, SOCKET_SET};
+use super::{SocketSetWrapper, SOCKET_SET};
 
 const PORT_NUM: usize = 65536;
 
 struct ListenTableEntry {
     listen_endpoint: IpListenEndpoint,
     syn_queue: VecDeque<SocketHandle>,
+    /// Woken up when any socket in the SYN queue changes its state.
+    waker: Option<Waker>,
 }
 
 impl ListenTableEntry {
     pub fn new(listen_endpoint: IpListenEndpoint) -> Self {
         Self {
             listen_endpoint,
-            syn_queue: VecDeque::with_capacity(LISTEN_QUEUE_SIZE),
+            syn_queue: VecDeque::new(),
+            waker: None,
         }
     }
 
@@ -78,6 +82,19 @@ impl ListenTable {
         *self.tcp[port as usize].lock() = None;
     }
 
+    /// Returns the listening endpoints, with the number of connections in
+    /// their SYN queues.
+    pub fn listening(&self) -> Vec<(IpListenEndpoint, usize)> {
+        self.tcp
+            .iter()
+            .filter_map(|entry| {
+                let entry = entry.lock();
+                let entry = entry.as_ref()?;
+                Some((entry.listen_endpoint, entry.syn_queue.len()))
+            })
+            .collect()
+    }
+
     pub fn can_accept(&self, port: u16) -> AxResult<bool> {
         if let Some(entry) = self.tcp[port as usize].lock().deref() {
             Ok(entry.syn_queue.iter().any(|&handle| is_connected(handle)))
@@ -86,6 +103,25 @@ impl ListenTable {
         }
     }
 
+    /// Registers a waker to be woken up when a connection may be ready to be
+    /// accepted.
+    pub fn register_waker(&self, port: u16, waker: &Waker) -> AxResult {
+        // Do not lock the socket set with the entry locked.
+        let handles: Vec<SocketHandle> =
+            if let Some(entry) = self.tcp[port as usize].lock().deref_mut() {
+                entry.waker = Some(waker.clone());
+                entry.syn_queue.iter().copied().collect()
+            } else {
+                return ax_err!(InvalidInput, "socket accept() failed: not listen");
+            };
+        for handle in handles {
+            SOCKET_SET.with_socket_mut::<tcp::Socket, _, _>(handle, |socket| {
+                socket.register_recv_waker(waker);
+            });
+        }
+        Ok(())
+    }
+
     pub fn accept(&self, port: u16) -> AxResult<(SocketHandle, (IpEndpoint, IpEndpoint))> {
         if let Some(entry) = self.tcp[port as usize].lock().deref_mut() {
             let syn_queue = &mut entry.syn_queue;
@@ -121,12 +157,15 @@ impl ListenTable {
                 // not listening on this address
                 return;
             }
-            if entry.syn_queue.len() >= LISTEN_QUEUE_SIZE {
+            if entry.syn_queue.len() >= super::listen_queue_size() {
                 // SYN queue is full, drop the packet
                 warn!("SYN queue overflow!");
                 return;
             }
             let mut socket = SocketSetWrapper::new_tcp_socket();
+            if let Some(waker) = &entry.waker {
+                socket.register_recv_waker(waker);
+            }
             if socket.listen(entry.listen_endpoint).is_ok() {
                 let handle = sockets.add(socket);
                 debug!(
@@ -8,9 +8,11 @@ mod udp;
 use alloc::vec;
 use core::cell::RefCell;
 use core::ops::DerefMut;
+use core::sync::atomic::{AtomicUsize, Ordering};
 
 use axdriver::prelude::*;
 use axdriver_net::{DevError, NetBufPtr};
+use axerrno::{ax_err, AxResult};
 use axhal::time::{wall_time_nanos, NANOS_PER_MICROS};
 use axsync::Mutex;
 use lazyinit::LazyInit;
@@ -23,7 +25,7 @@ use smoltcp::wire::{EthernetAddress, HardwareAddress, IpAddress, IpCidr};
 use self::listen_table::ListenTable;
 
 pub use self::dns::dns_query;
-pub use self::tcp::TcpSocket;
+pub use self::tcp::{tcp_sockets, TcpSocket, TcpSocketInfo, TcpState};
 pub use self::udp::UdpSocket;
 
 macro_rules! env_or_default {
@@ -48,7 +50,10 @@ const TCP_RX_BUF_LEN: usize = 64 * 1024;
 const TCP_TX_BUF_LEN: usize = 64 * 1024;
 const UDP_RX_BUF_LEN: usize = 64 * 1024;
 const UDP_TX_BUF_LEN: usize = 64 * 1024;
-const LISTEN_QUEUE_SIZE: usize = 512;
+
+/// The maximum number of pending connections of a listening TCP socket, as
+/// `net.core.somaxconn` in Linux.
+static LISTEN_QUEUE_SIZE: AtomicUsize = AtomicUsize::new(512);
 
 static LISTEN_TABLE: LazyInit<ListenTable> = LazyInit::new();
 static SOCKET_SET: LazyInit<SocketSetWrapper> = LazyInit::new();
@@ -119,6 +124,13 @@ impl<'a> SocketSetWrapper<'a> {
         f(socket)
     }
 
+    pub fn with_sockets<R, F>(&self, f: F) -> R
+    where
+        F: FnOnce(&SocketSet<'a>) -> R,
+    {
+        f(&self.0.lock())
+    }
+
     pub fn poll_interfaces(&self) {
         ETH0.poll(&self.0);
     }
@@ -300,6 +312,24 @@ pub fn poll_interfaces() {
     SOCKET_SET.poll_interfaces();
 }
 
+/// Returns the maximum number of pending connections of a listening TCP
+/// socket.
+pub fn listen_queue_size() -> usize {
+    LISTEN_QUEUE_SIZE.load(Ordering::Relaxed)
+}
+
+/// Sets the maximum number of pending connections of a listening TCP socket.
+/// It applies to the sockets already listening at once.
+///
+/// Returns [`Err(InvalidInput)`](axerrno::AxError::InvalidInput) if `size` is 0.
+pub fn set_listen_queue_size(size: usize) -> AxResult {
+    if size == 0 {
+        return ax_err!(InvalidInput, "zero listen queue size");
+    }
+    LISTEN_QUEUE_SIZE.store(size, Ordering::Relaxed);
+    Ok(())
+}
+
 /// Benchmark raw socket transmit bandwidth.
 pub fn bench_transmit() {
     ETH0.dev.lock().bench_transmit_bandwidth();
@@ -1,6 +1,8 @@
+use alloc::vec::Vec;
 use core::cell::UnsafeCell;
 use core::net::SocketAddr;
 use core::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use core::task::Waker;
 
 use axerrno::{ax_err, ax_err_type, AxError, AxResult};
 use axio::PollState;
@@ -8,9 +10,11 @@ use axsync::Mutex;
 
 use smoltcp::iface::SocketHandle;
 use smoltcp::socket::tcp::{self, ConnectError, State};
+use smoltcp::socket::AnySocket;
 use smoltcp::wire::{IpEndpoint, IpListenEndpoint};
 
-use super::addr::{from_core_sockaddr, into_core_sockaddr, is_unspecified, UNSPECIFIED_ENDPOINT};
+use super::addr::{from_core_sockaddr, into_core_sockaddr, is_unspecified};
+use super::addr::{UNSPECIFIED_ENDPOINT, UNSPECIFIED_IP};
 use super::{SocketSetWrapper, ETH0, LISTEN_TABLE, SOCKET_SET};
 
 // State transitions:
@@ -332,6 +336,53 @@ impl TcpSocket {
         })
     }
 
+    /// Registers a waker to be woken up when the socket may become readable,
+    /// i.e., data arrives, a connection can be accepted, or the connection is
+    /// closed.
+    ///
+    /// Only the last registered waker is woken up, when the state is changed
+    /// by [`poll_interfaces`](crate::poll_interfaces). It is woken up at once
+    /// if there is nothing to wait for.
+    pub fn register_recv_waker(&self, waker: &Waker) {
+        match self.get_state() {
+            STATE_CONNECTED => {
+                // SAFETY: `self.handle` should be initialized in a connected socket.
+                let handle = unsafe { self.handle.get().read().unwrap() };
+                SOCKET_SET.with_socket_mut::<tcp::Socket, _, _>(handle, |socket| {
+                    socket.register_recv_waker(waker);
+                });
+            }
+            STATE_LISTENING => {
+                // SAFETY: `self.local_addr` should be initialized in a listening socket.
+                let local_port = unsafe { self.local_addr.get().read().port };
+                if LISTEN_TABLE.register_waker(local_port, waker).is_err() {
+                    waker.wake_by_ref();
+                }
+            }
+            _ => waker.wake_by_ref(),
+        }
+    }
+
+    /// Registers a waker to be woken up when the socket may become writable,
+    /// i.e., the connection is established, there is room in the send buffer,
+    /// or the connection is closed.
+    ///
+    /// Only the last registered waker is woken up, when the state is changed
+    /// by [`poll_interfaces`](crate::poll_interfaces). It is woken up at once
+    /// if there is nothing to wait for.
+    pub fn register_send_waker(&self, waker: &Waker) {
+        match self.get_state() {
+            STATE_CONNECTING | STATE_CONNECTED => {
+                // SAFETY: `self.handle` should be initialized after `connect()`.
+                let handle = unsafe { self.handle.get().read().unwrap() };
+                SOCKET_SET.with_socket_mut::<tcp::Socket, _, _>(handle, |socket| {
+                    socket.register_send_waker(waker);
+                });
+            }
+            _ => waker.wake_by_ref(),
+        }
+    }
+
     /// Whether the socket is readable or writable.
     pub fn poll(&self) -> AxResult<PollState> {
         match self.get_state() {
@@ -503,6 +554,99 @@ impl Drop for TcpSocket {
     }
 }
 
+/// The state of a TCP socket, numbered as in Linux.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum TcpState {
+    Established = 1,
+    SynSent,
+    SynRecv,
+    FinWait1,
+    FinWait2,
+    TimeWait,
+    Close,
+    CloseWait,
+    LastAck,
+    Listen,
+    Closing,
+}
+
+impl From<State> for TcpState {
+    fn from(state: State) -> Self {
+        match state {
+            State::Closed => Self::Close,
+            State::Listen => Self::Listen,
+            State::SynSent => Self::SynSent,
+            State::SynReceived => Self::SynRecv,
+            State::Established => Self::Established,
+            State::FinWait1 => Self::FinWait1,
+            State::FinWait2 => Self::FinWait2,
+            State::CloseWait => Self::CloseWait,
+            State::Closing => Self::Closing,
+            State::LastAck => Self::LastAck,
+            State::TimeWait => Self::TimeWait,
+        }
+    }
+}
+
+/// Information of a TCP socket, as in `/proc/net/tcp`.
+#[derive(Debug, Clone)]
+pub struct TcpSocketInfo {
+    /// The local address and port.
+    pub local_addr: SocketAddr,
+    /// The remote address and port, which are unspecified if not connected.
+    pub peer_addr: SocketAddr,
+    /// The state of the connection.
+    pub state: TcpState,
+    /// Number of bytes not sent or acknowledged yet.
+    pub send_queue: usize,
+    /// Number of bytes received but not read yet, or the number of pending
+    /// connections for a listening socket.
+    pub recv_queue: usize,
+}
+
+/// Returns the information of the listening TCP sockets, followed by the ones
+/// in connections.
+pub fn tcp_sockets() -> Vec<TcpSocketInfo> {
+    let mut infos: Vec<_> = LISTEN_TABLE
+        .listening()
+        .into_iter()
+        .map(|(endpoint, pending)| TcpSocketInfo {
+            local_addr: into_core_sockaddr(IpEndpoint {
+                addr: endpoint.addr.unwrap_or(UNSPECIFIED_IP),
+                port: endpoint.port,
+            }),
+            peer_addr: into_core_sockaddr(UNSPECIFIED_ENDPOINT),
+            state: TcpState::Listen,
+            send_queue: 0,
+            recv_queue: pending,
+        })
+        .collect();
+    SOCKET_SET.with_sockets(|sockets| {
+        for (_, socket) in sockets.iter() {
+            let Some(socket) = tcp::Socket::downcast(socket) else {
+                continue;
+            };
+            // listening ones wait for connections in the SYN queues
+            if matches!(socket.state(), State::Closed | State::Listen) {
+                continue;
+            }
+            infos.push(TcpSocketInfo {
+                local_addr: into_core_sockaddr(
+                    socket.local_endpoint().unwrap_or(UNSPECIFIED_ENDPOINT),
+                ),
+                peer_addr: into_core_sockaddr(
+                    socket.remote_endpoint().unwrap_or(UNSPECIFIED_ENDPOINT),
+                ),
+                state: socket.state().into(),
+                send_queue: socket.send_queue(),
+                recv_queue: socket.recv_queue(),
+            });
+        }
+    });
+    infos
+}
+
 fn get_ephemeral_port() -> AxResult<u16> {
     const PORT_START: u16 = 0xc000;
     const PORT_END: u16 = 0xffff;
@@ -1,5 +1,6 @@
 use core::net::SocketAddr;
 use core::sync::atomic::{AtomicBool, Ordering};
+use core::task::Waker;
 
 use axerrno::{ax_err, ax_err_type, AxError, AxResult};
 use axio::PollState;
@@ -180,6 +181,27 @@ impl UdpSocket {
         Ok(())
     }
 
+    /// Registers a waker to be woken up when a datagram may be received.
+    ///
+    /// Only the last registered waker is woken up, when a datagram arrives in
+    /// [`poll_interfaces`](crate::poll_interfaces).
+    pub fn register_recv_waker(&self, waker: &Waker) {
+        SOCKET_SET.with_socket_mut::<udp::Socket, _, _>(self.handle, |socket| {
+            socket.register_recv_waker(waker);
+        });
+    }
+
+    /// Registers a waker to be woken up when there may be room to send a
+    /// datagram.
+    ///
+    /// Only the last registered waker is woken up, when queued datagrams are
+    /// sent in [`poll_interfaces`](crate::poll_interfaces).
+    pub fn register_send_waker(&self, waker: &Waker) {
+        SOCKET_SET.with_socket_mut::<udp::Socket, _, _>(self.handle, |socket| {
+            socket.register_send_waker(waker);
+        });
+    }
+
     /// Whether the socket is readable or writable.
     pub fn poll(&self) -> AxResult<PollState> {
         if self.local_addr.read().is_none() {
@@ -0,0 +1,340 @@
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::ffi::{c_char, c_int};
+use core::mem::size_of;
+
+use arceos_posix_api::{clone_fd_table, new_fd_table};
+use axerrno::{LinuxError, LinuxResult};
+use axhal::arch::{TrapFrame, UspaceContext};
+use axmm::AddrSpace;
+use axsync::Mutex;
+use axtask::{AxTaskRef, TaskExtRef};
+use bitflags::bitflags;
+use memory_addr::VirtAddr;
+
+use crate::futex::{exit_robust_list, futex_wake};
+use crate::loader::Program;
+use crate::process::{Pid, Process};
+use crate::signal::ProcessSignal;
+use crate::task::{current_fd_table, current_process, new_user_task, spawn_user_task};
+use crate::uaccess::{read_current_user, read_user_pod, read_user_str, write_user_pod};
+
+/// The low byte of the `clone` flags is the signal sent to the parent when the
+/// child exits.
+const CSIGNAL: usize = 0xff;
+
+/// `wait4` option: return immediately if no child has exited.
+const WNOHANG: u32 = 1;
+
+/// Longest path accepted, including the trailing NUL.
+const PATH_MAX: usize = 4096;
+/// Most bytes of the arguments and environment variables of `execve`,
+/// including the trailing NULs, as the old Linux `ARG_MAX`.
+const ARG_MAX: usize = 0x2_0000;
+
+bitflags! {
+    /// Flags for the `clone` syscall.
+    #[derive(Debug, Clone, Copy)]
+    pub struct CloneFlags: usize {
+        /// Share the address space.
+        const VM = 0x100;
+        /// Share the filesystem information.
+        const FS = 0x200;
+        /// Share the file descriptor table.
+        const FILES = 0x400;
+        /// Share the signal handlers.
+        const SIGHAND = 0x800;
+        /// Suspend the parent until the child execs or exits, which is not
+        /// supported.
+        const VFORK = 0x4000;
+        /// Put the child in the thread group of the caller.
+        const THREAD = 0x10000;
+        /// Set the thread-local storage of the child.
+        const SETTLS = 0x80000;
+        /// Store the child TID at `ptid` in the parent's memory.
+        const PARENT_SETTID = 0x10_0000;
+        /// Clear the child TID at `ctid` in the child's memory when it exits.
+        const CHILD_CLEARTID = 0x20_0000;
+        /// Store the child TID at `ctid` in the child's memory.
+        const CHILD_SETTID = 0x100_0000;
+    }
+}
+
+/// Reads a NULL-terminated array of strings, such as `argv` and `envp`.
+///
+/// The strings take up to `space` bytes with their trailing NULs, which is
+/// reduced by their sizes. Fails with `E2BIG` if they take more.
+fn user_str_array(
+    aspace: &Mutex<AddrSpace>,
+    ptr: *const *const c_char,
+    space: &mut usize,
+) -> LinuxResult<Vec<String>> {
+    let mut strs = Vec::new();
+    if ptr.is_null() {
+        return Ok(strs);
+    }
+    loop {
+        let uaddr = ptr as usize + strs.len() * size_of::<usize>();
+        let s: usize = read_user_pod(aspace, uaddr)?;
+        if s == 0 {
+            return Ok(strs);
+        }
+        let s = read_user_str(aspace, s, space.saturating_sub(1)).map_err(|e| match e {
+            LinuxError::ENAMETOOLONG => LinuxError::E2BIG,
+            e => e,
+        })?;
+        *space -= s.len() + 1;
+        strs.push(s);
+    }
+}
+
+/// Writes a TID to the user memory of the address space.
+fn put_tid(aspace: &Mutex<AddrSpace>, addr: usize, tid: Pid) -> LinuxResult {
+    aspace
+        .lock()
+        .write_user(VirtAddr::from(addr), &(tid as u32).to_ne_bytes())?;
+    Ok(())
+}
+
+/// Loads the program at `path`, and spawns the init process to run it.
+///
+/// The init process adopts all orphaned processes. It also enables the
+/// per-process file descriptor tables of [`arceos_posix_api`], and the checked
+/// user memory reads of [`axsyscall`].
+///
+/// Returns the main thread of the init process.
+pub fn spawn_init(path: &str, args: &[String], envs: &[String]) -> LinuxResult<AxTaskRef> {
+    arceos_posix_api::set_current_fd_table_fn(current_fd_table);
+    axsyscall::set_read_user_fn(read_current_user);
+
+    let mut uspace = axmm::new_user_aspace()?;
+    let uctx = Program::open(path)?.load(&mut uspace, args, envs)?;
+
+    let task = new_user_task(path.into());
+    let init = Process::new(
+        task.id().as_u64(),
+        None,
+        Arc::new(Mutex::new(uspace)),
+        Arc::new(new_fd_table()),
+        ProcessSignal::new(),
+    );
+    init.set_init();
+    Ok(spawn_user_task(task, init, uctx, 0))
+}
+
+/// Terminates the current thread. If it is the last thread of the process,
+/// the process becomes a zombie until its parent waits for it.
+pub fn sys_exit(exit_code: c_int) -> ! {
+    let curr = axtask::current();
+    let ext = curr.task_ext();
+    let robust_list = ext.robust_list();
+    if robust_list != 0 {
+        let tid = curr.id().as_u64() as u32;
+        exit_robust_list(ext.process.aspace(), robust_list as usize, tid);
+    }
+    let clear_child_tid = ext.clear_child_tid();
+    if clear_child_tid != 0 {
+        // The thread may exit by a fault on this address, just ignore it.
+        if put_tid(ext.process.aspace(), clear_child_tid as usize, 0).is_ok() {
+            // Wake up the thread joining this one, as `pthread_join` does.
+            futex_wake(ext.process.aspace(), clear_child_tid as usize, 1);
+        }
+    }
+    if ext.process.remove_thread(curr.id().as_u64()) {
+        ext.process.exit(exit_code);
+    }
+    axtask::exit(exit_code)
+}
+
+/// Terminates all threads of the current process.
+///
+/// The other threads exit on their next syscall or return to user space, see
+/// [`check_group_exit`]. Those blocked in interruptible waits are interrupted.
+pub fn sys_exit_group(exit_code: c_int) -> ! {
+    axtask::current().task_ext().process.group_exit(exit_code);
+    sys_exit(exit_code)
+}
+
+/// Terminates the current thread if another thread of the process has called
+/// `exit_group` or `execve`.
+///
+/// It should be called at the entry of every syscall.
+pub fn check_group_exit() {
+    let curr = axtask::current();
+    let process = &curr.task_ext().process;
+    if process.is_group_exiting() {
+        sys_exit(process.exit_code());
+    }
+    if process.is_exec_killed(curr.id().as_u64()) {
+        sys_exit(0);
+    }
+}
+
+/// Returns the PID of the current process.
+pub fn sys_getpid() -> LinuxResult<isize> {
+    Ok(axtask::current().task_ext().process.pid() as _)
+}
+
+/// Returns the PID of the parent of the current process.
+pub fn sys_getppid() -> LinuxResult<isize> {
+    Ok(axtask::current().task_ext().process.ppid() as _)
+}
+
+/// Returns the TID of the current thread.
+pub fn sys_gettid() -> LinuxResult<isize> {
+    Ok(axtask::current().id().as_u64() as _)
+}
+
+/// Sets the address to clear when the current thread exits. Returns the TID
+/// of the current thread.
+pub fn sys_set_tid_address(tid_ptr: *const i32) -> LinuxResult<isize> {
+    let curr = axtask::current();
+    curr.task_ext().set_clear_child_tid(tid_ptr as _);
+    Ok(curr.id().as_u64() as isize)
+}
+
+/// Creates a child process or thread, as `fork` and `pthread_create` do.
+///
+/// The child returns from the syscall with the trap frame `tf` of the caller,
+/// except that the return value is 0, and the stack pointer is `stack` if it
+/// is not zero. Returns the TID of the child to the caller.
+pub fn sys_clone(
+    tf: &TrapFrame,
+    flags: usize,
+    stack: usize,
+    ptid: usize,
+    tls: usize,
+    ctid: usize,
+) -> LinuxResult<isize> {
+    let clone_flags = CloneFlags::from_bits_truncate(flags & !CSIGNAL);
+    debug!("sys_clone <= flags: {:?}, stack: {:#x}", clone_flags, stack);
+    if clone_flags.contains(CloneFlags::THREAD)
+        && !clone_flags.contains(CloneFlags::VM | CloneFlags::SIGHAND)
+    {
+        return Err(LinuxError::EINVAL);
+    }
+    // The parent would have to wait for the child to exec, which cannot
+    // replace the address space shared with the parent.
+    if clone_flags.contains(CloneFlags::VFORK) {
+        return Err(LinuxError::EINVAL);
+    }
+
+    let mut uctx = UspaceContext::from(tf);
+    uctx.set_retval(0);
+    if stack != 0 {
+        uctx.set_sp(stack);
+    }
+    if clone_flags.contains(CloneFlags::SETTLS) {
+        uctx.set_tls(tls);
+    }
+
+    let curr = axtask::current();
+    let process = &curr.task_ext().process;
+    let task = new_user_task(curr.name().into());
+    let tid = task.id().as_u64();
+    let aspace = if clone_flags.contains(CloneFlags::VM) {
+        process.aspace().clone()
+    } else {
+        Arc::new(Mutex::new(process.aspace().lock().clone_cow()?))
+    };
+    let fd_table = process.fd_table().ok_or(LinuxError::EBADF)?;
+
+    // Everything that may fail is done before the child process is created,
+    // which is registered in the process table and cannot be rolled back.
+    if clone_flags.contains(CloneFlags::PARENT_SETTID) {
+        put_tid(process.aspace(), ptid, tid)?;
+    }
+    if clone_flags.contains(CloneFlags::CHILD_SETTID) {
+        put_tid(&aspace, ctid, tid)?;
+    }
+
+    let child = if clone_flags.contains(CloneFlags::THREAD) {
+        process.clone()
+    } else {
+        let fd_table = if clone_flags.contains(CloneFlags::FILES) {
+            fd_table
+        } else {
+            Arc::new(clone_fd_table(&fd_table))
+        };
+        Process::new(tid, Some(process), aspace, fd_table, process.signal().fork())
+    };
+    let clear_child_tid = if clone_flags.contains(CloneFlags::CHILD_CLEARTID) {
+        ctid
+    } else {
+        0
+    };
+    spawn_user_task(task, child, uctx, clear_child_tid as u64);
+    Ok(tid as isize)
+}
+
+/// Replaces the program of the current process with the one at `path`.
+///
+/// Only returns on failure. The other threads of the process exit before the
+/// old program is gone.
+pub fn sys_execve(
+    path: *const c_char,
+    argv: *const *const c_char,
+    envp: *const *const c_char,
+) -> LinuxResult<isize> {
+    // The inner result is the error after the old program is gone, which is
+    // fatal to the process.
+    let res = (|| -> LinuxResult<LinuxResult<UspaceContext>> {
+        let process = current_process();
+        let aspace = process.aspace();
+        let path = read_user_str(aspace, path as usize, PATH_MAX - 1)?;
+        let mut space = ARG_MAX;
+        let args = user_str_array(aspace, argv, &mut space)?;
+        let envs = user_str_array(aspace, envp, &mut space)?;
+        debug!("sys_execve <= path: {:?}, args: {:?}", path, args);
+
+        let program = Program::open(&path)?;
+        let curr = axtask::current();
+        process.kill_other_threads(curr.id().as_u64())?;
+
+        // The robust futexes are released as if the thread exits.
+        let robust_list = curr.task_ext().robust_list();
+        if robust_list != 0 {
+            let tid = curr.id().as_u64() as u32;
+            exit_robust_list(process.aspace(), robust_list as usize, tid);
+            curr.task_ext().set_robust_list(0);
+        }
+        let mut uspace = process.aspace().lock();
+        uspace.clear();
+        process.signal().reset_handlers();
+        Ok(program.load(&mut uspace, &args, &envs))
+    })();
+    // Nothing is left on the current kernel stack to be dropped from now on.
+    match res {
+        Ok(Ok(uctx)) => {
+            let kstack_top = axtask::current().kernel_stack_top().unwrap();
+            axtask::on_user_return();
+            unsafe { uctx.enter_uspace(kstack_top) }
+        }
+        Ok(Err(e)) => {
+            warn!("sys_execve: failed to load the program: {:?}", e);
+            sys_exit_group(-1)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Waits for a child process to exit, and reaps it. Returns the PID of the
+/// child, or 0 if `WNOHANG` is given and no child has exited.
+///
+/// If `pid` is positive, it waits for that child, otherwise it waits for any
+/// child as process groups are not supported.
+pub fn sys_wait4(pid: c_int, wstatus: *mut c_int, options: u32) -> LinuxResult<isize> {
+    debug!("sys_wait4 <= pid: {}, options: {:#x}", pid, options);
+    let pid = if pid > 0 { Some(pid as Pid) } else { None };
+    let process = current_process();
+    match process.wait_child(pid, options & WNOHANG != 0)? {
+        Some((pid, status)) => {
+            if !wstatus.is_null() {
+                write_user_pod(process.aspace(), wstatus as usize, &status)?;
+            }
+            Ok(pid as isize)
+        }
+        None => Ok(0),
+    }
+}
@@ -0,0 +1,410 @@
+//! Fast user-space mutexes, see `futex(2)`.
+//!
+//! A futex is identified by the address space and the user virtual address of
+//! the futex word. Futexes shared between processes by shared memory are not
+//! supported, so `FUTEX_PRIVATE_FLAG` makes no difference.
+//!
+//! The waiters are kept in buckets hashed by the futex, each with its own lock,
+//! so that unrelated futexes do not contend. Waits are interrupted by signals.
+//!
+//! The futexes in the robust list of a thread, set by `set_robust_list`, are
+//! marked with `FUTEX_OWNER_DIED` when the thread exits still holding them.
+
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::sync::Arc;
+use core::ffi::c_int;
+use core::mem::size_of;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::time::Duration;
+
+use arceos_posix_api::ctypes;
+use axerrno::{LinuxError, LinuxResult};
+use axmm::AddrSpace;
+use axsync::Mutex;
+use axtask::{AxTaskRef, Interrupted, TaskExtRef, WaitQueue};
+use kspin::SpinNoIrq;
+use memory_addr::VirtAddr;
+
+use crate::task::current_process;
+use crate::uaccess::read_user_pod;
+
+const FUTEX_WAIT: c_int = 0;
+const FUTEX_WAKE: c_int = 1;
+const FUTEX_REQUEUE: c_int = 3;
+const FUTEX_CMP_REQUEUE: c_int = 4;
+const FUTEX_WAIT_BITSET: c_int = 9;
+const FUTEX_WAKE_BITSET: c_int = 10;
+
+const FUTEX_PRIVATE_FLAG: c_int = 128;
+const FUTEX_CLOCK_REALTIME: c_int = 256;
+const FUTEX_CMD_MASK: c_int = !(FUTEX_PRIVATE_FLAG | FUTEX_CLOCK_REALTIME);
+
+/// Matches any waiter, as `FUTEX_WAIT` and `FUTEX_WAKE` use.
+const FUTEX_BITSET_MATCH_ANY: u32 = u32::MAX;
+
+// Bits of the futex words of robust futexes.
+const FUTEX_WAITERS: u32 = 0x8000_0000;
+const FUTEX_OWNER_DIED: u32 = 0x4000_0000;
+const FUTEX_TID_MASK: u32 = 0x3fff_ffff;
+
+/// The most entries of a robust list walked, in case it is circular.
+const ROBUST_LIST_LIMIT: usize = 2048;
+
+/// Number of bits of the bucket index, see [`bucket_index`].
+const FUTEX_HASH_BITS: u32 = 6;
+
+/// The address space, and the user address of the futex word.
+type FutexKey = (usize, usize);
+
+/// Waiters of the futexes in a bucket, in FIFO order for each futex.
+type FutexWaiters = BTreeMap<FutexKey, VecDeque<Arc<FutexWaiter>>>;
+
+/// A task waiting on a futex.
+struct FutexWaiter {
+    task: AxTaskRef,
+    bitset: u32,
+    /// The bucket whose wait queue the waiter sleeps in.
+    wq_bucket: usize,
+    /// The futex that the waiter is queued on, which is changed by requeueing
+    /// under the locks of both buckets.
+    key: SpinNoIrq<FutexKey>,
+    /// Set by the waker, then the waiter returns from `FUTEX_WAIT`.
+    woken: AtomicBool,
+}
+
+/// A hash bucket of futexes.
+struct FutexBucket {
+    /// It is a sleeping lock, as reading the futex word may fault on a lazily
+    /// mapped page.
+    waiters: Mutex<FutexWaiters>,
+    /// The waiters that start waiting in this bucket sleep here, even if they
+    /// are requeued to another bucket, and are woken up one by one with
+    /// [`WaitQueue::notify_task`].
+    wq: WaitQueue,
+}
+
+impl FutexBucket {
+    const fn new() -> Self {
+        Self {
+            waiters: Mutex::new(BTreeMap::new()),
+            wq: WaitQueue::new(),
+        }
+    }
+}
+
+static FUTEX_BUCKETS: [FutexBucket; 1 << FUTEX_HASH_BITS] =
+    [const { FutexBucket::new() }; 1 << FUTEX_HASH_BITS];
+
+fn futex_key(aspace: &Arc<Mutex<AddrSpace>>, uaddr: usize) -> FutexKey {
+    (Arc::as_ptr(aspace) as usize, uaddr)
+}
+
+/// Returns the index of the bucket of the futex, by Fibonacci hashing.
+fn bucket_index((aspace, uaddr): FutexKey) -> usize {
+    let hash = ((aspace ^ (uaddr >> 2)) as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    (hash >> (u64::BITS - FUTEX_HASH_BITS)) as usize
+}
+
+/// Reads the futex word in the address space, which must be mapped readable.
+fn load_futex_word(aspace: &Mutex<AddrSpace>, uaddr: usize) -> LinuxResult<u32> {
+    let mut word = [0; size_of::<u32>()];
+    aspace
+        .lock()
+        .read_user(VirtAddr::from(uaddr), &mut word)
+        .map_err(|_| LinuxError::EFAULT)?;
+    Ok(u32::from_ne_bytes(word))
+}
+
+/// Wakes up at most `count` waiters of the futex whose bitsets intersect with
+/// `bitset`. Returns the number of woken waiters.
+fn wake(futexes: &mut FutexWaiters, key: FutexKey, count: usize, bitset: u32) -> usize {
+    let Some(waiters) = futexes.get_mut(&key) else {
+        return 0;
+    };
+    let mut woken = 0;
+    waiters.retain(|waiter| {
+        if woken >= count || waiter.bitset & bitset == 0 {
+            return true;
+        }
+        waiter.woken.store(true, Ordering::Release);
+        FUTEX_BUCKETS[waiter.wq_bucket]
+            .wq
+            .notify_task(false, &waiter.task);
+        woken += 1;
+        false
+    });
+    if waiters.is_empty() {
+        futexes.remove(&key);
+    }
+    woken
+}
+
+/// Wakes up at most `count` waiters of the futex at `uaddr` in `aspace`.
+///
+/// It is used when a thread with `CLONE_CHILD_CLEARTID` exits.
+pub(crate) fn futex_wake(aspace: &Arc<Mutex<AddrSpace>>, uaddr: usize, count: usize) -> usize {
+    let key = futex_key(aspace, uaddr);
+    let mut futexes = FUTEX_BUCKETS[bucket_index(key)].waiters.lock();
+    wake(&mut futexes, key, count, FUTEX_BITSET_MATCH_ANY)
+}
+
+/// Removes the waiter from the futex that it is queued on, after it stops
+/// waiting by a timeout or a signal. Returns `true` if it has been woken up
+/// meanwhile.
+fn dequeue(waiter: &Arc<FutexWaiter>) -> bool {
+    loop {
+        let key = *waiter.key.lock();
+        let mut futexes = FUTEX_BUCKETS[bucket_index(key)].waiters.lock();
+        if waiter.woken.load(Ordering::Acquire) {
+            return true;
+        }
+        // It may have been requeued before the bucket is locked.
+        if *waiter.key.lock() != key {
+            continue;
+        }
+        if let Some(waiters) = futexes.get_mut(&key) {
+            waiters.retain(|w| !Arc::ptr_eq(w, waiter));
+            if waiters.is_empty() {
+                futexes.remove(&key);
+            }
+        }
+        return false;
+    }
+}
+
+/// Blocks the current thread on the futex if the futex word still equals
+/// `val`, until it is woken up, the `timeout` expires, or a signal arrives.
+fn wait(uaddr: usize, val: u32, bitset: u32, timeout: Option<Duration>) -> LinuxResult<isize> {
+    let process = current_process();
+    let key = futex_key(process.aspace(), uaddr);
+    let bucket = &FUTEX_BUCKETS[bucket_index(key)];
+    let waiter = Arc::new(FutexWaiter {
+        task: axtask::current().as_task_ref().clone(),
+        bitset,
+        wq_bucket: bucket_index(key),
+        key: SpinNoIrq::new(key),
+        woken: AtomicBool::new(false),
+    });
+    {
+        // Wakers lock the bucket too, so no wake-up is lost after the check.
+        let mut futexes = bucket.waiters.lock();
+        if load_futex_word(process.aspace(), uaddr)? != val {
+            return Err(LinuxError::EAGAIN);
+        }
+        futexes.entry(key).or_default().push_back(waiter.clone());
+    }
+
+    let woken = || waiter.woken.load(Ordering::Acquire);
+    let err = match timeout {
+        Some(dur) => match bucket.wq.wait_timeout_until_interruptible(dur, woken) {
+            Ok(false) => return Ok(0),
+            Ok(true) => LinuxError::ETIMEDOUT,
+            Err(Interrupted) => LinuxError::EINTR,
+        },
+        None => match bucket.wq.wait_until_interruptible(woken) {
+            Ok(()) => return Ok(0),
+            Err(Interrupted) => LinuxError::EINTR,
+        },
+    };
+    if dequeue(&waiter) {
+        Ok(0)
+    } else {
+        Err(err)
+    }
+}
+
+/// Wakes up at most `nr_wake` waiters of the futex at `uaddr`, and moves at
+/// most `nr_requeue` of the remaining ones to the futex at `uaddr2`.
+///
+/// If `cmp_val` is given, it fails with `EAGAIN` if the futex word does not
+/// equal it.
+fn requeue(
+    uaddr: usize,
+    nr_wake: usize,
+    uaddr2: usize,
+    nr_requeue: usize,
+    cmp_val: Option<u32>,
+) -> LinuxResult<isize> {
+    let process = current_process();
+    let key = futex_key(process.aspace(), uaddr);
+    let key2 = futex_key(process.aspace(), uaddr2);
+
+    // Lock both buckets in the order of their indices, to avoid deadlocks.
+    let (idx, idx2) = (bucket_index(key), bucket_index(key2));
+    let mut low = FUTEX_BUCKETS[idx.min(idx2)].waiters.lock();
+    let mut high = (idx != idx2).then(|| FUTEX_BUCKETS[idx.max(idx2)].waiters.lock());
+    let (futexes, futexes2) = match high.as_deref_mut() {
+        None => (&mut *low, None),
+        Some(high) if idx < idx2 => (&mut *low, Some(high)),
+        Some(high) => (high, Some(&mut *low)),
+    };
+
+    if let Some(val) = cmp_val {
+        if load_futex_word(process.aspace(), uaddr)? != val {
+            return Err(LinuxError::EAGAIN);
+        }
+    }
+    let woken = wake(futexes, key, nr_wake, FUTEX_BITSET_MATCH_ANY);
+    let mut requeued = 0;
+    if key != key2 {
+        if let Some(waiters) = futexes.get_mut(&key) {
+            let moved: VecDeque<_> = waiters
+                .drain(..nr_requeue.min(waiters.len()))
+                .collect();
+            if waiters.is_empty() {
+                futexes.remove(&key);
+            }
+            for waiter in moved.iter() {
+                *waiter.key.lock() = key2;
+            }
+            requeued = moved.len();
+            futexes2
+                .unwrap_or(futexes)
+                .entry(key2)
+                .or_default()
+                .extend(moved);
+        }
+    }
+    Ok((woken + requeued) as isize)
+}
+
+/// Wakes up at most `count` waiters of the futex at `uaddr` in the current
+/// process whose bitsets intersect with `bitset`.
+fn wake_bitset(uaddr: usize, count: usize, bitset: u32) -> usize {
+    let key = futex_key(current_process().aspace(), uaddr);
+    let mut futexes = FUTEX_BUCKETS[bucket_index(key)].waiters.lock();
+    wake(&mut futexes, key, count, bitset)
+}
+
+/// Reads the timeout of `FUTEX_WAIT` (relative) or `FUTEX_WAIT_BITSET`
+/// (absolute, by the clock selected in `op`) from the user.
+fn read_timeout(timeout: usize, op: c_int, absolute: bool) -> LinuxResult<Option<Duration>> {
+    if timeout == 0 {
+        return Ok(None);
+    }
+    let ts: ctypes::timespec = read_user_pod(current_process().aspace(), timeout)?;
+    if ts.tv_sec < 0 || !(0..1_000_000_000).contains(&ts.tv_nsec) {
+        return Err(LinuxError::EINVAL);
+    }
+    let dur = Duration::from(ts);
+    if !absolute {
+        return Ok(Some(dur));
+    }
+    let now = if op & FUTEX_CLOCK_REALTIME != 0 {
+        axhal::time::wall_time()
+    } else {
+        axhal::time::monotonic_time()
+    };
+    Ok(Some(dur.saturating_sub(now)))
+}
+
+/// The Linux `struct robust_list_head`.
+#[repr(C)]
+#[derive(Default)]
+struct RobustListHead {
+    /// The first entry, or the head itself if the list is empty.
+    list: usize,
+    /// The offset of the futex word from the address of an entry.
+    futex_offset: isize,
+    /// The entry being taken or released, which may not be in the list yet.
+    list_op_pending: usize,
+}
+
+/// Releases the robust futex at `uaddr` if it is held by the exiting thread
+/// `tid`, and wakes up a waiter to take it.
+fn handle_futex_death(aspace: &Arc<Mutex<AddrSpace>>, uaddr: usize, tid: u32) {
+    let val = {
+        let mut uspace = aspace.lock();
+        let Ok(word) = uspace.atomic_user_u32(VirtAddr::from(uaddr)) else {
+            return;
+        };
+        let res = word.fetch_update(Ordering::AcqRel, Ordering::Acquire, |val| {
+            (val & FUTEX_TID_MASK == tid).then_some((val & FUTEX_WAITERS) | FUTEX_OWNER_DIED)
+        });
+        match res {
+            Ok(val) => val,
+            Err(_) => return, // Not held by the thread.
+        }
+    };
+    if val & FUTEX_WAITERS != 0 {
+        futex_wake(aspace, uaddr, 1);
+    }
+}
+
+/// Releases the robust futexes still held by the exiting thread `tid`, whose
+/// robust list is at `head`. See `set_robust_list(2)`.
+pub(crate) fn exit_robust_list(aspace: &Arc<Mutex<AddrSpace>>, head: usize, tid: u32) {
+    let Ok(list) = read_user_pod::<RobustListHead>(aspace, head) else {
+        return;
+    };
+    // The lowest bit of an entry marks a priority-inheritance futex.
+    let pending = list.list_op_pending & !1;
+    let mut entry = list.list & !1;
+    for _ in 0..ROBUST_LIST_LIMIT {
+        if entry == head {
+            break;
+        }
+        // Read the next entry before the futex is released.
+        let Ok(next) = read_user_pod::<usize>(aspace, entry) else {
+            return;
+        };
+        if entry != pending {
+            handle_futex_death(aspace, entry.wrapping_add_signed(list.futex_offset), tid);
+        }
+        entry = next & !1;
+    }
+    if pending != 0 {
+        handle_futex_death(aspace, pending.wrapping_add_signed(list.futex_offset), tid);
+    }
+}
+
+/// Sets the robust list of the current thread, which is released when the
+/// thread exits. See `set_robust_list(2)`.
+pub fn sys_set_robust_list(head: usize, len: usize) -> LinuxResult<isize> {
+    if len != size_of::<RobustListHead>() {
+        return Err(LinuxError::EINVAL);
+    }
+    axtask::current().task_ext().set_robust_list(head as u64);
+    Ok(0)
+}
+
+/// Waits on or wakes up futexes, as `futex(2)`.
+///
+/// `FUTEX_WAIT`, `FUTEX_WAKE`, `FUTEX_REQUEUE`, `FUTEX_CMP_REQUEUE`,
+/// `FUTEX_WAIT_BITSET` and `FUTEX_WAKE_BITSET` are supported. For the requeue
+/// operations, `timeout` is the maximum number of waiters to requeue.
+pub fn sys_futex(
+    uaddr: usize,
+    op: c_int,
+    val: u32,
+    timeout: usize,
+    uaddr2: usize,
+    val3: u32,
+) -> LinuxResult<isize> {
+    if uaddr == 0 {
+        return Err(LinuxError::EFAULT);
+    }
+    if uaddr % 4 != 0 {
+        return Err(LinuxError::EINVAL);
+    }
+    match op & FUTEX_CMD_MASK {
+        FUTEX_WAIT => wait(
+            uaddr,
+            val,
+            FUTEX_BITSET_MATCH_ANY,
+            read_timeout(timeout, op, false)?,
+        ),
+        FUTEX_WAIT_BITSET if val3 != 0 => {
+            wait(uaddr, val, val3, read_timeout(timeout, op, true)?)
+        }
+        FUTEX_WAKE => Ok(wake_bitset(uaddr, val as usize, FUTEX_BITSET_MATCH_ANY) as isize),
+        FUTEX_WAKE_BITSET if val3 != 0 => Ok(wake_bitset(uaddr, val as usize, val3) as isize),
+        FUTEX_REQUEUE => requeue(uaddr, val as usize, uaddr2, timeout, None),
+        FUTEX_CMP_REQUEUE => requeue(uaddr, val as usize, uaddr2, timeout, Some(val3)),
+        FUTEX_WAIT_BITSET | FUTEX_WAKE_BITSET => Err(LinuxError::EINVAL),
+        cmd => {
+            warn!("sys_futex: unsupported operation {}", cmd);
+            Err(LinuxError::ENOSYS)
+        }
+    }
+}
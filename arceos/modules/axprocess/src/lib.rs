@@ -0,0 +1,67 @@
+//! [ArceOS](https://github.com/arceos-org/arceos) process management module
+//! for monolithic kernels.
+//!
+//! It implements the Linux process model on top of [`axtask`] tasks and
+//! [`axmm::AddrSpace`]:
+//!
+//! - A process is a thread group sharing one address space and one file
+//!   descriptor table. Every user thread is an [`axtask`] task whose extended
+//!   data is [`TaskExt`].
+//! - Processes form a tree. Orphans are adopted by the init process, and
+//!   exited processes stay as zombies until their parents reap them by
+//!   `wait4`.
+//! - The `sys_*` functions implement the corresponding Linux syscalls, such as
+//!   `clone`, `execve`, `wait4`, `exit_group` and `mmap`. They are registered
+//!   into [`axsyscall`], so the kernel only needs to forward the `SYSCALL`
+//!   traps to [`axsyscall::handle_syscall`].
+//!
+//! Threads synchronize with futexes keyed by the address space and the user
+//! address, and a thread with `CLONE_CHILD_CLEARTID` wakes up its joiner on
+//! exit.
+//!
+//! POSIX signals are delivered by [`handle_signals`], which the kernel calls
+//! before returning to user space. Page faults that cannot be resolved should
+//! be turned into `SIGSEGV` by [`force_fault_signal`].
+//!
+//! Each process has its own file descriptor table, which is used by the file
+//! operations of [`arceos_posix_api`] in its threads.
+//!
+//! The address space of a process is laid out as Linux does: the program
+//! break heap grows by `brk` right after the loaded image, the main stack has
+//! guard pages below it, and mappings can be resized or moved by `mremap`.
+//!
+//! Programs are loaded from ELF files with their segment permissions. Both
+//! static and dynamically linked executables are supported: the dynamic
+//! linker in `PT_INTERP` is loaded along with the program, position-independent
+//! executables are placed at a randomized base, and the initial stack carries
+//! the auxiliary vector that `ld.so` and libc expect.
+
+#![no_std]
+
+#[macro_use]
+extern crate log;
+extern crate alloc;
+
+mod api;
+mod futex;
+mod loader;
+mod mm;
+mod process;
+mod signal;
+mod syscall;
+mod task;
+mod uaccess;
+
+pub use self::api::{
+    check_group_exit, spawn_init, sys_clone, sys_execve, sys_exit, sys_exit_group, sys_getpid,
+    sys_getppid, sys_gettid, sys_set_tid_address, sys_wait4, CloneFlags,
+};
+pub use self::futex::{sys_futex, sys_set_robust_list};
+pub use self::mm::{sys_brk, sys_mmap, sys_mprotect, sys_mremap, sys_msync, sys_munmap};
+pub use self::process::{find_process, Pid, Process};
+pub use self::signal::{
+    force_fault_signal, handle_signals, sys_kill, sys_rt_sigaction, sys_rt_sigpending,
+    sys_rt_sigprocmask, sys_rt_sigreturn, sys_tgkill, sys_tkill, SigAction, SigSet, Signo, SIGBUS,
+    SIGFPE, SIGILL, SIGKILL, SIGRTMIN, SIGSEGV, SIGTRAP,
+};
+pub use self::task::{current_process, TaskExt};
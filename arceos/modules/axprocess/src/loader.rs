@@ -0,0 +1,510 @@
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ffi::CStr;
+use core::mem::size_of;
+
+use axerrno::{LinuxError, LinuxResult};
+use axfs::fops::{File, OpenOptions};
+use axhal::arch::UspaceContext;
+use axhal::mem::PAGE_SIZE_4K;
+use axhal::paging::MappingFlags;
+use axmm::{AddrSpace, VmaKind};
+use memory_addr::{MemoryAddr, VirtAddr};
+
+use elf::abi::{
+    DT_NULL, DT_RELA, DT_RELASZ, ET_DYN, ET_EXEC, PF_R, PF_W, PF_X, PT_DYNAMIC, PT_INTERP, PT_LOAD,
+    PT_PHDR,
+};
+use elf::dynamic::DynamicTable;
+use elf::endian::AnyEndian;
+use elf::file::{Class, FileHeader};
+use elf::parse::ParseAt;
+use elf::relocation::RelaIterator;
+use elf::segment::{ProgramHeader, SegmentTable};
+use elf::ElfBytes;
+
+use crate::signal::map_trampoline;
+
+/// Size of the user stack of the main thread.
+const USER_STACK_SIZE: usize = 0x10000;
+/// Size of the inaccessible guard pages below the user stack.
+const USER_STACK_GUARD_SIZE: usize = 0x1000;
+
+/// Lowest load address of position-independent executables.
+const PIE_BASE: usize = 0x10_0000_0000;
+/// Lowest load address of the dynamic linker.
+const INTERP_BASE: usize = 0x20_0000_0000;
+/// Number of pages that the load addresses are randomized within.
+const ASLR_PAGES: usize = 0x1_0000; // 256 MiB
+
+/// Size of the 64-bit ELF header.
+const EHDR_SIZE: usize = 64;
+/// Size of a 64-bit `Elf64_Rela` entry.
+const RELA_SIZE: usize = 24;
+/// Most bytes of program headers accepted, as Linux does.
+const MAX_PHDRS_SIZE: usize = 0x1_0000;
+/// Longest path of the dynamic linker accepted, including the trailing NUL.
+const PATH_MAX: usize = 4096;
+/// Size of the buffer that the segments are copied through.
+const LOAD_CHUNK_SIZE: usize = 0x4000;
+
+/// The relocation type that adds the load base to the addend.
+#[cfg(target_arch = "x86_64")]
+const R_RELATIVE: u32 = elf::abi::R_X86_64_RELATIVE;
+#[cfg(any(target_arch = "riscv32", target_arch = "riscv64"))]
+const R_RELATIVE: u32 = elf::abi::R_RISCV_RELATIVE;
+#[cfg(target_arch = "aarch64")]
+const R_RELATIVE: u32 = elf::abi::R_AARCH64_RELATIVE;
+
+/// Frequency of `times()` reported to the user, as Linux does.
+const CLOCKS_PER_SEC: usize = 100;
+
+// Auxiliary vector entry types, see `<elf.h>`.
+const AT_NULL: usize = 0;
+const AT_PHDR: usize = 3;
+const AT_PHENT: usize = 4;
+const AT_PHNUM: usize = 5;
+const AT_PAGESZ: usize = 6;
+const AT_BASE: usize = 7;
+const AT_FLAGS: usize = 8;
+const AT_ENTRY: usize = 9;
+const AT_UID: usize = 11;
+const AT_EUID: usize = 12;
+const AT_GID: usize = 13;
+const AT_EGID: usize = 14;
+const AT_HWCAP: usize = 16;
+const AT_CLKTCK: usize = 17;
+const AT_SECURE: usize = 23;
+const AT_RANDOM: usize = 25;
+const AT_EXECFN: usize = 31;
+
+/// Reads exactly `buf.len()` bytes of the file at `offset`. A file shorter
+/// than that is not a valid executable.
+fn read_exact_at(file: &File, offset: u64, buf: &mut [u8]) -> LinuxResult {
+    let mut read = 0;
+    while read < buf.len() {
+        match file.read_at(offset + read as u64, &mut buf[read..])? {
+            0 => return Err(LinuxError::ENOEXEC),
+            n => read += n,
+        }
+    }
+    Ok(())
+}
+
+/// An opened ELF file, ready to be loaded into an address space.
+///
+/// Only the headers are kept in memory, the segments are read from the file
+/// when loaded.
+struct ElfImage {
+    file: File,
+    ehdr: FileHeader<AnyEndian>,
+    /// Whether it is position-independent, i.e., `ET_DYN`.
+    pie: bool,
+    /// Unrelocated address of the program headers in memory, or 0 if they are
+    /// not loaded.
+    phdr: usize,
+    /// Path of the dynamic linker requested by `PT_INTERP`.
+    interp: Option<String>,
+    segments: Vec<ProgramHeader>,
+    /// The `PT_DYNAMIC` segment, if any.
+    dynamic: Option<ProgramHeader>,
+}
+
+impl ElfImage {
+    /// Opens and parses the ELF file at `path`, without touching any address
+    /// space.
+    fn open(path: &str) -> LinuxResult<Self> {
+        let mut opts = OpenOptions::new();
+        opts.read(true);
+        let file = File::open(path, &opts)?;
+        let file_size = file.get_attr()?.size();
+
+        let mut ehdr_buf = [0; EHDR_SIZE];
+        read_exact_at(&file, 0, &mut ehdr_buf)?;
+        let ehdr =
+            ElfBytes::<AnyEndian>::parse_elf_header(&ehdr_buf).map_err(|_| LinuxError::ENOEXEC)?;
+        if ehdr.class != Class::ELF64 {
+            return Err(LinuxError::ENOEXEC);
+        }
+        let pie = match ehdr.e_type {
+            ET_EXEC => false,
+            ET_DYN => true,
+            _ => return Err(LinuxError::ENOEXEC),
+        };
+
+        let phentsize = ProgramHeader::validate_entsize(ehdr.class, ehdr.e_phentsize as usize)
+            .map_err(|_| LinuxError::ENOEXEC)?;
+        let phdrs_size = phentsize * ehdr.e_phnum as usize;
+        if phdrs_size == 0 || phdrs_size > MAX_PHDRS_SIZE {
+            return Err(LinuxError::ENOEXEC);
+        }
+        let mut phdrs_buf = vec![0; phdrs_size];
+        read_exact_at(&file, ehdr.e_phoff, &mut phdrs_buf)?;
+        let phdrs = SegmentTable::new(ehdr.endianness, ehdr.class, &phdrs_buf);
+
+        let mut interp = None;
+        let mut phdr = None;
+        let mut dynamic = None;
+        let mut segments = Vec::new();
+        for ph in phdrs.iter() {
+            let (_, file_end) = ph.get_file_data_range().map_err(|_| LinuxError::ENOEXEC)?;
+            if file_end as u64 > file_size {
+                return Err(LinuxError::ENOEXEC);
+            }
+            match ph.p_type {
+                PT_LOAD => {
+                    if ph.p_filesz > ph.p_memsz {
+                        return Err(LinuxError::ENOEXEC);
+                    }
+                    segments.push(ph);
+                }
+                PT_PHDR => phdr = Some(ph.p_vaddr as usize),
+                PT_DYNAMIC => dynamic = Some(ph),
+                PT_INTERP => {
+                    if ph.p_filesz as usize > PATH_MAX {
+                        return Err(LinuxError::ENOEXEC);
+                    }
+                    let mut buf = vec![0; ph.p_filesz as usize];
+                    read_exact_at(&file, ph.p_offset, &mut buf)?;
+                    let path = CStr::from_bytes_until_nul(&buf).map_err(|_| LinuxError::ENOEXEC)?;
+                    let path = path.to_str().map_err(|_| LinuxError::ENOEXEC)?;
+                    interp = Some(path.to_string());
+                }
+                _ => {}
+            }
+        }
+        if segments.is_empty() {
+            return Err(LinuxError::ENOEXEC);
+        }
+
+        // Without `PT_PHDR`, find the program headers in the loaded segments.
+        let phoff = ehdr.e_phoff;
+        let phdr = phdr.unwrap_or_else(|| {
+            segments
+                .iter()
+                .find(|ph| ph.p_offset <= phoff && phoff < ph.p_offset + ph.p_filesz)
+                .map_or(0, |ph| (ph.p_vaddr + phoff - ph.p_offset) as usize)
+        });
+        Ok(Self {
+            file,
+            ehdr,
+            pie,
+            phdr,
+            interp,
+            segments,
+            dynamic,
+        })
+    }
+
+    fn entry(&self) -> usize {
+        self.ehdr.e_entry as usize
+    }
+
+    /// Returns the address to load the image at, which is randomized above
+    /// `min_base` for position-independent images.
+    fn choose_base(&self, min_base: usize) -> usize {
+        if self.pie {
+            min_base + (axhal::misc::random() as usize % ASLR_PAGES) * PAGE_SIZE_4K
+        } else {
+            0
+        }
+    }
+
+    /// Maps the loadable segments at `base` with their own permissions, and
+    /// copies their contents. The mappings are named after the file at `path`.
+    ///
+    /// Returns the end of the loaded image.
+    fn load(&self, uspace: &mut AddrSpace, base: usize, path: &str) -> LinuxResult<VirtAddr> {
+        let mut mapped_end = VirtAddr::from(0);
+        let mut last_flags = MappingFlags::empty();
+        let mut buf = vec![0; LOAD_CHUNK_SIZE];
+        for ph in &self.segments {
+            debug!(
+                "phdr: offset: {:#X}=>{:#X} size: {:#X}=>{:#X} flags: {:#x}",
+                ph.p_offset,
+                base + ph.p_vaddr as usize,
+                ph.p_filesz,
+                ph.p_memsz,
+                ph.p_flags
+            );
+            let seg_start = VirtAddr::from(base + ph.p_vaddr as usize);
+            let seg_end = (seg_start + ph.p_memsz as usize).align_up_4k();
+            let mut vaddr = seg_start.align_down_4k();
+            let flags = segment_flags(ph.p_flags);
+
+            // Segments that are not page-aligned may share a page with the
+            // previous one, which then needs the permissions of both.
+            if vaddr < mapped_end {
+                last_flags |= flags;
+                uspace.protect(vaddr, PAGE_SIZE_4K, last_flags)?;
+                vaddr = mapped_end;
+            }
+            if vaddr < seg_end {
+                uspace.map_alloc(vaddr, seg_end - vaddr, flags, true)?;
+                uspace.set_kind(vaddr, seg_end - vaddr, VmaKind::File(path.into()));
+                last_flags = flags;
+            }
+            mapped_end = mapped_end.max(seg_end);
+
+            // The rest of the segment (`.bss`) is left zero-filled.
+            let filesz = ph.p_filesz as usize;
+            let mut copied = 0;
+            while copied < filesz {
+                let len = (filesz - copied).min(LOAD_CHUNK_SIZE);
+                read_exact_at(&self.file, ph.p_offset + copied as u64, &mut buf[..len])?;
+                uspace.write(seg_start + copied, &buf[..len])?;
+                copied += len;
+            }
+        }
+        Ok(mapped_end)
+    }
+
+    /// Returns the file offset of the data at `vaddr` of `size` bytes, which
+    /// must be in one of the loadable segments.
+    fn vaddr_to_offset(&self, vaddr: u64, size: u64) -> LinuxResult<u64> {
+        self.segments
+            .iter()
+            .find(|ph| {
+                ph.p_vaddr <= vaddr
+                    && vaddr
+                        .checked_add(size)
+                        .is_some_and(|end| end <= ph.p_vaddr + ph.p_filesz)
+            })
+            .map(|ph| ph.p_offset + vaddr - ph.p_vaddr)
+            .ok_or(LinuxError::ENOEXEC)
+    }
+
+    /// Applies the relative relocations of the image loaded at `base`.
+    ///
+    /// Static PIEs have no dynamic linker to do this. Their start-up code
+    /// usually relocates itself as well, which is harmless as the `RELA`
+    /// relocations set absolute values.
+    fn relocate(&self, uspace: &mut AddrSpace, base: usize) -> LinuxResult {
+        let Some(dynamic) = &self.dynamic else {
+            return Ok(());
+        };
+        let mut buf = vec![0; dynamic.p_filesz as usize];
+        read_exact_at(&self.file, dynamic.p_offset, &mut buf)?;
+        let (mut rela, mut relasz) = (None, 0);
+        for entry in DynamicTable::new(self.ehdr.endianness, self.ehdr.class, &buf).iter() {
+            match entry.d_tag {
+                DT_NULL => break,
+                DT_RELA => rela = Some(entry.d_ptr()),
+                DT_RELASZ => relasz = entry.d_val(),
+                _ => {}
+            }
+        }
+        let Some(rela) = rela else {
+            return Ok(());
+        };
+        let offset = self.vaddr_to_offset(rela, relasz)?;
+
+        let chunk_size = LOAD_CHUNK_SIZE / RELA_SIZE * RELA_SIZE;
+        let mut buf = vec![0; chunk_size];
+        let mut done = 0;
+        while done < relasz {
+            let len = (relasz - done).min(chunk_size as u64) as usize;
+            read_exact_at(&self.file, offset + done, &mut buf[..len])?;
+            let relas = RelaIterator::new(self.ehdr.endianness, self.ehdr.class, &buf[..len]);
+            for rela in relas.filter(|rela| rela.r_type == R_RELATIVE) {
+                let val = base.wrapping_add_signed(rela.r_addend as isize);
+                let vaddr = VirtAddr::from(base + rela.r_offset as usize);
+                uspace.write(vaddr, &val.to_ne_bytes())?;
+            }
+            done += len as u64;
+        }
+        Ok(())
+    }
+}
+
+fn segment_flags(p_flags: u32) -> MappingFlags {
+    let mut flags = MappingFlags::USER;
+    if p_flags & PF_R != 0 {
+        flags |= MappingFlags::READ;
+    }
+    if p_flags & PF_W != 0 {
+        flags |= MappingFlags::WRITE;
+    }
+    if p_flags & PF_X != 0 {
+        flags |= MappingFlags::EXECUTE;
+    }
+    flags
+}
+
+/// A user program and its dynamic linker, opened but not loaded yet.
+pub(crate) struct Program {
+    path: String,
+    exe: ElfImage,
+    interp: Option<ElfImage>,
+}
+
+impl Program {
+    /// Opens and checks the executable at `path`, and the dynamic linker it
+    /// requests if any.
+    ///
+    /// All errors about the file formats are found here, so that the caller
+    /// can fail before destroying the old program, as `execve` does.
+    pub fn open(path: &str) -> LinuxResult<Self> {
+        let exe = ElfImage::open(path)?;
+        let interp = match &exe.interp {
+            Some(interp) => {
+                debug!("{}: dynamic linker {:?}", path, interp);
+                let image = ElfImage::open(interp)?;
+                if image.interp.is_some() {
+                    return Err(LinuxError::ENOEXEC);
+                }
+                Some(image)
+            }
+            None => None,
+        };
+        Ok(Self {
+            path: path.into(),
+            exe,
+            interp,
+        })
+    }
+
+    /// Loads the program and its dynamic linker into `uspace`, and sets up
+    /// the user stack with the arguments, environment variables and the
+    /// auxiliary vector.
+    ///
+    /// Returns the context to enter user space with, which starts from the
+    /// dynamic linker if there is one.
+    pub fn load(
+        &self,
+        uspace: &mut AddrSpace,
+        args: &[String],
+        envs: &[String],
+    ) -> LinuxResult<UspaceContext> {
+        let exe = &self.exe;
+        let base = exe.choose_base(PIE_BASE);
+        let exe_end = exe.load(uspace, base, &self.path)?;
+        // The program break starts right after the image, as Linux does
+        // without randomization.
+        uspace.init_heap(exe_end);
+
+        let (entry, interp_base) = match (&self.interp, &exe.interp) {
+            (Some(interp), Some(interp_path)) => {
+                let interp_base = interp.choose_base(INTERP_BASE);
+                interp.load(uspace, interp_base, interp_path)?;
+                (interp_base + interp.entry(), interp_base)
+            }
+            _ => {
+                if exe.pie {
+                    exe.relocate(uspace, base)?;
+                }
+                (base + exe.entry(), 0)
+            }
+        };
+
+        let auxv = [
+            (AT_PHDR, if exe.phdr != 0 { base + exe.phdr } else { 0 }),
+            (AT_PHENT, exe.ehdr.e_phentsize as usize),
+            (AT_PHNUM, exe.ehdr.e_phnum as usize),
+            (AT_PAGESZ, PAGE_SIZE_4K),
+            (AT_BASE, interp_base),
+            (AT_FLAGS, 0),
+            (AT_ENTRY, base + exe.entry()),
+            (AT_UID, 0),
+            (AT_EUID, 0),
+            (AT_GID, 0),
+            (AT_EGID, 0),
+            (AT_HWCAP, 0),
+            (AT_CLKTCK, CLOCKS_PER_SEC),
+            (AT_SECURE, 0),
+        ];
+        let ustack_top = init_user_stack(uspace, &self.path, args, envs, &auxv)?;
+        map_trampoline(uspace)?;
+        Ok(UspaceContext::new(entry, ustack_top))
+    }
+}
+
+/// Builds the initial user stack image downwards from `top`.
+struct StackBuilder {
+    top: usize,
+    data: Vec<u8>,
+}
+
+impl StackBuilder {
+    fn new(top: usize) -> Self {
+        Self {
+            top,
+            data: Vec::new(),
+        }
+    }
+
+    /// Returns the current stack pointer.
+    fn sp(&self) -> usize {
+        self.top - self.data.len()
+    }
+
+    /// Pushes the bytes, and returns their address.
+    fn push_bytes(&mut self, bytes: &[u8]) -> usize {
+        self.data.splice(0..0, bytes.iter().copied());
+        self.sp()
+    }
+
+    /// Pushes a NUL-terminated string, and returns its address.
+    fn push_str(&mut self, s: &str) -> usize {
+        self.push_bytes(&[0]);
+        self.push_bytes(s.as_bytes())
+    }
+
+    fn push_usize(&mut self, val: usize) {
+        self.push_bytes(&val.to_ne_bytes());
+    }
+
+    fn align_down(&mut self, align: usize) {
+        let pad = self.sp() - self.sp().align_down(align);
+        self.data.splice(0..0, core::iter::repeat(0).take(pad));
+    }
+}
+
+/// Maps the user stack at the end of the address space with guard pages below
+/// it, and pushes the arguments, environment variables and auxiliary vector
+/// onto it, as the System V ABI specifies. Returns the stack pointer, which points to `argc`.
+fn init_user_stack(
+    uspace: &mut AddrSpace,
+    path: &str,
+    args: &[String],
+    envs: &[String],
+    auxv: &[(usize, usize)],
+) -> LinuxResult<VirtAddr> {
+    let ustack_top = uspace.end();
+    let ustack_vaddr = ustack_top - USER_STACK_SIZE;
+    debug!("Mapping user stack: {:#x?} -> {:#x?}", ustack_vaddr, ustack_top);
+    uspace.map_stack(ustack_top, USER_STACK_SIZE, USER_STACK_GUARD_SIZE, true)?;
+
+    let mut stack = StackBuilder::new(ustack_top.as_usize());
+    let execfn = stack.push_str(path);
+    let random = stack.push_bytes(&axhal::misc::random().to_ne_bytes());
+    let envp: Vec<usize> = envs.iter().map(|env| stack.push_str(env)).collect();
+    let argv: Vec<usize> = args.iter().map(|arg| stack.push_str(arg)).collect();
+
+    // `argc`, `argv`, `envp` and `auxv`, each array ends with a NULL entry.
+    let words = 1 + (argv.len() + 1) + (envp.len() + 1) + (auxv.len() + 3) * 2;
+    stack.align_down(16);
+    if (words * size_of::<usize>()) % 16 != 0 {
+        stack.push_usize(0);
+    }
+    for &(key, val) in [(AT_NULL, 0), (AT_EXECFN, execfn), (AT_RANDOM, random)]
+        .iter()
+        .chain(auxv.iter().rev())
+    {
+        stack.push_usize(val);
+        stack.push_usize(key);
+    }
+    stack.push_usize(0);
+    envp.iter().rev().for_each(|&ptr| stack.push_usize(ptr));
+    stack.push_usize(0);
+    argv.iter().rev().for_each(|&ptr| stack.push_usize(ptr));
+    stack.push_usize(argv.len());
+
+    if stack.data.len() > USER_STACK_SIZE / 2 {
+        return Err(LinuxError::E2BIG);
+    }
+    let sp = VirtAddr::from(stack.sp());
+    uspace.write(sp, &stack.data)?;
+    Ok(sp)
+}
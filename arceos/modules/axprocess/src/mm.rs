@@ -0,0 +1,208 @@
+use alloc::sync::Arc;
+use core::ffi::c_int;
+
+use axerrno::{LinuxError, LinuxResult};
+use axhal::paging::MappingFlags;
+use memory_addr::{MemoryAddr, VirtAddr, VirtAddrRange};
+
+use crate::task::current_process;
+
+/// Lowest address of the mappings placed by the kernel, i.e., without
+/// `MAP_FIXED`.
+const USER_MMAP_BASE: usize = 0x30_0000_0000;
+
+bitflags::bitflags! {
+    /// Permissions for `mmap` and `mprotect`.
+    ///
+    /// See <https://github.com/bminor/glibc/blob/master/bits/mman.h>
+    #[derive(Clone, Copy, Debug)]
+    struct MmapProt: i32 {
+        /// Page can be read.
+        const PROT_READ = 1 << 0;
+        /// Page can be written.
+        const PROT_WRITE = 1 << 1;
+        /// Page can be executed.
+        const PROT_EXEC = 1 << 2;
+    }
+}
+
+impl From<MmapProt> for MappingFlags {
+    fn from(value: MmapProt) -> Self {
+        let mut flags = MappingFlags::USER;
+        if value.contains(MmapProt::PROT_READ) {
+            flags |= MappingFlags::READ;
+        }
+        if value.contains(MmapProt::PROT_WRITE) {
+            flags |= MappingFlags::WRITE;
+        }
+        if value.contains(MmapProt::PROT_EXEC) {
+            flags |= MappingFlags::EXECUTE;
+        }
+        flags
+    }
+}
+
+bitflags::bitflags! {
+    /// Flags for `mmap`.
+    ///
+    /// See <https://github.com/bminor/glibc/blob/master/bits/mman.h>
+    #[derive(Clone, Copy, Debug)]
+    struct MmapFlags: i32 {
+        /// Share changes.
+        const MAP_SHARED = 1 << 0;
+        /// Changes private; copy pages on write.
+        const MAP_PRIVATE = 1 << 1;
+        /// Map address must be exactly as requested, no matter whether it is available.
+        const MAP_FIXED = 1 << 4;
+        /// Don't use a file.
+        const MAP_ANONYMOUS = 1 << 5;
+        /// Don't check for reservations.
+        const MAP_NORESERVE = 1 << 14;
+        /// Allocation is for a stack.
+        const MAP_STACK = 0x20000;
+    }
+}
+
+/// Flag of `mremap`: the mapping may be moved if it cannot be resized in
+/// place.
+const MREMAP_MAYMOVE: c_int = 1;
+/// Flag of `mremap`: the mapping is moved to the given new address, whose old
+/// mappings are removed.
+const MREMAP_FIXED: c_int = 2;
+
+/// Maps files or anonymous memory into the address space of the current
+/// process. Returns the start address of the mapping.
+///
+/// Anonymous memory is allocated and file contents are loaded on page faults.
+pub fn sys_mmap(
+    addr: usize,
+    length: usize,
+    prot: c_int,
+    flags: c_int,
+    fd: c_int,
+    offset: isize,
+) -> LinuxResult<isize> {
+    let addr = VirtAddr::from(addr);
+    if !addr.is_aligned_4k() || length == 0 {
+        return Err(LinuxError::EINVAL);
+    }
+    if offset < 0 || !(offset as usize).is_aligned_4k() {
+        return Err(LinuxError::EINVAL);
+    }
+    let prot = MmapProt::from_bits(prot).ok_or(LinuxError::EINVAL)?;
+    let flags = MmapFlags::from_bits_truncate(flags);
+    let shared = flags.contains(MmapFlags::MAP_SHARED);
+    if shared == flags.contains(MmapFlags::MAP_PRIVATE) {
+        return Err(LinuxError::EINVAL);
+    }
+    let length = length.align_up_4k();
+
+    let process = current_process();
+    let mut uspace = process.aspace().lock();
+    let addr = if flags.contains(MmapFlags::MAP_FIXED) {
+        uspace.unmap(addr, length)?;
+        addr
+    } else {
+        let hint = addr.max(VirtAddr::from(USER_MMAP_BASE));
+        uspace
+            .find_free_area(hint, length, VirtAddrRange::new(uspace.base(), uspace.end()))
+            .ok_or(LinuxError::ENOMEM)?
+    };
+
+    if flags.contains(MmapFlags::MAP_ANONYMOUS) {
+        uspace.map_alloc(addr, length, prot.into(), false)?;
+    } else {
+        let file = Arc::new(arceos_posix_api::get_fs_file(fd)?);
+        uspace.map_file(addr, length, prot.into(), file, offset as usize, shared)?;
+    }
+    Ok(addr.as_usize() as isize)
+}
+
+/// Removes the mappings in the range. Shared file mappings are written back to
+/// the files.
+pub fn sys_munmap(addr: usize, length: usize) -> LinuxResult<isize> {
+    let addr = VirtAddr::from(addr);
+    if !addr.is_aligned_4k() || length == 0 {
+        return Err(LinuxError::EINVAL);
+    }
+    current_process()
+        .aspace()
+        .lock()
+        .unmap(addr, length.align_up_4k())?;
+    Ok(0)
+}
+
+/// Changes the permissions of the mappings in the range.
+pub fn sys_mprotect(addr: usize, length: usize, prot: c_int) -> LinuxResult<isize> {
+    let addr = VirtAddr::from(addr);
+    if !addr.is_aligned_4k() {
+        return Err(LinuxError::EINVAL);
+    }
+    let prot = MmapProt::from_bits(prot).ok_or(LinuxError::EINVAL)?;
+    current_process()
+        .aspace()
+        .lock()
+        .protect(addr, length.align_up_4k(), prot.into())?;
+    Ok(0)
+}
+
+/// Writes the modified pages of the shared file mappings in the range back to
+/// the files.
+pub fn sys_msync(addr: usize, length: usize, _flags: c_int) -> LinuxResult<isize> {
+    let addr = VirtAddr::from(addr);
+    if !addr.is_aligned_4k() {
+        return Err(LinuxError::EINVAL);
+    }
+    current_process()
+        .aspace()
+        .lock()
+        .msync(addr, length.align_up_4k())?;
+    Ok(0)
+}
+
+/// Changes the program break of the current process. Returns the new program
+/// break, or the current one on failure, as the raw Linux syscall does.
+pub fn sys_brk(addr: usize) -> LinuxResult<isize> {
+    let brk = current_process()
+        .aspace()
+        .lock()
+        .brk(VirtAddr::from(addr));
+    Ok(brk.as_usize() as isize)
+}
+
+/// Resizes the mapping at `old_addr`, and moves it if allowed by `flags`.
+/// Returns the new address of the mapping.
+pub fn sys_mremap(
+    old_addr: usize,
+    old_size: usize,
+    new_size: usize,
+    flags: c_int,
+    new_addr: usize,
+) -> LinuxResult<isize> {
+    let old_addr = VirtAddr::from(old_addr);
+    if !old_addr.is_aligned_4k() || old_size == 0 || new_size == 0 {
+        return Err(LinuxError::EINVAL);
+    }
+    if flags & !(MREMAP_MAYMOVE | MREMAP_FIXED) != 0
+        || (flags & MREMAP_FIXED != 0 && flags & MREMAP_MAYMOVE == 0)
+    {
+        return Err(LinuxError::EINVAL);
+    }
+    let new_addr = if flags & MREMAP_FIXED != 0 {
+        let new_addr = VirtAddr::from(new_addr);
+        if !new_addr.is_aligned_4k() {
+            return Err(LinuxError::EINVAL);
+        }
+        Some(new_addr)
+    } else {
+        None
+    };
+    let addr = current_process().aspace().lock().remap(
+        old_addr,
+        old_size.align_up_4k(),
+        new_size.align_up_4k(),
+        flags & MREMAP_MAYMOVE != 0,
+        new_addr,
+    )?;
+    Ok(addr.as_usize() as isize)
+}
@@ -0,0 +1,337 @@
+use alloc::collections::BTreeMap;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, AtomicI32, AtomicU32, AtomicU64, Ordering};
+
+use arceos_posix_api::FdTable;
+use axerrno::{LinuxError, LinuxResult};
+use axmm::AddrSpace;
+use axsync::Mutex;
+use axtask::WaitQueue;
+use kspin::SpinNoIrq;
+use lazyinit::LazyInit;
+
+use crate::signal::{send_signal, ProcessSignal, SigInfo, Signo, ThreadSignal, SIGCHLD};
+
+/// Process ID, which is also the thread ID of the main thread.
+pub type Pid = u64;
+
+/// All processes that have not been reaped, indexed by their PIDs.
+static PROCESS_TABLE: SpinNoIrq<BTreeMap<Pid, Weak<Process>>> = SpinNoIrq::new(BTreeMap::new());
+
+/// The init process, which adopts the orphaned processes.
+static INIT_PROC: LazyInit<Arc<Process>> = LazyInit::new();
+
+/// A process, i.e., a thread group sharing one address space and one file
+/// descriptor table.
+pub struct Process {
+    pid: Pid,
+    parent: SpinNoIrq<Weak<Process>>,
+    children: SpinNoIrq<Vec<Arc<Process>>>,
+    /// Signal states of the live threads in the thread group, indexed by
+    /// their thread IDs.
+    threads: SpinNoIrq<BTreeMap<Pid, Arc<ThreadSignal>>>,
+    aspace: Arc<Mutex<AddrSpace>>,
+    /// `None` after the process exits, so that the files are closed before
+    /// the process is reaped.
+    fd_table: SpinNoIrq<Option<Arc<FdTable>>>,
+    exit_code: AtomicI32,
+    /// The signal terminated the process, with bit 7 set if it dumped core,
+    /// or 0 if it exited normally.
+    term_signal: AtomicU32,
+    signal: ProcessSignal,
+    /// Set by `exit_group`, the remaining threads exit on their next syscall.
+    group_exiting: AtomicBool,
+    /// The thread in `execve`, for which the other threads exit, or 0.
+    exec_tid: AtomicU64,
+    /// Notified when a thread exits, waited by the thread in `execve`.
+    thread_exit_wq: WaitQueue,
+    zombie: AtomicBool,
+    /// Waited by the parent in `wait4`, notified when a child exits.
+    child_exit_wq: WaitQueue,
+}
+
+impl Process {
+    /// Creates a new process and registers it in the process table.
+    ///
+    /// The new process has no threads yet, `pid` should be the thread ID of
+    /// its first thread.
+    pub(crate) fn new(
+        pid: Pid,
+        parent: Option<&Arc<Process>>,
+        aspace: Arc<Mutex<AddrSpace>>,
+        fd_table: Arc<FdTable>,
+        signal: ProcessSignal,
+    ) -> Arc<Self> {
+        let process = Arc::new(Self {
+            pid,
+            parent: SpinNoIrq::new(parent.map_or(Weak::new(), Arc::downgrade)),
+            children: SpinNoIrq::new(Vec::new()),
+            threads: SpinNoIrq::new(BTreeMap::new()),
+            aspace,
+            fd_table: SpinNoIrq::new(Some(fd_table)),
+            exit_code: AtomicI32::new(0),
+            term_signal: AtomicU32::new(0),
+            signal,
+            group_exiting: AtomicBool::new(false),
+            exec_tid: AtomicU64::new(0),
+            thread_exit_wq: WaitQueue::new(),
+            zombie: AtomicBool::new(false),
+            child_exit_wq: WaitQueue::new(),
+        });
+        if let Some(parent) = parent {
+            parent.children.lock().push(process.clone());
+        }
+        PROCESS_TABLE.lock().insert(pid, Arc::downgrade(&process));
+        process
+    }
+
+    /// Makes the process the init process, which adopts the orphans.
+    pub(crate) fn set_init(self: &Arc<Self>) {
+        INIT_PROC.init_once(self.clone());
+    }
+
+    /// Returns the process ID.
+    pub fn pid(&self) -> Pid {
+        self.pid
+    }
+
+    /// Returns the parent process, or `None` for the init process.
+    pub fn parent(&self) -> Option<Arc<Process>> {
+        self.parent.lock().upgrade()
+    }
+
+    /// Returns the PID of the parent process, or 0 for the init process.
+    pub fn ppid(&self) -> Pid {
+        self.parent().map_or(0, |p| p.pid)
+    }
+
+    /// Returns the virtual memory address space of the process.
+    pub fn aspace(&self) -> &Arc<Mutex<AddrSpace>> {
+        &self.aspace
+    }
+
+    /// Returns the file descriptor table of the process, or `None` if it has
+    /// exited.
+    pub fn fd_table(&self) -> Option<Arc<FdTable>> {
+        self.fd_table.lock().clone()
+    }
+
+    /// Returns the exit code of the process.
+    pub fn exit_code(&self) -> i32 {
+        self.exit_code.load(Ordering::Acquire)
+    }
+
+    /// Returns the status of the exited process reported by `wait4`.
+    pub fn wait_status(&self) -> i32 {
+        match self.term_signal.load(Ordering::Acquire) {
+            0 => (self.exit_code() & 0xff) << 8,
+            sig => sig as i32,
+        }
+    }
+
+    pub(crate) fn signal(&self) -> &ProcessSignal {
+        &self.signal
+    }
+
+    /// Whether the process has exited but not been reaped by its parent.
+    pub fn is_zombie(&self) -> bool {
+        self.zombie.load(Ordering::Acquire)
+    }
+
+    /// Whether the threads of the process should exit, as a thread has called
+    /// `exit_group`.
+    pub fn is_group_exiting(&self) -> bool {
+        self.group_exiting.load(Ordering::Acquire)
+    }
+
+    /// Whether the thread `tid` should exit, as another thread of the process
+    /// is in `execve`.
+    pub fn is_exec_killed(&self, tid: Pid) -> bool {
+        let exec_tid = self.exec_tid.load(Ordering::Acquire);
+        exec_tid != 0 && exec_tid != tid
+    }
+
+    /// Returns the number of live threads in the process.
+    pub fn thread_count(&self) -> usize {
+        self.threads.lock().len()
+    }
+
+    pub(crate) fn add_thread(&self, tid: Pid, signal: Arc<ThreadSignal>) {
+        self.threads.lock().insert(tid, signal);
+    }
+
+    /// Returns the signal states of the thread `tid` in the process.
+    pub(crate) fn find_thread(&self, tid: Pid) -> Option<Arc<ThreadSignal>> {
+        self.threads.lock().get(&tid).cloned()
+    }
+
+    /// Interrupts the interruptible waits of the threads for which `f`
+    /// returns `true`, given their thread IDs and signal states.
+    pub(crate) fn interrupt_threads(&self, f: impl Fn(Pid, &ThreadSignal) -> bool) {
+        for (&tid, thread) in self.threads.lock().iter() {
+            if f(tid, thread) {
+                if let Some(task) = axtask::find_task(tid) {
+                    axtask::interrupt(&task);
+                }
+            }
+        }
+    }
+
+    /// Removes the thread from the thread group. Returns `true` if it was the
+    /// last thread.
+    pub(crate) fn remove_thread(&self, tid: Pid) -> bool {
+        let mut threads = self.threads.lock();
+        threads.remove(&tid);
+        let last = threads.is_empty();
+        drop(threads);
+        self.thread_exit_wq.notify_all(false);
+        last
+    }
+
+    /// Makes the other threads exit, and waits for them, before the thread
+    /// `tid` replaces the program in `execve`.
+    ///
+    /// Returns [`LinuxError::EINTR`] if another thread is in `execve` or the
+    /// process is exiting, then the thread `tid` should exit on its way back
+    /// to user space too.
+    pub(crate) fn kill_other_threads(&self, tid: Pid) -> LinuxResult {
+        if self
+            .exec_tid
+            .compare_exchange(0, tid, Ordering::AcqRel, Ordering::Acquire)
+            .is_err()
+        {
+            return Err(LinuxError::EINTR);
+        }
+        self.interrupt_threads(|thread_id, _| thread_id != tid);
+        self.thread_exit_wq
+            .wait_until(|| self.thread_count() == 1 || self.is_group_exiting());
+        self.exec_tid.store(0, Ordering::Release);
+        if self.is_group_exiting() {
+            return Err(LinuxError::EINTR);
+        }
+        Ok(())
+    }
+
+    /// Asks all threads to exit with the given exit code.
+    ///
+    /// The threads blocked in interruptible waits are interrupted, to exit on
+    /// their way back to user space.
+    pub(crate) fn group_exit(&self, exit_code: i32) {
+        if !self.group_exiting.swap(true, Ordering::AcqRel) {
+            self.exit_code.store(exit_code, Ordering::Release);
+            self.interrupt_threads(|_, _| true);
+        }
+    }
+
+    /// Asks all threads to exit, as the process is terminated by the signal.
+    pub(crate) fn group_exit_by_signal(&self, signo: Signo, core_dumped: bool) {
+        if !self.group_exiting.swap(true, Ordering::AcqRel) {
+            let core_flag = if core_dumped { 0x80 } else { 0 };
+            self.term_signal.store(signo | core_flag, Ordering::Release);
+            self.exit_code.store(128 + signo as i32, Ordering::Release);
+            self.interrupt_threads(|_, _| true);
+        }
+    }
+
+    /// Turns the process into a zombie after its last thread exits.
+    ///
+    /// The memory and files of the process are released at once. The children
+    /// are adopted by the init process, and the parent is notified.
+    pub(crate) fn exit(&self, exit_code: i32) {
+        if !self.is_group_exiting() {
+            self.exit_code.store(exit_code, Ordering::Release);
+        }
+        // The address space may still be used by the processes created with
+        // `CLONE_VM`.
+        if Arc::strong_count(&self.aspace) == 1 {
+            self.aspace.lock().clear();
+        }
+        self.fd_table.lock().take();
+
+        let children = core::mem::take(&mut *self.children.lock());
+        match INIT_PROC.get().filter(|init| init.pid != self.pid) {
+            Some(init) => {
+                for child in children.iter() {
+                    *child.parent.lock() = Arc::downgrade(init);
+                }
+                init.children.lock().extend(children);
+                init.child_exit_wq.notify_all(false);
+            }
+            None => {
+                for child in children {
+                    *child.parent.lock() = Weak::new();
+                }
+            }
+        }
+
+        self.zombie.store(true, Ordering::Release);
+        match self.parent() {
+            Some(parent) => {
+                // `SIGCHLD` is a standard signal, which is never refused.
+                let _ = send_signal(
+                    &parent,
+                    None,
+                    SigInfo::child_exit(self.pid, self.wait_status()),
+                );
+                parent.child_exit_wq.notify_all(false);
+            }
+            None => self.reap(), // Nobody would wait for it.
+        }
+    }
+
+    /// Removes the process from the process table.
+    fn reap(&self) {
+        PROCESS_TABLE.lock().remove(&self.pid);
+    }
+
+    /// Waits for a child process to exit, and reaps it. Returns the PID and
+    /// the wait status of the child.
+    ///
+    /// If `pid` is `None`, it waits for any child. If `nohang` is `true`, it
+    /// returns `Ok(None)` instead of blocking if no child has exited yet.
+    ///
+    /// Returns [`LinuxError::ECHILD`] if there is no such child, or
+    /// [`LinuxError::EINTR`] if the wait is interrupted.
+    pub fn wait_child(&self, pid: Option<Pid>, nohang: bool) -> LinuxResult<Option<(Pid, i32)>> {
+        let matches = |child: &Arc<Process>| pid.map_or(true, |pid| child.pid == pid);
+        loop {
+            {
+                let mut children = self.children.lock();
+                if !children.iter().any(matches) {
+                    return Err(LinuxError::ECHILD);
+                }
+                if let Some(idx) = children.iter().position(|c| matches(c) && c.is_zombie()) {
+                    let child = children.remove(idx);
+                    child.reap();
+                    return Ok(Some((child.pid, child.wait_status())));
+                }
+            }
+            if nohang {
+                return Ok(None);
+            }
+            self.child_exit_wq
+                .wait_until_interruptible(|| {
+                    let children = self.children.lock();
+                    !children.iter().any(matches)
+                        || children.iter().any(|c| matches(c) && c.is_zombie())
+                })
+                .map_err(|_| LinuxError::EINTR)?;
+        }
+    }
+}
+
+/// Finds the process with the given PID, including the zombies that have not
+/// been reaped yet.
+pub fn find_process(pid: Pid) -> Option<Arc<Process>> {
+    PROCESS_TABLE.lock().get(&pid).and_then(Weak::upgrade)
+}
+
+/// Returns all processes, including the zombies that have not been reaped yet.
+pub(crate) fn all_processes() -> Vec<Arc<Process>> {
+    PROCESS_TABLE
+        .lock()
+        .values()
+        .filter_map(Weak::upgrade)
+        .collect()
+}
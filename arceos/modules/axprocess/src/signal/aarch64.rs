@@ -0,0 +1,131 @@
+//! Signal frames of aarch64, laid out as in Linux.
+
+use core::mem::{offset_of, size_of};
+
+use axhal::arch::TrapFrame;
+use memory_addr::MemoryAddr;
+
+use super::{SigInfo, SigSet, SignalStack};
+
+/// `mov x8, #139` (`rt_sigreturn`) and `svc #0`.
+pub(super) const SIGRETURN_CODE: [u8; 8] = [0x68, 0x11, 0x80, 0xd2, 0x01, 0x00, 0x00, 0xd4];
+
+/// The `PSTATE` bits that user space may change: the condition flags `NZCV`.
+const USER_PSTATE: u64 = 0xf000_0000;
+
+/// The Linux `sigcontext`.
+#[repr(C, align(16))]
+struct MContext {
+    fault_address: u64,
+    regs: [u64; 31],
+    sp: u64,
+    pc: u64,
+    pstate: u64,
+    /// Records of the FP states and others. The FP states are not saved by
+    /// the kernel yet, it is left zero, as the terminating record.
+    _reserved: [u8; 4096],
+}
+
+impl Default for MContext {
+    fn default() -> Self {
+        Self {
+            fault_address: 0,
+            regs: [0; 31],
+            sp: 0,
+            pc: 0,
+            pstate: 0,
+            _reserved: [0; 4096],
+        }
+    }
+}
+
+/// The Linux `ucontext_t`.
+#[repr(C)]
+#[derive(Default)]
+struct UContext {
+    flags: usize,
+    link: usize,
+    stack: SignalStack,
+    /// The blocked signals before the handler runs.
+    sigmask: SigSet,
+    /// Reserved for a larger `sigset_t`.
+    _unused: [u64; 15],
+    mcontext: MContext,
+}
+
+/// Pushed onto the user stack when a signal handler is called.
+#[repr(C)]
+#[derive(Default)]
+pub(super) struct SignalFrame {
+    info: SigInfo,
+    ucontext: UContext,
+}
+
+impl SignalFrame {
+    /// Saves the user context in `tf` and the blocked signals.
+    pub(super) fn new(info: &SigInfo, tf: &TrapFrame, sigmask: SigSet, _restorer: usize) -> Self {
+        Self {
+            info: *info,
+            ucontext: UContext {
+                sigmask,
+                mcontext: MContext {
+                    regs: tf.r,
+                    sp: tf.usp,
+                    pc: tf.elr,
+                    pstate: tf.spsr,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Returns where the frame is pushed, below the user stack pointer `sp`.
+    pub(super) fn addr_below(sp: usize) -> usize {
+        (sp - size_of::<Self>()).align_down(16)
+    }
+
+    /// Returns where the frame is when the handler returns to call
+    /// `rt_sigreturn`.
+    pub(super) fn addr_on_return(tf: &TrapFrame) -> usize {
+        tf.usp as usize
+    }
+
+    /// Changes the trap frame to call the handler as
+    /// `handler(signo, &info, &ucontext)` with the frame at `addr`, which
+    /// returns to `restorer`.
+    pub(super) fn enter_handler(
+        &self,
+        tf: &mut TrapFrame,
+        addr: usize,
+        handler: usize,
+        restorer: usize,
+    ) {
+        tf.elr = handler as u64;
+        tf.usp = addr as u64;
+        tf.r[30] = restorer as u64;
+        tf.r[0] = self.info.signo() as u64;
+        tf.r[1] = (addr + offset_of!(Self, info)) as u64;
+        tf.r[2] = (addr + offset_of!(Self, ucontext)) as u64;
+    }
+
+    /// Returns the blocked signals saved in the frame.
+    pub(super) fn sigmask(&self) -> SigSet {
+        self.ucontext.sigmask
+    }
+
+    /// Returns the saved return value register.
+    pub(super) fn retval(&self) -> usize {
+        self.ucontext.mcontext.regs[0] as usize
+    }
+
+    /// Restores the registers that user space may change. The privileged
+    /// states, such as the exception level, stay as they are.
+    pub(super) fn restore(&self, tf: &mut TrapFrame) {
+        let ctx = &self.ucontext.mcontext;
+        tf.r = ctx.regs;
+        tf.usp = ctx.sp;
+        tf.elr = ctx.pc;
+        tf.spsr = (tf.spsr & !USER_PSTATE) | (ctx.pstate & USER_PSTATE);
+    }
+}
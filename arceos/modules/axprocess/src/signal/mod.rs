@@ -0,0 +1,822 @@
+//! POSIX signals of user threads, see `signal(7)`.
+//!
+//! The signal actions, and the signals sent to the whole process, are shared
+//! by the threads of a process. Each thread has its own blocked mask, and the
+//! signals sent to the thread itself.
+//!
+//! Pending signals are delivered by [`handle_signals`] just before the thread
+//! returns to user space. For a signal with a handler, a signal frame with
+//! the interrupted user context is built on the user stack, and the handler
+//! returns to a trampoline page calling `rt_sigreturn`, which is mapped into
+//! every address space by the loader.
+//!
+//! The signal frames are laid out as in Linux for each architecture. The
+//! handlers set with `SA_RESTORER` return to their restorers instead, as the
+//! C libraries of x86_64 and aarch64 do.
+//!
+//! A signal interrupts the interruptible waits of the threads that can take
+//! it, such as futex waits, which fail with `EINTR`. Real-time signals are
+//! queued, while a standard signal is pending at most once. Alternate signal
+//! stacks are not supported.
+
+#[cfg(target_arch = "aarch64")]
+#[path = "aarch64.rs"]
+mod arch;
+#[cfg(target_arch = "riscv64")]
+#[path = "riscv.rs"]
+mod arch;
+#[cfg(target_arch = "x86_64")]
+#[path = "x86_64.rs"]
+mod arch;
+
+use alloc::boxed::Box;
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::{vec, vec::Vec};
+use core::ffi::c_int;
+use core::mem::size_of;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use axerrno::{LinuxError, LinuxResult};
+use axhal::arch::TrapFrame;
+use axhal::mem::PAGE_SIZE_4K;
+use axhal::paging::MappingFlags;
+use axmm::AddrSpace;
+use axtask::{TaskExtRef, WaitQueue};
+use kspin::SpinNoIrq;
+use memory_addr::VirtAddr;
+
+use self::arch::SignalFrame;
+use crate::api::{check_group_exit, sys_exit};
+use crate::process::{all_processes, find_process, Pid, Process};
+use crate::task::current_process;
+use crate::uaccess::{read_user_pod, write_user_pod};
+
+/// Signal number, starting from 1.
+pub type Signo = u32;
+
+/// Number of signals, including the real-time ones.
+const NSIG: usize = 64;
+
+// Signal numbers, see `<signal.h>`.
+pub const SIGHUP: Signo = 1;
+pub const SIGINT: Signo = 2;
+pub const SIGQUIT: Signo = 3;
+pub const SIGILL: Signo = 4;
+pub const SIGTRAP: Signo = 5;
+pub const SIGABRT: Signo = 6;
+pub const SIGBUS: Signo = 7;
+pub const SIGFPE: Signo = 8;
+pub const SIGKILL: Signo = 9;
+pub const SIGUSR1: Signo = 10;
+pub const SIGSEGV: Signo = 11;
+pub const SIGUSR2: Signo = 12;
+pub const SIGPIPE: Signo = 13;
+pub const SIGALRM: Signo = 14;
+pub const SIGTERM: Signo = 15;
+pub const SIGSTKFLT: Signo = 16;
+pub const SIGCHLD: Signo = 17;
+pub const SIGCONT: Signo = 18;
+pub const SIGSTOP: Signo = 19;
+pub const SIGTSTP: Signo = 20;
+pub const SIGTTIN: Signo = 21;
+pub const SIGTTOU: Signo = 22;
+pub const SIGURG: Signo = 23;
+pub const SIGXCPU: Signo = 24;
+pub const SIGXFSZ: Signo = 25;
+pub const SIGVTALRM: Signo = 26;
+pub const SIGPROF: Signo = 27;
+pub const SIGWINCH: Signo = 28;
+pub const SIGIO: Signo = 29;
+pub const SIGPWR: Signo = 30;
+pub const SIGSYS: Signo = 31;
+
+/// The first real-time signal, which is queued if sent repeatedly.
+pub const SIGRTMIN: Signo = 32;
+
+/// The most signals pending in a process or a thread, beyond which sending
+/// real-time signals fails with `EAGAIN`.
+const MAX_PENDING_SIGNALS: usize = 1024;
+
+const SIG_DFL: usize = 0;
+const SIG_IGN: usize = 1;
+
+// `sa_flags` of `struct sigaction`.
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+const SA_RESTORER: usize = 0x0400_0000;
+const SA_NODEFER: usize = 0x4000_0000;
+const SA_RESETHAND: usize = 0x8000_0000;
+
+// `how` of `rt_sigprocmask`.
+const SIG_BLOCK: c_int = 0;
+const SIG_UNBLOCK: c_int = 1;
+const SIG_SETMASK: c_int = 2;
+
+// `si_code` of `siginfo_t`.
+const SI_USER: i32 = 0;
+const SI_TKILL: i32 = -6;
+const SEGV_MAPERR: i32 = 1;
+const CLD_EXITED: i32 = 1;
+const CLD_KILLED: i32 = 2;
+const CLD_DUMPED: i32 = 3;
+
+/// Address of the page where signal handlers return to, unless they have
+/// restorers.
+const SIGNAL_TRAMPOLINE: usize = 0x3f_ff00_0000;
+
+/// A set of signals, as the kernel `sigset_t`.
+#[repr(transparent)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SigSet(u64);
+
+impl SigSet {
+    /// `SIGKILL` and `SIGSTOP` cannot be blocked, caught or ignored.
+    const UNBLOCKABLE: Self = Self((1 << (SIGKILL - 1)) | (1 << (SIGSTOP - 1)));
+
+    const fn contains(&self, signo: Signo) -> bool {
+        self.0 & (1 << (signo - 1)) != 0
+    }
+
+    fn add(&mut self, signo: Signo) {
+        self.0 |= 1 << (signo - 1);
+    }
+
+    fn remove(&mut self, signo: Signo) {
+        self.0 &= !(1 << (signo - 1));
+    }
+}
+
+/// The default action of a signal when it has no handler.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum DefaultAction {
+    Terminate,
+    CoreDump,
+    Ignore,
+    Stop,
+    Continue,
+}
+
+fn default_action(signo: Signo) -> DefaultAction {
+    match signo {
+        SIGQUIT | SIGILL | SIGTRAP | SIGABRT | SIGBUS | SIGFPE | SIGSEGV | SIGXCPU | SIGXFSZ
+        | SIGSYS => DefaultAction::CoreDump,
+        SIGCHLD | SIGURG | SIGWINCH => DefaultAction::Ignore,
+        SIGSTOP | SIGTSTP | SIGTTIN | SIGTTOU => DefaultAction::Stop,
+        SIGCONT => DefaultAction::Continue,
+        _ => DefaultAction::Terminate,
+    }
+}
+
+/// The kernel `struct sigaction`.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SigAction {
+    handler: usize,
+    flags: usize,
+    /// Only the kernel `struct sigaction` of some architectures has it.
+    #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+    restorer: usize,
+    mask: SigSet,
+}
+
+impl SigAction {
+    /// Whether the signal is discarded when delivered with this action.
+    fn ignores(&self, signo: Signo) -> bool {
+        match self.handler {
+            SIG_IGN => true,
+            SIG_DFL => matches!(
+                default_action(signo),
+                DefaultAction::Ignore | DefaultAction::Continue
+            ),
+            _ => false,
+        }
+    }
+
+    /// Where the handler returns to.
+    fn restorer(&self) -> usize {
+        #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+        if self.flags & SA_RESTORER != 0 {
+            return self.restorer;
+        }
+        SIGNAL_TRAMPOLINE
+    }
+}
+
+/// The kernel `siginfo_t`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct SigInfo {
+    signo: i32,
+    errno: i32,
+    code: i32,
+    _pad: i32,
+    fields: [usize; 14],
+}
+
+impl SigInfo {
+    fn new(signo: Signo, code: i32) -> Self {
+        Self {
+            signo: signo as _,
+            errno: 0,
+            code,
+            _pad: 0,
+            fields: [0; 14],
+        }
+    }
+
+    /// Sent by `kill` or `tkill` from the process `pid`, which runs as root.
+    fn user(signo: Signo, code: i32, pid: Pid) -> Self {
+        let mut info = Self::new(signo, code);
+        info.fields[0] = pid as u32 as usize;
+        info
+    }
+
+    /// Raised by a fault at `addr`.
+    fn fault(signo: Signo, code: i32, addr: usize) -> Self {
+        let mut info = Self::new(signo, code);
+        info.fields[0] = addr;
+        info
+    }
+
+    /// `SIGCHLD` for the child `pid` that has exited with the wait status.
+    pub(crate) fn child_exit(pid: Pid, wait_status: i32) -> Self {
+        let (code, status) = match wait_status & 0x7f {
+            0 => (CLD_EXITED, (wait_status >> 8) & 0xff),
+            sig if wait_status & 0x80 != 0 => (CLD_DUMPED, sig),
+            sig => (CLD_KILLED, sig),
+        };
+        let mut info = Self::user(SIGCHLD, code, pid);
+        info.fields[1] = status as u32 as usize;
+        info
+    }
+
+    fn signo(&self) -> Signo {
+        self.signo as _
+    }
+}
+
+/// Signals sent but not delivered yet, in the order they are sent for each
+/// signal number.
+#[derive(Default)]
+struct PendingSignals {
+    queues: BTreeMap<Signo, VecDeque<SigInfo>>,
+    len: usize,
+}
+
+impl PendingSignals {
+    /// Adds a signal, unless a standard signal of the same number is already
+    /// pending. Returns `false` if it cannot be queued as too many signals
+    /// are pending.
+    fn push(&mut self, info: SigInfo) -> bool {
+        let signo = info.signo();
+        if signo < SIGRTMIN {
+            if self.queues.contains_key(&signo) {
+                return true;
+            }
+        } else if self.len >= MAX_PENDING_SIGNALS {
+            return false;
+        }
+        self.queues.entry(signo).or_default().push_back(info);
+        self.len += 1;
+        true
+    }
+
+    /// Takes the first signal of the lowest number not in `blocked`.
+    fn pop(&mut self, blocked: SigSet) -> Option<SigInfo> {
+        let (&signo, queue) = self
+            .queues
+            .iter_mut()
+            .find(|(&signo, _)| !blocked.contains(signo))?;
+        let info = queue.pop_front();
+        if queue.is_empty() {
+            self.queues.remove(&signo);
+        }
+        self.len -= 1;
+        info
+    }
+
+    /// Discards all pending signals of the number.
+    fn remove(&mut self, signo: Signo) {
+        if let Some(queue) = self.queues.remove(&signo) {
+            self.len -= queue.len();
+        }
+    }
+
+    /// Returns the set of the pending signal numbers.
+    fn signals(&self) -> SigSet {
+        let mut set = SigSet::default();
+        for &signo in self.queues.keys() {
+            set.add(signo);
+        }
+        set
+    }
+}
+
+/// Signal states shared by the threads of a process.
+pub(crate) struct ProcessSignal {
+    actions: SpinNoIrq<[SigAction; NSIG]>,
+    /// Signals sent to the process, delivered to any thread not blocking them.
+    pending: SpinNoIrq<PendingSignals>,
+    /// Set by a stop signal, and cleared by `SIGCONT` or `SIGKILL`.
+    stopped: AtomicBool,
+    /// Stopped threads wait here to continue.
+    cont_wq: WaitQueue,
+}
+
+impl ProcessSignal {
+    pub(crate) fn new() -> Self {
+        Self::with_actions([SigAction::default(); NSIG])
+    }
+
+    fn with_actions(actions: [SigAction; NSIG]) -> Self {
+        Self {
+            actions: SpinNoIrq::new(actions),
+            pending: SpinNoIrq::new(PendingSignals::default()),
+            stopped: AtomicBool::new(false),
+            cont_wq: WaitQueue::new(),
+        }
+    }
+
+    /// Creates the signal states of a child process, which inherits the
+    /// signal actions.
+    pub(crate) fn fork(&self) -> Self {
+        Self::with_actions(*self.actions.lock())
+    }
+
+    /// Resets the handled signals to the default action on `execve`, as the
+    /// handlers are gone with the old program. Ignored signals stay ignored.
+    pub(crate) fn reset_handlers(&self) {
+        for action in self.actions.lock().iter_mut() {
+            if action.handler != SIG_IGN {
+                *action = SigAction::default();
+            }
+        }
+    }
+
+    fn action(&self, signo: Signo) -> SigAction {
+        self.actions.lock()[signo as usize - 1]
+    }
+
+    fn set_action(&self, signo: Signo, action: SigAction) {
+        self.actions.lock()[signo as usize - 1] = action;
+    }
+
+    fn cont(&self) {
+        self.stopped.store(false, Ordering::Release);
+        self.cont_wq.notify_all(false);
+    }
+
+    /// Blocks the current thread while the process is stopped.
+    fn wait_while_stopped(&self, process: &Process) {
+        if !self.stopped.load(Ordering::Acquire) {
+            return;
+        }
+        self.cont_wq.wait_until(|| {
+            !self.stopped.load(Ordering::Acquire) || process.is_group_exiting()
+        });
+    }
+}
+
+/// Signal states of a thread.
+pub(crate) struct ThreadSignal {
+    blocked: AtomicU64,
+    /// Signals sent to this thread only, such as by `tgkill` or a fault.
+    pending: SpinNoIrq<PendingSignals>,
+    /// The signal frame read by `rt_sigreturn`, whose user context is put
+    /// into the trap frame just before returning to user space.
+    sigreturn_frame: SpinNoIrq<Option<Box<SignalFrame>>>,
+}
+
+impl ThreadSignal {
+    pub(crate) fn new(blocked: SigSet) -> Self {
+        Self {
+            blocked: AtomicU64::new(blocked.0),
+            pending: SpinNoIrq::new(PendingSignals::default()),
+            sigreturn_frame: SpinNoIrq::new(None),
+        }
+    }
+
+    /// Returns the blocked signals of the thread.
+    pub(crate) fn blocked(&self) -> SigSet {
+        SigSet(self.blocked.load(Ordering::Acquire))
+    }
+
+    fn set_blocked(&self, set: SigSet) {
+        self.blocked
+            .store(set.0 & !SigSet::UNBLOCKABLE.0, Ordering::Release);
+    }
+}
+
+/// Sends a signal to the process, or to the thread `tid` of it if given.
+///
+/// `SIGKILL` terminates the process at once. `SIGCONT` resumes a stopped
+/// process, and signals that would be ignored are discarded. The threads that
+/// can take the signal are interrupted, to deliver it on their way back to
+/// user space.
+///
+/// Returns [`LinuxError::EAGAIN`] if a real-time signal cannot be queued.
+pub(crate) fn send_signal(process: &Process, tid: Option<Pid>, info: SigInfo) -> LinuxResult {
+    let signo = info.signo();
+    let signal = process.signal();
+    match signo {
+        SIGKILL => {
+            process.group_exit_by_signal(SIGKILL, false);
+            signal.cont();
+            return Ok(());
+        }
+        SIGCONT => {
+            let mut pending = signal.pending.lock();
+            for stop in [SIGSTOP, SIGTSTP, SIGTTIN, SIGTTOU] {
+                pending.remove(stop);
+            }
+            drop(pending);
+            signal.cont();
+        }
+        SIGSTOP | SIGTSTP | SIGTTIN | SIGTTOU => {
+            signal.pending.lock().remove(SIGCONT);
+        }
+        _ => {}
+    }
+    if signal.action(signo).ignores(signo) {
+        return Ok(());
+    }
+    let queued = match tid {
+        Some(tid) => {
+            let Some(thread) = process.find_thread(tid) else {
+                return Ok(());
+            };
+            thread.pending.lock().push(info)
+        }
+        None => signal.pending.lock().push(info),
+    };
+    if !queued {
+        return Err(LinuxError::EAGAIN);
+    }
+    process.interrupt_threads(|thread_id, thread| {
+        tid.map_or(true, |tid| tid == thread_id) && !thread.blocked().contains(signo)
+    });
+    Ok(())
+}
+
+/// Sends a signal raised by a fault at `fault_addr` to the current thread,
+/// such as `SIGSEGV` for a page fault that cannot be resolved.
+///
+/// The signal cannot be blocked or ignored, it is delivered by
+/// [`handle_signals`] when the thread returns to user space.
+pub fn force_fault_signal(signo: Signo, fault_addr: VirtAddr) {
+    let curr = axtask::current();
+    let ext = curr.task_ext();
+    let signal = ext.process.signal();
+    let mut action = signal.action(signo);
+    if action.handler == SIG_IGN {
+        action.handler = SIG_DFL;
+        signal.set_action(signo, action);
+    }
+    let mut blocked = ext.signal.blocked();
+    if blocked.contains(signo) {
+        blocked.remove(signo);
+        ext.signal.set_blocked(blocked);
+    }
+    let code = if signo == SIGSEGV { SEGV_MAPERR } else { 0 };
+    let info = SigInfo::fault(signo, code, fault_addr.as_usize());
+    ext.signal.pending.lock().push(info);
+}
+
+/// Takes a pending signal not blocked by the thread, the ones sent to the
+/// thread first.
+fn dequeue_signal(thread: &ThreadSignal, process: &ProcessSignal) -> Option<SigInfo> {
+    let blocked = thread.blocked();
+    thread
+        .pending
+        .lock()
+        .pop(blocked)
+        .or_else(|| process.pending.lock().pop(blocked))
+}
+
+/// Terminates the current process by the signal.
+fn exit_by_signal(process: &Process, signo: Signo, core_dumped: bool) -> ! {
+    debug!("process {} terminated by signal {}", process.pid(), signo);
+    if core_dumped {
+        // No core file is written, show the memory map for debugging instead.
+        warn!(
+            "process {} dumped core by signal {}, memory map:\n{}",
+            process.pid(),
+            signo,
+            process.aspace().lock().maps()
+        );
+    }
+    process.group_exit_by_signal(signo, core_dumped);
+    sys_exit(process.exit_code())
+}
+
+/// Delivers the pending signals of the current thread.
+///
+/// It should be called just before returning to user space with the trap
+/// frame `tf`, which is changed to enter the signal handler if there is one
+/// to run. Threads are terminated or stopped here by the default actions of
+/// the signals.
+pub fn handle_signals(tf: &mut TrapFrame) {
+    let curr = axtask::current();
+    if unsafe { curr.task_ext_ptr() }.is_null() {
+        return;
+    }
+    let ext = curr.task_ext();
+    let process = &ext.process;
+    let signal = process.signal();
+    // The pending signals are delivered below, or stay blocked.
+    axtask::clear_interrupt();
+    if let Some(frame) = ext.signal.sigreturn_frame.lock().take() {
+        frame.restore(tf);
+    }
+    loop {
+        signal.wait_while_stopped(process);
+        check_group_exit();
+
+        let Some(info) = dequeue_signal(&ext.signal, signal) else {
+            return;
+        };
+        let signo = info.signo();
+        let action = signal.action(signo);
+        match action.handler {
+            SIG_IGN => {}
+            SIG_DFL => match default_action(signo) {
+                DefaultAction::Terminate => exit_by_signal(process, signo, false),
+                DefaultAction::CoreDump => exit_by_signal(process, signo, true),
+                DefaultAction::Stop => signal.stopped.store(true, Ordering::Release),
+                DefaultAction::Ignore | DefaultAction::Continue => {}
+            },
+            _ => {
+                let blocked = ext.signal.blocked();
+                if setup_frame(process, tf, &info, &action, blocked).is_err() {
+                    warn!("failed to deliver signal {} to {}", signo, curr.id_name());
+                    exit_by_signal(process, SIGSEGV, true);
+                }
+                let mut new_blocked = SigSet(blocked.0 | action.mask.0);
+                if action.flags & SA_NODEFER == 0 {
+                    new_blocked.add(signo);
+                }
+                ext.signal.set_blocked(new_blocked);
+                if action.flags & SA_RESETHAND != 0 {
+                    signal.set_action(signo, SigAction::default());
+                }
+                return;
+            }
+        }
+    }
+}
+
+/// Changes the action of a signal. See `rt_sigaction(2)`.
+pub fn sys_rt_sigaction(
+    signo: c_int,
+    act: *const SigAction,
+    oldact: *mut SigAction,
+    sigsetsize: usize,
+) -> LinuxResult<isize> {
+    if sigsetsize != size_of::<SigSet>() {
+        return Err(LinuxError::EINVAL);
+    }
+    let signo = check_signo(signo)?.ok_or(LinuxError::EINVAL)?;
+    // User memory is accessed out of the lock, as it may fault.
+    let new = if act.is_null() {
+        None
+    } else {
+        if SigSet::UNBLOCKABLE.contains(signo) {
+            return Err(LinuxError::EINVAL);
+        }
+        let mut new: SigAction = read_user_pod(current_process().aspace(), act as usize)?;
+        new.mask.0 &= !SigSet::UNBLOCKABLE.0;
+        Some(new)
+    };
+
+    let signal = current_process().signal();
+    let old = signal.action(signo);
+    if let Some(new) = new {
+        signal.set_action(signo, new);
+        if new.ignores(signo) {
+            signal.pending.lock().remove(signo);
+        }
+    }
+    if !oldact.is_null() {
+        write_user_pod(current_process().aspace(), oldact as usize, &old)?;
+    }
+    Ok(0)
+}
+
+/// Changes the blocked signals of the current thread. See
+/// `rt_sigprocmask(2)`.
+pub fn sys_rt_sigprocmask(
+    how: c_int,
+    set: *const SigSet,
+    oldset: *mut SigSet,
+    sigsetsize: usize,
+) -> LinuxResult<isize> {
+    if sigsetsize != size_of::<SigSet>() {
+        return Err(LinuxError::EINVAL);
+    }
+    let curr = axtask::current();
+    let ext = curr.task_ext();
+    let signal = &ext.signal;
+    let old = signal.blocked();
+    if !set.is_null() {
+        let set: SigSet = read_user_pod(ext.process.aspace(), set as usize)?;
+        let new = match how {
+            SIG_BLOCK => old.0 | set.0,
+            SIG_UNBLOCK => old.0 & !set.0,
+            SIG_SETMASK => set.0,
+            _ => return Err(LinuxError::EINVAL),
+        };
+        signal.set_blocked(SigSet(new));
+    }
+    if !oldset.is_null() {
+        write_user_pod(ext.process.aspace(), oldset as usize, &old)?;
+    }
+    Ok(0)
+}
+
+/// Returns the pending signals that are blocked by the current thread. See
+/// `rt_sigpending(2)`.
+pub fn sys_rt_sigpending(set: *mut SigSet, sigsetsize: usize) -> LinuxResult<isize> {
+    if sigsetsize != size_of::<SigSet>() {
+        return Err(LinuxError::EINVAL);
+    }
+    if set.is_null() {
+        return Err(LinuxError::EFAULT);
+    }
+    let curr = axtask::current();
+    let ext = curr.task_ext();
+    let pending = SigSet(
+        ext.signal.pending.lock().signals().0 | ext.process.signal().pending.lock().signals().0,
+    );
+    let pending = SigSet(pending.0 & ext.signal.blocked().0);
+    write_user_pod(ext.process.aspace(), set as usize, &pending)?;
+    Ok(0)
+}
+
+/// Checks the signal number from the user, where 0 means no signal.
+fn check_signo(signo: c_int) -> LinuxResult<Option<Signo>> {
+    match signo {
+        0 => Ok(None),
+        _ if (1..=NSIG as c_int).contains(&signo) => Ok(Some(signo as Signo)),
+        _ => Err(LinuxError::EINVAL),
+    }
+}
+
+/// Sends a signal to processes. See `kill(2)`.
+///
+/// As process groups are not supported, each process is taken as the only
+/// member of the group with the same ID, and `pid` 0 means the current
+/// process. `pid` -1 means all processes except the init process and the
+/// current one.
+pub fn sys_kill(pid: c_int, signo: c_int) -> LinuxResult<isize> {
+    let signo = check_signo(signo)?;
+    let curr = current_process();
+    let targets = match pid {
+        0 => vec![curr.clone()],
+        -1 => all_processes()
+            .into_iter()
+            .filter(|p| p.ppid() != 0 && p.pid() != curr.pid())
+            .collect(),
+        _ => {
+            let process = find_process(pid.unsigned_abs() as Pid).ok_or(LinuxError::ESRCH)?;
+            vec![process]
+        }
+    };
+    if targets.is_empty() {
+        return Err(LinuxError::ESRCH);
+    }
+    if let Some(signo) = signo {
+        // Fails only if none of the processes takes the signal, as Linux does.
+        let mut sent = false;
+        let mut err = None;
+        for process in targets.iter().filter(|p| !p.is_zombie()) {
+            match send_signal(process, None, SigInfo::user(signo, SI_USER, curr.pid())) {
+                Ok(()) => sent = true,
+                Err(e) => err = Some(e),
+            }
+        }
+        if let (false, Some(err)) = (sent, err) {
+            return Err(err);
+        }
+    }
+    Ok(0)
+}
+
+/// Sends a signal to the thread `tid` of the process `tgid`, or of any
+/// process if `tgid` is `None`.
+fn thread_kill(tgid: Option<Pid>, tid: Pid, signo: c_int) -> LinuxResult<isize> {
+    let signo = check_signo(signo)?;
+    let processes: Vec<_> = match tgid {
+        Some(tgid) => find_process(tgid).into_iter().collect(),
+        None => all_processes(),
+    };
+    let process = processes
+        .into_iter()
+        .find(|p| p.find_thread(tid).is_some())
+        .ok_or(LinuxError::ESRCH)?;
+    if let Some(signo) = signo {
+        let info = SigInfo::user(signo, SI_TKILL, current_process().pid());
+        send_signal(&process, Some(tid), info)?;
+    }
+    Ok(0)
+}
+
+/// Sends a signal to a thread. See `tkill(2)`.
+pub fn sys_tkill(tid: c_int, signo: c_int) -> LinuxResult<isize> {
+    if tid <= 0 {
+        return Err(LinuxError::EINVAL);
+    }
+    thread_kill(None, tid as Pid, signo)
+}
+
+/// Sends a signal to a thread of a process. See `tgkill(2)`.
+pub fn sys_tgkill(tgid: c_int, tid: c_int, signo: c_int) -> LinuxResult<isize> {
+    if tgid <= 0 || tid <= 0 {
+        return Err(LinuxError::EINVAL);
+    }
+    thread_kill(Some(tgid as Pid), tid as Pid, signo)
+}
+
+/// Returns from a signal handler, restoring the user context and the blocked
+/// signals saved in the signal frame.
+///
+/// The context is restored by [`handle_signals`] on the way back to user
+/// space, the return value is the restored return value register.
+pub fn sys_rt_sigreturn(tf: &TrapFrame) -> LinuxResult<isize> {
+    let curr = axtask::current();
+    let ext = curr.task_ext();
+    let mut frame = Box::<SignalFrame>::default();
+    let buf = unsafe {
+        core::slice::from_raw_parts_mut(
+            &mut *frame as *mut SignalFrame as *mut u8,
+            size_of::<SignalFrame>(),
+        )
+    };
+    let frame_addr = VirtAddr::from(SignalFrame::addr_on_return(tf));
+    if ext
+        .process
+        .aspace()
+        .lock()
+        .read_user(frame_addr, buf)
+        .is_err()
+    {
+        warn!("rt_sigreturn: bad signal frame at {:#x}", frame_addr);
+        exit_by_signal(&ext.process, SIGSEGV, true);
+    }
+    ext.signal.set_blocked(frame.sigmask());
+    let retval = frame.retval();
+    *ext.signal.sigreturn_frame.lock() = Some(frame);
+    Ok(retval as isize)
+}
+
+/// Maps the page with the code calling `rt_sigreturn`, where the signal
+/// handlers return to.
+pub(crate) fn map_trampoline(uspace: &mut AddrSpace) -> LinuxResult {
+    let vaddr = VirtAddr::from(SIGNAL_TRAMPOLINE);
+    uspace.map_alloc(
+        vaddr,
+        PAGE_SIZE_4K,
+        MappingFlags::READ | MappingFlags::EXECUTE | MappingFlags::USER,
+        true,
+    )?;
+    uspace.write(vaddr, &arch::SIGRETURN_CODE)?;
+    Ok(())
+}
+
+/// The Linux `stack_t`.
+#[repr(C)]
+#[derive(Default)]
+struct SignalStack {
+    sp: usize,
+    flags: i32,
+    size: usize,
+}
+
+impl Default for SigInfo {
+    fn default() -> Self {
+        Self::new(0, 0)
+    }
+}
+
+/// Pushes the signal frame onto the user stack, and changes the trap frame
+/// to call the handler as `handler(signo, &info, &ucontext)`.
+fn setup_frame(
+    process: &Process,
+    tf: &mut TrapFrame,
+    info: &SigInfo,
+    action: &SigAction,
+    blocked: SigSet,
+) -> LinuxResult {
+    let frame = Box::new(SignalFrame::new(info, tf, blocked, action.restorer()));
+    let frame_addr = SignalFrame::addr_below(tf.get_sp());
+    let buf = unsafe {
+        core::slice::from_raw_parts(
+            &*frame as *const SignalFrame as *const u8,
+            size_of::<SignalFrame>(),
+        )
+    };
+    process
+        .aspace()
+        .lock()
+        .write_user(VirtAddr::from(frame_addr), buf)?;
+    frame.enter_handler(tf, frame_addr, action.handler, action.restorer());
+    Ok(())
+}
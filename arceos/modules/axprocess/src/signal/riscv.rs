@@ -0,0 +1,117 @@
+//! Signal frames of riscv64, laid out as in Linux.
+
+use core::mem::{offset_of, size_of};
+
+use axhal::arch::{GeneralRegisters, TrapFrame};
+use memory_addr::MemoryAddr;
+
+use super::{SigInfo, SigSet, SignalStack};
+
+/// `li a7, 139` (`rt_sigreturn`) and `ecall`.
+pub(super) const SIGRETURN_CODE: [u8; 8] = [0x93, 0x08, 0xb0, 0x08, 0x73, 0x00, 0x00, 0x00];
+
+/// The Linux `sigcontext`: the PC, the general registers `x1`-`x31` and the
+/// FP states.
+#[repr(C)]
+#[derive(Default)]
+struct MContext {
+    pc: usize,
+    regs: GeneralRegisters,
+    fp: FpState,
+}
+
+/// FP states are not saved by the kernel yet, they are left zero.
+#[repr(C, align(16))]
+struct FpState([u64; 66]);
+
+impl Default for FpState {
+    fn default() -> Self {
+        Self([0; 66])
+    }
+}
+
+/// The Linux `ucontext_t`.
+#[repr(C)]
+#[derive(Default)]
+struct UContext {
+    flags: usize,
+    link: usize,
+    stack: SignalStack,
+    /// The blocked signals before the handler runs.
+    sigmask: SigSet,
+    /// Reserved for a larger `sigset_t`.
+    _unused: [u64; 15],
+    mcontext: MContext,
+}
+
+/// Pushed onto the user stack when a signal handler is called.
+#[repr(C)]
+#[derive(Default)]
+pub(super) struct SignalFrame {
+    info: SigInfo,
+    ucontext: UContext,
+}
+
+impl SignalFrame {
+    /// Saves the user context in `tf` and the blocked signals.
+    pub(super) fn new(info: &SigInfo, tf: &TrapFrame, sigmask: SigSet, _restorer: usize) -> Self {
+        Self {
+            info: *info,
+            ucontext: UContext {
+                sigmask,
+                mcontext: MContext {
+                    pc: tf.sepc,
+                    regs: tf.regs,
+                    fp: FpState::default(),
+                },
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Returns where the frame is pushed, below the user stack pointer `sp`.
+    pub(super) fn addr_below(sp: usize) -> usize {
+        (sp - size_of::<Self>()).align_down(16)
+    }
+
+    /// Returns where the frame is when the handler returns to call
+    /// `rt_sigreturn`.
+    pub(super) fn addr_on_return(tf: &TrapFrame) -> usize {
+        tf.regs.sp
+    }
+
+    /// Changes the trap frame to call the handler as
+    /// `handler(signo, &info, &ucontext)` with the frame at `addr`, which
+    /// returns to `restorer`.
+    pub(super) fn enter_handler(
+        &self,
+        tf: &mut TrapFrame,
+        addr: usize,
+        handler: usize,
+        restorer: usize,
+    ) {
+        tf.sepc = handler;
+        tf.regs.sp = addr;
+        tf.regs.ra = restorer;
+        tf.regs.a0 = self.info.signo() as usize;
+        tf.regs.a1 = addr + offset_of!(Self, info);
+        tf.regs.a2 = addr + offset_of!(Self, ucontext);
+    }
+
+    /// Returns the blocked signals saved in the frame.
+    pub(super) fn sigmask(&self) -> SigSet {
+        self.ucontext.sigmask
+    }
+
+    /// Returns the saved return value register.
+    pub(super) fn retval(&self) -> usize {
+        self.ucontext.mcontext.regs.a0
+    }
+
+    /// Restores the registers that user space may change. The privileged
+    /// states stay as they are.
+    pub(super) fn restore(&self, tf: &mut TrapFrame) {
+        tf.sepc = self.ucontext.mcontext.pc;
+        tf.regs = self.ucontext.mcontext.regs;
+    }
+}
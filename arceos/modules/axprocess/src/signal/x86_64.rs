@@ -0,0 +1,178 @@
+//! Signal frames of x86_64, laid out as in Linux.
+
+use core::mem::{offset_of, size_of};
+
+use axhal::arch::TrapFrame;
+use memory_addr::MemoryAddr;
+
+use super::{SigInfo, SigSet, SignalStack};
+
+/// `mov eax, 15` (`rt_sigreturn`) and `syscall`.
+pub(super) const SIGRETURN_CODE: [u8; 7] = [0xb8, 0x0f, 0x00, 0x00, 0x00, 0x0f, 0x05];
+
+/// The red zone below the stack pointer, which may be used by leaf functions.
+const RED_ZONE_SIZE: usize = 128;
+
+/// The `RFLAGS` bits that user space may change: `CF`, `PF`, `AF`, `ZF`,
+/// `SF`, `TF`, `DF`, `OF`, `RF` and `AC`.
+const USER_RFLAGS: u64 = 0x50dd5;
+
+/// The Linux `sigcontext`.
+#[repr(C)]
+#[derive(Default)]
+struct MContext {
+    r8: u64,
+    r9: u64,
+    r10: u64,
+    r11: u64,
+    r12: u64,
+    r13: u64,
+    r14: u64,
+    r15: u64,
+    rdi: u64,
+    rsi: u64,
+    rbp: u64,
+    rbx: u64,
+    rdx: u64,
+    rax: u64,
+    rcx: u64,
+    rsp: u64,
+    rip: u64,
+    rflags: u64,
+    cs: u16,
+    gs: u16,
+    fs: u16,
+    ss: u16,
+    err: u64,
+    trapno: u64,
+    oldmask: u64,
+    cr2: u64,
+    /// FP states are not saved by the kernel yet, the pointer is left null.
+    fpstate: u64,
+    _reserved: [u64; 8],
+}
+
+/// The Linux `ucontext_t`.
+#[repr(C)]
+#[derive(Default)]
+struct UContext {
+    flags: usize,
+    link: usize,
+    stack: SignalStack,
+    mcontext: MContext,
+    /// The blocked signals before the handler runs.
+    sigmask: SigSet,
+}
+
+/// Pushed onto the user stack when a signal handler is called.
+#[repr(C)]
+#[derive(Default)]
+pub(super) struct SignalFrame {
+    /// The return address of the handler, popped by its `ret`.
+    pretcode: usize,
+    ucontext: UContext,
+    info: SigInfo,
+}
+
+impl SignalFrame {
+    /// Saves the user context in `tf` and the blocked signals.
+    pub(super) fn new(info: &SigInfo, tf: &TrapFrame, sigmask: SigSet, restorer: usize) -> Self {
+        Self {
+            pretcode: restorer,
+            ucontext: UContext {
+                mcontext: MContext {
+                    r8: tf.r8,
+                    r9: tf.r9,
+                    r10: tf.r10,
+                    r11: tf.r11,
+                    r12: tf.r12,
+                    r13: tf.r13,
+                    r14: tf.r14,
+                    r15: tf.r15,
+                    rdi: tf.rdi,
+                    rsi: tf.rsi,
+                    rbp: tf.rbp,
+                    rbx: tf.rbx,
+                    rdx: tf.rdx,
+                    rax: tf.rax,
+                    rcx: tf.rcx,
+                    rsp: tf.rsp,
+                    rip: tf.rip,
+                    rflags: tf.rflags,
+                    cs: tf.cs as u16,
+                    ss: tf.ss as u16,
+                    err: tf.error_code,
+                    trapno: tf.vector,
+                    ..Default::default()
+                },
+                sigmask,
+                ..Default::default()
+            },
+            info: *info,
+        }
+    }
+
+    /// Returns where the frame is pushed, below the red zone of the user
+    /// stack pointer `sp`. The stack is aligned as if the handler is called.
+    pub(super) fn addr_below(sp: usize) -> usize {
+        (sp - RED_ZONE_SIZE - size_of::<Self>()).align_down(16) - 8
+    }
+
+    /// Returns where the frame is when the handler returns to call
+    /// `rt_sigreturn`, after its return address is popped.
+    pub(super) fn addr_on_return(tf: &TrapFrame) -> usize {
+        tf.rsp as usize - offset_of!(Self, ucontext)
+    }
+
+    /// Changes the trap frame to call the handler as
+    /// `handler(signo, &info, &ucontext)` with the frame at `addr`, which
+    /// returns to the restorer saved in the frame.
+    pub(super) fn enter_handler(
+        &self,
+        tf: &mut TrapFrame,
+        addr: usize,
+        handler: usize,
+        _restorer: usize,
+    ) {
+        tf.rip = handler as u64;
+        tf.rsp = addr as u64;
+        tf.rdi = self.info.signo() as u64;
+        tf.rsi = (addr + offset_of!(Self, info)) as u64;
+        tf.rdx = (addr + offset_of!(Self, ucontext)) as u64;
+        tf.rax = 0;
+    }
+
+    /// Returns the blocked signals saved in the frame.
+    pub(super) fn sigmask(&self) -> SigSet {
+        self.ucontext.sigmask
+    }
+
+    /// Returns the saved return value register.
+    pub(super) fn retval(&self) -> usize {
+        self.ucontext.mcontext.rax as usize
+    }
+
+    /// Restores the registers that user space may change. The privileged
+    /// states, such as the segments and `IF`, stay as they are.
+    pub(super) fn restore(&self, tf: &mut TrapFrame) {
+        let ctx = &self.ucontext.mcontext;
+        tf.r8 = ctx.r8;
+        tf.r9 = ctx.r9;
+        tf.r10 = ctx.r10;
+        tf.r11 = ctx.r11;
+        tf.r12 = ctx.r12;
+        tf.r13 = ctx.r13;
+        tf.r14 = ctx.r14;
+        tf.r15 = ctx.r15;
+        tf.rdi = ctx.rdi;
+        tf.rsi = ctx.rsi;
+        tf.rbp = ctx.rbp;
+        tf.rbx = ctx.rbx;
+        tf.rdx = ctx.rdx;
+        tf.rax = ctx.rax;
+        tf.rcx = ctx.rcx;
+        tf.rsp = ctx.rsp;
+        tf.rip = ctx.rip;
+        tf.rflags = (tf.rflags & !USER_RFLAGS) | (ctx.rflags & USER_RFLAGS);
+    }
+}
@@ -0,0 +1,79 @@
+//! Registers the syscalls implemented by this crate into [`axsyscall`].
+
+use axsyscall::register_syscalls;
+
+use crate::api::*;
+use crate::futex::*;
+use crate::mm::*;
+use crate::signal::*;
+
+register_syscalls! {
+    exit => |args| sys_exit(args.int(0)),
+    exit_group => |args| sys_exit_group(args.int(0)),
+    set_tid_address => |args| sys_set_tid_address(args.ptr(0)),
+    set_robust_list => |args| sys_set_robust_list(args.arg(0), args.arg(1)),
+    futex => |args| {
+        sys_futex(
+            args.arg(0),
+            args.int(1),
+            args.arg(2) as u32,
+            args.arg(3),
+            args.arg(4),
+            args.arg(5) as u32,
+        )
+    },
+    getpid => |_| sys_getpid(),
+    getppid => |_| sys_getppid(),
+    gettid => |_| sys_gettid(),
+    clone => |args| {
+        // The last two arguments are in the other order on x86_64.
+        #[cfg(target_arch = "x86_64")]
+        let (tls, ctid) = (args.arg(4), args.arg(3));
+        #[cfg(not(target_arch = "x86_64"))]
+        let (tls, ctid) = (args.arg(3), args.arg(4));
+        sys_clone(
+            args.trap_frame(),
+            args.arg(0),
+            args.arg(1),
+            args.arg(2),
+            tls,
+            ctid,
+        )
+    },
+    execve => |args| sys_execve(args.ptr(0), args.ptr(1), args.ptr(2)),
+    wait4 => |args| sys_wait4(args.int(0), args.ptr(1), args.arg(2) as u32),
+    mmap => |args| {
+        sys_mmap(
+            args.arg(0),
+            args.arg(1),
+            args.int(2),
+            args.int(3),
+            args.int(4),
+            args.arg(5) as isize,
+        )
+    },
+    munmap => |args| sys_munmap(args.arg(0), args.arg(1)),
+    mprotect => |args| sys_mprotect(args.arg(0), args.arg(1), args.int(2)),
+    msync => |args| sys_msync(args.arg(0), args.arg(1), args.int(2)),
+    brk => |args| sys_brk(args.arg(0)),
+    mremap => |args| {
+        sys_mremap(
+            args.arg(0),
+            args.arg(1),
+            args.arg(2),
+            args.int(3),
+            args.arg(4),
+        )
+    },
+    rt_sigaction => |args| {
+        sys_rt_sigaction(args.int(0), args.ptr(1), args.ptr(2), args.arg(3))
+    },
+    rt_sigprocmask => |args| {
+        sys_rt_sigprocmask(args.int(0), args.ptr(1), args.ptr(2), args.arg(3))
+    },
+    rt_sigpending => |args| sys_rt_sigpending(args.ptr(0), args.arg(1)),
+    rt_sigreturn => |args| sys_rt_sigreturn(args.trap_frame()),
+    kill => |args| sys_kill(args.int(0), args.int(1)),
+    tkill => |args| sys_tkill(args.int(0), args.int(1)),
+    tgkill => |args| sys_tgkill(args.int(0), args.int(1), args.int(2)),
+}
@@ -0,0 +1,134 @@
+use alloc::string::String;
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use arceos_posix_api::FdTable;
+use axhal::arch::UspaceContext;
+use axtask::{AxTaskRef, TaskExtRef, TaskInner};
+
+use crate::process::{Pid, Process};
+use crate::signal::{SigSet, ThreadSignal};
+
+/// Size of the kernel stack of each user thread.
+const KERNEL_STACK_SIZE: usize = 0x40000; // 256 KiB
+
+/// Task extended data for user threads.
+pub struct TaskExt {
+    /// The process that the thread belongs to.
+    pub process: Arc<Process>,
+    /// The user space context to start the thread with.
+    pub uctx: UspaceContext,
+    /// The clear thread tid field
+    ///
+    /// See <https://manpages.debian.org/unstable/manpages-dev/set_tid_address.2.en.html#clear_child_tid>
+    ///
+    /// When the thread exits, the kernel clears the word at this address if it is not NULL.
+    clear_child_tid: AtomicU64,
+    /// The robust list of futexes held by the thread, set by
+    /// `set_robust_list`, which are released when the thread exits.
+    robust_list: AtomicU64,
+    /// Signal states of the thread, also referenced by the process.
+    pub(crate) signal: Arc<ThreadSignal>,
+}
+
+impl TaskExt {
+    pub fn new(process: Arc<Process>, uctx: UspaceContext, blocked: SigSet) -> Self {
+        Self {
+            process,
+            uctx,
+            clear_child_tid: AtomicU64::new(0),
+            robust_list: AtomicU64::new(0),
+            signal: Arc::new(ThreadSignal::new(blocked)),
+        }
+    }
+
+    pub fn clear_child_tid(&self) -> u64 {
+        self.clear_child_tid.load(Ordering::Relaxed)
+    }
+
+    pub fn set_clear_child_tid(&self, clear_child_tid: u64) {
+        self.clear_child_tid
+            .store(clear_child_tid, Ordering::Relaxed);
+    }
+
+    pub fn robust_list(&self) -> u64 {
+        self.robust_list.load(Ordering::Relaxed)
+    }
+
+    pub fn set_robust_list(&self, robust_list: u64) {
+        self.robust_list.store(robust_list, Ordering::Relaxed);
+    }
+}
+
+axtask::def_task_ext!(TaskExt);
+
+/// Returns the process of the current task.
+///
+/// # Panics
+///
+/// Panics if the current task is not a user thread.
+pub fn current_process() -> Arc<Process> {
+    axtask::current().task_ext().process.clone()
+}
+
+/// Returns the file descriptor table of the current process, or `None` if the
+/// current task is a kernel task.
+pub(crate) fn current_fd_table() -> Option<Arc<FdTable>> {
+    let curr = axtask::current();
+    if unsafe { curr.task_ext_ptr() }.is_null() {
+        return None;
+    }
+    curr.task_ext().process.fd_table()
+}
+
+/// Creates a user thread that enters user space with the context in its
+/// [`TaskExt`], but does not spawn it.
+///
+/// The thread is not added to the process either, as its ID is the PID when a
+/// new process is created for it.
+pub(crate) fn new_user_task(name: String) -> TaskInner {
+    TaskInner::new(
+        || {
+            let curr = axtask::current();
+            let kstack_top = curr.kernel_stack_top().unwrap();
+            info!(
+                "Enter user space: entry={:#x}, ustack={:#x}, kstack={:#x}",
+                curr.task_ext().uctx.get_ip(),
+                curr.task_ext().uctx.get_sp(),
+                kstack_top,
+            );
+            axtask::on_user_return();
+            unsafe { curr.task_ext().uctx.enter_uspace(kstack_top) };
+        },
+        name,
+        KERNEL_STACK_SIZE,
+    )
+}
+
+/// Attaches the thread to the process and spawns it.
+///
+/// If `clear_child_tid` is not zero, the word at this address is cleared when
+/// the thread exits. The thread inherits the blocked signals of the current
+/// thread, if it is a user thread.
+pub(crate) fn spawn_user_task(
+    mut task: TaskInner,
+    process: Arc<Process>,
+    uctx: UspaceContext,
+    clear_child_tid: u64,
+) -> AxTaskRef {
+    let tid: Pid = task.id().as_u64();
+    task.ctx_mut()
+        .set_page_table_root(process.aspace().lock().page_table_root());
+    let curr = axtask::current();
+    let blocked = if unsafe { curr.task_ext_ptr() }.is_null() {
+        SigSet::default()
+    } else {
+        curr.task_ext().signal.blocked()
+    };
+    let ext = task
+        .init_task_ext(TaskExt::new(process.clone(), uctx, blocked))
+        .unwrap();
+    ext.set_clear_child_tid(clear_child_tid);
+    process.add_thread(tid, ext.signal.clone());
+    axtask::spawn_task(task)
+}
@@ -0,0 +1,86 @@
+//! Copying data between the kernel and the user memory.
+//!
+//! The syscalls never dereference user pointers directly: a bad pointer would
+//! fault in the kernel. The data is copied through the address space of the
+//! process instead, which fails with `EFAULT` on unmapped or inaccessible
+//! memory.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::mem::size_of;
+
+use axerrno::{LinuxError, LinuxResult};
+use axhal::mem::PAGE_SIZE_4K;
+use axmm::AddrSpace;
+use axsync::Mutex;
+use memory_addr::{MemoryAddr, VirtAddr};
+
+use crate::task::current_process;
+
+/// Reads a value of the plain old data type `T` from the user.
+pub(crate) fn read_user_pod<T: Default>(aspace: &Mutex<AddrSpace>, uaddr: usize) -> LinuxResult<T> {
+    let mut val = T::default();
+    let buf =
+        unsafe { core::slice::from_raw_parts_mut(&mut val as *mut T as *mut u8, size_of::<T>()) };
+    aspace
+        .lock()
+        .read_user(VirtAddr::from(uaddr), buf)
+        .map_err(|_| LinuxError::EFAULT)?;
+    Ok(val)
+}
+
+/// Writes a value of the plain old data type `T` to the user.
+pub(crate) fn write_user_pod<T>(aspace: &Mutex<AddrSpace>, uaddr: usize, val: &T) -> LinuxResult {
+    let buf = unsafe { core::slice::from_raw_parts(val as *const T as *const u8, size_of::<T>()) };
+    aspace
+        .lock()
+        .write_user(VirtAddr::from(uaddr), buf)
+        .map_err(|_| LinuxError::EFAULT)?;
+    Ok(())
+}
+
+/// Reads a NUL-terminated string of at most `max_len` bytes from the user.
+///
+/// It is copied page by page, so that the string may end right before an
+/// unmapped page. Fails with `ENAMETOOLONG` if no NUL is found within
+/// `max_len` bytes.
+pub(crate) fn read_user_str(
+    aspace: &Mutex<AddrSpace>,
+    uaddr: usize,
+    max_len: usize,
+) -> LinuxResult<String> {
+    let mut uspace = aspace.lock();
+    let mut buf = Vec::new();
+    let mut vaddr = VirtAddr::from(uaddr);
+    loop {
+        let start = buf.len();
+        if start > max_len {
+            return Err(LinuxError::ENAMETOOLONG);
+        }
+        let len = PAGE_SIZE_4K - vaddr.align_offset_4k();
+        buf.resize(start + len, 0);
+        uspace
+            .read_user(vaddr, &mut buf[start..])
+            .map_err(|_| LinuxError::EFAULT)?;
+        if let Some(pos) = buf[start..].iter().position(|&b| b == 0) {
+            buf.truncate(start + pos);
+            break;
+        }
+        vaddr += len;
+    }
+    if buf.len() > max_len {
+        return Err(LinuxError::ENAMETOOLONG);
+    }
+    String::from_utf8(buf).map_err(|_| LinuxError::EINVAL)
+}
+
+/// Reads the user memory of the current process, for the built-in syscalls of
+/// [`axsyscall`].
+pub(crate) fn read_current_user(uaddr: usize, buf: &mut [u8]) -> LinuxResult {
+    current_process()
+        .aspace()
+        .lock()
+        .read_user(VirtAddr::from(uaddr), buf)
+        .map_err(|_| LinuxError::EFAULT)?;
+    Ok(())
+}
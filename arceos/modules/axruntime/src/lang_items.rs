@@ -3,5 +3,15 @@ use core::panic::PanicInfo;
 #[panic_handler]
 fn panic(info: &PanicInfo) -> ! {
     error!("{}", info);
+    // Only the panicking task exits, unless it can not, e.g., with IRQs or
+    // preemption disabled by a spin lock, so the mutexes it holds are
+    // poisoned.
+    #[cfg(feature = "task_panic")]
+    if let Some(curr) = axtask::current_may_uninit() {
+        if !curr.is_init() && !curr.is_idle() && !axtask::in_atomic() {
+            // The exit code of a panicking Rust program.
+            axtask::exit(101);
+        }
+    }
     axhal::misc::terminate()
 }
@@ -6,11 +6,18 @@
 //! # Cargo Features
 //!
 //! - `alloc`: Enable global memory allocator.
-//! - `paging`: Enable page table manipulation support.
+//! - `paging`: Enable page table manipulation support. The task stacks are
+//!   mapped with guard pages below them if `multitask` is enabled as well.
 //! - `irq`: Enable interrupt handling support.
+//! - `tickless`: Stop the periodic timer ticks while the CPU is idle, and
+//!   wake it up only for the next timer event. See [`tick_stats`] for the
+//!   effect.
 //! - `multitask`: Enable multi-threading support.
+//! - `task_panic`: A panic in a task other than the main task exits the task
+//!   only, which poisons the mutexes it holds.
 //! - `smp`: Enable SMP (symmetric multiprocessing) support.
-//! - `fs`: Enable filesystem support.
+//! - `fs`: Enable filesystem support. The files of the procfs are generated
+//!   from the state of the memory allocator, the tasks, the network, etc.
 //! - `net`: Enable networking support.
 //! - `display`: Enable graphics support.
 //!
@@ -22,15 +29,27 @@
 #[macro_use]
 extern crate axlog;
 
+#[cfg(feature = "fs")]
+extern crate alloc;
+
 #[cfg(all(target_os = "none", not(test)))]
 mod lang_items;
 
 #[cfg(feature = "smp")]
 mod mp;
 
+#[cfg(feature = "irq")]
+mod timer;
+
+#[cfg(feature = "fs")]
+mod procfs;
+
 #[cfg(feature = "smp")]
 pub use self::mp::rust_main_secondary;
 
+#[cfg(feature = "irq")]
+pub use self::timer::{tick_stats, TickStats};
+
 const LOGO: &str = r#"
        d8888                            .d88888b.   .d8888b.
       d88888                           d88P" "Y88b d88P  Y88b
@@ -46,6 +65,23 @@ extern "C" {
     fn main();
 }
 
+#[cfg(all(feature = "paging", feature = "multitask"))]
+struct KernelStackIfImpl;
+
+#[cfg(all(feature = "paging", feature = "multitask"))]
+#[crate_interface::impl_interface]
+impl axtask::KernelStackIf for KernelStackIfImpl {
+    fn alloc_kernel_stack(size: usize) -> Option<axhal::mem::VirtAddr> {
+        axmm::alloc_kernel_stack(size).ok()
+    }
+
+    fn dealloc_kernel_stack(bottom: axhal::mem::VirtAddr, size: usize) {
+        if let Err(e) = axmm::dealloc_kernel_stack(bottom, size) {
+            warn!("failed to deallocate kernel stack at {:#x}: {:?}", bottom, e);
+        }
+    }
+}
+
 struct LogIfImpl;
 
 #[crate_interface::impl_interface]
@@ -153,10 +189,13 @@ pub extern "C" fn rust_main(cpu_id: usize, dtb: usize) -> ! {
     #[cfg(feature = "multitask")]
     axtask::init_scheduler();
 
-    #[cfg(any(feature = "fs", feature = "net", feature = "display"))]
+    #[cfg(any(feature = "fs", feature = "net", feature = "display", feature = "swap"))]
     {
-        #[allow(unused_variables)]
-        let all_devices = axdriver::init_drivers();
+        #[allow(unused_variables, unused_mut)]
+        let mut all_devices = axdriver::init_drivers();
+
+        #[cfg(feature = "swap")]
+        init_swap(&mut all_devices.block);
 
         #[cfg(feature = "fs")]
         axfs::init_filesystems(all_devices.block);
@@ -168,6 +207,9 @@ pub extern "C" fn rust_main(cpu_id: usize, dtb: usize) -> ! {
         axdisplay::init_display(all_devices.display);
     }
 
+    #[cfg(feature = "fs")]
+    self::procfs::init();
+
     #[cfg(feature = "smp")]
     self::mp::start_secondary_cpus(cpu_id);
 
@@ -197,7 +239,7 @@ pub extern "C" fn rust_main(cpu_id: usize, dtb: usize) -> ! {
     #[cfg(not(feature = "multitask"))]
     {
         debug!("main task exited: exit_code={}", 0);
-        axhal::misc::terminate();
+        axhal::misc::shutdown();
     }
 }
 
@@ -259,38 +301,67 @@ fn init_allocator() {
     }
 }
 
+/// Writes the modified file contents and disk blocks back before shutdown.
+#[cfg(feature = "fs")]
+#[axhal::misc::register_shutdown_hook(axhal::misc::SHUTDOWN)]
+fn sync_filesystems() {
+    if let Err(e) = axfs::api::sync() {
+        warn!("failed to sync filesystems: {:?}", e);
+    }
+}
+
+/// Initializes the swap area with the first block device that has a swap
+/// header made by `mkswap`.
+///
+/// The root filesystem uses the first block device if the `fs` feature is
+/// enabled, which is never used for swap. The other devices are left in
+/// `blk_devs` for the filesystems.
+#[cfg(feature = "swap")]
+fn init_swap(blk_devs: &mut axdriver::AxDeviceContainer<axdriver::AxBlockDevice>) {
+    let mut rest = axdriver::AxDeviceContainer::default();
+    if cfg!(feature = "fs") {
+        if let Some(dev) = blk_devs.take_one() {
+            rest.push(dev);
+        }
+    }
+    let mut found = false;
+    while let Some(dev) = blk_devs.take_one() {
+        match axmm::init_swap(dev) {
+            Ok(()) => found = true,
+            Err(dev) => rest.push(dev),
+        }
+    }
+    if !found {
+        warn!("No block device with a swap header found for the swap area!");
+    }
+    *blk_devs = rest;
+}
+
 #[cfg(feature = "irq")]
 fn init_interrupt() {
     use axhal::time::TIMER_IRQ_NUM;
 
     // Setup timer interrupt handler
-    const PERIODIC_INTERVAL_NANOS: u64 =
-        axhal::time::NANOS_PER_SEC / axconfig::TICKS_PER_SEC as u64;
-
-    #[percpu::def_percpu]
-    static NEXT_DEADLINE: u64 = 0;
-
-    fn update_timer() {
-        let now_ns = axhal::time::monotonic_time_nanos();
-        // Safety: we have disabled preemption in IRQ handler.
-        let mut deadline = unsafe { NEXT_DEADLINE.read_current_raw() };
-        if now_ns >= deadline {
-            deadline = now_ns + PERIODIC_INTERVAL_NANOS;
-        }
-        unsafe { NEXT_DEADLINE.write_current_raw(deadline + PERIODIC_INTERVAL_NANOS) };
-        axhal::time::set_oneshot_timer(deadline);
-    }
+    axhal::irq::register_handler(TIMER_IRQ_NUM, self::timer::on_timer_irq);
 
-    axhal::irq::register_handler(TIMER_IRQ_NUM, || {
-        update_timer();
-        #[cfg(feature = "multitask")]
-        axtask::on_timer_tick();
-    });
+    // Setup the handler of the inter-processor interrupts
+    #[cfg(feature = "smp")]
+    axhal::irq::register_handler(axhal::irq::IPI_IRQ_NUM, on_ipi);
 
     // Enable IRQs before starting app
     axhal::arch::enable_irqs();
 }
 
+/// Handles the inter-processor interrupts, which are sent when tasks are added
+/// to the run queue of this CPU by other CPUs, or kernel stacks are unmapped.
+#[cfg(all(feature = "irq", feature = "smp"))]
+fn on_ipi() {
+    #[cfg(feature = "paging")]
+    axmm::handle_tlb_shootdown();
+    #[cfg(feature = "multitask")]
+    axtask::on_resched_ipi();
+}
+
 #[cfg(all(feature = "tls", not(feature = "multitask")))]
 fn init_tls() {
     let main_tls = axhal::tls::TlsArea::alloc();
@@ -0,0 +1,245 @@
+//! Files of the procfs generated from the state of the other modules.
+
+use alloc::{format, string::String};
+
+use axfs::procfs::{self, ProcFile, VfsResult};
+
+pub(crate) fn init() {
+    info!("Initialize procfs...");
+    add_files().expect("failed to add procfs files");
+}
+
+fn add_files() -> VfsResult {
+    #[cfg(feature = "alloc")]
+    procfs::add("meminfo", ProcFile::new(meminfo))?;
+    procfs::add("uptime", ProcFile::new(uptime))?;
+    #[cfg(feature = "irq")]
+    procfs::add("interrupts", ProcFile::new(interrupts))?;
+
+    #[cfg(feature = "net")]
+    {
+        procfs::add("net/tcp", ProcFile::new(net_tcp))?;
+        procfs::add(
+            "sys/net/core/somaxconn",
+            ProcFile::new_rw(
+                || Ok(format!("{}\n", axnet::listen_queue_size())),
+                |value| axnet::set_listen_queue_size(parse(value)?),
+            ),
+        )?;
+    }
+
+    #[cfg(feature = "multitask")]
+    self::task::add_files()?;
+
+    Ok(())
+}
+
+/// Parses the value written to a tunable.
+#[cfg(feature = "net")]
+fn parse<T: core::str::FromStr>(value: &str) -> VfsResult<T> {
+    value.parse().map_err(|_| procfs::VfsError::InvalidInput)
+}
+
+/// Generates `/proc/meminfo` from the global allocator and the caches.
+#[cfg(feature = "alloc")]
+fn meminfo() -> VfsResult<String> {
+    use axfs::api::{buffer_cache_stats, page_cache_stats};
+    use axhal::mem::PAGE_SIZE_4K;
+    use core::fmt::Write;
+
+    let allocator = axalloc::global_allocator();
+    let free = allocator.available_pages() * PAGE_SIZE_4K;
+    let total = allocator.used_pages() * PAGE_SIZE_4K + free;
+    let (buffers, pages) = (buffer_cache_stats(), page_cache_stats());
+    // blocks of 512 bytes and pages of 4K bytes
+    let (buffers_size, cached_size) = (buffers.cached * 512, pages.cached * PAGE_SIZE_4K);
+    let dirty = buffers.dirty * 512 + pages.dirty * PAGE_SIZE_4K;
+    // the free heap and the clean caches can be reused without swapping
+    let available = free + allocator.available_bytes() + buffers_size + cached_size - dirty;
+
+    let mut buf = String::new();
+    let mut line = |name: &str, bytes: usize| writeln!(buf, "{:<16}{:>8} kB", name, bytes / 1024);
+    line("MemTotal:", total).unwrap();
+    line("MemFree:", free).unwrap();
+    line("MemAvailable:", available).unwrap();
+    line("Buffers:", buffers_size).unwrap();
+    line("Cached:", cached_size).unwrap();
+    line("Dirty:", dirty).unwrap();
+    #[cfg(feature = "swap")]
+    {
+        let swap = axmm::swap_stats();
+        let swap_free = swap.total_slots - swap.used_slots;
+        line("SwapTotal:", swap.total_slots * PAGE_SIZE_4K).unwrap();
+        line("SwapFree:", swap_free * PAGE_SIZE_4K).unwrap();
+    }
+    Ok(buf)
+}
+
+/// Generates `/proc/uptime`: the seconds since boot, and the seconds that
+/// the CPUs have spent idle, summed over all CPUs.
+fn uptime() -> VfsResult<String> {
+    let uptime = axhal::time::monotonic_time();
+    #[cfg(feature = "multitask")]
+    let idle = axtask::tasks()
+        .filter(|task| task.name() == "idle")
+        .map(|task| task.info())
+        .map(|info| info.kernel_time + info.user_time)
+        .sum::<axhal::time::Duration>();
+    #[cfg(not(feature = "multitask"))]
+    let idle = axhal::time::Duration::ZERO;
+    Ok(format!(
+        "{:.2} {:.2}\n",
+        uptime.as_secs_f64(),
+        idle.as_secs_f64()
+    ))
+}
+
+/// Generates `/proc/interrupts` from the IRQ counters of each CPU. The IRQs
+/// never taken are omitted.
+#[cfg(feature = "irq")]
+fn interrupts() -> VfsResult<String> {
+    use axhal::irq::{irq_count, MAX_IRQ_COUNT};
+    use core::fmt::Write;
+
+    let mut buf = String::from("    ");
+    for cpu_id in 0..axconfig::SMP {
+        write!(buf, " {:>10}", format!("CPU{cpu_id}")).unwrap();
+    }
+    buf.push('\n');
+    for irq_num in 0..MAX_IRQ_COUNT {
+        if (0..axconfig::SMP).all(|cpu_id| irq_count(irq_num, cpu_id) == 0) {
+            continue;
+        }
+        write!(buf, "{irq_num:>3}:").unwrap();
+        for cpu_id in 0..axconfig::SMP {
+            write!(buf, " {:>10}", irq_count(irq_num, cpu_id)).unwrap();
+        }
+        buf.push('\n');
+    }
+    Ok(buf)
+}
+
+/// Generates `/proc/net/tcp` in the format of Linux, with the fields not
+/// tracked as 0.
+#[cfg(feature = "net")]
+fn net_tcp() -> VfsResult<String> {
+    use core::fmt::Write;
+    use core::net::{IpAddr, SocketAddr};
+
+    // IPv4 addresses in the byte order of the host, as Linux does
+    fn hex_addr(addr: &SocketAddr) -> String {
+        let ip = match addr.ip() {
+            IpAddr::V4(ip) => u32::from_ne_bytes(ip.octets()),
+            IpAddr::V6(_) => 0,
+        };
+        format!("{:08X}:{:04X}", ip, addr.port())
+    }
+
+    let mut buf = String::from(
+        "  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode\n",
+    );
+    for (i, info) in axnet::tcp_sockets().iter().enumerate() {
+        writeln!(
+            buf,
+            "{:>4}: {} {} {:02X} {:08X}:{:08X} 00:00000000 00000000     0        0 0",
+            i,
+            hex_addr(&info.local_addr),
+            hex_addr(&info.peer_addr),
+            info.state as u8,
+            info.send_queue,
+            info.recv_queue,
+        )
+        .unwrap();
+    }
+    Ok(buf)
+}
+
+/// `/proc/<tid>` of each task, and `/proc/self` of the current one.
+#[cfg(feature = "multitask")]
+mod task {
+    use alloc::string::{String, ToString};
+    use alloc::{format, vec::Vec};
+    use core::fmt::Write;
+
+    use axfs::procfs::{self, ProcDir, ProcFile, ProcSymlink, VfsError, VfsNodeRef, VfsResult};
+    use axtask::{TaskInfo, TaskState};
+
+    pub(super) fn add_files() -> VfsResult {
+        procfs::add_dynamic(
+            "",
+            || {
+                axtask::tasks()
+                    .map(|task| task.id().as_u64().to_string())
+                    .collect()
+            },
+            |name| {
+                let id = name.parse().ok()?;
+                axtask::find_task(id)?;
+                Some(task_dir(id))
+            },
+        )?;
+        procfs::add(
+            "self",
+            ProcSymlink::new(|| Ok(axtask::current().id().as_u64().to_string())),
+        )
+    }
+
+    fn task_dir(id: u64) -> VfsNodeRef {
+        let dir = ProcDir::new();
+        dir.add("status", ProcFile::new(move || status(&task_info(id)?)));
+        dir.add("stat", ProcFile::new(move || stat(&task_info(id)?)));
+        dir
+    }
+
+    /// The task may have exited since its directory was looked up.
+    fn task_info(id: u64) -> VfsResult<TaskInfo> {
+        let task = axtask::find_task(id).ok_or(VfsError::NotFound)?;
+        Ok(task.info())
+    }
+
+    fn state(info: &TaskInfo) -> (char, &'static str) {
+        match info.state {
+            TaskState::Running | TaskState::Ready => ('R', "running"),
+            TaskState::Blocked | TaskState::Interruptible => ('S', "sleeping"),
+            TaskState::Exited => ('Z', "zombie"),
+        }
+    }
+
+    /// Generates `/proc/<tid>/status`. Each task is a process of one thread.
+    fn status(info: &TaskInfo) -> VfsResult<String> {
+        let (state, state_name) = state(info);
+        let mut buf = String::new();
+        writeln!(buf, "Name:\t{}", info.name).unwrap();
+        writeln!(buf, "State:\t{state} ({state_name})").unwrap();
+        writeln!(buf, "Tgid:\t{}", info.id).unwrap();
+        writeln!(buf, "Pid:\t{}", info.id).unwrap();
+        writeln!(buf, "PPid:\t0").unwrap();
+        writeln!(buf, "Threads:\t1").unwrap();
+        writeln!(buf, "voluntary_ctxt_switches:\t{}", info.voluntary_switches).unwrap();
+        writeln!(
+            buf,
+            "nonvoluntary_ctxt_switches:\t{}",
+            info.involuntary_switches
+        )
+        .unwrap();
+        Ok(buf)
+    }
+
+    /// Generates `/proc/<tid>/stat` in the format of Linux, up to the CPU
+    /// that the task ran on last time. The fields not tracked are 0.
+    fn stat(info: &TaskInfo) -> VfsResult<String> {
+        // in clock ticks of 100 Hz, as `sysconf(_SC_CLK_TCK)` returns
+        let ticks = |time: core::time::Duration| time.as_millis() / 10;
+        let mut fields: Vec<String> = Vec::new();
+        fields.push(info.id.to_string());
+        fields.push(format!("({})", info.name));
+        fields.push(state(info).0.to_string());
+        fields.extend((4..=13).map(|_| "0".into())); // ppid..cmajflt
+        fields.push(ticks(info.user_time).to_string());
+        fields.push(ticks(info.kernel_time).to_string());
+        fields.extend(["0", "0", "20", "0", "1"].map(String::from)); // cutime..num_threads
+        fields.extend((21..=38).map(|_| "0".into())); // itrealvalue..exit_signal
+        fields.push(info.cpu_id.to_string());
+        Ok(fields.join(" ") + "\n")
+    }
+}
@@ -0,0 +1,176 @@
+//! Timer interrupts in the periodic or the tickless mode.
+//!
+//! In the periodic mode, each CPU takes a timer interrupt every tick. In the
+//! tickless mode (the `tickless` feature), the ticks are kept only while the
+//! scheduler needs them, e.g., to share the CPU between tasks in time slices.
+//! Otherwise, the CPU programs its one-shot timer to the next timer event of
+//! its own, or to the end of the runtime budget of the running task. The ticks
+//! are restarted when the CPU switches tasks or is given a new task.
+
+use core::sync::atomic::{AtomicU64, Ordering};
+use core::time::Duration;
+
+use axhal::time::{monotonic_time_nanos, NANOS_PER_SEC};
+
+const PERIODIC_INTERVAL_NANOS: u64 = NANOS_PER_SEC / axconfig::TICKS_PER_SEC as u64;
+
+/// The deadline of the next periodic tick.
+#[percpu::def_percpu]
+static NEXT_DEADLINE: u64 = 0;
+
+/// When the ticks were stopped, or `u64::MAX` if they are running.
+#[cfg(feature = "tickless")]
+#[percpu::def_percpu]
+static STOPPED_SINCE: u64 = u64::MAX;
+
+struct TickCounters {
+    timer_irqs: AtomicU64,
+    ticks: AtomicU64,
+    stopped_nanos: AtomicU64,
+}
+
+impl TickCounters {
+    const fn new() -> Self {
+        Self {
+            timer_irqs: AtomicU64::new(0),
+            ticks: AtomicU64::new(0),
+            stopped_nanos: AtomicU64::new(0),
+        }
+    }
+}
+
+static TICK_COUNTERS: [TickCounters; axconfig::SMP] =
+    [const { TickCounters::new() }; axconfig::SMP];
+
+/// Timer interrupt statistics of a CPU, returned by [`tick_stats`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TickStats {
+    /// Number of the timer interrupts taken.
+    pub timer_irqs: u64,
+    /// Number of the timer interrupts that were periodic ticks. The others
+    /// woke up the CPU for the timer events while the ticks were stopped.
+    pub ticks: u64,
+    /// Total time for which the ticks were stopped. It is always zero in the
+    /// periodic mode.
+    pub stopped_time: Duration,
+}
+
+/// Returns the timer interrupt statistics of the CPU.
+///
+/// The periodic ticks that the tickless mode saved are about `stopped_time`
+/// multiplied by [`axconfig::TICKS_PER_SEC`].
+///
+/// # Panics
+///
+/// Panics if `cpu_id` is not less than [`axconfig::SMP`].
+pub fn tick_stats(cpu_id: usize) -> TickStats {
+    let counters = &TICK_COUNTERS[cpu_id];
+    TickStats {
+        timer_irqs: counters.timer_irqs.load(Ordering::Relaxed),
+        ticks: counters.ticks.load(Ordering::Relaxed),
+        stopped_time: Duration::from_nanos(counters.stopped_nanos.load(Ordering::Relaxed)),
+    }
+}
+
+fn this_cpu_counters() -> &'static TickCounters {
+    &TICK_COUNTERS[axhal::cpu::this_cpu_id()]
+}
+
+/// Programs the one-shot timer to the next periodic tick.
+fn next_tick(now_ns: u64) {
+    // Safety: we have disabled preemption in IRQ handler.
+    let mut deadline = unsafe { NEXT_DEADLINE.read_current_raw() };
+    if now_ns >= deadline {
+        deadline = now_ns + PERIODIC_INTERVAL_NANOS;
+    }
+    unsafe { NEXT_DEADLINE.write_current_raw(deadline + PERIODIC_INTERVAL_NANOS) };
+    axhal::time::set_oneshot_timer(deadline);
+}
+
+/// Handles a timer interrupt.
+pub(crate) fn on_timer_irq() {
+    let counters = this_cpu_counters();
+    counters.timer_irqs.fetch_add(1, Ordering::Relaxed);
+
+    #[cfg(not(feature = "tickless"))]
+    {
+        counters.ticks.fetch_add(1, Ordering::Relaxed);
+        next_tick(monotonic_time_nanos());
+        #[cfg(feature = "multitask")]
+        axtask::on_timer_tick();
+    }
+
+    #[cfg(feature = "tickless")]
+    {
+        let now_ns = monotonic_time_nanos();
+        if !stopped_until(now_ns) {
+            counters.ticks.fetch_add(1, Ordering::Relaxed);
+        }
+        // The timer events may wake up tasks and set new timers, so the next
+        // deadline is decided after them.
+        #[cfg(feature = "multitask")]
+        axtask::on_timer_tick();
+        update_timer(now_ns);
+    }
+}
+
+/// Marks the ticks as running, and accounts the time for which they were
+/// stopped. Returns whether they were stopped.
+#[cfg(feature = "tickless")]
+fn stopped_until(now_ns: u64) -> bool {
+    // Safety: IRQs are disabled here.
+    let since = unsafe { STOPPED_SINCE.read_current_raw() };
+    if since == u64::MAX {
+        return false;
+    }
+    unsafe { STOPPED_SINCE.write_current_raw(u64::MAX) };
+    this_cpu_counters()
+        .stopped_nanos
+        .fetch_add(now_ns.saturating_sub(since), Ordering::Relaxed);
+    true
+}
+
+/// Programs the one-shot timer to the next periodic tick if the scheduler
+/// needs it, or otherwise to the next timer event or when the scheduler needs
+/// the ticks again, whichever is earlier.
+#[cfg(feature = "tickless")]
+fn update_timer(now_ns: u64) {
+    #[cfg(feature = "multitask")]
+    let (sched_deadline, next_event) = (
+        axtask::next_tick_deadline().map(|deadline| deadline.as_nanos() as u64),
+        axtask::next_timer_deadline().map(|deadline| {
+            (deadline.as_nanos() as u64).saturating_sub(axhal::time::epochoffset_nanos())
+        }),
+    );
+    #[cfg(not(feature = "multitask"))]
+    let (sched_deadline, next_event) = (None::<u64>, None::<u64>);
+
+    if sched_deadline.is_some_and(|deadline| deadline <= now_ns + PERIODIC_INTERVAL_NANOS) {
+        next_tick(now_ns);
+        return;
+    }
+    let deadline = sched_deadline
+        .into_iter()
+        .chain(next_event)
+        .min()
+        .unwrap_or(u64::MAX);
+    if deadline > now_ns + PERIODIC_INTERVAL_NANOS {
+        // Safety: IRQs are disabled here.
+        unsafe { STOPPED_SINCE.write_current_raw(now_ns) };
+    }
+    axhal::time::set_oneshot_timer(deadline);
+}
+
+#[cfg(all(feature = "tickless", feature = "multitask"))]
+struct TicklessIfImpl;
+
+#[cfg(all(feature = "tickless", feature = "multitask"))]
+#[crate_interface::impl_interface]
+impl axtask::TicklessIf for TicklessIfImpl {
+    fn restart_tick() {
+        let now_ns = monotonic_time_nanos();
+        if stopped_until(now_ns) {
+            next_tick(now_ns);
+        }
+    }
+}
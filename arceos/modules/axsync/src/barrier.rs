@@ -0,0 +1,95 @@
+//! A barrier for a group of tasks.
+
+use core::fmt;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use axtask::WaitQueue;
+use kspin::SpinNoIrq;
+
+/// A barrier that blocks a number of tasks until all of them have arrived,
+/// similar to
+/// [`std::sync::Barrier`](https://doc.rust-lang.org/std/sync/struct.Barrier.html).
+///
+/// It can be reused once all the tasks have passed.
+pub struct Barrier {
+    num_tasks: usize,
+    /// Number of tasks that have arrived in the current round.
+    count: SpinNoIrq<usize>,
+    /// Incremented when a round is over, to release the waiting tasks.
+    generation: AtomicUsize,
+    wq: WaitQueue,
+}
+
+/// The result of [`Barrier::wait`], telling whether the task was the leader.
+pub struct BarrierWaitResult(bool);
+
+impl Barrier {
+    /// Creates a new barrier that blocks `n` tasks.
+    ///
+    /// A barrier of zero tasks behaves like one of a single task, i.e., it
+    /// never blocks.
+    pub const fn new(n: usize) -> Self {
+        Self {
+            num_tasks: n,
+            count: SpinNoIrq::new(0),
+            generation: AtomicUsize::new(0),
+            wq: WaitQueue::new(),
+        }
+    }
+
+    /// Blocks the current task until all the tasks have arrived here.
+    ///
+    /// The last task to arrive is the leader, which does not block and wakes
+    /// up the others.
+    pub fn wait(&self) -> BarrierWaitResult {
+        let generation = {
+            let mut count = self.count.lock();
+            let generation = self.generation.load(Ordering::Relaxed);
+            *count += 1;
+            if *count < self.num_tasks {
+                Some(generation)
+            } else {
+                *count = 0;
+                self.generation
+                    .store(generation.wrapping_add(1), Ordering::Release);
+                None
+            }
+        };
+        match generation {
+            Some(generation) => {
+                self.wq
+                    .wait_until(|| self.generation.load(Ordering::Acquire) != generation);
+                BarrierWaitResult(false)
+            }
+            None => {
+                self.wq.notify_all(true);
+                BarrierWaitResult(true)
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Barrier {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Barrier")
+            .field("num_tasks", &self.num_tasks)
+            .finish_non_exhaustive()
+    }
+}
+
+impl BarrierWaitResult {
+    /// Returns whether the task was the leader, i.e., the last one to arrive.
+    ///
+    /// Exactly one task is the leader in each round.
+    pub fn is_leader(&self) -> bool {
+        self.0
+    }
+}
+
+impl fmt::Debug for BarrierWaitResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BarrierWaitResult")
+            .field("is_leader", &self.0)
+            .finish()
+    }
+}
@@ -0,0 +1,133 @@
+//! A condition variable working with [`Mutex`].
+
+use core::fmt;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use axtask::WaitQueue;
+
+use crate::{Mutex, MutexGuard};
+
+/// A condition variable, similar to
+/// [`std::sync::Condvar`](https://doc.rust-lang.org/std/sync/struct.Condvar.html).
+///
+/// It blocks the current task while waiting for an event, with a locked
+/// [`Mutex`] that is released during the wait and locked again before
+/// returning. Spurious wake-ups are possible, so the condition should always
+/// be checked in a loop, or by [`Condvar::wait_while`].
+pub struct Condvar {
+    wq: WaitQueue,
+    /// Incremented on every notification, so that a notification between
+    /// unlocking the mutex and going to sleep is not lost.
+    seq: AtomicUsize,
+}
+
+impl Condvar {
+    /// Creates a new condition variable.
+    pub const fn new() -> Self {
+        Self {
+            wq: WaitQueue::new(),
+            seq: AtomicUsize::new(0),
+        }
+    }
+
+    /// Releases the mutex and blocks the current task until this condition
+    /// variable is notified, then locks the mutex again.
+    pub fn wait<'a, T: ?Sized>(&self, guard: MutexGuard<'a, T>) -> MutexGuard<'a, T> {
+        let seq = self.seq.load(Ordering::Acquire);
+        let mutex = unlock(guard);
+        self.wq
+            .wait_until(|| self.seq.load(Ordering::Acquire) != seq);
+        mutex.lock()
+    }
+
+    /// Blocks the current task while `condition` returns `true`, i.e., until
+    /// it returns `false`, waiting for notifications with the mutex released.
+    pub fn wait_while<'a, T: ?Sized, F>(
+        &self,
+        mut guard: MutexGuard<'a, T>,
+        mut condition: F,
+    ) -> MutexGuard<'a, T>
+    where
+        F: FnMut(&mut T) -> bool,
+    {
+        while condition(&mut *guard) {
+            guard = self.wait(guard);
+        }
+        guard
+    }
+
+    /// Waits on this condition variable like [`Condvar::wait`], but for at
+    /// most the given duration.
+    ///
+    /// Returns the locked guard, and whether it has timed out.
+    #[cfg(feature = "irq")]
+    pub fn wait_timeout<'a, T: ?Sized>(
+        &self,
+        guard: MutexGuard<'a, T>,
+        dur: core::time::Duration,
+    ) -> (MutexGuard<'a, T>, bool) {
+        let seq = self.seq.load(Ordering::Acquire);
+        let mutex = unlock(guard);
+        let timeout = self
+            .wq
+            .wait_timeout_until(dur, || self.seq.load(Ordering::Acquire) != seq);
+        (mutex.lock(), timeout)
+    }
+
+    /// Waits on this condition variable like [`Condvar::wait_while`], but for
+    /// at most the given duration.
+    ///
+    /// Returns the locked guard, and whether it has timed out with the
+    /// condition still `true`.
+    #[cfg(feature = "irq")]
+    pub fn wait_timeout_while<'a, T: ?Sized, F>(
+        &self,
+        mut guard: MutexGuard<'a, T>,
+        dur: core::time::Duration,
+        mut condition: F,
+    ) -> (MutexGuard<'a, T>, bool)
+    where
+        F: FnMut(&mut T) -> bool,
+    {
+        let deadline = axhal::time::wall_time() + dur;
+        while condition(&mut *guard) {
+            let now = axhal::time::wall_time();
+            if now >= deadline {
+                return (guard, true);
+            }
+            guard = self.wait_timeout(guard, deadline - now).0;
+        }
+        (guard, false)
+    }
+
+    /// Wakes up one task blocked on this condition variable, if any.
+    pub fn notify_one(&self) {
+        self.seq.fetch_add(1, Ordering::Release);
+        self.wq.notify_one(true);
+    }
+
+    /// Wakes up all tasks blocked on this condition variable.
+    pub fn notify_all(&self) {
+        self.seq.fetch_add(1, Ordering::Release);
+        self.wq.notify_all(true);
+    }
+}
+
+/// Unlocks the mutex of the guard, and returns the mutex to lock it again.
+fn unlock<'a, T: ?Sized>(guard: MutexGuard<'a, T>) -> &'a Mutex<T> {
+    let mutex = MutexGuard::mutex(&guard);
+    drop(guard);
+    mutex
+}
+
+impl Default for Condvar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for Condvar {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Condvar { .. }")
+    }
+}
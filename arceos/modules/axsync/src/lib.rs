@@ -3,25 +3,69 @@
 //! Currently supported primitives:
 //!
 //! - [`Mutex`]: A mutual exclusion primitive.
+//! - [`ReentrantMutex`]: A mutex that can be locked again by its owner.
+//! - [`RwLock`]: A reader-writer lock that prefers writers, with upgradable
+//!   reads.
+//! - [`Condvar`]: A condition variable working with [`Mutex`].
+//! - [`Semaphore`]: A counting semaphore.
+//! - [`Barrier`]: A barrier for a group of tasks.
+//! - [`Once`] and [`OnceLock`]: One-time initialization.
 //! - mod [`spin`]: spinlocks imported from the [`kspin`] crate.
 //!
+//! All of them except the spinlocks block the current task in an
+//! [`axtask::WaitQueue`], so they are available only with `multitask`.
+//!
 //! # Cargo Features
 //!
 //! - `multitask`: For use in the multi-threaded environments. If the feature is
 //!   not enabled, [`Mutex`] will be an alias of [`spin::SpinNoIrq`]. This
 //!   feature is enabled by default.
+//! - `irq`: Enable the waits with timeouts, such as [`Condvar::wait_timeout`]
+//!   and [`Mutex::try_lock_for`].
+//! - `lockdep`: Report the locks taken in inconsistent orders and the locks
+//!   taken where sleeping is not allowed, with `axtask::lockdep`.
 
 #![cfg_attr(not(test), no_std)]
 #![feature(doc_cfg)]
 
 pub use kspin as spin;
 
+#[cfg(feature = "multitask")]
+mod barrier;
+#[cfg(feature = "multitask")]
+mod condvar;
 #[cfg(feature = "multitask")]
 mod mutex;
+#[cfg(feature = "multitask")]
+mod once;
+#[cfg(feature = "multitask")]
+mod reentrant;
+#[cfg(feature = "multitask")]
+mod rwlock;
+#[cfg(feature = "multitask")]
+mod semaphore;
 
+#[cfg(feature = "multitask")]
+#[doc(cfg(feature = "multitask"))]
+pub use self::barrier::{Barrier, BarrierWaitResult};
+#[cfg(feature = "multitask")]
+#[doc(cfg(feature = "multitask"))]
+pub use self::condvar::Condvar;
 #[cfg(feature = "multitask")]
 #[doc(cfg(feature = "multitask"))]
 pub use self::mutex::{Mutex, MutexGuard};
+#[cfg(feature = "multitask")]
+#[doc(cfg(feature = "multitask"))]
+pub use self::once::{Once, OnceLock};
+#[cfg(feature = "multitask")]
+#[doc(cfg(feature = "multitask"))]
+pub use self::reentrant::{ReentrantMutex, ReentrantMutexGuard};
+#[cfg(feature = "multitask")]
+#[doc(cfg(feature = "multitask"))]
+pub use self::rwlock::{RwLock, RwLockReadGuard, RwLockUpgradableReadGuard, RwLockWriteGuard};
+#[cfg(feature = "multitask")]
+#[doc(cfg(feature = "multitask"))]
+pub use self::semaphore::{Semaphore, SemaphoreGuard};
 
 #[cfg(not(feature = "multitask"))]
 #[doc(cfg(not(feature = "multitask")))]
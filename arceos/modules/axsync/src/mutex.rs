@@ -3,7 +3,11 @@
 use core::cell::UnsafeCell;
 use core::fmt;
 use core::ops::{Deref, DerefMut};
-use core::sync::atomic::{AtomicU64, Ordering};
+#[cfg(debug_assertions)]
+use core::panic::Location;
+#[cfg(debug_assertions)]
+use core::sync::atomic::AtomicPtr;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 
 use axtask::{current, WaitQueue};
 
@@ -13,10 +17,34 @@ use axtask::{current, WaitQueue};
 /// When the mutex is locked, the current task will block and be put into the
 /// wait queue. When the mutex is unlocked, all tasks waiting on the queue
 /// will be woken up.
+///
+/// A real-time or deadline task waiting for the mutex lends its priority to
+/// the owner until the owner unlocks it, see [`axtask::wait_for_lock`].
+///
+/// If the owner exits without unlocking the mutex, e.g., killed by
+/// [`axtask::request_exit`] or by a panic, the mutex is unlocked and
+/// *poisoned* like the `std` one, see [`Mutex::is_poisoned`].
+///
+/// Locking the mutex again by its owner panics. With debug assertions, the
+/// panic message also shows where the owner has locked it.
 pub struct Mutex<T: ?Sized> {
+    raw: RawMutex,
+    data: UnsafeCell<T>,
+}
+
+/// The states of a [`Mutex`] apart from the data, so that they can be found
+/// by [`axtask`] without the type of the data.
+struct RawMutex {
     wq: WaitQueue,
+    /// The ID of the owner task, which has recorded the address of the mutex
+    /// by [`axtask::own_lock`], or 0 if it is unlocked.
     owner_id: AtomicU64,
-    data: UnsafeCell<T>,
+    /// Where the owner has locked the mutex.
+    #[cfg(debug_assertions)]
+    site: AtomicPtr<Location<'static>>,
+    poisoned: AtomicBool,
+    #[cfg(feature = "lockdep")]
+    dep_map: axtask::lockdep::LockdepMap,
 }
 
 /// A guard that provides mutable data access.
@@ -31,13 +59,99 @@ pub struct MutexGuard<'a, T: ?Sized + 'a> {
 unsafe impl<T: ?Sized + Send> Sync for Mutex<T> {}
 unsafe impl<T: ?Sized + Send> Send for Mutex<T> {}
 
+impl RawMutex {
+    const fn new() -> Self {
+        Self {
+            wq: WaitQueue::new(),
+            owner_id: AtomicU64::new(0),
+            #[cfg(debug_assertions)]
+            site: AtomicPtr::new(core::ptr::null_mut()),
+            poisoned: AtomicBool::new(false),
+            #[cfg(feature = "lockdep")]
+            dep_map: axtask::lockdep::LockdepMap::new(),
+        }
+    }
+
+    fn addr(&self) -> usize {
+        self as *const _ as usize
+    }
+
+    fn is_locked(&self) -> bool {
+        self.owner_id.load(Ordering::Relaxed) != 0
+    }
+
+    /// Records the current task as the owner, which has just locked it.
+    #[cfg_attr(debug_assertions, track_caller)]
+    fn set_owner(&self) {
+        #[cfg(debug_assertions)]
+        self.site
+            .store(Location::caller() as *const _ as *mut _, Ordering::Relaxed);
+        axtask::own_lock(self.addr(), Self::abandon);
+    }
+
+    /// Prepares to wait for the owner `owner_id` to unlock it.
+    fn before_wait(&self, owner_id: u64) {
+        let curr = current();
+        if owner_id == curr.id().as_u64() {
+            #[cfg(debug_assertions)]
+            if let Some(site) = unsafe { self.site.load(Ordering::Relaxed).as_ref() } {
+                panic!(
+                    "{} tried to acquire mutex it already owns, which was locked at {}.",
+                    curr.id_name(),
+                    site
+                );
+            }
+            panic!("{} tried to acquire mutex it already owns.", curr.id_name());
+        }
+        // Keep the owner from being preempted by the tasks of lower
+        // priorities than us, which would delay us as well.
+        axtask::wait_for_lock(self.addr(), owner_id);
+    }
+
+    fn unlock(&self) {
+        #[cfg(feature = "lockdep")]
+        axtask::lockdep::lock_release(&self.dep_map);
+        let curr = current();
+        axtask::disown_lock(curr.as_task_ref(), self.addr());
+        let owner_id = self.owner_id.swap(0, Ordering::Release);
+        assert_eq!(
+            owner_id,
+            curr.id().as_u64(),
+            "{} tried to release mutex it doesn't own",
+            curr.id_name()
+        );
+        self.wq.notify_one(true);
+    }
+
+    /// Unlocks and poisons the mutex at `addr`, as its owner is exiting
+    /// without unlocking it.
+    ///
+    /// The mutex is still there, as it is disowned when dropped.
+    fn abandon(addr: usize) {
+        let raw = unsafe { &*(addr as *const Self) };
+        raw.poisoned.store(true, Ordering::Relaxed);
+        raw.unlock();
+    }
+}
+
+impl Drop for RawMutex {
+    fn drop(&mut self) {
+        // Locked by a forgotten guard, maybe of another task.
+        let owner_id = *self.owner_id.get_mut();
+        if owner_id != 0 {
+            if let Some(owner) = axtask::find_task(owner_id) {
+                axtask::disown_lock(&owner, self.addr());
+            }
+        }
+    }
+}
+
 impl<T> Mutex<T> {
     /// Creates a new [`Mutex`] wrapping the supplied data.
     #[inline(always)]
     pub const fn new(data: T) -> Self {
         Self {
-            wq: WaitQueue::new(),
-            owner_id: AtomicU64::new(0),
+            raw: RawMutex::new(),
             data: UnsafeCell::new(data),
         }
     }
@@ -61,19 +175,28 @@ impl<T: ?Sized> Mutex<T> {
     /// the instant it is called. Do not use it for synchronization purposes. However, it may be useful as a heuristic.
     #[inline(always)]
     pub fn is_locked(&self) -> bool {
-        self.owner_id.load(Ordering::Relaxed) != 0
+        self.raw.is_locked()
     }
 
     /// Locks the [`Mutex`] and returns a guard that permits access to the inner data.
     ///
     /// The returned value may be dereferenced for data access
     /// and the lock will be dropped when the guard falls out of scope.
+    ///
+    /// It is locked even if the mutex is poisoned.
+    #[cfg_attr(any(debug_assertions, feature = "lockdep"), track_caller)]
     pub fn lock(&self) -> MutexGuard<T> {
+        #[cfg(feature = "lockdep")]
+        {
+            axtask::lockdep::might_sleep();
+            axtask::lockdep::lock_acquire(&self.raw.dep_map, false);
+        }
         let current_id = current().id().as_u64();
+        let mut waited = false;
         loop {
             // Can fail to lock even if the spinlock is not locked. May be more efficient than `try_lock`
             // when called in a loop.
-            match self.owner_id.compare_exchange_weak(
+            match self.raw.owner_id.compare_exchange_weak(
                 0,
                 current_id,
                 Ordering::Acquire,
@@ -81,17 +204,17 @@ impl<T: ?Sized> Mutex<T> {
             ) {
                 Ok(_) => break,
                 Err(owner_id) => {
-                    assert_ne!(
-                        owner_id,
-                        current_id,
-                        "{} tried to acquire mutex it already owns.",
-                        current().id_name()
-                    );
+                    self.raw.before_wait(owner_id);
+                    waited = true;
                     // Wait until the lock looks unlocked before retrying
-                    self.wq.wait_until(|| !self.is_locked());
+                    self.raw.wq.wait_until(|| !self.is_locked());
                 }
             }
         }
+        if waited {
+            axtask::end_lock_wait();
+        }
+        self.raw.set_owner();
         MutexGuard {
             lock: self,
             data: unsafe { &mut *self.data.get() },
@@ -100,15 +223,20 @@ impl<T: ?Sized> Mutex<T> {
 
     /// Try to lock this [`Mutex`], returning a lock guard if successful.
     #[inline(always)]
+    #[cfg_attr(any(debug_assertions, feature = "lockdep"), track_caller)]
     pub fn try_lock(&self) -> Option<MutexGuard<T>> {
         let current_id = current().id().as_u64();
         // The reason for using a strong compare_exchange is explained here:
         // https://github.com/Amanieu/parking_lot/pull/207#issuecomment-575869107
         if self
+            .raw
             .owner_id
             .compare_exchange(0, current_id, Ordering::Acquire, Ordering::Relaxed)
             .is_ok()
         {
+            #[cfg(feature = "lockdep")]
+            axtask::lockdep::lock_acquire(&self.raw.dep_map, true);
+            self.raw.set_owner();
             Some(MutexGuard {
                 lock: self,
                 data: unsafe { &mut *self.data.get() },
@@ -118,6 +246,61 @@ impl<T: ?Sized> Mutex<T> {
         }
     }
 
+    /// Tries to lock this [`Mutex`], blocking the current task for at most
+    /// the given duration. Returns a lock guard if successful.
+    #[cfg(feature = "irq")]
+    #[cfg_attr(any(debug_assertions, feature = "lockdep"), track_caller)]
+    pub fn try_lock_for(&self, timeout: core::time::Duration) -> Option<MutexGuard<T>> {
+        let deadline = axhal::time::wall_time() + timeout;
+        let mut waited = false;
+        loop {
+            let guard = self.try_lock();
+            let now = axhal::time::wall_time();
+            if guard.is_some() || now >= deadline {
+                if waited {
+                    axtask::end_lock_wait();
+                }
+                return guard;
+            }
+            let owner_id = self.raw.owner_id.load(Ordering::Relaxed);
+            if owner_id != 0 {
+                self.raw.before_wait(owner_id);
+                waited = true;
+                self.raw
+                    .wq
+                    .wait_timeout_until(deadline - now, || !self.is_locked());
+            }
+        }
+    }
+
+    /// Returns whether the mutex is poisoned, i.e., a task has exited without
+    /// unlocking it, so the data may be inconsistent.
+    #[inline(always)]
+    pub fn is_poisoned(&self) -> bool {
+        self.raw.poisoned.load(Ordering::Relaxed)
+    }
+
+    /// Clears the poisoned state of the mutex, e.g., after the data has been
+    /// recovered.
+    #[inline(always)]
+    pub fn clear_poison(&self) {
+        self.raw.poisoned.store(false, Ordering::Relaxed);
+    }
+
+    /// Creates a guard for the [`Mutex`] that the current task has locked,
+    /// e.g., by a guard that has been forgotten.
+    ///
+    /// # Safety
+    ///
+    /// The mutex must be held by the current task, and no other guard of it
+    /// may be alive.
+    pub unsafe fn make_guard_unchecked(&self) -> MutexGuard<T> {
+        MutexGuard {
+            lock: self,
+            data: self.data.get(),
+        }
+    }
+
     /// Force unlock the [`Mutex`].
     ///
     /// # Safety
@@ -126,14 +309,7 @@ impl<T: ?Sized> Mutex<T> {
     /// thread. However, this can be useful in some instances for exposing
     /// the lock to FFI that doesn’t know how to deal with RAII.
     pub unsafe fn force_unlock(&self) {
-        let owner_id = self.owner_id.swap(0, Ordering::Release);
-        assert_eq!(
-            owner_id,
-            current().id().as_u64(),
-            "{} tried to release mutex it doesn't own",
-            current().id_name()
-        );
-        self.wq.notify_one(true);
+        self.raw.unlock();
     }
 
     /// Returns a mutable reference to the underlying data.
@@ -167,6 +343,16 @@ impl<T: ?Sized + fmt::Debug> fmt::Debug for Mutex<T> {
     }
 }
 
+impl<'a, T: ?Sized> MutexGuard<'a, T> {
+    /// Returns the mutex that the guard has locked.
+    ///
+    /// It is an associated function, so that it does not hide the methods of
+    /// the data.
+    pub fn mutex(this: &Self) -> &'a Mutex<T> {
+        this.lock
+    }
+}
+
 impl<'a, T: ?Sized> Deref for MutexGuard<'a, T> {
     type Target = T;
     #[inline(always)]
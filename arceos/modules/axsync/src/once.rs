@@ -0,0 +1,183 @@
+//! One-time initialization.
+
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::mem::MaybeUninit;
+use core::sync::atomic::{AtomicU8, Ordering};
+
+use axtask::WaitQueue;
+
+const INCOMPLETE: u8 = 0;
+const RUNNING: u8 = 1;
+const COMPLETE: u8 = 2;
+
+/// A cell that can be written only once, similar to
+/// [`std::sync::OnceLock`](https://doc.rust-lang.org/std/sync/struct.OnceLock.html).
+///
+/// While one task runs the initializer of [`OnceLock::get_or_init`], the
+/// other tasks trying to initialize it block until it completes.
+pub struct OnceLock<T> {
+    state: AtomicU8,
+    wq: WaitQueue,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+/// A synchronization primitive to run a one-time global initialization,
+/// similar to [`std::sync::Once`](https://doc.rust-lang.org/std/sync/struct.Once.html).
+pub struct Once(OnceLock<()>);
+
+// Same unsafe impls as `std::sync::OnceLock`
+unsafe impl<T: Send> Send for OnceLock<T> {}
+unsafe impl<T: Send + Sync> Sync for OnceLock<T> {}
+
+impl<T> OnceLock<T> {
+    /// Creates a new empty cell.
+    pub const fn new() -> Self {
+        Self {
+            state: AtomicU8::new(INCOMPLETE),
+            wq: WaitQueue::new(),
+            value: UnsafeCell::new(MaybeUninit::uninit()),
+        }
+    }
+
+    fn is_complete(&self) -> bool {
+        self.state.load(Ordering::Acquire) == COMPLETE
+    }
+
+    /// Returns a reference to the value, or `None` if the cell is empty or
+    /// being initialized.
+    pub fn get(&self) -> Option<&T> {
+        if self.is_complete() {
+            Some(unsafe { (*self.value.get()).assume_init_ref() })
+        } else {
+            None
+        }
+    }
+
+    /// Returns a mutable reference to the value, or `None` if the cell is
+    /// empty.
+    pub fn get_mut(&mut self) -> Option<&mut T> {
+        if *self.state.get_mut() == COMPLETE {
+            Some(unsafe { self.value.get_mut().assume_init_mut() })
+        } else {
+            None
+        }
+    }
+
+    /// Sets the value of the cell, blocking the current task if another task
+    /// is initializing it.
+    ///
+    /// Returns `Err(value)` if the cell has been initialized.
+    pub fn set(&self, value: T) -> Result<(), T> {
+        let mut value = Some(value);
+        self.get_or_init(|| value.take().unwrap());
+        match value {
+            None => Ok(()),
+            Some(value) => Err(value),
+        }
+    }
+
+    /// Gets the value of the cell, initializing it with `f` if it is empty.
+    ///
+    /// If another task is initializing it, the current task blocks until that
+    /// completes. Calling it again from `f` on the same cell deadlocks.
+    pub fn get_or_init<F>(&self, f: F) -> &T
+    where
+        F: FnOnce() -> T,
+    {
+        if let Some(value) = self.get() {
+            return value;
+        }
+        match self
+            .state
+            .compare_exchange(INCOMPLETE, RUNNING, Ordering::Acquire, Ordering::Acquire)
+        {
+            Ok(_) => {
+                unsafe { (*self.value.get()).write(f()) };
+                self.state.store(COMPLETE, Ordering::Release);
+                self.wq.notify_all(true);
+            }
+            Err(_) => self.wq.wait_until(|| self.is_complete()),
+        }
+        unsafe { (*self.value.get()).assume_init_ref() }
+    }
+
+    /// Consumes the cell, returning the value if it has been initialized.
+    pub fn into_inner(mut self) -> Option<T> {
+        self.take()
+    }
+
+    /// Takes the value out of the cell, leaving it empty.
+    pub fn take(&mut self) -> Option<T> {
+        if *self.state.get_mut() == COMPLETE {
+            *self.state.get_mut() = INCOMPLETE;
+            Some(unsafe { self.value.get_mut().assume_init_read() })
+        } else {
+            None
+        }
+    }
+}
+
+impl<T> Default for OnceLock<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for OnceLock<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.get() {
+            Some(value) => f.debug_tuple("OnceLock").field(value).finish(),
+            None => f.write_str("OnceLock(<uninit>)"),
+        }
+    }
+}
+
+impl<T> From<T> for OnceLock<T> {
+    fn from(value: T) -> Self {
+        let cell = Self::new();
+        let _ = cell.set(value);
+        cell
+    }
+}
+
+impl<T> Drop for OnceLock<T> {
+    fn drop(&mut self) {
+        if *self.state.get_mut() == COMPLETE {
+            unsafe { self.value.get_mut().assume_init_drop() };
+        }
+    }
+}
+
+impl Once {
+    /// Creates a new [`Once`] value.
+    pub const fn new() -> Self {
+        Self(OnceLock::new())
+    }
+
+    /// Runs the initialization `f` if it is the first call, otherwise blocks
+    /// the current task until the first call has completed.
+    pub fn call_once<F>(&self, f: F)
+    where
+        F: FnOnce(),
+    {
+        self.0.get_or_init(f);
+    }
+
+    /// Returns whether a call of [`Once::call_once`] has completed.
+    pub fn is_completed(&self) -> bool {
+        self.0.is_complete()
+    }
+}
+
+impl Default for Once {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for Once {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Once").finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,171 @@
+//! A reentrant mutex.
+
+use core::cell::Cell;
+use core::fmt;
+use core::marker::PhantomData;
+use core::ops::Deref;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use axtask::current;
+
+use crate::Mutex;
+
+/// A mutex that can be locked again by the task that holds it, similar to
+/// [`std::sync::ReentrantLock`](https://doc.rust-lang.org/std/sync/struct.ReentrantLock.html).
+///
+/// It is unlocked when all the guards of the owner are dropped. The guards
+/// only give shared references to the data, as there may be several of them,
+/// so the data usually needs a [`Cell`] or a [`RefCell`] inside.
+///
+/// It is built on a [`Mutex`], so the other tasks waiting for it lend their
+/// priorities to the owner as well.
+///
+/// [`RefCell`]: core::cell::RefCell
+pub struct ReentrantMutex<T: ?Sized> {
+    mutex: Mutex<()>,
+    owner_id: AtomicU64,
+    /// The number of the guards of the owner, only accessed by the owner.
+    count: Cell<usize>,
+    data: T,
+}
+
+/// A guard that provides shared data access of a [`ReentrantMutex`].
+///
+/// When the last guard of the owner falls out of scope, the lock is released.
+pub struct ReentrantMutexGuard<'a, T: ?Sized + 'a> {
+    lock: &'a ReentrantMutex<T>,
+    /// The guard must be dropped by the owner task.
+    _not_send: PhantomData<*const ()>,
+}
+
+// Same unsafe impls as `std::sync::ReentrantLock`
+unsafe impl<T: ?Sized + Send> Send for ReentrantMutex<T> {}
+unsafe impl<T: ?Sized + Send> Sync for ReentrantMutex<T> {}
+
+unsafe impl<T: ?Sized + Sync> Sync for ReentrantMutexGuard<'_, T> {}
+
+impl<T> ReentrantMutex<T> {
+    /// Creates a new [`ReentrantMutex`] wrapping the supplied data.
+    pub const fn new(data: T) -> Self {
+        Self {
+            mutex: Mutex::new(()),
+            owner_id: AtomicU64::new(0),
+            count: Cell::new(0),
+            data,
+        }
+    }
+
+    /// Consumes this [`ReentrantMutex`] and unwraps the underlying data.
+    pub fn into_inner(self) -> T {
+        self.data
+    }
+}
+
+impl<T: ?Sized> ReentrantMutex<T> {
+    fn is_owned_by_current(&self) -> bool {
+        self.owner_id.load(Ordering::Relaxed) == current().id().as_u64()
+    }
+
+    /// Records the current task as the owner, which has just locked the
+    /// inner mutex.
+    fn set_owner(&self) {
+        self.owner_id
+            .store(current().id().as_u64(), Ordering::Relaxed);
+        // Left over by an owner that exited with guards.
+        self.count.set(0);
+    }
+
+    /// Increments the count of the guards of the current task, which owns the
+    /// lock.
+    fn new_guard(&self) -> ReentrantMutexGuard<T> {
+        let count = self.count.get().checked_add(1);
+        self.count
+            .set(count.expect("lock count overflow in reentrant mutex"));
+        ReentrantMutexGuard {
+            lock: self,
+            _not_send: PhantomData,
+        }
+    }
+
+    /// Locks the [`ReentrantMutex`], blocking the current task until it can be
+    /// acquired, unless the current task holds it already.
+    #[cfg_attr(any(debug_assertions, feature = "lockdep"), track_caller)]
+    pub fn lock(&self) -> ReentrantMutexGuard<T> {
+        if !self.is_owned_by_current() {
+            core::mem::forget(self.mutex.lock());
+            self.set_owner();
+        }
+        self.new_guard()
+    }
+
+    /// Tries to lock the [`ReentrantMutex`], returning a guard if it is
+    /// unlocked or held by the current task.
+    #[cfg_attr(any(debug_assertions, feature = "lockdep"), track_caller)]
+    pub fn try_lock(&self) -> Option<ReentrantMutexGuard<T>> {
+        if !self.is_owned_by_current() {
+            core::mem::forget(self.mutex.try_lock()?);
+            self.set_owner();
+        }
+        Some(self.new_guard())
+    }
+
+    /// Returns whether the underlying [`Mutex`] is poisoned, i.e., the owner
+    /// has exited without dropping all its guards.
+    pub fn is_poisoned(&self) -> bool {
+        self.mutex.is_poisoned()
+    }
+
+    /// Clears the poisoned state of the underlying [`Mutex`].
+    pub fn clear_poison(&self) {
+        self.mutex.clear_poison();
+    }
+
+    /// Returns a mutable reference to the underlying data.
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.data
+    }
+}
+
+impl<T: Default> Default for ReentrantMutex<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for ReentrantMutex<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.try_lock() {
+            Some(guard) => f
+                .debug_struct("ReentrantMutex")
+                .field("data", &&*guard)
+                .finish(),
+            None => f.write_str("ReentrantMutex { <locked> }"),
+        }
+    }
+}
+
+impl<T: ?Sized> Deref for ReentrantMutexGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.lock.data
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for ReentrantMutexGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: ?Sized> Drop for ReentrantMutexGuard<'_, T> {
+    /// Releases the lock if it is the last guard of the owner.
+    fn drop(&mut self) {
+        let lock = self.lock;
+        let count = lock.count.get() - 1;
+        lock.count.set(count);
+        if count == 0 {
+            lock.owner_id.store(0, Ordering::Relaxed);
+            unsafe { lock.mutex.force_unlock() };
+        }
+    }
+}
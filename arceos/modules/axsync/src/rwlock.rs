@@ -0,0 +1,490 @@
+//! A sleeping reader-writer lock that prefers writers.
+
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use axtask::WaitQueue;
+
+/// Held by a writer.
+const WRITER: usize = 1;
+/// Held by an upgradable reader.
+const UPGRADABLE: usize = 1 << 1;
+/// One reader, counted from this bit.
+const READER: usize = 1 << 2;
+
+/// A reader-writer lock, similar to
+/// [`std::sync::RwLock`](https://doc.rust-lang.org/std/sync/struct.RwLock.html).
+///
+/// Writers are preferred: once a writer is waiting, new readers block until
+/// it has released the lock, so that a stream of readers cannot starve it.
+///
+/// Besides the shared and the exclusive accesses, the lock can be held by one
+/// upgradable reader at a time, along with the other readers. An upgradable
+/// reader can become a writer without releasing the lock, see
+/// [`RwLockUpgradableReadGuard::upgrade`].
+pub struct RwLock<T: ?Sized> {
+    /// The [`WRITER`] and [`UPGRADABLE`] bits and the number of readers.
+    state: AtomicUsize,
+    /// Number of writers waiting for the lock.
+    writers_waiting: AtomicUsize,
+    /// Readers and upgradable readers waiting for the lock.
+    read_wq: WaitQueue,
+    /// Writers waiting for the lock.
+    write_wq: WaitQueue,
+    /// The upgradable reader waiting for the other readers to leave.
+    upgrade_wq: WaitQueue,
+    #[cfg(feature = "lockdep")]
+    dep_map: axtask::lockdep::LockdepMap,
+    data: UnsafeCell<T>,
+}
+
+/// A guard that provides shared data access.
+///
+/// When the guard falls out of scope it will release the shared access.
+pub struct RwLockReadGuard<'a, T: ?Sized + 'a> {
+    lock: &'a RwLock<T>,
+}
+
+/// A guard that provides mutable data access.
+///
+/// When the guard falls out of scope it will release the lock.
+pub struct RwLockWriteGuard<'a, T: ?Sized + 'a> {
+    lock: &'a RwLock<T>,
+}
+
+/// A guard that provides shared data access, and can be upgraded to a
+/// [`RwLockWriteGuard`].
+///
+/// When the guard falls out of scope it will release the shared access.
+pub struct RwLockUpgradableReadGuard<'a, T: ?Sized + 'a> {
+    lock: &'a RwLock<T>,
+}
+
+// Same unsafe impls as `std::sync::RwLock`
+unsafe impl<T: ?Sized + Send> Send for RwLock<T> {}
+unsafe impl<T: ?Sized + Send + Sync> Sync for RwLock<T> {}
+
+unsafe impl<T: ?Sized + Sync> Sync for RwLockReadGuard<'_, T> {}
+unsafe impl<T: ?Sized + Sync> Sync for RwLockWriteGuard<'_, T> {}
+unsafe impl<T: ?Sized + Sync> Sync for RwLockUpgradableReadGuard<'_, T> {}
+
+impl<T> RwLock<T> {
+    /// Creates a new [`RwLock`] wrapping the supplied data.
+    #[inline(always)]
+    pub const fn new(data: T) -> Self {
+        Self {
+            state: AtomicUsize::new(0),
+            writers_waiting: AtomicUsize::new(0),
+            read_wq: WaitQueue::new(),
+            write_wq: WaitQueue::new(),
+            upgrade_wq: WaitQueue::new(),
+            #[cfg(feature = "lockdep")]
+            dep_map: axtask::lockdep::LockdepMap::new(),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    /// Consumes this [`RwLock`] and unwraps the underlying data.
+    #[inline(always)]
+    pub fn into_inner(self) -> T {
+        // We know statically that there are no outstanding references to
+        // `self` so there's no need to lock.
+        let RwLock { data, .. } = self;
+        data.into_inner()
+    }
+}
+
+impl<T: ?Sized> RwLock<T> {
+    /// Returns `true` if the lock is currently held by a writer.
+    ///
+    /// The result is only a heuristic, as it may be out of date the instant
+    /// it is returned.
+    #[inline(always)]
+    pub fn is_locked_exclusive(&self) -> bool {
+        self.state.load(Ordering::Relaxed) & WRITER != 0
+    }
+
+    /// Returns the number of readers holding the lock, not counting the
+    /// upgradable reader.
+    ///
+    /// The result is only a heuristic, as it may be out of date the instant
+    /// it is returned.
+    #[inline(always)]
+    pub fn reader_count(&self) -> usize {
+        self.state.load(Ordering::Relaxed) / READER
+    }
+
+    /// Whether a new reader has to wait, for a writer holding the lock or
+    /// waiting for it.
+    fn read_blocked(&self) -> bool {
+        self.state.load(Ordering::Relaxed) & WRITER != 0
+            || self.writers_waiting.load(Ordering::Relaxed) != 0
+    }
+
+    /// Records the acquisition for the `lockdep` feature, before waiting for
+    /// the lock.
+    #[cfg_attr(feature = "lockdep", track_caller)]
+    fn acquire(&self, _trylock: bool) {
+        #[cfg(feature = "lockdep")]
+        {
+            if !_trylock {
+                axtask::lockdep::might_sleep();
+            }
+            axtask::lockdep::lock_acquire(&self.dep_map, _trylock);
+        }
+    }
+
+    /// Records the release for the `lockdep` feature.
+    fn release(&self) {
+        #[cfg(feature = "lockdep")]
+        axtask::lockdep::lock_release(&self.dep_map);
+    }
+
+    /// Locks this [`RwLock`] with shared read access, blocking the current
+    /// task until it can be acquired.
+    #[cfg_attr(feature = "lockdep", track_caller)]
+    pub fn read(&self) -> RwLockReadGuard<T> {
+        self.acquire(false);
+        while !self.raw_try_read() {
+            self.read_wq.wait_until(|| !self.read_blocked());
+        }
+        RwLockReadGuard { lock: self }
+    }
+
+    /// Tries to lock this [`RwLock`] with shared read access, returning a
+    /// guard if successful.
+    ///
+    /// It fails if a writer holds the lock, or is waiting for it.
+    #[cfg_attr(feature = "lockdep", track_caller)]
+    pub fn try_read(&self) -> Option<RwLockReadGuard<T>> {
+        if self.raw_try_read() {
+            self.acquire(true);
+            Some(RwLockReadGuard { lock: self })
+        } else {
+            None
+        }
+    }
+
+    fn raw_try_read(&self) -> bool {
+        let mut state = self.state.load(Ordering::Relaxed);
+        loop {
+            if state & WRITER != 0 || self.writers_waiting.load(Ordering::Relaxed) != 0 {
+                return false;
+            }
+            match self.state.compare_exchange_weak(
+                state,
+                state + READER,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return true,
+                Err(s) => state = s,
+            }
+        }
+    }
+
+    /// Locks this [`RwLock`] with upgradable read access, blocking the
+    /// current task until it can be acquired.
+    ///
+    /// Only one upgradable reader can hold the lock at a time, but the other
+    /// readers are still allowed.
+    #[cfg_attr(feature = "lockdep", track_caller)]
+    pub fn upgradable_read(&self) -> RwLockUpgradableReadGuard<T> {
+        self.acquire(false);
+        while !self.raw_try_upgradable_read() {
+            self.read_wq.wait_until(|| {
+                !self.read_blocked() && self.state.load(Ordering::Relaxed) & UPGRADABLE == 0
+            });
+        }
+        RwLockUpgradableReadGuard { lock: self }
+    }
+
+    /// Tries to lock this [`RwLock`] with upgradable read access, returning a
+    /// guard if successful.
+    #[cfg_attr(feature = "lockdep", track_caller)]
+    pub fn try_upgradable_read(&self) -> Option<RwLockUpgradableReadGuard<T>> {
+        if self.raw_try_upgradable_read() {
+            self.acquire(true);
+            Some(RwLockUpgradableReadGuard { lock: self })
+        } else {
+            None
+        }
+    }
+
+    fn raw_try_upgradable_read(&self) -> bool {
+        let mut state = self.state.load(Ordering::Relaxed);
+        loop {
+            if state & (WRITER | UPGRADABLE) != 0
+                || self.writers_waiting.load(Ordering::Relaxed) != 0
+            {
+                return false;
+            }
+            match self.state.compare_exchange_weak(
+                state,
+                state | UPGRADABLE,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return true,
+                Err(s) => state = s,
+            }
+        }
+    }
+
+    /// Locks this [`RwLock`] with exclusive write access, blocking the current
+    /// task until it can be acquired.
+    ///
+    /// New readers are held off while the current task is waiting.
+    #[cfg_attr(feature = "lockdep", track_caller)]
+    pub fn write(&self) -> RwLockWriteGuard<T> {
+        self.acquire(false);
+        if !self.raw_try_write() {
+            self.writers_waiting.fetch_add(1, Ordering::Relaxed);
+            loop {
+                self.write_wq
+                    .wait_until(|| self.state.load(Ordering::Relaxed) == 0);
+                if self.raw_try_write() {
+                    self.writers_waiting.fetch_sub(1, Ordering::Relaxed);
+                    break;
+                }
+            }
+        }
+        RwLockWriteGuard { lock: self }
+    }
+
+    /// Tries to lock this [`RwLock`] with exclusive write access, returning a
+    /// guard if successful.
+    #[cfg_attr(feature = "lockdep", track_caller)]
+    pub fn try_write(&self) -> Option<RwLockWriteGuard<T>> {
+        if self.raw_try_write() {
+            self.acquire(true);
+            Some(RwLockWriteGuard { lock: self })
+        } else {
+            None
+        }
+    }
+
+    fn raw_try_write(&self) -> bool {
+        self.state
+            .compare_exchange(0, WRITER, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+    }
+
+    /// Force releases a shared read access, e.g., when its guard has been
+    /// forgotten.
+    ///
+    /// # Safety
+    ///
+    /// The current task must hold a read access without a guard.
+    pub unsafe fn force_read_unlock(&self) {
+        self.release();
+        self.read_unlock();
+    }
+
+    /// Force releases the exclusive write access, e.g., when its guard has
+    /// been forgotten.
+    ///
+    /// # Safety
+    ///
+    /// The current task must hold the write access without a guard.
+    pub unsafe fn force_write_unlock(&self) {
+        self.release();
+        self.write_unlock();
+    }
+
+    /// Returns a mutable reference to the underlying data.
+    ///
+    /// Since this call borrows the [`RwLock`] mutably, no actual locking
+    /// needs to take place.
+    #[inline(always)]
+    pub fn get_mut(&mut self) -> &mut T {
+        // We know statically that there are no other references to `self`, so
+        // there's no need to lock the inner lock.
+        unsafe { &mut *self.data.get() }
+    }
+
+    fn read_unlock(&self) {
+        let state = self.state.fetch_sub(READER, Ordering::Release) - READER;
+        if state == UPGRADABLE {
+            self.upgrade_wq.notify_one(true);
+        } else if state == 0 {
+            self.write_wq.notify_one(true);
+        }
+    }
+
+    fn upgradable_unlock(&self) {
+        let state = self.state.fetch_and(!UPGRADABLE, Ordering::Release) & !UPGRADABLE;
+        if state == 0 {
+            self.write_wq.notify_one(true);
+        }
+        self.read_wq.notify_all(true);
+    }
+
+    fn write_unlock(&self) {
+        self.state.fetch_and(!WRITER, Ordering::Release);
+        self.wake_after_write();
+    }
+
+    /// Wakes up a waiting writer, or the readers if there are no writers.
+    fn wake_after_write(&self) {
+        if self.writers_waiting.load(Ordering::Relaxed) != 0 {
+            self.write_wq.notify_one(true);
+        } else {
+            self.read_wq.notify_all(true);
+        }
+    }
+}
+
+impl<T: ?Sized + Default> Default for RwLock<T> {
+    #[inline(always)]
+    fn default() -> Self {
+        Self::new(Default::default())
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for RwLock<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.try_read() {
+            Some(guard) => write!(f, "RwLock {{ data: ")
+                .and_then(|()| (*guard).fmt(f))
+                .and_then(|()| write!(f, "}}")),
+            None => write!(f, "RwLock {{ <locked> }}"),
+        }
+    }
+}
+
+impl<'a, T: ?Sized> RwLockWriteGuard<'a, T> {
+    /// Atomically downgrades the write lock into a read lock, without allowing
+    /// any writers to take exclusive access in the meantime.
+    pub fn downgrade(self) -> RwLockReadGuard<'a, T> {
+        let lock = self.lock;
+        core::mem::forget(self);
+        lock.state.store(READER, Ordering::Release);
+        lock.wake_after_write();
+        RwLockReadGuard { lock }
+    }
+}
+
+impl<'a, T: ?Sized> RwLockUpgradableReadGuard<'a, T> {
+    /// Upgrades to exclusive write access, blocking the current task until
+    /// the other readers have released the lock.
+    pub fn upgrade(self) -> RwLockWriteGuard<'a, T> {
+        let lock = self.lock;
+        core::mem::forget(self);
+        loop {
+            if lock
+                .state
+                .compare_exchange(UPGRADABLE, WRITER, Ordering::Acquire, Ordering::Relaxed)
+                .is_ok()
+            {
+                return RwLockWriteGuard { lock };
+            }
+            lock.upgrade_wq
+                .wait_until(|| lock.state.load(Ordering::Relaxed) == UPGRADABLE);
+        }
+    }
+
+    /// Tries to upgrade to exclusive write access, returning the guard back
+    /// if other readers are holding the lock.
+    pub fn try_upgrade(self) -> Result<RwLockWriteGuard<'a, T>, Self> {
+        let lock = self.lock;
+        match lock
+            .state
+            .compare_exchange(UPGRADABLE, WRITER, Ordering::Acquire, Ordering::Relaxed)
+        {
+            Ok(_) => {
+                core::mem::forget(self);
+                Ok(RwLockWriteGuard { lock })
+            }
+            Err(_) => Err(self),
+        }
+    }
+
+    /// Downgrades to plain read access, allowing another upgradable reader to
+    /// take the lock.
+    pub fn downgrade(self) -> RwLockReadGuard<'a, T> {
+        let lock = self.lock;
+        core::mem::forget(self);
+        // Take a read access before releasing the upgradable one, so that no
+        // writer can come in between.
+        lock.state.fetch_add(READER, Ordering::Acquire);
+        lock.upgradable_unlock();
+        RwLockReadGuard { lock }
+    }
+}
+
+impl<T: ?Sized> Deref for RwLockReadGuard<'_, T> {
+    type Target = T;
+    #[inline(always)]
+    fn deref(&self) -> &T {
+        // We know statically that only readers are referencing data
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized> Deref for RwLockUpgradableReadGuard<'_, T> {
+    type Target = T;
+    #[inline(always)]
+    fn deref(&self) -> &T {
+        // We know statically that only readers are referencing data
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized> Deref for RwLockWriteGuard<'_, T> {
+    type Target = T;
+    #[inline(always)]
+    fn deref(&self) -> &T {
+        // We know statically that only we are referencing data
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for RwLockWriteGuard<'_, T> {
+    #[inline(always)]
+    fn deref_mut(&mut self) -> &mut T {
+        // We know statically that only we are referencing data
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for RwLockReadGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for RwLockUpgradableReadGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for RwLockWriteGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: ?Sized> Drop for RwLockReadGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.release();
+        self.lock.read_unlock();
+    }
+}
+
+impl<T: ?Sized> Drop for RwLockUpgradableReadGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.release();
+        self.lock.upgradable_unlock();
+    }
+}
+
+impl<T: ?Sized> Drop for RwLockWriteGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.release();
+        self.lock.write_unlock();
+    }
+}
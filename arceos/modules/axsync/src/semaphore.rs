@@ -0,0 +1,101 @@
+//! A counting semaphore.
+
+use core::fmt;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use axtask::WaitQueue;
+
+/// A counting semaphore.
+///
+/// It holds a number of permits. [`Semaphore::acquire`] takes one, blocking
+/// the current task until one is available, and [`Semaphore::release`] gives
+/// one back.
+pub struct Semaphore {
+    permits: AtomicUsize,
+    wq: WaitQueue,
+}
+
+/// A guard that releases the permit acquired by [`Semaphore::access`] when it
+/// falls out of scope.
+pub struct SemaphoreGuard<'a> {
+    sem: &'a Semaphore,
+}
+
+impl Semaphore {
+    /// Creates a new semaphore with the initial number of permits.
+    pub const fn new(permits: usize) -> Self {
+        Self {
+            permits: AtomicUsize::new(permits),
+            wq: WaitQueue::new(),
+        }
+    }
+
+    /// Returns the number of the available permits.
+    ///
+    /// The result is only a heuristic, as it may be out of date the instant
+    /// it is returned.
+    pub fn available_permits(&self) -> usize {
+        self.permits.load(Ordering::Relaxed)
+    }
+
+    /// Takes a permit, blocking the current task until one is available.
+    pub fn acquire(&self) {
+        while !self.try_acquire() {
+            self.wq
+                .wait_until(|| self.permits.load(Ordering::Relaxed) != 0);
+        }
+    }
+
+    /// Tries to take a permit without blocking, returning whether it
+    /// succeeded.
+    pub fn try_acquire(&self) -> bool {
+        self.permits
+            .fetch_update(Ordering::Acquire, Ordering::Relaxed, |n| n.checked_sub(1))
+            .is_ok()
+    }
+
+    /// Takes a permit like [`Semaphore::acquire`], but for at most the given
+    /// duration.
+    ///
+    /// Returns whether a permit has been taken.
+    #[cfg(feature = "irq")]
+    pub fn acquire_timeout(&self, dur: core::time::Duration) -> bool {
+        let deadline = axhal::time::wall_time() + dur;
+        while !self.try_acquire() {
+            let now = axhal::time::wall_time();
+            if now >= deadline {
+                return false;
+            }
+            self.wq
+                .wait_timeout_until(deadline - now, || self.permits.load(Ordering::Relaxed) != 0);
+        }
+        true
+    }
+
+    /// Takes a permit like [`Semaphore::acquire`], and returns a guard that
+    /// gives it back when dropped.
+    pub fn access(&self) -> SemaphoreGuard {
+        self.acquire();
+        SemaphoreGuard { sem: self }
+    }
+
+    /// Gives back a permit, waking up a task waiting for it.
+    pub fn release(&self) {
+        self.permits.fetch_add(1, Ordering::Release);
+        self.wq.notify_one(true);
+    }
+}
+
+impl fmt::Debug for Semaphore {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Semaphore")
+            .field("permits", &self.available_permits())
+            .finish()
+    }
+}
+
+impl Drop for SemaphoreGuard<'_> {
+    fn drop(&mut self) {
+        self.sem.release();
+    }
+}
@@ -0,0 +1,255 @@
+use core::cell::{Cell, UnsafeCell};
+use core::mem::MaybeUninit;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex as StdMutex, Once as StdOnce};
+
+use axsync::{Barrier, Condvar, Mutex, OnceLock, ReentrantMutex, RwLock, Semaphore};
+use axtask as thread;
+
+static INIT: StdOnce = StdOnce::new();
+static SERIAL: StdMutex<()> = StdMutex::new(());
+
+fn may_interrupt() {
+    // simulate interrupts
+    if rand::random::<u32>() % 3 == 0 {
+        thread::yield_now();
+    }
+}
+
+fn wait_for(counter: &AtomicUsize, n: usize) {
+    while counter.load(Ordering::Acquire) < n {
+        thread::yield_now();
+    }
+}
+
+#[test]
+fn mutex_poison_and_reentrant() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(thread::init_scheduler);
+
+    static M: Mutex<u32> = Mutex::new(0);
+    static R: ReentrantMutex<Cell<u32>> = ReentrantMutex::new(Cell::new(0));
+    static FINISHED: AtomicUsize = AtomicUsize::new(0);
+
+    // The owner exits without unlocking them.
+    let task = thread::spawn(|| {
+        let mut val = M.lock();
+        *val += 1;
+        let outer = R.lock();
+        let inner = R.lock();
+        inner.set(outer.get() + 1);
+        thread::exit(0);
+    });
+    task.join();
+
+    assert!(M.is_poisoned());
+    assert_eq!(*M.lock(), 1);
+    M.clear_poison();
+    assert!(!M.is_poisoned());
+
+    assert!(R.is_poisoned());
+    let outer = R.lock();
+    let inner = R.try_lock().unwrap();
+    assert_eq!(inner.get(), 1);
+    drop(outer);
+    drop(inner);
+    R.clear_poison();
+    thread::spawn(|| {
+        assert!(R.try_lock().is_some());
+        FINISHED.fetch_add(1, Ordering::Release);
+    });
+    wait_for(&FINISHED, 1);
+    println!("Mutex poison and ReentrantMutex test OK");
+}
+
+#[test]
+fn mutex_dropped_by_another_task() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(thread::init_scheduler);
+
+    struct Slot(UnsafeCell<MaybeUninit<Mutex<u32>>>);
+    unsafe impl Sync for Slot {}
+    static SLOT: Slot = Slot(UnsafeCell::new(MaybeUninit::uninit()));
+    static STEP: AtomicUsize = AtomicUsize::new(0);
+
+    let slot = unsafe { &mut *SLOT.0.get() };
+    slot.write(Mutex::new(0));
+    // The owner keeps it locked by a forgotten guard.
+    let task = thread::spawn(|| {
+        core::mem::forget(unsafe { (*SLOT.0.get()).assume_init_ref() }.lock());
+        STEP.store(1, Ordering::Release);
+        wait_for(&STEP, 2);
+    });
+    wait_for(&STEP, 1);
+    // Another mutex takes its place before the owner exits.
+    unsafe { slot.assume_init_drop() };
+    let m = slot.write(Mutex::new(0));
+    STEP.store(2, Ordering::Release);
+    task.join();
+
+    assert!(!m.is_locked());
+    assert!(!m.is_poisoned());
+    println!("Mutex dropped by another task test OK");
+}
+
+#[test]
+fn rwlock_readers_and_writers() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(thread::init_scheduler);
+
+    const NUM_TASKS: usize = 8;
+    const NUM_ITERS: usize = 1_000;
+    // Two halves that the writers keep equal.
+    static RW: RwLock<(usize, usize)> = RwLock::new((0, 0));
+    static FINISHED: AtomicUsize = AtomicUsize::new(0);
+
+    for i in 0..NUM_TASKS {
+        thread::spawn(move || {
+            for _ in 0..NUM_ITERS {
+                match i % 4 {
+                    0 => {
+                        let mut val = RW.write();
+                        val.0 += 1;
+                        may_interrupt();
+                        val.1 += 1;
+                    }
+                    1 => {
+                        let val = RW.upgradable_read();
+                        assert_eq!(val.0, val.1);
+                        may_interrupt();
+                        let mut val = val.upgrade();
+                        val.0 += 1;
+                        may_interrupt();
+                        val.1 += 1;
+                    }
+                    _ => {
+                        let val = RW.read();
+                        may_interrupt();
+                        assert_eq!(val.0, val.1);
+                    }
+                }
+                may_interrupt();
+            }
+            FINISHED.fetch_add(1, Ordering::Release);
+        });
+    }
+
+    wait_for(&FINISHED, NUM_TASKS);
+    let writers = NUM_TASKS / 4 * 2;
+    assert_eq!(*RW.read(), (writers * NUM_ITERS, writers * NUM_ITERS));
+
+    let val = RW.write().downgrade();
+    assert!(RW.try_write().is_none());
+    assert!(RW.try_read().is_some());
+    drop(val);
+    assert!(RW.try_write().is_some());
+    println!("RwLock test OK");
+}
+
+#[test]
+fn condvar_producer_consumer() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(thread::init_scheduler);
+
+    const NUM_ITEMS: usize = 1_000;
+    static QUEUE: Mutex<Vec<usize>> = Mutex::new(Vec::new());
+    static NOT_EMPTY: Condvar = Condvar::new();
+    static FINISHED: AtomicUsize = AtomicUsize::new(0);
+
+    thread::spawn(|| {
+        let mut sum = 0;
+        for _ in 0..NUM_ITEMS {
+            let mut queue = NOT_EMPTY.wait_while(QUEUE.lock(), |queue| queue.is_empty());
+            sum += queue.pop().unwrap();
+            drop(queue);
+            may_interrupt();
+        }
+        assert_eq!(sum, NUM_ITEMS * (NUM_ITEMS - 1) / 2);
+        FINISHED.fetch_add(1, Ordering::Release);
+    });
+
+    for i in 0..NUM_ITEMS {
+        QUEUE.lock().push(i);
+        NOT_EMPTY.notify_one();
+        may_interrupt();
+    }
+    wait_for(&FINISHED, 1);
+    println!("Condvar test OK");
+}
+
+#[test]
+fn semaphore_limits_concurrency() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(thread::init_scheduler);
+
+    const NUM_TASKS: usize = 10;
+    const NUM_PERMITS: usize = 3;
+    static SEM: Semaphore = Semaphore::new(NUM_PERMITS);
+    static INSIDE: AtomicUsize = AtomicUsize::new(0);
+    static FINISHED: AtomicUsize = AtomicUsize::new(0);
+
+    for _ in 0..NUM_TASKS {
+        thread::spawn(|| {
+            for _ in 0..100 {
+                let _permit = SEM.access();
+                assert!(INSIDE.fetch_add(1, Ordering::AcqRel) < NUM_PERMITS);
+                may_interrupt();
+                INSIDE.fetch_sub(1, Ordering::AcqRel);
+            }
+            FINISHED.fetch_add(1, Ordering::Release);
+        });
+    }
+
+    wait_for(&FINISHED, NUM_TASKS);
+    assert_eq!(SEM.available_permits(), NUM_PERMITS);
+    println!("Semaphore test OK");
+}
+
+#[test]
+fn barrier_and_once() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(thread::init_scheduler);
+
+    const NUM_TASKS: usize = 5;
+    const NUM_ROUNDS: usize = 10;
+    static BARRIER: Barrier = Barrier::new(NUM_TASKS);
+    static ARRIVED: AtomicUsize = AtomicUsize::new(0);
+    static LEADERS: AtomicUsize = AtomicUsize::new(0);
+    static VALUE: OnceLock<usize> = OnceLock::new();
+    static INIT_RUNS: AtomicUsize = AtomicUsize::new(0);
+
+    for i in 0..NUM_TASKS - 1 {
+        thread::spawn(move || {
+            for round in 0..NUM_ROUNDS {
+                ARRIVED.fetch_add(1, Ordering::AcqRel);
+                may_interrupt();
+                if BARRIER.wait().is_leader() {
+                    LEADERS.fetch_add(1, Ordering::AcqRel);
+                }
+                assert!(ARRIVED.load(Ordering::Acquire) >= (round + 1) * NUM_TASKS);
+            }
+            let value = VALUE.get_or_init(|| {
+                INIT_RUNS.fetch_add(1, Ordering::AcqRel);
+                may_interrupt();
+                i
+            });
+            assert!(*value < NUM_TASKS);
+        });
+    }
+
+    for _ in 0..NUM_ROUNDS {
+        ARRIVED.fetch_add(1, Ordering::AcqRel);
+        if BARRIER.wait().is_leader() {
+            LEADERS.fetch_add(1, Ordering::AcqRel);
+        }
+    }
+    wait_for(&LEADERS, NUM_ROUNDS);
+    assert_eq!(LEADERS.load(Ordering::Acquire), NUM_ROUNDS);
+
+    while VALUE.get().is_none() {
+        thread::yield_now();
+    }
+    assert_eq!(VALUE.set(NUM_TASKS), Err(NUM_TASKS));
+    assert_eq!(INIT_RUNS.load(Ordering::Acquire), 1);
+    println!("Barrier and OnceLock test OK");
+}
@@ -2,14 +2,20 @@
 
 use alloc::{string::String, sync::Arc};
 
-pub(crate) use crate::run_queue::{AxRunQueue, RUN_QUEUE};
+use crate::run_queue::current_run_queue;
 
 #[doc(cfg(feature = "multitask"))]
-pub use crate::task::{CurrentTask, TaskId, TaskInner};
+pub use crate::cpumask::CpuMask;
+#[doc(cfg(feature = "multitask"))]
+pub use crate::registry::{find_task, tasks, TaskInfo, Tasks};
+#[doc(cfg(feature = "multitask"))]
+pub use crate::sched::{SchedPolicy, MAX_RT_PRIO};
+#[doc(cfg(feature = "multitask"))]
+pub use crate::task::{CurrentTask, TaskId, TaskInner, TaskState};
 #[doc(cfg(feature = "multitask"))]
 pub use crate::task_ext::{TaskExtMut, TaskExtRef};
 #[doc(cfg(feature = "multitask"))]
-pub use crate::wait_queue::WaitQueue;
+pub use crate::wait_queue::{Interrupted, WaitQueue};
 
 /// The reference type of a task.
 pub type AxTaskRef = Arc<AxTask>;
@@ -18,14 +24,14 @@ cfg_if::cfg_if! {
     if #[cfg(feature = "sched_rr")] {
         const MAX_TIME_SLICE: usize = 5;
         pub(crate) type AxTask = scheduler::RRTask<TaskInner, MAX_TIME_SLICE>;
-        pub(crate) type Scheduler = scheduler::RRScheduler<TaskInner, MAX_TIME_SLICE>;
+        pub(crate) type FairScheduler = scheduler::RRScheduler<TaskInner, MAX_TIME_SLICE>;
     } else if #[cfg(feature = "sched_cfs")] {
         pub(crate) type AxTask = scheduler::CFSTask<TaskInner>;
-        pub(crate) type Scheduler = scheduler::CFScheduler<TaskInner>;
+        pub(crate) type FairScheduler = scheduler::CFScheduler<TaskInner>;
     } else {
         // If no scheduler features are set, use FIFO as the default.
         pub(crate) type AxTask = scheduler::FifoTask<TaskInner>;
-        pub(crate) type Scheduler = scheduler::FifoScheduler<TaskInner>;
+        pub(crate) type FairScheduler = scheduler::FifoScheduler<TaskInner>;
     }
 }
 
@@ -48,6 +54,46 @@ impl kernel_guard::KernelGuardIf for KernelGuardIfImpl {
     }
 }
 
+/// The interface to map kernel stacks with guard areas, which should be
+/// implemented when the `paging` feature is enabled.
+#[cfg(feature = "paging")]
+#[crate_interface::def_interface]
+pub trait KernelStackIf {
+    /// Allocates a kernel stack of `size` bytes, with an unmapped guard area
+    /// of [`KERNEL_STACK_GUARD_SIZE`] below it. Returns the lowest address of
+    /// the stack.
+    ///
+    /// [`KERNEL_STACK_GUARD_SIZE`]: axhal::trap::KERNEL_STACK_GUARD_SIZE
+    fn alloc_kernel_stack(size: usize) -> Option<memory_addr::VirtAddr>;
+
+    /// Deallocates the kernel stack allocated by `alloc_kernel_stack`.
+    fn dealloc_kernel_stack(bottom: memory_addr::VirtAddr, size: usize);
+}
+
+/// The interface to restart the periodic timer ticks, which should be
+/// implemented when the `tickless` feature is enabled.
+#[cfg(feature = "tickless")]
+#[crate_interface::def_interface]
+pub trait TicklessIf {
+    /// Restarts the timer ticks of the current CPU, which may have been
+    /// stopped until [`next_tick_deadline`]. It is called when the CPU
+    /// switches tasks or a task is added to its run queue, with IRQs disabled.
+    fn restart_tick();
+}
+
+/// Reports the kernel stack overflow caught by the guard area.
+#[cfg(feature = "paging")]
+#[axhal::trap::register_trap_handler(axhal::trap::STACK_OVERFLOW)]
+fn report_stack_overflow(vaddr: memory_addr::VirtAddr) {
+    if let Some(curr) = current_may_uninit() {
+        panic!(
+            "kernel stack overflow in task {}, fault_vaddr={:#x}",
+            curr.id_name(),
+            vaddr
+        );
+    }
+}
+
 /// Gets the current task, or returns [`None`] if the current task is not
 /// initialized.
 pub fn current_may_uninit() -> Option<CurrentTask> {
@@ -70,13 +116,20 @@ pub fn init_scheduler() {
     crate::run_queue::init();
     #[cfg(feature = "irq")]
     crate::timers::init();
+    #[cfg(feature = "lockdep")]
+    crate::lockdep::init();
 
-    info!("  use {} scheduler.", Scheduler::scheduler_name());
+    info!(
+        "  use {} scheduler, with deadline and real-time classes.",
+        crate::sched::Scheduler::scheduler_name()
+    );
 }
 
 /// Initializes the task scheduler for secondary CPUs.
 pub fn init_scheduler_secondary() {
     crate::run_queue::init_secondary();
+    #[cfg(feature = "irq")]
+    crate::timers::init();
 }
 
 /// Handles periodic timer ticks for the task manager.
@@ -86,13 +139,60 @@ pub fn init_scheduler_secondary() {
 #[doc(cfg(feature = "irq"))]
 pub fn on_timer_tick() {
     crate::timers::check_events();
-    RUN_QUEUE.lock().scheduler_timer_tick();
+    current_run_queue().scheduler_timer_tick();
+}
+
+/// Handles the reschedule IPI, which other CPUs send after adding tasks to the
+/// run queue of the current CPU.
+///
+/// The current task is preempted if the preemption is enabled and a task of a
+/// higher class or priority is added. The stopped ticks are restarted.
+#[cfg(feature = "irq")]
+#[doc(cfg(feature = "irq"))]
+pub fn on_resched_ipi() {
+    current_run_queue().on_resched_ipi();
+}
+
+/// Returns when (in monotonic time) the current CPU needs the periodic timer
+/// ticks again, or [`None`] if they are not needed at all.
+///
+/// The scheduler needs them to advance its states, e.g., to end the time slice
+/// of the running task. The ticks can be stopped until then, or until the
+/// next timer event, see [`next_timer_deadline`].
+#[cfg(feature = "tickless")]
+#[doc(cfg(feature = "tickless"))]
+pub fn next_tick_deadline() -> Option<axhal::time::TimeValue> {
+    current_run_queue().next_tick_deadline()
+}
+
+/// Returns the earliest deadline (in wall time) of the timer events of the
+/// current CPU, such as the wake-ups of the sleeping tasks and the callback
+/// timers.
+#[cfg(feature = "tickless")]
+#[doc(cfg(feature = "tickless"))]
+pub fn next_timer_deadline() -> Option<axhal::time::TimeValue> {
+    crate::timers::next_deadline()
+}
+
+/// Charges the CPU time of the current task when it traps from user space into
+/// the kernel.
+pub fn on_user_entry() {
+    current().charge_cpu_time(axhal::time::monotonic_time(), false);
+}
+
+/// Charges the CPU time of the current task when it returns to user space.
+///
+/// It is a safe point to exit, so the task exits here if it has been asked to
+/// by [`request_exit`].
+pub fn on_user_return() {
+    exit_if_requested();
+    current().charge_cpu_time(axhal::time::monotonic_time(), true);
 }
 
 /// Adds the given task to the run queue, returns the task reference.
 pub fn spawn_task(task: TaskInner) -> AxTaskRef {
     let task_ref = task.into_arc();
-    RUN_QUEUE.lock().add_task(task_ref.clone());
+    crate::run_queue::spawn_task(task_ref.clone());
     task_ref
 }
 
@@ -123,19 +223,175 @@ where
 ///
 /// The range of the priority is dependent on the underlying scheduler. For
 /// example, in the [CFS] scheduler, the priority is the nice value, ranging from
-/// -20 to 19.
+/// -20 to 19. It only matters when the task has the [`SchedPolicy::Normal`]
+/// policy.
 ///
 /// Returns `true` if the priority is set successfully.
 ///
 /// [CFS]: https://en.wikipedia.org/wiki/Completely_Fair_Scheduler
 pub fn set_priority(prio: isize) -> bool {
-    RUN_QUEUE.lock().set_current_priority(prio)
+    current_run_queue().set_current_priority(prio)
+}
+
+/// Sets the scheduling policy of the task, and moves it within its run queue
+/// at once if it is ready.
+///
+/// Returns `false` if the policy is invalid, i.e., a real-time priority above
+/// [`MAX_RT_PRIO`] or a deadline runtime not in `(0, period]`, or if the
+/// deadline tasks would reserve more than 95% of the CPUs in total.
+pub fn set_sched_policy(task: &AxTaskRef, policy: SchedPolicy) -> bool {
+    crate::sched::set_sched_policy(task, policy)
+}
+
+/// Most owners that a priority is passed on to along a chain of locks, which
+/// bounds the walk if the owners wait for each other in a deadlock.
+const MAX_INHERIT_DEPTH: usize = 8;
+
+/// Records that the current task is going to wait for the lock at `addr`, which
+/// is owned by the task `owner_id`, and lends its priority to the owner
+/// (priority inheritance).
+///
+/// So the owner is not delayed by the tasks of lower priorities than the
+/// current task. Only the real-time and deadline tasks lend their priorities,
+/// and a deadline task lends the highest real-time priority. If the owner is
+/// waiting for another lock, the priority is passed on to the owner of that
+/// one, and so on. The owner keeps the priority until it disowns the lock by
+/// [`disown_lock`], while those lent for its other locks are kept.
+///
+/// The record is cleared by [`end_lock_wait`] when the current task stops
+/// waiting, whether it has got the lock or not.
+pub fn wait_for_lock(addr: usize, owner_id: u64) {
+    let curr = current();
+    curr.set_waiting_lock(Some((addr, owner_id)));
+    let Some(prio) = curr.sched_entity().lending_prio() else {
+        return;
+    };
+    let (mut addr, mut owner_id) = (addr, owner_id);
+    for _ in 0..MAX_INHERIT_DEPTH {
+        let Some(owner) = find_task(owner_id) else {
+            break;
+        };
+        if !crate::task::lend_priority(&owner, addr, prio) {
+            break;
+        }
+        let Some(next) = owner.waiting_lock() else {
+            break;
+        };
+        (addr, owner_id) = next;
+    }
+}
+
+/// Clears the record of [`wait_for_lock`], when the current task has got the
+/// lock or given up waiting for it, e.g., on timeout.
+pub fn end_lock_wait() {
+    current().set_waiting_lock(None);
+}
+
+/// Records that the current task owns the lock at `addr`, e.g., a mutex it
+/// has locked.
+///
+/// If the task exits without [`disown_lock`], `release(addr)` is called on
+/// its exit, so that the lock is not held forever. The lock must be disowned
+/// before it is moved or dropped.
+pub fn own_lock(addr: usize, release: fn(usize)) {
+    current().own_lock(addr, release);
+}
+
+/// Records that `owner` no longer owns the lock at `addr`, e.g., as it has
+/// unlocked the lock, or another task has dropped it. The priority lent to
+/// `owner` for the lock is dropped. See [`own_lock`].
+pub fn disown_lock(owner: &AxTaskRef, addr: usize) {
+    crate::task::disown_lock(owner, addr);
+}
+
+/// Whether the current task is in an atomic section, i.e., with IRQs or
+/// kernel preemption disabled, e.g., by a spin lock. It can not block or exit
+/// there.
+pub fn in_atomic() -> bool {
+    if !axhal::arch::irqs_enabled() {
+        return true;
+    }
+    #[cfg(feature = "preempt")]
+    if current_may_uninit().is_some_and(|curr| !curr.can_preempt(0)) {
+        return true;
+    }
+    false
+}
+
+/// Sets the CPUs that the current task is allowed to run on, and migrates it
+/// to one of them at once if the current CPU is not allowed.
+///
+/// Returns `false` if `mask` is empty.
+pub fn set_current_affinity(mask: CpuMask) -> bool {
+    if mask.is_empty() {
+        return false;
+    }
+    let rq = current_run_queue();
+    let curr = current();
+    curr.set_cpu_affinity(mask);
+    if !mask.get(axhal::cpu::this_cpu_id()) {
+        rq.yield_current();
+    }
+    true
+}
+
+/// Asks the task to exit with the exit code, e.g., to kill a stuck task from a
+/// debug shell.
+///
+/// The task is only marked, and it exits at a safe point where it holds
+/// nothing: when it returns to user space (see [`on_user_return`]), or when
+/// its main loop calls [`exit_if_requested`]. If it is blocked in an
+/// interruptible wait, e.g., [`WaitQueue::wait_interruptible`], the wait
+/// returns [`Interrupted`] at once, and the other waits are not affected. Only
+/// the locks it owns by [`own_lock`] are released on its exit, such as the
+/// locked mutexes. User threads should be sent signals instead.
+///
+/// Returns `false` if the task is a system task (see [`TaskInner::is_system`]),
+/// which cannot exit, or it has been asked before.
+pub fn request_exit(task: &AxTaskRef, exit_code: i32) -> bool {
+    if task.is_system() || !task.set_exit_requested(exit_code) {
+        return false;
+    }
+    // Pairs with the fence in `blocked_resched()`: either the request is seen
+    // there, or the blocked state is seen here.
+    core::sync::atomic::fence(core::sync::atomic::Ordering::SeqCst);
+    let _guard = kernel_guard::NoPreemptIrqSave::new();
+    crate::run_queue::interrupt_task(task.clone());
+    true
+}
+
+/// Interrupts the task: its interruptible wait returns [`Interrupted`] at
+/// once, and so do the later ones until it calls [`clear_interrupt`].
+///
+/// It is used to deliver signals to user threads blocked in the kernel.
+pub fn interrupt(task: &AxTaskRef) {
+    task.set_interrupted(true);
+    // Pairs with the fence in `blocked_resched()`.
+    core::sync::atomic::fence(core::sync::atomic::Ordering::SeqCst);
+    let _guard = kernel_guard::NoPreemptIrqSave::new();
+    crate::run_queue::interrupt_task(task.clone());
+}
+
+/// Clears the interruption of the current task by [`interrupt`], once the
+/// reason is handled, e.g., the pending signals are delivered.
+pub fn clear_interrupt() {
+    current().set_interrupted(false);
+}
+
+/// Exits the current task if it has been asked to by [`request_exit`].
+///
+/// Long-running kernel tasks call it in their main loops, where they hold
+/// nothing.
+pub fn exit_if_requested() {
+    if let Some(exit_code) = current().exit_requested() {
+        exit(exit_code);
+    }
 }
 
 /// Current task gives up the CPU time voluntarily, and switches to another
 /// ready task.
 pub fn yield_now() {
-    RUN_QUEUE.lock().yield_current();
+    current_run_queue().yield_current();
 }
 
 /// Current task is going to sleep for the given duration.
@@ -149,25 +405,56 @@ pub fn sleep(dur: core::time::Duration) {
 ///
 /// If the feature `irq` is not enabled, it uses busy-wait instead.
 pub fn sleep_until(deadline: axhal::time::TimeValue) {
+    #[cfg(feature = "lockdep")]
+    crate::lockdep::might_sleep();
     #[cfg(feature = "irq")]
-    RUN_QUEUE.lock().sleep_until(deadline);
+    current_run_queue().sleep_until(deadline);
     #[cfg(not(feature = "irq"))]
     axhal::time::busy_wait_until(deadline);
 }
 
+/// Like [`sleep`], but returns [`Interrupted`] early once the current task is
+/// interrupted, see [`interrupt`].
+///
+/// If the feature `irq` is not enabled, it busy-waits and cannot be
+/// interrupted.
+pub fn sleep_interruptible(dur: core::time::Duration) -> Result<(), Interrupted> {
+    #[cfg(feature = "irq")]
+    let res = WaitQueue::new().wait_timeout_interruptible(dur).map(|_| ());
+    #[cfg(not(feature = "irq"))]
+    let res = {
+        sleep(dur);
+        Ok(())
+    };
+    res
+}
+
 /// Exits the current task.
+///
+/// The locks it still owns (see [`own_lock`]) are released first.
 pub fn exit(exit_code: i32) -> ! {
-    RUN_QUEUE.lock().exit_current(exit_code)
+    current().release_owned_locks();
+    current_run_queue().exit_current(exit_code)
 }
 
 /// The idle task routine.
 ///
-/// It runs an infinite loop that keeps calling [`yield_now()`].
+/// It runs an infinite loop that keeps calling [`yield_now()`], and waits for
+/// IRQs when there is nothing to run.
 pub fn run_idle() -> ! {
     loop {
         yield_now();
         debug!("idle task: waiting for IRQs...");
         #[cfg(feature = "irq")]
-        axhal::arch::wait_for_irqs();
+        {
+            // The IPI from another CPU that adds a task after the check is not
+            // taken until the CPU waits, so that it is not missed.
+            axhal::arch::disable_irqs();
+            if current_run_queue().has_runnable() {
+                axhal::arch::enable_irqs();
+            } else {
+                axhal::arch::enable_irqs_and_wait();
+            }
+        }
     }
 }
@@ -0,0 +1,90 @@
+use core::fmt;
+
+const _: () = assert!(
+    axconfig::SMP <= CpuMask::MAX_CPUS,
+    "too many CPUs for `CpuMask`"
+);
+
+/// A set of CPUs, e.g., the CPUs that a task is allowed to run on.
+///
+/// Only the CPUs that exist, i.e., whose IDs are less than [`axconfig::SMP`],
+/// can be in the set.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CpuMask(u64);
+
+impl CpuMask {
+    /// Maximum number of CPUs that a mask can hold.
+    pub const MAX_CPUS: usize = u64::BITS as usize;
+
+    /// Creates an empty set.
+    pub const fn new() -> Self {
+        Self(0)
+    }
+
+    /// Creates a set of all CPUs.
+    pub const fn full() -> Self {
+        if axconfig::SMP == Self::MAX_CPUS {
+            Self(u64::MAX)
+        } else {
+            Self((1 << axconfig::SMP) - 1)
+        }
+    }
+
+    /// Creates a set of only one CPU.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the CPU does not exist.
+    pub const fn single(cpu_id: usize) -> Self {
+        assert!(cpu_id < axconfig::SMP);
+        Self(1 << cpu_id)
+    }
+
+    /// Returns whether the CPU is in the set.
+    pub const fn get(&self, cpu_id: usize) -> bool {
+        cpu_id < Self::MAX_CPUS && self.0 & (1 << cpu_id) != 0
+    }
+
+    /// Adds the CPU to the set, or removes it from the set.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the CPU does not exist.
+    pub fn set(&mut self, cpu_id: usize, value: bool) {
+        assert!(cpu_id < axconfig::SMP, "CPU {} does not exist", cpu_id);
+        if value {
+            self.0 |= 1 << cpu_id;
+        } else {
+            self.0 &= !(1 << cpu_id);
+        }
+    }
+
+    /// Returns whether the set is empty.
+    pub const fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Returns the number of CPUs in the set.
+    pub const fn len(&self) -> usize {
+        self.0.count_ones() as usize
+    }
+
+    /// Iterates over the IDs of the CPUs in the set, in ascending order.
+    pub fn iter(&self) -> impl Iterator<Item = usize> {
+        let bits = self.0;
+        (0..axconfig::SMP).filter(move |&cpu_id| bits & (1 << cpu_id) != 0)
+    }
+}
+
+impl Default for CpuMask {
+    /// All CPUs, as tasks are allowed to run anywhere by default.
+    fn default() -> Self {
+        Self::full()
+    }
+}
+
+impl fmt::Debug for CpuMask {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
@@ -4,15 +4,39 @@
 //! creation, scheduling, sleeping, termination, etc. The scheduler algorithm
 //! is configurable by cargo features.
 //!
+//! Each CPU has its own run queue and timer list. New and woken-up tasks are
+//! put into the least loaded run queue among the CPUs they are allowed to run
+//! on (see [`TaskInner::set_cpu_affinity`]), and idle CPUs steal ready tasks
+//! from the busy ones.
+//!
+//! Above the scheduler algorithm, tasks can be given real-time priorities, or
+//! runtime budgets with deadlines, by [`set_sched_policy`]. Deadline tasks run
+//! first in the earliest deadline order, then the real-time tasks by their
+//! priorities. [`wait_for_lock`] lets sleeping locks avoid priority
+//! inversion.
+//!
+//! All live tasks can be listed by [`tasks`], with the statistics in
+//! [`TaskInner::info`] to find out what each task is doing.
+//!
 //! # Cargo Features
 //!
 //! - `multitask`: Enable multi-task support. If it's enabled, complex task
 //!   management and scheduling is used, as well as more task-related APIs.
 //!   Otherwise, only a few APIs with naive implementation is available.
 //! - `irq`: Interrupts are enabled. If this feature is enabled, timer-based
-//!    APIs can be used, such as [`sleep`], [`sleep_until`], and
-//!    [`WaitQueue::wait_timeout`].
+//!    APIs can be used, such as [`sleep`], [`sleep_until`],
+//!    [`WaitQueue::wait_timeout`], and the callback timers by `set_timer`.
+//! - `tickless`: The periodic timer ticks may be stopped while the scheduler
+//!   does not need them, e.g., while the CPU is idle or runs a real-time task
+//!   alone. The runtime has to implement `TicklessIf` to restart them when the
+//!   CPU switches tasks or is given a new task.
 //! - `preempt`: Enable preemptive scheduling.
+//! - `paging`: Map the task stacks with unmapped guard areas below them by
+//!   `KernelStackIf`, so that stack overflows fault at once. Otherwise, the
+//!   stacks are taken from the heap, and their overflows are found by the
+//!   canaries at the bottom on every context switch.
+//! - `lockdep`: Validate the orders of the locks at runtime, and report
+//!   sleeping in atomic sections, see the [`lockdep`] module.
 //! - `sched_fifo`: Use the [FIFO cooperative scheduler][1]. It also enables the
 //!   `multitask` feature if it is enabled. This feature is enabled by default,
 //!   and it can be overriden by other scheduler features.
@@ -42,7 +66,10 @@ cfg_if::cfg_if! {
         extern crate log;
         extern crate alloc;
 
+        mod cpumask;
+        mod registry;
         mod run_queue;
+        mod sched;
         mod task;
         mod task_ext;
         mod api;
@@ -50,10 +77,14 @@ cfg_if::cfg_if! {
 
         #[cfg(feature = "irq")]
         mod timers;
+        #[cfg(feature = "lockdep")]
+        pub mod lockdep;
 
         #[doc(cfg(feature = "multitask"))]
         pub use self::api::*;
         pub use self::api::{sleep, sleep_until, yield_now};
+        #[cfg(feature = "irq")]
+        pub use self::timers::{set_periodic_timer, set_timer, TimerHandle};
     } else {
         mod api_s;
         pub use self::api_s::{sleep, sleep_until, yield_now};
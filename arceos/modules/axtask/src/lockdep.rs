@@ -0,0 +1,597 @@
+//! Lock dependency validator, enabled by the `lockdep` feature.
+//!
+//! Every lock belongs to a *class*. A lock is a class by itself, identified
+//! by its [`LockdepMap`], so the orders between two locks are found wherever
+//! they are taken. Sleeping locks (e.g., of `axsync`) report themselves with
+//! [`lock_acquire`] and [`lock_release`], and spin locks of `kspin` report
+//! themselves through `kernel_guard`, along with the guards they acquire.
+//!
+//! When a task takes a lock while holding others, the orders from the held
+//! classes to the new one are added to a global dependency graph. The
+//! validator reports:
+//!
+//! - an order that closes a cycle in the graph, i.e., the locks may be taken
+//!   in inverse orders by two tasks, which deadlock;
+//! - sleeping (e.g., waiting on a [`WaitQueue`] or locking a sleeping lock)
+//!   while holding a spin lock, with IRQs or preemption disabled by a guard,
+//!   or in an IRQ handler;
+//! - a `SpinRaw` taken with IRQs enabled, which deadlocks if an IRQ handler
+//!   takes it too, or if the task is preempted while holding it.
+//!
+//! A report shows the task, the locks it holds and a backtrace, then the
+//! validator turns itself off. The code should be compiled with frame
+//! pointers for the backtraces to be found.
+//!
+//! [`WaitQueue`]: crate::WaitQueue
+
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::panic::Location;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use kernel_guard::lockdep::{Frames, GuardKind, LockdepIf};
+
+pub use kernel_guard::lockdep::LockdepMap;
+
+use crate::current_may_uninit;
+
+/// Maximum number of lock classes. The validator turns itself off when they
+/// are used up.
+const MAX_CLASSES: usize = 512;
+/// Maximum number of locks and guards held by a task at the same time.
+const MAX_HELD: usize = 32;
+/// Maximum number of classes shown in a dependency cycle.
+const MAX_CYCLE: usize = 16;
+/// Maximum number of frames shown in a backtrace.
+const MAX_FRAMES: usize = 16;
+
+const DEP_WORDS: usize = MAX_CLASSES / 64;
+
+/// Whether the validator is running. It is started by [`init`], and stopped
+/// after the first report.
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Where a lock is taken, i.e., the caller of the locking function.
+#[derive(Clone, Copy)]
+enum LockSite {
+    Location(&'static Location<'static>),
+    /// For the guards out of locks, and the unused slots.
+    Unknown,
+}
+
+impl fmt::Display for LockSite {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Location(loc) => write!(f, "{}", loc),
+            Self::Unknown => write!(f, "unknown site"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ClassState {
+    Free,
+    /// A forgotten class, which does not end the probing of the hash table.
+    Removed,
+    Spin,
+    Sleep,
+}
+
+#[derive(Clone, Copy)]
+struct Class {
+    state: ClassState,
+    key: usize,
+    /// Where it was first taken.
+    site: LockSite,
+}
+
+impl Class {
+    const FREE: Self = Self {
+        state: ClassState::Free,
+        key: 0,
+        site: LockSite::Unknown,
+    };
+}
+
+/// The classes in an open addressing hash table, and the dependencies
+/// between them as an adjacency matrix.
+struct Graph {
+    classes: [Class; MAX_CLASSES],
+    /// `deps[a]` has the bit `b` if `b` has been taken while holding `a`.
+    deps: [[u64; DEP_WORDS]; MAX_CLASSES],
+    nr_classes: usize,
+    /// Scratch space to search for cycles.
+    prev: [u16; MAX_CLASSES],
+    queue: [u16; MAX_CLASSES],
+}
+
+impl Graph {
+    const fn new() -> Self {
+        Self {
+            classes: [Class::FREE; MAX_CLASSES],
+            deps: [[0; DEP_WORDS]; MAX_CLASSES],
+            nr_classes: 0,
+            prev: [0; MAX_CLASSES],
+            queue: [0; MAX_CLASSES],
+        }
+    }
+
+    /// Finds the class of `key`, or registers it. Returns `None` if the
+    /// classes are used up.
+    fn class_of(&mut self, state: ClassState, key: usize, site: LockSite) -> Option<usize> {
+        let start = key.wrapping_mul(0x9e37_79b9_7f4a_7c15_u64 as usize) % MAX_CLASSES;
+        let mut slot = None;
+        for i in 0..MAX_CLASSES {
+            let idx = (start + i) % MAX_CLASSES;
+            let class = &self.classes[idx];
+            if class.state == state && class.key == key {
+                return Some(idx);
+            }
+            if class.state == ClassState::Free || class.state == ClassState::Removed {
+                slot.get_or_insert(idx);
+            }
+            if class.state == ClassState::Free {
+                break;
+            }
+        }
+        // Keep a free slot, so that the probing always ends.
+        let idx = slot.filter(|_| self.nr_classes + 1 < MAX_CLASSES)?;
+        self.classes[idx] = Class { state, key, site };
+        self.nr_classes += 1;
+        Some(idx)
+    }
+
+    fn forget(&mut self, map: &LockdepMap) {
+        let Some(idx) = map.take_class() else {
+            return;
+        };
+        self.classes[idx].state = ClassState::Removed;
+        self.nr_classes -= 1;
+        self.deps[idx] = [0; DEP_WORDS];
+        for deps in self.deps.iter_mut() {
+            deps[idx / 64] &= !(1 << (idx % 64));
+        }
+    }
+
+    fn has_dep(&self, from: usize, to: usize) -> bool {
+        self.deps[from][to / 64] & (1 << (to % 64)) != 0
+    }
+
+    /// Records that `to` is taken while holding `from`. If `from` can be
+    /// taken while holding `to`, the order is not recorded, and the path from
+    /// `to` to `from` is returned.
+    fn add_dep(&mut self, from: usize, to: usize) -> Result<(), Cycle> {
+        if from == to || self.has_dep(from, to) {
+            return Ok(());
+        }
+        if let Some(cycle) = self.find_path(to, from) {
+            return Err(cycle);
+        }
+        self.deps[from][to / 64] |= 1 << (to % 64);
+        Ok(())
+    }
+
+    /// Searches the graph breadth-first for a path.
+    fn find_path(&mut self, from: usize, to: usize) -> Option<Cycle> {
+        const UNVISITED: u16 = u16::MAX;
+        self.prev.fill(UNVISITED);
+        self.prev[from] = from as u16;
+        self.queue[0] = from as u16;
+        let (mut head, mut tail) = (0, 1);
+        while head < tail {
+            let curr = self.queue[head] as usize;
+            head += 1;
+            if curr == to {
+                let mut cycle = Cycle::new();
+                let mut idx = to;
+                while idx != from {
+                    cycle.push(self.classes[idx].site);
+                    idx = self.prev[idx] as usize;
+                }
+                cycle.push(self.classes[from].site);
+                cycle.sites[..cycle.len].reverse();
+                return Some(cycle);
+            }
+            for next in 0..MAX_CLASSES {
+                if self.prev[next] == UNVISITED && self.has_dep(curr, next) {
+                    self.prev[next] = curr as u16;
+                    self.queue[tail] = next as u16;
+                    tail += 1;
+                }
+            }
+        }
+        None
+    }
+}
+
+/// The graph with a spin lock of its own, as the validator can not use the
+/// guards which it validates.
+struct GraphLock {
+    locked: AtomicBool,
+    graph: UnsafeCell<Graph>,
+}
+
+unsafe impl Sync for GraphLock {}
+
+struct GraphGuard<'a>(&'a GraphLock);
+
+impl GraphLock {
+    /// Locks the graph. IRQs must be disabled.
+    fn lock(&self) -> GraphGuard {
+        while self
+            .locked
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            core::hint::spin_loop();
+        }
+        GraphGuard(self)
+    }
+}
+
+impl core::ops::Deref for GraphGuard<'_> {
+    type Target = Graph;
+    fn deref(&self) -> &Graph {
+        unsafe { &*self.0.graph.get() }
+    }
+}
+
+impl core::ops::DerefMut for GraphGuard<'_> {
+    fn deref_mut(&mut self) -> &mut Graph {
+        unsafe { &mut *self.0.graph.get() }
+    }
+}
+
+impl Drop for GraphGuard<'_> {
+    fn drop(&mut self) {
+        self.0.locked.store(false, Ordering::Release);
+    }
+}
+
+static GRAPH: GraphLock = GraphLock {
+    locked: AtomicBool::new(false),
+    graph: UnsafeCell::new(Graph::new()),
+};
+
+/// A lock or a guard held by a task.
+#[derive(Clone, Copy)]
+struct HeldLock {
+    /// The guard of a spin lock or of a section, or `None` for a sleeping
+    /// lock.
+    guard: Option<GuardKind>,
+    /// The class, or `None` for a guard out of a spin lock.
+    class: Option<usize>,
+    site: LockSite,
+}
+
+/// The locks and guards held by a task, in the order of acquisition.
+pub(crate) struct HeldLocks {
+    locks: [HeldLock; MAX_HELD],
+    len: usize,
+}
+
+impl HeldLocks {
+    pub(crate) const fn new() -> Self {
+        const EMPTY: HeldLock = HeldLock {
+            guard: None,
+            class: None,
+            site: LockSite::Unknown,
+        };
+        Self {
+            locks: [EMPTY; MAX_HELD],
+            len: 0,
+        }
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &HeldLock> {
+        self.locks[..self.len].iter()
+    }
+
+    fn push(&mut self, lock: HeldLock) -> Result<(), Problem> {
+        if self.len == MAX_HELD {
+            return Err(Problem::TooManyHeld);
+        }
+        self.locks[self.len] = lock;
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Removes the latest guard if it is not a part of a lock, and returns
+    /// its kind.
+    fn pop_guard(&mut self) -> Option<GuardKind> {
+        let lock = self.locks[..self.len].last()?;
+        if lock.class.is_some() {
+            return None;
+        }
+        self.len -= 1;
+        lock.guard
+    }
+
+    /// Returns the latest lock that matches.
+    fn find_mut(&mut self, f: impl Fn(&HeldLock) -> bool) -> Option<&mut HeldLock> {
+        self.locks[..self.len].iter_mut().rev().find(|lock| f(lock))
+    }
+
+    /// Removes the latest lock that matches.
+    fn remove(&mut self, f: impl Fn(&HeldLock) -> bool) {
+        if let Some(i) = self.locks[..self.len].iter().rposition(f) {
+            self.locks.copy_within(i + 1..self.len, i);
+            self.len -= 1;
+        }
+    }
+
+    /// Whether the task is in an atomic section, where it must not sleep.
+    fn in_atomic(&self) -> bool {
+        self.iter().any(|lock| lock.guard.is_some())
+    }
+}
+
+/// Classes in a dependency cycle.
+struct Cycle {
+    sites: [LockSite; MAX_CYCLE],
+    len: usize,
+    /// Whether some classes are not shown.
+    truncated: bool,
+}
+
+impl Cycle {
+    fn new() -> Self {
+        Self {
+            sites: [LockSite::Unknown; MAX_CYCLE],
+            len: 0,
+            truncated: false,
+        }
+    }
+
+    fn push(&mut self, site: LockSite) {
+        if self.len < MAX_CYCLE {
+            self.sites[self.len] = site;
+            self.len += 1;
+        } else {
+            self.truncated = true;
+        }
+    }
+}
+
+enum Problem {
+    Cycle {
+        site: LockSite,
+        cycle: Cycle,
+    },
+    SleepInAtomic,
+    #[cfg(feature = "irq")]
+    SleepInIrq,
+    IrqUnsafe {
+        site: LockSite,
+    },
+    TooManyHeld,
+    TooManyClasses,
+}
+
+/// Starts the validator, once the current task can be found.
+pub(crate) fn init() {
+    ENABLED.store(true, Ordering::Release);
+}
+
+fn enabled() -> bool {
+    ENABLED.load(Ordering::Acquire)
+}
+
+fn with_irqs_disabled<R>(f: impl FnOnce() -> R) -> R {
+    let enabled = axhal::arch::irqs_enabled();
+    axhal::arch::disable_irqs();
+    let ret = f();
+    if enabled {
+        axhal::arch::enable_irqs();
+    }
+    ret
+}
+
+/// Runs `f` with the locks held by the current task, if the validator is
+/// running and the current task has been initialized.
+fn with_held_locks(f: impl FnOnce(&mut HeldLocks) -> Result<(), Problem>) {
+    if !enabled() {
+        return;
+    }
+    let Some(curr) = current_may_uninit() else {
+        return;
+    };
+    // IRQ handlers use the list of the interrupted task.
+    if let Err(problem) = with_irqs_disabled(|| f(unsafe { &mut *curr.held_locks() })) {
+        report(problem);
+    }
+}
+
+/// Records a lock of `map` taken at `site`, with the orders from the held
+/// locks.
+fn acquire(
+    held: &mut HeldLocks,
+    guard: Option<GuardKind>,
+    state: ClassState,
+    map: &LockdepMap,
+    site: LockSite,
+    trylock: bool,
+) -> Result<(), Problem> {
+    let mut graph = GRAPH.lock();
+    let class = match map.class() {
+        Some(class) => class,
+        None => {
+            let class = graph
+                .class_of(state, map.key(), site)
+                .ok_or(Problem::TooManyClasses)?;
+            map.set_class(class);
+            class
+        }
+    };
+    // Taking a lock without waiting does not deadlock.
+    if !trylock {
+        for from in held.iter().filter_map(|lock| lock.class) {
+            graph
+                .add_dep(from, class)
+                .map_err(|cycle| Problem::Cycle { site, cycle })?;
+        }
+    }
+    drop(graph);
+    held.push(HeldLock {
+        guard,
+        class: Some(class),
+        site,
+    })
+}
+
+/// Records a sleeping lock taken by the current task at the caller.
+///
+/// `trylock` means that it is taken without waiting.
+#[track_caller]
+pub fn lock_acquire(map: &LockdepMap, trylock: bool) {
+    let site = LockSite::Location(Location::caller());
+    with_held_locks(|held| acquire(held, None, ClassState::Sleep, map, site, trylock));
+}
+
+/// Records a sleeping lock released by the current task.
+pub fn lock_release(map: &LockdepMap) {
+    let class = map.class();
+    with_held_locks(|held| {
+        held.remove(|lock| lock.guard.is_none() && lock.class == class);
+        Ok(())
+    });
+}
+
+/// Checks that the current task can sleep, i.e., it is not in an IRQ
+/// handler, and holds no spin locks or guards.
+pub fn might_sleep() {
+    with_held_locks(|held| {
+        #[cfg(feature = "irq")]
+        if axhal::irq::in_irq() {
+            return Err(Problem::SleepInIrq);
+        }
+        if held.in_atomic() {
+            return Err(Problem::SleepInAtomic);
+        }
+        Ok(())
+    });
+}
+
+struct LockdepIfImpl;
+
+#[crate_interface::impl_interface]
+impl LockdepIf for LockdepIfImpl {
+    fn guard_acquired(kind: GuardKind) {
+        with_held_locks(|held| {
+            held.push(HeldLock {
+                guard: Some(kind),
+                class: None,
+                site: LockSite::Unknown,
+            })
+        });
+    }
+
+    fn guard_releasing(kind: GuardKind) {
+        with_held_locks(|held| {
+            held.remove(|lock| lock.guard == Some(kind) && lock.class.is_none());
+            Ok(())
+        });
+    }
+
+    fn spin_acquired(map: &LockdepMap, site: &'static Location<'static>, trylock: bool) {
+        // Read before `with_held_locks`, which disables IRQs.
+        let irqs_enabled = axhal::arch::irqs_enabled();
+        let site = LockSite::Location(site);
+        with_held_locks(|held| {
+            // The guard of the lock has just been acquired, which becomes a
+            // part of the lock.
+            let Some(kind) = held.pop_guard() else {
+                return Ok(());
+            };
+            if kind == GuardKind::NoOp && irqs_enabled {
+                return Err(Problem::IrqUnsafe { site });
+            }
+            acquire(held, Some(kind), ClassState::Spin, map, site, trylock)
+        });
+    }
+
+    fn spin_releasing(map: &LockdepMap) {
+        let class = map.class();
+        with_held_locks(|held| {
+            // Leave the guard, which is going to be released.
+            if let Some(lock) = held.find_mut(|lock| lock.guard.is_some() && lock.class == class) {
+                lock.class = None;
+                lock.site = LockSite::Unknown;
+            }
+            Ok(())
+        });
+    }
+
+    fn map_dropped(map: &LockdepMap) {
+        with_irqs_disabled(|| GRAPH.lock().forget(map));
+    }
+}
+
+/// Prints the report, and turns the validator off.
+#[cold]
+fn report(problem: Problem) {
+    // Stop first, as printing takes locks too.
+    if !ENABLED.swap(false, Ordering::AcqRel) {
+        return;
+    }
+    let curr = crate::current();
+    error!("========================================================");
+    match &problem {
+        Problem::Cycle { site, cycle } => {
+            error!("lockdep: possible circular locking dependency detected");
+            error!(
+                "{} is taking {} while holding the last of these locks,",
+                curr.id_name(),
+                site
+            );
+            error!("which have been taken in this order:");
+            for (i, site) in cycle.sites[..cycle.len].iter().enumerate() {
+                error!("  #{}: {}", i, site);
+            }
+            if cycle.truncated {
+                error!("  ...");
+            }
+        }
+        Problem::SleepInAtomic => {
+            error!("lockdep: sleeping function called from an atomic section");
+            error!(
+                "{} may sleep while holding a spin lock or a guard",
+                curr.id_name()
+            );
+        }
+        #[cfg(feature = "irq")]
+        Problem::SleepInIrq => {
+            error!("lockdep: sleeping function called from an IRQ handler");
+            error!("{} was interrupted", curr.id_name());
+        }
+        Problem::IrqUnsafe { site } => {
+            error!("lockdep: IRQ-unsafe lock usage detected");
+            error!(
+                "{} is taking {} (a `SpinRaw`) with IRQs enabled",
+                curr.id_name(),
+                site
+            );
+        }
+        Problem::TooManyHeld => {
+            error!("lockdep: too many locks held by {}", curr.id_name());
+        }
+        Problem::TooManyClasses => {
+            error!("lockdep: too many lock classes");
+        }
+    }
+    error!("locks held by {}:", curr.id_name());
+    let held = unsafe { &*curr.held_locks() };
+    for (i, lock) in held.iter().enumerate() {
+        match (lock.guard, lock.class) {
+            (Some(kind), None) => error!("  #{}: {:?} guard", i, kind),
+            (Some(kind), Some(_)) => error!("  #{}: {} ({:?})", i, lock.site, kind),
+            (None, _) => error!("  #{}: {}", i, lock.site),
+        }
+    }
+    error!("backtrace:");
+    for (i, addr) in Frames::new().take(MAX_FRAMES).enumerate() {
+        error!("  #{}: {:#x}", i, addr);
+    }
+    error!("lockdep: turning off the locking correctness validator");
+    error!("========================================================");
+}
@@ -0,0 +1,134 @@
+//! The registry of all live tasks, and the statistics reported for them.
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+use core::time::Duration;
+
+use axhal::time::TimeValue;
+use kspin::SpinNoIrq;
+
+use crate::task::TaskState;
+use crate::{AxTask, AxTaskRef, SchedPolicy};
+
+/// All tasks that are not dropped yet, by their IDs.
+static TASKS: SpinNoIrq<BTreeMap<u64, Weak<AxTask>>> = SpinNoIrq::new(BTreeMap::new());
+
+pub(crate) fn register(task: &AxTaskRef) {
+    TASKS
+        .lock()
+        .insert(task.id().as_u64(), Arc::downgrade(task));
+}
+
+pub(crate) fn unregister(id: u64) {
+    TASKS.lock().remove(&id);
+}
+
+/// Returns the live task with the ID.
+pub fn find_task(id: u64) -> Option<AxTaskRef> {
+    TASKS.lock().get(&id)?.upgrade()
+}
+
+/// Iterates over the live tasks in the order of their IDs.
+///
+/// Tasks created or dropped during the iteration may be missed or not, but
+/// no task is visited twice.
+pub fn tasks() -> Tasks {
+    Tasks { next_id: 0 }
+}
+
+/// An iterator over the live tasks, see [`tasks`].
+pub struct Tasks {
+    next_id: u64,
+}
+
+impl Iterator for Tasks {
+    type Item = AxTaskRef;
+
+    fn next(&mut self) -> Option<AxTaskRef> {
+        let tasks = TASKS.lock();
+        let (id, task) = tasks
+            .range(self.next_id..)
+            .find_map(|(&id, task)| Some((id, task.upgrade()?)))?;
+        self.next_id = id + 1;
+        Some(task)
+    }
+}
+
+/// CPU time spent by a task.
+#[derive(Default)]
+pub(crate) struct CpuTimes {
+    user: Duration,
+    kernel: Duration,
+    /// When the time was charged last time.
+    since: TimeValue,
+    in_user: bool,
+}
+
+impl CpuTimes {
+    /// Charges the time since last time, and switches to the user or kernel
+    /// mode.
+    pub fn charge(&mut self, now: TimeValue, in_user: bool) {
+        let delta = now.saturating_sub(self.since);
+        if self.in_user {
+            self.user += delta;
+        } else {
+            self.kernel += delta;
+        }
+        self.since = now;
+        self.in_user = in_user;
+    }
+
+    /// Starts charging when the task switches in.
+    pub fn start(&mut self, now: TimeValue) {
+        self.since = now;
+    }
+
+    /// Returns the user and kernel time, including the time since last charged
+    /// if the task is running.
+    pub fn get(&self, now: TimeValue, running: bool) -> (Duration, Duration) {
+        let pending = if running {
+            now.saturating_sub(self.since)
+        } else {
+            Duration::ZERO
+        };
+        if self.in_user {
+            (self.user + pending, self.kernel)
+        } else {
+            (self.user, self.kernel + pending)
+        }
+    }
+}
+
+/// Information and statistics of a task, see [`TaskInner::info`].
+///
+/// [`TaskInner::info`]: crate::TaskInner::info
+#[derive(Debug, Clone)]
+pub struct TaskInfo {
+    /// The task ID.
+    pub id: u64,
+    /// The task name.
+    pub name: String,
+    /// The state of the task.
+    pub state: TaskState,
+    /// The CPU that the task is running on, or whose run queue it is in, or it
+    /// ran on last time.
+    pub cpu_id: usize,
+    /// The scheduling policy of the task.
+    pub policy: SchedPolicy,
+    /// CPU time spent in user space, which is zero for kernel tasks.
+    pub user_time: Duration,
+    /// CPU time spent in the kernel.
+    pub kernel_time: Duration,
+    /// Number of times that the task switched out by blocking or yielding.
+    pub voluntary_switches: u64,
+    /// Number of times that the task was preempted.
+    pub involuntary_switches: u64,
+    /// Size of the kernel stack in bytes, or 0 if the task runs on the boot
+    /// stack.
+    pub stack_size: usize,
+    /// The most bytes of the kernel stack that have ever been used.
+    pub stack_high_water: usize,
+    /// Whether the task is waiting in a [`WaitQueue`](crate::WaitQueue).
+    pub in_wait_queue: bool,
+}
@@ -1,16 +1,26 @@
 use alloc::collections::VecDeque;
 use alloc::sync::Arc;
-use kspin::SpinNoIrq;
+use core::ops::Deref;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use kernel_guard::NoPreemptIrqSave;
+use kspin::{SpinNoIrq, SpinRaw, SpinRawGuard};
 use lazyinit::LazyInit;
 use scheduler::BaseScheduler;
 
+use crate::sched::{SchedEntity, Scheduler};
 use crate::task::{CurrentTask, TaskState};
-use crate::{AxTaskRef, Scheduler, TaskInner, WaitQueue};
+use crate::{AxTaskRef, CpuMask, TaskInner, WaitQueue};
+
+/// The run queue of the current CPU.
+#[percpu::def_percpu]
+static RUN_QUEUE: LazyInit<AxRunQueue> = LazyInit::new();
 
-// TODO: per-CPU
-pub(crate) static RUN_QUEUE: LazyInit<SpinNoIrq<AxRunQueue>> = LazyInit::new();
+/// Run queues of all CPUs, for other CPUs to wake up tasks into them or to
+/// steal tasks from them.
+static RUN_QUEUES: [LazyInit<&'static AxRunQueue>; axconfig::SMP] =
+    [const { LazyInit::new() }; axconfig::SMP];
 
-// TODO: per-CPU
 static EXITED_TASKS: SpinNoIrq<VecDeque<AxTaskRef>> = SpinNoIrq::new(VecDeque::new());
 
 static WAIT_FOR_EXIT: WaitQueue = WaitQueue::new();
@@ -18,55 +28,301 @@ static WAIT_FOR_EXIT: WaitQueue = WaitQueue::new();
 #[percpu::def_percpu]
 static IDLE_TASK: LazyInit<AxTaskRef> = LazyInit::new();
 
+/// The task that the current CPU has just switched away from.
+#[percpu::def_percpu]
+static PREV_TASK: Option<PrevTask> = None;
+
+struct PrevTask {
+    task: AxTaskRef,
+    /// Whether it is no longer allowed to run on this CPU, and should be moved
+    /// to another run queue.
+    migrate: bool,
+}
+
+/// The run queue of a CPU.
+///
+/// Only its own CPU switches tasks with it, while other CPUs may add woken-up
+/// tasks to it, or steal tasks from it when they are idle. The scheduler is
+/// locked only briefly, and IRQs must be disabled when locking it.
 pub(crate) struct AxRunQueue {
-    scheduler: Scheduler,
+    cpu_id: usize,
+    scheduler: SpinRaw<Scheduler>,
+    /// Number of tasks in the scheduler, for load balancing.
+    nr_ready: AtomicUsize,
+}
+
+/// The run queue of the current CPU, with IRQs and preemption disabled as
+/// long as it is held, so that the current task stays on this CPU.
+///
+/// After a context switch, the current task may have been migrated to another
+/// CPU, so the reference should be dropped at once.
+pub(crate) struct CurrentRunQueueRef {
+    rq: &'static AxRunQueue,
+    _guard: NoPreemptIrqSave,
+}
+
+impl Deref for CurrentRunQueueRef {
+    type Target = AxRunQueue;
+    fn deref(&self) -> &Self::Target {
+        self.rq
+    }
+}
+
+/// Returns the run queue of the current CPU.
+pub(crate) fn current_run_queue() -> CurrentRunQueueRef {
+    let guard = NoPreemptIrqSave::new();
+    CurrentRunQueueRef {
+        rq: unsafe { RUN_QUEUE.current_ref_raw() },
+        _guard: guard,
+    }
+}
+
+/// Chooses the run queue to put the task into, which is the least loaded one
+/// among the CPUs it is allowed to run on. The current CPU and the CPU it ran
+/// on last time are preferred when the loads are equal.
+///
+/// IRQs must be disabled.
+fn select_run_queue(task: &AxTaskRef) -> &'static AxRunQueue {
+    let this_cpu = axhal::cpu::this_cpu_id();
+    let last_cpu = task.cpu_id();
+    task.cpu_affinity()
+        .iter()
+        .filter_map(|cpu_id| RUN_QUEUES[cpu_id].get().copied())
+        .min_by_key(|rq| (rq.nr_ready(), rq.cpu_id != this_cpu, rq.cpu_id != last_cpu))
+        .unwrap_or_else(|| {
+            // None of the CPUs are online yet.
+            warn!("no online CPU in the affinity of {}", task.id_name());
+            unsafe { &**RUN_QUEUE.current_ref_raw() }
+        })
+}
+
+/// Wakes up a blocked task, and puts it into a run queue.
+///
+/// If the task is put into the run queue of the current CPU, the current task
+/// will be preempted when the preemption is enabled, if `resched` is true or
+/// the task is of a higher class or priority.
+///
+/// IRQs must be disabled.
+pub(crate) fn unblock_task(task: AxTaskRef, resched: bool) {
+    // Only one of the wakers (e.g., a timer and a `notify()`) can succeed.
+    if task.transition_state(TaskState::Blocked, TaskState::Ready)
+        || task.transition_state(TaskState::Interruptible, TaskState::Ready)
+    {
+        wake_task(task, resched);
+    }
+}
+
+/// Wakes up the task if it is blocked in an interruptible wait.
+///
+/// IRQs and preemption must be disabled.
+pub(crate) fn interrupt_task(task: AxTaskRef) {
+    if task.transition_state(TaskState::Interruptible, TaskState::Ready) {
+        wake_task(task, false);
+    }
+}
+
+/// Adds the task that has just been made ready by a waker to a run queue.
+fn wake_task(task: AxTaskRef, resched: bool) {
+    // It may be still switching out on another CPU, whose context must be
+    // saved before it runs again.
+    while task.on_cpu() {
+        core::hint::spin_loop();
+    }
+    let rq = select_run_queue(&task);
+    debug!("task unblock: {} on CPU {}", task.id_name(), rq.cpu_id);
+    rq.add_task(task);
+    #[cfg(feature = "preempt")]
+    if rq.cpu_id == axhal::cpu::this_cpu_id() {
+        let curr = crate::current();
+        if resched || rq.scheduler.lock().should_preempt(curr.as_task_ref()) {
+            curr.set_preempt_pending(true);
+        }
+    }
+    #[cfg(not(feature = "preempt"))]
+    let _ = resched;
+}
+
+/// Changes the scheduling states of the task with `f`, and moves the task to
+/// its new place in the run queue if it is ready.
+///
+/// The current task is preempted when the preemption is enabled, if it is no
+/// longer the most urgent one on this CPU.
+pub(crate) fn update_sched<T>(task: &AxTaskRef, f: impl FnOnce(&mut SchedEntity) -> T) -> T {
+    let _guard = NoPreemptIrqSave::new();
+    let ret = loop {
+        let queued_cpu = task.sched_entity().queued_cpu();
+        if let Some(rq) = queued_cpu.and_then(|cpu_id| RUN_QUEUES[cpu_id].get()) {
+            let mut scheduler = rq.scheduler.lock();
+            // It may have been picked or stolen before the lock is acquired.
+            if let Some(task) = scheduler.remove_task(task) {
+                let ret = f(&mut task.sched_entity());
+                scheduler.add_task(task);
+                break ret;
+            }
+        } else {
+            let mut se = task.sched_entity();
+            if se.queued_cpu().is_none() {
+                break f(&mut se);
+            }
+        }
+    };
+    #[cfg(feature = "preempt")]
+    {
+        let rq = unsafe { RUN_QUEUE.current_ref_raw() };
+        let curr = crate::current();
+        if rq.scheduler.lock().should_preempt(curr.as_task_ref()) {
+            curr.set_preempt_pending(true);
+        }
+    }
+    ret
+}
+
+/// Adds a newly spawned task to a run queue.
+pub(crate) fn spawn_task(task: AxTaskRef) {
+    let _guard = NoPreemptIrqSave::new();
+    debug!("task spawn: {}", task.id_name());
+    assert!(task.is_ready());
+    select_run_queue(&task).add_task(task);
 }
 
 impl AxRunQueue {
-    pub fn new() -> SpinNoIrq<Self> {
-        let gc_task = TaskInner::new(gc_entry, "gc".into(), axconfig::TASK_STACK_SIZE).into_arc();
-        let mut scheduler = Scheduler::new();
-        scheduler.add_task(gc_task);
-        SpinNoIrq::new(Self { scheduler })
+    fn new(cpu_id: usize) -> Self {
+        Self {
+            cpu_id,
+            scheduler: SpinRaw::new(Scheduler::new(cpu_id)),
+            nr_ready: AtomicUsize::new(0),
+        }
     }
 
-    pub fn add_task(&mut self, task: AxTaskRef) {
-        debug!("task spawn: {}", task.id_name());
-        assert!(task.is_ready());
-        self.scheduler.add_task(task);
+    fn nr_ready(&self) -> usize {
+        self.nr_ready.load(Ordering::Relaxed)
+    }
+
+    fn add_task(&self, task: AxTaskRef) {
+        task.set_cpu_id(self.cpu_id);
+        self.scheduler.lock().add_task(task);
+        self.nr_ready.fetch_add(1, Ordering::Relaxed);
+        self.kick();
+    }
+
+    /// Makes the CPU of the run queue notice the task just added: another CPU
+    /// is sent a reschedule IPI, while the ticks of the current CPU are
+    /// restarted if they were stopped.
+    fn kick(&self) {
+        if self.cpu_id != axhal::cpu::this_cpu_id() {
+            #[cfg(feature = "irq")]
+            axhal::irq::send_ipi(self.cpu_id);
+        } else {
+            #[cfg(feature = "tickless")]
+            crate_interface::call_interface!(crate::TicklessIf::restart_tick());
+        }
+    }
+
+    fn put_prev_task(&self, task: AxTaskRef, preempt: bool) {
+        self.scheduler.lock().put_prev_task(task, preempt);
+        self.nr_ready.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn pick_next_task(&self) -> Option<AxTaskRef> {
+        let task = self.scheduler.lock().pick_next_task()?;
+        self.nr_ready.fetch_sub(1, Ordering::Relaxed);
+        Some(task)
+    }
+
+    /// Takes a task from the run queue of another CPU, when there is nothing
+    /// to run on this CPU.
+    ///
+    /// The busy run queues are skipped instead of waiting for them. Tasks that
+    /// are not allowed to run on this CPU, or are still switching out, are
+    /// left there. See [`Scheduler::steal_task`].
+    fn steal_task(&self) -> Option<AxTaskRef> {
+        for cpu_id in (self.cpu_id + 1..axconfig::SMP).chain(0..self.cpu_id) {
+            let Some(victim) = RUN_QUEUES[cpu_id].get() else {
+                continue;
+            };
+            if victim.nr_ready() == 0 {
+                continue;
+            }
+            let Some(mut scheduler) = victim.scheduler.try_lock() else {
+                continue;
+            };
+            if let Some(task) = scheduler.steal_task(self.cpu_id) {
+                victim.nr_ready.fetch_sub(1, Ordering::Relaxed);
+                debug!(
+                    "task steal: {} from CPU {} to CPU {}",
+                    task.id_name(),
+                    cpu_id,
+                    self.cpu_id
+                );
+                return Some(task);
+            }
+        }
+        None
     }
 
     #[cfg(feature = "irq")]
-    pub fn scheduler_timer_tick(&mut self) {
+    pub fn scheduler_timer_tick(&self) {
         let curr = crate::current();
-        if !curr.is_idle() && self.scheduler.task_tick(curr.as_task_ref()) {
+        if !curr.is_idle() && self.scheduler.lock().task_tick(curr.as_task_ref()) {
             #[cfg(feature = "preempt")]
             curr.set_preempt_pending(true);
         }
     }
 
-    pub fn yield_current(&mut self) {
+    #[cfg(feature = "irq")]
+    pub fn on_resched_ipi(&self) {
+        #[cfg(feature = "preempt")]
+        {
+            let curr = crate::current();
+            if self.scheduler.lock().should_preempt(curr.as_task_ref()) {
+                curr.set_preempt_pending(true);
+            }
+        }
+        #[cfg(feature = "tickless")]
+        crate_interface::call_interface!(crate::TicklessIf::restart_tick());
+    }
+
+    /// Whether the `idle` task has something to switch to.
+    #[cfg(feature = "irq")]
+    pub fn has_runnable(&self) -> bool {
+        let now = axhal::time::monotonic_time();
+        self.scheduler
+            .lock()
+            .tick_deadline(None, self.nr_ready(), now)
+            .is_some_and(|deadline| deadline <= now)
+    }
+
+    #[cfg(feature = "tickless")]
+    pub fn next_tick_deadline(&self) -> Option<axhal::time::TimeValue> {
+        let curr = crate::current();
+        let curr = (!curr.is_idle()).then(|| curr.as_task_ref());
+        self.scheduler
+            .lock()
+            .tick_deadline(curr, self.nr_ready(), axhal::time::monotonic_time())
+    }
+
+    pub fn yield_current(&self) {
         let curr = crate::current();
         trace!("task yield: {}", curr.id_name());
         assert!(curr.is_running());
         self.resched(false);
     }
 
-    pub fn set_current_priority(&mut self, prio: isize) -> bool {
+    pub fn set_current_priority(&self, prio: isize) -> bool {
         self.scheduler
+            .lock()
             .set_priority(crate::current().as_task_ref(), prio)
     }
 
     #[cfg(feature = "preempt")]
-    pub fn preempt_resched(&mut self) {
+    pub fn preempt_resched(&self) {
         let curr = crate::current();
         assert!(curr.is_running());
 
-        // When we get the mutable reference of the run queue, we must
-        // have held the `SpinNoIrq` lock with both IRQs and preemption
-        // disabled. So we need to set `current_disable_count` to 1 in
-        // `can_preempt()` to obtain the preemption permission before
-        //  locking the run queue.
+        // When we get the reference of the current run queue, we must have
+        // disabled both IRQs and preemption. So we need to set
+        // `current_disable_count` to 1 in `can_preempt()` to obtain the
+        // preemption permission.
         let can_preempt = curr.can_preempt(1);
 
         debug!(
@@ -81,28 +337,35 @@ impl AxRunQueue {
         }
     }
 
-    pub fn exit_current(&mut self, exit_code: i32) -> ! {
+    pub fn exit_current(&self, exit_code: i32) -> ! {
         let curr = crate::current();
         debug!("task exit: {}, exit_code={}", curr.id_name(), exit_code);
         assert!(curr.is_running());
         assert!(!curr.is_idle());
         if curr.is_init() {
             EXITED_TASKS.lock().clear();
-            axhal::misc::terminate();
+            axhal::misc::shutdown();
         } else {
+            curr.sched_entity().exit();
             curr.set_state(TaskState::Exited);
-            curr.notify_exit(exit_code, self);
-            EXITED_TASKS.lock().push_back(curr.clone());
-            WAIT_FOR_EXIT.notify_one_locked(false, self);
+            curr.notify_exit(exit_code);
+            // It is handed over to the `gc` task in `finish_switch()`.
             self.resched(false);
         }
         unreachable!("task exited!");
     }
 
-    pub fn block_current<F>(&mut self, wait_queue_push: F)
-    where
-        F: FnOnce(AxTaskRef),
-    {
+    /// Blocks the current task, and puts it into the wait queue locked by
+    /// `wq_guard`. The wait queue is unlocked before switching to another
+    /// task.
+    ///
+    /// If `interruptible`, the task is not blocked if it has been interrupted,
+    /// or it is woken up once it is, see [`interrupt_task`].
+    pub fn blocked_resched(
+        &self,
+        mut wq_guard: SpinRawGuard<VecDeque<AxTaskRef>>,
+        interruptible: bool,
+    ) {
         let curr = crate::current();
         debug!("task block: {}", curr.id_name());
         assert!(curr.is_running());
@@ -112,25 +375,28 @@ impl AxRunQueue {
         #[cfg(feature = "preempt")]
         assert!(curr.can_preempt(1));
 
-        curr.set_state(TaskState::Blocked);
-        wait_queue_push(curr.clone());
-        self.resched(false);
-    }
-
-    pub fn unblock_task(&mut self, task: AxTaskRef, resched: bool) {
-        debug!("task unblock: {}", task.id_name());
-        if task.is_blocked() {
-            task.set_state(TaskState::Ready);
-            self.scheduler.add_task(task); // TODO: priority
-            if resched {
-                #[cfg(feature = "preempt")]
-                crate::current().set_preempt_pending(true);
+        if interruptible {
+            curr.set_state(TaskState::Interruptible);
+            // Pairs with the fences in `interrupt()` and `request_exit()`:
+            // either the interruption is seen here, or the blocked state is
+            // seen there.
+            core::sync::atomic::fence(Ordering::SeqCst);
+            if curr.interrupt_pending()
+                && curr.transition_state(TaskState::Interruptible, TaskState::Running)
+            {
+                return;
             }
+        } else {
+            curr.set_state(TaskState::Blocked);
         }
+        curr.set_in_wait_queue(true);
+        wq_guard.push_back(curr.clone());
+        drop(wq_guard);
+        self.resched(false);
     }
 
     #[cfg(feature = "irq")]
-    pub fn sleep_until(&mut self, deadline: axhal::time::TimeValue) {
+    pub fn sleep_until(&self, deadline: axhal::time::TimeValue) {
         let curr = crate::current();
         debug!("task sleep: {}, deadline={:?}", curr.id_name(), deadline);
         assert!(curr.is_running());
@@ -148,22 +414,40 @@ impl AxRunQueue {
 impl AxRunQueue {
     /// Common reschedule subroutine. If `preempt`, keep current task's time
     /// slice, otherwise reset it.
-    fn resched(&mut self, preempt: bool) {
+    fn resched(&self, preempt: bool) {
         let prev = crate::current();
+        prev.sched_entity()
+            .update_runtime(axhal::time::monotonic_time());
+        let mut migrate = false;
         if prev.is_running() {
             prev.set_state(TaskState::Ready);
             if !prev.is_idle() {
-                self.scheduler.put_prev_task(prev.clone(), preempt);
+                if prev.cpu_affinity().get(self.cpu_id) {
+                    self.put_prev_task(prev.clone(), preempt);
+                } else {
+                    // It can be put into another run queue only after it has
+                    // switched out.
+                    migrate = true;
+                }
             }
         }
-        let next = self.scheduler.pick_next_task().unwrap_or_else(|| unsafe {
-            // Safety: IRQs must be disabled at this time.
-            IDLE_TASK.current_ref_raw().get_unchecked().clone()
-        });
-        self.switch_to(prev, next);
+        let next = self
+            .pick_next_task()
+            .or_else(|| self.steal_task())
+            .unwrap_or_else(|| unsafe {
+                // Safety: IRQs must be disabled at this time.
+                IDLE_TASK.current_ref_raw().get_unchecked().clone()
+            });
+        self.switch_to(prev, next, migrate, preempt);
     }
 
-    fn switch_to(&mut self, prev_task: CurrentTask, next_task: AxTaskRef) {
+    fn switch_to(
+        &self,
+        prev_task: CurrentTask,
+        next_task: AxTaskRef,
+        migrate: bool,
+        preempt: bool,
+    ) {
         trace!(
             "context switch: {} -> {}",
             prev_task.id_name(),
@@ -175,22 +459,63 @@ impl AxRunQueue {
         if prev_task.ptr_eq(&next_task) {
             return;
         }
+        next_task.set_cpu_id(self.cpu_id);
+        next_task.set_on_cpu(true);
+        let now = axhal::time::monotonic_time();
+        prev_task.switch_out(now, preempt);
+        next_task.switch_in(now);
+        #[cfg(feature = "tickless")]
+        crate_interface::call_interface!(crate::TicklessIf::restart_tick());
+        #[cfg(not(feature = "paging"))]
+        prev_task.check_stack_canary();
+        #[cfg(feature = "paging")]
+        axhal::trap::set_kernel_stack_limit(next_task.kernel_stack_bottom().unwrap_or(0.into()));
 
         unsafe {
             let prev_ctx_ptr = prev_task.ctx_mut_ptr();
             let next_ctx_ptr = next_task.ctx_mut_ptr();
 
-            // The strong reference count of `prev_task` will be decremented by 1,
-            // but won't be dropped until `gc_entry()` is called.
+            // The previous task is kept alive here, until the next task
+            // releases it in `finish_switch()`.
+            *PREV_TASK.current_ref_mut_raw() = Some(PrevTask {
+                task: prev_task.clone(),
+                migrate,
+            });
             assert!(Arc::strong_count(prev_task.as_task_ref()) > 1);
             assert!(Arc::strong_count(&next_task) >= 1);
 
             CurrentTask::set_current(prev_task, next_task);
             (*prev_ctx_ptr).switch_to(&*next_ctx_ptr);
+
+            // Now we are back as the previous task, maybe on another CPU.
+            finish_switch();
         }
     }
 }
 
+/// Finishes the context switch on the current CPU, in the context of the task
+/// that has just switched in.
+///
+/// The previous task can run elsewhere once its context is saved, so it is put
+/// into another run queue here if it is migrating, or handed over to the `gc`
+/// task if it has exited.
+///
+/// # Safety
+///
+/// It must be called right after a context switch, with IRQs disabled.
+pub(crate) unsafe fn finish_switch() {
+    let Some(PrevTask { task, migrate }) = PREV_TASK.current_ref_mut_raw().take() else {
+        return;
+    };
+    task.set_on_cpu(false);
+    if migrate {
+        select_run_queue(&task).add_task(task);
+    } else if task.state() == TaskState::Exited {
+        EXITED_TASKS.lock().push_back(task);
+        WAIT_FOR_EXIT.notify_one(false);
+    }
+}
+
 fn gc_entry() {
     loop {
         // Drop all exited tasks and recycle resources.
@@ -203,8 +528,8 @@ fn gc_entry() {
                     // If I'm the last holder of the task, drop it immediately.
                     drop(task);
                 } else {
-                    // Otherwise (e.g, `switch_to` is not compeleted, held by the
-                    // joiner, etc), push it back and wait for them to drop first.
+                    // Otherwise (e.g, held by the joiner), push it back and
+                    // wait for them to drop first.
                     EXITED_TASKS.lock().push_back(task);
                 }
             }
@@ -213,10 +538,22 @@ fn gc_entry() {
     }
 }
 
+/// Initializes the run queue of the current CPU, and makes it visible to the
+/// other CPUs.
+fn init_run_queue(cpu_id: usize) {
+    RUN_QUEUE.with_current(|rq| {
+        rq.init_once(AxRunQueue::new(cpu_id));
+    });
+    RUN_QUEUES[cpu_id].init_once(unsafe { RUN_QUEUE.current_ref_raw() });
+}
+
 pub(crate) fn init() {
+    let cpu_id = axhal::cpu::this_cpu_id();
+
     // Create the `idle` task (not current task).
     const IDLE_TASK_STACK_SIZE: usize = 4096;
     let idle_task = TaskInner::new(|| crate::run_idle(), "idle".into(), IDLE_TASK_STACK_SIZE);
+    idle_task.set_cpu_affinity(CpuMask::single(cpu_id));
     IDLE_TASK.with_current(|i| {
         i.init_once(idle_task.into_arc());
     });
@@ -226,15 +563,25 @@ pub(crate) fn init() {
     main_task.set_state(TaskState::Running);
     unsafe { CurrentTask::init_current(main_task) };
 
-    RUN_QUEUE.init_once(AxRunQueue::new());
+    init_run_queue(cpu_id);
+    let gc_task = TaskInner::new(gc_entry, "gc".into(), axconfig::TASK_STACK_SIZE)
+        .into_system()
+        .into_arc();
+    spawn_task(gc_task);
 }
 
 pub(crate) fn init_secondary() {
+    let cpu_id = axhal::cpu::this_cpu_id();
+
     // Put the subsequent execution into the `idle` task.
-    let idle_task = TaskInner::new_init("idle".into()).into_arc();
+    let idle_task = TaskInner::new_init("idle".into());
+    idle_task.set_cpu_affinity(CpuMask::single(cpu_id));
+    let idle_task = idle_task.into_arc();
     idle_task.set_state(TaskState::Running);
     IDLE_TASK.with_current(|i| {
         i.init_once(idle_task.clone());
     });
     unsafe { CurrentTask::init_current(idle_task) }
+
+    init_run_queue(cpu_id);
 }
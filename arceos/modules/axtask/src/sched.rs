@@ -0,0 +1,548 @@
+//! Scheduling classes on top of the scheduler chosen by cargo features.
+//!
+//! The tasks in a higher class always run before the ones in a lower class:
+//!
+//! 1. Deadline: the task with the earliest deadline runs first (EDF), and the
+//!    runtime budget of each task is enforced in every period.
+//! 2. Real-time: fixed priorities from 0 to [`MAX_RT_PRIO`], the higher the
+//!    earlier. Tasks of the same priority run in FIFO or round-robin order.
+//! 3. Fair: all the other tasks, scheduled by the [`FairScheduler`].
+
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU64, Ordering};
+use core::time::Duration;
+
+use axhal::time::{monotonic_time, TimeValue};
+use scheduler::BaseScheduler;
+
+use crate::{AxTaskRef, FairScheduler};
+
+/// The highest priority of the real-time policies.
+pub const MAX_RT_PRIO: u8 = 99;
+
+/// Time slice of the [`SchedPolicy::RoundRobin`] tasks, in timer ticks.
+const RT_TIME_SLICE: usize = 5;
+
+/// Bandwidth that the deadline tasks can reserve in total, in millionths of a
+/// CPU. 5% of each CPU is left for the other classes.
+const DL_BANDWIDTH_LIMIT: u64 = axconfig::SMP as u64 * 950_000;
+
+/// Bandwidth reserved by the deadline tasks, in millionths of a CPU.
+static DL_BANDWIDTH: AtomicU64 = AtomicU64::new(0);
+
+/// The scheduling policy of a task.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SchedPolicy {
+    /// Scheduled by the fair scheduler, with the priority set by
+    /// [`set_priority`](crate::set_priority).
+    #[default]
+    Normal,
+    /// Real-time with the priority, which runs until it blocks, yields, or is
+    /// preempted by a higher priority.
+    Fifo(u8),
+    /// Real-time with the priority, like [`SchedPolicy::Fifo`], but the tasks
+    /// of the same priority take turns after each time slice.
+    RoundRobin(u8),
+    /// Gets `runtime` of CPU time in every `period`, before the end of which
+    /// is its deadline.
+    Deadline {
+        /// The runtime budget in every period.
+        runtime: Duration,
+        /// The period, which is also the relative deadline.
+        period: Duration,
+    },
+}
+
+impl SchedPolicy {
+    fn is_valid(&self) -> bool {
+        match *self {
+            Self::Normal => true,
+            Self::Fifo(prio) | Self::RoundRobin(prio) => prio <= MAX_RT_PRIO,
+            Self::Deadline { runtime, period } => !runtime.is_zero() && runtime <= period,
+        }
+    }
+
+    /// Bandwidth reserved by the policy, in millionths of a CPU.
+    fn bandwidth(&self) -> u64 {
+        match *self {
+            Self::Deadline { runtime, period } => {
+                (runtime.as_nanos() * 1_000_000 / period.as_nanos()) as u64
+            }
+            _ => 0,
+        }
+    }
+}
+
+/// The class that a task is scheduled in, as its policy and inherited priority
+/// imply.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SchedClass {
+    Deadline,
+    RealTime(u8),
+    Fair,
+}
+
+/// Scheduling states of a task.
+///
+/// It is locked after the scheduler of a run queue, if both are needed.
+pub(crate) struct SchedEntity {
+    policy: SchedPolicy,
+    /// The real-time priority lent by the tasks waiting for it.
+    inherited: Option<u8>,
+    /// The CPU whose run queue the task is in, and the class it is queued as.
+    queued: Option<(usize, SchedClass)>,
+    /// Remaining timer ticks of a round-robin task.
+    time_slice: usize,
+    /// The absolute deadline of the current period of a deadline task.
+    deadline: TimeValue,
+    /// The runtime budget left in the current period of a deadline task.
+    budget: Duration,
+    /// When the CPU time was charged last time, or the task switched in.
+    exec_start: TimeValue,
+}
+
+impl SchedEntity {
+    pub const fn new() -> Self {
+        Self {
+            policy: SchedPolicy::Normal,
+            inherited: None,
+            queued: None,
+            time_slice: RT_TIME_SLICE,
+            deadline: TimeValue::ZERO,
+            budget: Duration::ZERO,
+            exec_start: TimeValue::ZERO,
+        }
+    }
+
+    pub fn policy(&self) -> SchedPolicy {
+        self.policy
+    }
+
+    pub fn inherited(&self) -> Option<u8> {
+        self.inherited
+    }
+
+    /// Returns the CPU whose run queue the task is in.
+    pub fn queued_cpu(&self) -> Option<usize> {
+        self.queued.map(|(cpu_id, _)| cpu_id)
+    }
+
+    /// Changes the policy and returns the old one. A deadline task starts a
+    /// new period at once.
+    fn set_policy(&mut self, policy: SchedPolicy) -> SchedPolicy {
+        let now = monotonic_time();
+        let old = core::mem::replace(&mut self.policy, policy);
+        self.time_slice = RT_TIME_SLICE;
+        self.deadline = TimeValue::ZERO;
+        self.budget = Duration::ZERO;
+        self.exec_start = now;
+        self.replenish(now);
+        old
+    }
+
+    /// Sets the highest priority lent for the locks that the task owns.
+    pub fn set_inherited(&mut self, prio: Option<u8>) {
+        self.inherited = prio;
+    }
+
+    /// Releases the reserved bandwidth when the task exits.
+    pub fn exit(&mut self) {
+        let old = self.set_policy(SchedPolicy::Normal);
+        release_bandwidth(old.bandwidth());
+        self.inherited = None;
+    }
+
+    fn class(&self) -> SchedClass {
+        match (self.policy, self.inherited) {
+            (SchedPolicy::Deadline { .. }, _) => SchedClass::Deadline,
+            (SchedPolicy::Fifo(prio) | SchedPolicy::RoundRobin(prio), inherited) => {
+                SchedClass::RealTime(prio.max(inherited.unwrap_or(0)))
+            }
+            (SchedPolicy::Normal, Some(prio)) => SchedClass::RealTime(prio),
+            (SchedPolicy::Normal, None) => SchedClass::Fair,
+        }
+    }
+
+    /// The real-time priority that the task lends to the owner of a lock it
+    /// waits for. A deadline task lends the highest one.
+    pub fn lending_prio(&self) -> Option<u8> {
+        match self.class() {
+            SchedClass::Deadline => Some(MAX_RT_PRIO),
+            SchedClass::RealTime(prio) => Some(prio),
+            SchedClass::Fair => None,
+        }
+    }
+
+    /// Charges the CPU time since `exec_start` to the budget of a deadline
+    /// task.
+    pub fn update_runtime(&mut self, now: TimeValue) {
+        if let SchedPolicy::Deadline { .. } = self.policy {
+            let delta = now.saturating_sub(self.exec_start);
+            self.budget = self.budget.saturating_sub(delta);
+        }
+        self.exec_start = now;
+    }
+
+    /// Starts a new period of a deadline task if the current one is over, or
+    /// the budget left cannot be used up before the deadline without exceeding
+    /// the reserved bandwidth (the wake-up rule of the constant bandwidth
+    /// server).
+    fn replenish(&mut self, now: TimeValue) {
+        let SchedPolicy::Deadline { runtime, period } = self.policy else {
+            return;
+        };
+        if self.deadline <= now
+            || self.budget.as_nanos() * period.as_nanos()
+                > (self.deadline - now).as_nanos() * runtime.as_nanos()
+        {
+            self.deadline = now + period;
+            self.budget = runtime;
+        }
+    }
+}
+
+/// Reserves the bandwidth of a deadline task. Returns `false` if the limit
+/// would be exceeded.
+fn reserve_bandwidth(bandwidth: u64) -> bool {
+    DL_BANDWIDTH
+        .fetch_update(Ordering::AcqRel, Ordering::Acquire, |total| {
+            Some(total + bandwidth).filter(|&total| total <= DL_BANDWIDTH_LIMIT)
+        })
+        .is_ok()
+}
+
+fn release_bandwidth(bandwidth: u64) {
+    DL_BANDWIDTH.fetch_sub(bandwidth, Ordering::AcqRel);
+}
+
+/// Sets the scheduling policy of the task, see [`crate::set_sched_policy`].
+pub(crate) fn set_sched_policy(task: &AxTaskRef, policy: SchedPolicy) -> bool {
+    if !policy.is_valid() || !reserve_bandwidth(policy.bandwidth()) {
+        return false;
+    }
+    let old = crate::run_queue::update_sched(task, |se| se.set_policy(policy));
+    release_bandwidth(old.bandwidth());
+    true
+}
+
+/// The scheduler of a run queue, with a queue for each class.
+pub(crate) struct Scheduler {
+    cpu_id: usize,
+    /// Deadline tasks ordered by their deadlines, and then by their IDs.
+    deadline: BTreeMap<(TimeValue, u64), AxTaskRef>,
+    /// Deadline tasks that have used up their budgets, until their deadlines.
+    throttled: Vec<AxTaskRef>,
+    /// Real-time tasks of each priority.
+    realtime: [VecDeque<AxTaskRef>; MAX_RT_PRIO as usize + 1],
+    /// Bitmap of the non-empty priorities in `realtime`.
+    realtime_bitmap: u128,
+    fair: FairScheduler,
+}
+
+impl Scheduler {
+    pub fn new(cpu_id: usize) -> Self {
+        Self {
+            cpu_id,
+            deadline: BTreeMap::new(),
+            throttled: Vec::new(),
+            realtime: [const { VecDeque::new() }; MAX_RT_PRIO as usize + 1],
+            realtime_bitmap: 0,
+            fair: FairScheduler::new(),
+        }
+    }
+
+    pub fn scheduler_name() -> &'static str {
+        FairScheduler::scheduler_name()
+    }
+
+    fn realtime_top(&self) -> Option<u8> {
+        (self.realtime_bitmap != 0).then(|| (127 - self.realtime_bitmap.leading_zeros()) as u8)
+    }
+
+    fn enqueue(&mut self, task: AxTaskRef, class: SchedClass, front: bool) {
+        match class {
+            SchedClass::Deadline => {
+                let (deadline, exhausted) = {
+                    let se = task.sched_entity();
+                    (se.deadline, se.budget.is_zero())
+                };
+                if exhausted {
+                    self.throttled.push(task);
+                } else {
+                    self.deadline.insert((deadline, task.id().as_u64()), task);
+                }
+            }
+            SchedClass::RealTime(prio) => {
+                let queue = &mut self.realtime[prio as usize];
+                if front {
+                    queue.push_front(task);
+                } else {
+                    queue.push_back(task);
+                }
+                self.realtime_bitmap |= 1 << prio;
+            }
+            SchedClass::Fair => unreachable!(),
+        }
+    }
+
+    fn remove_realtime(&mut self, prio: u8, index: usize) -> Option<AxTaskRef> {
+        let queue = &mut self.realtime[prio as usize];
+        let task = queue.remove(index);
+        if queue.is_empty() {
+            self.realtime_bitmap &= !(1 << prio);
+        }
+        task
+    }
+
+    /// Moves the throttled tasks whose periods are over back to the queue.
+    fn release_throttled(&mut self, now: TimeValue) {
+        let mut i = 0;
+        while i < self.throttled.len() {
+            if self.throttled[i].sched_entity().deadline <= now {
+                let task = self.throttled.swap_remove(i);
+                let deadline = {
+                    let mut se = task.sched_entity();
+                    se.replenish(now);
+                    se.deadline
+                };
+                self.deadline.insert((deadline, task.id().as_u64()), task);
+            } else {
+                i += 1;
+            }
+        }
+    }
+
+    /// Marks the task as taken out to run.
+    fn dequeued(task: AxTaskRef, now: TimeValue) -> AxTaskRef {
+        let mut se = task.sched_entity();
+        se.queued = None;
+        se.exec_start = now;
+        drop(se);
+        task
+    }
+
+    /// Whether a queued task should preempt the running task with `se`.
+    fn outranks(&self, se: &SchedEntity) -> bool {
+        match se.class() {
+            SchedClass::Deadline => self
+                .deadline
+                .first_key_value()
+                .is_some_and(|(&(deadline, _), _)| deadline < se.deadline),
+            SchedClass::RealTime(prio) => {
+                !self.deadline.is_empty() || self.realtime_top().is_some_and(|top| top > prio)
+            }
+            SchedClass::Fair => !self.deadline.is_empty() || self.realtime_bitmap != 0,
+        }
+    }
+
+    /// Whether the running task `curr` should be preempted by a queued task of
+    /// a higher class or priority.
+    #[cfg(feature = "preempt")]
+    pub fn should_preempt(&self, curr: &AxTaskRef) -> bool {
+        !curr.is_idle() && self.outranks(&curr.sched_entity())
+    }
+
+    /// Returns when the timer ticks are needed again to advance the scheduler
+    /// states, for the running task `curr` (or [`None`] for `idle`), and the
+    /// `nr_queued` tasks in the scheduler. It is `now` if the ticks should
+    /// keep going, or [`None`] if they are not needed at all.
+    #[cfg(any(feature = "irq", test))]
+    pub fn tick_deadline(
+        &self,
+        curr: Option<&AxTaskRef>,
+        nr_queued: usize,
+        now: TimeValue,
+    ) -> Option<TimeValue> {
+        // The throttled tasks come back at their deadlines.
+        let released = self
+            .throttled
+            .iter()
+            .map(|task| task.sched_entity().deadline)
+            .min();
+        let runnable = nr_queued > self.throttled.len();
+        let Some(curr) = curr else {
+            return if runnable { Some(now) } else { released };
+        };
+        let se = curr.sched_entity();
+        let deadline = if self.outranks(&se) {
+            Some(now)
+        } else {
+            match se.class() {
+                SchedClass::Deadline => Some((se.exec_start + se.budget).max(now)),
+                SchedClass::RealTime(prio) => match se.policy {
+                    SchedPolicy::RoundRobin(_) if !self.realtime[prio as usize].is_empty() => {
+                        Some(now)
+                    }
+                    _ => None,
+                },
+                SchedClass::Fair => {
+                    // The FIFO fair scheduler has no time slices.
+                    let sliced = cfg!(any(feature = "sched_rr", feature = "sched_cfs"));
+                    (sliced && runnable).then_some(now)
+                }
+            }
+        };
+        deadline.into_iter().chain(released).min()
+    }
+
+    /// Takes a task that is allowed to run on the CPU and is not running, for
+    /// the CPU to steal it. The higher classes are tried first.
+    pub fn steal_task(&mut self, cpu_id: usize) -> Option<AxTaskRef> {
+        let now = monotonic_time();
+        let can_steal = |task: &AxTaskRef| task.cpu_affinity().get(cpu_id) && !task.on_cpu();
+
+        let key = self.deadline.iter().find(|(_, task)| can_steal(task));
+        if let Some(key) = key.map(|(&key, _)| key) {
+            let task = self.deadline.remove(&key).unwrap();
+            return Some(Self::dequeued(task, now));
+        }
+        for prio in (0..=MAX_RT_PRIO).rev() {
+            if self.realtime_bitmap & (1 << prio) == 0 {
+                continue;
+            }
+            if let Some(index) = self.realtime[prio as usize].iter().position(can_steal) {
+                let task = self.remove_realtime(prio, index).unwrap();
+                return Some(Self::dequeued(task, now));
+            }
+        }
+        // The fair scheduler can only be looked into from its head, so the
+        // tasks are taken out until one can be stolen. If any is skipped, all
+        // the others are taken out too, and put back in the same order.
+        let mut skipped = Vec::new();
+        let stolen = loop {
+            let Some(task) = self.fair.pick_next_task() else {
+                break None;
+            };
+            if can_steal(&task) {
+                break Some(task);
+            }
+            skipped.push(task);
+        };
+        if !skipped.is_empty() {
+            while let Some(task) = self.fair.pick_next_task() {
+                skipped.push(task);
+            }
+            for task in skipped {
+                self.fair.put_prev_task(task, false);
+            }
+        }
+        stolen.map(|task| Self::dequeued(task, now))
+    }
+}
+
+impl BaseScheduler for Scheduler {
+    type SchedItem = AxTaskRef;
+
+    fn init(&mut self) {
+        self.fair.init();
+    }
+
+    fn add_task(&mut self, task: AxTaskRef) {
+        let class = {
+            let mut se = task.sched_entity();
+            let class = se.class();
+            if class == SchedClass::Deadline {
+                se.replenish(monotonic_time());
+            }
+            se.queued = Some((self.cpu_id, class));
+            class
+        };
+        match class {
+            SchedClass::Fair => self.fair.add_task(task),
+            _ => self.enqueue(task, class, false),
+        }
+    }
+
+    fn remove_task(&mut self, task: &AxTaskRef) -> Option<AxTaskRef> {
+        let (class, deadline) = {
+            let se = task.sched_entity();
+            match se.queued {
+                Some((cpu_id, class)) if cpu_id == self.cpu_id => (class, se.deadline),
+                _ => return None,
+            }
+        };
+        let task_ref = match class {
+            SchedClass::Deadline => self
+                .deadline
+                .remove(&(deadline, task.id().as_u64()))
+                .or_else(|| {
+                    let index = self.throttled.iter().position(|t| Arc::ptr_eq(t, task))?;
+                    Some(self.throttled.swap_remove(index))
+                }),
+            SchedClass::RealTime(prio) => {
+                let queue = &self.realtime[prio as usize];
+                let index = queue.iter().position(|t| Arc::ptr_eq(t, task))?;
+                self.remove_realtime(prio, index)
+            }
+            SchedClass::Fair => self.fair.remove_task(task),
+        };
+        if task_ref.is_some() {
+            task.sched_entity().queued = None;
+        }
+        task_ref
+    }
+
+    fn pick_next_task(&mut self) -> Option<AxTaskRef> {
+        let now = monotonic_time();
+        self.release_throttled(now);
+        let task = if let Some((_, task)) = self.deadline.pop_first() {
+            task
+        } else if let Some(prio) = self.realtime_top() {
+            self.remove_realtime(prio, 0).unwrap()
+        } else {
+            self.fair.pick_next_task()?
+        };
+        Some(Self::dequeued(task, now))
+    }
+
+    fn put_prev_task(&mut self, prev: AxTaskRef, preempt: bool) {
+        let (class, front) = {
+            let mut se = prev.sched_entity();
+            let class = se.class();
+            // A preempted real-time task stays at the head of its priority,
+            // unless its time slice is used up.
+            let front = preempt && se.time_slice > 0;
+            if !front {
+                se.time_slice = RT_TIME_SLICE;
+            }
+            se.queued = Some((self.cpu_id, class));
+            (class, front)
+        };
+        match class {
+            SchedClass::Fair => self.fair.put_prev_task(prev, preempt),
+            _ => self.enqueue(prev, class, front),
+        }
+    }
+
+    fn task_tick(&mut self, current: &AxTaskRef) -> bool {
+        let now = monotonic_time();
+        self.release_throttled(now);
+        let mut se = current.sched_entity();
+        match se.class() {
+            SchedClass::Deadline => {
+                se.update_runtime(now);
+                se.budget.is_zero() || self.outranks(&se)
+            }
+            SchedClass::RealTime(_) => {
+                if let SchedPolicy::RoundRobin(_) = se.policy {
+                    se.time_slice = se.time_slice.saturating_sub(1);
+                    if se.time_slice == 0 {
+                        return true;
+                    }
+                }
+                self.outranks(&se)
+            }
+            SchedClass::Fair => {
+                let outranked = self.outranks(&se);
+                drop(se);
+                outranked || self.fair.task_tick(current)
+            }
+        }
+    }
+
+    fn set_priority(&mut self, task: &AxTaskRef, prio: isize) -> bool {
+        self.fair.set_priority(task, prio)
+    }
+}
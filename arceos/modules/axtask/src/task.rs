@@ -1,19 +1,23 @@
 use alloc::{boxed::Box, string::String, sync::Arc};
 use core::ops::Deref;
-use core::sync::atomic::{AtomicBool, AtomicI32, AtomicU64, AtomicU8, Ordering};
+use core::sync::atomic::{AtomicBool, AtomicI32, AtomicU64, AtomicU8, AtomicUsize, Ordering};
 use core::{alloc::Layout, cell::UnsafeCell, fmt, ptr::NonNull};
 
-#[cfg(feature = "preempt")]
-use core::sync::atomic::AtomicUsize;
-
 #[cfg(feature = "tls")]
 use axhal::tls::TlsArea;
 
 use axhal::arch::TaskContext;
+use kspin::{SpinNoIrq, SpinNoIrqGuard};
 use memory_addr::{align_up_4k, VirtAddr};
 
+use crate::registry::{CpuTimes, TaskInfo};
+use crate::sched::SchedEntity;
 use crate::task_ext::AxTaskExt;
-use crate::{AxRunQueue, AxTask, AxTaskRef, WaitQueue};
+use crate::{AxTask, AxTaskRef, CpuMask, SchedPolicy, WaitQueue};
+
+/// Maximum number of locks owned by a task that are released on its exit, see
+/// [`crate::own_lock`].
+const MAX_OWNED_LOCKS: usize = 16;
 
 /// A unique identifier for a thread.
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
@@ -22,11 +26,58 @@ pub struct TaskId(u64);
 /// The possible states of a task.
 #[repr(u8)]
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
-pub(crate) enum TaskState {
+pub enum TaskState {
+    /// Running on a CPU.
     Running = 1,
+    /// Ready to run, in a run queue.
     Ready = 2,
+    /// Blocked, e.g., waiting in a wait queue or sleeping.
     Blocked = 3,
+    /// Exited, but not dropped yet.
     Exited = 4,
+    /// Blocked in an interruptible wait, which also ends if the task is
+    /// interrupted, see [`Interrupted`](crate::Interrupted).
+    Interruptible = 5,
+}
+
+/// A lock owned by a task.
+#[derive(Clone, Copy)]
+struct OwnedLock {
+    addr: usize,
+    /// The function to release the lock.
+    release: fn(usize),
+    /// The highest priority lent by the tasks waiting for the lock.
+    boost: Option<u8>,
+}
+
+/// The locks owned by a task, in the order of acquisition.
+struct OwnedLocks {
+    locks: [Option<OwnedLock>; MAX_OWNED_LOCKS],
+    len: usize,
+}
+
+impl OwnedLocks {
+    const fn new() -> Self {
+        Self {
+            locks: [None; MAX_OWNED_LOCKS],
+            len: 0,
+        }
+    }
+
+    fn position(&self, addr: usize) -> Option<usize> {
+        self.locks[..self.len]
+            .iter()
+            .rposition(|lock| lock.is_some_and(|lock| lock.addr == addr))
+    }
+
+    /// The highest priority lent for any of the locks.
+    fn inherited(&self) -> Option<u8> {
+        self.locks[..self.len]
+            .iter()
+            .flatten()
+            .filter_map(|lock| lock.boost)
+            .max()
+    }
 }
 
 /// The inner task structure.
@@ -35,13 +86,29 @@ pub struct TaskInner {
     name: String,
     is_idle: bool,
     is_init: bool,
+    is_system: bool,
 
     entry: Option<*mut dyn FnOnce()>,
     state: AtomicU8,
 
+    /// CPUs that the task is allowed to run on.
+    cpumask: SpinNoIrq<CpuMask>,
+    /// The CPU that the task is running on, or whose run queue it is in.
+    cpu_id: AtomicUsize,
+    /// Whether the task is running on a CPU, i.e., its context is not saved
+    /// yet even if it is going to switch out.
+    on_cpu: AtomicBool,
+    sched: SpinNoIrq<SchedEntity>,
+
     in_wait_queue: AtomicBool,
+    /// Only the timer event with the current ticket wakes the task up, the
+    /// others are cancelled.
+    #[cfg(feature = "irq")]
+    timer_ticket: AtomicU64,
+    /// The CPU whose timer list holds the pending timer event, or
+    /// `usize::MAX` if there is none.
     #[cfg(feature = "irq")]
-    in_timer_list: AtomicBool,
+    timer_cpu: AtomicUsize,
 
     #[cfg(feature = "preempt")]
     need_resched: AtomicBool,
@@ -50,6 +117,24 @@ pub struct TaskInner {
 
     exit_code: AtomicI32,
     wait_for_exit: WaitQueue,
+    /// Whether the task has been asked to exit by [`crate::request_exit`].
+    exit_requested: AtomicBool,
+    /// Set by [`crate::interrupt`], until the task clears it.
+    interrupted: AtomicBool,
+    /// The locks owned by the task, to be released if it exits without
+    /// unlocking them. Other tasks remove them too, e.g., by dropping them.
+    ///
+    /// It is locked before the scheduling states, which are updated with the
+    /// priorities lent for the locks.
+    owned_locks: SpinNoIrq<OwnedLocks>,
+    /// The lock that the task is going to wait for and the ID of its owner,
+    /// for the priorities lent to the task to be passed on to the owner.
+    waiting_lock: SpinNoIrq<Option<(usize, u64)>>,
+
+    cpu_times: SpinNoIrq<CpuTimes>,
+    /// Numbers of voluntary and involuntary context switches.
+    nvcsw: AtomicU64,
+    nivcsw: AtomicU64,
 
     kstack: Option<TaskStack>,
     ctx: UnsafeCell<TaskContext>,
@@ -57,6 +142,11 @@ pub struct TaskInner {
 
     #[cfg(feature = "tls")]
     tls: TlsArea,
+
+    /// The locks held by the task, only accessed by itself with IRQs
+    /// disabled.
+    #[cfg(feature = "lockdep")]
+    held_locks: UnsafeCell<crate::lockdep::HeldLocks>,
 }
 
 impl TaskId {
@@ -79,6 +169,7 @@ impl From<u8> for TaskState {
             2 => Self::Ready,
             3 => Self::Blocked,
             4 => Self::Exited,
+            5 => Self::Interruptible,
             _ => unreachable!(),
         }
     }
@@ -95,7 +186,7 @@ impl TaskInner {
     {
         let mut t = Self::new_common(TaskId::new(), name);
         debug!("new task: {}", t.id_name());
-        let kstack = TaskStack::alloc(align_up_4k(stack_size));
+        let kstack = TaskStack::alloc(stack_size);
 
         #[cfg(feature = "tls")]
         let tls = VirtAddr::from(t.tls.tls_ptr() as usize);
@@ -126,6 +217,67 @@ impl TaskInner {
         alloc::format!("Task({}, {:?})", self.id.as_u64(), self.name)
     }
 
+    /// Returns the CPUs that the task is allowed to run on. It is all CPUs by
+    /// default.
+    pub fn cpu_affinity(&self) -> CpuMask {
+        *self.cpumask.lock()
+    }
+
+    /// Sets the CPUs that the task is allowed to run on.
+    ///
+    /// It takes effect the next time the task is put into a run queue, e.g.,
+    /// when it is woken up. Use [`set_current_affinity`] to move the current
+    /// task at once.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `mask` is empty.
+    ///
+    /// [`set_current_affinity`]: crate::set_current_affinity
+    pub fn set_cpu_affinity(&self, mask: CpuMask) {
+        assert!(!mask.is_empty(), "empty CPU affinity");
+        *self.cpumask.lock() = mask;
+    }
+
+    /// Returns the ID of the CPU that the task is running on, or the one it
+    /// is going to run on if it is ready.
+    pub fn cpu_id(&self) -> usize {
+        self.cpu_id.load(Ordering::Acquire)
+    }
+
+    /// Returns the scheduling policy of the task, which is
+    /// [`SchedPolicy::Normal`] by default.
+    ///
+    /// Use [`set_sched_policy`] to change it.
+    ///
+    /// [`set_sched_policy`]: crate::set_sched_policy
+    pub fn sched_policy(&self) -> SchedPolicy {
+        self.sched.lock().policy()
+    }
+
+    /// Returns the information and statistics of the task.
+    pub fn info(&self) -> TaskInfo {
+        let state = self.state();
+        let (user_time, kernel_time) = self
+            .cpu_times
+            .lock()
+            .get(axhal::time::monotonic_time(), state == TaskState::Running);
+        TaskInfo {
+            id: self.id.as_u64(),
+            name: self.name.clone(),
+            state,
+            cpu_id: self.cpu_id(),
+            policy: self.sched_policy(),
+            user_time,
+            kernel_time,
+            voluntary_switches: self.nvcsw.load(Ordering::Relaxed),
+            involuntary_switches: self.nivcsw.load(Ordering::Relaxed),
+            stack_size: self.kstack.as_ref().map_or(0, |s| s.layout.size()),
+            stack_high_water: self.stack_high_water_mark(),
+            in_wait_queue: self.in_wait_queue(),
+        }
+    }
+
     /// Wait for the task to exit, and return the exit code.
     ///
     /// It will return immediately if the task has already exited (but not dropped).
@@ -169,22 +321,38 @@ impl TaskInner {
             name,
             is_idle: false,
             is_init: false,
+            is_system: false,
             entry: None,
             state: AtomicU8::new(TaskState::Ready as u8),
+            cpumask: SpinNoIrq::new(CpuMask::full()),
+            cpu_id: AtomicUsize::new(0),
+            on_cpu: AtomicBool::new(false),
+            sched: SpinNoIrq::new(SchedEntity::new()),
             in_wait_queue: AtomicBool::new(false),
             #[cfg(feature = "irq")]
-            in_timer_list: AtomicBool::new(false),
+            timer_ticket: AtomicU64::new(0),
+            #[cfg(feature = "irq")]
+            timer_cpu: AtomicUsize::new(usize::MAX),
             #[cfg(feature = "preempt")]
             need_resched: AtomicBool::new(false),
             #[cfg(feature = "preempt")]
             preempt_disable_count: AtomicUsize::new(0),
             exit_code: AtomicI32::new(0),
             wait_for_exit: WaitQueue::new(),
+            exit_requested: AtomicBool::new(false),
+            interrupted: AtomicBool::new(false),
+            owned_locks: SpinNoIrq::new(OwnedLocks::new()),
+            waiting_lock: SpinNoIrq::new(None),
+            cpu_times: SpinNoIrq::new(CpuTimes::default()),
+            nvcsw: AtomicU64::new(0),
+            nivcsw: AtomicU64::new(0),
             kstack: None,
             ctx: UnsafeCell::new(TaskContext::new()),
             task_ext: AxTaskExt::empty(),
             #[cfg(feature = "tls")]
             tls: TlsArea::alloc(),
+            #[cfg(feature = "lockdep")]
+            held_locks: UnsafeCell::new(crate::lockdep::HeldLocks::new()),
         }
     }
 
@@ -205,8 +373,16 @@ impl TaskInner {
         t
     }
 
+    /// Marks the task as a system task, which cannot exit.
+    pub(crate) fn into_system(mut self) -> Self {
+        self.is_system = true;
+        self
+    }
+
     pub(crate) fn into_arc(self) -> AxTaskRef {
-        Arc::new(AxTask::new(self))
+        let task = Arc::new(AxTask::new(self));
+        crate::registry::register(&task);
+        task
     }
 
     #[inline]
@@ -219,6 +395,15 @@ impl TaskInner {
         self.state.store(state as u8, Ordering::Release)
     }
 
+    /// Changes the state from `from` to `to` atomically. Returns `false` if
+    /// the state was not `from`.
+    #[inline]
+    pub(crate) fn transition_state(&self, from: TaskState, to: TaskState) -> bool {
+        self.state
+            .compare_exchange(from as u8, to as u8, Ordering::AcqRel, Ordering::Acquire)
+            .is_ok()
+    }
+
     #[inline]
     pub(crate) fn is_running(&self) -> bool {
         matches!(self.state(), TaskState::Running)
@@ -229,19 +414,24 @@ impl TaskInner {
         matches!(self.state(), TaskState::Ready)
     }
 
+    /// Whether the task is an init task, i.e., the main task or the first
+    /// task of a secondary CPU.
     #[inline]
-    pub(crate) fn is_blocked(&self) -> bool {
-        matches!(self.state(), TaskState::Blocked)
+    pub const fn is_init(&self) -> bool {
+        self.is_init
     }
 
+    /// Whether the task is an idle task.
     #[inline]
-    pub(crate) const fn is_init(&self) -> bool {
-        self.is_init
+    pub const fn is_idle(&self) -> bool {
+        self.is_idle
     }
 
+    /// Whether the task is a system task, i.e., an idle, init, `gc` or `timer`
+    /// task, which cannot exit.
     #[inline]
-    pub(crate) const fn is_idle(&self) -> bool {
-        self.is_idle
+    pub const fn is_system(&self) -> bool {
+        self.is_system || self.is_idle || self.is_init
     }
 
     #[inline]
@@ -254,16 +444,54 @@ impl TaskInner {
         self.in_wait_queue.store(in_wait_queue, Ordering::Release);
     }
 
+    #[inline]
+    pub(crate) fn set_cpu_id(&self, cpu_id: usize) {
+        self.cpu_id.store(cpu_id, Ordering::Release);
+    }
+
+    #[inline]
+    pub(crate) fn on_cpu(&self) -> bool {
+        self.on_cpu.load(Ordering::Acquire)
+    }
+
+    #[inline]
+    pub(crate) fn set_on_cpu(&self, on_cpu: bool) {
+        self.on_cpu.store(on_cpu, Ordering::Release);
+    }
+
+    #[inline]
+    pub(crate) fn sched_entity(&self) -> SpinNoIrqGuard<SchedEntity> {
+        self.sched.lock()
+    }
+
+    #[inline]
+    #[cfg(feature = "irq")]
+    pub(crate) fn timer_ticket(&self) -> u64 {
+        self.timer_ticket.load(Ordering::Acquire)
+    }
+
+    /// Cancels the pending timer event of the task, by moving on to the next
+    /// ticket.
+    #[inline]
+    #[cfg(feature = "irq")]
+    pub(crate) fn expire_timer_ticket(&self) {
+        self.timer_ticket.fetch_add(1, Ordering::AcqRel);
+    }
+
     #[inline]
     #[cfg(feature = "irq")]
-    pub(crate) fn in_timer_list(&self) -> bool {
-        self.in_timer_list.load(Ordering::Acquire)
+    pub(crate) fn set_timer_cpu(&self, cpu_id: usize) {
+        self.timer_cpu.store(cpu_id, Ordering::Release);
     }
 
+    /// Takes the CPU whose timer list holds the pending timer event.
     #[inline]
     #[cfg(feature = "irq")]
-    pub(crate) fn set_in_timer_list(&self, in_timer_list: bool) {
-        self.in_timer_list.store(in_timer_list, Ordering::Release);
+    pub(crate) fn take_timer_cpu(&self) -> Option<usize> {
+        match self.timer_cpu.swap(usize::MAX, Ordering::AcqRel) {
+            usize::MAX => None,
+            cpu_id => Some(cpu_id),
+        }
     }
 
     #[inline]
@@ -272,6 +500,12 @@ impl TaskInner {
         self.need_resched.store(pending, Ordering::Release)
     }
 
+    #[inline]
+    #[cfg(feature = "lockdep")]
+    pub(crate) fn held_locks(&self) -> *mut crate::lockdep::HeldLocks {
+        self.held_locks.get()
+    }
+
     #[inline]
     #[cfg(feature = "preempt")]
     pub(crate) fn can_preempt(&self, current_disable_count: usize) -> bool {
@@ -297,16 +531,136 @@ impl TaskInner {
     fn current_check_preempt_pending() {
         let curr = crate::current();
         if curr.need_resched.load(Ordering::Acquire) && curr.can_preempt(0) {
-            let mut rq = crate::RUN_QUEUE.lock();
+            let rq = crate::run_queue::current_run_queue();
             if curr.need_resched.load(Ordering::Acquire) {
                 rq.preempt_resched();
             }
         }
     }
 
-    pub(crate) fn notify_exit(&self, exit_code: i32, rq: &mut AxRunQueue) {
+    /// Charges the CPU time when the task traps from or returns to user space,
+    /// or switches out.
+    #[inline]
+    pub(crate) fn charge_cpu_time(&self, now: axhal::time::TimeValue, in_user: bool) {
+        self.cpu_times.lock().charge(now, in_user);
+    }
+
+    /// Counts a context switch, and charges the CPU time before it.
+    pub(crate) fn switch_out(&self, now: axhal::time::TimeValue, preempt: bool) {
+        self.charge_cpu_time(now, false);
+        if preempt {
+            self.nivcsw.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.nvcsw.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    #[inline]
+    pub(crate) fn switch_in(&self, now: axhal::time::TimeValue) {
+        self.cpu_times.lock().start(now);
+    }
+
+    /// Returns the most bytes of the kernel stack that have ever been used.
+    ///
+    /// The stack of a task running on another CPU is being written, so it is
+    /// only scanned by the task itself or while the task is switched out, and
+    /// the scan is discarded if the task has switched in meanwhile. The last
+    /// mark found is returned otherwise.
+    fn stack_high_water_mark(&self) -> usize {
+        let Some(stack) = self.kstack.as_ref() else {
+            return 0;
+        };
+        let switches = || self.nvcsw.load(Ordering::Relaxed) + self.nivcsw.load(Ordering::Relaxed);
+        if crate::current_may_uninit().is_some_and(|curr| core::ptr::eq(&*curr, self)) {
+            stack.record_high_water_mark(stack.scan_high_water_mark());
+        } else if !self.on_cpu() {
+            let before = switches();
+            let mark = stack.scan_high_water_mark();
+            core::sync::atomic::fence(Ordering::Acquire);
+            if !self.on_cpu() && switches() == before {
+                stack.record_high_water_mark(mark);
+            }
+        }
+        stack.high_water.load(Ordering::Relaxed)
+    }
+
+    /// Asks the task to exit with the exit code. Returns `false` if it has
+    /// been asked before.
+    pub(crate) fn set_exit_requested(&self, exit_code: i32) -> bool {
+        if self.exit_requested.swap(true, Ordering::AcqRel) {
+            return false;
+        }
+        self.exit_code.store(exit_code, Ordering::Release);
+        true
+    }
+
+    #[inline]
+    pub(crate) fn exit_requested(&self) -> Option<i32> {
+        if self.exit_requested.load(Ordering::Acquire) {
+            Some(self.exit_code.load(Ordering::Acquire))
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    pub(crate) fn set_interrupted(&self, interrupted: bool) {
+        self.interrupted.store(interrupted, Ordering::Release);
+    }
+
+    /// Whether the task should leave its interruptible waits, i.e., it has
+    /// been interrupted or asked to exit.
+    #[inline]
+    pub(crate) fn interrupt_pending(&self) -> bool {
+        self.interrupted.load(Ordering::Acquire) || self.exit_requested.load(Ordering::Acquire)
+    }
+
+    pub(crate) fn own_lock(&self, addr: usize, release: fn(usize)) {
+        let mut locks = self.owned_locks.lock();
+        if locks.len == MAX_OWNED_LOCKS {
+            warn!(
+                "{} owns too many locks, the lock at {:#x} is not released on its exit",
+                self.id_name(),
+                addr
+            );
+            return;
+        }
+        let len = locks.len;
+        locks.locks[len] = Some(OwnedLock {
+            addr,
+            release,
+            boost: None,
+        });
+        locks.len += 1;
+    }
+
+    /// Releases the locks that the task still owns, the latest first.
+    pub(crate) fn release_owned_locks(&self) {
+        let locks = core::mem::replace(&mut *self.owned_locks.lock(), OwnedLocks::new());
+        for lock in locks.locks[..locks.len].iter().rev().flatten() {
+            (lock.release)(lock.addr);
+        }
+    }
+
+    #[inline]
+    pub(crate) fn waiting_lock(&self) -> Option<(usize, u64)> {
+        *self.waiting_lock.lock()
+    }
+
+    #[inline]
+    pub(crate) fn set_waiting_lock(&self, lock: Option<(usize, u64)>) {
+        *self.waiting_lock.lock() = lock;
+    }
+
+    /// Returns the real-time priority lent by the tasks waiting for the locks
+    /// that the task owns, see [`crate::wait_for_lock`].
+    pub fn inherited_priority(&self) -> Option<u8> {
+        self.sched.lock().inherited()
+    }
+
+    pub(crate) fn notify_exit(&self, exit_code: i32) {
         self.exit_code.store(exit_code, Ordering::Release);
-        self.wait_for_exit.notify_all_locked(false, rq);
+        self.wait_for_exit.notify_all(false);
     }
 
     #[inline]
@@ -328,6 +682,22 @@ impl TaskInner {
             None => None,
         }
     }
+
+    /// Returns the lowest address of the kernel stack.
+    #[inline]
+    #[cfg(feature = "paging")]
+    pub(crate) fn kernel_stack_bottom(&self) -> Option<VirtAddr> {
+        self.kstack.as_ref().map(|s| s.bottom())
+    }
+
+    /// Panics if the kernel stack has overflowed, which is found out by the
+    /// canary at the bottom of the stack.
+    #[cfg(not(feature = "paging"))]
+    pub(crate) fn check_stack_canary(&self) {
+        if self.kstack.as_ref().is_some_and(|s| s.canary_changed()) {
+            panic!("kernel stack overflow in task {}", self.id_name());
+        }
+    }
 }
 
 impl fmt::Debug for TaskInner {
@@ -343,23 +713,82 @@ impl fmt::Debug for TaskInner {
 impl Drop for TaskInner {
     fn drop(&mut self) {
         debug!("task drop: {}", self.id_name());
+        crate::registry::unregister(self.id.as_u64());
     }
 }
 
 struct TaskStack {
     ptr: NonNull<u8>,
     layout: Layout,
+    /// The most bytes that have been found used by scanning the stack.
+    high_water: AtomicUsize,
 }
 
+/// The pattern that an unused stack is filled with, to find out how much of it
+/// has been used.
+const STACK_PAINT: u64 = 0x5a5a_5a5a_5a5a_5a5a;
+
+/// The word at the bottom of a stack without a guard area, which is changed
+/// if the stack overflows.
+const STACK_CANARY: u64 = 0xdead_beef_c0de_cafe;
+
+/// Number of words at the bottom that hold the canary instead of the paint.
+const CANARY_WORDS: usize = if cfg!(feature = "paging") { 0 } else { 1 };
+
 impl TaskStack {
+    /// Allocates a stack, which is mapped with a guard area below it if the
+    /// `paging` feature is enabled, or is taken from the heap with a canary at
+    /// the bottom otherwise. The size is rounded up to 4K.
     pub fn alloc(size: usize) -> Self {
+        let size = align_up_4k(size);
         let layout = Layout::from_size_align(size, 16).unwrap();
+        #[cfg(feature = "paging")]
+        let ptr = crate_interface::call_interface!(crate::KernelStackIf::alloc_kernel_stack(size))
+            .and_then(|bottom| NonNull::new(bottom.as_mut_ptr()))
+            .expect("failed to allocate the kernel stack");
+        #[cfg(not(feature = "paging"))]
+        let ptr = NonNull::new(unsafe { alloc::alloc::alloc(layout) }).unwrap();
+        let words = size / core::mem::size_of::<u64>();
+        let stack = unsafe { core::slice::from_raw_parts_mut(ptr.as_ptr() as *mut u64, words) };
+        stack[..CANARY_WORDS].fill(STACK_CANARY);
+        stack[CANARY_WORDS..].fill(STACK_PAINT);
         Self {
-            ptr: NonNull::new(unsafe { alloc::alloc::alloc(layout) }).unwrap(),
+            ptr,
             layout,
+            high_water: AtomicUsize::new(0),
         }
     }
 
+    /// Scans for the most bytes that have ever been used, as the stack grows
+    /// down from the top over the painted words.
+    ///
+    /// The words are read with volatile reads, as the stack may be written by
+    /// its task meanwhile, in which case the caller discards the result.
+    pub fn scan_high_water_mark(&self) -> usize {
+        let words = self.layout.size() / core::mem::size_of::<u64>();
+        let base = self.ptr.as_ptr() as *const u64;
+        let unused = (CANARY_WORDS..words)
+            .take_while(|&i| unsafe { base.add(i).read_volatile() } == STACK_PAINT)
+            .count();
+        self.layout.size() - (CANARY_WORDS + unused) * core::mem::size_of::<u64>()
+    }
+
+    pub fn record_high_water_mark(&self, mark: usize) {
+        self.high_water.fetch_max(mark, Ordering::Relaxed);
+    }
+
+    /// Returns whether the canary at the bottom is changed, i.e., the stack
+    /// has overflowed.
+    #[cfg(not(feature = "paging"))]
+    pub fn canary_changed(&self) -> bool {
+        unsafe { (self.ptr.as_ptr() as *const u64).read() != STACK_CANARY }
+    }
+
+    #[cfg(feature = "paging")]
+    pub fn bottom(&self) -> VirtAddr {
+        VirtAddr::from(self.ptr.as_ptr() as usize)
+    }
+
     pub const fn top(&self) -> VirtAddr {
         unsafe { core::mem::transmute(self.ptr.as_ptr().add(self.layout.size())) }
     }
@@ -367,7 +796,15 @@ impl TaskStack {
 
 impl Drop for TaskStack {
     fn drop(&mut self) {
-        unsafe { alloc::alloc::dealloc(self.ptr.as_ptr(), self.layout) }
+        #[cfg(feature = "paging")]
+        crate_interface::call_interface!(crate::KernelStackIf::dealloc_kernel_stack(
+            self.bottom(),
+            self.layout.size()
+        ));
+        #[cfg(not(feature = "paging"))]
+        unsafe {
+            alloc::alloc::dealloc(self.ptr.as_ptr(), self.layout)
+        }
     }
 }
 
@@ -407,6 +844,9 @@ impl CurrentTask {
 
     pub(crate) unsafe fn init_current(init_task: AxTaskRef) {
         assert!(init_task.is_init());
+        init_task.set_cpu_id(axhal::cpu::this_cpu_id());
+        init_task.set_on_cpu(true);
+        init_task.switch_in(axhal::time::monotonic_time());
         #[cfg(feature = "tls")]
         axhal::arch::write_thread_pointer(init_task.tls.tls_ptr() as usize);
         let ptr = Arc::into_raw(init_task);
@@ -429,8 +869,8 @@ impl Deref for CurrentTask {
 }
 
 extern "C" fn task_entry() -> ! {
-    // release the lock that was implicitly held across the reschedule
-    unsafe { crate::RUN_QUEUE.force_unlock() };
+    // finish the context switch that has switched to this new task
+    unsafe { crate::run_queue::finish_switch() };
     #[cfg(feature = "irq")]
     axhal::arch::enable_irqs();
     let task = crate::current();
@@ -439,3 +879,38 @@ extern "C" fn task_entry() -> ! {
     }
     crate::exit(0);
 }
+
+/// Lends `prio` to `owner` for the lock at `addr`. Returns `false` if it does
+/// not own the lock, or has been lent no lower priority for it.
+pub(crate) fn lend_priority(owner: &AxTaskRef, addr: usize, prio: u8) -> bool {
+    let mut locks = owner.owned_locks.lock();
+    let Some(idx) = locks.position(addr) else {
+        return false;
+    };
+    let lock = locks.locks[idx].as_mut().unwrap();
+    if lock.boost >= Some(prio) {
+        return false;
+    }
+    lock.boost = Some(prio);
+    let inherited = locks.inherited();
+    crate::run_queue::update_sched(owner, |se| se.set_inherited(inherited));
+    true
+}
+
+/// Removes the lock at `addr` from the locks owned by `owner`, with the
+/// priority lent for it.
+pub(crate) fn disown_lock(owner: &AxTaskRef, addr: usize) {
+    let mut locks = owner.owned_locks.lock();
+    let Some(idx) = locks.position(addr) else {
+        return;
+    };
+    let len = locks.len;
+    let boost = locks.locks[idx].unwrap().boost;
+    locks.locks.copy_within(idx + 1..len, idx);
+    locks.locks[len - 1] = None;
+    locks.len -= 1;
+    if boost.is_some() {
+        let inherited = locks.inherited();
+        crate::run_queue::update_sched(owner, |se| se.set_inherited(inherited));
+    }
+}
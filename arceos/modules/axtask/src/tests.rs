@@ -6,6 +6,16 @@ use crate::{api as axtask, current, WaitQueue};
 static INIT: Once = Once::new();
 static SERIAL: Mutex<()> = Mutex::new(());
 
+/// The ticks of the host are never stopped.
+#[cfg(feature = "tickless")]
+struct TicklessIfImpl;
+
+#[cfg(feature = "tickless")]
+#[crate_interface::impl_interface]
+impl crate::TicklessIf for TicklessIfImpl {
+    fn restart_tick() {}
+}
+
 #[test]
 fn test_sched_fifo() {
     let _lock = SERIAL.lock();
@@ -128,3 +138,390 @@ fn test_task_join() {
         assert_eq!(tasks[i].join(), Some(i as _));
     }
 }
+
+#[test]
+fn test_cpu_affinity() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(axtask::init_scheduler);
+
+    assert!(!axtask::set_current_affinity(axtask::CpuMask::new()));
+    assert_eq!(current().cpu_affinity(), axtask::CpuMask::full());
+
+    let task = axtask::spawn(|| {
+        assert!(axtask::set_current_affinity(axtask::CpuMask::single(0)));
+        axtask::yield_now();
+        assert_eq!(current().cpu_id(), 0);
+        assert_eq!(current().cpu_affinity().iter().collect::<Vec<_>>(), [0]);
+    });
+    assert_eq!(task.join(), Some(0));
+}
+
+#[test]
+fn test_steal_task() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(axtask::init_scheduler);
+
+    use crate::sched::Scheduler;
+    use crate::TaskInner;
+    use scheduler::BaseScheduler;
+    use std::sync::Arc;
+
+    let tasks: Vec<_> = (0..4)
+        .map(|i| TaskInner::new(|| {}, format!("S{}", i), 0x1000).into_arc())
+        .collect();
+    let mut sched = Scheduler::new(0);
+    for task in &tasks {
+        sched.add_task(task.clone());
+    }
+
+    // Tasks still switching out are skipped.
+    tasks[0].set_on_cpu(true);
+    tasks[1].set_on_cpu(true);
+    let stolen = sched.steal_task(0).unwrap();
+    assert!(Arc::ptr_eq(&stolen, &tasks[2]));
+    assert!(sched
+        .steal_task(0)
+        .is_some_and(|task| Arc::ptr_eq(&task, &tasks[3])));
+    assert!(sched.steal_task(0).is_none());
+
+    // The skipped ones are kept in order.
+    sched.add_task(stolen);
+    for i in [0, 1, 2] {
+        assert!(Arc::ptr_eq(&sched.pick_next_task().unwrap(), &tasks[i]));
+    }
+    assert!(sched.pick_next_task().is_none());
+}
+
+#[test]
+fn test_sched_policy() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(axtask::init_scheduler);
+
+    use axtask::SchedPolicy;
+    use core::time::Duration;
+
+    const PRIOS: [u8; 3] = [10, 50, 30];
+    static ORDER: Mutex<Vec<u8>> = Mutex::new(Vec::new());
+
+    // Keep the tasks from running before their policies are set, even if we
+    // are preempted.
+    let curr = current();
+    let task = curr.as_task_ref();
+    assert!(axtask::set_sched_policy(
+        task,
+        SchedPolicy::Fifo(axtask::MAX_RT_PRIO)
+    ));
+    let tasks: Vec<_> = PRIOS
+        .iter()
+        .map(|&prio| {
+            let task = axtask::spawn(move || ORDER.lock().unwrap().push(prio));
+            assert!(axtask::set_sched_policy(&task, SchedPolicy::Fifo(prio)));
+            assert_eq!(task.sched_policy(), SchedPolicy::Fifo(prio));
+            task
+        })
+        .collect();
+    for task in tasks {
+        task.join();
+    }
+    assert_eq!(*ORDER.lock().unwrap(), [50, 30, 10]);
+    assert!(axtask::set_sched_policy(task, SchedPolicy::Normal));
+
+    assert!(!axtask::set_sched_policy(
+        task,
+        SchedPolicy::RoundRobin(100)
+    ));
+    let policy = SchedPolicy::Deadline {
+        runtime: Duration::from_millis(20),
+        period: Duration::from_millis(10),
+    };
+    assert!(!axtask::set_sched_policy(task, policy));
+    assert_eq!(curr.sched_policy(), SchedPolicy::Normal);
+}
+
+#[test]
+fn test_task_registry() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(axtask::init_scheduler);
+
+    static WQ: WaitQueue = WaitQueue::new();
+
+    let task = axtask::spawn_raw(
+        || {
+            assert_eq!(WQ.wait_interruptible(), Err(axtask::Interrupted));
+            axtask::exit_if_requested();
+            unreachable!("the task should exit in `exit_if_requested()`");
+        },
+        "registry".into(),
+        0x4000,
+    );
+    axtask::yield_now(); // let it block
+
+    let info = task.info();
+    assert_eq!(info.name, "registry");
+    assert_eq!(info.state, axtask::TaskState::Interruptible);
+    assert!(info.in_wait_queue);
+    assert_eq!(info.stack_size, 0x4000);
+    assert!(info.stack_high_water > 0 && info.stack_high_water < 0x4000);
+    assert!(info.voluntary_switches >= 1);
+
+    let unaligned = axtask::spawn_raw(|| {}, "unaligned".into(), 0x1800);
+    assert_eq!(unaligned.info().stack_size, 0x2000);
+    assert!(axtask::tasks().any(|t| std::sync::Arc::ptr_eq(&t, &task)));
+    assert!(axtask::find_task(task.id().as_u64()).is_some());
+
+    assert!(axtask::request_exit(&task, 42));
+    assert!(!axtask::request_exit(&task, 43));
+    assert_eq!(task.join(), Some(42));
+    assert!(!axtask::request_exit(current().as_task_ref(), 0));
+}
+
+#[test]
+fn test_request_exit() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(axtask::init_scheduler);
+
+    static WQ: WaitQueue = WaitQueue::new();
+    static READY: AtomicUsize = AtomicUsize::new(0);
+
+    let task = axtask::spawn_raw(
+        || {
+            WQ.wait_until(|| READY.load(Ordering::Acquire) == 1);
+            // The request is still pending, so it does not block at all.
+            assert_eq!(WQ.wait_interruptible(), Err(axtask::Interrupted));
+            axtask::exit_if_requested();
+            unreachable!("the task should exit in `exit_if_requested()`");
+        },
+        "request_exit".into(),
+        0x4000,
+    );
+    axtask::yield_now(); // let it block
+
+    // The uninterruptible wait goes on.
+    assert!(axtask::request_exit(&task, 7));
+    axtask::yield_now();
+    assert_eq!(task.info().state, axtask::TaskState::Blocked);
+
+    READY.store(1, Ordering::Release);
+    WQ.notify_one(true);
+    assert_eq!(task.join(), Some(7));
+
+    // System tasks cannot exit.
+    let gc = axtask::tasks().find(|t| t.name() == "gc").unwrap();
+    assert!(gc.is_system());
+    assert!(!axtask::request_exit(&gc, 0));
+}
+
+#[test]
+fn test_interrupt() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(axtask::init_scheduler);
+
+    static WQ: WaitQueue = WaitQueue::new();
+
+    let task = axtask::spawn(|| {
+        assert_eq!(
+            WQ.wait_until_interruptible(|| false),
+            Err(axtask::Interrupted)
+        );
+        assert_eq!(WQ.wait_interruptible(), Err(axtask::Interrupted));
+        axtask::clear_interrupt();
+        assert_eq!(WQ.wait_interruptible(), Ok(()));
+    });
+    axtask::yield_now(); // let it block
+    assert_eq!(task.info().state, axtask::TaskState::Interruptible);
+
+    axtask::interrupt(&task);
+    axtask::yield_now();
+    assert_eq!(task.info().state, axtask::TaskState::Interruptible);
+    assert!(WQ.notify_one(true));
+    assert_eq!(task.join(), Some(0));
+}
+
+#[test]
+#[cfg(feature = "irq")]
+fn test_sleep_interruptible() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(axtask::init_scheduler);
+
+    const HOUR: core::time::Duration = core::time::Duration::from_secs(3600);
+
+    let task = axtask::spawn(|| {
+        assert_eq!(axtask::sleep_interruptible(HOUR), Err(axtask::Interrupted));
+        axtask::clear_interrupt();
+    });
+    axtask::yield_now(); // let it sleep
+    assert_eq!(task.info().state, axtask::TaskState::Interruptible);
+
+    axtask::interrupt(&task);
+    assert_eq!(task.join(), Some(0));
+    // The alarm is cancelled with the interrupted sleep.
+    assert_eq!(crate::timers::next_deadline(), None);
+}
+
+#[test]
+#[cfg(feature = "irq")]
+fn test_timer_cancel() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(axtask::init_scheduler);
+
+    use crate::timers::next_deadline;
+    use crate::{set_periodic_timer, set_timer};
+    use core::time::Duration;
+
+    const HOUR: Duration = Duration::from_secs(3600);
+    static FIRED: AtomicUsize = AtomicUsize::new(0);
+    static WQ: WaitQueue = WaitQueue::new();
+
+    // Cancelled timers are removed from the timer list.
+    let now = axhal::time::wall_time();
+    let timer = set_periodic_timer(now + HOUR, HOUR, || {
+        FIRED.fetch_add(1, Ordering::Relaxed);
+    });
+    assert_eq!(next_deadline(), Some(now + HOUR));
+    assert!(timer.cancel());
+    assert!(!timer.cancel());
+    assert_eq!(next_deadline(), None);
+
+    // So are the alarms of the tasks woken up before the timeout.
+    let task = axtask::spawn(|| assert!(!WQ.wait_timeout(HOUR)));
+    axtask::yield_now(); // let it block
+    assert!(next_deadline().is_some());
+    assert!(WQ.notify_one(false));
+    assert_eq!(task.join(), Some(0));
+    assert_eq!(next_deadline(), None);
+
+    // The callbacks of the expired timers run in the `timer` task.
+    let timer = set_timer(now, || {
+        FIRED.fetch_add(1, Ordering::Relaxed);
+    });
+    axtask::on_timer_tick();
+    while FIRED.load(Ordering::Relaxed) == 0 {
+        axtask::yield_now();
+    }
+    assert_eq!(timer.deadline(), None);
+    assert!(!timer.cancel());
+    assert_eq!(FIRED.load(Ordering::Relaxed), 1);
+}
+
+#[test]
+fn test_tick_deadline() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(axtask::init_scheduler);
+
+    use crate::sched::Scheduler;
+    use crate::{SchedPolicy, TaskInner};
+    use core::time::Duration;
+    use scheduler::BaseScheduler;
+    use std::sync::Arc;
+
+    let new_task = |policy| {
+        let task = TaskInner::new(|| {}, "D".into(), 0x1000).into_arc();
+        assert!(axtask::set_sched_policy(&task, policy));
+        task
+    };
+    let mut sched = Scheduler::new(0);
+    let now = axhal::time::monotonic_time();
+    assert_eq!(sched.tick_deadline(None, 0, now), None);
+
+    // A FIFO task running alone needs no ticks, while a round-robin one
+    // needs them only with a competitor of the same priority.
+    let fifo = new_task(SchedPolicy::Fifo(10));
+    assert_eq!(sched.tick_deadline(Some(&fifo), 0, now), None);
+    let rr = new_task(SchedPolicy::RoundRobin(10));
+    assert_eq!(sched.tick_deadline(Some(&rr), 0, now), None);
+    sched.add_task(new_task(SchedPolicy::RoundRobin(10)));
+    assert_eq!(sched.tick_deadline(Some(&rr), 1, now), Some(now));
+    assert_eq!(sched.tick_deadline(None, 1, now), Some(now));
+    sched.pick_next_task().unwrap();
+
+    // A deadline task needs them when its budget runs out.
+    let runtime = Duration::from_millis(20);
+    let dl = new_task(SchedPolicy::Deadline {
+        runtime,
+        period: Duration::from_secs(1),
+    });
+    sched.add_task(dl.clone());
+    assert!(sched
+        .pick_next_task()
+        .is_some_and(|task| Arc::ptr_eq(&task, &dl)));
+    let deadline = sched.tick_deadline(Some(&dl), 0, now).unwrap();
+    assert!(deadline > now && deadline <= axhal::time::monotonic_time() + runtime);
+
+    // The fair tasks need them for their time slices, if any.
+    let fair = TaskInner::new(|| {}, "F".into(), 0x1000).into_arc();
+    sched.add_task(TaskInner::new(|| {}, "F".into(), 0x1000).into_arc());
+    let sliced = cfg!(any(feature = "sched_rr", feature = "sched_cfs"));
+    assert_eq!(
+        sched.tick_deadline(Some(&fair), 1, now),
+        sliced.then_some(now)
+    );
+    dl.sched_entity().exit();
+}
+
+#[test]
+fn test_priority_inheritance() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(axtask::init_scheduler);
+
+    use axtask::SchedPolicy;
+    use core::sync::atomic::AtomicBool;
+
+    const LOCK_A: usize = 0xa;
+    const LOCK_B: usize = 0xb;
+    const LOCK_C: usize = 0xc;
+    static DONE: AtomicBool = AtomicBool::new(false);
+    static WQ: WaitQueue = WaitQueue::new();
+
+    // `low` owns A and B, while `mid` owns C and waits for A.
+    let low = axtask::spawn(|| {
+        axtask::own_lock(LOCK_A, |_| {});
+        axtask::own_lock(LOCK_B, |_| {});
+        WQ.wait_until(|| DONE.load(Ordering::Relaxed));
+    });
+    let low_id = low.id().as_u64();
+    let mid = axtask::spawn(move || {
+        axtask::own_lock(LOCK_C, |_| {});
+        axtask::wait_for_lock(LOCK_A, low_id);
+        WQ.wait_until(|| DONE.load(Ordering::Relaxed));
+        axtask::end_lock_wait();
+    });
+    while !low.info().in_wait_queue || !mid.info().in_wait_queue {
+        axtask::yield_now();
+    }
+
+    let curr = current();
+    let lend = |addr, owner_id, prio| {
+        assert!(axtask::set_sched_policy(
+            curr.as_task_ref(),
+            SchedPolicy::Fifo(prio)
+        ));
+        axtask::wait_for_lock(addr, owner_id);
+        axtask::end_lock_wait();
+    };
+
+    // The priorities are lent for each lock.
+    lend(LOCK_A, low_id, 40);
+    lend(LOCK_B, low_id, 20);
+    assert_eq!(low.inherited_priority(), Some(40));
+    assert_eq!(mid.inherited_priority(), None);
+
+    // They are passed on to the owner of the lock that the owner waits for.
+    lend(LOCK_C, mid.id().as_u64(), 50);
+    assert_eq!(mid.inherited_priority(), Some(50));
+    assert_eq!(low.inherited_priority(), Some(50));
+
+    // Unlocking one lock keeps those lent for the others.
+    axtask::disown_lock(&low, LOCK_A);
+    assert_eq!(low.inherited_priority(), Some(20));
+    axtask::disown_lock(&low, LOCK_B);
+    assert_eq!(low.inherited_priority(), None);
+
+    assert!(axtask::set_sched_policy(
+        curr.as_task_ref(),
+        SchedPolicy::Normal
+    ));
+    DONE.store(true, Ordering::Relaxed);
+    WQ.notify_all(false);
+    assert_eq!(low.join(), Some(0));
+    assert_eq!(mid.join(), Some(0));
+}
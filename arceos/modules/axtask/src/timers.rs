@@ -1,41 +1,108 @@
-use alloc::sync::Arc;
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::sync::{Arc, Weak};
+use core::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use core::time::Duration;
+
 use axhal::time::wall_time;
+use kernel_guard::NoPreemptIrqSave;
 use kspin::SpinNoIrq;
 use lazyinit::LazyInit;
 use timer_list::{TimeValue, TimerEvent, TimerList};
 
-use crate::{AxTaskRef, RUN_QUEUE};
+use crate::run_queue::unblock_task;
+use crate::{AxTask, AxTaskRef, TaskInner, WaitQueue};
+
+/// Timer events of all CPUs. Each CPU checks its own events on its timer
+/// ticks, and the events are removed from any CPU on cancellation.
+static TIMER_LISTS: [LazyInit<SpinNoIrq<TimerList<AxTimerEvent>>>; axconfig::SMP] =
+    [const { LazyInit::new() }; axconfig::SMP];
+
+/// Callback timers that have expired, to be run by the `timer` task.
+static EXPIRED_TIMERS: SpinNoIrq<VecDeque<(Arc<CallbackTimer>, u64)>> =
+    SpinNoIrq::new(VecDeque::new());
+
+static TIMER_TASK_WQ: WaitQueue = WaitQueue::new();
 
-// TODO: per-CPU
-static TIMER_LIST: LazyInit<SpinNoIrq<TimerList<TaskWakeupEvent>>> = LazyInit::new();
+/// Whether the `timer` task has been spawned.
+static TIMER_TASK_STARTED: AtomicBool = AtomicBool::new(false);
+
+fn timer_list(cpu_id: usize) -> &'static SpinNoIrq<TimerList<AxTimerEvent>> {
+    &TIMER_LISTS[cpu_id]
+}
+
+fn local_timer_list() -> &'static SpinNoIrq<TimerList<AxTimerEvent>> {
+    timer_list(axhal::cpu::this_cpu_id())
+}
 
-struct TaskWakeupEvent(AxTaskRef);
+enum AxTimerEvent {
+    Wakeup(TaskWakeupEvent),
+    Callback(CallbackEvent),
+}
+
+impl TimerEvent for AxTimerEvent {
+    fn callback(self, now: TimeValue) {
+        match self {
+            Self::Wakeup(event) => event.callback(now),
+            Self::Callback(event) => event.callback(now),
+        }
+    }
+}
+
+struct TaskWakeupEvent {
+    ticket: u64,
+    task: Weak<AxTask>,
+}
 
 impl TimerEvent for TaskWakeupEvent {
     fn callback(self, _now: TimeValue) {
-        let mut rq = RUN_QUEUE.lock();
-        self.0.set_in_timer_list(false);
-        rq.unblock_task(self.0, true);
+        // The event may be cancelled after it is taken from the timer list.
+        if let Some(task) = self.task.upgrade() {
+            if task.timer_ticket() == self.ticket {
+                task.expire_timer_ticket();
+                unblock_task(task, true);
+            }
+        }
     }
 }
 
+/// Wakes up the task at the deadline, unless the alarm is cancelled by
+/// [`cancel_alarm`] before.
 pub fn set_alarm_wakeup(deadline: TimeValue, task: AxTaskRef) {
-    let mut timers = TIMER_LIST.lock();
-    task.set_in_timer_list(true);
-    timers.set(deadline, TaskWakeupEvent(task));
+    let _guard = NoPreemptIrqSave::new();
+    let event = TaskWakeupEvent {
+        ticket: task.timer_ticket(),
+        task: Arc::downgrade(&task),
+    };
+    let cpu_id = axhal::cpu::this_cpu_id();
+    timer_list(cpu_id)
+        .lock()
+        .set(deadline, AxTimerEvent::Wakeup(event));
+    task.set_timer_cpu(cpu_id);
 }
 
+/// Cancels the alarm set by [`set_alarm_wakeup`], and removes its event from
+/// the timer list.
 pub fn cancel_alarm(task: &AxTaskRef) {
-    let mut timers = TIMER_LIST.lock();
-    task.set_in_timer_list(false);
-    timers.cancel(|t| Arc::ptr_eq(&t.0, task));
+    let ticket = task.timer_ticket();
+    task.expire_timer_ticket();
+    let Some(cpu_id) = task.take_timer_cpu() else {
+        return;
+    };
+    timer_list(cpu_id).lock().cancel(|event| match event {
+        AxTimerEvent::Wakeup(event) => {
+            event.ticket == ticket && core::ptr::eq(event.task.as_ptr(), Arc::as_ptr(task))
+        }
+        AxTimerEvent::Callback(_) => false,
+    });
 }
 
 pub fn check_events() {
     loop {
         let now = wall_time();
-        let event = TIMER_LIST.lock().expire_one(now);
+        let event = local_timer_list().lock().expire_one(now);
         if let Some((_deadline, event)) = event {
+            let _guard = NoPreemptIrqSave::new();
             event.callback(now);
         } else {
             break;
@@ -43,6 +110,217 @@ pub fn check_events() {
     }
 }
 
+/// Returns the earliest deadline in the timer list of the current CPU.
+#[cfg(any(feature = "tickless", test))]
+pub fn next_deadline() -> Option<TimeValue> {
+    local_timer_list().lock().next_deadline()
+}
+
+/// Initializes the timer list of the current CPU.
 pub fn init() {
-    TIMER_LIST.init_once(SpinNoIrq::new(TimerList::new()));
+    TIMER_LISTS[axhal::cpu::this_cpu_id()].init_once(SpinNoIrq::new(TimerList::new()));
+}
+
+/// Spawns the `timer` task, which runs the callbacks of the expired timers,
+/// if it has not been spawned.
+fn start_timer_task() {
+    if !TIMER_TASK_STARTED.swap(true, Ordering::AcqRel) {
+        let task = TaskInner::new(timer_task_entry, "timer".into(), axconfig::TASK_STACK_SIZE);
+        crate::spawn_task(task.into_system());
+    }
+}
+
+fn timer_task_entry() {
+    loop {
+        TIMER_TASK_WQ.wait_until(|| !EXPIRED_TIMERS.lock().is_empty());
+        loop {
+            let Some((timer, ticket)) = EXPIRED_TIMERS.lock().pop_front() else {
+                break;
+            };
+            timer.queued.store(false, Ordering::Release);
+            // It may be cancelled or reset after expiration.
+            if timer.ticket.load(Ordering::Acquire) == ticket {
+                let overrun = timer.pending_overrun.swap(0, Ordering::AcqRel);
+                timer.overrun.store(overrun, Ordering::Release);
+                (timer.callback)();
+            }
+        }
+    }
+}
+
+struct CallbackTimer {
+    callback: Box<dyn Fn() + Send + Sync>,
+    interval: Option<Duration>,
+    /// The CPU whose timer list holds the events of the timer.
+    cpu_id: usize,
+    /// Incremented on cancellation, so that the pending events are dropped.
+    ticket: AtomicU64,
+    /// The next deadline in nanoseconds, or `u64::MAX` if not active.
+    deadline_ns: AtomicU64,
+    /// Whether it is in [`EXPIRED_TIMERS`].
+    queued: AtomicBool,
+    /// Expirations missed since the callback was last queued.
+    pending_overrun: AtomicUsize,
+    /// Expirations missed before the last callback.
+    overrun: AtomicUsize,
+}
+
+struct CallbackEvent {
+    ticket: u64,
+    deadline: TimeValue,
+    timer: Arc<CallbackTimer>,
+}
+
+impl CallbackEvent {
+    fn callback(self, now: TimeValue) {
+        let timer = self.timer;
+        if timer.ticket.load(Ordering::Acquire) != self.ticket {
+            return;
+        }
+        // Re-arm a periodic timer from the old deadline to avoid drifting,
+        // skipping the expirations that have been missed.
+        let mut missed = 0;
+        let next = timer.interval.map(|interval| {
+            let mut next = self.deadline + interval;
+            while next <= now {
+                next += interval;
+                missed += 1;
+            }
+            next
+        });
+        let next_ns = next.map_or(u64::MAX, |next| next.as_nanos() as u64);
+        // Re-armed under the lock, so that it is not missed by `cancel`.
+        let mut timers = timer_list(timer.cpu_id).lock();
+        if timer
+            .deadline_ns
+            .compare_exchange(
+                self.deadline.as_nanos() as u64,
+                next_ns,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            )
+            .is_err()
+        {
+            // Cancelled just now.
+            return;
+        }
+        if let Some(next) = next {
+            let event = CallbackEvent {
+                ticket: self.ticket,
+                deadline: next,
+                timer: timer.clone(),
+            };
+            timers.set(next, AxTimerEvent::Callback(event));
+        }
+        drop(timers);
+        timer.pending_overrun.fetch_add(missed, Ordering::AcqRel);
+        if timer.queued.swap(true, Ordering::AcqRel) {
+            // The callback of the last expiration has not run yet.
+            timer.pending_overrun.fetch_add(1, Ordering::AcqRel);
+        } else {
+            EXPIRED_TIMERS.lock().push_back((timer, self.ticket));
+            TIMER_TASK_WQ.notify_one(false);
+        }
+    }
+}
+
+/// A handle to a timer set by [`set_timer`] or [`set_periodic_timer`].
+///
+/// Dropping the handle does not cancel the timer.
+pub struct TimerHandle(Arc<CallbackTimer>);
+
+impl TimerHandle {
+    /// Cancels the timer, so that its callback will not run again.
+    ///
+    /// Returns whether the timer was active, i.e., it was periodic, or it had
+    /// not expired yet. The callback may be running on return.
+    pub fn cancel(&self) -> bool {
+        let timer = &self.0;
+        let mut timers = timer_list(timer.cpu_id).lock();
+        let active = timer.deadline_ns.swap(u64::MAX, Ordering::AcqRel) != u64::MAX;
+        timer.ticket.fetch_add(1, Ordering::AcqRel);
+        if active {
+            timers.cancel(|event| match event {
+                AxTimerEvent::Callback(event) => Arc::ptr_eq(&event.timer, timer),
+                AxTimerEvent::Wakeup(_) => false,
+            });
+        }
+        active
+    }
+
+    /// Returns the next deadline, or `None` if the timer has been cancelled
+    /// or expired.
+    pub fn deadline(&self) -> Option<TimeValue> {
+        match self.0.deadline_ns.load(Ordering::Acquire) {
+            u64::MAX => None,
+            ns => Some(Duration::from_nanos(ns)),
+        }
+    }
+
+    /// Returns the interval of a periodic timer.
+    pub fn interval(&self) -> Option<Duration> {
+        self.0.interval
+    }
+
+    /// Returns the number of expirations missed before the last run of the
+    /// callback, as the callback ran late or the timer ticks were late.
+    pub fn overrun(&self) -> usize {
+        self.0.overrun.load(Ordering::Acquire)
+    }
+}
+
+fn set_callback_timer(
+    deadline: TimeValue,
+    interval: Option<Duration>,
+    callback: Box<dyn Fn() + Send + Sync>,
+) -> TimerHandle {
+    start_timer_task();
+    let cpu_id = axhal::cpu::this_cpu_id();
+    let timer = Arc::new(CallbackTimer {
+        callback,
+        interval,
+        cpu_id,
+        ticket: AtomicU64::new(0),
+        deadline_ns: AtomicU64::new(deadline.as_nanos() as u64),
+        queued: AtomicBool::new(false),
+        pending_overrun: AtomicUsize::new(0),
+        overrun: AtomicUsize::new(0),
+    });
+    let event = CallbackEvent {
+        ticket: 0,
+        deadline,
+        timer: timer.clone(),
+    };
+    timer_list(cpu_id)
+        .lock()
+        .set(deadline, AxTimerEvent::Callback(event));
+    TimerHandle(timer)
+}
+
+/// Runs the `callback` once at the `deadline`.
+///
+/// The callback runs in the `timer` task rather than in the interrupt
+/// context, so it may block, but it delays the other timers then. The `timer`
+/// task is spawned when the first timer is set.
+pub fn set_timer<F>(deadline: TimeValue, callback: F) -> TimerHandle
+where
+    F: Fn() + Send + Sync + 'static,
+{
+    set_callback_timer(deadline, None, Box::new(callback))
+}
+
+/// Runs the `callback` at the `deadline`, and then every `interval`, until
+/// the timer is cancelled.
+///
+/// The callback runs in the `timer` task like [`set_timer`]. If it is late
+/// for more than an interval, the missed runs are skipped and counted in
+/// [`TimerHandle::overrun`].
+///
+/// Panics if the `interval` is zero.
+pub fn set_periodic_timer<F>(deadline: TimeValue, interval: Duration, callback: F) -> TimerHandle
+where
+    F: Fn() + Send + Sync + 'static,
+{
+    assert!(!interval.is_zero(), "zero timer interval");
+    set_callback_timer(deadline, Some(interval), Box::new(callback))
 }
@@ -1,8 +1,16 @@
 use alloc::collections::VecDeque;
 use alloc::sync::Arc;
+use kernel_guard::NoPreemptIrqSave;
 use kspin::SpinRaw;
 
-use crate::{AxRunQueue, AxTaskRef, CurrentTask, RUN_QUEUE};
+use crate::run_queue::{current_run_queue, unblock_task};
+use crate::{AxTaskRef, CurrentTask};
+
+/// The error returned by the interruptible waits of a [`WaitQueue`], when the
+/// current task is interrupted by [`crate::interrupt`], e.g., for a signal, or
+/// asked to exit by [`crate::request_exit`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Interrupted;
 
 /// A queue to store sleeping tasks.
 ///
@@ -27,7 +35,9 @@ use crate::{AxRunQueue, AxTaskRef, CurrentTask, RUN_QUEUE};
 /// assert_eq!(VALUE.load(Ordering::Relaxed), 1);
 /// ```
 pub struct WaitQueue {
-    queue: SpinRaw<VecDeque<AxTaskRef>>, // we already disabled IRQs when lock the `RUN_QUEUE`
+    // IRQs and preemption are always disabled before locking it, usually by
+    // `current_run_queue()`.
+    queue: SpinRaw<VecDeque<AxTaskRef>>,
 }
 
 impl WaitQueue {
@@ -50,26 +60,25 @@ impl WaitQueue {
         // the event from another queue.
         if curr.in_wait_queue() {
             // wake up by timer (timeout).
-            // `RUN_QUEUE` is not locked here, so disable IRQs.
-            let _guard = kernel_guard::IrqSave::new();
+            let _guard = NoPreemptIrqSave::new();
             self.queue.lock().retain(|t| !curr.ptr_eq(t));
             curr.set_in_wait_queue(false);
         }
+        // timeout was set but not triggered (wake up by `WaitQueue::notify()`)
         #[cfg(feature = "irq")]
-        if curr.in_timer_list() {
-            // timeout was set but not triggered (wake up by `WaitQueue::notify()`)
-            crate::timers::cancel_alarm(curr.as_task_ref());
-        }
+        crate::timers::cancel_alarm(curr.as_task_ref());
     }
 
     /// Blocks the current task and put it into the wait queue, until other task
     /// notifies it.
     pub fn wait(&self) {
-        RUN_QUEUE.lock().block_current(|task| {
-            task.set_in_wait_queue(true);
-            self.queue.lock().push_back(task)
-        });
-        self.cancel_events(crate::current());
+        self.wait_common(false).unwrap();
+    }
+
+    /// Like [`wait`](Self::wait), but returns [`Interrupted`] once the current
+    /// task is interrupted.
+    pub fn wait_interruptible(&self) -> Result<(), Interrupted> {
+        self.wait_common(true)
     }
 
     /// Blocks the current task and put it into the wait queue, until the given
@@ -81,23 +90,109 @@ impl WaitQueue {
     where
         F: Fn() -> bool,
     {
-        loop {
-            let mut rq = RUN_QUEUE.lock();
-            if condition() {
-                break;
-            }
-            rq.block_current(|task| {
-                task.set_in_wait_queue(true);
-                self.queue.lock().push_back(task);
-            });
-        }
-        self.cancel_events(crate::current());
+        self.wait_until_common(condition, false).unwrap();
+    }
+
+    /// Like [`wait_until`](Self::wait_until), but returns [`Interrupted`] once
+    /// the current task is interrupted.
+    pub fn wait_until_interruptible<F>(&self, condition: F) -> Result<(), Interrupted>
+    where
+        F: Fn() -> bool,
+    {
+        self.wait_until_common(condition, true)
     }
 
     /// Blocks the current task and put it into the wait queue, until other tasks
     /// notify it, or the given duration has elapsed.
     #[cfg(feature = "irq")]
     pub fn wait_timeout(&self, dur: core::time::Duration) -> bool {
+        self.wait_timeout_common(dur, false).unwrap()
+    }
+
+    /// Like [`wait_timeout`](Self::wait_timeout), but returns [`Interrupted`]
+    /// once the current task is interrupted.
+    #[cfg(feature = "irq")]
+    pub fn wait_timeout_interruptible(
+        &self,
+        dur: core::time::Duration,
+    ) -> Result<bool, Interrupted> {
+        self.wait_timeout_common(dur, true)
+    }
+
+    /// Blocks the current task and put it into the wait queue, until the given
+    /// `condition` becomes true, or the given duration has elapsed.
+    ///
+    /// Note that even other tasks notify this task, it will not wake up until
+    /// the above conditions are met.
+    #[cfg(feature = "irq")]
+    pub fn wait_timeout_until<F>(&self, dur: core::time::Duration, condition: F) -> bool
+    where
+        F: Fn() -> bool,
+    {
+        self.wait_timeout_until_common(dur, condition, false)
+            .unwrap()
+    }
+
+    /// Like [`wait_timeout_until`](Self::wait_timeout_until), but returns
+    /// [`Interrupted`] once the current task is interrupted.
+    #[cfg(feature = "irq")]
+    pub fn wait_timeout_until_interruptible<F>(
+        &self,
+        dur: core::time::Duration,
+        condition: F,
+    ) -> Result<bool, Interrupted>
+    where
+        F: Fn() -> bool,
+    {
+        self.wait_timeout_until_common(dur, condition, true)
+    }
+
+    fn wait_common(&self, interruptible: bool) -> Result<(), Interrupted> {
+        #[cfg(feature = "lockdep")]
+        crate::lockdep::might_sleep();
+        current_run_queue().blocked_resched(self.queue.lock(), interruptible);
+        let curr = crate::current();
+        let interrupted = interruptible && curr.interrupt_pending();
+        self.cancel_events(curr);
+        if interrupted {
+            Err(Interrupted)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn wait_until_common<F>(&self, condition: F, interruptible: bool) -> Result<(), Interrupted>
+    where
+        F: Fn() -> bool,
+    {
+        #[cfg(feature = "lockdep")]
+        crate::lockdep::might_sleep();
+        let curr = crate::current();
+        let ret = loop {
+            let rq = current_run_queue();
+            // Notifiers lock the queue too, so no notification is lost after
+            // checking the condition.
+            let wq = self.queue.lock();
+            if condition() {
+                break Ok(());
+            }
+            if interruptible && curr.interrupt_pending() {
+                break Err(Interrupted);
+            }
+            rq.blocked_resched(wq, interruptible);
+        };
+        self.cancel_events(curr);
+        ret
+    }
+
+    #[cfg(feature = "irq")]
+    fn wait_timeout_common(
+        &self,
+        dur: core::time::Duration,
+        interruptible: bool,
+    ) -> Result<bool, Interrupted> {
+        #[cfg(feature = "lockdep")]
+        crate::lockdep::might_sleep();
         let curr = crate::current();
         let deadline = axhal::time::wall_time() + dur;
         debug!(
@@ -105,27 +200,33 @@ impl WaitQueue {
             curr.id_name(),
             deadline
         );
-        crate::timers::set_alarm_wakeup(deadline, curr.clone());
-
-        RUN_QUEUE.lock().block_current(|task| {
-            task.set_in_wait_queue(true);
-            self.queue.lock().push_back(task)
-        });
+        {
+            let rq = current_run_queue();
+            crate::timers::set_alarm_wakeup(deadline, curr.clone());
+            rq.blocked_resched(self.queue.lock(), interruptible);
+        }
         let timeout = curr.in_wait_queue(); // still in the wait queue, must have timed out
+        let interrupted = interruptible && curr.interrupt_pending();
         self.cancel_events(curr);
-        timeout
+        if interrupted {
+            Err(Interrupted)
+        } else {
+            Ok(timeout)
+        }
     }
 
-    /// Blocks the current task and put it into the wait queue, until the given
-    /// `condition` becomes true, or the given duration has elapsed.
-    ///
-    /// Note that even other tasks notify this task, it will not wake up until
-    /// the above conditions are met.
     #[cfg(feature = "irq")]
-    pub fn wait_timeout_until<F>(&self, dur: core::time::Duration, condition: F) -> bool
+    fn wait_timeout_until_common<F>(
+        &self,
+        dur: core::time::Duration,
+        condition: F,
+        interruptible: bool,
+    ) -> Result<bool, Interrupted>
     where
         F: Fn() -> bool,
     {
+        #[cfg(feature = "lockdep")]
+        crate::lockdep::might_sleep();
         let curr = crate::current();
         let deadline = axhal::time::wall_time() + dur;
         debug!(
@@ -135,20 +236,22 @@ impl WaitQueue {
         );
         crate::timers::set_alarm_wakeup(deadline, curr.clone());
 
-        let mut timeout = true;
-        while axhal::time::wall_time() < deadline {
-            let mut rq = RUN_QUEUE.lock();
+        let ret = loop {
+            let rq = current_run_queue();
+            if axhal::time::wall_time() >= deadline {
+                break Ok(true);
+            }
+            let wq = self.queue.lock();
             if condition() {
-                timeout = false;
-                break;
+                break Ok(false);
             }
-            rq.block_current(|task| {
-                task.set_in_wait_queue(true);
-                self.queue.lock().push_back(task);
-            });
-        }
+            if interruptible && curr.interrupt_pending() {
+                break Err(Interrupted);
+            }
+            rq.blocked_resched(wq, interruptible);
+        };
         self.cancel_events(curr);
-        timeout
+        ret
     }
 
     /// Wakes up one task in the wait queue, usually the first one.
@@ -156,9 +259,12 @@ impl WaitQueue {
     /// If `resched` is true, the current task will be preempted when the
     /// preemption is enabled.
     pub fn notify_one(&self, resched: bool) -> bool {
-        let mut rq = RUN_QUEUE.lock();
-        if !self.queue.lock().is_empty() {
-            self.notify_one_locked(resched, &mut rq)
+        let _guard = NoPreemptIrqSave::new();
+        let mut wq = self.queue.lock();
+        if let Some(task) = wq.pop_front() {
+            task.set_in_wait_queue(false);
+            unblock_task(task, resched);
+            true
         } else {
             false
         }
@@ -169,15 +275,11 @@ impl WaitQueue {
     /// If `resched` is true, the current task will be preempted when the
     /// preemption is enabled.
     pub fn notify_all(&self, resched: bool) {
-        loop {
-            let mut rq = RUN_QUEUE.lock();
-            if let Some(task) = self.queue.lock().pop_front() {
-                task.set_in_wait_queue(false);
-                rq.unblock_task(task, resched);
-            } else {
-                break;
-            }
-            drop(rq); // we must unlock `RUN_QUEUE` after unlocking `self.queue`.
+        let _guard = NoPreemptIrqSave::new();
+        let mut wq = self.queue.lock();
+        while let Some(task) = wq.pop_front() {
+            task.set_in_wait_queue(false);
+            unblock_task(task, resched);
         }
     }
 
@@ -185,32 +287,15 @@ impl WaitQueue {
     ///
     /// If `resched` is true, the current task will be preempted when the
     /// preemption is enabled.
-    pub fn notify_task(&mut self, resched: bool, task: &AxTaskRef) -> bool {
-        let mut rq = RUN_QUEUE.lock();
+    pub fn notify_task(&self, resched: bool, task: &AxTaskRef) -> bool {
+        let _guard = NoPreemptIrqSave::new();
         let mut wq = self.queue.lock();
         if let Some(index) = wq.iter().position(|t| Arc::ptr_eq(t, task)) {
             task.set_in_wait_queue(false);
-            rq.unblock_task(wq.remove(index).unwrap(), resched);
-            true
-        } else {
-            false
-        }
-    }
-
-    pub(crate) fn notify_one_locked(&self, resched: bool, rq: &mut AxRunQueue) -> bool {
-        if let Some(task) = self.queue.lock().pop_front() {
-            task.set_in_wait_queue(false);
-            rq.unblock_task(task, resched);
+            unblock_task(wq.remove(index).unwrap(), resched);
             true
         } else {
             false
         }
     }
-
-    pub(crate) fn notify_all_locked(&self, resched: bool, rq: &mut AxRunQueue) {
-        while let Some(task) = self.queue.lock().pop_front() {
-            task.set_in_wait_queue(false);
-            rq.unblock_task(task, resched);
-        }
-    }
 }
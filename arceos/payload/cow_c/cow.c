@@ -0,0 +1,113 @@
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <unistd.h>
+#include <sys/mman.h>
+#include <sys/wait.h>
+
+#define PAGE_SIZE 4096
+#define NR_PAGES 16
+
+static int data = 1;
+
+void check(int cond, const char *msg)
+{
+    if (!cond) {
+        printf("Cow error: %s!\n", msg);
+        exit(-1);
+    }
+}
+
+/* Both the parent and the child write to the pages shared by fork,
+ * and each of them must see its own writes only. */
+void test_private_pages()
+{
+    int i;
+    int status;
+    pid_t pid;
+    char *buf;
+
+    buf = mmap(NULL, NR_PAGES * PAGE_SIZE, PROT_READ | PROT_WRITE,
+               MAP_PRIVATE | MAP_ANONYMOUS, -1, 0);
+    check(buf != MAP_FAILED, "mmap");
+    /* Touch every other page, the rest are not present at fork. */
+    for (i = 0; i < NR_PAGES; i += 2)
+        buf[i * PAGE_SIZE] = 'p';
+
+    pid = fork();
+    check(pid >= 0, "fork");
+    if (pid == 0) {
+        check(data == 1, "child reads data");
+        data = 2;
+        for (i = 0; i < NR_PAGES; i++) {
+            check(buf[i * PAGE_SIZE] == (i % 2 ? 0 : 'p'), "child reads page");
+            buf[i * PAGE_SIZE] = 'c';
+        }
+        exit(0);
+    }
+    check(waitpid(pid, &status, 0) == pid, "waitpid");
+    check(WIFEXITED(status) && WEXITSTATUS(status) == 0, "child failed");
+
+    check(data == 1, "parent sees the data of child");
+    for (i = 0; i < NR_PAGES; i++)
+        check(buf[i * PAGE_SIZE] == (i % 2 ? 0 : 'p'), "parent sees the page of child");
+    /* The frames are not shared any more, write them in place. */
+    memset(buf, 'q', NR_PAGES * PAGE_SIZE);
+    check(buf[NR_PAGES * PAGE_SIZE - 1] == 'q', "parent writes");
+    munmap(buf, NR_PAGES * PAGE_SIZE);
+}
+
+/* The parent writes first while the child is still alive. */
+void test_parent_writes_first()
+{
+    int status;
+    int pipefd[2];
+    pid_t pid;
+    char c;
+
+    check(pipe(pipefd) == 0, "pipe");
+    pid = fork();
+    check(pid >= 0, "fork");
+    if (pid == 0) {
+        close(pipefd[1]);
+        /* Wait until the parent has written. */
+        check(read(pipefd[0], &c, 1) == 1, "child reads pipe");
+        check(data == 1, "child sees the data of parent");
+        exit(0);
+    }
+    close(pipefd[0]);
+    data = 3;
+    check(write(pipefd[1], "x", 1) == 1, "parent writes pipe");
+    check(waitpid(pid, &status, 0) == pid, "waitpid");
+    check(WIFEXITED(status) && WEXITSTATUS(status) == 0, "child failed");
+    close(pipefd[1]);
+}
+
+/* Many forks without writes share the same frames. */
+void test_many_forks()
+{
+    int i;
+    int status;
+    pid_t pid;
+
+    for (i = 0; i < 32; i++) {
+        pid = fork();
+        check(pid >= 0, "fork");
+        if (pid == 0)
+            exit(data == 3 ? 0 : 1);
+        check(waitpid(pid, &status, 0) == pid, "waitpid");
+        check(WIFEXITED(status) && WEXITSTATUS(status) == 0, "child failed");
+    }
+}
+
+int main()
+{
+    printf("Cow ...\n");
+
+    test_private_pages();
+    test_parent_writes_first();
+    test_many_forks();
+
+    printf("Cow ok!\n");
+    return 0;
+}
@@ -0,0 +1,154 @@
+#include <errno.h>
+#include <linux/futex.h>
+#include <pthread.h>
+#include <signal.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <sys/syscall.h>
+#include <time.h>
+#include <unistd.h>
+
+#define NR_THREADS 4
+#define NR_LOOPS 10000
+
+static pthread_mutex_t lock = PTHREAD_MUTEX_INITIALIZER;
+static long counter;
+static volatile int word;
+static volatile int word2;
+static volatile int ready;
+static volatile int handled;
+
+void check(int cond, const char *msg)
+{
+    if (!cond) {
+        printf("Futex error: %s!\n", msg);
+        exit(-1);
+    }
+}
+
+long futex(volatile int *uaddr, int op, int val, const struct timespec *timeout,
+           volatile int *uaddr2, int val3)
+{
+    return syscall(SYS_futex, uaddr, op, val, timeout, uaddr2, val3);
+}
+
+void *add(void *arg)
+{
+    int i;
+
+    for (i = 0; i < NR_LOOPS; i++) {
+        pthread_mutex_lock(&lock);
+        counter++;
+        pthread_mutex_unlock(&lock);
+    }
+    return arg;
+}
+
+/* The mutex waits and wakes through futexes, and `pthread_join` waits for
+ * the futex woken by `CLONE_CHILD_CLEARTID`. */
+void test_mutex()
+{
+    int i;
+    pthread_t threads[NR_THREADS];
+
+    for (i = 0; i < NR_THREADS; i++)
+        check(pthread_create(&threads[i], NULL, add, NULL) == 0, "pthread_create");
+    for (i = 0; i < NR_THREADS; i++)
+        check(pthread_join(threads[i], NULL) == 0, "pthread_join");
+    check(counter == NR_THREADS * NR_LOOPS, "counter");
+}
+
+void test_errors()
+{
+    struct timespec ts = { 0, 10 * 1000 * 1000 };
+
+    word = 1;
+    check(futex(&word, FUTEX_WAIT_PRIVATE, 0, NULL, NULL, 0) == -1 && errno == EAGAIN,
+          "EAGAIN");
+    check(futex(&word, FUTEX_WAIT_PRIVATE, 1, &ts, NULL, 0) == -1 && errno == ETIMEDOUT,
+          "ETIMEDOUT");
+    check(futex((int *)0x1000, FUTEX_WAIT_PRIVATE, 0, NULL, NULL, 0) == -1 && errno == EFAULT,
+          "EFAULT");
+    check(futex(&word, FUTEX_WAIT_PRIVATE, 1, (struct timespec *)0x1000, NULL, 0) == -1 &&
+              errno == EFAULT,
+          "EFAULT timeout");
+}
+
+void *wait_word(void *arg)
+{
+    __sync_fetch_and_add(&ready, 1);
+    while (word == 0)
+        futex(&word, FUTEX_WAIT_PRIVATE, 0, NULL, NULL, 0);
+    return arg;
+}
+
+/* Waiters requeued to another futex are woken up by it. */
+void test_requeue()
+{
+    int i;
+    pthread_t threads[2];
+
+    word = 0;
+    word2 = 0;
+    ready = 0;
+    for (i = 0; i < 2; i++)
+        check(pthread_create(&threads[i], NULL, wait_word, NULL) == 0, "pthread_create");
+    while (ready < 2)
+        sched_yield();
+    usleep(10000); /* let them block */
+
+    check(futex(&word, FUTEX_CMP_REQUEUE_PRIVATE, 0, (void *)2, &word2, 0) == 2, "requeue");
+    check(futex(&word, FUTEX_WAKE_PRIVATE, 2, NULL, NULL, 0) == 0, "wake requeued");
+    word = 1;
+    check(futex(&word2, FUTEX_WAKE_PRIVATE, 2, NULL, NULL, 0) == 2, "wake");
+    for (i = 0; i < 2; i++)
+        check(pthread_join(threads[i], NULL) == 0, "pthread_join");
+}
+
+void on_signal(int signo)
+{
+    handled = signo;
+}
+
+void *wait_signal(void *arg)
+{
+    long ret;
+
+    ready = 1;
+    ret = futex(&word, FUTEX_WAIT_PRIVATE, 0, NULL, NULL, 0);
+    return (void *)(long)(ret == -1 && errno == EINTR);
+}
+
+/* A signal interrupts the wait, after the handler runs. */
+void test_signal()
+{
+    pthread_t thread;
+    void *interrupted;
+    struct sigaction act = { .sa_handler = on_signal }; /* no SA_RESTART */
+
+    word = 0;
+    ready = 0;
+    check(sigaction(SIGUSR1, &act, NULL) == 0, "sigaction");
+    check(pthread_create(&thread, NULL, wait_signal, NULL) == 0, "pthread_create");
+    while (!ready)
+        sched_yield();
+    usleep(10000); /* let it block */
+
+    check(pthread_kill(thread, SIGUSR1) == 0, "pthread_kill");
+    check(pthread_join(thread, &interrupted) == 0, "pthread_join");
+    check(interrupted != NULL, "EINTR");
+    check(handled == SIGUSR1, "handler");
+}
+
+int main()
+{
+    printf("Futex ...\n");
+
+    test_mutex();
+    test_errors();
+    test_requeue();
+    test_signal();
+
+    printf("Futex ok!\n");
+    return 0;
+}
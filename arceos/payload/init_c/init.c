@@ -0,0 +1,67 @@
+#include <stdio.h>
+#include <stdlib.h>
+#include <unistd.h>
+#include <sys/wait.h>
+
+/* Programs launched by init one by one. */
+static char *const programs[] = {
+    "/sbin/hello",
+    "/sbin/fileops",
+    "/sbin/cow",
+    "/sbin/mapshared",
+    "/sbin/mremap",
+    "/sbin/futex",
+    "/sbin/process",
+    "/sbin/signal",
+    "/sbin/syscall",
+    "/sbin/loader",
+    NULL,
+};
+
+pid_t spawn(char *path)
+{
+    pid_t pid;
+    char *argv[] = { path, NULL };
+    char *envp[] = { NULL };
+
+    pid = fork();
+    if (pid == 0) {
+        execve(path, argv, envp);
+        printf("init: cannot execute %s!\n", path);
+        exit(127);
+    }
+    return pid;
+}
+
+int main()
+{
+    int i;
+    int status;
+    pid_t pid;
+
+    printf("init: started as pid %d\n", getpid());
+
+    for (i = 0; programs[i] != NULL; i++) {
+        pid = spawn(programs[i]);
+        if (pid < 0) {
+            printf("init: fork error!\n");
+            continue;
+        }
+        if (waitpid(pid, &status, 0) != pid)
+            continue;
+        if (WIFSIGNALED(status)) {
+            printf("init: %s [%d] killed by signal %d\n",
+                   programs[i], pid, WTERMSIG(status));
+        } else {
+            printf("init: %s [%d] exited with %d\n",
+                   programs[i], pid, WEXITSTATUS(status));
+        }
+    }
+
+    /* Reap the orphans adopted by init. */
+    while (wait(NULL) > 0)
+        ;
+
+    printf("init: all programs finished.\n");
+    return 0;
+}
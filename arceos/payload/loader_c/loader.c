@@ -0,0 +1,127 @@
+#include <elf.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <sys/auxv.h>
+#include <sys/stat.h>
+#include <unistd.h>
+
+/* Lowest load address of position-independent executables. */
+#define PIE_BASE 0x1000000000UL
+
+extern void _start(void);
+
+static int value = 42;
+static int *pointer = &value;
+
+void check(int cond, const char *msg)
+{
+    if (!cond) {
+        printf("Loader error: %s!\n", msg);
+        exit(-1);
+    }
+}
+
+/* The program is a static PIE, so it is loaded above the PIE base and
+ * relocated there. */
+void test_static_pie()
+{
+    check((unsigned long)&test_static_pie >= PIE_BASE, "PIE base");
+    check(pointer == &value && *pointer == 42, "relocation");
+}
+
+void test_auxv(const char *path)
+{
+    Elf64_Phdr *phdr = (Elf64_Phdr *)getauxval(AT_PHDR);
+    unsigned long phnum = getauxval(AT_PHNUM);
+    unsigned long i;
+    int found = 0;
+
+    check(getauxval(AT_PAGESZ) == 4096, "AT_PAGESZ");
+    check(getauxval(AT_ENTRY) == (unsigned long)&_start, "AT_ENTRY");
+    check(getauxval(AT_BASE) == 0, "AT_BASE");
+    check(getauxval(AT_RANDOM) != 0, "AT_RANDOM");
+    check(strcmp((const char *)getauxval(AT_EXECFN), path) == 0, "AT_EXECFN");
+
+    check(getauxval(AT_PHENT) == sizeof(Elf64_Phdr), "AT_PHENT");
+    check(phdr != NULL && phnum > 0, "AT_PHDR");
+    for (i = 0; i < phnum; i++) {
+        if (phdr[i].p_type == PT_LOAD)
+            found++;
+    }
+    check(found > 0, "PT_LOAD");
+}
+
+char *read_file(const char *path, size_t *size)
+{
+    struct stat st;
+    char *buf;
+    int fd;
+
+    fd = open(path, O_RDONLY);
+    check(fd >= 0, "open");
+    check(fstat(fd, &st) == 0, "fstat");
+    buf = malloc(st.st_size);
+    check(buf != NULL, "malloc");
+    check(read(fd, buf, st.st_size) == st.st_size, "read");
+    close(fd);
+    *size = st.st_size;
+    return buf;
+}
+
+/* Writes the image to a file and executes it, which must fail without
+ * replacing this program. */
+void check_rejected(const char *image, size_t size, const char *msg)
+{
+    char fname[] = "bad_loader";
+    char *argv[] = { fname, NULL };
+    char *envp[] = { NULL };
+    int fd;
+
+    fd = open(fname, O_WRONLY | O_CREAT | O_TRUNC, 0700);
+    check(fd >= 0, "create file");
+    check(write(fd, image, size) == (ssize_t)size, "write file");
+    close(fd);
+
+    check(execve(fname, argv, envp) == -1 && errno == ENOEXEC, msg);
+    unlink(fname);
+}
+
+void test_bad_images(const char *path)
+{
+    size_t size;
+    char *image = read_file(path, &size);
+    Elf64_Ehdr *ehdr = (Elf64_Ehdr *)image;
+    Elf64_Phdr *phdr = (Elf64_Phdr *)(image + ehdr->e_phoff);
+    int i;
+
+    check(memcmp(ehdr->e_ident, ELFMAG, SELFMAG) == 0, "ELF magic");
+
+    /* The segments lie beyond the end of the file. */
+    check_rejected(image, ehdr->e_phoff + ehdr->e_phnum * sizeof(Elf64_Phdr),
+                   "truncated");
+
+    /* A segment with more bytes in the file than in memory. */
+    for (i = 0; i < ehdr->e_phnum; i++) {
+        if (phdr[i].p_type == PT_LOAD) {
+            phdr[i].p_filesz = phdr[i].p_memsz + 1;
+            break;
+        }
+    }
+    check_rejected(image, size, "p_filesz > p_memsz");
+    free(image);
+}
+
+int main(int argc, char *argv[])
+{
+    printf("Loader ...\n");
+
+    test_static_pie();
+    test_auxv(argv[0]);
+    test_bad_images(argv[0]);
+
+    printf("Loader ok!\n");
+    return 0;
+}
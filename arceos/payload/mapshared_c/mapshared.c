@@ -0,0 +1,106 @@
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <unistd.h>
+#include <fcntl.h>
+#include <sys/mman.h>
+#include <sys/wait.h>
+
+#define PAGE_SIZE 4096
+#define NR_PAGES 4
+
+void check(int cond, const char *msg)
+{
+    if (!cond) {
+        printf("MapShared error: %s!\n", msg);
+        exit(-1);
+    }
+}
+
+int create_file(const char *fname)
+{
+    int fd;
+    char page[PAGE_SIZE];
+    int i;
+
+    fd = open(fname, O_RDWR | O_CREAT | O_TRUNC, 0600);
+    check(fd >= 0, "create file");
+    memset(page, 'a', PAGE_SIZE);
+    for (i = 0; i < NR_PAGES; i++)
+        check(write(fd, page, PAGE_SIZE) == PAGE_SIZE, "write file");
+    return fd;
+}
+
+/* Two shared mappings of the same file see the writes of each other, and
+ * the writes through the file. */
+void test_same_file(int fd)
+{
+    char *a, *b;
+    char c;
+
+    a = mmap(NULL, NR_PAGES * PAGE_SIZE, PROT_READ | PROT_WRITE, MAP_SHARED, fd, 0);
+    b = mmap(NULL, NR_PAGES * PAGE_SIZE, PROT_READ, MAP_SHARED, fd, 0);
+    check(a != MAP_FAILED && b != MAP_FAILED, "mmap");
+    check(b[0] == 'a', "read mapping");
+    a[0] = 'b';
+    check(b[0] == 'b', "see the write of another mapping");
+    check(pwrite(fd, "c", 1, 1) == 1, "pwrite");
+    check(a[1] == 'c', "see the write of the file");
+    check(pread(fd, &c, 1, 0) == 1 && c == 'b', "read the write of the mapping");
+    munmap(a, NR_PAGES * PAGE_SIZE);
+    munmap(b, NR_PAGES * PAGE_SIZE);
+}
+
+/* The pages shared with a child are the same frames, and the modified
+ * ones are written back by msync. */
+void test_fork(int fd)
+{
+    int status;
+    pid_t pid;
+    char *buf;
+    char c;
+
+    buf = mmap(NULL, NR_PAGES * PAGE_SIZE, PROT_READ | PROT_WRITE, MAP_SHARED, fd, 0);
+    check(buf != MAP_FAILED, "mmap");
+    buf[PAGE_SIZE] = 'p';
+
+    pid = fork();
+    check(pid >= 0, "fork");
+    if (pid == 0) {
+        check(buf[PAGE_SIZE] == 'p', "child reads page");
+        buf[PAGE_SIZE] = 'c';
+        /* Never touched by the parent before. */
+        buf[2 * PAGE_SIZE] = 'c';
+        check(msync(buf, NR_PAGES * PAGE_SIZE, MS_SYNC) == 0, "child msync");
+        exit(0);
+    }
+    check(waitpid(pid, &status, 0) == pid, "waitpid");
+    check(WIFEXITED(status) && WEXITSTATUS(status) == 0, "child failed");
+
+    check(buf[PAGE_SIZE] == 'c', "parent sees the write of child");
+    check(buf[2 * PAGE_SIZE] == 'c', "parent sees the new page of child");
+    check(pread(fd, &c, 1, 2 * PAGE_SIZE) == 1 && c == 'c', "read the write of child");
+    /* Written again after msync, then written back by munmap. */
+    buf[3 * PAGE_SIZE] = 'q';
+    check(msync(buf, NR_PAGES * PAGE_SIZE, MS_SYNC) == 0, "parent msync");
+    buf[3 * PAGE_SIZE + 1] = 'q';
+    munmap(buf, NR_PAGES * PAGE_SIZE);
+    check(pread(fd, &c, 1, 3 * PAGE_SIZE + 1) == 1 && c == 'q', "read after munmap");
+}
+
+int main()
+{
+    int fd;
+    char fname[] = "test_shared_file";
+
+    printf("MapShared ...\n");
+
+    fd = create_file(fname);
+    test_same_file(fd);
+    test_fork(fd);
+    close(fd);
+    unlink(fname);
+
+    printf("MapShared ok!\n");
+    return 0;
+}
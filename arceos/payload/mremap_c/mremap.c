@@ -0,0 +1,107 @@
+#define _GNU_SOURCE
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <unistd.h>
+#include <sys/mman.h>
+#include <sys/syscall.h>
+#include <sys/wait.h>
+
+#define PAGE_SIZE 4096
+
+void check(int cond, const char *msg)
+{
+    if (!cond) {
+        printf("Mremap error: %s!\n", msg);
+        exit(-1);
+    }
+}
+
+/* The raw syscall, as musl refuses to move the program break. */
+char *brk_to(char *addr)
+{
+    return (char *)syscall(SYS_brk, addr);
+}
+
+/* The heap grows in many small steps, and keeps its content when it shrinks
+ * and grows again. */
+void test_brk()
+{
+    int i;
+    char *start, *brk;
+
+    start = brk_to(NULL);
+    brk = start;
+    for (i = 0; i < 64; i++) {
+        check(brk_to(brk + 100) == brk + 100, "grow heap");
+        brk[99] = (char)(i + 1);
+        brk += 100;
+    }
+    for (i = 0; i < 64; i++)
+        check(start[i * 100 + 99] == (char)(i + 1), "read heap");
+    check(brk_to(start + PAGE_SIZE) == start + PAGE_SIZE, "shrink heap");
+    check(brk_to(start - PAGE_SIZE) == start + PAGE_SIZE, "shrink below start");
+    check(brk_to(start + 16 * PAGE_SIZE) == start + 16 * PAGE_SIZE, "grow heap again");
+    check(start[99] == 1, "kept content");
+    memset(start, 'h', 16 * PAGE_SIZE);
+    check(brk_to(start) == start, "free heap");
+}
+
+/* Anonymous memory shared with a child by fork is moved, and both of them
+ * still see their own writes only. */
+void test_move()
+{
+    int status;
+    int pipefd[2];
+    pid_t pid;
+    char *buf, *moved;
+    char c;
+
+    buf = mmap(NULL, 4 * PAGE_SIZE, PROT_READ | PROT_WRITE,
+               MAP_PRIVATE | MAP_ANONYMOUS, -1, 0);
+    check(buf != MAP_FAILED, "mmap");
+    buf[0] = 'a';
+    buf[2 * PAGE_SIZE] = 'b';
+
+    check(pipe(pipefd) == 0, "pipe");
+    pid = fork();
+    check(pid >= 0, "fork");
+    if (pid == 0) {
+        close(pipefd[1]);
+        check(read(pipefd[0], &c, 1) == 1, "child reads pipe");
+        check(buf[0] == 'a' && buf[2 * PAGE_SIZE] == 'b', "child sees the writes of parent");
+        exit(0);
+    }
+    close(pipefd[0]);
+
+    /* Block the growth in place, so that it has to move. */
+    check(mmap(buf + 4 * PAGE_SIZE, PAGE_SIZE, PROT_READ, MAP_PRIVATE | MAP_ANONYMOUS | MAP_FIXED,
+               -1, 0) != MAP_FAILED, "mmap after");
+    moved = mremap(buf, 4 * PAGE_SIZE, 8 * PAGE_SIZE, MREMAP_MAYMOVE);
+    check(moved != MAP_FAILED && moved != buf, "mremap");
+    check(moved[0] == 'a' && moved[2 * PAGE_SIZE] == 'b', "moved content");
+    moved[0] = 'x';
+    moved[7 * PAGE_SIZE] = 'y';
+
+    check(write(pipefd[1], "x", 1) == 1, "parent writes pipe");
+    check(waitpid(pid, &status, 0) == pid, "waitpid");
+    check(WIFEXITED(status) && WEXITSTATUS(status) == 0, "child failed");
+    close(pipefd[1]);
+
+    /* Shrink in place. */
+    check(mremap(moved, 8 * PAGE_SIZE, 2 * PAGE_SIZE, 0) == moved, "shrink");
+    check(moved[0] == 'x', "shrunk content");
+    munmap(moved, 2 * PAGE_SIZE);
+    munmap(buf + 4 * PAGE_SIZE, PAGE_SIZE);
+}
+
+int main()
+{
+    printf("Mremap ...\n");
+
+    test_brk();
+    test_move();
+
+    printf("Mremap ok!\n");
+    return 0;
+}
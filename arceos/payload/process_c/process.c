@@ -0,0 +1,211 @@
+#define _GNU_SOURCE
+#include <errno.h>
+#include <linux/futex.h>
+#include <pthread.h>
+#include <sched.h>
+#include <signal.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <sys/syscall.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#define EXEC_EXIT_CODE 42
+/* An unmapped address, which the syscalls must not fault on. */
+#define BAD_PTR ((void *)8)
+/* Longer than all the arguments of `execve` may take. */
+#define LONG_ARG_LEN (256 * 1024)
+
+static volatile int word;
+static volatile int ready;
+
+void check(int cond, const char *msg)
+{
+    if (!cond) {
+        printf("Process error: %s!\n", msg);
+        exit(-1);
+    }
+}
+
+/* Blocks in an interruptible wait that is never woken up. */
+void *block(void *arg)
+{
+    __sync_fetch_and_add(&ready, 1);
+    for (;;)
+        syscall(SYS_futex, &word, FUTEX_WAIT_PRIVATE, 0, NULL, NULL, 0);
+    return arg;
+}
+
+void *sleep_long(void *arg)
+{
+    __sync_fetch_and_add(&ready, 1);
+    for (;;)
+        sleep(1000);
+    return arg;
+}
+
+void *spin(void *arg)
+{
+    __sync_fetch_and_add(&ready, 1);
+    for (;;)
+        ;
+    return arg;
+}
+
+/* Starts the threads that never return by themselves. */
+void start_threads()
+{
+    pthread_t thread;
+
+    ready = 0;
+    check(pthread_create(&thread, NULL, block, NULL) == 0, "pthread_create");
+    check(pthread_create(&thread, NULL, sleep_long, NULL) == 0, "pthread_create");
+    check(pthread_create(&thread, NULL, spin, NULL) == 0, "pthread_create");
+    while (ready < 3)
+        sched_yield();
+    usleep(10000); /* let them block */
+}
+
+int wait_child(pid_t pid)
+{
+    int status;
+
+    check(waitpid(pid, &status, 0) == pid, "waitpid");
+    return status;
+}
+
+/* No child is left behind when `clone` fails to store the TID. */
+void test_clone_fault()
+{
+    long pid;
+
+    pid = syscall(SYS_clone, SIGCHLD | CLONE_PARENT_SETTID, 0, (void *)8, 0, 0);
+    if (pid == 0)
+        _exit(0);
+    if (pid > 0) {
+        check(WIFEXITED(wait_child(pid)), "clone child");
+    } else {
+        check(errno == EFAULT, "clone EFAULT");
+        check(waitpid(-1, NULL, WNOHANG) == -1 && errno == ECHILD, "clone rollback");
+    }
+}
+
+/* `exit_group` terminates the blocked and running threads. */
+void test_exit_group()
+{
+    int status;
+    pid_t pid;
+
+    pid = fork();
+    if (pid == 0) {
+        start_threads();
+        exit(3);
+    }
+    check(pid > 0, "fork");
+    status = wait_child(pid);
+    check(WIFEXITED(status) && WEXITSTATUS(status) == 3, "exit_group");
+}
+
+/* `SIGKILL` terminates a process blocked in `wait4` and its threads. */
+void test_kill()
+{
+    int hold[2], started[2];
+    int status;
+    char c;
+    pid_t pid;
+
+    check(pipe(hold) == 0 && pipe(started) == 0, "pipe");
+    pid = fork();
+    if (pid == 0) {
+        close(hold[1]);
+        close(started[0]);
+        if (fork() == 0) {
+            /* Keeps its parent waiting until we close the write end. */
+            read(hold[0], &c, 1);
+            _exit(0);
+        }
+        start_threads();
+        write(started[1], "s", 1);
+        wait(NULL);
+        _exit(0);
+    }
+    check(pid > 0, "fork");
+    close(hold[0]);
+    close(started[1]);
+    check(read(started[0], &c, 1) == 1, "read");
+    usleep(10000); /* let it block */
+
+    check(kill(pid, SIGKILL) == 0, "kill");
+    status = wait_child(pid);
+    check(WIFSIGNALED(status) && WTERMSIG(status) == SIGKILL, "SIGKILL");
+    close(hold[1]);
+    close(started[0]);
+}
+
+/* `execve` terminates the other threads before running the new program. */
+void test_execve(char *path)
+{
+    int status;
+    pid_t pid;
+    char *argv[] = { path, "exec", NULL };
+    char *envp[] = { NULL };
+
+    pid = fork();
+    if (pid == 0) {
+        start_threads();
+        execve(path, argv, envp);
+        _exit(-1);
+    }
+    check(pid > 0, "fork");
+    status = wait_child(pid);
+    check(WIFEXITED(status) && WEXITSTATUS(status) == EXEC_EXIT_CODE, "execve");
+}
+
+/* A failed `execve` returns to the old program. */
+void test_execve_errors(char *path)
+{
+    char *argv[] = { path, NULL, NULL };
+    char *bad_argv[] = { path, BAD_PTR, NULL };
+    char *envp[] = { NULL };
+    char *long_arg;
+
+    check(syscall(SYS_execve, BAD_PTR, argv, envp) == -1 && errno == EFAULT, "execve path");
+    check(syscall(SYS_execve, path, BAD_PTR, envp) == -1 && errno == EFAULT, "execve argv");
+    check(execve(path, bad_argv, envp) == -1 && errno == EFAULT, "execve argv[1]");
+
+    long_arg = malloc(LONG_ARG_LEN + 1);
+    check(long_arg != NULL, "malloc");
+    memset(long_arg, 'a', LONG_ARG_LEN);
+    long_arg[LONG_ARG_LEN] = '\0';
+    argv[1] = long_arg;
+    check(execve(path, argv, envp) == -1 && errno == E2BIG, "execve E2BIG");
+    free(long_arg);
+}
+
+/* `CLONE_VFORK` is not supported, and rejected instead of ignored. */
+void test_vfork()
+{
+    check(syscall(SYS_clone, CLONE_VM | CLONE_VFORK | SIGCHLD, 0, NULL, NULL, 0) == -1 &&
+              errno == EINVAL,
+          "CLONE_VFORK");
+}
+
+int main(int argc, char *argv[])
+{
+    if (argc > 1 && strcmp(argv[1], "exec") == 0)
+        return EXEC_EXIT_CODE;
+
+    printf("Process ...\n");
+    fflush(stdout); /* not to be printed again by the children */
+
+    test_clone_fault();
+    test_exit_group();
+    test_kill();
+    test_execve(argv[0]);
+    test_execve_errors(argv[0]);
+    test_vfork();
+
+    printf("Process ok!\n");
+    return 0;
+}
@@ -0,0 +1,191 @@
+#define _GNU_SOURCE
+#include <errno.h>
+#include <setjmp.h>
+#include <signal.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <sys/syscall.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#define NR_QUEUED 3
+/* An unmapped address, which the syscalls must not fault on. */
+#define BAD_PTR ((void *)8)
+#define SIGSET_SIZE 8
+
+static volatile int usr1_count;
+static volatile int usr1_blocked;
+static volatile int usr2_count;
+static volatile int rt_count;
+static volatile siginfo_t usr1_info;
+static sigjmp_buf segv_env;
+
+void check(int cond, const char *msg)
+{
+    if (!cond) {
+        printf("Signal error: %s!\n", msg);
+        exit(-1);
+    }
+}
+
+void on_usr1(int signo, siginfo_t *info, void *ucontext)
+{
+    sigset_t set;
+
+    usr1_count++;
+    usr1_info = *info;
+    sigprocmask(SIG_BLOCK, NULL, &set);
+    usr1_blocked = sigismember(&set, SIGUSR1);
+    (void)signo;
+    (void)ucontext;
+}
+
+void on_usr2(int signo)
+{
+    usr2_count++;
+    (void)signo;
+}
+
+void on_rt(int signo)
+{
+    rt_count++;
+    (void)signo;
+}
+
+void on_segv(int signo)
+{
+    siglongjmp(segv_env, signo);
+}
+
+void set_action(int signo, void (*handler)(int), int flags)
+{
+    struct sigaction sa;
+
+    memset(&sa, 0, sizeof(sa));
+    sa.sa_handler = handler;
+    sa.sa_flags = flags;
+    check(sigaction(signo, &sa, NULL) == 0, "sigaction");
+}
+
+/* A handler gets the signal information, with the signal blocked while it
+ * runs, and the interrupted context is restored after it returns. */
+void test_handler()
+{
+    struct sigaction sa;
+    sigset_t set;
+    volatile long local = 0x5a5a5a5a;
+    pid_t pid = getpid();
+
+    memset(&sa, 0, sizeof(sa));
+    sa.sa_sigaction = on_usr1;
+    sa.sa_flags = SA_SIGINFO;
+    check(sigaction(SIGUSR1, &sa, NULL) == 0, "sigaction");
+
+    check(kill(pid, SIGUSR1) == 0, "kill");
+    check(usr1_count == 1, "handler not called");
+    check(usr1_info.si_signo == SIGUSR1, "si_signo");
+    check(usr1_info.si_code == SI_USER, "si_code");
+    check(usr1_info.si_pid == pid, "si_pid");
+    check(usr1_blocked, "signal not blocked in its handler");
+    check(local == 0x5a5a5a5a && getpid() == pid, "context not restored");
+
+    sigprocmask(SIG_BLOCK, NULL, &set);
+    check(!sigismember(&set, SIGUSR1), "signal still blocked");
+}
+
+/* Blocked real-time signals are queued, standard ones are pending once. */
+void test_queue()
+{
+    sigset_t set, pending;
+    int i;
+
+    set_action(SIGUSR2, on_usr2, 0);
+    set_action(SIGRTMIN + 1, on_rt, 0);
+    sigemptyset(&set);
+    sigaddset(&set, SIGUSR2);
+    sigaddset(&set, SIGRTMIN + 1);
+    check(sigprocmask(SIG_BLOCK, &set, NULL) == 0, "sigprocmask");
+
+    for (i = 0; i < NR_QUEUED; i++) {
+        check(kill(getpid(), SIGUSR2) == 0, "kill");
+        check(kill(getpid(), SIGRTMIN + 1) == 0, "kill");
+    }
+    check(usr2_count == 0 && rt_count == 0, "blocked signal delivered");
+    check(sigpending(&pending) == 0, "sigpending");
+    check(sigismember(&pending, SIGUSR2), "SIGUSR2 not pending");
+    check(sigismember(&pending, SIGRTMIN + 1), "SIGRTMIN+1 not pending");
+
+    check(sigprocmask(SIG_UNBLOCK, &set, NULL) == 0, "sigprocmask");
+    check(usr2_count == 1, "standard signal delivered more than once");
+    check(rt_count == NR_QUEUED, "real-time signal lost");
+    check(sigpending(&pending) == 0, "sigpending");
+    check(!sigismember(&pending, SIGRTMIN + 1), "SIGRTMIN+1 still pending");
+}
+
+/* `SA_RESETHAND` restores the default action when the handler is called. */
+void test_resethand()
+{
+    struct sigaction sa;
+
+    usr2_count = 0;
+    set_action(SIGUSR2, on_usr2, SA_RESETHAND);
+    check(kill(getpid(), SIGUSR2) == 0, "kill");
+    check(usr2_count == 1, "handler not called");
+    check(sigaction(SIGUSR2, NULL, &sa) == 0, "sigaction");
+    check(sa.sa_handler == SIG_DFL, "action not reset");
+}
+
+/* A fault is delivered to its handler, which may jump out of it. */
+void test_fault()
+{
+    volatile int *null = NULL;
+
+    set_action(SIGSEGV, on_segv, SA_NODEFER);
+    if (sigsetjmp(segv_env, 1) == 0) {
+        *null = 1;
+        check(0, "no fault");
+    }
+    set_action(SIGSEGV, SIG_DFL, 0);
+}
+
+/* Bad user pointers fail the syscalls with `EFAULT`. */
+void test_bad_pointers()
+{
+    pid_t pid;
+
+    check(syscall(SYS_rt_sigaction, SIGUSR1, BAD_PTR, NULL, SIGSET_SIZE) == -1 &&
+              errno == EFAULT,
+          "rt_sigaction act");
+    check(syscall(SYS_rt_sigaction, SIGUSR1, NULL, BAD_PTR, SIGSET_SIZE) == -1 &&
+              errno == EFAULT,
+          "rt_sigaction oldact");
+    check(syscall(SYS_rt_sigprocmask, SIG_BLOCK, BAD_PTR, NULL, SIGSET_SIZE) == -1 &&
+              errno == EFAULT,
+          "rt_sigprocmask set");
+    check(syscall(SYS_rt_sigprocmask, SIG_BLOCK, NULL, BAD_PTR, SIGSET_SIZE) == -1 &&
+              errno == EFAULT,
+          "rt_sigprocmask oldset");
+    check(syscall(SYS_rt_sigpending, BAD_PTR, SIGSET_SIZE) == -1 && errno == EFAULT,
+          "rt_sigpending");
+
+    pid = fork();
+    if (pid == 0)
+        _exit(0);
+    check(pid > 0, "fork");
+    check(syscall(SYS_wait4, pid, BAD_PTR, 0, NULL) == -1 && errno == EFAULT, "wait4");
+}
+
+int main()
+{
+    printf("Signal ...\n");
+
+    test_handler();
+    test_queue();
+    test_resethand();
+    test_fault();
+    test_bad_pointers();
+
+    printf("Signal ok!\n");
+    return 0;
+}
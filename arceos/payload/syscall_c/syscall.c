@@ -0,0 +1,126 @@
+#include <dirent.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <pthread.h>
+#include <signal.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <sys/syscall.h>
+#include <unistd.h>
+
+/* An unmapped address, which the syscalls must not fault on. */
+#define BAD_PTR ((void *)8)
+#define LONG_PATH_LEN 5000
+
+static pthread_mutex_t robust;
+
+void check(int cond, const char *msg)
+{
+    if (!cond) {
+        printf("Syscall error: %s!\n", msg);
+        exit(-1);
+    }
+}
+
+/* Lists /sbin through `getdents64`, which this program is in. */
+void test_getdents()
+{
+    DIR *dir;
+    struct dirent *ent;
+    int found = 0;
+
+    dir = opendir("/sbin");
+    check(dir != NULL, "opendir");
+    while ((ent = readdir(dir)) != NULL) {
+        if (strcmp(ent->d_name, "syscall") == 0 || strcmp(ent->d_name, "init") == 0) {
+            check(ent->d_type == DT_REG, "d_type");
+            found++;
+        }
+    }
+    check(found == 2, "readdir");
+    check(closedir(dir) == 0, "closedir");
+}
+
+void test_getdents_errors()
+{
+    char buf[8];
+    int fd;
+
+    fd = open("/sbin", O_RDONLY | O_DIRECTORY);
+    check(fd >= 0, "open O_DIRECTORY");
+    check(syscall(SYS_getdents64, fd, buf, sizeof(buf)) == -1 && errno == EINVAL,
+          "EINVAL");
+    close(fd);
+
+    check(open("/sbin/syscall", O_RDONLY | O_DIRECTORY) == -1 && errno == ENOTDIR,
+          "ENOTDIR open");
+    fd = open("/sbin/syscall", O_RDONLY);
+    check(fd >= 0, "open");
+    check(syscall(SYS_getdents64, fd, buf, sizeof(buf)) == -1 && errno == ENOTDIR,
+          "ENOTDIR getdents64");
+    close(fd);
+}
+
+/* Bad or too long paths fail the syscalls instead of faulting in the
+ * kernel. */
+void test_bad_paths()
+{
+    char *path;
+
+    check(syscall(SYS_openat, AT_FDCWD, BAD_PTR, O_RDONLY, 0) == -1 && errno == EFAULT,
+          "openat EFAULT");
+    check(syscall(SYS_mkdirat, AT_FDCWD, BAD_PTR, 0755) == -1 && errno == EFAULT,
+          "mkdirat EFAULT");
+    check(syscall(SYS_chdir, BAD_PTR) == -1 && errno == EFAULT, "chdir EFAULT");
+
+    path = malloc(LONG_PATH_LEN + 1);
+    check(path != NULL, "malloc");
+    memset(path, 'a', LONG_PATH_LEN);
+    path[0] = '/';
+    path[LONG_PATH_LEN] = '\0';
+    check(open(path, O_RDONLY) == -1 && errno == ENAMETOOLONG, "open ENAMETOOLONG");
+    free(path);
+}
+
+/* Exits the thread by the syscall, so that the mutex is left to the kernel
+ * walking the robust list, not to `pthread_exit`. */
+void *lock_and_die(void *arg)
+{
+    pthread_mutex_lock(&robust);
+    syscall(SYS_exit, 0);
+    return arg;
+}
+
+void test_robust_mutex()
+{
+    pthread_t thread;
+    pthread_mutexattr_t attr;
+
+    check(pthread_mutexattr_init(&attr) == 0, "pthread_mutexattr_init");
+    check(pthread_mutexattr_setrobust(&attr, PTHREAD_MUTEX_ROBUST) == 0,
+          "pthread_mutexattr_setrobust");
+    check(pthread_mutex_init(&robust, &attr) == 0, "pthread_mutex_init");
+
+    check(pthread_create(&thread, NULL, lock_and_die, NULL) == 0, "pthread_create");
+    check(pthread_join(thread, NULL) == 0, "pthread_join");
+
+    check(pthread_mutex_lock(&robust) == EOWNERDEAD, "EOWNERDEAD");
+    check(pthread_mutex_consistent(&robust) == 0, "pthread_mutex_consistent");
+    check(pthread_mutex_unlock(&robust) == 0, "pthread_mutex_unlock");
+    check(pthread_mutex_lock(&robust) == 0, "pthread_mutex_lock");
+    check(pthread_mutex_unlock(&robust) == 0, "pthread_mutex_unlock again");
+}
+
+int main()
+{
+    printf("Syscall ...\n");
+
+    test_getdents();
+    test_getdents_errors();
+    test_bad_paths();
+    test_robust_mutex();
+
+    printf("Syscall ok!\n");
+    return 0;
+}
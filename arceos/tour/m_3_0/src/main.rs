@@ -28,6 +28,9 @@ const KERNEL_STACK_SIZE: usize = 0x40000; // 256 KiB
 
 #[cfg_attr(feature = "axstd", no_mangle)]
 fn main() {
+    // Show the syscalls of the app as they are handled.
+    axsyscall::set_trace(true);
+
     // A new address space for user app.
     let mut uspace = axmm::new_user_aspace().unwrap();
 
@@ -1,53 +1,20 @@
-#![allow(dead_code)]
-
-use core::ffi::c_void;
 use axhal::arch::TrapFrame;
 use axhal::trap::{register_trap_handler, SYSCALL};
-use axerrno::LinuxError;
 use axtask::current;
 use axtask::TaskExtRef;
-use arceos_posix_api as api;
 
-const SYS_IOCTL: usize = 29;
-const SYS_WRITEV: usize = 66;
-const SYS_EXIT: usize = 93;
-const SYS_EXIT_GROUP: usize = 94;
-const SYS_SET_TID_ADDRESS: usize = 96;
+// The other syscalls of the app are handled by the built-in handlers.
+axsyscall::register_syscalls! {
+    set_tid_address => |args| Ok(sys_set_tid_address(args.ptr(0))),
+}
 
 #[register_trap_handler(SYSCALL)]
 fn handle_syscall(tf: &TrapFrame, syscall_num: usize) -> isize {
-    ax_println!("handle_syscall [{}] ...", syscall_num);
-    let ret = match syscall_num {
-         SYS_IOCTL => sys_ioctl(tf.arg0() as _, tf.arg1() as _, tf.arg2() as _) as _,
-        SYS_SET_TID_ADDRESS => sys_set_tid_address(tf.arg0() as _),
-        SYS_WRITEV => sys_writev(tf.arg0() as _, tf.arg1() as _, tf.arg2() as _),
-        SYS_EXIT_GROUP => {
-            ax_println!("[SYS_EXIT_GROUP]: system is exiting ..");
-            axtask::exit(tf.arg0() as _)
-        },
-        SYS_EXIT => {
-            ax_println!("[SYS_EXIT]: system is exiting ..");
-            axtask::exit(tf.arg0() as _)
-        },
-        _ => {
-            ax_println!("Unimplemented syscall: {}", syscall_num);
-            -LinuxError::ENOSYS.code() as _
-        }
-    };
-    ret
-}
-
-fn sys_writev(fd: i32, iov: *const api::ctypes::iovec, iocnt: i32) -> isize {
-    unsafe { api::sys_writev(fd, iov, iocnt) }
+    axsyscall::handle_syscall(tf, syscall_num)
 }
 
-pub(crate) fn sys_set_tid_address(tid_ptd: *const i32) -> isize {
+fn sys_set_tid_address(tid_ptd: *const i32) -> isize {
     let curr = current();
     curr.task_ext().set_clear_child_tid(tid_ptd as _);
     curr.id().as_u64() as isize
 }
-
-fn sys_ioctl(_fd: i32, _op: usize, _argp: *mut c_void) -> i32 {
-    ax_println!("Unimplemented syscall: SYS_IOCTL");
-    0
-}
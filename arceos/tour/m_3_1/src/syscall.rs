@@ -1,71 +1,16 @@
-#![allow(dead_code)]
-
-use core::ffi::{c_void, c_char, c_int};
 use axhal::arch::TrapFrame;
 use axhal::trap::{register_trap_handler, SYSCALL};
-use axerrno::LinuxError;
 use axtask::current;
 use axtask::TaskExtRef;
-use arceos_posix_api as api;
-
-const SYS_IOCTL: usize = 29;
-const SYS_OPENAT: usize = 56;
-const SYS_CLOSE: usize = 57;
-const SYS_READ: usize = 63;
-const SYS_WRITE: usize = 64;
-const SYS_WRITEV: usize = 66;
-const SYS_EXIT: usize = 93;
-const SYS_EXIT_GROUP: usize = 94;
-const SYS_SET_TID_ADDRESS: usize = 96;
 
-const AT_FDCWD: i32 = -100;
+// The other syscalls of the app are handled by the built-in handlers.
+axsyscall::register_syscalls! {
+    set_tid_address => |args| Ok(sys_set_tid_address(args.ptr(0))),
+}
 
 #[register_trap_handler(SYSCALL)]
 fn handle_syscall(tf: &TrapFrame, syscall_num: usize) -> isize {
-    ax_println!("handle_syscall [{}] ...", syscall_num);
-    let ret = match syscall_num {
-         SYS_IOCTL => sys_ioctl(tf.arg0() as _, tf.arg1() as _, tf.arg2() as _) as _,
-        SYS_SET_TID_ADDRESS => sys_set_tid_address(tf.arg0() as _),
-        SYS_OPENAT => sys_openat(tf.arg0() as _, tf.arg1() as _, tf.arg2() as _, tf.arg3() as _),
-        SYS_CLOSE => sys_close(tf.arg0() as _),
-        SYS_READ => sys_read(tf.arg0() as _, tf.arg1() as _, tf.arg2() as _),
-        SYS_WRITE => sys_write(tf.arg0() as _, tf.arg1() as _, tf.arg2() as _),
-        SYS_WRITEV => sys_writev(tf.arg0() as _, tf.arg1() as _, tf.arg2() as _),
-        SYS_EXIT_GROUP => {
-            ax_println!("[SYS_EXIT_GROUP]: system is exiting ..");
-            axtask::exit(tf.arg0() as _)
-        },
-        SYS_EXIT => {
-            ax_println!("[SYS_EXIT]: system is exiting ..");
-            axtask::exit(tf.arg0() as _)
-        },
-        _ => {
-            ax_println!("Unimplemented syscall: {}", syscall_num);
-            -LinuxError::ENOSYS.code() as _
-        }
-    };
-    ret
-}
-
-fn sys_openat(dfd: c_int, fname: *const c_char, flags: c_int, mode: api::ctypes::mode_t) -> isize {
-    assert_eq!(dfd, AT_FDCWD);
-    api::sys_open(fname, flags, mode) as isize
-}
-
-fn sys_close(fd: i32) -> isize {
-    api::sys_close(fd) as isize
-}
-
-fn sys_read(fd: i32, buf: *mut c_void, count: usize) -> isize {
-    api::sys_read(fd, buf, count)
-}
-
-fn sys_write(fd: i32, buf: *const c_void, count: usize) -> isize {
-    api::sys_write(fd, buf, count)
-}
-
-fn sys_writev(fd: i32, iov: *const api::ctypes::iovec, iocnt: i32) -> isize {
-    unsafe { api::sys_writev(fd, iov, iocnt) }
+    axsyscall::handle_syscall(tf, syscall_num)
 }
 
 fn sys_set_tid_address(tid_ptd: *const i32) -> isize {
@@ -73,8 +18,3 @@ fn sys_set_tid_address(tid_ptd: *const i32) -> isize {
     curr.task_ext().set_clear_child_tid(tid_ptd as _);
     curr.id().as_u64() as isize
 }
-
-fn sys_ioctl(_fd: i32, _op: usize, _argp: *mut c_void) -> i32 {
-    ax_println!("Ignore SYS_IOCTL");
-    0
-}
@@ -0,0 +1,72 @@
+#![cfg_attr(feature = "axstd", no_std)]
+#![cfg_attr(feature = "axstd", no_main)]
+
+#[cfg(feature = "axstd")]
+extern crate axstd as std;
+extern crate alloc;
+
+#[macro_use]
+extern crate axlog;
+
+mod syscall;
+
+use alloc::string::String;
+use axhal::arch::TrapFrame;
+use axhal::mem::VirtAddr;
+use axhal::paging::MappingFlags;
+use axhal::trap::{register_trap_handler, PAGE_FAULT, USER_ENTRY, USER_RETURN};
+use axtask::TaskExtRef;
+
+const INIT_PATH: &str = "/sbin/init";
+const USER_ASPACE_END: usize = 0x40_0000_0000;
+
+#[cfg_attr(feature = "axstd", no_mangle)]
+fn main() {
+    // The init process launches the other programs, and reaps the orphans.
+    let init = match axprocess::spawn_init(INIT_PATH, &[String::from(INIT_PATH)], &[]) {
+        Ok(task) => task,
+        Err(err) => panic!("Cannot load init! {:?}", err),
+    };
+
+    // Wait for init process to exit ...
+    let exit_code = init.join();
+    ax_println!("monolithic kernel exit [{:?}] normally!", exit_code);
+}
+
+#[register_trap_handler(PAGE_FAULT)]
+fn handle_page_fault(vaddr: VirtAddr, access_flags: MappingFlags, is_user: bool) -> bool {
+    // The kernel may also fault on the lazily mapped user memory in syscalls.
+    if !is_user && vaddr.as_usize() >= USER_ASPACE_END {
+        return false;
+    }
+    let curr = axtask::current();
+    if unsafe { curr.task_ext_ptr() }.is_null() {
+        return false;
+    }
+    if curr
+        .task_ext()
+        .process
+        .aspace()
+        .lock()
+        .handle_page_fault(vaddr, access_flags)
+    {
+        return true;
+    }
+    if is_user {
+        // Delivered on the way back to user space.
+        axprocess::force_fault_signal(axprocess::SIGSEGV, vaddr);
+        return true;
+    }
+    false
+}
+
+#[register_trap_handler(USER_ENTRY)]
+fn handle_user_entry(_tf: &TrapFrame) {
+    axtask::on_user_entry();
+}
+
+#[register_trap_handler(USER_RETURN)]
+fn handle_user_return(tf: &mut TrapFrame) {
+    axprocess::handle_signals(tf);
+    axtask::on_user_return();
+}
@@ -0,0 +1,9 @@
+use axhal::arch::TrapFrame;
+use axhal::trap::{register_trap_handler, SYSCALL};
+
+#[register_trap_handler(SYSCALL)]
+fn handle_syscall(tf: &TrapFrame, syscall_num: usize) -> isize {
+    // Another thread may have terminated the whole process.
+    axprocess::check_group_exit();
+    axsyscall::handle_syscall(tf, syscall_num)
+}
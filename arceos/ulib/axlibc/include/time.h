@@ -8,6 +8,17 @@
 #define CLOCK_MONOTONIC 1
 #define CLOCKS_PER_SEC  1000000L
 
+#define TIMER_ABSTIME 1
+
+typedef void *timer_t;
+
+struct itimerspec {
+    struct timespec it_interval;
+    struct timespec it_value;
+};
+
+struct sigevent;
+
 struct tm {
     int tm_sec;   /* seconds of minute */
     int tm_min;   /* minutes of hour */
@@ -40,4 +51,10 @@ void tzset(void);
 int nanosleep(const struct timespec *requested_time, struct timespec *remaining);
 int clock_gettime(clockid_t _clk, struct timespec *ts);
 
+int timer_create(clockid_t, struct sigevent *__restrict, timer_t *__restrict);
+int timer_delete(timer_t);
+int timer_settime(timer_t, int, const struct itimerspec *__restrict, struct itimerspec *__restrict);
+int timer_gettime(timer_t, struct itimerspec *);
+int timer_getoverrun(timer_t);
+
 #endif // __TIME_H__
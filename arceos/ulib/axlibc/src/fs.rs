@@ -1,7 +1,8 @@
-use core::ffi::{c_char, c_int};
+use core::ffi::{c_char, c_int, c_ulong, c_void};
 
 use arceos_posix_api::{
-    sys_fstat, sys_getcwd, sys_lseek, sys_lstat, sys_open, sys_rename, sys_stat,
+    sys_fstat, sys_getcwd, sys_link, sys_lseek, sys_lstat, sys_mount, sys_open, sys_readlink,
+    sys_rename, sys_stat, sys_symlink, sys_umount2,
 };
 
 use crate::{ctypes, utils::e};
@@ -51,6 +52,34 @@ pub unsafe extern "C" fn lstat(path: *const c_char, buf: *mut ctypes::stat) -> c
     e(sys_lstat(path, buf) as _)
 }
 
+/// Read the target of the symbolic link `path` into `buf`.
+///
+/// Return the number of bytes placed in `buf`.
+#[no_mangle]
+pub unsafe extern "C" fn readlink(
+    path: *const c_char,
+    buf: *mut c_char,
+    bufsize: usize,
+) -> ctypes::ssize_t {
+    e(sys_readlink(path, buf, bufsize) as _) as _
+}
+
+/// Create a symbolic link `link` which contains the string `target`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[no_mangle]
+pub unsafe extern "C" fn symlink(target: *const c_char, link: *const c_char) -> c_int {
+    e(sys_symlink(target, link))
+}
+
+/// Create a new hard link `new` to the existing file `old`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[no_mangle]
+pub unsafe extern "C" fn link(old: *const c_char, new: *const c_char) -> c_int {
+    e(sys_link(old, new))
+}
+
 /// Get the path of the current directory.
 #[no_mangle]
 pub unsafe extern "C" fn getcwd(buf: *mut c_char, size: usize) -> *mut c_char {
@@ -65,3 +94,33 @@ pub unsafe extern "C" fn getcwd(buf: *mut c_char, size: usize) -> *mut c_char {
 pub unsafe extern "C" fn rename(old: *const c_char, new: *const c_char) -> c_int {
     e(sys_rename(old, new))
 }
+
+/// Mount the filesystem `fstype` on `source` at the directory `target`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[no_mangle]
+pub unsafe extern "C" fn mount(
+    source: *const c_char,
+    target: *const c_char,
+    fstype: *const c_char,
+    flags: c_ulong,
+    data: *const c_void,
+) -> c_int {
+    e(sys_mount(source, target, fstype, flags, data))
+}
+
+/// Unmount the filesystem mounted at `target`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[no_mangle]
+pub unsafe extern "C" fn umount(target: *const c_char) -> c_int {
+    e(sys_umount2(target, 0))
+}
+
+/// Unmount the filesystem mounted at `target` with `flags`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[no_mangle]
+pub unsafe extern "C" fn umount2(target: *const c_char, flags: c_int) -> c_int {
+    e(sys_umount2(target, flags))
+}
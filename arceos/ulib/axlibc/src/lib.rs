@@ -74,6 +74,7 @@ mod mktime;
 mod rand;
 mod resource;
 mod setjmp;
+mod signal;
 mod sys;
 mod time;
 mod unistd;
@@ -87,6 +88,7 @@ pub use self::mktime::mktime;
 pub use self::rand::{rand, random, srand};
 pub use self::resource::{getrlimit, setrlimit};
 pub use self::setjmp::{longjmp, setjmp};
+pub use self::signal::sigaction;
 pub use self::sys::sysconf;
 pub use self::time::{clock_gettime, nanosleep};
 pub use self::unistd::{abort, exit, getpid};
@@ -100,7 +102,7 @@ pub use self::strftime::strftime;
 pub use self::fd_ops::{ax_fcntl, close, dup, dup2, dup3};
 
 #[cfg(feature = "fs")]
-pub use self::fs::{ax_open, fstat, getcwd, lseek, lstat, rename, stat};
+pub use self::fs::{ax_open, fstat, getcwd, lseek, lstat, mount, rename, stat, umount, umount2};
 
 #[cfg(feature = "net")]
 pub use self::net::{
@@ -108,10 +110,30 @@ pub use self::net::{
     recvfrom, send, sendto, shutdown, socket,
 };
 
+#[cfg(all(feature = "multitask", feature = "irq"))]
+pub use self::pthread::pthread_cond_timedwait;
+#[cfg(feature = "multitask")]
+pub use self::pthread::{
+    pthread_barrier_destroy, pthread_barrier_init, pthread_barrier_wait, pthread_cond_broadcast,
+    pthread_cond_destroy, pthread_cond_init, pthread_cond_signal, pthread_cond_wait,
+};
 #[cfg(feature = "multitask")]
 pub use self::pthread::{pthread_create, pthread_exit, pthread_join, pthread_self};
 #[cfg(feature = "multitask")]
 pub use self::pthread::{pthread_mutex_init, pthread_mutex_lock, pthread_mutex_unlock};
+#[cfg(feature = "multitask")]
+pub use self::pthread::{
+    pthread_rwlock_destroy, pthread_rwlock_init, pthread_rwlock_rdlock, pthread_rwlock_tryrdlock,
+    pthread_rwlock_trywrlock, pthread_rwlock_unlock, pthread_rwlock_wrlock,
+};
+
+#[cfg(all(feature = "multitask", feature = "irq"))]
+pub use self::time::{
+    getitimer, setitimer, timer_create, timer_delete, timer_getoverrun, timer_gettime,
+    timer_settime,
+};
+#[cfg(all(feature = "multitask", feature = "irq"))]
+pub use self::unistd::alarm;
 
 #[cfg(feature = "pipe")]
 pub use self::pipe::pipe;
@@ -1,6 +1,6 @@
 use crate::{ctypes, utils::e};
 use arceos_posix_api as api;
-use core::ffi::{c_int, c_void};
+use core::ffi::{c_int, c_uint, c_void};
 
 /// Returns the `pthread` struct of current thread.
 #[no_mangle]
@@ -57,3 +57,137 @@ pub unsafe extern "C" fn pthread_mutex_lock(mutex: *mut ctypes::pthread_mutex_t)
 pub unsafe extern "C" fn pthread_mutex_unlock(mutex: *mut ctypes::pthread_mutex_t) -> c_int {
     e(api::sys_pthread_mutex_unlock(mutex))
 }
+
+/// Converts the result of a `sys_pthread_*` call to a `pthread_*` return
+/// value, which is the error number itself instead of `-1` with `errno`.
+fn pthread_err(ret: c_int) -> c_int {
+    if ret < 0 {
+        -ret
+    } else {
+        ret
+    }
+}
+
+/// Initialize a condition variable.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_cond_init(
+    cond: *mut ctypes::pthread_cond_t,
+    attr: *const ctypes::pthread_condattr_t,
+) -> c_int {
+    pthread_err(api::sys_pthread_cond_init(cond, attr))
+}
+
+/// Destroy a condition variable.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_cond_destroy(cond: *mut ctypes::pthread_cond_t) -> c_int {
+    pthread_err(api::sys_pthread_cond_destroy(cond))
+}
+
+/// Unblock one of the threads blocked on the condition variable.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_cond_signal(cond: *mut ctypes::pthread_cond_t) -> c_int {
+    pthread_err(api::sys_pthread_cond_signal(cond))
+}
+
+/// Unblock all threads blocked on the condition variable.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_cond_broadcast(cond: *mut ctypes::pthread_cond_t) -> c_int {
+    pthread_err(api::sys_pthread_cond_broadcast(cond))
+}
+
+/// Release the locked mutex and block on the condition variable, then lock
+/// the mutex again.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_cond_wait(
+    cond: *mut ctypes::pthread_cond_t,
+    mutex: *mut ctypes::pthread_mutex_t,
+) -> c_int {
+    pthread_err(api::sys_pthread_cond_wait(cond, mutex))
+}
+
+/// Wait on the condition variable until the absolute time `abstime`,
+/// returning `ETIMEDOUT` if it has passed.
+#[cfg(feature = "irq")]
+#[no_mangle]
+pub unsafe extern "C" fn pthread_cond_timedwait(
+    cond: *mut ctypes::pthread_cond_t,
+    mutex: *mut ctypes::pthread_mutex_t,
+    abstime: *const ctypes::timespec,
+) -> c_int {
+    pthread_err(api::sys_pthread_cond_timedwait(cond, mutex, abstime))
+}
+
+/// Initialize a read-write lock.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_rwlock_init(
+    rwlock: *mut ctypes::pthread_rwlock_t,
+    attr: *const ctypes::pthread_rwlockattr_t,
+) -> c_int {
+    pthread_err(api::sys_pthread_rwlock_init(rwlock, attr))
+}
+
+/// Destroy a read-write lock.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_rwlock_destroy(rwlock: *mut ctypes::pthread_rwlock_t) -> c_int {
+    pthread_err(api::sys_pthread_rwlock_destroy(rwlock))
+}
+
+/// Lock the given read-write lock for reading.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_rwlock_rdlock(rwlock: *mut ctypes::pthread_rwlock_t) -> c_int {
+    pthread_err(api::sys_pthread_rwlock_rdlock(rwlock))
+}
+
+/// Try to lock the given read-write lock for reading, returning `EBUSY` if
+/// it is held by a writer.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_rwlock_tryrdlock(rwlock: *mut ctypes::pthread_rwlock_t) -> c_int {
+    pthread_err(api::sys_pthread_rwlock_tryrdlock(rwlock))
+}
+
+/// Lock the given read-write lock for writing.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_rwlock_wrlock(rwlock: *mut ctypes::pthread_rwlock_t) -> c_int {
+    pthread_err(api::sys_pthread_rwlock_wrlock(rwlock))
+}
+
+/// Try to lock the given read-write lock for writing, returning `EBUSY` if it
+/// is held.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_rwlock_trywrlock(rwlock: *mut ctypes::pthread_rwlock_t) -> c_int {
+    pthread_err(api::sys_pthread_rwlock_trywrlock(rwlock))
+}
+
+/// Unlock the given read-write lock.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_rwlock_unlock(rwlock: *mut ctypes::pthread_rwlock_t) -> c_int {
+    pthread_err(api::sys_pthread_rwlock_unlock(rwlock))
+}
+
+/// Initialize a barrier for `count` threads.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_barrier_init(
+    barrier: *mut ctypes::pthread_barrier_t,
+    attr: *const ctypes::pthread_barrierattr_t,
+    count: c_uint,
+) -> c_int {
+    pthread_err(api::sys_pthread_barrier_init(barrier, attr, count))
+}
+
+/// Destroy a barrier.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_barrier_destroy(barrier: *mut ctypes::pthread_barrier_t) -> c_int {
+    pthread_err(api::sys_pthread_barrier_destroy(barrier))
+}
+
+/// Block until enough threads have reached the barrier.
+///
+/// Returns `PTHREAD_BARRIER_SERIAL_THREAD` in one of the threads, and 0 in
+/// the others.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_barrier_wait(barrier: *mut ctypes::pthread_barrier_t) -> c_int {
+    match api::sys_pthread_barrier_wait(barrier) {
+        1 => ctypes::PTHREAD_BARRIER_SERIAL_THREAD,
+        ret => pthread_err(ret),
+    }
+}
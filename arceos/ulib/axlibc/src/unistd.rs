@@ -18,3 +18,10 @@ pub unsafe extern "C" fn abort() -> ! {
 pub unsafe extern "C" fn exit(exit_code: c_int) -> ! {
     sys_exit(exit_code)
 }
+
+/// Set an alarm clock for delivery of a signal
+#[cfg(all(feature = "multitask", feature = "irq"))]
+#[no_mangle]
+pub unsafe extern "C" fn alarm(seconds: core::ffi::c_uint) -> core::ffi::c_uint {
+    arceos_posix_api::sys_alarm(seconds)
+}
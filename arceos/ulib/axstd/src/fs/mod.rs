@@ -42,6 +42,30 @@ pub fn metadata(path: &str) -> io::Result<Metadata> {
     File::open(path)?.metadata()
 }
 
+/// Queries the metadata about a file without following symbolic links.
+pub fn symlink_metadata(path: &str) -> io::Result<Metadata> {
+    arceos_api::fs::ax_symlink_attr(path).map(Metadata)
+}
+
+/// Reads a symbolic link, returning the path that it points to.
+#[cfg(feature = "alloc")]
+pub fn read_link(path: &str) -> io::Result<String> {
+    arceos_api::fs::ax_read_link(path)
+}
+
+/// Creates a new symbolic link at `link`, which points to `original`.
+pub fn symlink(original: &str, link: &str) -> io::Result<()> {
+    arceos_api::fs::ax_symlink(original, link)
+}
+
+/// Creates a new hard link at `link` to the file at `original`.
+///
+/// Both paths must be in the same mounted fs, and `original` cannot be a
+/// directory.
+pub fn hard_link(original: &str, link: &str) -> io::Result<()> {
+    arceos_api::fs::ax_hard_link(original, link)
+}
+
 /// Returns an iterator over the entries within a directory.
 pub fn read_dir(path: &str) -> io::Result<ReadDir> {
     ReadDir::new(path)
@@ -1,5 +1,9 @@
 use crate::io::{self, prelude::*, BufReader};
+// The handles are never poisoned, so the locks without results are used.
+#[cfg(not(feature = "multitask"))]
 use crate::sync::{Mutex, MutexGuard};
+#[cfg(feature = "multitask")]
+use arceos_api::modules::axsync::{Mutex, MutexGuard};
 
 #[cfg(feature = "alloc")]
 use alloc::{string::String, vec::Vec};
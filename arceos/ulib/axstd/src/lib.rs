@@ -15,6 +15,7 @@
 //!     - `fp_simd`: Enable floating point and SIMD support.
 //! - Interrupts:
 //!     - `irq`: Enable interrupt handling support.
+//!     - `tickless`: Stop the periodic timer ticks on idle CPUs.
 //! - Memory
 //!     - `alloc`: Enable dynamic memory allocation.
 //!     - `alloc-tlsf`: Use the TLSF allocator.
@@ -27,9 +28,15 @@
 //!     - `sched_fifo`: Use the FIFO cooperative scheduler.
 //!     - `sched_rr`: Use the Round-robin preemptive scheduler.
 //!     - `sched_cfs`: Use the Completely Fair Scheduler (CFS) preemptive scheduler.
+//!     - `lockdep`: Validate the lock acquisition order and the sleeps in atomic
+//!       contexts at runtime (needs frame pointers).
+//!     - `task_panic`: Exit only the panicking task instead of the system, if
+//!       it is not the main task, poisoning the mutexes it holds.
+//!     - `async`: Enable the async runtime in [`rt`].
 //! - Upperlayer stacks
 //!     - `fs`: Enable file system support.
 //!     - `myfs`: Allow users to define their custom filesystems to override the default.
+//!     - `ext4fs`: Use the ext4 filesystem on the disk as the root filesystem.
 //!     - `net`: Enable networking support.
 //!     - `dns`: Enable DNS lookup support.
 //!     - `display`: Enable graphics support.
@@ -75,3 +82,5 @@ pub mod time;
 pub mod fs;
 #[cfg(feature = "net")]
 pub mod net;
+#[cfg(feature = "async")]
+pub mod rt;
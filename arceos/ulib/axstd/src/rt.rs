@@ -0,0 +1,23 @@
+//! An async runtime, which runs many async tasks on a few threads.
+//!
+//! Futures are run by [`block_on`], along with the tasks [`spawn`]ed by them.
+//! The async sockets and files are in [`net`] and [`fs`].
+
+#[doc(no_inline)]
+pub use arceos_api::modules::axasync::{block_on, spawn, yield_now, JoinHandle};
+#[doc(no_inline)]
+pub use arceos_api::modules::axasync::{sleep, sleep_until, timeout, Sleep, Timeout};
+
+/// Async networking.
+#[cfg(feature = "net")]
+pub mod net {
+    #[doc(no_inline)]
+    pub use arceos_api::modules::axasync::{TcpSocket, UdpSocket};
+}
+
+/// Async filesystem access.
+#[cfg(feature = "fs")]
+pub mod fs {
+    #[doc(no_inline)]
+    pub use arceos_api::modules::axasync::File;
+}
@@ -0,0 +1,165 @@
+//! A condition variable with the interface of `std::sync::Condvar`.
+
+use core::fmt;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use core::time::Duration;
+
+use arceos_api::task::{self as api, AxWaitQueueHandle};
+
+use super::{LockResult, MutexGuard, PoisonError};
+
+/// A condition variable, similar to
+/// [`std::sync::Condvar`](https://doc.rust-lang.org/std/sync/struct.Condvar.html).
+///
+/// The timed waits need the `irq` feature, otherwise the timeouts are ignored.
+pub struct Condvar {
+    wq: AxWaitQueueHandle,
+    /// Incremented on every notification, so that a notification between
+    /// unlocking the mutex and going to sleep is not lost.
+    seq: AtomicUsize,
+}
+
+/// A type indicating whether a timed wait on a condition variable returned
+/// due to a time out or not.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct WaitTimeoutResult(bool);
+
+impl WaitTimeoutResult {
+    /// Returns `true` if the wait was known to have timed out.
+    pub fn timed_out(&self) -> bool {
+        self.0
+    }
+}
+
+impl Condvar {
+    /// Creates a new condition variable.
+    pub const fn new() -> Self {
+        Self {
+            wq: AxWaitQueueHandle::new(),
+            seq: AtomicUsize::new(0),
+        }
+    }
+
+    fn wait_inner<'a, T: ?Sized>(
+        &self,
+        guard: MutexGuard<'a, T>,
+        timeout: Option<Duration>,
+    ) -> (MutexGuard<'a, T>, bool) {
+        let seq = self.seq.load(Ordering::Acquire);
+        let mutex = MutexGuard::mutex(&guard);
+        drop(guard);
+        let timed_out = api::ax_wait_queue_wait(
+            &self.wq,
+            || self.seq.load(Ordering::Acquire) != seq,
+            timeout,
+        );
+        (mutex.lock(), timed_out)
+    }
+
+    /// Blocks the current thread until this condition variable receives a
+    /// notification, with the mutex unlocked in the meantime.
+    pub fn wait<'a, T: ?Sized>(&self, guard: MutexGuard<'a, T>) -> LockResult<MutexGuard<'a, T>> {
+        poison_result(self.wait_inner(guard, None).0)
+    }
+
+    /// Blocks the current thread while `condition` returns `true`.
+    pub fn wait_while<'a, T, F>(
+        &self,
+        mut guard: MutexGuard<'a, T>,
+        mut condition: F,
+    ) -> LockResult<MutexGuard<'a, T>>
+    where
+        T: ?Sized,
+        F: FnMut(&mut T) -> bool,
+    {
+        while condition(&mut *guard) {
+            guard = self.wait_inner(guard, None).0;
+        }
+        poison_result(guard)
+    }
+
+    /// Waits on this condition variable for a notification, timing out after
+    /// the specified duration.
+    pub fn wait_timeout<'a, T: ?Sized>(
+        &self,
+        guard: MutexGuard<'a, T>,
+        dur: Duration,
+    ) -> LockResult<(MutexGuard<'a, T>, WaitTimeoutResult)> {
+        let (guard, timed_out) = self.wait_inner(guard, Some(dur));
+        poison_result((guard, WaitTimeoutResult(timed_out)))
+    }
+
+    /// Waits on this condition variable while `condition` returns `true`,
+    /// timing out after the specified duration.
+    pub fn wait_timeout_while<'a, T, F>(
+        &self,
+        mut guard: MutexGuard<'a, T>,
+        dur: Duration,
+        mut condition: F,
+    ) -> LockResult<(MutexGuard<'a, T>, WaitTimeoutResult)>
+    where
+        T: ?Sized,
+        F: FnMut(&mut T) -> bool,
+    {
+        let deadline = arceos_api::time::ax_wall_time() + dur;
+        while condition(&mut *guard) {
+            let now = arceos_api::time::ax_wall_time();
+            if now >= deadline {
+                return poison_result((guard, WaitTimeoutResult(true)));
+            }
+            guard = self.wait_inner(guard, Some(deadline - now)).0;
+        }
+        poison_result((guard, WaitTimeoutResult(false)))
+    }
+
+    /// Wakes up one blocked thread on this condvar.
+    pub fn notify_one(&self) {
+        self.seq.fetch_add(1, Ordering::Release);
+        api::ax_wait_queue_wake(&self.wq, 1);
+    }
+
+    /// Wakes up all blocked threads on this condvar.
+    pub fn notify_all(&self) {
+        self.seq.fetch_add(1, Ordering::Release);
+        api::ax_wait_queue_wake(&self.wq, u32::MAX);
+    }
+}
+
+/// Returns an error if the mutex of the guard is poisoned, like those of
+/// `std`.
+fn poison_result<'a, T: ?Sized, R: AsGuard<'a, T>>(ret: R) -> LockResult<R> {
+    if MutexGuard::mutex(ret.as_guard()).is_poisoned() {
+        Err(PoisonError::new(ret))
+    } else {
+        Ok(ret)
+    }
+}
+
+/// The results of the waits, which contain the guard.
+trait AsGuard<'a, T: ?Sized> {
+    fn as_guard(&self) -> &MutexGuard<'a, T>;
+}
+
+impl<'a, T: ?Sized> AsGuard<'a, T> for MutexGuard<'a, T> {
+    fn as_guard(&self) -> &MutexGuard<'a, T> {
+        self
+    }
+}
+
+impl<'a, T: ?Sized> AsGuard<'a, T> for (MutexGuard<'a, T>, WaitTimeoutResult) {
+    fn as_guard(&self) -> &MutexGuard<'a, T> {
+        &self.0
+    }
+}
+
+impl Default for Condvar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for Condvar {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Condvar").finish_non_exhaustive()
+    }
+}
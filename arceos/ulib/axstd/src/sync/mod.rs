@@ -7,12 +7,37 @@ pub use core::sync::atomic;
 #[doc(no_inline)]
 pub use alloc::sync::{Arc, Weak};
 
+#[cfg(feature = "multitask")]
+mod condvar;
 #[cfg(feature = "multitask")]
 mod mutex;
+#[cfg(feature = "multitask")]
+mod poison;
+#[cfg(feature = "multitask")]
+mod rwlock;
 
+#[cfg(feature = "multitask")]
+#[doc(cfg(feature = "multitask"))]
+pub use self::condvar::{Condvar, WaitTimeoutResult};
 #[cfg(feature = "multitask")]
 #[doc(cfg(feature = "multitask"))]
 pub use self::mutex::{Mutex, MutexGuard};
+#[cfg(feature = "multitask")]
+#[doc(cfg(feature = "multitask"))]
+pub use self::poison::{LockResult, PoisonError, TryLockError, TryLockResult};
+#[cfg(feature = "multitask")]
+#[doc(cfg(feature = "multitask"))]
+pub use self::rwlock::{RwLock, RwLockReadGuard, RwLockUpgradableReadGuard, RwLockWriteGuard};
+
+// These have the same interface as those in `std::sync`, or no stable
+// counterpart there (`ReentrantMutex`, `Semaphore`), so they are exported from
+// `axsync` directly.
+#[cfg(feature = "multitask")]
+#[doc(cfg(feature = "multitask"))]
+pub use arceos_api::modules::axsync::{
+    Barrier, BarrierWaitResult, Once, OnceLock, ReentrantMutex, ReentrantMutexGuard, Semaphore,
+    SemaphoreGuard,
+};
 
 #[cfg(not(feature = "multitask"))]
 #[doc(cfg(not(feature = "multitask")))]
@@ -1,120 +1,95 @@
-//! A naïve sleeping mutex.
+//! A mutex with the interface of `std::sync::Mutex`.
 
-use core::cell::UnsafeCell;
 use core::fmt;
-use core::ops::{Deref, DerefMut};
-use core::sync::atomic::{AtomicU64, Ordering};
 
-use arceos_api::task::{self as api, AxWaitQueueHandle};
+use arceos_api::modules::axsync;
+
+use super::{LockResult, PoisonError, TryLockError, TryLockResult};
+
+pub use axsync::MutexGuard;
 
 /// A mutual exclusion primitive useful for protecting shared data, similar to
 /// [`std::sync::Mutex`](https://doc.rust-lang.org/std/sync/struct.Mutex.html).
 ///
-/// When the mutex is locked, the current task will block and be put into the
-/// wait queue. When the mutex is unlocked, all tasks waiting on the queue
-/// will be woken up.
-pub struct Mutex<T: ?Sized> {
-    wq: AxWaitQueueHandle,
-    owner_id: AtomicU64,
-    data: UnsafeCell<T>,
-}
-
-/// A guard that provides mutable data access.
-///
-/// When the guard falls out of scope it will release the lock.
-pub struct MutexGuard<'a, T: ?Sized + 'a> {
-    lock: &'a Mutex<T>,
-    data: *mut T,
-}
-
-// Same unsafe impls as `std::sync::Mutex`
-unsafe impl<T: ?Sized + Send> Sync for Mutex<T> {}
-unsafe impl<T: ?Sized + Send> Send for Mutex<T> {}
+/// The mutex is poisoned if a thread exits while holding it, e.g., on a panic
+/// with the `task_panic` feature. Locking it again by the thread holding it
+/// panics rather than deadlocks.
+pub struct Mutex<T: ?Sized>(axsync::Mutex<T>);
 
 impl<T> Mutex<T> {
-    /// Creates a new [`Mutex`] wrapping the supplied data.
+    /// Creates a new [`Mutex`] in an unlocked state ready for use.
     #[inline(always)]
-    pub const fn new(data: T) -> Self {
-        Self {
-            wq: AxWaitQueueHandle::new(),
-            owner_id: AtomicU64::new(0),
-            data: UnsafeCell::new(data),
-        }
+    pub const fn new(t: T) -> Self {
+        Self(axsync::Mutex::new(t))
     }
 
-    /// Consumes this [`Mutex`] and unwraps the underlying data.
-    #[inline(always)]
-    pub fn into_inner(self) -> T {
-        // We know statically that there are no outstanding references to
-        // `self` so there's no need to lock.
-        let Mutex { data, .. } = self;
-        data.into_inner()
+    /// Consumes this [`Mutex`], returning the underlying data.
+    pub fn into_inner(self) -> LockResult<T> {
+        let poisoned = self.0.is_poisoned();
+        let data = self.0.into_inner();
+        if poisoned {
+            Err(PoisonError::new(data))
+        } else {
+            Ok(data)
+        }
     }
 }
 
 impl<T: ?Sized> Mutex<T> {
-    /// Returns `true` if the lock is currently held.
+    fn poison_result<G>(&self, guard: G) -> LockResult<G> {
+        if self.0.is_poisoned() {
+            Err(PoisonError::new(guard))
+        } else {
+            Ok(guard)
+        }
+    }
+
+    /// Acquires the [`Mutex`], blocking the current thread until it is able
+    /// to do so.
+    pub fn lock(&self) -> LockResult<MutexGuard<'_, T>> {
+        self.poison_result(self.0.lock())
+    }
+
+    /// Attempts to acquire this [`Mutex`] without blocking.
+    pub fn try_lock(&self) -> TryLockResult<MutexGuard<'_, T>> {
+        match self.0.try_lock() {
+            Some(guard) => Ok(self.poison_result(guard)?),
+            None => Err(TryLockError::WouldBlock),
+        }
+    }
+
+    /// Attempts to acquire this [`Mutex`], blocking the current thread for at
+    /// most the given duration.
     ///
-    /// # Safety
+    /// It has no counterpart in `std`, and needs the `irq` feature.
+    #[cfg(feature = "irq")]
+    pub fn try_lock_for(&self, timeout: core::time::Duration) -> TryLockResult<MutexGuard<'_, T>> {
+        match self.0.try_lock_for(timeout) {
+            Some(guard) => Ok(self.poison_result(guard)?),
+            None => Err(TryLockError::WouldBlock),
+        }
+    }
+
+    /// Returns `true` if the lock is currently held.
     ///
-    /// This function provides no synchronization guarantees and so its result should be considered 'out of date'
-    /// the instant it is called. Do not use it for synchronization purposes. However, it may be useful as a heuristic.
+    /// The result is only a heuristic, as it may be out of date the instant
+    /// it is returned.
     #[inline(always)]
     pub fn is_locked(&self) -> bool {
-        self.owner_id.load(Ordering::Relaxed) != 0
+        self.0.is_locked()
     }
 
-    /// Locks the [`Mutex`] and returns a guard that permits access to the inner data.
-    ///
-    /// The returned value may be dereferenced for data access
-    /// and the lock will be dropped when the guard falls out of scope.
-    pub fn lock(&self) -> MutexGuard<T> {
-        let current_id = api::ax_current_task_id();
-        loop {
-            // Can fail to lock even if the spinlock is not locked. May be more efficient than `try_lock`
-            // when called in a loop.
-            match self.owner_id.compare_exchange_weak(
-                0,
-                current_id,
-                Ordering::Acquire,
-                Ordering::Relaxed,
-            ) {
-                Ok(_) => break,
-                Err(owner_id) => {
-                    assert_ne!(
-                        owner_id, current_id,
-                        "Thread({}) tried to acquire mutex it already owns.",
-                        current_id,
-                    );
-                    // Wait until the lock looks unlocked before retrying
-                    api::ax_wait_queue_wait(&self.wq, || !self.is_locked(), None);
-                }
-            }
-        }
-        MutexGuard {
-            lock: self,
-            data: unsafe { &mut *self.data.get() },
-        }
+    /// Determines whether the mutex is poisoned, i.e., a thread has exited
+    /// while holding it.
+    #[inline(always)]
+    pub fn is_poisoned(&self) -> bool {
+        self.0.is_poisoned()
     }
 
-    /// Try to lock this [`Mutex`], returning a lock guard if successful.
+    /// Clears the poisoned state from the mutex.
     #[inline(always)]
-    pub fn try_lock(&self) -> Option<MutexGuard<T>> {
-        let current_id = api::ax_current_task_id();
-        // The reason for using a strong compare_exchange is explained here:
-        // https://github.com/Amanieu/parking_lot/pull/207#issuecomment-575869107
-        if self
-            .owner_id
-            .compare_exchange(0, current_id, Ordering::Acquire, Ordering::Relaxed)
-            .is_ok()
-        {
-            Some(MutexGuard {
-                lock: self,
-                data: unsafe { &mut *self.data.get() },
-            })
-        } else {
-            None
-        }
+    pub fn clear_poison(&self) {
+        self.0.clear_poison();
     }
 
     /// Force unlock the [`Mutex`].
@@ -125,74 +100,35 @@ impl<T: ?Sized> Mutex<T> {
     /// thread. However, this can be useful in some instances for exposing
     /// the lock to FFI that doesn’t know how to deal with RAII.
     pub unsafe fn force_unlock(&self) {
-        let owner_id = self.owner_id.swap(0, Ordering::Release);
-        let current_id = api::ax_current_task_id();
-        assert_eq!(
-            owner_id, current_id,
-            "Thread({}) tried to release mutex it doesn't own",
-            current_id,
-        );
-        // wake up one waiting thread.
-        api::ax_wait_queue_wake(&self.wq, 1);
+        self.0.force_unlock();
     }
 
     /// Returns a mutable reference to the underlying data.
-    ///
-    /// Since this call borrows the [`Mutex`] mutably, and a mutable reference is guaranteed to be exclusive in
-    /// Rust, no actual locking needs to take place -- the mutable borrow statically guarantees no locks exist. As
-    /// such, this is a 'zero-cost' operation.
-    #[inline(always)]
-    pub fn get_mut(&mut self) -> &mut T {
-        // We know statically that there are no other references to `self`, so
-        // there's no need to lock the inner mutex.
-        unsafe { &mut *self.data.get() }
-    }
-}
-
-impl<T: ?Sized + Default> Default for Mutex<T> {
-    #[inline(always)]
-    fn default() -> Self {
-        Self::new(Default::default())
-    }
-}
-
-impl<T: ?Sized + fmt::Debug> fmt::Debug for Mutex<T> {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self.try_lock() {
-            Some(guard) => write!(f, "Mutex {{ data: ")
-                .and_then(|()| (*guard).fmt(f))
-                .and_then(|()| write!(f, "}}")),
-            None => write!(f, "Mutex {{ <locked> }}"),
+    pub fn get_mut(&mut self) -> LockResult<&mut T> {
+        let poisoned = self.0.is_poisoned();
+        let data = self.0.get_mut();
+        if poisoned {
+            Err(PoisonError::new(data))
+        } else {
+            Ok(data)
         }
     }
 }
 
-impl<'a, T: ?Sized> Deref for MutexGuard<'a, T> {
-    type Target = T;
-    #[inline(always)]
-    fn deref(&self) -> &T {
-        // We know statically that only we are referencing data
-        unsafe { &*self.data }
+impl<T: Default> Default for Mutex<T> {
+    fn default() -> Self {
+        Self::new(T::default())
     }
 }
 
-impl<'a, T: ?Sized> DerefMut for MutexGuard<'a, T> {
-    #[inline(always)]
-    fn deref_mut(&mut self) -> &mut T {
-        // We know statically that only we are referencing data
-        unsafe { &mut *self.data }
+impl<T> From<T> for Mutex<T> {
+    fn from(t: T) -> Self {
+        Self::new(t)
     }
 }
 
-impl<'a, T: ?Sized + fmt::Debug> fmt::Debug for MutexGuard<'a, T> {
+impl<T: ?Sized + fmt::Debug> fmt::Debug for Mutex<T> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        fmt::Debug::fmt(&**self, f)
-    }
-}
-
-impl<'a, T: ?Sized> Drop for MutexGuard<'a, T> {
-    /// The dropping of the [`MutexGuard`] will release the lock it was created from.
-    fn drop(&mut self) {
-        unsafe { self.lock.force_unlock() }
+        fmt::Debug::fmt(&self.0, f)
     }
 }
@@ -0,0 +1,91 @@
+//! Lock results compatible with those of `std::sync`.
+//!
+//! A panicking thread does not unwind, so only [`Mutex`] is poisoned, when a
+//! thread exits while holding it, e.g., on a panic with the `task_panic`
+//! feature. The other locks are never poisoned, but the results are kept for
+//! the code written against `std::sync`.
+//!
+//! [`Mutex`]: super::Mutex
+
+use core::fmt;
+
+/// A type of error which can be returned whenever a lock is acquired, similar
+/// to [`std::sync::PoisonError`](https://doc.rust-lang.org/std/sync/struct.PoisonError.html).
+pub struct PoisonError<T> {
+    guard: T,
+}
+
+/// An enumeration of possible errors of the `try_lock` methods, similar to
+/// [`std::sync::TryLockError`](https://doc.rust-lang.org/std/sync/enum.TryLockError.html).
+pub enum TryLockError<T> {
+    /// The lock could not be acquired because another task panicked while
+    /// holding it.
+    Poisoned(PoisonError<T>),
+    /// The lock could not be acquired at this time because the operation
+    /// would otherwise block.
+    WouldBlock,
+}
+
+/// The result of the blocking locking methods.
+pub type LockResult<Guard> = Result<Guard, PoisonError<Guard>>;
+
+/// The result of the non-blocking locking methods.
+pub type TryLockResult<Guard> = Result<Guard, TryLockError<Guard>>;
+
+impl<T> PoisonError<T> {
+    /// Creates a [`PoisonError`] wrapping the guard.
+    pub fn new(guard: T) -> Self {
+        Self { guard }
+    }
+
+    /// Consumes this error, returning the underlying guard.
+    pub fn into_inner(self) -> T {
+        self.guard
+    }
+
+    /// Returns a reference to the underlying guard.
+    pub fn get_ref(&self) -> &T {
+        &self.guard
+    }
+
+    /// Returns a mutable reference to the underlying guard.
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.guard
+    }
+}
+
+impl<T> fmt::Debug for PoisonError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PoisonError").finish_non_exhaustive()
+    }
+}
+
+impl<T> fmt::Display for PoisonError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("poisoned lock: another task failed inside")
+    }
+}
+
+impl<T> From<PoisonError<T>> for TryLockError<T> {
+    fn from(err: PoisonError<T>) -> Self {
+        Self::Poisoned(err)
+    }
+}
+
+impl<T> fmt::Debug for TryLockError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Poisoned(err) => fmt::Debug::fmt(err, f),
+            Self::WouldBlock => f.write_str("WouldBlock"),
+        }
+    }
+}
+
+impl<T> fmt::Display for TryLockError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Poisoned(err) => fmt::Display::fmt(err, f),
+            Self::WouldBlock => f.write_str("try_lock failed because the operation would block"),
+        }
+    }
+}
@@ -0,0 +1,96 @@
+//! A reader-writer lock with the interface of `std::sync::RwLock`.
+
+use core::fmt;
+
+use arceos_api::modules::axsync;
+
+use super::{LockResult, TryLockError, TryLockResult};
+
+pub use axsync::{RwLockReadGuard, RwLockUpgradableReadGuard, RwLockWriteGuard};
+
+/// A reader-writer lock, similar to
+/// [`std::sync::RwLock`](https://doc.rust-lang.org/std/sync/struct.RwLock.html).
+///
+/// Writers are preferred: once a writer is waiting, new readers block until
+/// it has released the lock. Besides the methods of `std`, it can be locked
+/// by an upgradable reader, see [`RwLock::upgradable_read`].
+pub struct RwLock<T: ?Sized>(axsync::RwLock<T>);
+
+impl<T> RwLock<T> {
+    /// Creates a new instance of an [`RwLock`] which is unlocked.
+    pub const fn new(t: T) -> Self {
+        Self(axsync::RwLock::new(t))
+    }
+
+    /// Consumes this [`RwLock`], returning the underlying data.
+    pub fn into_inner(self) -> LockResult<T> {
+        Ok(self.0.into_inner())
+    }
+}
+
+impl<T: ?Sized> RwLock<T> {
+    /// Locks this [`RwLock`] with shared read access, blocking the current
+    /// thread until it can be acquired.
+    pub fn read(&self) -> LockResult<RwLockReadGuard<'_, T>> {
+        Ok(self.0.read())
+    }
+
+    /// Attempts to acquire this [`RwLock`] with shared read access without
+    /// blocking.
+    pub fn try_read(&self) -> TryLockResult<RwLockReadGuard<'_, T>> {
+        self.0.try_read().ok_or(TryLockError::WouldBlock)
+    }
+
+    /// Locks this [`RwLock`] with exclusive write access, blocking the current
+    /// thread until it can be acquired.
+    pub fn write(&self) -> LockResult<RwLockWriteGuard<'_, T>> {
+        Ok(self.0.write())
+    }
+
+    /// Attempts to lock this [`RwLock`] with exclusive write access without
+    /// blocking.
+    pub fn try_write(&self) -> TryLockResult<RwLockWriteGuard<'_, T>> {
+        self.0.try_write().ok_or(TryLockError::WouldBlock)
+    }
+
+    /// Locks this [`RwLock`] with upgradable read access, blocking the current
+    /// thread until it can be acquired.
+    ///
+    /// Only one upgradable reader can hold the lock at a time, along with the
+    /// other readers, and it can be upgraded to write access without
+    /// releasing the lock.
+    pub fn upgradable_read(&self) -> LockResult<RwLockUpgradableReadGuard<'_, T>> {
+        Ok(self.0.upgradable_read())
+    }
+
+    /// Determines whether the lock is poisoned, which is never the case.
+    pub fn is_poisoned(&self) -> bool {
+        false
+    }
+
+    /// Clears the poisoned state from the lock, which does nothing.
+    pub fn clear_poison(&self) {}
+
+    /// Returns a mutable reference to the underlying data.
+    pub fn get_mut(&mut self) -> LockResult<&mut T> {
+        Ok(self.0.get_mut())
+    }
+}
+
+impl<T: Default> Default for RwLock<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T> From<T> for RwLock<T> {
+    fn from(t: T) -> Self {
+        Self::new(t)
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for RwLock<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&self.0, f)
+    }
+}
@@ -95,3 +95,62 @@ impl Sub<Instant> for Instant {
         self.duration_since(other)
     }
 }
+
+/// A timer that runs a callback at a deadline, and optionally every interval
+/// after that.
+///
+/// The callbacks run in a dedicated timer thread, one after another, so they
+/// should be short. Dropping the timer does not cancel it.
+#[cfg(all(feature = "multitask", feature = "irq"))]
+pub struct Timer(arceos_api::time::AxTimerHandle);
+
+#[cfg(all(feature = "multitask", feature = "irq"))]
+impl Timer {
+    /// Runs the callback once after the duration.
+    pub fn once<F>(dur: Duration, callback: F) -> Timer
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        Self::at(Instant::now() + dur, callback)
+    }
+
+    /// Runs the callback once at the deadline.
+    pub fn at<F>(deadline: Instant, callback: F) -> Timer
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        Timer(arceos_api::time::ax_set_timer(deadline.0, callback))
+    }
+
+    /// Runs the callback every `interval`, starting after the first interval.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the `interval` is zero.
+    pub fn periodic<F>(interval: Duration, callback: F) -> Timer
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        let first = Instant::now() + interval;
+        Timer(arceos_api::time::ax_set_periodic_timer(
+            first.0, interval, callback,
+        ))
+    }
+
+    /// Cancels the timer. Returns whether it was still active.
+    pub fn cancel(&self) -> bool {
+        self.0.cancel()
+    }
+
+    /// Returns when the callback runs next, or `None` if the timer has been
+    /// cancelled or expired.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.0.deadline().map(Instant)
+    }
+
+    /// Returns the number of runs skipped before the last one, as the callback
+    /// or the timer interrupts were late.
+    pub fn overrun(&self) -> usize {
+        self.0.overrun()
+    }
+}
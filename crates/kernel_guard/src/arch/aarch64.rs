@@ -12,3 +12,19 @@ pub fn local_irq_save_and_disable() -> usize {
 pub fn local_irq_restore(flags: usize) {
     unsafe { asm!("msr daif, {}", in(reg) flags) };
 }
+
+/// Returns the frame pointer of the current function.
+#[cfg(feature = "lockdep")]
+#[inline(always)]
+pub fn frame_pointer() -> usize {
+    let fp: usize;
+    unsafe { asm!("mov {}, x29", out(reg) fp) };
+    fp
+}
+
+/// Offset from the frame pointer to the saved frame pointer of the caller.
+#[cfg(feature = "lockdep")]
+pub const FRAME_FP_OFFSET: isize = 0;
+/// Offset from the frame pointer to the return address.
+#[cfg(feature = "lockdep")]
+pub const FRAME_RA_OFFSET: isize = 8;
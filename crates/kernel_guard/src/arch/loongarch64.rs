@@ -15,3 +15,19 @@ pub fn local_irq_restore(flags: usize) {
     // restore the `IE` bit
     unsafe { asm!("csrxchg {}, {}, 0x0", in(reg) flags, in(reg) IE_MASK) };
 }
+
+/// Returns the frame pointer of the current function.
+#[cfg(feature = "lockdep")]
+#[inline(always)]
+pub fn frame_pointer() -> usize {
+    let fp: usize;
+    unsafe { asm!("move {}, $fp", out(reg) fp) };
+    fp
+}
+
+/// Offset from the frame pointer to the saved frame pointer of the caller.
+#[cfg(feature = "lockdep")]
+pub const FRAME_FP_OFFSET: isize = -16;
+/// Offset from the frame pointer to the return address.
+#[cfg(feature = "lockdep")]
+pub const FRAME_RA_OFFSET: isize = -8;
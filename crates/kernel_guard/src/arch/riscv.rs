@@ -18,3 +18,19 @@ pub fn local_irq_restore(flags: usize) {
     // restore the `SIE` bit
     unsafe { asm!("csrrs x0, sstatus, {}", in(reg) flags) };
 }
+
+/// Returns the frame pointer of the current function.
+#[cfg(feature = "lockdep")]
+#[inline(always)]
+pub fn frame_pointer() -> usize {
+    let fp: usize;
+    unsafe { asm!("mv {}, s0", out(reg) fp) };
+    fp
+}
+
+/// Offset from the frame pointer to the saved frame pointer of the caller.
+#[cfg(feature = "lockdep")]
+pub const FRAME_FP_OFFSET: isize = -2 * core::mem::size_of::<usize>() as isize;
+/// Offset from the frame pointer to the return address.
+#[cfg(feature = "lockdep")]
+pub const FRAME_RA_OFFSET: isize = -(core::mem::size_of::<usize>() as isize);
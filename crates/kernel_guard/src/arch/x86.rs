@@ -18,3 +18,26 @@ pub fn local_irq_restore(flags: usize) {
         unsafe { asm!("cli") };
     }
 }
+
+/// Returns the frame pointer of the current function.
+#[cfg(feature = "lockdep")]
+#[inline(always)]
+pub fn frame_pointer() -> usize {
+    let fp: usize;
+    #[cfg(target_arch = "x86_64")]
+    unsafe {
+        asm!("mov {}, rbp", out(reg) fp)
+    };
+    #[cfg(target_arch = "x86")]
+    unsafe {
+        asm!("mov {}, ebp", out(reg) fp)
+    };
+    fp
+}
+
+/// Offset from the frame pointer to the saved frame pointer of the caller.
+#[cfg(feature = "lockdep")]
+pub const FRAME_FP_OFFSET: isize = 0;
+/// Offset from the frame pointer to the return address.
+#[cfg(feature = "lockdep")]
+pub const FRAME_RA_OFFSET: isize = core::mem::size_of::<usize>() as isize;
@@ -24,6 +24,9 @@
 //!    need to implement the [`KernelGuardIf`] trait in other crates. Otherwise
 //!    the preemption enable/disable operations will be no-ops. This feature is
 //!    disabled by default.
+//! - `lockdep`: Report the guards to a lock dependency validator, which must
+//!   implement the [`lockdep::LockdepIf`] trait in other crates. See the
+//!   [`lockdep`] module.
 //!
 //! # Examples
 //!
@@ -55,6 +58,12 @@
 
 mod arch;
 
+#[cfg(feature = "lockdep")]
+pub mod lockdep;
+
+#[cfg(feature = "lockdep")]
+use self::lockdep::GuardKind;
+
 /// Low-level interfaces that must be implemented by the crate user.
 #[crate_interface::def_interface]
 pub trait KernelGuardIf {
@@ -112,8 +121,14 @@ cfg_if::cfg_if! {
 
 impl BaseGuard for NoOp {
     type State = ();
-    fn acquire() -> Self::State {}
-    fn release(_state: Self::State) {}
+    fn acquire() -> Self::State {
+        #[cfg(feature = "lockdep")]
+        lockdep::acquired(GuardKind::NoOp);
+    }
+    fn release(_state: Self::State) {
+        #[cfg(feature = "lockdep")]
+        lockdep::releasing(GuardKind::NoOp);
+    }
 }
 
 impl NoOp {
@@ -136,11 +151,16 @@ mod imp {
 
         #[inline]
         fn acquire() -> Self::State {
-            super::arch::local_irq_save_and_disable()
+            let state = super::arch::local_irq_save_and_disable();
+            #[cfg(feature = "lockdep")]
+            lockdep::acquired(GuardKind::IrqSave);
+            state
         }
 
         #[inline]
         fn release(state: Self::State) {
+            #[cfg(feature = "lockdep")]
+            lockdep::releasing(GuardKind::IrqSave);
             // restore IRQ states
             super::arch::local_irq_restore(state);
         }
@@ -152,8 +172,12 @@ mod imp {
             // disable preempt
             #[cfg(feature = "preempt")]
             crate_interface::call_interface!(KernelGuardIf::disable_preempt);
+            #[cfg(feature = "lockdep")]
+            lockdep::acquired(GuardKind::NoPreempt);
         }
         fn release(_state: Self::State) {
+            #[cfg(feature = "lockdep")]
+            lockdep::releasing(GuardKind::NoPreempt);
             // enable preempt
             #[cfg(feature = "preempt")]
             crate_interface::call_interface!(KernelGuardIf::enable_preempt);
@@ -167,9 +191,14 @@ mod imp {
             #[cfg(feature = "preempt")]
             crate_interface::call_interface!(KernelGuardIf::disable_preempt);
             // disable IRQs and save IRQ states
-            super::arch::local_irq_save_and_disable()
+            let state = super::arch::local_irq_save_and_disable();
+            #[cfg(feature = "lockdep")]
+            lockdep::acquired(GuardKind::NoPreemptIrqSave);
+            state
         }
         fn release(state: Self::State) {
+            #[cfg(feature = "lockdep")]
+            lockdep::releasing(GuardKind::NoPreemptIrqSave);
             // restore IRQ states
             super::arch::local_irq_restore(state);
             // enable preempt
@@ -0,0 +1,196 @@
+//! Hooks for a lock dependency validator, enabled by the `lockdep` feature.
+//!
+//! Every guard is reported when it is acquired and released. A spin lock
+//! built on the guards (e.g., those in the `kspin` crate) holds a
+//! [`LockdepMap`], and reports itself by [`spin_acquired`] after acquiring
+//! its guard, and by [`spin_releasing`] before releasing it, so that the
+//! validator knows which lock each guard belongs to.
+//!
+//! The crate user must implement the [`LockdepIf`] trait using
+//! [`crate_interface::impl_interface`] if the feature is enabled.
+//!
+//! [`Frames`] follows the frame pointers, so the code should be compiled with
+//! them (e.g., with `-C force-frame-pointers=yes`) for the backtraces.
+
+use core::mem::align_of;
+use core::panic::Location;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::arch::{frame_pointer, FRAME_FP_OFFSET, FRAME_RA_OFFSET};
+
+/// The largest stack frame that [`Frames`] steps over. A larger distance
+/// between two frame pointers means that the chain is broken.
+const MAX_FRAME_SIZE: usize = 0x10_0000;
+
+/// Types of the guards.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GuardKind {
+    /// [`NoOp`](crate::NoOp), e.g., of `kspin::SpinRaw`.
+    NoOp,
+    /// [`IrqSave`](crate::IrqSave).
+    IrqSave,
+    /// [`NoPreempt`](crate::NoPreempt), e.g., of `kspin::SpinNoPreempt`.
+    NoPreempt,
+    /// [`NoPreemptIrqSave`](crate::NoPreemptIrqSave), e.g., of
+    /// `kspin::SpinNoIrq`.
+    NoPreemptIrqSave,
+}
+
+impl GuardKind {
+    /// Whether the guard disables local IRQs.
+    pub const fn disables_irqs(self) -> bool {
+        matches!(self, Self::IrqSave | Self::NoPreemptIrqSave)
+    }
+}
+
+/// The lock class of a lock, assigned by the validator on its first
+/// acquisition.
+///
+/// It should be a field of the lock, so that the validator forgets the class
+/// when the lock is dropped. The lock is identified by the address of the
+/// map, so that two places locking the same lock share the class.
+pub struct LockdepMap {
+    /// Index of the class plus one, or 0 if it has not been assigned.
+    class: AtomicUsize,
+}
+
+impl LockdepMap {
+    /// Creates a new map, without a class.
+    pub const fn new() -> Self {
+        Self {
+            class: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns the key that identifies the lock.
+    pub fn key(&self) -> usize {
+        self as *const _ as usize
+    }
+
+    /// Returns the assigned class.
+    pub fn class(&self) -> Option<usize> {
+        self.class.load(Ordering::Relaxed).checked_sub(1)
+    }
+
+    /// Assigns the class.
+    pub fn set_class(&self, class: usize) {
+        self.class.store(class + 1, Ordering::Relaxed);
+    }
+
+    /// Removes the class, and returns it.
+    pub fn take_class(&self) -> Option<usize> {
+        self.class.swap(0, Ordering::Relaxed).checked_sub(1)
+    }
+}
+
+impl Default for LockdepMap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for LockdepMap {
+    fn drop(&mut self) {
+        if *self.class.get_mut() != 0 {
+            crate_interface::call_interface!(LockdepIf::map_dropped(self));
+        }
+    }
+}
+
+/// Low-level interfaces of the lock dependency validator.
+#[crate_interface::def_interface]
+pub trait LockdepIf {
+    /// Called when a guard of `kind` has entered its critical section, i.e.,
+    /// with IRQs already disabled by the guard if it does so.
+    fn guard_acquired(kind: GuardKind);
+
+    /// Called when a guard of `kind` is going to leave its critical section.
+    fn guard_releasing(kind: GuardKind);
+
+    /// Called when the spin lock of `map` is taken at `site`, right after
+    /// its guard is acquired.
+    ///
+    /// `trylock` means that it is taken without spinning.
+    fn spin_acquired(map: &LockdepMap, site: &'static Location<'static>, trylock: bool);
+
+    /// Called when the spin lock of `map` is going to be released, before
+    /// its guard is released.
+    fn spin_releasing(map: &LockdepMap);
+
+    /// Called when a map with a class is dropped.
+    fn map_dropped(map: &LockdepMap);
+}
+
+/// An iterator over the return addresses on the stack, from the caller of the
+/// function where it is created, by following the frame pointers.
+pub struct Frames {
+    fp: usize,
+}
+
+impl Frames {
+    /// Starts from the frame of the current function.
+    #[inline(always)]
+    pub fn new() -> Self {
+        Self {
+            fp: frame_pointer(),
+        }
+    }
+}
+
+impl Default for Frames {
+    #[inline(always)]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Iterator for Frames {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        if self.fp == 0 || self.fp & (align_of::<usize>() - 1) != 0 {
+            return None;
+        }
+        let (ra, prev_fp) = unsafe {
+            (
+                *(self.fp.wrapping_add_signed(FRAME_RA_OFFSET) as *const usize),
+                *(self.fp.wrapping_add_signed(FRAME_FP_OFFSET) as *const usize),
+            )
+        };
+        // The stack grows downwards, so the frames of the callers are above.
+        self.fp = if prev_fp > self.fp && prev_fp - self.fp <= MAX_FRAME_SIZE {
+            prev_fp
+        } else {
+            0
+        };
+        (ra != 0).then_some(ra)
+    }
+}
+
+/// Notifies the validator of an acquired guard.
+#[inline(always)]
+pub(crate) fn acquired(kind: GuardKind) {
+    crate_interface::call_interface!(LockdepIf::guard_acquired(kind));
+}
+
+/// Notifies the validator of a guard to be released.
+#[inline(always)]
+pub(crate) fn releasing(kind: GuardKind) {
+    crate_interface::call_interface!(LockdepIf::guard_releasing(kind));
+}
+
+/// Notifies the validator of the spin lock of `map` taken at the caller,
+/// after acquiring its guard.
+#[inline(always)]
+#[track_caller]
+pub fn spin_acquired(map: &LockdepMap, trylock: bool) {
+    let site = Location::caller();
+    crate_interface::call_interface!(LockdepIf::spin_acquired(map, site, trylock));
+}
+
+/// Notifies the validator of the spin lock of `map` to be released, before
+/// releasing its guard.
+#[inline(always)]
+pub fn spin_releasing(map: &LockdepMap) {
+    crate_interface::call_interface!(LockdepIf::spin_releasing(map));
+}
@@ -0,0 +1,50 @@
+#!/bin/bash
+
+tmp_file=process_output.txt
+# The programs put into /sbin, which are launched by init one by one.
+programs="init_c/init hello_c/hello fileops_c/fileops cow_c/cow mapshared_c/mapshared mremap_c/mremap futex_c/futex process_c/process signal_c/signal syscall_c/syscall loader_c/loader"
+# Each of the programs prints its line on success.
+grep_contents=(
+    "init: all programs finished."
+    "Cow ok!"
+    "MapShared ok!"
+    "Mremap ok!"
+    "Futex ok!"
+    "Process ok!"
+    "Signal ok!"
+    "Syscall ok!"
+    "Loader ok!"
+)
+
+cd arceos/ || exit
+
+rm pflash.img -f
+rm disk.img -f
+
+make pflash_img
+make disk_img
+
+make payload
+for program in $programs; do
+    ./update_disk.sh payload/$program
+done
+
+make run A=tour/m_4_0/ BLK=y > $tmp_file 2>/dev/null
+
+failed=0
+for grep_content in "${grep_contents[@]}"; do
+    if [[ -z $(grep -a "$grep_content" $tmp_file) ]]; then
+        echo "missing output: $grep_content"
+        failed=1
+    fi
+done
+
+rm -rf $tmp_file
+
+if [[ $failed -ne 0 ]]; then
+    echo "process default"
+    exit 1
+else
+    echo "process pass"
+    exit 0
+fi
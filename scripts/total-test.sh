@@ -42,4 +42,10 @@ else
     echo "test-simple_hv failed" >> $file_name
 fi
 
+if ./scripts/test-process.sh ; then
+    ((score += 100))
+else
+    echo "test-process failed" >> $file_name
+fi
+
 echo "$score"